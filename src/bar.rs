@@ -1,20 +1,26 @@
-use crate::config::{BarConfig, BarPosition, MarginConfig, ModuleConfig};
+use crate::config::{BarConfig, BarPosition, MarginConfig, ModuleConfig, OverflowMode};
+use crate::keybinds;
 use crate::modules::{BarModuleFactory, ModuleInfo, ModuleLocation};
 use crate::popup::Popup;
 use crate::Ironbar;
-use color_eyre::Result;
+use color_eyre::{Help, Report, Result};
 use glib::Propagation;
 use gtk::gdk::Monitor;
 use gtk::prelude::*;
-use gtk::{Application, ApplicationWindow, IconTheme, Orientation, Window, WindowType};
-use gtk_layer_shell::LayerShell;
+use gtk::{
+    Adjustment, Application, ApplicationWindow, IconTheme, Label, Orientation, PolicyType,
+    ScrolledWindow, ShadowType, Viewport, Widget, Window, WindowType,
+};
+use gtk_layer_shell::{Edge, LayerShell};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::str::FromStr;
 use std::time::Duration;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone)]
 enum Inner {
-    New { config: Option<BarConfig> },
+    New { config: Option<Box<BarConfig>> },
     Loaded { popup: Rc<Popup> },
 }
 
@@ -23,6 +29,9 @@ pub struct Bar {
     name: String,
     monitor_name: String,
     position: BarPosition,
+    /// The exclusive zone to reserve,
+    /// or `None` to size it automatically from the window.
+    exclusive_zone: Option<i32>,
 
     ironbar: Rc<Ironbar>,
 
@@ -63,14 +72,15 @@ impl Bar {
             .orientation(orientation)
             .spacing(0)
             .hexpand(false)
-            .name("bar");
+            .name("bar")
+            .build();
 
-        let content = if orientation == Orientation::Horizontal {
-            content.height_request(config.height)
+        let min_length = config.min_length.unwrap_or(-1);
+        if orientation == Orientation::Horizontal {
+            content.set_size_request(min_length, config.height);
         } else {
-            content.width_request(config.height)
+            content.set_size_request(config.height, min_length);
         }
-        .build();
 
         content.style_context().add_class("container");
 
@@ -78,11 +88,25 @@ impl Bar {
         let center = create_container("center", orientation);
         let end = create_container("end", orientation);
 
-        content.add(&start);
-        content.set_center_widget(Some(&center));
-        content.pack_end(&end, false, false, 0);
+        let constrain = |container: &gtk::Box| {
+            constrain_container(container, orientation, config.max_length, config.overflow)
+        };
 
-        window.add(&content);
+        content.add(&constrain(&start));
+        content.set_center_widget(Some(&constrain(&center)));
+        content.pack_end(&constrain(&end), false, false, 0);
+
+        if let Some(opacity) = config.opacity {
+            Self::setup_opacity(&window, &content, opacity);
+        }
+
+        let exclusive_zone = if config.fixed_size {
+            window.add(&fix_thickness(&content, orientation, config.height));
+            Some(exclusive_zone(position, config.height, &config.margin))
+        } else {
+            window.add(&content);
+            None
+        };
 
         window.connect_destroy_event(|_, _| {
             info!("Shutting down");
@@ -94,6 +118,7 @@ impl Bar {
             name,
             monitor_name,
             position,
+            exclusive_zone,
             ironbar,
             window,
             content,
@@ -101,7 +126,7 @@ impl Bar {
             center,
             end,
             inner: Inner::New {
-                config: Some(config),
+                config: Some(Box::new(config)),
             },
         }
     }
@@ -120,38 +145,48 @@ impl Bar {
             self.name, self.monitor_name
         );
 
+        let visible = initially_visible(
+            config.start_hidden,
+            config.autohide.is_some(),
+            self.ironbar.is_bar_hidden(&self.name, &self.monitor_name),
+        );
+
         self.setup_layer_shell(
             &self.window,
-            true,
+            Some(visible),
             config.anchor_to_edges,
             config.margin,
             monitor,
         );
 
-        let start_hidden = config
-            .start_hidden
-            .unwrap_or_else(|| config.autohide.is_some());
-
         if let Some(autohide) = config.autohide {
             let hotspot_window = Window::new(WindowType::Toplevel);
 
             Self::setup_autohide(&self.window, &hotspot_window, autohide);
             self.setup_layer_shell(
                 &hotspot_window,
-                false,
+                None,
                 config.anchor_to_edges,
                 config.margin,
                 monitor,
             );
 
-            if start_hidden {
+            if !visible {
                 hotspot_window.show();
             }
         }
 
-        let load_result = self.load_modules(config, monitor)?;
+        let keybinds = config.keybinds.clone();
+        let load_result = self.load_modules(*config, monitor)?;
 
-        self.show(!start_hidden);
+        keybinds::install(
+            &self.window,
+            &keybinds,
+            load_result.popup.clone(),
+            self.ironbar.clone(),
+        );
+
+        self.show(visible);
 
         self.inner = Inner::Loaded {
             popup: load_result.popup,
@@ -159,11 +194,39 @@ impl Bar {
         Ok(self)
     }
 
+    /// Makes the bar translucent.
+    ///
+    /// The opacity is applied to the bar's content rather than the window itself,
+    /// as not all backends support window opacity.
+    /// The window instead uses an RGBA visual and does not paint its own background.
+    fn setup_opacity(window: &ApplicationWindow, content: &gtk::Box, opacity: f64) {
+        let opacity = clamp_opacity(opacity);
+        if opacity >= 1.0 {
+            return;
+        }
+
+        let visual = WidgetExt::screen(window)
+            .filter(gtk::gdk::Screen::is_composited)
+            .and_then(|screen| screen.rgba_visual());
+
+        let Some(visual) = visual else {
+            warn!("Compositor does not support transparency, bar will be opaque");
+            return;
+        };
+
+        window.set_visual(Some(&visual));
+        window.set_app_paintable(true);
+        content.set_opacity(opacity);
+    }
+
     /// Sets up GTK layer shell for a provided application window.
+    ///
+    /// `visible` is whether the bar starts shown,
+    /// or `None` for windows which never reserve space.
     fn setup_layer_shell(
         &self,
         win: &impl IsA<Window>,
-        exclusive_zone: bool,
+        visible: Option<bool>,
         anchor_to_edges: bool,
         margin: MarginConfig,
         monitor: &Monitor,
@@ -175,37 +238,27 @@ impl Bar {
         win.set_layer(gtk_layer_shell::Layer::Top);
         win.set_namespace(env!("CARGO_PKG_NAME"));
 
-        if exclusive_zone {
-            win.auto_exclusive_zone_enable();
+        if let Some(visible) = visible {
+            self.reserve_exclusive_zone(win, visible);
         }
 
-        win.set_layer_shell_margin(gtk_layer_shell::Edge::Top, margin.top);
-        win.set_layer_shell_margin(gtk_layer_shell::Edge::Bottom, margin.bottom);
-        win.set_layer_shell_margin(gtk_layer_shell::Edge::Left, margin.left);
-        win.set_layer_shell_margin(gtk_layer_shell::Edge::Right, margin.right);
+        for (edge, margin) in layer_shell_margins(margin) {
+            win.set_layer_shell_margin(edge, margin);
+        }
 
-        let bar_orientation = position.orientation();
+        for (edge, anchored) in layer_shell_anchors(position, anchor_to_edges) {
+            win.set_anchor(edge, anchored);
+        }
+    }
 
-        win.set_anchor(
-            gtk_layer_shell::Edge::Top,
-            position == BarPosition::Top
-                || (bar_orientation == Orientation::Vertical && anchor_to_edges),
-        );
-        win.set_anchor(
-            gtk_layer_shell::Edge::Bottom,
-            position == BarPosition::Bottom
-                || (bar_orientation == Orientation::Vertical && anchor_to_edges),
-        );
-        win.set_anchor(
-            gtk_layer_shell::Edge::Left,
-            position == BarPosition::Left
-                || (bar_orientation == Orientation::Horizontal && anchor_to_edges),
-        );
-        win.set_anchor(
-            gtk_layer_shell::Edge::Right,
-            position == BarPosition::Right
-                || (bar_orientation == Orientation::Horizontal && anchor_to_edges),
-        );
+    /// Reserves space for the bar at the screen edge,
+    /// so that other windows do not cover it.
+    fn reserve_exclusive_zone(&self, win: &impl IsA<Window>, visible: bool) {
+        match zone_reservation(visible, self.exclusive_zone) {
+            ZoneReservation::Fixed(zone) => win.set_exclusive_zone(zone),
+            ZoneReservation::Auto => win.auto_exclusive_zone_enable(),
+            ZoneReservation::None => win.set_exclusive_zone(0),
+        }
     }
 
     fn setup_autohide(window: &ApplicationWindow, hotspot_window: &Window, timeout: u64) {
@@ -255,32 +308,46 @@ impl Bar {
             ($location:expr) => {
                 ModuleInfo {
                     app,
+                    bar_name: &self.name,
                     bar_position: config.position,
                     monitor,
                     output_name: &self.monitor_name,
                     location: $location,
                     icon_theme: &icon_theme,
+                    hide_on_error: config.hide_on_error,
                 }
             };
         }
 
         // popup ignores module location so can bodge this for now
         let popup = Popup::new(&info!(ModuleLocation::Left), config.popup_gap);
+        popup.set_animations_enabled(!self.ironbar.config.borrow().disable_animations);
         let popup = Rc::new(popup);
 
+        let overflow = config
+            .max_length
+            .map(|max_length| (max_length, config.overflow));
+
         if let Some(modules) = config.start {
             let info = info!(ModuleLocation::Left);
-            add_modules(&self.start, modules, &info, &self.ironbar, &popup)?;
+            add_modules(&self.start, modules, &info, &self.ironbar, &popup, overflow);
         }
 
         if let Some(modules) = config.center {
             let info = info!(ModuleLocation::Center);
-            add_modules(&self.center, modules, &info, &self.ironbar, &popup)?;
+            add_modules(
+                &self.center,
+                modules,
+                &info,
+                &self.ironbar,
+                &popup,
+                overflow,
+            );
         }
 
         if let Some(modules) = config.end {
             let info = info!(ModuleLocation::Right);
-            add_modules(&self.end, modules, &info, &self.ironbar, &popup)?;
+            add_modules(&self.end, modules, &info, &self.ironbar, &popup, overflow);
         }
 
         let result = BarLoadResult { popup };
@@ -303,6 +370,25 @@ impl Bar {
         }
     }
 
+    /// Shows or hides the bar window.
+    pub fn set_visible(&self, visible: bool) {
+        if visible {
+            self.window.show();
+
+            // the layer surface is re-created when the window is mapped,
+            // so the exclusive zone must be reserved again.
+            self.reserve_exclusive_zone(&self.window, true);
+        } else {
+            // released first, so windows can use the space straight away
+            self.reserve_exclusive_zone(&self.window, false);
+            self.window.hide();
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.window.is_visible()
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -312,6 +398,15 @@ impl Bar {
         &self.monitor_name
     }
 
+    /// Closes the bar's window, along with its popup.
+    pub fn close(&self) {
+        if let Inner::Loaded { popup } = &self.inner {
+            popup.window.close();
+        }
+
+        self.window.close();
+    }
+
     pub fn popup(&self) -> Rc<Popup> {
         match &self.inner {
             Inner::New { .. } => {
@@ -322,6 +417,91 @@ impl Bar {
     }
 }
 
+/// A change to the visibility of every bar,
+/// of the bars on a single monitor, or of a single named bar.
+///
+/// These are parsed from the part of a `bar:` command after the prefix,
+/// in the format `action[:target]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisibilityCommand {
+    pub action: VisibilityAction,
+    /// The name of the bar or monitor to target.
+    /// If not set, all bars are targeted.
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityAction {
+    Show,
+    Hide,
+    Toggle,
+}
+
+impl VisibilityAction {
+    /// Gets the new visibility state,
+    /// from the current state.
+    pub const fn apply(self, visible: bool) -> bool {
+        match self {
+            Self::Show => true,
+            Self::Hide => false,
+            Self::Toggle => !visible,
+        }
+    }
+}
+
+impl VisibilityCommand {
+    /// Checks whether the command applies to a bar,
+    /// by either its own name or the name of its monitor.
+    pub fn targets(&self, bar_name: &str, monitor_name: &str) -> bool {
+        self.target
+            .as_deref()
+            .is_none_or(|target| target == bar_name || target == monitor_name)
+    }
+
+    /// Checks whether the command targets the bar by its own name,
+    /// rather than every bar on its monitor.
+    pub fn targets_by_name(&self, bar_name: &str) -> bool {
+        self.target.as_deref() == Some(bar_name)
+    }
+}
+
+impl FromStr for VisibilityCommand {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (action, target) = s
+            .split_once(':')
+            .map_or((s, None), |(action, target)| (action, Some(target)));
+
+        let action = match action {
+            "show" => VisibilityAction::Show,
+            "hide" => VisibilityAction::Hide,
+            "toggle" => VisibilityAction::Toggle,
+            _ => {
+                return Err(Report::msg(format!("Invalid bar action: '{action}'"))
+                    .suggestion("Use one of `show`, `hide` or `toggle`"))
+            }
+        };
+
+        let target = match target {
+            Some("") => return Err(Report::msg("Bar or monitor name cannot be empty")),
+            target => target.map(ToString::to_string),
+        };
+
+        Ok(Self { action, target })
+    }
+}
+
+/// Clamps the configured bar opacity into the valid `0.0`-`1.0` range.
+/// Invalid values are treated as fully opaque.
+fn clamp_opacity(opacity: f64) -> f64 {
+    if opacity.is_nan() {
+        1.0
+    } else {
+        opacity.clamp(0.0, 1.0)
+    }
+}
+
 /// Creates a `gtk::Box` container to place widgets inside.
 fn create_container(name: &str, orientation: Orientation) -> gtk::Box {
     let container = gtk::Box::builder()
@@ -339,6 +519,166 @@ struct BarLoadResult {
     popup: Rc<Popup>,
 }
 
+/// Places the bar content inside a scrolled window
+/// which is exactly `thickness` pixels across,
+/// clipping anything which does not fit.
+fn fix_thickness(content: &gtk::Box, orientation: Orientation, thickness: i32) -> Widget {
+    let scrolled = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+    scrolled.set_shadow_type(ShadowType::None);
+
+    if orientation == Orientation::Horizontal {
+        scrolled.set_policy(PolicyType::Never, PolicyType::External);
+        scrolled.set_min_content_height(thickness);
+        scrolled.set_max_content_height(thickness);
+    } else {
+        scrolled.set_policy(PolicyType::External, PolicyType::Never);
+        scrolled.set_min_content_width(thickness);
+        scrolled.set_max_content_width(thickness);
+    }
+
+    let viewport = Viewport::new(None::<&Adjustment>, None::<&Adjustment>);
+    viewport.set_shadow_type(ShadowType::None);
+    viewport.add(content);
+    scrolled.add(&viewport);
+    scrolled.show_all();
+
+    scrolled.upcast()
+}
+
+/// Gets the layer shell margin for each screen edge.
+const fn layer_shell_margins(margin: MarginConfig) -> [(Edge, i32); 4] {
+    [
+        (Edge::Top, margin.top),
+        (Edge::Bottom, margin.bottom),
+        (Edge::Left, margin.left),
+        (Edge::Right, margin.right),
+    ]
+}
+
+/// Gets whether the bar is anchored to each screen edge.
+///
+/// The bar is always anchored to the edge it is positioned on.
+/// With `anchor_to_edges`, it is also anchored to the two edges either side,
+/// stretching it along the full length of the screen.
+fn layer_shell_anchors(position: BarPosition, anchor_to_edges: bool) -> [(Edge, bool); 4] {
+    let orientation = position.orientation();
+
+    let stretch_vertical = orientation == Orientation::Vertical && anchor_to_edges;
+    let stretch_horizontal = orientation == Orientation::Horizontal && anchor_to_edges;
+
+    [
+        (Edge::Top, position == BarPosition::Top || stretch_vertical),
+        (
+            Edge::Bottom,
+            position == BarPosition::Bottom || stretch_vertical,
+        ),
+        (
+            Edge::Left,
+            position == BarPosition::Left || stretch_horizontal,
+        ),
+        (
+            Edge::Right,
+            position == BarPosition::Right || stretch_horizontal,
+        ),
+    ]
+}
+
+/// Gets whether a bar should be shown once it has loaded.
+///
+/// Bars with `autohide` start hidden unless `start_hidden` is explicitly `false`,
+/// and bars hidden by a command stay hidden when they are re-created.
+const fn initially_visible(
+    start_hidden: Option<bool>,
+    autohide: bool,
+    hidden_by_command: bool,
+) -> bool {
+    let start_hidden = match start_hidden {
+        Some(start_hidden) => start_hidden,
+        None => autohide,
+    };
+
+    !start_hidden && !hidden_by_command
+}
+
+/// Space reserved for a bar at its screen edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ZoneReservation {
+    None,
+    Fixed(i32),
+    /// Sized automatically from the window.
+    Auto,
+}
+
+/// Gets the space to reserve for a bar.
+/// Hidden bars reserve nothing, so that other windows can use the space.
+const fn zone_reservation(visible: bool, exclusive_zone: Option<i32>) -> ZoneReservation {
+    match (visible, exclusive_zone) {
+        (false, _) => ZoneReservation::None,
+        (true, Some(zone)) => ZoneReservation::Fixed(zone),
+        (true, None) => ZoneReservation::Auto,
+    }
+}
+
+/// Gets the exclusive zone for a bar with a fixed thickness.
+///
+/// The compositor measures the zone from the anchored edge's margin,
+/// so this covers the bar and the margin on its inner edge,
+/// matching how GTK Layer Shell sizes an automatic zone.
+const fn exclusive_zone(position: BarPosition, thickness: i32, margin: &MarginConfig) -> i32 {
+    let margin = match position {
+        BarPosition::Top => margin.bottom,
+        BarPosition::Bottom => margin.top,
+        BarPosition::Left => margin.right,
+        BarPosition::Right => margin.left,
+    };
+
+    thickness + margin
+}
+
+/// Places a section container inside a scrolled window
+/// limited to `max_length`, if the overflow mode needs one.
+///
+/// Otherwise, the container is returned as-is.
+fn constrain_container(
+    container: &gtk::Box,
+    orientation: Orientation,
+    max_length: Option<i32>,
+    overflow: OverflowMode,
+) -> Widget {
+    let policy = match (max_length, overflow) {
+        (Some(_), OverflowMode::Scroll) => PolicyType::Automatic,
+        // content is clipped without a scrollbar,
+        // so the ellipsized label can shrink
+        (Some(_), OverflowMode::Ellipsize) => PolicyType::External,
+        _ => return container.clone().upcast(),
+    };
+
+    let max_length = max_length.unwrap_or_default();
+
+    let scrolled = ScrolledWindow::new(None::<&Adjustment>, None::<&Adjustment>);
+    scrolled.set_shadow_type(ShadowType::None);
+
+    if orientation == Orientation::Horizontal {
+        scrolled.set_policy(policy, PolicyType::Never);
+        scrolled.set_max_content_width(max_length);
+        scrolled.set_propagate_natural_width(true);
+    } else {
+        scrolled.set_policy(PolicyType::Never, policy);
+        scrolled.set_max_content_height(max_length);
+        scrolled.set_propagate_natural_height(true);
+    }
+
+    let viewport = Viewport::new(None::<&Adjustment>, None::<&Adjustment>);
+    viewport.set_shadow_type(ShadowType::None);
+    viewport.add(container);
+    scrolled.add(&viewport);
+
+    // modules are added after this, so are not affected
+    scrolled.show_all();
+
+    scrolled.upcast()
+}
+
 /// Adds modules into a provided GTK box,
 /// which should be one of its left, center or right containers.
 fn add_modules(
@@ -347,14 +687,168 @@ fn add_modules(
     info: &ModuleInfo,
     ironbar: &Rc<Ironbar>,
     popup: &Rc<Popup>,
-) -> Result<()> {
+    overflow: Option<(i32, OverflowMode)>,
+) {
     let module_factory = BarModuleFactory::new(ironbar.clone(), popup.clone()).into();
 
+    let mut priorities = vec![];
+
     for config in modules {
-        config.create(&module_factory, content, info)?;
+        let priority = config.common().map_or(0, |common| common.priority);
+        config.create_or_fallback(&module_factory, content, info);
+
+        // a module adds a single widget, or one for an error,
+        // or none if the error is hidden
+        let count = content.children().len();
+        priorities.resize(count, priority);
+    }
+
+    match overflow {
+        Some((max_length, OverflowMode::Hide)) => {
+            let modules = content.children().into_iter().zip(priorities).collect();
+            install_overflow_hide(content, modules, max_length);
+        }
+        Some((_, OverflowMode::Ellipsize)) => {
+            if let Some(label) = last_label(content.upcast_ref()) {
+                label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Finds the last label inside a widget, searching depth-first.
+fn last_label(widget: &Widget) -> Option<Label> {
+    if let Some(label) = widget.downcast_ref::<Label>() {
+        return Some(label.clone());
+    }
+
+    widget
+        .downcast_ref::<gtk::Container>()?
+        .children()
+        .iter()
+        .rev()
+        .find_map(last_label)
+}
+
+/// Hides modules in the container, lowest priority first,
+/// whenever they do not all fit within `max_length`.
+///
+/// Modules hidden for other reasons, such as `show_if`, are left alone.
+fn install_overflow_hide(container: &gtk::Box, modules: Vec<(Widget, i32)>, max_length: i32) {
+    let overflow = Rc::new(RefCell::new(OverflowHide::new(
+        modules,
+        container.orientation(),
+        max_length,
+    )));
+    let pending = Rc::new(Cell::new(false));
+
+    // changing visibility while allocating causes another allocation,
+    // so the update is deferred until afterwards
+    container.connect_size_allocate(move |_, _| {
+        if pending.replace(true) {
+            return;
+        }
+
+        let pending = pending.clone();
+        let overflow = overflow.clone();
+
+        glib::idle_add_local_once(move || {
+            pending.set(false);
+            overflow.borrow_mut().update();
+        });
+    });
+}
+
+/// The state of a section whose modules are hidden on overflow.
+struct OverflowHide {
+    /// Each module with its priority.
+    modules: Vec<(Widget, i32)>,
+    orientation: Orientation,
+    max_length: i32,
+    /// Modules hidden by the overflow, rather than by themselves.
+    hidden: Vec<Widget>,
+    /// The length of each module when it was last shown.
+    /// Hidden widgets measure as zero,
+    /// so this is used for them instead.
+    lengths: Vec<i32>,
+}
+
+impl OverflowHide {
+    fn new(modules: Vec<(Widget, i32)>, orientation: Orientation, max_length: i32) -> Self {
+        let lengths = vec![0; modules.len()];
+
+        Self {
+            modules,
+            orientation,
+            max_length,
+            hidden: vec![],
+            lengths,
+        }
+    }
+
+    /// Measures the modules, then hides or shows each so that they fit.
+    fn update(&mut self) {
+        let mut candidates = vec![];
+        let mut lengths = vec![];
+
+        for (index, (widget, priority)) in self.modules.iter().enumerate() {
+            if widget.is_visible() {
+                self.lengths[index] = if self.orientation == Orientation::Horizontal {
+                    widget.preferred_width().1
+                } else {
+                    widget.preferred_height().1
+                };
+            } else if !self.hidden.contains(widget) {
+                continue;
+            }
+
+            candidates.push(widget);
+            lengths.push((*priority, self.lengths[index]));
+        }
+
+        let to_hide = modules_to_hide(&lengths, self.max_length);
+
+        let mut now_hidden = vec![];
+        for (index, widget) in candidates.into_iter().enumerate() {
+            if to_hide.contains(&index) {
+                widget.hide();
+                now_hidden.push(widget.clone());
+            } else if self.hidden.contains(widget) {
+                widget.show();
+            }
+        }
+
+        self.hidden = now_hidden;
     }
+}
+
+/// Picks which modules to hide so that the rest fit within `max_length`.
+///
+/// Each module is a `(priority, length)` pair, in the order they appear on the bar.
+/// Modules with the lowest priority are hidden first,
+/// and where priorities are equal, the module furthest along the bar is hidden first.
+///
+/// Returns the indices of the modules to hide.
+fn modules_to_hide(modules: &[(i32, i32)], max_length: i32) -> Vec<usize> {
+    let mut total = modules.iter().map(|&(_, length)| length).sum::<i32>();
+
+    let mut order = (0..modules.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| (modules[index].0, std::cmp::Reverse(index)));
+
+    let mut hidden = vec![];
+
+    for index in order {
+        if total <= max_length {
+            break;
+        }
 
-    Ok(())
+        total -= modules[index].1;
+        hidden.push(index);
+    }
+
+    hidden.sort_unstable();
+    hidden
 }
 
 pub fn create_bar(
@@ -367,3 +861,261 @@ pub fn create_bar(
     let bar = Bar::new(app, monitor_name, config, ironbar);
     bar.init(monitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_visibility_command() {
+        assert_eq!(
+            "toggle".parse::<VisibilityCommand>().unwrap(),
+            VisibilityCommand {
+                action: VisibilityAction::Toggle,
+                target: None
+            }
+        );
+        assert_eq!(
+            "hide:DP-1".parse::<VisibilityCommand>().unwrap(),
+            VisibilityCommand {
+                action: VisibilityAction::Hide,
+                target: Some("DP-1".to_string())
+            }
+        );
+        assert_eq!(
+            "show".parse::<VisibilityCommand>().unwrap().action,
+            VisibilityAction::Show
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_visibility_command() {
+        assert!("".parse::<VisibilityCommand>().is_err());
+        assert!("flip".parse::<VisibilityCommand>().is_err());
+        assert!("hide:".parse::<VisibilityCommand>().is_err());
+    }
+
+    #[test]
+    fn test_visibility_targets() {
+        let all = "hide".parse::<VisibilityCommand>().unwrap();
+        assert!(all.targets("main", "DP-1"));
+        assert!(all.targets("main", "HDMI-A-1"));
+        assert!(!all.targets_by_name("main"));
+
+        let single = "hide:DP-1".parse::<VisibilityCommand>().unwrap();
+        assert!(single.targets("main", "DP-1"));
+        assert!(!single.targets("main", "HDMI-A-1"));
+        assert!(!single.targets_by_name("main"));
+    }
+
+    #[test]
+    fn test_visibility_targets_bar_name() {
+        let overlay = "toggle:overlay".parse::<VisibilityCommand>().unwrap();
+        assert!(overlay.targets("overlay", "DP-1"));
+        assert!(overlay.targets("overlay", "HDMI-A-1"));
+        assert!(!overlay.targets("main", "DP-1"));
+        assert!(overlay.targets_by_name("overlay"));
+    }
+
+    #[test]
+    fn test_initially_visible() {
+        assert!(initially_visible(None, false, false));
+        assert!(!initially_visible(Some(true), false, false));
+
+        // autohide starts hidden unless told otherwise
+        assert!(!initially_visible(None, true, false));
+        assert!(initially_visible(Some(false), true, false));
+
+        // hidden by a command before being re-created
+        assert!(!initially_visible(None, false, true));
+        assert!(!initially_visible(Some(false), false, true));
+    }
+
+    #[test]
+    fn test_zone_only_reserved_when_visible() {
+        assert_eq!(zone_reservation(true, Some(32)), ZoneReservation::Fixed(32));
+        assert_eq!(zone_reservation(true, None), ZoneReservation::Auto);
+        assert_eq!(zone_reservation(false, Some(32)), ZoneReservation::None);
+        assert_eq!(zone_reservation(false, None), ZoneReservation::None);
+    }
+
+    #[test]
+    fn test_visibility_action() {
+        assert!(VisibilityAction::Show.apply(false));
+        assert!(!VisibilityAction::Hide.apply(true));
+        assert!(VisibilityAction::Toggle.apply(false));
+        assert!(!VisibilityAction::Toggle.apply(true));
+    }
+
+    #[test]
+    fn test_exclusive_zone() {
+        let margin = MarginConfig {
+            top: 1,
+            bottom: 2,
+            left: 3,
+            right: 4,
+        };
+
+        assert_eq!(exclusive_zone(BarPosition::Top, 30, &margin), 32);
+        assert_eq!(exclusive_zone(BarPosition::Bottom, 30, &margin), 31);
+        assert_eq!(exclusive_zone(BarPosition::Left, 40, &margin), 44);
+        assert_eq!(exclusive_zone(BarPosition::Right, 40, &margin), 43);
+        assert_eq!(
+            exclusive_zone(BarPosition::Top, 30, &MarginConfig::default()),
+            30
+        );
+    }
+
+    /// Gets the edges which are set in a list of layer shell anchors.
+    fn anchored(anchors: [(Edge, bool); 4]) -> Vec<Edge> {
+        anchors
+            .into_iter()
+            .filter(|(_, anchored)| *anchored)
+            .map(|(edge, _)| edge)
+            .collect()
+    }
+
+    #[test]
+    fn test_layer_shell_anchors() {
+        use BarPosition::{Bottom, Left, Right, Top};
+
+        assert_eq!(anchored(layer_shell_anchors(Top, false)), [Edge::Top]);
+        assert_eq!(anchored(layer_shell_anchors(Right, false)), [Edge::Right]);
+
+        assert_eq!(
+            anchored(layer_shell_anchors(Top, true)),
+            [Edge::Top, Edge::Left, Edge::Right]
+        );
+        assert_eq!(
+            anchored(layer_shell_anchors(Bottom, true)),
+            [Edge::Bottom, Edge::Left, Edge::Right]
+        );
+        assert_eq!(
+            anchored(layer_shell_anchors(Left, true)),
+            [Edge::Top, Edge::Bottom, Edge::Left]
+        );
+        assert_eq!(
+            anchored(layer_shell_anchors(Right, true)),
+            [Edge::Top, Edge::Bottom, Edge::Right]
+        );
+    }
+
+    #[test]
+    fn test_top_and_bottom_bars_reserve_separate_edges() {
+        // both bars share a margin, as when inherited from the same config
+        let margin = MarginConfig {
+            top: 4,
+            bottom: 6,
+            left: 0,
+            right: 0,
+        };
+
+        // each zone covers the margin on the bar's inner edge
+        assert_eq!(exclusive_zone(BarPosition::Top, 30, &margin), 36);
+        assert_eq!(exclusive_zone(BarPosition::Bottom, 24, &margin), 28);
+
+        // the zone is reserved against the edge which is not stretched along,
+        // so the bars never reserve the same edge
+        let top = anchored(layer_shell_anchors(BarPosition::Top, true));
+        let bottom = anchored(layer_shell_anchors(BarPosition::Bottom, true));
+        assert!(top.contains(&Edge::Top) && !top.contains(&Edge::Bottom));
+        assert!(bottom.contains(&Edge::Bottom) && !bottom.contains(&Edge::Top));
+    }
+
+    #[test]
+    fn test_layer_shell_margins() {
+        let margin = MarginConfig {
+            top: 1,
+            bottom: 2,
+            left: 3,
+            right: 4,
+        };
+
+        assert_eq!(
+            layer_shell_margins(margin),
+            [
+                (Edge::Top, 1),
+                (Edge::Bottom, 2),
+                (Edge::Left, 3),
+                (Edge::Right, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modules_to_hide_fits() {
+        assert!(modules_to_hide(&[(0, 100), (0, 200)], 300).is_empty());
+        assert!(modules_to_hide(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_modules_to_hide_by_priority() {
+        let modules = [(5, 100), (0, 100), (10, 100), (1, 100)];
+
+        assert_eq!(modules_to_hide(&modules, 300), [1]);
+        assert_eq!(modules_to_hide(&modules, 250), [1, 3]);
+        assert_eq!(modules_to_hide(&modules, 100), [0, 1, 3]);
+        assert_eq!(modules_to_hide(&modules, 0), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_modules_to_hide_ties() {
+        // equal priorities hide from the end of the bar
+        let modules = [(0, 100), (0, 100), (0, 100)];
+        assert_eq!(modules_to_hide(&modules, 150), [1, 2]);
+    }
+
+    #[test]
+    fn test_modules_to_hide_stops_once_fits() {
+        // hiding the large low-priority module is enough
+        let modules = [(1, 50), (0, 400), (1, 50)];
+        assert_eq!(modules_to_hide(&modules, 200), [1]);
+    }
+
+    #[test]
+    fn test_overflow_hidden_modules_stay_hidden() {
+        crate::gtk_helpers::with_gtk(|| {
+            let modules = [1, 0, 2]
+                .into_iter()
+                .map(|priority| {
+                    let widget = gtk::Box::new(Orientation::Horizontal, 0);
+                    widget.set_size_request(100, 10);
+                    widget.show();
+                    (widget.upcast::<Widget>(), priority)
+                })
+                .collect::<Vec<_>>();
+
+            let mut overflow = OverflowHide::new(modules.clone(), Orientation::Horizontal, 250);
+            let visible = || {
+                modules
+                    .iter()
+                    .map(|(widget, _)| widget.is_visible())
+                    .collect::<Vec<_>>()
+            };
+
+            overflow.update();
+            assert_eq!(visible(), [true, false, true]);
+
+            // the hidden module is measured by its last shown length,
+            // so it is not shown again only to be hidden on the next update
+            overflow.update();
+            assert_eq!(visible(), [true, false, true]);
+
+            // it is shown once there is room again
+            modules[0].0.hide();
+            overflow.update();
+            assert_eq!(visible(), [false, true, true]);
+        });
+    }
+
+    #[test]
+    fn test_clamp_opacity() {
+        assert!((clamp_opacity(0.5) - 0.5).abs() < f64::EPSILON);
+        assert!((clamp_opacity(0.0) - 0.0).abs() < f64::EPSILON);
+        assert!((clamp_opacity(1.0) - 1.0).abs() < f64::EPSILON);
+
+        assert!((clamp_opacity(-0.2) - 0.0).abs() < f64::EPSILON);
+        assert!((clamp_opacity(1.7) - 1.0).abs() < f64::EPSILON);
+        assert!((clamp_opacity(f64::NAN) - 1.0).abs() < f64::EPSILON);
+    }
+}
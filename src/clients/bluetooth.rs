@@ -0,0 +1,69 @@
+use crate::register_fallible_client;
+use color_eyre::{Report, Result};
+use tracing::debug;
+use zbus::fdo::ObjectManagerProxy;
+
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+
+#[zbus::dbus_proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+trait Adapter {
+    /// Powered property
+    #[dbus_proxy(property)]
+    fn powered(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_powered(&self, value: bool) -> zbus::Result<()>;
+}
+
+/// Client for the first BlueZ bluetooth adapter.
+#[derive(Debug)]
+pub struct Client {
+    proxy: AdapterProxy<'static>,
+}
+
+impl Client {
+    pub async fn new() -> Result<Self> {
+        let dbus = Box::pin(zbus::Connection::system()).await?;
+
+        let objects = ObjectManagerProxy::builder(&dbus)
+            .destination("org.bluez")?
+            .path("/")?
+            .build()
+            .await?
+            .get_managed_objects()
+            .await?;
+
+        let path = objects
+            .into_iter()
+            .filter(|(_, interfaces)| {
+                interfaces
+                    .keys()
+                    .any(|interface| interface.as_str() == ADAPTER_INTERFACE)
+            })
+            .map(|(path, _)| path)
+            // sorted so that the same adapter is used each time
+            .min_by(|a, b| a.as_str().cmp(b.as_str()))
+            .ok_or_else(|| Report::msg("No bluetooth adapter found"))?;
+
+        debug!("Using bluetooth adapter '{}'", path.as_str());
+
+        let proxy = AdapterProxy::builder(&dbus).path(path)?.build().await?;
+
+        Ok(Self { proxy })
+    }
+
+    pub async fn powered(&self) -> Result<bool> {
+        Ok(self.proxy.powered().await?)
+    }
+
+    pub async fn set_powered(&self, powered: bool) -> Result<()> {
+        debug!("Setting bluetooth powered: {powered}");
+        Ok(self.proxy.set_powered(powered).await?)
+    }
+
+    /// Gets a stream which yields each time the adapter is powered on or off.
+    pub async fn receive_powered_changed(&self) -> zbus::PropertyStream<'static, bool> {
+        self.proxy.receive_powered_changed().await
+    }
+}
+
+register_fallible_client!(Client, bluetooth);
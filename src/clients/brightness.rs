@@ -0,0 +1,112 @@
+use crate::register_fallible_client;
+use color_eyre::{Report, Result};
+use std::fs;
+use std::path::Path;
+use tracing::debug;
+
+const BACKLIGHT_DIR: &str = "/sys/class/backlight";
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    /// SetBrightness method
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+}
+
+/// Client for the screen backlight.
+///
+/// The brightness is read from sysfs,
+/// and set through logind so that no extra permissions are needed.
+#[derive(Debug)]
+pub struct Client {
+    proxy: SessionProxy<'static>,
+    device: String,
+    max_brightness: u32,
+}
+
+impl Client {
+    pub async fn new() -> Result<Self> {
+        let device = fs::read_dir(BACKLIGHT_DIR)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .min()
+            .ok_or_else(|| Report::msg("No backlight device found"))?;
+
+        let max_brightness = read_value(&device, "max_brightness")?;
+        if max_brightness == 0 {
+            return Err(Report::msg(format!(
+                "Backlight device '{device}' has no brightness range"
+            )));
+        }
+
+        let dbus = Box::pin(zbus::Connection::system()).await?;
+        let proxy = SessionProxy::new(&dbus).await?;
+
+        debug!("Using backlight device '{device}'");
+
+        Ok(Self {
+            proxy,
+            device,
+            max_brightness,
+        })
+    }
+
+    /// Gets the current brightness as a percentage.
+    pub fn brightness(&self) -> Result<f64> {
+        let brightness = read_value(&self.device, "brightness")?;
+        Ok(to_percent(brightness, self.max_brightness))
+    }
+
+    /// Sets the brightness to a percentage.
+    pub async fn set_brightness(&self, percent: f64) -> Result<()> {
+        let brightness = from_percent(percent, self.max_brightness);
+        debug!("Setting brightness of '{}' to {brightness}", self.device);
+
+        Ok(self
+            .proxy
+            .set_brightness("backlight", &self.device, brightness)
+            .await?)
+    }
+}
+
+fn read_value(device: &str, file: &str) -> Result<u32> {
+    let path = Path::new(BACKLIGHT_DIR).join(device).join(file);
+    Ok(fs::read_to_string(path)?.trim().parse()?)
+}
+
+fn to_percent(brightness: u32, max_brightness: u32) -> f64 {
+    f64::from(brightness) / f64::from(max_brightness) * 100.0
+}
+
+fn from_percent(percent: f64, max_brightness: u32) -> u32 {
+    let brightness = percent.clamp(0.0, 100.0) / 100.0 * f64::from(max_brightness);
+
+    // clamped above, so always in range
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    {
+        brightness.round() as u32
+    }
+}
+
+register_fallible_client!(Client, brightness);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_round_trip() {
+        assert!((to_percent(60, 120) - 50.0).abs() < f64::EPSILON);
+        assert_eq!(from_percent(50.0, 120), 60);
+        assert_eq!(from_percent(33.3, 255), 85);
+    }
+
+    #[test]
+    fn test_percent_clamped() {
+        assert_eq!(from_percent(150.0, 255), 255);
+        assert_eq!(from_percent(-5.0, 255), 0);
+    }
+}
@@ -1,16 +1,23 @@
-use super::wayland::{self, ClipboardItem};
-use crate::{arc_mut, lock, register_client, spawn, try_send};
+use super::wayland::{self, ClipboardItem, ClipboardValue};
+use crate::{arc_mut, lock, register_client, spawn, try_send, Ironbar};
+use color_eyre::Result;
 use indexmap::map::Iter;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
-use tracing::{debug, trace};
+use tracing::{debug, error, trace};
 
 #[derive(Debug)]
 pub enum ClipboardEvent {
     Add(ClipboardItem),
     Remove(usize),
     Activate(usize),
+    /// An item was pinned or unpinned.
+    Pin(usize, bool),
 }
 
 type EventSender = mpsc::Sender<ClipboardEvent>;
@@ -23,6 +30,7 @@ pub struct Client {
 
     senders: Arc<Mutex<Vec<(EventSender, usize)>>>,
     cache: Arc<Mutex<ClipboardCache>>,
+    pins: Option<PinStore>,
 }
 
 impl Client {
@@ -31,7 +39,16 @@ impl Client {
 
         let senders = arc_mut!(Vec::<(EventSender, usize)>::new());
 
-        let cache = arc_mut!(ClipboardCache::new());
+        let pins = PinStore::load();
+        let mut cache = ClipboardCache::new();
+
+        for item in pins.iter().flat_map(PinStore::items) {
+            let id = item.id;
+            cache.insert(item, 0);
+            cache.pin(id);
+        }
+
+        let cache = arc_mut!(cache);
 
         {
             let senders = senders.clone();
@@ -55,9 +72,10 @@ impl Client {
                 while let Ok(item) = rx.recv().await {
                     debug!("Received clipboard item (ID: {})", item.id);
 
+                    // pinned items do not count towards each bar's maximum
                     let (existing_id, cache_size) = {
                         let cache = lock!(cache);
-                        (cache.contains(&item), cache.len())
+                        (cache.contains(&item), cache.unpinned_len())
                     };
 
                     existing_id.map_or_else(
@@ -70,11 +88,10 @@ impl Client {
                             let senders = lock!(senders);
                             let iter = senders.iter();
                             for (tx, sender_cache_size) in iter {
-                                if cache_size == *sender_cache_size {
-                                    let removed_id = lock!(cache)
-                                        .remove_ref_first()
-                                        .expect("Clipboard cache unexpectedly empty");
-                                    try_send!(tx, ClipboardEvent::Remove(removed_id));
+                                if cache_size >= *sender_cache_size {
+                                    if let Some(removed_id) = lock!(cache).remove_ref_first() {
+                                        try_send!(tx, ClipboardEvent::Remove(removed_id));
+                                    }
                                 }
                                 try_send!(tx, ClipboardEvent::Add(item.clone()));
                             }
@@ -95,20 +112,28 @@ impl Client {
             wayland: wl,
             senders,
             cache,
+            pins,
         }
     }
 
     pub fn subscribe(&self, cache_size: usize) -> mpsc::Receiver<ClipboardEvent> {
-        let (tx, rx) = mpsc::channel(16);
-
-        {
+        let (tx, rx) = {
             let cache = lock!(self.cache);
 
+            // pinned items are not limited, so make sure they all fit
+            let (tx, rx) = mpsc::channel(16.max(cache.cache.len() + cache.pinned.len()));
+
             let iter = cache.iter();
-            for (_, (item, _)) in iter {
+            for (id, (item, _)) in iter {
                 try_send!(tx, ClipboardEvent::Add(item.clone()));
+
+                if cache.is_pinned(*id) {
+                    try_send!(tx, ClipboardEvent::Pin(*id, true));
+                }
             }
-        }
+
+            (tx, rx)
+        };
 
         lock!(self.senders).push((tx, cache_size));
 
@@ -135,7 +160,16 @@ impl Client {
     }
 
     pub fn remove(&self, id: usize) {
-        lock!(self.cache).remove(id);
+        let was_pinned = {
+            let mut cache = lock!(self.cache);
+            let was_pinned = cache.is_pinned(id);
+            cache.remove(id);
+            was_pinned
+        };
+
+        if was_pinned {
+            self.save_pins();
+        }
 
         let senders = lock!(self.senders);
         let iter = senders.iter();
@@ -143,6 +177,127 @@ impl Client {
             try_send!(tx, ClipboardEvent::Remove(id));
         }
     }
+
+    /// Pins or unpins the item with `id`,
+    /// so that it is kept regardless of the maximum number of items.
+    ///
+    /// Pinned text items are saved, and restored on the next start.
+    pub fn set_pinned(&self, id: usize, pinned: bool) {
+        debug!("Setting pinned state of item with id {id} to {pinned}");
+
+        let ref_count = lock!(self.senders).len();
+
+        let changed = {
+            let mut cache = lock!(self.cache);
+            if pinned {
+                cache.pin(id)
+            } else {
+                cache.unpin(id, ref_count)
+            }
+        };
+
+        if !changed {
+            return;
+        }
+
+        self.save_pins();
+
+        let senders = lock!(self.senders);
+        for (tx, _) in senders.iter() {
+            try_send!(tx, ClipboardEvent::Pin(id, pinned));
+        }
+    }
+
+    fn save_pins(&self) {
+        let Some(pins) = &self.pins else {
+            return;
+        };
+
+        let items = lock!(self.cache).pinned_items();
+        if let Err(err) = pins.save(&items) {
+            error!(
+                "{:?}",
+                err.wrap_err("Failed to save pinned clipboard items")
+            );
+        }
+    }
+}
+
+/// A pinned item, as saved to the state file.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct SavedPin {
+    mime_type: String,
+    value: String,
+}
+
+/// Pinned clipboard items,
+/// persisted to a state file so that they survive restarts.
+///
+/// Only text items are saved.
+#[derive(Debug)]
+struct PinStore {
+    path: PathBuf,
+    pins: Vec<SavedPin>,
+}
+
+impl PinStore {
+    /// Loads the pins from the state file in the user's data directory.
+    fn load() -> Option<Self> {
+        let path = dirs::data_dir()?
+            .join("ironbar")
+            .join("clipboard_pins.json");
+        Some(Self::load_from(path))
+    }
+
+    /// Loads the pins from `path`.
+    /// If the file is missing or invalid, there are no pins.
+    fn load_from(path: PathBuf) -> Self {
+        let pins = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+                error!(
+                    "Invalid pinned clipboard items in '{}': {err}",
+                    path.display()
+                );
+                vec![]
+            }),
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => {
+                error!("Failed to read '{}': {err}", path.display());
+                vec![]
+            }
+        };
+
+        Self { path, pins }
+    }
+
+    /// Creates a clipboard item for each saved pin.
+    fn items(&self) -> impl Iterator<Item = ClipboardItem> + '_ {
+        self.pins.iter().map(|pin| ClipboardItem {
+            id: Ironbar::unique_id(),
+            value: Arc::new(ClipboardValue::Text(pin.value.clone())),
+            mime_type: pin.mime_type.as_str().into(),
+        })
+    }
+
+    fn save(&self, items: &[ClipboardItem]) -> Result<()> {
+        let pins = items
+            .iter()
+            .filter_map(|item| match item.value.as_ref() {
+                ClipboardValue::Text(value) => Some(SavedPin {
+                    mime_type: item.mime_type.to_string(),
+                    value: value.clone(),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(&self.path, serde_json::to_string(&pins)?)?;
+        Ok(())
+    }
 }
 
 /// Shared clipboard item cache.
@@ -150,9 +305,13 @@ impl Client {
 /// Items are stored with a number of references,
 /// allowing different consumers to 'remove' cached items
 /// at different times.
+///
+/// Pinned items are never evicted,
+/// and are only removed when explicitly requested.
 #[derive(Debug)]
 struct ClipboardCache {
     cache: IndexMap<usize, (ClipboardItem, usize)>,
+    pinned: IndexSet<usize>,
 }
 
 impl ClipboardCache {
@@ -160,6 +319,7 @@ impl ClipboardCache {
     fn new() -> Self {
         Self {
             cache: IndexMap::new(),
+            pinned: IndexSet::new(),
         }
     }
 
@@ -176,8 +336,9 @@ impl ClipboardCache {
     }
 
     /// Removes the entry with key `id`.
-    /// This ignores references.
+    /// This ignores references and pins.
     fn remove(&mut self, id: usize) -> Option<ClipboardItem> {
+        self.pinned.shift_remove(&id);
         self.cache.shift_remove(&id).map(|(item, _)| item)
     }
 
@@ -187,7 +348,7 @@ impl ClipboardCache {
     /// is removed from the cache.
     fn remove_ref(&mut self, id: usize) {
         if let Some(entry) = self.cache.get_mut(&id) {
-            entry.1 -= 1;
+            entry.1 = entry.1.saturating_sub(1);
 
             if entry.1 == 0 {
                 self.cache.shift_remove(&id);
@@ -195,18 +356,55 @@ impl ClipboardCache {
         }
     }
 
-    /// Removes a reference to the first entry.
+    /// Removes a reference to the first unpinned entry.
     ///
     /// If the reference count reaches zero, the entry
     /// is removed from the cache.
     fn remove_ref_first(&mut self) -> Option<usize> {
-        if let Some((id, _)) = self.cache.first() {
-            let id = *id;
-            self.remove_ref(id);
-            Some(id)
-        } else {
-            None
+        let id = *self.cache.keys().find(|id| !self.pinned.contains(*id))?;
+        self.remove_ref(id);
+        Some(id)
+    }
+
+    /// Pins the entry with key `id`, so that it is never evicted.
+    ///
+    /// Returns `false` if the entry does not exist or is already pinned.
+    fn pin(&mut self, id: usize) -> bool {
+        self.cache.contains_key(&id) && self.pinned.insert(id)
+    }
+
+    /// Unpins the entry with key `id`,
+    /// giving it `ref_count` references so it can be evicted again.
+    ///
+    /// Returns `false` if the entry was not pinned.
+    fn unpin(&mut self, id: usize, ref_count: usize) -> bool {
+        if !self.pinned.shift_remove(&id) {
+            return false;
+        }
+
+        if let Some(entry) = self.cache.get_mut(&id) {
+            entry.1 = ref_count;
         }
+
+        true
+    }
+
+    fn is_pinned(&self, id: usize) -> bool {
+        self.pinned.contains(&id)
+    }
+
+    /// Gets the pinned items, in the order they were added.
+    fn pinned_items(&self) -> Vec<ClipboardItem> {
+        self.cache
+            .iter()
+            .filter(|(id, _)| self.pinned.contains(*id))
+            .map(|(_, (item, _))| item.clone())
+            .collect()
+    }
+
+    /// Gets the number of items which can be evicted.
+    fn unpinned_len(&self) -> usize {
+        self.cache.len() - self.pinned.len()
     }
 
     /// Checks if an item with matching mime type and value
@@ -221,14 +419,134 @@ impl ClipboardCache {
         })
     }
 
-    /// Gets the current number of items in the cache.
-    fn len(&self) -> usize {
-        self.cache.len()
-    }
-
     fn iter(&self) -> Iter<'_, usize, (ClipboardItem, usize)> {
         self.cache.iter()
     }
 }
 
 register_client!(Client, clipboard);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn item(value: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: Ironbar::unique_id(),
+            value: Arc::new(ClipboardValue::Text(value.to_string())),
+            mime_type: "text/plain".into(),
+        }
+    }
+
+    fn cache(values: &[&str]) -> (ClipboardCache, Vec<usize>) {
+        let mut cache = ClipboardCache::new();
+        let ids = values
+            .iter()
+            .map(|value| {
+                let item = item(value);
+                let id = item.id;
+                cache.insert(item, 1);
+                id
+            })
+            .collect();
+
+        (cache, ids)
+    }
+
+    #[test]
+    fn test_pin_unpin() {
+        let (mut cache, ids) = cache(&["a", "b"]);
+
+        assert!(!cache.is_pinned(ids[0]));
+        assert_eq!(cache.unpinned_len(), 2);
+
+        assert!(cache.pin(ids[0]));
+        assert!(cache.is_pinned(ids[0]));
+        assert_eq!(cache.unpinned_len(), 1);
+
+        // already pinned
+        assert!(!cache.pin(ids[0]));
+
+        assert!(cache.unpin(ids[0], 1));
+        assert!(!cache.is_pinned(ids[0]));
+        assert_eq!(cache.unpinned_len(), 2);
+
+        // already unpinned
+        assert!(!cache.unpin(ids[0], 1));
+
+        // missing items cannot be pinned
+        assert!(!cache.pin(usize::MAX));
+    }
+
+    #[test]
+    fn test_pinned_items() {
+        let (mut cache, ids) = cache(&["a", "b", "c"]);
+
+        cache.pin(ids[2]);
+        cache.pin(ids[0]);
+
+        let pinned = cache.pinned_items();
+        assert_eq!(
+            pinned.iter().map(|item| item.id).collect::<Vec<_>>(),
+            [ids[0], ids[2]]
+        );
+
+        cache.remove(ids[0]);
+        assert!(!cache.is_pinned(ids[0]));
+        assert_eq!(cache.pinned_items().len(), 1);
+    }
+
+    #[test]
+    fn test_eviction_skips_pinned() {
+        let (mut cache, ids) = cache(&["a", "b", "c"]);
+        cache.pin(ids[0]);
+
+        assert_eq!(cache.remove_ref_first(), Some(ids[1]));
+        assert_eq!(cache.remove_ref_first(), Some(ids[2]));
+        assert!(cache.get(ids[1]).is_none());
+
+        // only pinned items remain
+        assert_eq!(cache.remove_ref_first(), None);
+        assert!(cache.get(ids[0]).is_some());
+    }
+
+    #[test]
+    fn test_unpinned_can_be_evicted() {
+        let (mut cache, ids) = cache(&["a", "b"]);
+        cache.pin(ids[0]);
+        cache.unpin(ids[0], 1);
+
+        assert_eq!(cache.remove_ref_first(), Some(ids[0]));
+        assert!(cache.get(ids[0]).is_none());
+    }
+
+    #[test]
+    fn test_pin_store_round_trip() {
+        let path = env::temp_dir()
+            .join(format!("ironbar-test-{}", process::id()))
+            .join("clipboard_pins.json");
+
+        let store = PinStore::load_from(path.clone());
+        assert_eq!(store.items().count(), 0);
+
+        let image = ClipboardItem {
+            id: Ironbar::unique_id(),
+            value: Arc::new(ClipboardValue::Image(glib::Bytes::from_static(&[1, 2, 3]))),
+            mime_type: "image/png".into(),
+        };
+        store.save(&[item("pinned"), image]).unwrap();
+
+        // images are not saved
+        let loaded = PinStore::load_from(path.clone());
+        let items = loaded.items().collect::<Vec<_>>();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].value.as_ref(),
+            &ClipboardValue::Text("pinned".to_string())
+        );
+        assert_eq!(items[0].mime_type.as_ref(), "text/plain");
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
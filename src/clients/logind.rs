@@ -0,0 +1,61 @@
+use crate::register_fallible_client;
+use color_eyre::Result;
+use tracing::debug;
+use zbus::zvariant::OwnedFd;
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    /// Inhibit method
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    /// BlockInhibited property
+    #[dbus_proxy(property)]
+    fn block_inhibited(&self) -> zbus::Result<String>;
+}
+
+#[derive(Debug)]
+pub struct Client {
+    proxy: LoginManagerProxy<'static>,
+}
+
+/// A held logind inhibitor lock.
+///
+/// The lock is released when this is dropped,
+/// which closes its file descriptor.
+#[derive(Debug)]
+pub struct Inhibitor {
+    _fd: OwnedFd,
+}
+
+impl Client {
+    pub async fn new() -> Result<Self> {
+        let dbus = Box::pin(zbus::Connection::system()).await?;
+        let proxy = LoginManagerProxy::new(&dbus).await?;
+
+        // proxies are created lazily,
+        // so make a call to check logind is actually running
+        let blocked = proxy.block_inhibited().await?;
+        debug!("Connected to logind, currently inhibited: '{blocked}'");
+
+        Ok(Self { proxy })
+    }
+
+    /// Takes a blocking inhibitor lock for the colon-separated list of operations in `what`,
+    /// such as `sleep:idle`.
+    pub async fn inhibit(&self, what: &str, why: &str) -> Result<Inhibitor> {
+        debug!("Inhibiting '{what}'");
+
+        let fd = self
+            .proxy
+            .inhibit(what, env!("CARGO_PKG_NAME"), why, "block")
+            .await?;
+
+        Ok(Inhibitor { _fd: fd })
+    }
+}
+
+register_fallible_client!(Client, logind);
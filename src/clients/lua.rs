@@ -1,6 +1,8 @@
+use crate::send;
 use mlua::Lua;
 use std::ops::Deref;
 use std::path::Path;
+use tokio::sync::broadcast;
 use tracing::{debug, error};
 
 /// Wrapper around Lua instance
@@ -8,6 +10,8 @@ use tracing::{debug, error};
 #[derive(Debug)]
 pub struct LuaEngine {
     lua: Lua,
+    reload_tx: broadcast::Sender<()>,
+    _reload_rx: broadcast::Receiver<()>,
 }
 
 impl LuaEngine {
@@ -28,7 +32,23 @@ impl LuaEngine {
             error!("{err:?}");
         }
 
-        Self { lua }
+        let (reload_tx, reload_rx) = broadcast::channel(1);
+
+        Self {
+            lua,
+            reload_tx,
+            _reload_rx: reload_rx,
+        }
+    }
+
+    /// Asks all modules using the engine to reload their scripts.
+    pub fn reload(&self) {
+        debug!("reloading lua scripts");
+        send!(self.reload_tx, ());
+    }
+
+    pub fn subscribe_reload(&self) -> broadcast::Receiver<()> {
+        self.reload_tx.subscribe()
     }
 }
 
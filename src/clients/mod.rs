@@ -1,24 +1,35 @@
+use crate::modules::tasks::TaskScope;
 use crate::{await_sync, Ironbar};
 use color_eyre::Result;
 use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
 
+#[cfg(feature = "quicksettings")]
+pub mod bluetooth;
+#[cfg(feature = "quicksettings")]
+pub mod brightness;
 #[cfg(feature = "clipboard")]
 pub mod clipboard;
 #[cfg(feature = "workspaces")]
 pub mod compositor;
+#[cfg(feature = "caffeine")]
+pub mod logind;
 #[cfg(feature = "cairo")]
 pub mod lua;
 #[cfg(feature = "music")]
 pub mod music;
+#[cfg(feature = "quicksettings")]
+pub mod network;
+#[cfg(feature = "upower")]
+pub mod power_profiles;
 #[cfg(feature = "notifications")]
 pub mod swaync;
 #[cfg(feature = "tray")]
 pub mod tray;
 #[cfg(feature = "upower")]
 pub mod upower;
-#[cfg(feature = "volume")]
+#[cfg(any(feature = "volume", feature = "sink"))]
 pub mod volume;
 pub mod wayland;
 
@@ -29,19 +40,29 @@ pub struct Clients {
     wayland: Option<Arc<wayland::Client>>,
     #[cfg(feature = "workspaces")]
     workspaces: Option<Arc<dyn compositor::WorkspaceClient>>,
+    #[cfg(feature = "quicksettings")]
+    bluetooth: Option<Arc<bluetooth::Client>>,
+    #[cfg(feature = "quicksettings")]
+    brightness: Option<Arc<brightness::Client>>,
     #[cfg(feature = "clipboard")]
     clipboard: Option<Arc<clipboard::Client>>,
+    #[cfg(feature = "caffeine")]
+    logind: Option<Arc<logind::Client>>,
     #[cfg(feature = "cairo")]
     lua: Option<Rc<lua::LuaEngine>>,
     #[cfg(feature = "music")]
     music: std::collections::HashMap<music::ClientType, Arc<dyn music::MusicClient>>,
+    #[cfg(feature = "quicksettings")]
+    network: Option<Arc<network::Client>>,
     #[cfg(feature = "notifications")]
     notifications: Option<Arc<swaync::Client>>,
+    #[cfg(feature = "upower")]
+    power_profiles: Option<Arc<power_profiles::Client>>,
     #[cfg(feature = "tray")]
     tray: Option<Arc<tray::Client>>,
     #[cfg(feature = "upower")]
     upower: Option<Arc<zbus::fdo::PropertiesProxy<'static>>>,
-    #[cfg(feature = "volume")]
+    #[cfg(any(feature = "volume", feature = "sink"))]
     volume: Option<Arc<volume::Client>>,
 }
 
@@ -54,16 +75,50 @@ impl Clients {
 
     pub fn wayland(&mut self) -> Arc<wayland::Client> {
         self.wayland
-            .get_or_insert_with(|| Arc::new(wayland::Client::new()))
+            .get_or_insert_with(|| TaskScope::detached(|| Arc::new(wayland::Client::new())))
             .clone()
     }
 
+    #[cfg(feature = "quicksettings")]
+    pub fn bluetooth(&mut self) -> ClientResult<bluetooth::Client> {
+        let client = match &self.bluetooth {
+            Some(client) => client.clone(),
+            None => {
+                let client =
+                    TaskScope::detached(|| await_sync(async { bluetooth::Client::new().await }))?;
+                let client = Arc::new(client);
+                self.bluetooth.replace(client.clone());
+                client
+            }
+        };
+
+        Ok(client)
+    }
+
+    #[cfg(feature = "quicksettings")]
+    pub fn brightness(&mut self) -> ClientResult<brightness::Client> {
+        let client = match &self.brightness {
+            Some(client) => client.clone(),
+            None => {
+                let client =
+                    TaskScope::detached(|| await_sync(async { brightness::Client::new().await }))?;
+                let client = Arc::new(client);
+                self.brightness.replace(client.clone());
+                client
+            }
+        };
+
+        Ok(client)
+    }
+
     #[cfg(feature = "clipboard")]
     pub fn clipboard(&mut self) -> Arc<clipboard::Client> {
         let wayland = self.wayland();
 
         self.clipboard
-            .get_or_insert_with(|| Arc::new(clipboard::Client::new(wayland)))
+            .get_or_insert_with(|| {
+                TaskScope::detached(|| Arc::new(clipboard::Client::new(wayland)))
+            })
             .clone()
     }
 
@@ -72,7 +127,7 @@ impl Clients {
         let client = match &self.workspaces {
             Some(workspaces) => workspaces.clone(),
             None => {
-                let client = compositor::Compositor::create_workspace_client()?;
+                let client = TaskScope::detached(compositor::Compositor::create_workspace_client)?;
                 self.workspaces.replace(client.clone());
                 client
             }
@@ -81,6 +136,22 @@ impl Clients {
         Ok(client)
     }
 
+    #[cfg(feature = "caffeine")]
+    pub fn logind(&mut self) -> ClientResult<logind::Client> {
+        let client = match &self.logind {
+            Some(client) => client.clone(),
+            None => {
+                let client =
+                    TaskScope::detached(|| await_sync(async { logind::Client::new().await }))?;
+                let client = Arc::new(client);
+                self.logind.replace(client.clone());
+                client
+            }
+        };
+
+        Ok(client)
+    }
+
     #[cfg(feature = "cairo")]
     pub fn lua(&mut self, config_dir: &Path) -> Rc<lua::LuaEngine> {
         self.lua
@@ -88,20 +159,43 @@ impl Clients {
             .clone()
     }
 
+    /// Gets the Lua engine if a module has already started it.
+    #[cfg(feature = "cairo")]
+    pub fn lua_if_loaded(&self) -> Option<Rc<lua::LuaEngine>> {
+        self.lua.clone()
+    }
+
     #[cfg(feature = "music")]
     pub fn music(&mut self, client_type: music::ClientType) -> Arc<dyn music::MusicClient> {
         self.music
             .entry(client_type.clone())
-            .or_insert_with(|| music::create_client(client_type))
+            .or_insert_with(|| TaskScope::detached(|| music::create_client(client_type)))
             .clone()
     }
 
+    #[cfg(feature = "quicksettings")]
+    pub fn network(&mut self) -> ClientResult<network::Client> {
+        let client = match &self.network {
+            Some(client) => client.clone(),
+            None => {
+                let client =
+                    TaskScope::detached(|| await_sync(async { network::Client::new().await }))?;
+                let client = Arc::new(client);
+                self.network.replace(client.clone());
+                client
+            }
+        };
+
+        Ok(client)
+    }
+
     #[cfg(feature = "notifications")]
     pub fn notifications(&mut self) -> ClientResult<swaync::Client> {
         let client = match &self.notifications {
             Some(client) => client.clone(),
             None => {
-                let client = await_sync(async { swaync::Client::new().await })?;
+                let client =
+                    TaskScope::detached(|| await_sync(async { swaync::Client::new().await }))?;
                 let client = Arc::new(client);
                 self.notifications.replace(client.clone());
                 client
@@ -118,7 +212,9 @@ impl Clients {
             None => {
                 let service_name = format!("{}-{}", env!("CARGO_CRATE_NAME"), Ironbar::unique_id());
 
-                let client = await_sync(async { tray::Client::new(&service_name).await })?;
+                let client = TaskScope::detached(|| {
+                    await_sync(async { tray::Client::new(&service_name).await })
+                })?;
                 let client = Arc::new(client);
                 self.tray.replace(client.clone());
                 client
@@ -128,19 +224,36 @@ impl Clients {
         Ok(client)
     }
 
+    #[cfg(feature = "upower")]
+    pub fn power_profiles(&mut self) -> ClientResult<power_profiles::Client> {
+        let client = match &self.power_profiles {
+            Some(client) => client.clone(),
+            None => {
+                let client = TaskScope::detached(|| {
+                    await_sync(async { power_profiles::Client::new().await })
+                })?;
+                let client = Arc::new(client);
+                self.power_profiles.replace(client.clone());
+                client
+            }
+        };
+
+        Ok(client)
+    }
+
     #[cfg(feature = "upower")]
     pub fn upower(&mut self) -> Arc<zbus::fdo::PropertiesProxy<'static>> {
         self.upower
             .get_or_insert_with(|| {
-                crate::await_sync(async { upower::create_display_proxy().await })
+                TaskScope::detached(|| await_sync(async { upower::create_display_proxy().await }))
             })
             .clone()
     }
 
-    #[cfg(feature = "volume")]
+    #[cfg(any(feature = "volume", feature = "sink"))]
     pub fn volume(&mut self) -> Arc<volume::Client> {
         self.volume
-            .get_or_insert_with(volume::create_client)
+            .get_or_insert_with(|| TaskScope::detached(volume::create_client))
             .clone()
     }
 }
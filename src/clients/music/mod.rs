@@ -32,6 +32,8 @@ pub struct Track {
     pub genre: Option<String>,
     pub track: Option<u64>,
     pub cover_path: Option<String>,
+    /// Name of the player the track is playing in, if known.
+    pub player: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -48,6 +50,29 @@ pub struct Status {
     pub volume_percent: Option<u8>,
     pub playlist_position: u32,
     pub playlist_length: u32,
+    pub capabilities: Capabilities,
+}
+
+/// Controls supported by the player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub play: bool,
+    pub pause: bool,
+    pub next: bool,
+    pub previous: bool,
+    pub volume: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            play: true,
+            pause: true,
+            next: true,
+            previous: true,
+            volume: true,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -70,13 +95,19 @@ pub trait MusicClient: Debug + Send + Sync {
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum ClientType {
-    Mpd { host: String, music_dir: PathBuf },
-    Mpris,
+    Mpd {
+        host: String,
+        music_dir: PathBuf,
+    },
+    Mpris {
+        /// Player names to prefer, highest priority first.
+        priority: Vec<String>,
+    },
 }
 
 pub fn create_client(client_type: ClientType) -> Arc<dyn MusicClient> {
     match client_type {
         ClientType::Mpd { host, music_dir } => Arc::new(mpd::Client::new(host, music_dir)),
-        ClientType::Mpris => Arc::new(mpris::Client::new()),
+        ClientType::Mpris { priority } => Arc::new(mpris::Client::new(priority)),
     }
 }
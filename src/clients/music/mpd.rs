@@ -1,5 +1,6 @@
 use super::{
-    MusicClient, PlayerState, PlayerUpdate, ProgressTick, Status, Track, TICK_INTERVAL_MS,
+    Capabilities, MusicClient, PlayerState, PlayerUpdate, ProgressTick, Status, Track,
+    TICK_INTERVAL_MS,
 };
 use crate::{await_sync, send, spawn, Ironbar};
 use color_eyre::Report;
@@ -178,6 +179,7 @@ fn convert_song(song: &Song, music_dir: &Path) -> Track {
         disc: Some(disc),
         track: Some(track),
         cover_path,
+        player: None,
     }
 }
 
@@ -196,6 +198,7 @@ impl From<mpd_client::responses::Status> for Status {
             volume_percent: Some(status.volume),
             playlist_position: status.current_song.map_or(0, |(pos, _)| pos.0 as u32),
             playlist_length: status.playlist_length as u32,
+            capabilities: Capabilities::default(),
         }
     }
 }
@@ -1,4 +1,6 @@
-use super::{MusicClient, PlayerState, PlayerUpdate, Status, Track, TICK_INTERVAL_MS};
+use super::{
+    Capabilities, MusicClient, PlayerState, PlayerUpdate, Status, Track, TICK_INTERVAL_MS,
+};
 use crate::clients::music::ProgressTick;
 use crate::{arc_mut, lock, send, spawn_blocking};
 use color_eyre::Result;
@@ -24,10 +26,11 @@ const NO_SERVICE: &str = "org.freedesktop.DBus.Error.ServiceUnknown";
 const NO_METHOD: &str = "org.freedesktop.DBus.Error.UnknownMethod";
 
 impl Client {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(priority: Vec<String>) -> Self {
         let (tx, rx) = broadcast::channel(32);
 
         let current_player = arc_mut!(None);
+        let priority = Arc::new(priority);
 
         {
             let players_list = arc_mut!(HashSet::new());
@@ -57,18 +60,39 @@ impl Client {
                     {
                         let mut current_player_lock = lock!(current_player);
 
-                        let mut players_list_val = lock!(players_list);
-                        for player in players {
-                            let identity = player.identity();
-
-                            if current_player_lock.is_none() {
+                        let states = players
+                            .iter()
+                            .map(|player| {
+                                let state = player
+                                    .get_playback_status()
+                                    .map(PlayerState::from)
+                                    .unwrap_or_default();
+                                (player.identity(), state)
+                            })
+                            .collect::<Vec<_>>();
+
+                        let selected =
+                            select_player(&states, &priority, current_player_lock.as_deref());
+
+                        if let Some(identity) = selected {
+                            if current_player_lock.as_deref() != Some(identity) {
                                 debug!("Setting active player to '{identity}'");
                                 current_player_lock.replace(identity.to_string());
 
-                                if let Err(err) = Self::send_update(&player, &tx) {
-                                    error!("{err:?}");
+                                if let Some(player) =
+                                    players.iter().find(|player| player.identity() == identity)
+                                {
+                                    if let Err(err) = Self::send_update(player, &tx) {
+                                        error!("{err:?}");
+                                    }
                                 }
                             }
+                        }
+
+                        let mut players_list_val = lock!(players_list);
+                        for player in &players {
+                            let identity = player.identity();
+
                             if !players_list_val.contains(identity) {
                                 debug!("Adding MPRIS player '{identity}'");
                                 players_list_val.insert(identity.to_string());
@@ -77,6 +101,7 @@ impl Client {
                                     identity.to_string(),
                                     players_list.clone(),
                                     current_player.clone(),
+                                    priority.clone(),
                                     tx.clone(),
                                 );
                             }
@@ -113,6 +138,7 @@ impl Client {
         player_id: String,
         players: Arc<Mutex<HashSet<String>>>,
         current_player: Arc<Mutex<Option<String>>>,
+        priority: Arc<Vec<String>>,
         tx: broadcast::Sender<PlayerUpdate>,
     ) {
         spawn_blocking(move || {
@@ -156,7 +182,14 @@ impl Client {
                         }
                         Ok(_) => {
                             let mut current_player_lock = lock!(current_player);
-                            if matches!(event, Ok(Event::Playing)) {
+                            // lower priority players are switched to by the poll loop
+                            // once the current player stops
+                            if matches!(event, Ok(Event::Playing))
+                                && current_player_lock.as_deref().is_none_or(|current| {
+                                    priority_rank(identity, &priority)
+                                        <= priority_rank(current, &priority)
+                                })
+                            {
                                 current_player_lock.replace(identity.to_string());
                             }
                             if let Some(current_identity) = current_player_lock.as_ref() {
@@ -203,9 +236,17 @@ impl Client {
             playlist_length: track_list.map(|list| list.len() as u32).unwrap_or(u32::MAX),
             state: PlayerState::from(playback_status),
             volume_percent,
+            capabilities: Capabilities {
+                play: player.can_play().unwrap_or(true),
+                pause: player.can_pause().unwrap_or(true),
+                next: player.can_go_next().unwrap_or(true),
+                previous: player.can_go_previous().unwrap_or(true),
+                volume: volume_percent.is_some() && player.can_control().unwrap_or(true),
+            },
         };
 
-        let track = Track::from(metadata);
+        let mut track = Track::from(metadata);
+        track.player = Some(player.identity().to_string());
 
         let player_update = PlayerUpdate::Update(Box::new(Some(track)), status);
         send!(tx, player_update);
@@ -314,6 +355,7 @@ impl MusicClient for Client {
                 playlist_length: 0,
                 state: PlayerState::Stopped,
                 volume_percent: None,
+                capabilities: Capabilities::default(),
             };
             send!(self.tx, PlayerUpdate::Update(Box::new(None), status));
         }
@@ -351,6 +393,7 @@ impl From<Metadata> for Track {
                 .and_then(|arr| arr.first().map(|val| (*val).to_string())),
             track: value.track_number().map(|track| track as u64),
             cover_path: value.art_url().map(ToString::to_string),
+            player: None,
         }
     }
 }
@@ -372,3 +415,136 @@ fn replace_empty_none(string: String) -> Option<String> {
         Some(string)
     }
 }
+
+/// Gets the position of the player in the priority list,
+/// with players not in the list ranked after all those that are.
+fn priority_rank(identity: &str, priority: &[String]) -> usize {
+    priority
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(identity))
+        .unwrap_or(priority.len())
+}
+
+/// Picks the player to show from those running,
+/// given as `(identity, state)` pairs in the order they were found.
+///
+/// The highest priority playing player is picked.
+/// If nothing is playing, the current player is kept while it is running.
+/// On a tie, the current player is kept if it is one of those tied,
+/// otherwise the first found is picked.
+fn select_player<'a>(
+    players: &[(&'a str, PlayerState)],
+    priority: &[String],
+    current: Option<&str>,
+) -> Option<&'a str> {
+    let best = |candidates: Vec<&'a str>| {
+        let top = candidates
+            .iter()
+            .map(|identity| priority_rank(identity, priority))
+            .min()?;
+
+        let tied = candidates
+            .into_iter()
+            .filter(|identity| priority_rank(identity, priority) == top)
+            .collect::<Vec<_>>();
+
+        tied.iter()
+            .find(|&&identity| Some(identity) == current)
+            .or_else(|| tied.first())
+            .copied()
+    };
+
+    let playing = players
+        .iter()
+        .filter(|(_, state)| matches!(state, PlayerState::Playing))
+        .map(|(identity, _)| *identity)
+        .collect::<Vec<_>>();
+
+    if !playing.is_empty() {
+        return best(playing);
+    }
+
+    if let Some(&(identity, _)) = players
+        .iter()
+        .find(|(identity, _)| Some(*identity) == current)
+    {
+        return Some(identity);
+    }
+
+    best(players.iter().map(|(identity, _)| *identity).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn priority(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_highest_priority_playing() {
+        let players = [
+            ("Mozilla Firefox", PlayerState::Playing),
+            ("Spotify", PlayerState::Playing),
+        ];
+        let priority = priority(&["spotify", "Mozilla Firefox"]);
+
+        assert_eq!(
+            select_player(&players, &priority, Some("Mozilla Firefox")),
+            Some("Spotify")
+        );
+    }
+
+    #[test]
+    fn test_playing_preferred_over_priority() {
+        let players = [
+            ("Spotify", PlayerState::Paused),
+            ("Mozilla Firefox", PlayerState::Playing),
+        ];
+        let priority = priority(&["Spotify"]);
+
+        // switches away once the preferred player stops
+        assert_eq!(
+            select_player(&players, &priority, Some("Spotify")),
+            Some("Mozilla Firefox")
+        );
+    }
+
+    #[test]
+    fn test_nothing_playing_keeps_current() {
+        let players = [
+            ("Spotify", PlayerState::Paused),
+            ("Mozilla Firefox", PlayerState::Stopped),
+        ];
+        let priority = priority(&["Spotify"]);
+
+        assert_eq!(
+            select_player(&players, &priority, Some("Mozilla Firefox")),
+            Some("Mozilla Firefox")
+        );
+        assert_eq!(select_player(&players, &priority, None), Some("Spotify"));
+        assert_eq!(
+            select_player(&players, &priority, Some("vlc")),
+            Some("Spotify")
+        );
+    }
+
+    #[test]
+    fn test_unlisted_players() {
+        let players = [("vlc", PlayerState::Playing), ("mpv", PlayerState::Playing)];
+
+        // without a priority, the current player is kept
+        assert_eq!(select_player(&players, &[], Some("mpv")), Some("mpv"));
+        assert_eq!(select_player(&players, &[], None), Some("vlc"));
+
+        // unlisted players rank below listed ones
+        let priority = priority(&["mpv"]);
+        assert_eq!(select_player(&players, &priority, Some("vlc")), Some("mpv"));
+    }
+
+    #[test]
+    fn test_no_players() {
+        assert_eq!(select_player(&[], &priority(&["Spotify"]), None), None);
+    }
+}
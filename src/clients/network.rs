@@ -0,0 +1,52 @@
+use crate::register_fallible_client;
+use color_eyre::Result;
+use tracing::debug;
+
+#[zbus::dbus_proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    /// WirelessEnabled property
+    #[dbus_proxy(property)]
+    fn wireless_enabled(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_wireless_enabled(&self, value: bool) -> zbus::Result<()>;
+}
+
+/// Client for NetworkManager.
+#[derive(Debug)]
+pub struct Client {
+    proxy: NetworkManagerProxy<'static>,
+}
+
+impl Client {
+    pub async fn new() -> Result<Self> {
+        let dbus = Box::pin(zbus::Connection::system()).await?;
+        let proxy = NetworkManagerProxy::new(&dbus).await?;
+
+        // proxies are created lazily,
+        // so make a call to check the daemon is actually running
+        let enabled = proxy.wireless_enabled().await?;
+        debug!("Connected to NetworkManager, Wi-Fi enabled: {enabled}");
+
+        Ok(Self { proxy })
+    }
+
+    pub async fn wireless_enabled(&self) -> Result<bool> {
+        Ok(self.proxy.wireless_enabled().await?)
+    }
+
+    pub async fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        debug!("Setting Wi-Fi enabled: {enabled}");
+        Ok(self.proxy.set_wireless_enabled(enabled).await?)
+    }
+
+    /// Gets a stream which yields each time Wi-Fi is enabled or disabled.
+    pub async fn receive_wireless_enabled_changed(&self) -> zbus::PropertyStream<'static, bool> {
+        self.proxy.receive_wireless_enabled_changed().await
+    }
+}
+
+register_fallible_client!(Client, network);
@@ -0,0 +1,70 @@
+use crate::register_fallible_client;
+use color_eyre::Result;
+use std::collections::HashMap;
+use tracing::debug;
+use zbus::zvariant::OwnedValue;
+
+#[zbus::dbus_proxy(
+    interface = "net.hadess.PowerProfiles",
+    default_service = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles"
+)]
+trait PowerProfiles {
+    /// ActiveProfile property
+    #[dbus_proxy(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn set_active_profile(&self, value: &str) -> zbus::Result<()>;
+
+    /// Profiles property
+    #[dbus_proxy(property)]
+    fn profiles(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+}
+
+/// Client for `power-profiles-daemon`.
+#[derive(Debug)]
+pub struct Client {
+    proxy: PowerProfilesProxy<'static>,
+}
+
+impl Client {
+    pub async fn new() -> Result<Self> {
+        let dbus = Box::pin(zbus::Connection::system()).await?;
+        let proxy = PowerProfilesProxy::new(&dbus).await?;
+
+        // proxies are created lazily,
+        // so make a call to check the daemon is actually running
+        let active = proxy.active_profile().await?;
+        debug!("Connected to power-profiles-daemon, active profile: '{active}'");
+
+        Ok(Self { proxy })
+    }
+
+    /// Gets the names of the available profiles,
+    /// such as `power-saver` or `performance`.
+    pub async fn profiles(&self) -> Result<Vec<String>> {
+        let profiles = self.proxy.profiles().await?;
+
+        Ok(profiles
+            .iter()
+            .filter_map(|profile| profile.get("Profile")?.downcast_ref::<str>())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    pub async fn active_profile(&self) -> Result<String> {
+        Ok(self.proxy.active_profile().await?)
+    }
+
+    pub async fn set_active_profile(&self, profile: &str) -> Result<()> {
+        debug!("Setting power profile to '{profile}'");
+        Ok(self.proxy.set_active_profile(profile).await?)
+    }
+
+    /// Gets a stream which yields each time the active profile changes.
+    pub async fn receive_active_profile_changed(&self) -> zbus::PropertyStream<'static, String> {
+        self.proxy.receive_active_profile_changed().await
+    }
+}
+
+register_fallible_client!(Client, power_profiles);
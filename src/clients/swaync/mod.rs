@@ -75,6 +75,13 @@ impl Client {
         }
     }
 
+    pub async fn set_dnd(&self, dnd: bool) {
+        debug!("Setting do not disturb to {dnd}");
+        if let Err(err) = self.proxy.set_dnd(dnd).await {
+            error!("{err:?}");
+        }
+    }
+
     pub async fn toggle_visibility(&self) {
         debug!("Toggling visibility");
         if let Err(err) = self.proxy.toggle_visibility().await {
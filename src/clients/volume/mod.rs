@@ -261,7 +261,12 @@ fn set_default_sink(
 ) {
     let default_sink_name = info.default_sink_name.as_ref().map(ToString::to_string);
 
-    if default_sink_name != *lock!(default_sink) {
+    let changed = default_sink_name != *lock!(default_sink);
+
+    // stored before sending so subscribers see the new default
+    *lock!(default_sink) = default_sink_name.clone();
+
+    if changed {
         if let Some(ref default_sink_name) = default_sink_name {
             if let Some(sink) = lock!(sinks)
                 .iter_mut()
@@ -275,8 +280,6 @@ fn set_default_sink(
             }
         }
     }
-
-    *lock!(default_sink) = default_sink_name;
 }
 
 /// Converts a Pulse `ChannelVolumes` struct into a single percentage value,
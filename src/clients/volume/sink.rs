@@ -17,6 +17,11 @@ pub struct Sink {
     pub volume: f64,
     pub muted: bool,
     pub active: bool,
+    /// Name of the active port, such as `analog-output-headphones`.
+    pub port: Option<String>,
+    /// Type of device, such as `headphone` or `speaker`,
+    /// if the server knows it.
+    pub form_factor: Option<String>,
 }
 
 impl From<&SinkInfo<'_>> for Sink {
@@ -36,6 +41,12 @@ impl From<&SinkInfo<'_>> for Sink {
             muted: value.mute,
             volume: volume_to_percent(value.volume),
             active: value.state == SinkState::Running,
+            port: value
+                .active_port
+                .as_ref()
+                .and_then(|port| port.name.as_ref())
+                .map(ToString::to_string),
+            form_factor: value.proplist.get_str("device.form_factor"),
         }
     }
 }
@@ -45,6 +56,11 @@ impl Client {
         self.data.sinks.clone()
     }
 
+    /// Gets the name of the server's default sink.
+    pub fn default_sink_name(&self) -> Option<String> {
+        lock!(self.data.default_sink_name).clone()
+    }
+
     pub fn set_default_sink(&self, name: &str) {
         if let ConnectionState::Connected { context, .. } = &*lock!(self.connection) {
             lock!(context).set_default_sink(name, |_| {});
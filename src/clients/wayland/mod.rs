@@ -25,7 +25,7 @@ use wayland_client::{Connection, QueueHandle};
 pub use wl_output::{OutputEvent, OutputEventType};
 
 cfg_if! {
-    if #[cfg(any(feature = "focused", feature = "launcher"))] {
+    if #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))] {
         mod wlr_foreign_toplevel;
         use crate::{delegate_foreign_toplevel_handle, delegate_foreign_toplevel_manager};
         use wlr_foreign_toplevel::manager::ToplevelManagerState;
@@ -58,7 +58,7 @@ cfg_if! {
 #[derive(Debug)]
 pub enum Event {
     Output(OutputEvent),
-    #[cfg(any(feature = "focused", feature = "launcher"))]
+    #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
     Toplevel(ToplevelEvent),
     #[cfg(feature = "clipboard")]
     Clipboard(ClipboardItem),
@@ -71,9 +71,9 @@ pub enum Request {
     #[cfg(feature = "ipc")]
     OutputInfoAll,
 
-    #[cfg(any(feature = "focused", feature = "launcher"))]
+    #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
     ToplevelInfoAll,
-    #[cfg(feature = "launcher")]
+    #[cfg(any(feature = "launcher", feature = "taskbar"))]
     ToplevelFocus(usize),
 
     #[cfg(feature = "clipboard")]
@@ -90,7 +90,7 @@ pub enum Response {
     #[cfg(feature = "ipc")]
     OutputInfoAll(Vec<smithay_client_toolkit::output::OutputInfo>),
 
-    #[cfg(any(feature = "focused", feature = "launcher"))]
+    #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
     ToplevelInfoAll(Vec<ToplevelInfo>),
 
     #[cfg(feature = "clipboard")]
@@ -113,7 +113,7 @@ pub struct Client {
     rx: Arc<Mutex<std::sync::mpsc::Receiver<Response>>>,
 
     output_channel: BroadcastChannel<OutputEvent>,
-    #[cfg(any(feature = "focused", feature = "launcher"))]
+    #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
     toplevel_channel: BroadcastChannel<ToplevelEvent>,
     #[cfg(feature = "clipboard")]
     clipboard_channel: BroadcastChannel<ClipboardItem>,
@@ -127,7 +127,7 @@ impl Client {
         let (response_tx, response_rx) = std::sync::mpsc::channel();
 
         let output_channel = broadcast::channel(32);
-        #[cfg(any(feature = "focused", feature = "launcher"))]
+        #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
         let toplevel_channel = broadcast::channel(32);
 
         #[cfg(feature = "clipboard")]
@@ -140,7 +140,7 @@ impl Client {
         // listen to events
         {
             let output_tx = output_channel.0.clone();
-            #[cfg(any(feature = "focused", feature = "launcher"))]
+            #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
             let toplevel_tx = toplevel_channel.0.clone();
 
             #[cfg(feature = "clipboard")]
@@ -150,7 +150,7 @@ impl Client {
                 while let Some(event) = event_rx.recv().await {
                     match event {
                         Event::Output(event) => send!(output_tx, event),
-                        #[cfg(any(feature = "focused", feature = "launcher"))]
+                        #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
                         Event::Toplevel(event) => send!(toplevel_tx, event),
                         #[cfg(feature = "clipboard")]
                         Event::Clipboard(item) => send!(clipboard_tx, item),
@@ -164,7 +164,7 @@ impl Client {
             rx: arc_mut!(response_rx),
 
             output_channel: output_channel.into(),
-            #[cfg(any(feature = "focused", feature = "launcher"))]
+            #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
             toplevel_channel: toplevel_channel.into(),
             #[cfg(feature = "clipboard")]
             clipboard_channel: clipboard_channel.into(),
@@ -198,7 +198,7 @@ pub struct Environment {
     response_tx: std::sync::mpsc::Sender<Response>,
 
     // local state
-    #[cfg(any(feature = "focused", feature = "launcher"))]
+    #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
     handles: Vec<ToplevelHandle>,
 
     // -- clipboard --
@@ -223,7 +223,7 @@ delegate_output!(Environment);
 delegate_seat!(Environment);
 
 cfg_if! {
-    if #[cfg(any(feature = "focused", feature = "launcher"))] {
+    if #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))] {
         delegate_foreign_toplevel_manager!(Environment);
         delegate_foreign_toplevel_handle!(Environment);
     }
@@ -263,7 +263,7 @@ impl Environment {
 
         let output_state = OutputState::new(&globals, &qh);
         let seat_state = SeatState::new(&globals, &qh);
-        #[cfg(any(feature = "focused", feature = "launcher"))]
+        #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
         ToplevelManagerState::bind(&globals, &qh)
             .expect("to bind to wlr_foreign_toplevel_manager global");
 
@@ -281,7 +281,7 @@ impl Environment {
             loop_handle: loop_handle.clone(),
             event_tx,
             response_tx,
-            #[cfg(any(feature = "focused", feature = "launcher"))]
+            #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
             handles: vec![],
 
             #[cfg(feature = "clipboard")]
@@ -324,7 +324,7 @@ impl Environment {
                 let infos = env.output_info_all();
                 send!(env.response_tx, Response::OutputInfoAll(infos));
             }
-            #[cfg(any(feature = "focused", feature = "launcher"))]
+            #[cfg(any(feature = "focused", feature = "launcher", feature = "taskbar"))]
             Msg(Request::ToplevelInfoAll) => {
                 let infos = env
                     .handles
@@ -333,7 +333,7 @@ impl Environment {
                     .collect();
                 send!(env.response_tx, Response::ToplevelInfoAll(infos));
             }
-            #[cfg(feature = "launcher")]
+            #[cfg(any(feature = "launcher", feature = "taskbar"))]
             Msg(Request::ToplevelFocus(id)) => {
                 let handle = env
                     .handles
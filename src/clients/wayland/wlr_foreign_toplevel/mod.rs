@@ -28,7 +28,7 @@ impl Client {
     }
 
     /// Focuses the toplevel with the provided ID.
-    #[cfg(feature = "launcher")]
+    #[cfg(any(feature = "launcher", feature = "taskbar"))]
     pub fn toplevel_focus(&self, handle_id: usize) {
         match self.send_request(Request::ToplevelFocus(handle_id)) {
             Response::Ok => (),
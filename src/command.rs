@@ -0,0 +1,277 @@
+use crate::bar::VisibilityCommand;
+use crate::modules::custom::{StackCommand, SubpopupCommand};
+use crate::popup::Popup;
+use crate::script::Script;
+use crate::{lock, Ironbar};
+use color_eyre::{Help, Report, Result};
+use std::str::FromStr;
+
+/// A command which can be run from a custom widget,
+/// bound to a key combination, or run from a Lua script.
+///
+/// Some commands act on the custom widget they are run from,
+/// so can only be run from one. See [`Command::requires_widget`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Runs a shell script.
+    Script(String),
+    /// Toggles the popup for the named module on the bar,
+    /// or for the widget's own module if `None`.
+    TogglePopup(Option<String>),
+    /// Opens the popup for the named module on the bar,
+    /// or for the widget's own module if `None`.
+    OpenPopup(Option<String>),
+    /// Closes the popup on the bar.
+    ClosePopup,
+    /// Shows or hides bars.
+    Visibility(VisibilityCommand),
+    /// Opens, closes or toggles a subpopup of the widget's module.
+    Subpopup(SubpopupCommand),
+    /// Switches the visible page of the widget's stacks.
+    Stack(StackCommand),
+    /// Re-runs the polling script for the named module.
+    Refresh(String),
+    /// Sets the value of an ironvar.
+    /// If the value is `None`, the first argument passed by the widget is used,
+    /// such as the value of a slider.
+    #[cfg(feature = "ipc")]
+    SetVariable(Box<str>, Option<String>),
+    /// Toggles the outline around every module.
+    #[cfg(feature = "ipc")]
+    DebugOutline,
+}
+
+impl Command {
+    /// Parses a command which is run outside a custom widget,
+    /// such as from a keybind or Lua script.
+    ///
+    /// # Errors
+    ///
+    /// If the command is invalid, or can only be run from a custom widget.
+    pub fn parse_standalone(s: &str) -> Result<Self> {
+        let command = s.parse::<Self>()?;
+
+        if command.requires_widget() {
+            Err(
+                Report::msg(format!("Command can only be used in custom widgets: '{s}'"))
+                    .suggestion("Popup commands must include the module name"),
+            )
+        } else {
+            Ok(command)
+        }
+    }
+
+    /// Whether the command acts on the custom widget it is run from.
+    pub fn requires_widget(&self) -> bool {
+        match self {
+            Self::TogglePopup(name) | Self::OpenPopup(name) => name.is_none(),
+            Self::Subpopup(_) | Self::Stack(_) => true,
+            #[cfg(feature = "ipc")]
+            Self::SetVariable(_, value) => value.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Runs the command against the bar with the given popup.
+    ///
+    /// # Errors
+    ///
+    /// If the command targets a popup or script which does not exist,
+    /// or can only be run from a custom widget.
+    pub fn run(&self, popup: &Popup, ironbar: &Ironbar) -> Result<()> {
+        let popup_error = |err: &str, name: &str| Report::msg(format!("{err}: '{name}'"));
+
+        match self {
+            Self::Script(cmd) => Script::from(cmd.as_str()).run_as_oneshot(None),
+            Self::TogglePopup(Some(name)) => popup
+                .toggle_named(name)
+                .map_err(|err| popup_error(err, name))?,
+            Self::OpenPopup(Some(name)) => popup
+                .open_named(name)
+                .map_err(|err| popup_error(err, name))?,
+            Self::ClosePopup => popup.hide(),
+            Self::Visibility(command) => ironbar.set_bars_visible(command),
+            Self::Refresh(name) => {
+                if !lock!(Ironbar::script_refresh()).refresh(name) {
+                    return Err(Report::msg(format!("No script to refresh: '{name}'")));
+                }
+            }
+            #[cfg(feature = "ipc")]
+            Self::SetVariable(key, Some(value)) => {
+                crate::write_lock!(Ironbar::variable_manager()).set(key.clone(), value.clone())?;
+            }
+            #[cfg(feature = "ipc")]
+            Self::DebugOutline => {
+                ironbar.toggle_debug_outline();
+            }
+            _ => {
+                return Err(Report::msg(format!(
+                    "Command can only be run from a custom widget: {self:?}"
+                )))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Command {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(cmd) = s.strip_prefix('!') {
+            return Ok(Self::Script(cmd.to_string()));
+        }
+
+        if let Some(command) = s.strip_prefix("bar:") {
+            return command.parse().map(Self::Visibility);
+        }
+
+        if let Some(command) = s.strip_prefix("subpopup:") {
+            return command.parse().map(Self::Subpopup);
+        }
+
+        if let Some(command) = s.strip_prefix("stack:") {
+            return command.parse().map(Self::Stack);
+        }
+
+        #[cfg(feature = "ipc")]
+        if s == "debug:outline" {
+            return Ok(Self::DebugOutline);
+        }
+
+        #[cfg(feature = "ipc")]
+        if let Some(command) = s.strip_prefix("var:set:") {
+            let (key, value) = match command.split_once(':') {
+                Some((key, value)) => (key, Some(value.to_string())),
+                None => (command, None),
+            };
+
+            return if key.is_empty() {
+                Err(Report::msg(format!("Invalid variable command: '{s}'"))
+                    .suggestion("Use `var:set:<name>:<value>`"))
+            } else {
+                Ok(Self::SetVariable(key.into(), value))
+            };
+        }
+
+        #[cfg(not(feature = "ipc"))]
+        if s == "debug:outline" || s.starts_with("var:set:") {
+            return Err(Report::msg(format!(
+                "Command requires the `ipc` feature: '{s}'"
+            )));
+        }
+
+        match s.split_once(':') {
+            Some(("refresh", name)) if !name.is_empty() => Ok(Self::Refresh(name.to_string())),
+            Some(("popup", "close")) => Ok(Self::ClosePopup),
+            Some(("popup", "toggle")) => Ok(Self::TogglePopup(None)),
+            Some(("popup", "open")) => Ok(Self::OpenPopup(None)),
+            Some(("popup", command)) => match command.split_once(':') {
+                Some(("toggle", name)) if !name.is_empty() => {
+                    Ok(Self::TogglePopup(Some(name.to_string())))
+                }
+                Some(("open", name)) if !name.is_empty() => {
+                    Ok(Self::OpenPopup(Some(name.to_string())))
+                }
+                _ => Err(Report::msg(format!("Invalid popup command: '{s}'"))
+                    .suggestion("Use `popup:toggle:<name>`, `popup:open:<name>` or `popup:close`")),
+            },
+            _ => Err(Report::msg(format!("Invalid command: '{s}'"))
+                .suggestion("Prefix shell commands with `!`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            "popup:toggle:clock".parse::<Command>().unwrap(),
+            Command::TogglePopup(Some("clock".to_string()))
+        );
+        assert_eq!(
+            "popup:toggle".parse::<Command>().unwrap(),
+            Command::TogglePopup(None)
+        );
+        assert_eq!(
+            "popup:close".parse::<Command>().unwrap(),
+            Command::ClosePopup
+        );
+        assert_eq!(
+            "!notify-send hi".parse::<Command>().unwrap(),
+            Command::Script("notify-send hi".to_string())
+        );
+        assert!(matches!(
+            "bar:toggle".parse::<Command>().unwrap(),
+            Command::Visibility(_)
+        ));
+
+        assert_eq!(
+            "refresh:weather".parse::<Command>().unwrap(),
+            Command::Refresh("weather".to_string())
+        );
+        assert!("popup:toggle:".parse::<Command>().is_err());
+        assert!("refresh:".parse::<Command>().is_err());
+        assert!("notify-send".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_parse_widget_commands() {
+        assert!(matches!(
+            "stack:show media".parse::<Command>().unwrap(),
+            Command::Stack(_)
+        ));
+        assert!(matches!(
+            "subpopup:open volume".parse::<Command>().unwrap(),
+            Command::Subpopup(_)
+        ));
+        assert!("stack:hide media".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_parse_standalone() {
+        assert!(Command::parse_standalone("popup:toggle:clock").is_ok());
+        assert!(Command::parse_standalone("popup:toggle").is_err());
+        assert!(Command::parse_standalone("popup:open").is_err());
+        assert!(Command::parse_standalone("stack:show media").is_err());
+        assert!(Command::parse_standalone("subpopup:open volume").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ipc")]
+    fn test_parse_set_variable() {
+        assert_eq!(
+            "var:set:volume:50".parse::<Command>().unwrap(),
+            Command::SetVariable("volume".into(), Some("50".to_string()))
+        );
+        assert_eq!(
+            "var:set:time:12:30".parse::<Command>().unwrap(),
+            Command::SetVariable("time".into(), Some("12:30".to_string()))
+        );
+        assert_eq!(
+            "var:set:empty:".parse::<Command>().unwrap(),
+            Command::SetVariable("empty".into(), Some(String::new()))
+        );
+        assert_eq!(
+            "var:set:volume".parse::<Command>().unwrap(),
+            Command::SetVariable("volume".into(), None)
+        );
+
+        assert!("var:set::50".parse::<Command>().is_err());
+        assert!(Command::parse_standalone("var:set:volume").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ipc")]
+    fn test_parse_debug_outline() {
+        assert_eq!(
+            "debug:outline".parse::<Command>().unwrap(),
+            Command::DebugOutline
+        );
+        assert!("debug:".parse::<Command>().is_err());
+    }
+}
@@ -1,15 +1,31 @@
-use crate::dynamic_value::{dynamic_string, DynamicBool};
+use crate::dynamic_value::{dynamic_string_when_visible, DynamicBool};
+use crate::gtk_helpers::{rotate_widget, watch_hovered, IronbarGtkExt};
+use crate::popup::PopupOptions;
 use crate::script::{Script, ScriptInput};
-use glib::Propagation;
+use glib::{ControlFlow, Propagation, SourceId};
 use gtk::gdk::ScrollDirection;
 use gtk::prelude::*;
-use gtk::{EventBox, Orientation, Revealer, RevealerTransitionType};
-use serde::Deserialize;
-use tracing::trace;
+use gtk::{
+    Align, EventBox, GestureMultiPress, Orientation, PropagationPhase, Revealer,
+    RevealerTransitionType, Widget,
+};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer};
+use std::cell::RefCell;
+#[cfg(feature = "ipc")]
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+use tracing::{trace, warn};
+
+/// Class toggled on a widget while it blinks.
+const BLINK_CLASS: &str = "blink";
 
 /// Common configuration options
 /// which can be set on every module.
-#[derive(Debug, Default, Deserialize, Clone)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone)]
 pub struct CommonConfig {
     pub class: Option<String>,
     pub name: Option<String>,
@@ -18,20 +34,91 @@ pub struct CommonConfig {
     pub transition_type: Option<TransitionType>,
     pub transition_duration: Option<u32>,
 
+    /// Hides the widget until the pointer is over the module containing it.
+    #[serde(default, deserialize_with = "deserialize_reveal_on_hover")]
+    #[schemars(with = "Option<RevealOnHoverInput>")]
+    pub reveal_on_hover: Option<RevealOnHover>,
+
+    /// Blinks the widget while the condition is true,
+    /// by toggling the `blink` class.
+    pub blink_if: Option<DynamicBool>,
+    /// Time in milliseconds between toggling the `blink` class.
+    pub blink_interval: Option<u32>,
+
+    /// Horizontal alignment of the widget within its allocated space.
+    pub halign: Option<Alignment>,
+    /// Vertical alignment of the widget within its allocated space.
+    pub valign: Option<Alignment>,
+    /// Whether the widget takes up any extra horizontal space.
+    pub hexpand: Option<bool>,
+    /// Whether the widget takes up any extra vertical space.
+    pub vexpand: Option<bool>,
+    /// Rotation of the widget's text or icon,
+    /// so that it reads correctly on a vertical bar.
+    pub rotate: Option<Rotation>,
+    /// Whether pointer events pass through the widget
+    /// to whatever is beneath it.
+    #[serde(default)]
+    pub passthrough: bool,
+    /// Priority for keeping the widget on the bar
+    /// when its section overflows.
+    /// Widgets with lower priorities are hidden first.
+    #[serde(default)]
+    pub priority: i32,
+
+    #[serde(alias = "on_click")]
     pub on_click_left: Option<ScriptInput>,
     pub on_click_right: Option<ScriptInput>,
     pub on_click_middle: Option<ScriptInput>,
     pub on_scroll_up: Option<ScriptInput>,
     pub on_scroll_down: Option<ScriptInput>,
+    /// Runs on scrolling in either direction
+    /// which does not have its own script,
+    /// passing the direction as an argument.
+    pub on_scroll: Option<ScriptInput>,
     pub on_mouse_enter: Option<ScriptInput>,
     pub on_mouse_exit: Option<ScriptInput>,
+    /// Runs when the module fails to load or enters an error state,
+    /// passing the error message as an argument.
+    /// Only the `dbus`, `mqtt` and `script` modules have an error state.
+    pub on_error: Option<ScriptInput>,
 
     pub tooltip: Option<String>,
+    /// Whether to wait until the tooltip is first hovered before running its scripts,
+    /// pausing them again while it is not.
+    /// For custom modules, this also defers scripts in the popup until it is first opened.
+    #[serde(default)]
+    pub defer_until_visible: bool,
+    /// Text to show in a badge in the top-right corner.
+    /// The badge is hidden while the text is empty or zero.
+    pub badge: Option<String>,
     #[serde(default)]
     pub disable_popup: bool,
+    /// Where to open the popup relative to the module.
+    #[serde(default)]
+    pub popup_anchor: PopupAnchor,
+    /// Distance in pixels to shift the popup along the bar.
+    #[serde(default)]
+    pub popup_offset: i32,
+    /// Whether to close the popup once the pointer leaves it
+    /// or it loses focus.
+    #[serde(default)]
+    pub popup_auto_close: bool,
+    /// The animation to use when opening and closing the popup.
+    pub popup_transition: Option<PopupTransition>,
+    /// The length of the popup animation in milliseconds.
+    pub popup_transition_duration: Option<u32>,
+
+    /// Minimum time in milliseconds between widget updates.
+    #[serde(alias = "throttle")]
+    pub debounce: Option<u64>,
+    /// Time in milliseconds an error or unavailable state must persist
+    /// before it is shown.
+    /// Only the `dbus`, `mqtt` and `script` modules have an error state.
+    pub grace_period: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum TransitionType {
     None,
@@ -40,7 +127,145 @@ pub enum TransitionType {
     SlideEnd,
 }
 
-#[derive(Debug, Default, Deserialize, Clone, Copy)]
+/// Animation used when revealing a widget on hover.
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RevealOnHover {
+    pub transition_type: Option<TransitionType>,
+    /// The length of the animation in milliseconds.
+    pub transition_duration: Option<u32>,
+}
+
+/// Accepts either `true` to reveal with the default animation,
+/// or the animation options.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RevealOnHoverInput {
+    Enabled(bool),
+    Options(RevealOnHover),
+}
+
+fn deserialize_reveal_on_hover<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<RevealOnHover>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(
+        match Option::<RevealOnHoverInput>::deserialize(deserializer)? {
+            Some(RevealOnHoverInput::Enabled(true)) => Some(RevealOnHover::default()),
+            Some(RevealOnHoverInput::Options(options)) => Some(options),
+            Some(RevealOnHoverInput::Enabled(false)) | None => None,
+        },
+    )
+}
+
+/// Which part of the popup lines up with the module.
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PopupAnchor {
+    /// The popup's start edge aligns with the module's start edge.
+    Start,
+    /// The popup is centered on the module.
+    #[default]
+    Center,
+    /// The popup's end edge aligns with the module's end edge.
+    End,
+}
+
+/// Animation used when opening and closing a popup.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PopupTransition {
+    /// The popup fades in and out.
+    Crossfade,
+    /// The popup slides out from the bar.
+    Slide,
+}
+
+/// Position of a widget within its allocated space.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    Start,
+    Center,
+    End,
+    /// The widget is stretched to fill the space.
+    Fill,
+}
+
+impl From<Alignment> for Align {
+    fn from(alignment: Alignment) -> Self {
+        match alignment {
+            Alignment::Start => Self::Start,
+            Alignment::Center => Self::Center,
+            Alignment::End => Self::End,
+            Alignment::Fill => Self::Fill,
+        }
+    }
+}
+
+/// Counter-clockwise rotation of a widget's text or icon.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "RotationInput")]
+pub enum Rotation {
+    /// Rotated by 90 degrees on vertical bars,
+    /// and left as-is on horizontal bars.
+    Auto,
+    /// Rotated by a fixed number of degrees,
+    /// which is a multiple of 90.
+    Degrees(u16),
+}
+
+/// Rotations can be written as `auto`,
+/// or as a number of degrees either with or without quotes.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RotationInput {
+    Degrees(u16),
+    Name(String),
+}
+
+impl TryFrom<RotationInput> for Rotation {
+    type Error = String;
+
+    fn try_from(input: RotationInput) -> Result<Self, Self::Error> {
+        let degrees = match input {
+            RotationInput::Name(name) if name == "auto" => return Ok(Self::Auto),
+            RotationInput::Name(name) => name.parse().ok(),
+            RotationInput::Degrees(degrees) => Some(degrees),
+        };
+
+        match degrees {
+            Some(degrees @ (0 | 90 | 180 | 270)) => Ok(Self::Degrees(degrees)),
+            _ => Err("expected one of `auto`, `0`, `90`, `180` or `270`".to_string()),
+        }
+    }
+}
+
+impl JsonSchema for Rotation {
+    fn schema_name() -> String {
+        "Rotation".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RotationInput::json_schema(gen)
+    }
+}
+
+impl Rotation {
+    /// Gets the angle in degrees to rotate by
+    /// on a bar with the given orientation.
+    pub const fn angle(self, bar_orientation: Orientation) -> u16 {
+        match (self, bar_orientation) {
+            (Self::Auto, Orientation::Vertical) => 90,
+            (Self::Auto, _) => 0,
+            (Self::Degrees(degrees), _) => degrees,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum ModuleOrientation {
     #[default]
@@ -85,43 +310,123 @@ impl TransitionType {
 }
 
 impl CommonConfig {
+    /// Applies the configured alignment and expand properties to the widget.
+    /// Unset properties are left at the widget's defaults.
+    pub fn apply_alignment(&self, widget: &Widget) {
+        if let Some(halign) = self.halign {
+            widget.set_halign(halign.into());
+        }
+
+        if let Some(valign) = self.valign {
+            widget.set_valign(valign.into());
+        }
+
+        if let Some(hexpand) = self.hexpand {
+            widget.set_hexpand(hexpand);
+        }
+
+        if let Some(vexpand) = self.vexpand {
+            widget.set_vexpand(vexpand);
+        }
+    }
+
+    /// Rotates the widget's text or icon by the configured rotation.
+    /// Widgets without any text or icon to rotate are left as-is.
+    pub fn apply_rotation(&self, widget: &Widget, bar_orientation: Orientation) {
+        let Some(rotation) = self.rotate else {
+            return;
+        };
+
+        let angle = rotation.angle(bar_orientation);
+        if angle != 0 && !rotate_widget(widget, angle) {
+            warn!(
+                "Cannot rotate '{}' widget, as it contains no labels or images",
+                widget.type_().name()
+            );
+        }
+    }
+
+    pub const fn popup_options(&self) -> PopupOptions {
+        PopupOptions {
+            anchor: self.popup_anchor,
+            offset: self.popup_offset,
+            auto_close: self.popup_auto_close,
+            transition: self.popup_transition,
+            transition_duration: match self.popup_transition_duration {
+                Some(duration) => duration,
+                None => 250,
+            },
+        }
+    }
+
+    /// Gets the command for each configured event script,
+    /// keyed by its option name.
+    #[cfg(feature = "ipc")]
+    pub fn event_scripts(&self) -> BTreeMap<&'static str, String> {
+        [
+            ("on_click_left", &self.on_click_left),
+            ("on_click_right", &self.on_click_right),
+            ("on_click_middle", &self.on_click_middle),
+            ("on_scroll_up", &self.on_scroll_up),
+            ("on_scroll_down", &self.on_scroll_down),
+            ("on_scroll", &self.on_scroll),
+            ("on_mouse_enter", &self.on_mouse_enter),
+            ("on_mouse_exit", &self.on_mouse_exit),
+            ("on_error", &self.on_error),
+        ]
+        .into_iter()
+        .filter_map(|(event, script)| {
+            script
+                .clone()
+                .map(|script| (event, Script::from(script).cmd))
+        })
+        .collect()
+    }
+
     /// Configures the module's container according to the common config options.
     pub fn install_events(mut self, container: &EventBox, revealer: &Revealer) {
         self.install_show_if(container, revealer);
 
-        let left_click_script = self.on_click_left.map(Script::new_polling);
-        let middle_click_script = self.on_click_middle.map(Script::new_polling);
-        let right_click_script = self.on_click_right.map(Script::new_polling);
+        let click_scripts = ClickScripts {
+            left: self.on_click_left.map(Script::new_polling),
+            middle: self.on_click_middle.map(Script::new_polling),
+            right: self.on_click_right.map(Script::new_polling),
+        };
 
-        container.connect_button_press_event(move |_, event| {
-            let script = match event.button() {
-                1 => left_click_script.as_ref(),
-                2 => middle_click_script.as_ref(),
-                3 => right_click_script.as_ref(),
-                _ => None,
-            };
+        if !click_scripts.is_empty() {
+            // clicks are picked up in the capture phase,
+            // before they reach the module's own widgets.
+            // the gesture never claims the click,
+            // so buttons inside the module still receive it as normal.
+            let gesture = GestureMultiPress::new(container);
+            gesture.set_button(0);
+            gesture.set_propagation_phase(PropagationPhase::Capture);
 
-            if let Some(script) = script {
-                trace!("Running on-click script: {}", event.button());
-                script.run_as_oneshot(None);
-            }
+            gesture.connect_pressed(move |gesture, _, _, _| {
+                let button = gesture.current_button();
+                if let Some(script) = click_scripts.get(button) {
+                    trace!("Running on-click script: {button}");
+                    script.run_as_oneshot(None);
+                }
+            });
 
-            Propagation::Proceed
-        });
+            // gestures are not owned by their widget in GTK3,
+            // so keep it alive for as long as the container
+            container.connect_destroy(move |_| {
+                let _ = &gesture;
+            });
+        }
 
-        let scroll_up_script = self.on_scroll_up.map(Script::new_polling);
-        let scroll_down_script = self.on_scroll_down.map(Script::new_polling);
+        let scroll_scripts = ScrollScripts {
+            up: self.on_scroll_up.map(Script::new_polling),
+            down: self.on_scroll_down.map(Script::new_polling),
+            any: self.on_scroll.map(Script::new_polling),
+        };
 
         container.connect_scroll_event(move |_, event| {
-            let script = match event.direction() {
-                ScrollDirection::Up => scroll_up_script.as_ref(),
-                ScrollDirection::Down => scroll_down_script.as_ref(),
-                _ => None,
-            };
-
-            if let Some(script) = script {
+            if let Some((script, args)) = scroll_scripts.get(event.direction()) {
                 trace!("Running on-scroll script: {}", event.direction());
-                script.run_as_oneshot(None);
+                script.run_as_oneshot(args.as_deref());
             }
 
             Propagation::Proceed
@@ -142,13 +447,30 @@ impl CommonConfig {
         install_oneshot!(self.on_mouse_exit, connect_leave_notify_event);
 
         if let Some(tooltip) = self.tooltip {
+            let visible = self.defer_until_visible.then(|| watch_hovered(container));
+
             let container = container.clone();
-            dynamic_string(&tooltip, move |string| {
+            dynamic_string_when_visible(&tooltip, visible, move |string| {
                 container.set_tooltip_text(Some(&string));
             });
         }
     }
 
+    /// Blinks the widget while the `blink_if` condition is true.
+    ///
+    /// If animations are disabled,
+    /// the `blink` class is added for as long as the condition is true instead.
+    pub fn install_blink(&mut self, widget: &Widget, animations_enabled: bool) {
+        let Some(blink_if) = self.blink_if.take() else {
+            return;
+        };
+
+        let interval = Duration::from_millis(u64::from(self.blink_interval.unwrap_or(500)));
+        let blinker = Blinker::new(widget.clone(), interval, animations_enabled);
+
+        blink_if.subscribe(move |active| blinker.set_active(active));
+    }
+
     fn install_show_if(&mut self, container: &EventBox, revealer: &Revealer) {
         self.show_if.take().map_or_else(
             || {
@@ -178,3 +500,425 @@ impl CommonConfig {
         );
     }
 }
+
+/// Scripts to run when the module is clicked.
+struct ClickScripts {
+    left: Option<Script>,
+    middle: Option<Script>,
+    right: Option<Script>,
+}
+
+impl ClickScripts {
+    const fn is_empty(&self) -> bool {
+        self.left.is_none() && self.middle.is_none() && self.right.is_none()
+    }
+
+    /// Gets the script for a mouse button, if there is one.
+    const fn get(&self, button: u32) -> Option<&Script> {
+        match button {
+            1 => self.left.as_ref(),
+            2 => self.middle.as_ref(),
+            3 => self.right.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Scripts to run when the module is scrolled on.
+struct ScrollScripts {
+    up: Option<Script>,
+    down: Option<Script>,
+    any: Option<Script>,
+}
+
+impl ScrollScripts {
+    /// Gets the script for a scroll direction, if there is one,
+    /// along with the arguments to run it with.
+    ///
+    /// Direction-specific scripts take priority over `on_scroll`,
+    /// which receives the direction as its argument.
+    fn get(&self, direction: ScrollDirection) -> Option<(&Script, Option<Vec<String>>)> {
+        let (specific, name) = match direction {
+            ScrollDirection::Up => (self.up.as_ref(), "up"),
+            ScrollDirection::Down => (self.down.as_ref(), "down"),
+            _ => return None,
+        };
+
+        specific.map(|script| (script, None)).or_else(|| {
+            self.any
+                .as_ref()
+                .map(|script| (script, Some(vec![name.to_string()])))
+        })
+    }
+}
+
+/// Toggles the `blink` class on a widget at a fixed interval
+/// while it is active.
+struct Blinker {
+    widget: Widget,
+    interval: Duration,
+    animated: bool,
+    /// The running timeout, if blinking.
+    source: RefCell<Option<SourceId>>,
+}
+
+impl Blinker {
+    fn new(widget: Widget, interval: Duration, animated: bool) -> Rc<Self> {
+        let blinker = Rc::new(Self {
+            widget,
+            interval,
+            animated,
+            source: RefCell::new(None),
+        });
+
+        // the timeout holds the widget, so is stopped when it is destroyed
+        let weak = Rc::downgrade(&blinker);
+        blinker.widget.connect_destroy(move |_| {
+            if let Some(source) = weak.upgrade().and_then(|blinker| blinker.source.take()) {
+                source.remove();
+            }
+        });
+
+        blinker
+    }
+
+    /// Starts or stops blinking.
+    /// Does nothing if already in the requested state.
+    fn set_active(&self, active: bool) {
+        if active == self.is_active() {
+            return;
+        }
+
+        if active {
+            self.widget.add_class(BLINK_CLASS);
+
+            if self.animated {
+                let widget = self.widget.clone();
+                let source = glib::timeout_add_local(self.interval, move || {
+                    let style = widget.style_context();
+                    if style.has_class(BLINK_CLASS) {
+                        style.remove_class(BLINK_CLASS);
+                    } else {
+                        style.add_class(BLINK_CLASS);
+                    }
+
+                    ControlFlow::Continue
+                });
+
+                self.source.replace(Some(source));
+            }
+        } else {
+            if let Some(source) = self.source.take() {
+                source.remove();
+            }
+
+            self.widget.style_context().remove_class(BLINK_CLASS);
+        }
+    }
+
+    /// Whether the blink condition is currently met.
+    fn is_active(&self) -> bool {
+        if self.animated {
+            self.source.borrow().is_some()
+        } else {
+            self.widget.style_context().has_class(BLINK_CLASS)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use gtk::Label;
+    use std::cell::Cell;
+
+    fn blinker(animated: bool) -> (Label, Rc<Blinker>) {
+        let label = Label::new(None);
+        let blinker = Blinker::new(label.clone().upcast(), Duration::from_secs(60), animated);
+        (label, blinker)
+    }
+
+    fn is_running(blinker: &Blinker) -> bool {
+        blinker.source.borrow().is_some()
+    }
+
+    fn has_class(label: &Label) -> bool {
+        label.style_context().has_class(BLINK_CLASS)
+    }
+
+    fn script(cmd: &str) -> Option<Script> {
+        Some(Script::from(cmd))
+    }
+
+    fn cmd(script: Option<&Script>) -> Option<&str> {
+        script.map(|script| script.cmd.as_str())
+    }
+
+    fn rotation(value: serde_json::Value) -> Result<Rotation, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn test_rotation_input() {
+        assert_eq!(rotation(serde_json::json!("auto")).unwrap(), Rotation::Auto);
+        assert_eq!(
+            rotation(serde_json::json!(90)).unwrap(),
+            Rotation::Degrees(90)
+        );
+        assert_eq!(
+            rotation(serde_json::json!("270")).unwrap(),
+            Rotation::Degrees(270)
+        );
+
+        for invalid in [serde_json::json!(45), serde_json::json!("sideways")] {
+            let err = rotation(invalid).unwrap_err();
+            assert!(err.to_string().contains("`0`"));
+        }
+    }
+
+    #[test]
+    fn test_auto_rotation_angle() {
+        assert_eq!(Rotation::Auto.angle(Orientation::Horizontal), 0);
+        assert_eq!(Rotation::Auto.angle(Orientation::Vertical), 90);
+    }
+
+    #[test]
+    fn test_fixed_rotation_angle() {
+        for orientation in [Orientation::Horizontal, Orientation::Vertical] {
+            assert_eq!(Rotation::Degrees(180).angle(orientation), 180);
+            assert_eq!(Rotation::Degrees(270).angle(orientation), 270);
+        }
+    }
+
+    #[test]
+    fn test_click_scripts() {
+        let scripts = ClickScripts {
+            left: script("left"),
+            middle: None,
+            right: script("right"),
+        };
+
+        assert!(!scripts.is_empty());
+        assert_eq!(cmd(scripts.get(1)), Some("left"));
+        assert_eq!(cmd(scripts.get(2)), None);
+        assert_eq!(cmd(scripts.get(3)), Some("right"));
+        assert_eq!(cmd(scripts.get(8)), None);
+    }
+
+    #[test]
+    fn test_click_scripts_empty() {
+        let scripts = ClickScripts {
+            left: None,
+            middle: None,
+            right: None,
+        };
+
+        // no gesture is installed, so clicks go straight to the module
+        assert!(scripts.is_empty());
+        assert!(scripts.get(1).is_none());
+    }
+
+    #[test]
+    fn test_scroll_scripts() {
+        let scripts = ScrollScripts {
+            up: script("up"),
+            down: None,
+            any: script("scroll"),
+        };
+
+        let (up, args) = scripts.get(ScrollDirection::Up).unwrap();
+        assert_eq!(up.cmd, "up");
+        assert_eq!(args, None);
+
+        let (down, args) = scripts.get(ScrollDirection::Down).unwrap();
+        assert_eq!(down.cmd, "scroll");
+        assert_eq!(args, Some(vec!["down".to_string()]));
+
+        assert!(scripts.get(ScrollDirection::Smooth).is_none());
+    }
+
+    #[test]
+    fn test_config_aliases() {
+        let config: CommonConfig =
+            serde_json::from_str(r#"{ "on_click": "echo hi", "on_scroll": "echo scroll" }"#)
+                .unwrap();
+
+        assert!(config.on_click_left.is_some());
+        assert!(config.on_scroll.is_some());
+    }
+
+    #[test]
+    fn test_reveal_on_hover_config() {
+        let parse = |json: &str| {
+            serde_json::from_str::<CommonConfig>(json)
+                .unwrap()
+                .reveal_on_hover
+        };
+
+        assert!(parse("{}").is_none());
+        assert!(parse(r#"{ "reveal_on_hover": false }"#).is_none());
+
+        let options = parse(r#"{ "reveal_on_hover": true }"#).unwrap();
+        assert!(options.transition_type.is_none());
+        assert!(options.transition_duration.is_none());
+
+        let options =
+            parse(r#"{ "reveal_on_hover": { "transition_type": "crossfade", "transition_duration": 100 } }"#)
+                .unwrap();
+        assert!(matches!(
+            options.transition_type,
+            Some(TransitionType::Crossfade)
+        ));
+        assert_eq!(options.transition_duration, Some(100));
+
+        assert!(
+            serde_json::from_str::<CommonConfig>(r#"{ "reveal_on_hover": { "delay": 1 } }"#)
+                .is_err()
+        );
+    }
+
+    /// Emits a synthetic scroll event on the container,
+    /// returning whether it was stopped from propagating
+    /// and whether a handler connected after the common ones received it.
+    fn emit_scroll(container: &EventBox) -> (bool, bool) {
+        let received = Rc::new(Cell::new(false));
+
+        {
+            let received = received.clone();
+            container.connect_scroll_event(move |_, _| {
+                received.set(true);
+                Propagation::Proceed
+            });
+        }
+
+        // synthetic scroll events default to scrolling up
+        let stopped = container.emit_by_name::<bool>(
+            "scroll-event",
+            &[&gtk::gdk::Event::new(gtk::gdk::EventType::Scroll)],
+        );
+
+        (stopped, received.get())
+    }
+
+    #[test]
+    fn test_install_events_without_scripts() {
+        with_gtk(|| {
+            let container = EventBox::new();
+            let revealer = Revealer::new();
+
+            CommonConfig::default().install_events(&container, &revealer);
+
+            // the module's own handlers are left to handle events
+            let (stopped, received) = emit_scroll(&container);
+            assert!(!stopped);
+            assert!(received);
+        });
+    }
+
+    #[test]
+    fn test_install_events_runs_script() {
+        with_gtk(|| {
+            let path = std::env::temp_dir().join(format!("ironbar-scroll-{}", std::process::id()));
+            std::fs::remove_file(&path).ok();
+
+            let config: CommonConfig = serde_json::from_value(serde_json::json!({
+                "on_scroll_up": format!("touch {}", path.display())
+            }))
+            .unwrap();
+
+            let container = EventBox::new();
+            let revealer = Revealer::new();
+            config.install_events(&container, &revealer);
+
+            // the script runs without stopping the module receiving the event
+            let (stopped, received) = emit_scroll(&container);
+            assert!(!stopped);
+            assert!(received);
+
+            let start = std::time::Instant::now();
+            while !path.exists() && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+
+            assert!(path.exists());
+            std::fs::remove_file(&path).ok();
+        });
+    }
+
+    #[test]
+    fn test_blink_starts_and_stops() {
+        with_gtk(|| {
+            let (label, blinker) = blinker(true);
+            assert!(!is_running(&blinker));
+
+            blinker.set_active(true);
+            assert!(is_running(&blinker));
+            assert!(has_class(&label));
+
+            blinker.set_active(false);
+            assert!(!is_running(&blinker));
+            assert!(!has_class(&label));
+        });
+    }
+
+    #[test]
+    fn test_blink_stops_on_destroy() {
+        with_gtk(|| {
+            let (label, blinker) = blinker(true);
+
+            blinker.set_active(true);
+            assert!(is_running(&blinker));
+
+            // SAFETY: the label is not used again after being destroyed
+            unsafe { label.destroy() };
+            assert!(!is_running(&blinker));
+        });
+    }
+
+    #[test]
+    fn test_blink_repeated_condition() {
+        with_gtk(|| {
+            let (_label, blinker) = blinker(true);
+
+            blinker.set_active(false);
+            assert!(!is_running(&blinker));
+
+            blinker.set_active(true);
+            let source = blinker
+                .source
+                .borrow()
+                .as_ref()
+                .map(|source| source.as_raw());
+
+            // a second truthy update keeps the same timer
+            blinker.set_active(true);
+            assert_eq!(
+                blinker
+                    .source
+                    .borrow()
+                    .as_ref()
+                    .map(|source| source.as_raw()),
+                source
+            );
+
+            blinker.set_active(false);
+            blinker.set_active(false);
+            assert!(!is_running(&blinker));
+        });
+    }
+
+    #[test]
+    fn test_blink_without_animations() {
+        with_gtk(|| {
+            let (label, blinker) = blinker(false);
+
+            blinker.set_active(true);
+            assert!(!is_running(&blinker));
+            assert!(has_class(&label));
+
+            blinker.set_active(false);
+            assert!(!has_class(&label));
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use super::{BarConfig, BarPosition, MonitorConfig};
+use super::{BarConfig, BarPosition, Config, MonitorConfig};
 use color_eyre::{Help, Report};
 use gtk::Orientation;
 use serde::{Deserialize, Deserializer};
@@ -56,3 +56,203 @@ impl BarPosition {
         }
     }
 }
+
+impl Config {
+    /// Gets the config for each bar to be displayed on an output.
+    ///
+    /// The `monitors` map is checked for the output name first,
+    /// and then for the output's index (as a string, ie `"0"`).
+    /// If neither is present, the top-level bar config is used,
+    /// provided it contains any modules, followed by each of the extra `bars`.
+    ///
+    /// Monitor and extra bars inherit the top-level opacity if they do not set their own.
+    pub fn bars_for_monitor(&self, name: &str, index: Option<usize>) -> Vec<BarConfig> {
+        let monitor_config = self.monitors.as_ref().and_then(|monitors| {
+            monitors
+                .get(name)
+                .or_else(|| index.and_then(|index| monitors.get(&index.to_string())))
+        });
+
+        let inherit = |config: &BarConfig| BarConfig {
+            opacity: config.opacity.or(self.bar.opacity),
+            ..config.clone()
+        };
+
+        match monitor_config {
+            Some(MonitorConfig::Single(config)) => vec![inherit(config)],
+            Some(MonitorConfig::Multiple(configs)) => configs.iter().map(inherit).collect(),
+            None => {
+                let has_modules =
+                    self.bar.start.is_some() || self.bar.center.is_some() || self.bar.end.is_some();
+
+                has_modules
+                    .then(|| self.bar.clone())
+                    .into_iter()
+                    .chain(self.bars.iter().map(inherit))
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MarginConfig;
+    use serde_json::json;
+
+    fn config() -> Config {
+        serde_json::from_value(json!({
+            "name": "default",
+            "start": [],
+            "monitors": {
+                "DP-1": { "name": "dp-1" },
+                "1": [{ "name": "index-1-a" }, { "name": "index-1-b" }],
+                "HDMI-A-1": { "name": "hdmi" }
+            }
+        }))
+        .expect("valid config")
+    }
+
+    fn names(bars: &[BarConfig]) -> Vec<&str> {
+        bars.iter().filter_map(|bar| bar.name.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_position_orientation() {
+        assert_eq!(BarPosition::Top.orientation(), Orientation::Horizontal);
+        assert_eq!(BarPosition::Bottom.orientation(), Orientation::Horizontal);
+        assert_eq!(BarPosition::Left.orientation(), Orientation::Vertical);
+        assert_eq!(BarPosition::Right.orientation(), Orientation::Vertical);
+
+        assert_eq!(BarPosition::Top.get_angle(), 0.0);
+        assert_eq!(BarPosition::Left.get_angle(), 90.0);
+        assert_eq!(BarPosition::Right.get_angle(), 270.0);
+    }
+
+    #[test]
+    fn test_edge_alias() {
+        let config: BarConfig = serde_json::from_value(json!({ "edge": "left" })).unwrap();
+        assert_eq!(config.position, BarPosition::Left);
+    }
+
+    #[test]
+    fn test_margin() {
+        let margin = |value| serde_json::from_value::<MarginConfig>(value).unwrap();
+
+        assert_eq!(
+            margin(json!(8)),
+            MarginConfig {
+                top: 8,
+                bottom: 8,
+                left: 8,
+                right: 8
+            }
+        );
+        assert_eq!(
+            margin(json!({ "top": 4, "left": 2 })),
+            MarginConfig {
+                top: 4,
+                bottom: 0,
+                left: 2,
+                right: 0
+            }
+        );
+        assert!(serde_json::from_value::<MarginConfig>(json!({ "middle": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_monitor_by_name() {
+        let config = config();
+        assert_eq!(names(&config.bars_for_monitor("DP-1", Some(1))), ["dp-1"]);
+    }
+
+    #[test]
+    fn test_monitor_by_index() {
+        let config = config();
+        assert_eq!(
+            names(&config.bars_for_monitor("DP-2", Some(1))),
+            ["index-1-a", "index-1-b"]
+        );
+    }
+
+    #[test]
+    fn test_monitor_fallback() {
+        let config = config();
+        assert_eq!(
+            names(&config.bars_for_monitor("DP-3", Some(2))),
+            ["default"]
+        );
+        assert_eq!(names(&config.bars_for_monitor("DP-3", None)), ["default"]);
+    }
+
+    #[test]
+    fn test_monitor_no_default() {
+        let config: Config = serde_json::from_value(json!({
+            "monitors": { "DP-1": { "name": "dp-1" } }
+        }))
+        .expect("valid config");
+
+        assert!(config.bars_for_monitor("DP-2", Some(0)).is_empty());
+    }
+
+    #[test]
+    fn test_monitor_inherits_opacity() {
+        let config: Config = serde_json::from_value(json!({
+            "opacity": 0.8,
+            "monitors": {
+                "DP-1": { "name": "dp-1" },
+                "DP-2": [{ "name": "dp-2-a", "opacity": 0.5 }, { "name": "dp-2-b" }]
+            }
+        }))
+        .expect("valid config");
+
+        let opacities = |name| {
+            config
+                .bars_for_monitor(name, None)
+                .iter()
+                .map(|bar| bar.opacity)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(opacities("DP-1"), [Some(0.8)]);
+        assert_eq!(opacities("DP-2"), [Some(0.5), Some(0.8)]);
+    }
+
+    #[test]
+    fn test_extra_bars() {
+        let config: Config = serde_json::from_value(json!({
+            "name": "default",
+            "position": "bottom",
+            "opacity": 0.8,
+            "start": [],
+            "bars": [{ "name": "extra", "position": "top" }],
+            "monitors": { "DP-1": [{ "name": "dp-1" }] }
+        }))
+        .expect("valid config");
+
+        let bars = config.bars_for_monitor("DP-2", None);
+        assert_eq!(names(&bars), ["default", "extra"]);
+        assert_eq!(bars[1].position, BarPosition::Top);
+        assert_eq!(bars[1].opacity, Some(0.8));
+
+        // monitor entries replace the extra bars too
+        assert_eq!(names(&config.bars_for_monitor("DP-1", None)), ["dp-1"]);
+    }
+
+    #[test]
+    fn test_extra_bars_without_default() {
+        let config: Config = serde_json::from_value(json!({
+            "bars": [
+                { "name": "top", "position": "top", "start": [] },
+                { "name": "bottom", "start": [] }
+            ]
+        }))
+        .expect("valid config");
+
+        assert_eq!(
+            names(&config.bars_for_monitor("DP-1", Some(0))),
+            ["top", "bottom"]
+        );
+    }
+}
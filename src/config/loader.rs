@@ -0,0 +1,266 @@
+use super::{validate, variables, Config};
+use color_eyre::eyre::WrapErr;
+use color_eyre::{Help, Report, Result};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+use universal_config::ConfigLoader;
+
+/// Top-level key used to include other config files.
+const INCLUDE_KEY: &str = "include";
+
+/// Supported config file extensions,
+/// in the order they are searched for.
+const EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml", "corn", "ron"];
+
+/// Attempts to find a `config.<ext>` file
+/// inside the provided directory.
+pub fn find_file(dir: &Path) -> Result<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| dir.join(format!("config.{extension}")))
+        .find(|path| path.exists())
+        .ok_or_else(|| {
+            Report::msg(format!(
+                "Unable to find any valid config file within '{}'",
+                dir.display()
+            ))
+        })
+}
+
+/// Loads the config file at the provided path,
+/// resolving any `include` directives and variable references,
+/// then validates and deserializes the result.
+pub fn load(path: &Path) -> Result<Config> {
+    let mut value = load_value(path, &mut vec![])?;
+    variables::substitute(&mut value)?;
+    validate::validate(&value)?;
+    serde_json::from_value(value).wrap_err("Failed to deserialize config")
+}
+
+/// Loads the file at the provided path into a generic value,
+/// recursively loading and merging any included files.
+///
+/// Included files are merged in the order they are listed,
+/// so later includes take precedence over earlier ones.
+/// Keys in the including file always take precedence over its includes.
+///
+/// `stack` contains the canonical paths of each file currently being loaded,
+/// and is used to detect include cycles.
+fn load_value(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value> {
+    let path = path
+        .canonicalize()
+        .wrap_err_with(|| format!("Failed to read config file '{}'", path.display()))?;
+
+    if stack.contains(&path) {
+        let chain = stack
+            .iter()
+            .chain(std::iter::once(&path))
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+
+        return Err(
+            Report::msg(format!("Config include cycle detected: {chain}"))
+                .suggestion("Remove one of the `include` entries to break the cycle"),
+        );
+    }
+
+    let mut value: Value = ConfigLoader::load(&path)
+        .map_err(Report::new)
+        .wrap_err_with(|| format!("Failed to load config file '{}'", path.display()))?;
+
+    let includes = match &mut value {
+        Value::Object(map) => map.remove(INCLUDE_KEY),
+        _ => None,
+    };
+
+    let Some(includes) = includes else {
+        return Ok(value);
+    };
+
+    let includes = parse_includes(includes)
+        .wrap_err_with(|| format!("Invalid `include` in '{}'", path.display()))?;
+
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    stack.push(path.clone());
+
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        let include_path = dir.join(&include);
+
+        if !include_path.exists() {
+            return Err(Report::msg(format!(
+                "Included config file '{}' does not exist",
+                include_path.display()
+            ))
+            .note(format!("Included from '{}'", path.display())));
+        }
+
+        debug!("Including config file '{}'", include_path.display());
+
+        let included = load_value(&include_path, stack)?;
+        merge(&mut merged, included);
+    }
+
+    stack.pop();
+
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Parses the value of an `include` key,
+/// which may be either a single path or a list of paths.
+fn parse_includes(value: Value) -> Result<Vec<PathBuf>> {
+    match value {
+        Value::String(path) => Ok(vec![PathBuf::from(path)]),
+        Value::Array(paths) => paths
+            .into_iter()
+            .map(|path| match path {
+                Value::String(path) => Ok(PathBuf::from(path)),
+                _ => Err(Report::msg(format!(
+                    "Expected a path string, found `{path}`"
+                ))),
+            })
+            .collect(),
+        _ => Err(Report::msg(format!(
+            "Expected a path string or list of path strings, found `{value}`"
+        ))),
+    }
+}
+
+/// Deep-merges `overlay` into `base`.
+///
+/// Objects are merged key-by-key.
+/// Any other value in `overlay`, including arrays,
+/// replaces the value in `base` outright.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Creates a new empty directory to write test config files into.
+    fn test_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "ironbar-config-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        fs::create_dir_all(&dir).expect("to create test dir");
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, value: &Value) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, value.to_string()).expect("to write test file");
+        path
+    }
+
+    #[test]
+    fn test_merge_nested_objects() {
+        let mut base = json!({ "a": { "b": 1, "c": 2 }, "d": [1, 2] });
+        merge(&mut base, json!({ "a": { "c": 3 }, "d": [3] }));
+
+        assert_eq!(base, json!({ "a": { "b": 1, "c": 3 }, "d": [3] }));
+    }
+
+    #[test]
+    fn test_include_precedence() {
+        let dir = test_dir();
+
+        write(
+            &dir,
+            "first.json",
+            &json!({ "height": 10, "position": "top", "margin": { "top": 1 } }),
+        );
+        write(
+            &dir,
+            "second.json",
+            &json!({ "height": 20, "margin": { "bottom": 2 } }),
+        );
+        let main = write(
+            &dir,
+            "config.json",
+            &json!({ "include": ["first.json", "second.json"], "position": "left" }),
+        );
+
+        let value = load_value(&main, &mut vec![]).expect("to load");
+
+        assert_eq!(
+            value,
+            json!({
+                "height": 20,
+                "position": "left",
+                "margin": { "top": 1, "bottom": 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_nested_include() {
+        let dir = test_dir();
+        fs::create_dir_all(dir.join("modules")).expect("to create dir");
+
+        write(
+            &dir,
+            "modules/clock.json",
+            &json!({ "end": [{ "type": "clock" }] }),
+        );
+        write(
+            &dir,
+            "modules/all.json",
+            &json!({ "include": "clock.json" }),
+        );
+        let main = write(
+            &dir,
+            "config.json",
+            &json!({ "include": "modules/all.json" }),
+        );
+
+        let value = load_value(&main, &mut vec![]).expect("to load");
+        assert_eq!(value, json!({ "end": [{ "type": "clock" }] }));
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = test_dir();
+
+        write(&dir, "a.json", &json!({ "include": "b.json" }));
+        write(&dir, "b.json", &json!({ "include": "a.json" }));
+        let main = write(&dir, "config.json", &json!({ "include": "a.json" }));
+
+        let err = load_value(&main, &mut vec![]).expect_err("to detect cycle");
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn test_missing_include() {
+        let dir = test_dir();
+        let main = write(&dir, "config.json", &json!({ "include": "missing.json" }));
+
+        let err = load_value(&main, &mut vec![]).expect_err("to error");
+        assert!(err.to_string().contains("missing.json"));
+    }
+}
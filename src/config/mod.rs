@@ -1,7 +1,13 @@
 mod common;
 mod r#impl;
+mod loader;
+mod schema;
 mod truncate;
+mod validate;
+mod variables;
 
+#[cfg(feature = "caffeine")]
+use crate::modules::caffeine::CaffeineModule;
 #[cfg(feature = "cairo")]
 use crate::modules::cairo::CairoModule;
 #[cfg(feature = "clipboard")]
@@ -9,18 +15,36 @@ use crate::modules::clipboard::ClipboardModule;
 #[cfg(feature = "clock")]
 use crate::modules::clock::ClockModule;
 use crate::modules::custom::CustomModule;
+#[cfg(feature = "dbus")]
+use crate::modules::dbus::DbusModule;
 #[cfg(feature = "focused")]
 use crate::modules::focused::FocusedModule;
 use crate::modules::label::LabelModule;
 #[cfg(feature = "launcher")]
 use crate::modules::launcher::LauncherModule;
+#[cfg(feature = "mqtt")]
+use crate::modules::mqtt::MqttModule;
 #[cfg(feature = "music")]
 use crate::modules::music::MusicModule;
 #[cfg(feature = "notifications")]
 use crate::modules::notifications::NotificationsModule;
+#[cfg(feature = "power")]
+use crate::modules::power::PowerModule;
+#[cfg(feature = "quicksettings")]
+use crate::modules::quicksettings::QuickSettingsModule;
+#[cfg(feature = "screenshot")]
+use crate::modules::screenshot::ScreenshotModule;
 use crate::modules::script::ScriptModule;
+#[cfg(feature = "sink")]
+use crate::modules::sink::SinkModule;
 #[cfg(feature = "sys_info")]
 use crate::modules::sysinfo::SysInfoModule;
+#[cfg(feature = "taskbar")]
+use crate::modules::taskbar::TaskbarModule;
+#[cfg(feature = "temperature")]
+use crate::modules::temperature::TemperatureModule;
+#[cfg(feature = "timer")]
+use crate::modules::timer::TimerModule;
 #[cfg(feature = "tray")]
 use crate::modules::tray::TrayModule;
 #[cfg(feature = "upower")]
@@ -30,18 +54,40 @@ use crate::modules::volume::VolumeModule;
 #[cfg(feature = "workspaces")]
 use crate::modules::workspaces::WorkspacesModule;
 
-use crate::modules::{AnyModuleFactory, ModuleFactory, ModuleInfo};
+#[cfg(feature = "ipc")]
+use crate::ipc::ModuleBuilder;
+use crate::keybinds::KeybindsConfig;
+#[cfg(feature = "ipc")]
+use crate::modules::OwnedModuleInfo;
+use crate::modules::{
+    create_error_widget, error_hook, AnyModuleFactory, ModuleFactory, ModuleInfo,
+};
+use crate::script::{Script, ScriptInput};
 use cfg_if::cfg_if;
 use color_eyre::Result;
+use gtk::prelude::*;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
+#[cfg(feature = "ipc")]
+use std::rc::Rc;
+use tracing::error;
 
-pub use self::common::{CommonConfig, ModuleOrientation, TransitionType};
-pub use self::truncate::TruncateMode;
+pub use self::common::{
+    CommonConfig, ModuleOrientation, PopupAnchor, PopupTransition, RevealOnHover, TransitionType,
+};
+pub use self::loader::{find_file, load};
+pub use self::schema::schema;
+pub use self::truncate::{MarkupTruncate, TruncateMode};
+pub use self::validate::field_names;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ModuleConfig {
+    #[cfg(feature = "caffeine")]
+    Caffeine(Box<CaffeineModule>),
     #[cfg(feature = "cairo")]
     Cairo(Box<CairoModule>),
     #[cfg(feature = "clipboard")]
@@ -49,18 +95,36 @@ pub enum ModuleConfig {
     #[cfg(feature = "clock")]
     Clock(Box<ClockModule>),
     Custom(Box<CustomModule>),
+    #[cfg(feature = "dbus")]
+    Dbus(Box<DbusModule>),
     #[cfg(feature = "focused")]
     Focused(Box<FocusedModule>),
     Label(Box<LabelModule>),
     #[cfg(feature = "launcher")]
     Launcher(Box<LauncherModule>),
+    #[cfg(feature = "mqtt")]
+    Mqtt(Box<MqttModule>),
     #[cfg(feature = "music")]
     Music(Box<MusicModule>),
     #[cfg(feature = "notifications")]
     Notifications(Box<NotificationsModule>),
+    #[cfg(feature = "power")]
+    Power(Box<PowerModule>),
+    #[cfg(feature = "quicksettings")]
+    Quicksettings(Box<QuickSettingsModule>),
+    #[cfg(feature = "screenshot")]
+    Screenshot(Box<ScreenshotModule>),
     Script(Box<ScriptModule>),
+    #[cfg(feature = "sink")]
+    Sink(Box<SinkModule>),
     #[cfg(feature = "sys_info")]
     SysInfo(Box<SysInfoModule>),
+    #[cfg(feature = "taskbar")]
+    Taskbar(Box<TaskbarModule>),
+    #[cfg(feature = "temperature")]
+    Temperature(Box<TemperatureModule>),
+    #[cfg(feature = "timer")]
+    Timer(Box<TimerModule>),
     #[cfg(feature = "tray")]
     Tray(Box<TrayModule>),
     #[cfg(feature = "upower")]
@@ -72,12 +136,15 @@ pub enum ModuleConfig {
 }
 
 impl ModuleConfig {
+    /// Creates the module and adds it to the container.
+    ///
+    /// Returns the module's ID.
     pub fn create(
         self,
         module_factory: &AnyModuleFactory,
         container: &gtk::Box,
         info: &ModuleInfo,
-    ) -> Result<()> {
+    ) -> Result<usize> {
         macro_rules! create {
             ($module:expr) => {
                 module_factory.create(*$module, container, info)
@@ -85,6 +152,8 @@ impl ModuleConfig {
         }
 
         match self {
+            #[cfg(feature = "caffeine")]
+            Self::Caffeine(module) => create!(module),
             #[cfg(feature = "cairo")]
             Self::Cairo(module) => create!(module),
             #[cfg(feature = "clipboard")]
@@ -92,18 +161,36 @@ impl ModuleConfig {
             #[cfg(feature = "clock")]
             Self::Clock(module) => create!(module),
             Self::Custom(module) => create!(module),
+            #[cfg(feature = "dbus")]
+            Self::Dbus(module) => create!(module),
             #[cfg(feature = "focused")]
             Self::Focused(module) => create!(module),
             Self::Label(module) => create!(module),
             #[cfg(feature = "launcher")]
             Self::Launcher(module) => create!(module),
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(module) => create!(module),
             #[cfg(feature = "music")]
             Self::Music(module) => create!(module),
             #[cfg(feature = "notifications")]
             Self::Notifications(module) => create!(module),
+            #[cfg(feature = "power")]
+            Self::Power(module) => create!(module),
+            #[cfg(feature = "quicksettings")]
+            Self::Quicksettings(module) => create!(module),
+            #[cfg(feature = "screenshot")]
+            Self::Screenshot(module) => create!(module),
             Self::Script(module) => create!(module),
+            #[cfg(feature = "sink")]
+            Self::Sink(module) => create!(module),
             #[cfg(feature = "sys_info")]
             Self::SysInfo(module) => create!(module),
+            #[cfg(feature = "taskbar")]
+            Self::Taskbar(module) => create!(module),
+            #[cfg(feature = "temperature")]
+            Self::Temperature(module) => create!(module),
+            #[cfg(feature = "timer")]
+            Self::Timer(module) => create!(module),
             #[cfg(feature = "tray")]
             Self::Tray(module) => create!(module),
             #[cfg(feature = "upower")]
@@ -114,21 +201,126 @@ impl ModuleConfig {
             Self::Workspaces(module) => create!(module),
         }
     }
+
+    /// Gets the common options for the module.
+    pub fn common(&self) -> Option<&CommonConfig> {
+        match self {
+            #[cfg(feature = "caffeine")]
+            Self::Caffeine(module) => module.common.as_ref(),
+            #[cfg(feature = "cairo")]
+            Self::Cairo(module) => module.common.as_ref(),
+            #[cfg(feature = "clipboard")]
+            Self::Clipboard(module) => module.common.as_ref(),
+            #[cfg(feature = "clock")]
+            Self::Clock(module) => module.common.as_ref(),
+            Self::Custom(module) => module.common.as_ref(),
+            #[cfg(feature = "dbus")]
+            Self::Dbus(module) => module.common.as_ref(),
+            #[cfg(feature = "focused")]
+            Self::Focused(module) => module.common.as_ref(),
+            Self::Label(module) => module.common.as_ref(),
+            #[cfg(feature = "launcher")]
+            Self::Launcher(module) => module.common.as_ref(),
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(module) => module.common.as_ref(),
+            #[cfg(feature = "music")]
+            Self::Music(module) => module.common.as_ref(),
+            #[cfg(feature = "notifications")]
+            Self::Notifications(module) => module.common.as_ref(),
+            #[cfg(feature = "power")]
+            Self::Power(module) => module.common.as_ref(),
+            #[cfg(feature = "quicksettings")]
+            Self::Quicksettings(module) => module.common.as_ref(),
+            #[cfg(feature = "screenshot")]
+            Self::Screenshot(module) => module.common.as_ref(),
+            Self::Script(module) => module.common.as_ref(),
+            #[cfg(feature = "sink")]
+            Self::Sink(module) => module.common.as_ref(),
+            #[cfg(feature = "sys_info")]
+            Self::SysInfo(module) => module.common.as_ref(),
+            #[cfg(feature = "taskbar")]
+            Self::Taskbar(module) => module.common.as_ref(),
+            #[cfg(feature = "temperature")]
+            Self::Temperature(module) => module.common.as_ref(),
+            #[cfg(feature = "timer")]
+            Self::Timer(module) => module.common.as_ref(),
+            #[cfg(feature = "tray")]
+            Self::Tray(module) => module.common.as_ref(),
+            #[cfg(feature = "upower")]
+            Self::Upower(module) => module.common.as_ref(),
+            #[cfg(feature = "volume")]
+            Self::Volume(module) => module.common.as_ref(),
+            #[cfg(feature = "workspaces")]
+            Self::Workspaces(module) => module.common.as_ref(),
+        }
+    }
+
+    /// Creates the module and adds it to the container.
+    ///
+    /// If the module fails to load, the error is logged,
+    /// its `on_error` script is run,
+    /// and an error widget is added in its place
+    /// unless the bar is set to hide errors.
+    pub fn create_or_fallback(
+        self,
+        module_factory: &AnyModuleFactory,
+        container: &gtk::Box,
+        info: &ModuleInfo,
+    ) {
+        #[cfg(feature = "ipc")]
+        let builder = self.builder(module_factory, info);
+
+        let on_error = self.common().and_then(|common| common.on_error.clone());
+
+        match self.create(module_factory, container, info) {
+            #[cfg(feature = "ipc")]
+            Ok(id) => module_factory.ironbar().set_module_builder(id, builder),
+            #[cfg(not(feature = "ipc"))]
+            Ok(_) => {}
+            Err(err) => {
+                error!("{err:?}");
+
+                if let Some(script) = on_error {
+                    error_hook::run_hook(&Script::new_polling(script), err.to_string());
+                }
+
+                if !info.hide_on_error {
+                    container.add(&create_error_widget(&err));
+                }
+            }
+        }
+    }
+
+    /// Gets a function which creates this module again,
+    /// so that it can be reloaded.
+    #[cfg(feature = "ipc")]
+    fn builder(&self, module_factory: &AnyModuleFactory, info: &ModuleInfo) -> ModuleBuilder {
+        let config = self.clone();
+        let module_factory = module_factory.clone();
+        let info = OwnedModuleInfo::from(info);
+
+        ModuleBuilder(Rc::new(move |container: &gtk::Box| {
+            config
+                .clone()
+                .create_or_fallback(&module_factory, container, &info.as_info());
+        }))
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub enum BarEntryConfig {
     Single(BarConfig),
     Monitors(HashMap<String, MonitorConfig>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, JsonSchema)]
+#[schemars(untagged)]
 pub enum MonitorConfig {
     Single(BarConfig),
     Multiple(Vec<BarConfig>),
 }
 
-#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, JsonSchema, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum BarPosition {
     Top,
@@ -143,26 +335,103 @@ impl Default for BarPosition {
     }
 }
 
+/// Gap in pixels between each side of the bar and the screen edge.
+///
+/// Can be set as a single number to use the same gap on every side.
 #[derive(Debug, Default, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(from = "MarginInput")]
 pub struct MarginConfig {
-    #[serde(default)]
     pub bottom: i32,
-    #[serde(default)]
     pub left: i32,
-    #[serde(default)]
     pub right: i32,
-    #[serde(default)]
     pub top: i32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct BarConfig {
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum MarginInput {
+    All(i32),
+    Sides(MarginSides),
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct MarginSides {
+    #[serde(default)]
+    bottom: i32,
+    #[serde(default)]
+    left: i32,
     #[serde(default)]
+    right: i32,
+    #[serde(default)]
+    top: i32,
+}
+
+impl JsonSchema for MarginConfig {
+    fn schema_name() -> String {
+        "MarginConfig".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        MarginInput::json_schema(gen)
+    }
+}
+
+impl From<MarginInput> for MarginConfig {
+    fn from(input: MarginInput) -> Self {
+        match input {
+            MarginInput::All(margin) => Self {
+                bottom: margin,
+                left: margin,
+                right: margin,
+                top: margin,
+            },
+            MarginInput::Sides(MarginSides {
+                bottom,
+                left,
+                right,
+                top,
+            }) => Self {
+                bottom,
+                left,
+                right,
+                top,
+            },
+        }
+    }
+}
+
+/// How a section of the bar handles modules
+/// which do not fit within its maximum length.
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowMode {
+    /// Modules are hidden, lowest priority first, until the rest fit.
+    #[default]
+    Hide,
+    /// The section scrolls.
+    Scroll,
+    /// The last label in the section is shortened with an ellipsis.
+    Ellipsize,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct BarConfig {
+    /// The screen edge the bar is attached to.
+    #[serde(default, alias = "edge")]
     pub position: BarPosition,
     #[serde(default = "default_true")]
     pub anchor_to_edges: bool,
-    #[serde(default = "default_bar_height")]
+    /// Thickness of the bar in pixels.
+    /// The bar grows to fit its content unless `fixed_size` is set.
+    #[serde(default = "default_bar_height", alias = "width")]
     pub height: i32,
+    /// Whether to keep the bar at exactly `height`,
+    /// clipping any content which does not fit.
+    #[serde(default)]
+    pub fixed_size: bool,
+    /// Minimum length of the bar in pixels.
+    pub min_length: Option<i32>,
     #[serde(default)]
     pub margin: MarginConfig,
     pub name: Option<String>,
@@ -175,12 +444,32 @@ pub struct BarConfig {
     /// GTK icon theme to use.
     pub icon_theme: Option<String>,
 
+    /// Opacity of the bar, between `0.0` and `1.0`.
+    /// Bars configured under `monitors` inherit the top-level value unless set.
+    pub opacity: Option<f64>,
+
+    /// Whether to leave out modules which fail to load,
+    /// rather than showing an error icon in their place.
+    #[serde(default)]
+    pub hide_on_error: bool,
+
     pub start: Option<Vec<ModuleConfig>>,
     pub center: Option<Vec<ModuleConfig>>,
     pub end: Option<Vec<ModuleConfig>>,
 
     #[serde(default = "default_popup_gap")]
     pub popup_gap: i32,
+
+    /// Maximum length in pixels of each of the start, center and end sections.
+    pub max_length: Option<i32>,
+    /// How to handle modules which do not fit within `max_length`.
+    #[serde(default)]
+    pub overflow: OverflowMode,
+
+    /// Map of key combinations against commands,
+    /// active while the bar has keyboard focus.
+    #[serde(default)]
+    pub keybinds: KeybindsConfig,
 }
 
 impl Default for BarConfig {
@@ -206,11 +495,15 @@ impl Default for BarConfig {
         Self {
             position: BarPosition::default(),
             height: default_bar_height(),
+            fixed_size: false,
+            min_length: None,
             margin: MarginConfig::default(),
             name: None,
             start_hidden: None,
             autohide: None,
             icon_theme: None,
+            opacity: None,
+            hide_on_error: false,
             start: Some(vec![ModuleConfig::Label(
                 LabelModule::new("ℹ️ Using default config".to_string()).into(),
             )]),
@@ -218,16 +511,48 @@ impl Default for BarConfig {
             end,
             anchor_to_edges: default_true(),
             popup_gap: default_popup_gap(),
+            max_length: None,
+            overflow: OverflowMode::default(),
+            keybinds: KeybindsConfig::default(),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default)]
 pub struct Config {
     pub ironvar_defaults: Option<HashMap<Box<str>, String>>,
+    /// The maximum number of scaled images to keep in memory.
+    pub icon_cache_size: Option<usize>,
+    /// Icon to show in place of icons which are not in the theme.
+    /// A built-in icon is used if this is not in the theme either.
+    pub fallback_icon: Option<String>,
+    /// Whether to turn off popup animations and widget blinking on every bar.
+    #[serde(default)]
+    pub disable_animations: bool,
+
+    /// Time in milliseconds between refreshes
+    /// for modules which poll on an interval,
+    /// unless set on the module.
+    pub interval: Option<u64>,
+    /// Maximum time in milliseconds to randomly delay each poll by,
+    /// unless set on the module.
+    pub jitter: Option<u64>,
+
+    /// Commands to run in order once Ironbar starts,
+    /// and again after the config is reloaded.
+    #[serde(default)]
+    pub on_startup: Vec<ScriptInput>,
+    /// Commands to run in order when Ironbar exits,
+    /// and before the config is reloaded.
+    #[serde(default)]
+    pub on_shutdown: Vec<ScriptInput>,
 
     #[serde(flatten)]
     pub bar: BarConfig,
+    /// Extra bars to show alongside the top-level bar
+    /// on each output without a `monitors` entry.
+    #[serde(default)]
+    pub bars: Vec<BarConfig>,
     pub monitors: Option<HashMap<String, MonitorConfig>>,
 }
 
@@ -0,0 +1,114 @@
+use super::Config;
+use schemars::gen::SchemaSettings;
+use serde_json::Value;
+
+/// Builds a JSON Schema describing the config file,
+/// for editors to validate and autocomplete against.
+///
+/// The schema is derived from the config types,
+/// so it only includes the modules enabled in this build.
+/// Doc comments on config fields are included as descriptions.
+pub fn schema() -> Value {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Config>();
+
+    schema.schema.metadata().title = Some("Ironbar config".to_string());
+
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::validate::module_types;
+    use crate::modules::custom::Widget;
+
+    /// Gets the `type` of each variant of a tagged enum definition.
+    fn types(schema: &Value, definition: &str) -> Vec<String> {
+        variants(schema, definition)
+            .iter()
+            .filter_map(|variant| variant["properties"]["type"]["enum"][0].as_str())
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn variants<'a>(schema: &'a Value, definition: &str) -> &'a Vec<Value> {
+        schema["definitions"][definition]["oneOf"]
+            .as_array()
+            .unwrap()
+    }
+
+    /// Gets the properties of the variant of a tagged enum definition with the given `type`.
+    fn variant<'a>(schema: &'a Value, definition: &str, r#type: &str) -> &'a Value {
+        let variant = variants(schema, definition)
+            .iter()
+            .find(|variant| variant["properties"]["type"]["enum"][0] == r#type)
+            .unwrap();
+
+        &variant["properties"]
+    }
+
+    #[test]
+    fn test_schema_modules() {
+        let schema = schema();
+        let types = types(&schema, "ModuleConfig");
+
+        for r#type in module_types() {
+            assert!(types.iter().any(|t| t == r#type), "missing {type}");
+        }
+
+        for r#type in ["custom", "label", "script"] {
+            assert!(types.iter().any(|t| t == r#type), "missing {type}");
+        }
+
+        #[cfg(feature = "clock")]
+        assert!(types.iter().any(|t| t == "clock"));
+        #[cfg(not(feature = "clock"))]
+        assert!(!types.iter().any(|t| t == "clock"));
+    }
+
+    #[test]
+    fn test_schema_module_options() {
+        let schema = schema();
+
+        let script = variant(&schema, "ModuleConfig", "script");
+        assert!(script.get("cmd").is_some());
+        assert!(script.get("mode").is_some());
+
+        let custom = variant(&schema, "ModuleConfig", "custom");
+        assert_eq!(custom["bar"]["items"]["$ref"], "#/definitions/WidgetConfig");
+    }
+
+    #[test]
+    fn test_schema_widgets() {
+        let schema = schema();
+        assert_eq!(types(&schema, "Widget"), Widget::TYPES);
+
+        let label = variant(&schema, "Widget", "label");
+        assert!(label.get("label").is_some());
+
+        let r#box = variant(&schema, "Widget", "box");
+        assert_eq!(
+            r#box["widgets"]["items"]["$ref"],
+            "#/definitions/WidgetConfig"
+        );
+
+        let stack = variant(&schema, "Widget", "stack");
+        assert_eq!(stack["pages"]["items"]["$ref"], "#/definitions/StackPage");
+    }
+
+    #[test]
+    fn test_schema_common_options() {
+        let schema = schema();
+
+        let label = variant(&schema, "ModuleConfig", "label");
+        assert!(label.get("class").is_some());
+        assert!(label.get("show_if").is_some());
+        assert!(label.get("on_click_left").is_some());
+
+        let bar = &schema["properties"];
+        assert!(bar.get("position").is_some());
+        assert_eq!(bar["end"]["items"]["$ref"], "#/definitions/ModuleConfig");
+    }
+}
@@ -1,12 +1,14 @@
 use gtk::pango::EllipsizeMode as GtkEllipsizeMode;
 use gtk::prelude::*;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum EllipsizeMode {
     Start,
     Middle,
+    #[default]
     End,
 }
 
@@ -20,7 +22,7 @@ impl From<EllipsizeMode> for GtkEllipsizeMode {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy)]
 #[serde(untagged)]
 pub enum TruncateMode {
     Auto(EllipsizeMode),
@@ -64,3 +66,228 @@ impl TruncateMode {
         }
     }
 }
+
+/// Truncates text to a maximum number of characters
+/// before it is rendered,
+/// replacing the removed characters with an ellipsis.
+///
+/// Unlike [`TruncateMode`], this works on Pango markup,
+/// only counting visible characters and keeping all tags.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct MarkupTruncate {
+    /// Where to remove characters from.
+    #[serde(default)]
+    pub mode: EllipsizeMode,
+    /// The maximum number of visible characters, including the ellipsis.
+    pub max_length: usize,
+    /// The string to put in place of the removed characters.
+    #[serde(default = "default_ellipsis")]
+    pub ellipsis: String,
+}
+
+fn default_ellipsis() -> String {
+    String::from("…")
+}
+
+/// Part of a Pango markup string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkupToken<'a> {
+    /// An element tag, such as `<b>` or `</span>`.
+    Tag(&'a str),
+    /// A single visible character,
+    /// which may be written as an entity such as `&amp;`.
+    Char(&'a str),
+}
+
+/// Splits Pango markup into tags and visible characters.
+fn tokenize_markup(markup: &str) -> Vec<MarkupToken<'_>> {
+    let mut tokens = vec![];
+    let mut rest = markup;
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '<' => rest.find('>').map(|end| end + 1),
+            '&' => rest
+                .find(';')
+                .filter(|&end| {
+                    end > 1
+                        && rest[1..end]
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                })
+                .map(|end| end + 1),
+            _ => None,
+        }
+        .unwrap_or(c.len_utf8());
+
+        let (token, tail) = rest.split_at(len);
+
+        // an unclosed `<` is just a character
+        if c == '<' && token.ends_with('>') {
+            tokens.push(MarkupToken::Tag(token));
+        } else {
+            tokens.push(MarkupToken::Char(token));
+        }
+
+        rest = tail;
+    }
+
+    tokens
+}
+
+impl MarkupTruncate {
+    /// Truncates the markup, if it is longer than the max length.
+    pub fn truncate(&self, markup: &str) -> String {
+        let tokens = tokenize_markup(markup);
+
+        let length = tokens
+            .iter()
+            .filter(|token| matches!(token, MarkupToken::Char(_)))
+            .count();
+
+        if length <= self.max_length {
+            return markup.to_string();
+        }
+
+        let keep = self
+            .max_length
+            .saturating_sub(self.ellipsis.chars().count());
+
+        // range of visible characters to remove
+        let (from, to) = match self.mode {
+            EllipsizeMode::Start => (0, length - keep),
+            EllipsizeMode::Middle => {
+                let start = keep.div_ceil(2);
+                (start, length - (keep - start))
+            }
+            EllipsizeMode::End => (keep, length),
+        };
+
+        let ellipsis = glib::markup_escape_text(&self.ellipsis);
+
+        let mut output = String::with_capacity(markup.len());
+        let mut index = 0;
+
+        for token in tokens {
+            match token {
+                // tags are always kept, so the markup stays balanced
+                MarkupToken::Tag(tag) => output.push_str(tag),
+                MarkupToken::Char(c) => {
+                    if index == from {
+                        output.push_str(&ellipsis);
+                    }
+
+                    if !(from..to).contains(&index) {
+                        output.push_str(c);
+                    }
+
+                    index += 1;
+                }
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn truncate(mode: EllipsizeMode, max_length: usize, markup: &str) -> String {
+        MarkupTruncate {
+            mode,
+            max_length,
+            ellipsis: default_ellipsis(),
+        }
+        .truncate(markup)
+    }
+
+    #[test]
+    fn test_short_text_unchanged() {
+        assert_eq!(truncate(EllipsizeMode::End, 5, "hello"), "hello");
+        assert_eq!(
+            truncate(EllipsizeMode::End, 5, "<b>hello</b>"),
+            "<b>hello</b>"
+        );
+        assert_eq!(truncate(EllipsizeMode::End, 5, ""), "");
+    }
+
+    #[test]
+    fn test_truncate_end() {
+        assert_eq!(truncate(EllipsizeMode::End, 6, "hello world"), "hello…");
+    }
+
+    #[test]
+    fn test_truncate_start() {
+        assert_eq!(truncate(EllipsizeMode::Start, 6, "hello world"), "…world");
+    }
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(
+            truncate(EllipsizeMode::Middle, 10, "/home/user/music/song.mp3"),
+            "/home….mp3"
+        );
+        assert_eq!(truncate(EllipsizeMode::Middle, 6, "abcdefgh"), "abc…gh");
+    }
+
+    #[test]
+    fn test_custom_ellipsis() {
+        let truncate = MarkupTruncate {
+            mode: EllipsizeMode::End,
+            max_length: 8,
+            ellipsis: "...".to_string(),
+        };
+        assert_eq!(truncate.truncate("hello world"), "hello...");
+
+        // ellipsis is escaped as it is inserted into markup
+        let truncate = MarkupTruncate {
+            mode: EllipsizeMode::End,
+            max_length: 4,
+            ellipsis: "&".to_string(),
+        };
+        assert_eq!(truncate.truncate("hello"), "hel&amp;");
+    }
+
+    #[test]
+    fn test_ellipsis_longer_than_max() {
+        assert_eq!(truncate(EllipsizeMode::End, 1, "hello"), "…");
+        assert_eq!(truncate(EllipsizeMode::Middle, 0, "hello"), "…");
+    }
+
+    #[test]
+    fn test_markup_tags_kept() {
+        assert_eq!(
+            truncate(EllipsizeMode::End, 6, "<b>hello</b> world"),
+            "<b>hello</b>…"
+        );
+        assert_eq!(
+            truncate(EllipsizeMode::End, 4, "<span color='red'>hello</span>"),
+            "<span color='red'>hel…</span>"
+        );
+        assert_eq!(
+            truncate(EllipsizeMode::Start, 4, "<b>hello</b> <i>world</i>"),
+            "<b>…</b><i>rld</i>"
+        );
+        assert_eq!(
+            truncate(EllipsizeMode::Middle, 5, "<b>hello</b><i>world</i>"),
+            "<b>he…</b><i>ld</i>"
+        );
+    }
+
+    #[test]
+    fn test_markup_entities() {
+        assert_eq!(
+            truncate(EllipsizeMode::End, 4, "a &amp; b &lt; c"),
+            "a &amp;…"
+        );
+        assert_eq!(
+            truncate(EllipsizeMode::Start, 3, "x &#169;&gt;"),
+            "…&#169;&gt;"
+        );
+
+        // a lone ampersand is a single character
+        assert_eq!(truncate(EllipsizeMode::End, 3, "a & b"), "a …");
+    }
+}
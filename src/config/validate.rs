@@ -0,0 +1,697 @@
+use super::{BarConfig, CommonConfig, Config};
+#[cfg(feature = "caffeine")]
+use crate::modules::caffeine::CaffeineModule;
+#[cfg(feature = "cairo")]
+use crate::modules::cairo::CairoModule;
+#[cfg(feature = "clipboard")]
+use crate::modules::clipboard::ClipboardModule;
+#[cfg(feature = "clock")]
+use crate::modules::clock::ClockModule;
+use crate::modules::custom::{CustomModule, Widget};
+#[cfg(feature = "dbus")]
+use crate::modules::dbus::DbusModule;
+#[cfg(feature = "focused")]
+use crate::modules::focused::FocusedModule;
+use crate::modules::label::LabelModule;
+#[cfg(feature = "launcher")]
+use crate::modules::launcher::LauncherModule;
+#[cfg(feature = "mqtt")]
+use crate::modules::mqtt::MqttModule;
+#[cfg(feature = "music")]
+use crate::modules::music::MusicModule;
+#[cfg(feature = "notifications")]
+use crate::modules::notifications::NotificationsModule;
+#[cfg(feature = "power")]
+use crate::modules::power::PowerModule;
+#[cfg(feature = "quicksettings")]
+use crate::modules::quicksettings::QuickSettingsModule;
+#[cfg(feature = "screenshot")]
+use crate::modules::screenshot::ScreenshotModule;
+use crate::modules::script::ScriptModule;
+#[cfg(feature = "sink")]
+use crate::modules::sink::SinkModule;
+#[cfg(feature = "sys_info")]
+use crate::modules::sysinfo::SysInfoModule;
+#[cfg(feature = "taskbar")]
+use crate::modules::taskbar::TaskbarModule;
+#[cfg(feature = "temperature")]
+use crate::modules::temperature::TemperatureModule;
+#[cfg(feature = "timer")]
+use crate::modules::timer::TimerModule;
+#[cfg(feature = "tray")]
+use crate::modules::tray::TrayModule;
+#[cfg(feature = "upower")]
+use crate::modules::upower::UpowerModule;
+#[cfg(feature = "volume")]
+use crate::modules::volume::VolumeModule;
+#[cfg(feature = "workspaces")]
+use crate::modules::workspaces::WorkspacesModule;
+
+use super::ModuleConfig;
+use color_eyre::{Help, Report, Result};
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+
+/// Function getting the names of the options accepted by a config type.
+type FieldNames = fn() -> Vec<String>;
+
+/// Checks the raw config for unknown keys and invalid modules,
+/// collecting every problem into a single report
+/// rather than stopping at the first.
+pub fn validate(config: &Value) -> Result<()> {
+    let Value::Object(config) = config else {
+        // leave it to serde to report
+        return Ok(());
+    };
+
+    let mut validator = Validator::default();
+    validator.check_root(config);
+
+    if validator.errors.is_empty() {
+        return Ok(());
+    }
+
+    let count = validator.errors.len();
+    let errors = validator
+        .errors
+        .iter()
+        .map(|err| format!("  - {err}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Err(Report::msg(format!(
+        "Found {count} {} in config:\n{errors}",
+        if count == 1 { "error" } else { "errors" }
+    ))
+    .suggestion("Check the documentation for the list of valid options for each module"))
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<String>,
+}
+
+impl Validator {
+    fn check_root(&mut self, config: &Map<String, Value>) {
+        let mut fields = field_names::<Config>();
+        fields.extend(field_names::<BarConfig>());
+
+        self.check_keys(config, "", "bar", &fields);
+        self.check_bar_modules(config, "");
+
+        if let Some(Value::Array(bars)) = config.get("bars") {
+            self.check_bars(bars, "bars");
+        }
+
+        let Some(Value::Object(monitors)) = config.get("monitors") else {
+            return;
+        };
+
+        for (name, monitor) in monitors {
+            let path = format!("monitors.{name}");
+            match monitor {
+                Value::Object(bar) => self.check_bar(bar, &path),
+                Value::Array(bars) => self.check_bars(bars, &path),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_bars(&mut self, bars: &[Value], path: &str) {
+        for (i, bar) in bars.iter().enumerate() {
+            if let Value::Object(bar) = bar {
+                self.check_bar(bar, &format!("{path}[{i}]"));
+            }
+        }
+    }
+
+    fn check_bar(&mut self, bar: &Map<String, Value>, path: &str) {
+        self.check_keys(bar, path, "bar", &field_names::<BarConfig>());
+        self.check_bar_modules(bar, path);
+    }
+
+    fn check_bar_modules(&mut self, bar: &Map<String, Value>, path: &str) {
+        for side in ["start", "center", "end"] {
+            let Some(Value::Array(modules)) = bar.get(side) else {
+                continue;
+            };
+
+            for (i, module) in modules.iter().enumerate() {
+                self.check_module(module, &join(path, &format!("{side}[{i}]")));
+            }
+        }
+    }
+
+    /// Checks a module placed directly on a bar.
+    fn check_module(&mut self, module: &Value, path: &str) {
+        let Some((module, r#type)) = self.get_type(module, path) else {
+            return;
+        };
+
+        let Some(mut fields) = module_fields(r#type) else {
+            self.unknown_type(r#type, path, module_types());
+            return;
+        };
+
+        fields.extend(field_names::<CommonConfig>());
+
+        let count = self.errors.len();
+        self.check_keys(module, path, r#type, &fields);
+
+        if r#type == "custom" {
+            self.check_widget_lists(module, path, &["bar", "popup"]);
+            self.check_subpopups(module, path);
+        }
+
+        // only attempt a full parse if the keys are valid,
+        // to avoid reporting the same problem twice.
+        if self.errors.len() == count {
+            if let Err(err) = serde_json::from_value::<ModuleConfig>(Value::Object(module.clone()))
+            {
+                self.errors.push(format!("`{path}` ({type}): {err}"));
+            }
+        }
+    }
+
+    /// Checks an entry inside a custom module,
+    /// which may be either a widget or another module.
+    fn check_widget(&mut self, widget: &Value, path: &str) {
+        let Some((widget, r#type)) = self.get_type(widget, path) else {
+            return;
+        };
+
+        let Some(mut fields) = Widget::fields(r#type).or_else(|| module_fields(r#type)) else {
+            self.unknown_type(
+                r#type,
+                path,
+                Widget::TYPES.into_iter().chain(module_types()),
+            );
+            return;
+        };
+
+        fields.extend(field_names::<CommonConfig>());
+        self.check_keys(widget, path, r#type, &fields);
+
+        match r#type {
+            "box" | "button" => self.check_widget_lists(widget, path, &["widgets"]),
+            "stack" => self.check_stack_pages(widget, path),
+            "custom" => {
+                self.check_widget_lists(widget, path, &["bar", "popup"]);
+                self.check_subpopups(widget, path);
+            }
+            _ => {}
+        }
+    }
+
+    fn check_widget_lists(&mut self, object: &Map<String, Value>, path: &str, keys: &[&str]) {
+        for key in keys {
+            let Some(Value::Array(widgets)) = object.get(*key) else {
+                continue;
+            };
+
+            for (i, widget) in widgets.iter().enumerate() {
+                self.check_widget(widget, &format!("{path}.{key}[{i}]"));
+            }
+        }
+    }
+
+    /// Checks the widget list for each page of a stack widget.
+    fn check_stack_pages(&mut self, stack: &Map<String, Value>, path: &str) {
+        let Some(Value::Array(pages)) = stack.get("pages") else {
+            return;
+        };
+
+        for (i, page) in pages.iter().enumerate() {
+            if let Value::Object(page) = page {
+                self.check_widget_lists(page, &format!("{path}.pages[{i}]"), &["widgets"]);
+            }
+        }
+    }
+
+    /// Checks the widget list for each of a custom module's named subpopups.
+    fn check_subpopups(&mut self, object: &Map<String, Value>, path: &str) {
+        let Some(Value::Object(subpopups)) = object.get("subpopups") else {
+            return;
+        };
+
+        for (name, widgets) in subpopups {
+            let Value::Array(widgets) = widgets else {
+                continue;
+            };
+
+            for (i, widget) in widgets.iter().enumerate() {
+                self.check_widget(widget, &format!("{path}.subpopups.{name}[{i}]"));
+            }
+        }
+    }
+
+    /// Gets the `type` of a module or widget object,
+    /// recording an error if it is missing.
+    fn get_type<'a>(
+        &mut self,
+        value: &'a Value,
+        path: &str,
+    ) -> Option<(&'a Map<String, Value>, &'a str)> {
+        let Value::Object(object) = value else {
+            self.errors
+                .push(format!("`{path}`: expected an object, found `{value}`"));
+            return None;
+        };
+
+        if let Some(Value::String(r#type)) = object.get("type") {
+            Some((object, r#type.as_str()))
+        } else {
+            self.errors
+                .push(format!("`{path}`: missing or invalid `type`"));
+            None
+        }
+    }
+
+    fn unknown_type<'a>(
+        &mut self,
+        r#type: &str,
+        path: &str,
+        types: impl IntoIterator<Item = &'a str>,
+    ) {
+        let mut message = format!("`{path}`: unknown type `{type}`");
+        if let Some(suggestion) = suggest(r#type, types) {
+            message.push_str(&format!(" (did you mean `{suggestion}`?)"));
+        }
+
+        self.errors.push(message);
+    }
+
+    /// Records an error for each key in `object` not in `fields`,
+    /// along with the closest field if one exists.
+    fn check_keys(
+        &mut self,
+        object: &Map<String, Value>,
+        path: &str,
+        owner: &str,
+        fields: &[String],
+    ) {
+        let location = if path.is_empty() {
+            owner.to_string()
+        } else {
+            format!("`{path}` ({owner})")
+        };
+
+        for key in object.keys() {
+            if key == "type" || fields.contains(key) {
+                continue;
+            }
+
+            let mut message = format!("{location}: unknown key `{key}`");
+            if let Some(suggestion) = suggest(key, fields.iter().map(String::as_str)) {
+                message.push_str(&format!(" (did you mean `{suggestion}`?)"));
+            }
+
+            self.errors.push(message);
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Each module `type` enabled in this build,
+/// with the names of its options.
+const MODULES: &[(&str, FieldNames)] = &[
+    #[cfg(feature = "caffeine")]
+    ("caffeine", field_names::<CaffeineModule>),
+    #[cfg(feature = "cairo")]
+    ("cairo", field_names::<CairoModule>),
+    #[cfg(feature = "clipboard")]
+    ("clipboard", field_names::<ClipboardModule>),
+    #[cfg(feature = "clock")]
+    ("clock", field_names::<ClockModule>),
+    ("custom", field_names::<CustomModule>),
+    #[cfg(feature = "dbus")]
+    ("dbus", field_names::<DbusModule>),
+    #[cfg(feature = "focused")]
+    ("focused", field_names::<FocusedModule>),
+    ("label", field_names::<LabelModule>),
+    #[cfg(feature = "launcher")]
+    ("launcher", field_names::<LauncherModule>),
+    #[cfg(feature = "mqtt")]
+    ("mqtt", field_names::<MqttModule>),
+    #[cfg(feature = "music")]
+    ("music", field_names::<MusicModule>),
+    #[cfg(feature = "notifications")]
+    ("notifications", field_names::<NotificationsModule>),
+    #[cfg(feature = "power")]
+    ("power", field_names::<PowerModule>),
+    #[cfg(feature = "quicksettings")]
+    ("quicksettings", field_names::<QuickSettingsModule>),
+    #[cfg(feature = "screenshot")]
+    ("screenshot", field_names::<ScreenshotModule>),
+    ("script", field_names::<ScriptModule>),
+    #[cfg(feature = "sink")]
+    ("sink", field_names::<SinkModule>),
+    #[cfg(feature = "sys_info")]
+    ("sys_info", field_names::<SysInfoModule>),
+    #[cfg(feature = "taskbar")]
+    ("taskbar", field_names::<TaskbarModule>),
+    #[cfg(feature = "temperature")]
+    ("temperature", field_names::<TemperatureModule>),
+    #[cfg(feature = "timer")]
+    ("timer", field_names::<TimerModule>),
+    #[cfg(feature = "tray")]
+    ("tray", field_names::<TrayModule>),
+    #[cfg(feature = "upower")]
+    ("upower", field_names::<UpowerModule>),
+    #[cfg(feature = "volume")]
+    ("volume", field_names::<VolumeModule>),
+    #[cfg(feature = "workspaces")]
+    ("workspaces", field_names::<WorkspacesModule>),
+];
+
+/// Gets the `type` of each module enabled in this build.
+pub fn module_types() -> impl Iterator<Item = &'static str> {
+    MODULES.iter().map(|(r#type, _)| *r#type)
+}
+
+/// Gets the names of the options accepted by the module with the given `type`.
+///
+/// Returns `None` if the type does not exist.
+fn module_fields(r#type: &str) -> Option<Vec<String>> {
+    MODULES
+        .iter()
+        .find(|(name, _)| *name == r#type)
+        .map(|(_, fields)| fields())
+}
+
+/// Finds the name in `names` nearest to `key`.
+///
+/// Names within a Levenshtein distance of one are suggested.
+/// For longer keys, names within a distance of two are also suggested.
+fn suggest<'a>(key: &str, names: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = if key.chars().count() <= 4 { 1 } else { 2 };
+
+    names
+        .into_iter()
+        .map(|name| (levenshtein(key, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Gets the number of single character
+/// deletions, insertions or substitutions needed to turn `a` into `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Gets the names of the options accepted by `T`.
+///
+/// Names are taken from the schema for `T`,
+/// which includes the fields of flattened structs.
+/// Aliases are not part of the schema,
+/// so are added from the fields serde exposes where it does so.
+pub fn field_names<T: DeserializeOwned + JsonSchema>() -> Vec<String> {
+    let schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<T>();
+
+    let mut names = schema
+        .schema
+        .object
+        .map(|object| object.properties.into_keys().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    for field in struct_fields::<T>().unwrap_or_default() {
+        if !names.iter().any(|name| name == field) {
+            names.push((*field).to_string());
+        }
+    }
+
+    names
+}
+
+/// Gets the names of the fields of struct `T`, including aliases.
+///
+/// Returns `None` if `T` is not a struct,
+/// or contains a flattened field, as serde does not expose the fields of those.
+fn struct_fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldsDeserializer {
+        fields: &mut fields,
+    });
+    fields
+}
+
+#[derive(Debug)]
+struct ProbeError;
+
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "probe finished")
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: Display>(_msg: T) -> Self {
+        Self
+    }
+}
+
+struct FieldsDeserializer<'a> {
+    fields: &'a mut Option<&'static [&'static str]>,
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldsDeserializer<'a> {
+    type Error = ProbeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(ProbeError)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.fields = Some(fields);
+        Err(ProbeError)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn has_field<T: DeserializeOwned + JsonSchema>(name: &str) -> bool {
+        field_names::<T>().iter().any(|field| field == name)
+    }
+
+    #[test]
+    fn test_field_names() {
+        assert!(has_field::<CommonConfig>("on_click_left"));
+        assert!(!has_field::<CommonConfig>("on_click_lefty"));
+
+        // aliases
+        assert!(has_field::<CommonConfig>("on_click"));
+        assert!(has_field::<BarConfig>("edge"));
+
+        // flattened fields
+        assert!(has_field::<LabelModule>("label"));
+        assert!(has_field::<LabelModule>("class"));
+        assert!(has_field::<Config>("height"));
+
+        assert!(has_field::<BarConfig>("height"));
+        assert!(has_field::<Config>("monitors"));
+        assert!(!has_field::<BarConfig>("monitors"));
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let fields = struct_fields::<CommonConfig>().unwrap();
+        assert!(fields.contains(&"on_click_left"));
+        assert!(fields.contains(&"tooltip"));
+
+        assert!(struct_fields::<BarConfig>().unwrap().contains(&"height"));
+
+        // flattened fields are not exposed
+        assert!(struct_fields::<LabelModule>().is_none());
+        assert!(struct_fields::<String>().is_none());
+    }
+
+    #[test]
+    fn test_module_types() {
+        for r#type in module_types() {
+            assert!(module_fields(r#type).is_some());
+        }
+
+        assert!(module_types().any(|r#type| r#type == "custom"));
+        assert!(module_fields("nope").is_none());
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("format", "format"), 0);
+        assert_eq!(levenshtein("formatt", "format"), 1);
+        assert_eq!(levenshtein("fromat", "format"), 2);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest_nearest_field() {
+        let names = ["format", "formats", "tooltip", "icon"];
+
+        assert_eq!(suggest("formatt", names), Some("format"));
+        assert_eq!(suggest("fromat", names), Some("format"));
+        assert_eq!(suggest("toltipp", names), Some("tooltip"));
+        assert_eq!(suggest("ico", names), Some("icon"));
+        assert_eq!(suggest("ic", names), None);
+        assert_eq!(suggest("something", names), None);
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let config = json!({
+            "height": 30,
+            "margin": { "top": 2 },
+            "start": [{ "type": "label", "label": "hello", "class": "greeting" }],
+            "end": [{
+                "type": "custom",
+                "bar": [{ "type": "button", "label": "open", "on_click": "popup:toggle" }],
+                "popup": [{
+                    "type": "box",
+                    "widgets": [{ "type": "label", "label": "inside", "name": "inner" }]
+                }]
+            }],
+            "bars": [{ "position": "top", "start": [{ "type": "label", "label": "top" }] }],
+            "monitors": { "DP-1": [{ "position": "top" }] }
+        });
+
+        validate(&config).expect("to be valid");
+    }
+
+    #[test]
+    fn test_extra_bar_errors() {
+        let config = json!({
+            "bars": [{ "positon": "top", "start": [{ "type": "label", "lable": "hello" }] }]
+        });
+
+        let err = validate(&config).expect_err("to be invalid").to_string();
+
+        assert!(err.contains("Found 2 errors"));
+        assert!(err.contains("`bars[0]` (bar): unknown key `positon` (did you mean `position`?)"));
+        assert!(
+            err.contains("`bars[0].start[0]` (label): unknown key `lable` (did you mean `label`?)")
+        );
+    }
+
+    #[test]
+    fn test_aggregated_errors() {
+        let config = json!({
+            "heigth": 30,
+            "start": [
+                { "type": "label", "lable": "hello" },
+                { "type": "lable", "label": "hello" }
+            ],
+            "end": [{
+                "type": "custom",
+                "bar": [{ "type": "button", "lable": "open" }]
+            }],
+            "monitors": { "DP-1": { "positon": "top" } }
+        });
+
+        let err = validate(&config).expect_err("to be invalid").to_string();
+
+        assert!(err.contains("Found 5 errors"));
+        assert!(err.contains("bar: unknown key `heigth` (did you mean `height`?)"));
+        assert!(err.contains("`start[0]` (label): unknown key `lable` (did you mean `label`?)"));
+        assert!(err.contains("`start[1]`: unknown type `lable` (did you mean `label`?)"));
+        assert!(
+            err.contains("`end[0].bar[0]` (button): unknown key `lable` (did you mean `label`?)")
+        );
+        assert!(
+            err.contains("`monitors.DP-1` (bar): unknown key `positon` (did you mean `position`?)")
+        );
+    }
+
+    #[test]
+    fn test_subpopup_errors() {
+        let config = json!({
+            "end": [{
+                "type": "custom",
+                "bar": [{ "type": "button", "label": "open", "on_click": "popup:toggle" }],
+                "popup": [{ "type": "button", "label": "more", "on_click": "subpopup:open more" }],
+                "subpopups": {
+                    "more": [{ "type": "label", "lable": "inside" }]
+                }
+            }]
+        });
+
+        let err = validate(&config).expect_err("to be invalid").to_string();
+        assert!(err.contains(
+            "`end[0].subpopups.more[0]` (label): unknown key `lable` (did you mean `label`?)"
+        ));
+    }
+
+    #[test]
+    fn test_stack_page_errors() {
+        let config = json!({
+            "end": [{
+                "type": "custom",
+                "bar": [{
+                    "type": "stack",
+                    "pages": [{
+                        "name": "first",
+                        "widgets": [{ "type": "box", "widgets": [{ "type": "label", "lable": "inside" }] }]
+                    }]
+                }]
+            }]
+        });
+
+        let err = validate(&config).expect_err("to be invalid").to_string();
+        assert!(err.contains(
+            "`end[0].bar[0].pages[0].widgets[0].widgets[0]` (label): unknown key `lable` (did you mean `label`?)"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let config = json!({ "end": [{ "type": "label" }] });
+
+        let err = validate(&config).expect_err("to be invalid").to_string();
+        assert!(err.contains("`end[0]` (label): missing field `label`"));
+    }
+}
@@ -0,0 +1,330 @@
+use color_eyre::{Help, Report, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::env;
+
+/// Top-level key containing user-defined variables.
+const VARIABLES_KEY: &str = "variables";
+
+/// Expands `$name` and `${name}` references in every string value of the config,
+/// using the top-level `variables` table and falling back to the process environment.
+///
+/// `$$` is replaced with a literal `$`.
+/// Anything after a `$` that is not a valid variable name is left untouched,
+/// so shell syntax such as `$0` or `${0%.*}` continues to work.
+///
+/// To avoid interfering with existing scripts,
+/// substitution only takes place when a `variables` table is present.
+pub fn substitute(config: &mut Value) -> Result<()> {
+    let Value::Object(map) = config else {
+        return Ok(());
+    };
+
+    let Some(variables) = map.remove(VARIABLES_KEY) else {
+        return Ok(());
+    };
+
+    let Value::Object(variables) = variables else {
+        return Err(Report::msg(format!(
+            "Expected `{VARIABLES_KEY}` to be a map, found `{variables}`"
+        )));
+    };
+
+    let mut resolver = Resolver::new(variables);
+    resolver.walk(config, &mut String::new())
+}
+
+/// A single parsed piece of a string value.
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Static(&'a str),
+    Variable(&'a str),
+}
+
+struct Resolver {
+    variables: Map<String, Value>,
+    resolved: HashMap<String, Value>,
+    /// Names of the variables currently being resolved,
+    /// used to detect reference cycles.
+    stack: Vec<String>,
+}
+
+impl Resolver {
+    fn new(variables: Map<String, Value>) -> Self {
+        Self {
+            variables,
+            resolved: HashMap::new(),
+            stack: vec![],
+        }
+    }
+
+    /// Recursively expands all strings inside `value`.
+    /// `path` tracks the location inside the config for error messages.
+    fn walk(&mut self, value: &mut Value, path: &mut String) -> Result<()> {
+        match value {
+            Value::String(string) => {
+                *value = self
+                    .expand(string)
+                    .map_err(|err| err.note(format!("Found at `{path}`")))?;
+            }
+            Value::Array(values) => {
+                for (i, value) in values.iter_mut().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{i}]"));
+                    self.walk(value, path)?;
+                    path.truncate(len);
+                }
+            }
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                    self.walk(value, path)?;
+                    path.truncate(len);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Expands all variable references in `input`.
+    ///
+    /// If the input consists of a single reference to a non-string variable,
+    /// such as a number, that value is returned as-is
+    /// so it can be used for non-string options.
+    fn expand(&mut self, input: &str) -> Result<Value> {
+        let segments = parse(input);
+
+        if let [Segment::Variable(name)] = segments.as_slice() {
+            return self.resolve(name);
+        }
+
+        let mut output = String::with_capacity(input.len());
+        for segment in segments {
+            match segment {
+                Segment::Static(str) => output.push_str(str),
+                Segment::Variable(name) => match self.resolve(name)? {
+                    Value::String(value) => output.push_str(&value),
+                    Value::Number(value) => output.push_str(&value.to_string()),
+                    Value::Bool(value) => output.push_str(&value.to_string()),
+                    value => {
+                        return Err(Report::msg(format!(
+                            "Variable `${name}` cannot be embedded in a string as it is `{value}`"
+                        )))
+                    }
+                },
+            }
+        }
+
+        Ok(Value::String(output))
+    }
+
+    /// Gets the value of a variable,
+    /// checking the `variables` table first and the environment second.
+    fn resolve(&mut self, name: &str) -> Result<Value> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(value.clone());
+        }
+
+        if self.stack.iter().any(|n| n == name) {
+            let chain = self
+                .stack
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(name))
+                .map(|name| format!("${name}"))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(Report::msg(format!(
+                "Variable reference cycle detected: {chain}"
+            )));
+        }
+
+        let value = match self.variables.get(name).cloned() {
+            Some(Value::String(value)) => {
+                self.stack.push(name.to_string());
+                let value = self.expand(&value);
+                self.stack.pop();
+                value?
+            }
+            Some(value) => value,
+            None => match env::var(name) {
+                Ok(value) => Value::String(value),
+                Err(_) => {
+                    return Err(Report::msg(format!("Undefined variable `${name}`"))
+                        .suggestion(format!(
+                            "Define `{name}` in the `{VARIABLES_KEY}` table or the environment"
+                        ))
+                        .suggestion("Use `$$` to write a literal `$`"))
+                }
+            },
+        };
+
+        self.resolved.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+}
+
+/// Checks whether `name` is a valid variable name,
+/// following the same rules as shell variables.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits the input string into static and variable segments.
+fn parse(input: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+
+    let mut static_start = 0;
+    let mut i = 0;
+
+    let bytes = input.as_bytes();
+
+    while i < bytes.len() {
+        if bytes[i] != b'$' {
+            i += 1;
+            continue;
+        }
+
+        let rest = &input[i + 1..];
+
+        // (length of the whole reference including `$`, variable name or `None` for escape)
+        let reference = if rest.starts_with('$') {
+            Some((2, None))
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            braced
+                .find('}')
+                .map(|end| &braced[..end])
+                .filter(|name| is_valid_name(name))
+                .map(|name| (name.len() + 3, Some(name)))
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+
+            Some(&rest[..len])
+                .filter(|name| is_valid_name(name))
+                .map(|name| (len + 1, Some(name)))
+        };
+
+        match reference {
+            Some((len, name)) => {
+                if static_start < i {
+                    segments.push(Segment::Static(&input[static_start..i]));
+                }
+
+                match name {
+                    Some(name) => segments.push(Segment::Variable(name)),
+                    None => segments.push(Segment::Static("$")),
+                }
+
+                i += len;
+                static_start = i;
+            }
+            None => i += 1,
+        }
+    }
+
+    if static_start < input.len() {
+        segments.push(Segment::Static(&input[static_start..]));
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("a $b ${c}d"),
+            vec![
+                Segment::Static("a "),
+                Segment::Variable("b"),
+                Segment::Static(" "),
+                Segment::Variable("c"),
+                Segment::Static("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_shell_syntax_untouched() {
+        assert_eq!(
+            parse("echo $0 ${0%.*}"),
+            vec![Segment::Static("echo $0 ${0%.*}")]
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut config = json!({ "variables": {}, "cmd": "echo $$HOME $$" });
+        substitute(&mut config).expect("to substitute");
+
+        assert_eq!(config, json!({ "cmd": "echo $HOME $" }));
+    }
+
+    #[test]
+    fn test_nested_references() {
+        let mut config = json!({
+            "variables": { "base": "/home/me", "icons": "${base}/icons", "size": 32 },
+            "end": [{ "type": "image", "src": "$icons/foo.png", "size": "$size" }]
+        });
+        substitute(&mut config).expect("to substitute");
+
+        assert_eq!(
+            config,
+            json!({ "end": [{ "type": "image", "src": "/home/me/icons/foo.png", "size": 32 }] })
+        );
+    }
+
+    #[test]
+    fn test_env_fallback() {
+        env::set_var("IRONBAR_TEST_VARIABLE", "from-env");
+
+        let mut config = json!({
+            "variables": { "local": "from-table" },
+            "label": "${IRONBAR_TEST_VARIABLE} $local"
+        });
+        substitute(&mut config).expect("to substitute");
+
+        assert_eq!(config, json!({ "label": "from-env from-table" }));
+    }
+
+    #[test]
+    fn test_undefined() {
+        let mut config = json!({ "variables": {}, "label": "$ironbar_undefined_variable" });
+        let err = substitute(&mut config).expect_err("to error");
+
+        assert!(err.to_string().contains("ironbar_undefined_variable"));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut config = json!({ "variables": { "a": "$b", "b": "$a" }, "label": "$a" });
+        let err = substitute(&mut config).expect_err("to error");
+
+        assert!(err.to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn test_no_table() {
+        let mut config = json!({ "label": "$undefined" });
+        substitute(&mut config).expect("to skip substitution");
+
+        assert_eq!(config, json!({ "label": "$undefined" }));
+    }
+}
@@ -3,15 +3,16 @@ use crate::{glib_recv_mpsc, spawn, try_send};
 #[cfg(feature = "ipc")]
 use crate::{send_async, Ironbar};
 use cfg_if::cfg_if;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::mpsc;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum DynamicBool {
     /// Either a script or variable, to be determined.
     Unknown(String),
-    Script(Script),
+    Script(Box<Script>),
     #[cfg(feature = "ipc")]
     Variable(Box<str>),
 }
@@ -33,7 +34,7 @@ impl DynamicBool {
                     }
                 } else {
                     let script = Script::from(input.as_str());
-                    Self::Script(script)
+                    Self::Script(Box::new(script))
                 }
             }
             _ => self,
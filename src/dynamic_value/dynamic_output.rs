@@ -0,0 +1,146 @@
+use crate::script::{OutputStream, Script};
+use crate::{glib_recv_mpsc, spawn};
+#[cfg(feature = "ipc")]
+use crate::{send_async, Ironbar};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, error};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum DynamicOutput {
+    /// Either a script or variable, to be determined.
+    Unknown(String),
+    Script(Box<Script>),
+    #[cfg(feature = "ipc")]
+    Variable(Box<str>),
+}
+
+impl DynamicOutput {
+    /// Resolves a string input into a script or variable.
+    ///
+    /// Without the `ipc` feature, variables are unavailable
+    /// so the input is always treated as a script.
+    fn resolve(self) -> Self {
+        match self {
+            Self::Unknown(input) => {
+                #[cfg(feature = "ipc")]
+                if let Some(name) = input.strip_prefix('#') {
+                    return Self::Variable(name.into());
+                }
+
+                Self::Script(Box::new(Script::from(input.as_str())))
+            }
+            _ => self,
+        }
+    }
+
+    /// Calls `f` on the GTK thread with each new value.
+    ///
+    /// For scripts, this is each line of stdout.
+    /// For variables, this is the value each time the variable is set,
+    /// starting with its current value.
+    /// `None` means the variable has no value.
+    ///
+    /// If `visible` is set, scripts are deferred until it is first `true`,
+    /// and are paused again while it is `false`.
+    /// Variables are unaffected.
+    pub fn subscribe_when_visible<F>(self, visible: Option<watch::Receiver<bool>>, mut f: F)
+    where
+        F: FnMut(Option<String>) + 'static,
+    {
+        let value = self.resolve();
+
+        let (tx, rx) = mpsc::channel(128);
+
+        glib_recv_mpsc!(rx, val => f(val));
+
+        spawn(async move {
+            match value {
+                Self::Script(script) => {
+                    let callback = |stream: OutputStream, _success: bool| match stream {
+                        OutputStream::Stdout(out) => {
+                            // only the latest value is shown,
+                            // so values are dropped if the widget has fallen behind
+                            if tx.try_send(Some(out)).is_err() {
+                                debug!("Dropped script output, as the widget is behind");
+                            }
+                        }
+                        OutputStream::Stderr(err) => error!("{err:?}"),
+                    };
+
+                    match visible {
+                        Some(visible) => script.run_when_visible(None, visible, callback).await,
+                        None => script.run(None, callback).await,
+                    }
+                }
+                #[cfg(feature = "ipc")]
+                Self::Variable(name) => {
+                    let variable_manager = Ironbar::variable_manager();
+                    let mut rx = crate::write_lock!(variable_manager).subscribe(name);
+
+                    while let Ok(value) = rx.recv().await {
+                        send_async!(tx, value);
+                    }
+                }
+                Self::Unknown(_) => unreachable!(),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "ipc")]
+    fn test_resolve_variable() {
+        let output = DynamicOutput::Unknown("#volume".to_string()).resolve();
+        assert!(matches!(output, DynamicOutput::Variable(name) if &*name == "volume"));
+
+        let output = DynamicOutput::Unknown("echo 50".to_string()).resolve();
+        assert!(matches!(output, DynamicOutput::Script(_)));
+    }
+
+    #[test]
+    #[cfg(feature = "ipc")]
+    fn test_variable_update() {
+        use crate::gtk_helpers::with_gtk;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use std::time::{Duration, Instant};
+
+        with_gtk(|| {
+            let name = format!("ironbar_test_bind_{}", std::process::id());
+            let values = Rc::new(RefCell::new(vec![]));
+
+            {
+                let values = values.clone();
+                DynamicOutput::Unknown(format!("#{name}"))
+                    .subscribe_when_visible(None, move |value| values.borrow_mut().push(value));
+            }
+
+            let context = glib::MainContext::default();
+            let wait_for = |count: usize| {
+                let deadline = Instant::now() + Duration::from_secs(5);
+                while values.borrow().len() < count && Instant::now() < deadline {
+                    context.iteration(false);
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            };
+
+            // current value is sent on subscribe
+            wait_for(1);
+            assert_eq!(*values.borrow(), [None]);
+
+            crate::write_lock!(Ironbar::variable_manager())
+                .set(name.into(), "50".to_string())
+                .expect("to set variable");
+
+            wait_for(2);
+            assert_eq!(*values.borrow(), [None, Some("50".to_string())]);
+        });
+    }
+}
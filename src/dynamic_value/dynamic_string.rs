@@ -2,7 +2,7 @@ use crate::script::{OutputStream, Script};
 #[cfg(feature = "ipc")]
 use crate::Ironbar;
 use crate::{arc_mut, glib_recv_mpsc, lock, spawn, try_send};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
 /// A segment of a dynamic string,
 /// containing either a static string
@@ -10,7 +10,7 @@ use tokio::sync::mpsc;
 #[derive(Debug)]
 enum DynamicStringSegment {
     Static(String),
-    Script(Script),
+    Script(Box<Script>),
     #[cfg(feature = "ipc")]
     Variable(Box<str>),
 }
@@ -25,7 +25,18 @@ enum DynamicStringSegment {
 ///     label.set_markup(&string);
 /// });
 /// ```
-pub fn dynamic_string<F>(input: &str, mut f: F)
+pub fn dynamic_string<F>(input: &str, f: F)
+where
+    F: FnMut(String) + 'static,
+{
+    dynamic_string_when_visible(input, None, f);
+}
+
+/// Creates a new dynamic string as [`dynamic_string`] does.
+///
+/// If `visible` is set, scripts are deferred until it is first `true`,
+/// and are paused again while it is `false`.
+pub fn dynamic_string_when_visible<F>(input: &str, visible: Option<watch::Receiver<bool>>, mut f: F)
 where
     F: FnMut(String) + 'static,
 {
@@ -42,23 +53,27 @@ where
             DynamicStringSegment::Script(script) => {
                 let tx = tx.clone();
                 let label_parts = label_parts.clone();
+                let visible = visible.clone();
 
                 // insert blank value to preserve segment order
                 lock!(label_parts).push(String::new());
 
                 spawn(async move {
-                    script
-                        .run(None, |out, _| {
-                            if let OutputStream::Stdout(out) = out {
-                                let mut label_parts = lock!(label_parts);
-
-                                let _: String = std::mem::replace(&mut label_parts[i], out);
-
-                                let string = label_parts.join("");
-                                try_send!(tx, string);
-                            }
-                        })
-                        .await;
+                    let callback = |out: OutputStream, _: bool| {
+                        if let OutputStream::Stdout(out) = out {
+                            let mut label_parts = lock!(label_parts);
+
+                            let _: String = std::mem::replace(&mut label_parts[i], out);
+
+                            let string = label_parts.join("");
+                            try_send!(tx, string);
+                        }
+                    };
+
+                    match visible {
+                        Some(visible) => script.run_when_visible(None, visible, callback).await,
+                        None => script.run(None, callback).await,
+                    }
                 });
             }
             #[cfg(feature = "ipc")]
@@ -145,7 +160,7 @@ fn parse_script(chars: &[char]) -> (DynamicStringSegment, usize) {
     let len = str.chars().count() + SKIP_BRACKETS;
     let script = Script::from(str.as_str());
 
-    (DynamicStringSegment::Script(script), len)
+    (DynamicStringSegment::Script(Box::new(script)), len)
 }
 
 #[cfg(feature = "ipc")]
@@ -1,7 +1,9 @@
 #![doc = include_str!("../../docs/Dynamic values.md")]
 
 mod dynamic_bool;
+mod dynamic_output;
 mod dynamic_string;
 
 pub use dynamic_bool::DynamicBool;
-pub use dynamic_string::dynamic_string;
+pub use dynamic_output::DynamicOutput;
+pub use dynamic_string::{dynamic_string, dynamic_string_when_visible};
@@ -0,0 +1,268 @@
+use tracing::warn;
+
+/// Resolves each conditional expression in `format`,
+/// written as `{condition ? "then" : "otherwise"}`,
+/// to the branch chosen using the values of `tokens`.
+///
+/// The rest of the string is left untouched,
+/// so any tokens inside the chosen branch are replaced as normal afterwards.
+///
+/// Conditions can be a single value, which is true unless empty, `0` or `false`,
+/// or two values compared with `==` or `!=`.
+/// Values are either token names or quoted strings.
+///
+/// Invalid expressions are left as-is, and a warning is logged.
+pub fn resolve_conditionals(format: &str, tokens: &[(&str, &str)]) -> String {
+    let mut output = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        match expression_end(after) {
+            Some(end) if is_conditional(&after[..end]) => {
+                let expression = &after[..end];
+
+                match evaluate(expression, tokens) {
+                    Some(value) => output.push_str(&value),
+                    None => {
+                        warn!("Invalid format expression '{{{expression}}}', showing it as-is");
+                        output.push('{');
+                        output.push_str(expression);
+                        output.push('}');
+                    }
+                }
+
+                rest = &after[end + 1..];
+            }
+            // a plain token, which is left for the caller to replace
+            _ => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Gets the index of the `}` closing an expression,
+/// ignoring any inside quoted strings.
+fn expression_end(expression: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, char) in expression.char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '}' if !quoted => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Checks whether the expression is a conditional,
+/// rather than a plain token.
+fn is_conditional(expression: &str) -> bool {
+    tokenize(expression).map_or_else(
+        // anything malformed with a `?` outside of quotes was intended as one
+        || {
+            expression
+                .split('"')
+                .step_by(2)
+                .any(|part| part.contains('?'))
+        },
+        |tokens| tokens.contains(&Token::Question),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A token name, or a bare number.
+    Word(String),
+    /// A quoted string.
+    String(String),
+    Equal,
+    NotEqual,
+    Question,
+    Colon,
+}
+
+fn tokenize(expression: &str) -> Option<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        let token = match char {
+            _ if char.is_whitespace() => continue,
+            '?' => Token::Question,
+            ':' => Token::Colon,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Equal,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEqual,
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => string.push(chars.next()?),
+                        char => string.push(char),
+                    }
+                }
+                Token::String(string)
+            }
+            _ if is_word_char(char) => {
+                let mut word = char.to_string();
+                while let Some(char) = chars.next_if(|&char| is_word_char(char)) {
+                    word.push(char);
+                }
+                Token::Word(word)
+            }
+            _ => return None,
+        };
+
+        tokens.push(token);
+    }
+
+    Some(tokens)
+}
+
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '_' | '-' | '.')
+}
+
+/// Evaluates a conditional expression,
+/// returning `None` if it is invalid.
+fn evaluate(expression: &str, tokens: &[(&str, &str)]) -> Option<String> {
+    let parts = tokenize(expression)?;
+
+    let question = parts.iter().position(|token| token == &Token::Question)?;
+    let (condition, branches) = parts.split_at(question);
+
+    let [_, then, Token::Colon, otherwise] = branches else {
+        return None;
+    };
+
+    let condition = match condition {
+        [value] => is_truthy(&resolve(value, tokens)?),
+        [left, Token::Equal, right] => is_equal(&resolve(left, tokens)?, &resolve(right, tokens)?),
+        [left, Token::NotEqual, right] => {
+            !is_equal(&resolve(left, tokens)?, &resolve(right, tokens)?)
+        }
+        _ => return None,
+    };
+
+    resolve(if condition { then } else { otherwise }, tokens)
+}
+
+/// Gets the value of a token name, number or string.
+/// Unknown token names are invalid.
+fn resolve(token: &Token, tokens: &[(&str, &str)]) -> Option<String> {
+    match token {
+        Token::String(string) => Some(string.clone()),
+        Token::Word(word) => tokens
+            .iter()
+            .find(|(name, _)| name == word)
+            .map(|(_, value)| (*value).to_string())
+            .or_else(|| word.parse::<f64>().is_ok().then(|| word.clone())),
+        _ => None,
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+/// Compares values as numbers if both are numeric,
+/// so that `50` and `50.0` are equal.
+fn is_equal(left: &str, right: &str) -> bool {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(left), Ok(right)) => (left - right).abs() < f64::EPSILON,
+        _ => left == right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKENS: &[(&str, &str)] = &[
+        ("muted", "true"),
+        ("unmuted", "false"),
+        ("volume", "50"),
+        ("empty", ""),
+        ("state", "charging"),
+    ];
+
+    fn render(format: &str) -> String {
+        resolve_conditionals(format, TOKENS)
+    }
+
+    #[test]
+    fn test_truthy_branches() {
+        assert_eq!(render(r#"{muted ? "" : "{volume}%"}"#), "");
+        assert_eq!(render(r#"{unmuted ? "" : "{volume}%"}"#), "{volume}%");
+        assert_eq!(render(r#"{volume ? "on" : "off"}"#), "on");
+        assert_eq!(render(r#"{empty ? "on" : "off"}"#), "off");
+        assert_eq!(render(r#"{0 ? "on" : "off"}"#), "off");
+    }
+
+    #[test]
+    fn test_equality() {
+        assert_eq!(render(r#"{state == "charging" ? "+" : "-"}"#), "+");
+        assert_eq!(render(r#"{state != "charging" ? "+" : "-"}"#), "-");
+        assert_eq!(render(r#"{volume == 50.0 ? "half" : volume}"#), "half");
+        assert_eq!(render(r#"{volume == 100 ? "full" : volume}"#), "50");
+        assert_eq!(
+            render(r#"{muted==unmuted?"same":"different"}"#),
+            "different"
+        );
+    }
+
+    #[test]
+    fn test_surrounding_text() {
+        assert_eq!(
+            render(r#"{icon} {muted ? "muted" : "{volume}%"} on {name}"#),
+            "{icon} muted on {name}"
+        );
+        assert_eq!(render(r#"{unmuted ? "a" : "b"}{muted ? "c" : "d"}"#), "bc");
+        assert_eq!(render("no expressions {here}"), "no expressions {here}");
+    }
+
+    #[test]
+    fn test_quotes_in_branches() {
+        assert_eq!(render(r#"{muted ? "a \"b\" }" : ""}"#), r#"a "b" }"#);
+        assert_eq!(render(r#"{muted ? "a:b?c" : ""}"#), "a:b?c");
+    }
+
+    #[test]
+    fn test_malformed_fallback() {
+        for format in [
+            r#"{muted ? "a"}"#,
+            r#"{muted ? "a" : "b" : "c"}"#,
+            r#"{unknown ? "a" : "b"}"#,
+            r#"{muted = "x" ? "a" : "b"}"#,
+            r#"{muted ? "a : "b"}"#,
+            r#"{? "a" : "b"}"#,
+            r#"{muted & volume ? "a" : "b"}"#,
+        ] {
+            assert_eq!(render(format), format, "{format}");
+        }
+
+        // the rest of the string is still resolved
+        assert_eq!(
+            render(r#"{muted ? "a"} {muted ? "b" : "c"}"#),
+            r#"{muted ? "a"} b"#
+        );
+    }
+
+    #[test]
+    fn test_unclosed() {
+        assert_eq!(render(r#"{muted ? "a" : "b""#), r#"{muted ? "a" : "b""#);
+    }
+}
@@ -1,6 +1,10 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
 use glib::IsA;
 use gtk::prelude::*;
-use gtk::{Orientation, Widget};
+use gtk::{cairo, gdk_pixbuf, Orientation, Widget};
+use tokio::sync::watch;
 
 /// Represents a widget's size
 /// and location relative to the bar's start edge.
@@ -18,6 +22,9 @@ pub struct WidgetGeometry {
 pub trait IronbarGtkExt {
     /// Adds a new CSS class to the widget.
     fn add_class(&self, class: &str);
+    /// Removes each CSS class in `old` that is not in `new`,
+    /// and adds each class in `new` that is not in `old`.
+    fn replace_classes(&self, old: &[String], new: &[String]);
     /// Gets the geometry for the widget
     fn geometry(&self, orientation: Orientation) -> WidgetGeometry;
 
@@ -32,6 +39,18 @@ impl<W: IsA<Widget>> IronbarGtkExt for W {
         self.style_context().add_class(class);
     }
 
+    fn replace_classes(&self, old: &[String], new: &[String]) {
+        let style = self.style_context();
+
+        for class in old.iter().filter(|class| !new.contains(class)) {
+            style.remove_class(class);
+        }
+
+        for class in new.iter().filter(|class| !old.contains(class)) {
+            style.add_class(class);
+        }
+    }
+
     fn geometry(&self, orientation: Orientation) -> WidgetGeometry {
         let allocation = self.allocation();
 
@@ -75,3 +94,433 @@ impl<W: IsA<Widget>> IronbarGtkExt for W {
         unsafe { self.set_data(key, value) }
     }
 }
+
+/// Checks that `markup` is valid Pango markup,
+/// escaping it to be shown as plain text if not.
+///
+/// GTK silently drops tooltips with invalid markup,
+/// so this makes sure something is still shown.
+pub fn valid_markup(markup: &str) -> String {
+    match gtk::pango::parse_markup(markup, '\0') {
+        Ok(_) => markup.to_string(),
+        Err(err) => {
+            tracing::warn!("Invalid markup '{markup}': {err}");
+            glib::markup_escape_text(markup).to_string()
+        }
+    }
+}
+
+/// Replaces each `{token}` in a Pango markup format string with its value,
+/// escaping the values so that they are always shown as plain text.
+///
+/// If the format itself is not valid markup,
+/// it is escaped to be shown as plain text as per [`valid_markup`].
+/// Either way, each value is escaped exactly once.
+///
+/// Tokens which `value` returns `None` for are left as-is.
+pub fn format_markup(format: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let format = valid_markup(format);
+
+    let mut markup = String::with_capacity(format.len());
+    let mut rest = format.as_str();
+
+    while let Some(start) = rest.find('{') {
+        markup.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let replacement = rest
+            .find('}')
+            .map(|end| &rest[..end])
+            .filter(|token| {
+                !token.is_empty()
+                    && token
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            })
+            .and_then(|token| value(token).map(|value| (token.len(), value)));
+
+        match replacement {
+            Some((len, value)) => {
+                markup.push_str(&glib::markup_escape_text(&value));
+                rest = &rest[len + 1..];
+            }
+            None => markup.push('{'),
+        }
+    }
+
+    markup.push_str(rest);
+    markup
+}
+
+/// Rotates the text and icons inside a widget counter-clockwise
+/// by `angle` degrees, which must be a multiple of 90.
+///
+/// Labels are rotated using their Pango angle.
+/// Images are rotated now and whenever their contents are replaced.
+/// Containers have each of their children rotated,
+/// including any children added later.
+///
+/// Returns `false` if there was nothing to rotate.
+pub fn rotate_widget(widget: &Widget, angle: u16) -> bool {
+    if let Some(label) = widget.downcast_ref::<gtk::Label>() {
+        label.set_angle(f64::from(angle));
+        true
+    } else if let Some(image) = widget.downcast_ref::<gtk::Image>() {
+        rotate_image(image, angle);
+        true
+    } else if let Some(container) = widget.downcast_ref::<gtk::Container>() {
+        container.connect_add(move |_, child| {
+            rotate_widget(child, angle);
+        });
+
+        // every child is rotated, so this must not stop at the first
+        let mut rotated = false;
+        for child in container.children() {
+            rotated |= rotate_widget(&child, angle);
+        }
+        rotated
+    } else {
+        false
+    }
+}
+
+fn rotate_image(image: &gtk::Image, angle: u16) {
+    let rotate = move |image: &gtk::Image| {
+        if let Some(pixbuf) = image.pixbuf() {
+            let rotation = match angle {
+                90 => gdk_pixbuf::PixbufRotation::Counterclockwise,
+                180 => gdk_pixbuf::PixbufRotation::Upsidedown,
+                270 => gdk_pixbuf::PixbufRotation::Clockwise,
+                _ => return,
+            };
+
+            if let Some(pixbuf) = pixbuf.rotate_simple(rotation) {
+                image.set_from_pixbuf(Some(&pixbuf));
+            }
+        } else if let Some(surface) = image.property::<Option<cairo::Surface>>("surface") {
+            if let Some(surface) = rotate_surface(&surface, angle) {
+                image.set_from_surface(Some(&surface));
+            }
+        }
+    };
+
+    rotate(image);
+
+    // setting the rotated contents notifies again,
+    // so those changes are skipped
+    let rotating = Rc::new(Cell::new(false));
+    for property in ["pixbuf", "surface"] {
+        let rotating = rotating.clone();
+        image.connect_notify_local(Some(property), move |image, _| {
+            if !rotating.replace(true) {
+                rotate(image);
+                rotating.set(false);
+            }
+        });
+    }
+}
+
+/// Paints a surface onto a new surface, rotated counter-clockwise.
+/// The device scale is kept, so HiDPI images stay sharp.
+fn rotate_surface(surface: &cairo::Surface, angle: u16) -> Option<cairo::Surface> {
+    let source = cairo::ImageSurface::try_from(surface.clone()).ok()?;
+    let (scale_x, scale_y) = source.device_scale();
+
+    let (width, height) = (source.width(), source.height());
+    let (rotated_width, rotated_height) = if angle.is_multiple_of(180) {
+        (width, height)
+    } else {
+        (height, width)
+    };
+
+    let rotated =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, rotated_width, rotated_height).ok()?;
+    rotated.set_device_scale(scale_x, scale_y);
+
+    {
+        let cr = cairo::Context::new(&rotated).ok()?;
+        cr.translate(
+            f64::from(rotated_width) / scale_x / 2.0,
+            f64::from(rotated_height) / scale_y / 2.0,
+        );
+        // cairo rotates clockwise, whereas pango angles are counter-clockwise
+        cr.rotate(-f64::from(angle).to_radians());
+        cr.set_source_surface(
+            &source,
+            -f64::from(width) / scale_x / 2.0,
+            -f64::from(height) / scale_y / 2.0,
+        )
+        .ok()?;
+        cr.paint().ok()?;
+    }
+
+    Some((*rotated).clone())
+}
+
+/// Tracks whether the widget is mapped,
+/// which is while it and all of its parents are shown.
+pub fn watch_mapped(widget: &impl IsA<Widget>) -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(widget.is_mapped());
+    let tx = Rc::new(tx);
+
+    {
+        let tx = tx.clone();
+        widget.connect_map(move |_| {
+            tx.send_replace(true);
+        });
+    }
+
+    widget.connect_unmap(move |_| {
+        tx.send_replace(false);
+    });
+
+    rx
+}
+
+/// Tracks whether the pointer is over the widget.
+///
+/// The widget must receive enter and leave events,
+/// such as an `EventBox`.
+pub fn watch_hovered(widget: &impl IsA<Widget>) -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    let tx = Rc::new(tx);
+
+    {
+        let tx = tx.clone();
+        widget.connect_enter_notify_event(move |_, _| {
+            tx.send_replace(true);
+            glib::Propagation::Proceed
+        });
+    }
+
+    widget.connect_leave_notify_event(move |_, event| {
+        // moving onto a child widget is still hovering this one
+        if event.detail() != gtk::gdk::NotifyType::Inferior {
+            tx.send_replace(false);
+        }
+        glib::Propagation::Proceed
+    });
+
+    rx
+}
+
+/// Removes `old` from `container`, then calls `add` to add its replacement.
+///
+/// Any widgets added are moved into the position `old` was in,
+/// keeping their order.
+pub fn replace_child(container: &gtk::Box, old: &impl IsA<Widget>, add: impl FnOnce(&gtk::Box)) {
+    let old = old.upcast_ref::<Widget>();
+    let position = container
+        .children()
+        .iter()
+        .position(|child| child == old)
+        .unwrap_or(container.children().len());
+
+    container.remove(old);
+
+    let count = container.children().len();
+    add(container);
+
+    for (offset, child) in container.children().iter().skip(count).enumerate() {
+        container.reorder_child(child, (position + offset) as i32);
+    }
+}
+
+/// Runs `f` on the thread GTK was initialized on,
+/// returning its result.
+///
+/// GTK can only be used from a single thread,
+/// whereas each test runs on its own.
+/// Returns `None` if GTK could not be initialized,
+/// for example when there is no display available.
+#[cfg(test)]
+pub fn with_gtk<F, T>(f: F) -> Option<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{mpsc, Mutex, OnceLock};
+    use std::thread;
+
+    type Job = Box<dyn FnOnce() + Send>;
+
+    static GTK_THREAD: OnceLock<Option<Mutex<mpsc::Sender<Job>>>> = OnceLock::new();
+
+    let jobs = GTK_THREAD
+        .get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<Job>();
+            let (init_tx, init_rx) = mpsc::channel();
+
+            thread::spawn(move || {
+                let initialized = gtk::init().is_ok();
+                init_tx.send(initialized).ok();
+
+                if initialized {
+                    for job in rx {
+                        job();
+                    }
+                }
+            });
+
+            init_rx.recv().unwrap_or_default().then(|| Mutex::new(tx))
+        })
+        .as_ref()?;
+
+    let (result_tx, result_rx) = mpsc::channel();
+
+    jobs.lock()
+        .expect("to get lock")
+        .send(Box::new(move || {
+            result_tx
+                .send(panic::catch_unwind(AssertUnwindSafe(f)))
+                .ok();
+        }))
+        .ok()?;
+
+    match result_rx.recv().ok()? {
+        Ok(result) => Some(result),
+        Err(err) => panic::resume_unwind(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_markup_kept() {
+        assert_eq!(
+            valid_markup("<b>Title</b> &amp; more"),
+            "<b>Title</b> &amp; more"
+        );
+    }
+
+    #[test]
+    fn test_invalid_markup_escaped() {
+        assert_eq!(valid_markup("<b>Unclosed"), "&lt;b&gt;Unclosed");
+        assert_eq!(valid_markup("Rock & Roll"), "Rock &amp; Roll");
+    }
+
+    #[test]
+    fn test_format_markup() {
+        let value = |token: &str| match token {
+            "title" => Some("Rock & <Roll>".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            format_markup("<b>{title}</b> {other} {", value),
+            "<b>Rock &amp; &lt;Roll&gt;</b> {other} {"
+        );
+
+        // values are never treated as tokens
+        assert_eq!(
+            format_markup("{title}", |_| Some("{title}".to_string())),
+            "{title}"
+        );
+    }
+
+    #[test]
+    fn test_format_markup_invalid_escaped_once() {
+        let value = |_: &str| Some("Rock & Roll".to_string());
+
+        assert_eq!(
+            format_markup("<b>{title}", value),
+            "&lt;b&gt;Rock &amp; Roll"
+        );
+    }
+
+    #[test]
+    fn test_rotate_widget() {
+        with_gtk(|| {
+            let label = gtk::Label::new(Some("text"));
+            let button = gtk::Button::new();
+            button.add(&label);
+
+            assert!(rotate_widget(button.upcast_ref(), 90));
+            assert_eq!(label.angle(), 90.0);
+
+            // nothing inside to rotate
+            assert!(!rotate_widget(
+                gtk::Separator::new(Orientation::Horizontal).upcast_ref(),
+                90
+            ));
+            assert!(!rotate_widget(
+                gtk::Box::new(Orientation::Horizontal, 0).upcast_ref(),
+                90
+            ));
+        });
+    }
+
+    #[test]
+    fn test_rotate_widget_added_later() {
+        with_gtk(|| {
+            let container = gtk::Box::new(Orientation::Horizontal, 0);
+            rotate_widget(container.upcast_ref(), 270);
+
+            let label = gtk::Label::new(Some("text"));
+            container.add(&label);
+
+            assert_eq!(label.angle(), 270.0);
+        });
+    }
+
+    fn labels(names: &[&str]) -> gtk::Box {
+        let container = gtk::Box::new(Orientation::Horizontal, 0);
+        for name in names {
+            container.add(&gtk::Label::new(Some(name)));
+        }
+        container
+    }
+
+    fn names(container: &gtk::Box) -> Vec<String> {
+        container
+            .children()
+            .into_iter()
+            .map(|child| child.downcast::<gtk::Label>().unwrap().label().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_replace_child_keeps_position() {
+        with_gtk(|| {
+            let container = labels(&["first", "second", "third"]);
+            let old = container.children()[1].clone();
+
+            replace_child(&container, &old, |container| {
+                container.add(&gtk::Label::new(Some("new")));
+            });
+
+            assert_eq!(names(&container), ["first", "new", "third"]);
+            assert!(old.parent().is_none());
+        });
+    }
+
+    #[test]
+    fn test_replace_child_multiple() {
+        with_gtk(|| {
+            let container = labels(&["first", "second"]);
+            let old = container.children()[0].clone();
+
+            replace_child(&container, &old, |container| {
+                container.add(&gtk::Label::new(Some("a")));
+                container.add(&gtk::Label::new(Some("b")));
+            });
+
+            assert_eq!(names(&container), ["a", "b", "second"]);
+        });
+    }
+
+    #[test]
+    fn test_replace_child_nothing_added() {
+        with_gtk(|| {
+            let container = labels(&["first", "second"]);
+            let old = container.children()[1].clone();
+
+            replace_child(&container, &old, |_| {});
+
+            assert_eq!(names(&container), ["first"]);
+        });
+    }
+}
@@ -0,0 +1,112 @@
+use crate::script::{Script, ScriptInput};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Maximum time each startup command may run for before it is killed,
+/// so that a hung command cannot stop the remaining commands from running.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum time each shutdown command may run for before it is killed,
+/// so that a hung command cannot stop Ironbar from exiting.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Converts the configured hook commands into scripts.
+pub fn scripts(inputs: &[ScriptInput]) -> Vec<Script> {
+    inputs.iter().cloned().map(Script::from).collect()
+}
+
+/// Runs each script in turn,
+/// waiting for it to exit before starting the next.
+///
+/// Scripts which fail or time out are logged,
+/// and do not stop the remaining scripts from running.
+pub async fn run_in_order(scripts: &[Script], limit: Option<Duration>) {
+    for script in scripts {
+        debug!("Running hook: '{}'", script.cmd);
+
+        match script.run_to_completion(limit).await {
+            Ok(status) if !status.success() => {
+                error!("Hook '{}' failed with {status}", script.cmd);
+            }
+            Ok(_) => {}
+            Err(err) => error!("{err:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Instant;
+
+    fn script(cmd: &str) -> Script {
+        Script::from(ScriptInput::String(cmd.to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_runs_in_order() {
+        let path = std::env::temp_dir().join(format!("ironbar-hooks-{}", std::process::id()));
+
+        // the first script is slowest, so would finish last if not waited on
+        let scripts = [
+            script(&format!("sleep 0.2; echo 1 >> {}", path.display())),
+            script(&format!("sleep 0.1; echo 2 >> {}", path.display())),
+            script(&format!("echo 3 >> {}", path.display())),
+        ];
+
+        run_in_order(&scripts, None).await;
+
+        let output = fs::read_to_string(&path).expect("to read output");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(output, "1\n2\n3\n");
+    }
+
+    #[tokio::test]
+    async fn test_failure_does_not_stop_others() {
+        let path = std::env::temp_dir().join(format!("ironbar-hooks-fail-{}", std::process::id()));
+
+        let scripts = [
+            script("exit 1"),
+            script(&format!("echo ok > {}", path.display())),
+        ];
+
+        run_in_order(&scripts, None).await;
+
+        let output = fs::read_to_string(&path).expect("to read output");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(output, "ok\n");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_timeout() {
+        let path =
+            std::env::temp_dir().join(format!("ironbar-hooks-timeout-{}", std::process::id()));
+
+        let scripts = [
+            script("sleep 10"),
+            script(&format!("echo ok > {}", path.display())),
+        ];
+
+        let start = Instant::now();
+        run_in_order(&scripts, Some(Duration::from_millis(100))).await;
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // the hung script is killed, and the next still runs
+        let output = fs::read_to_string(&path).expect("to read output");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(output, "ok\n");
+    }
+
+    #[tokio::test]
+    async fn test_background_process_not_waited_on() {
+        let start = Instant::now();
+        run_in_order(&[script("sleep 3 &")], None).await;
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
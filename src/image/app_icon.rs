@@ -0,0 +1,128 @@
+use gtk::prelude::*;
+use gtk::IconTheme;
+
+/// Gets the names to try when looking up the icon for an app ID,
+/// in order of preference.
+///
+/// Window classes and app IDs are not consistently formatted,
+/// so as well as the ID as-is, this tries:
+///
+/// - The ID without a `.desktop` suffix.
+/// - The ID in lowercase, with spaces and underscores replaced by dashes.
+/// - The last segment of a reverse-DNS ID, such as `nautilus` for `org.gnome.Nautilus`.
+fn icon_name_candidates(app_id: &str) -> Vec<String> {
+    let app_id = app_id.trim();
+
+    let stripped = app_id
+        .len()
+        .checked_sub(".desktop".len())
+        .filter(|&end| app_id[end..].eq_ignore_ascii_case(".desktop"))
+        .map_or(app_id, |end| &app_id[..end]);
+
+    let lower = stripped.to_lowercase();
+
+    let mut candidates = vec![
+        app_id.to_string(),
+        stripped.to_string(),
+        lower.clone(),
+        lower.replace([' ', '_'], "-"),
+    ];
+
+    if let Some((_, name)) = lower.rsplit_once('.') {
+        candidates.push(name.to_string());
+    }
+
+    let mut unique = Vec::with_capacity(candidates.len());
+    for name in candidates {
+        if !name.is_empty() && !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+
+    unique
+}
+
+/// Finds the name of the themed icon for an app ID or window class.
+///
+/// Returns `None` if the theme has no icon for any of the name variations.
+/// See [`icon_name_candidates`] for the variations tried.
+pub fn icon_name_for_app_id(app_id: &str, theme: &IconTheme) -> Option<String> {
+    icon_name_candidates(app_id)
+        .into_iter()
+        .find(|name| theme.has_icon(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use std::fs;
+
+    #[test]
+    fn test_reverse_dns() {
+        assert_eq!(
+            icon_name_candidates("org.gnome.Nautilus"),
+            ["org.gnome.Nautilus", "org.gnome.nautilus", "nautilus"]
+        );
+    }
+
+    #[test]
+    fn test_desktop_suffix() {
+        assert_eq!(
+            icon_name_candidates("org.gnome.Nautilus.desktop"),
+            [
+                "org.gnome.Nautilus.desktop",
+                "org.gnome.Nautilus",
+                "org.gnome.nautilus",
+                "nautilus"
+            ]
+        );
+
+        assert_eq!(
+            icon_name_candidates("firefox.DESKTOP"),
+            ["firefox.DESKTOP", "firefox"]
+        );
+    }
+
+    #[test]
+    fn test_window_class() {
+        assert_eq!(
+            icon_name_candidates("Google Chrome"),
+            ["Google Chrome", "google chrome", "google-chrome"]
+        );
+        assert_eq!(icon_name_candidates("kitty"), ["kitty"]);
+        assert!(icon_name_candidates("  ").is_empty());
+    }
+
+    #[test]
+    fn test_fallback_order() {
+        with_gtk(|| {
+            let dir = std::env::temp_dir().join(format!("ironbar-icons-{}", std::process::id()));
+            fs::create_dir_all(&dir).expect("to create test dir");
+
+            for name in ["ironbartest", "org.example.ironbartest", "ironbar-test"] {
+                fs::write(dir.join(format!("{name}.svg")), "<svg/>").expect("to write icon");
+            }
+
+            let theme = IconTheme::new();
+            theme.append_search_path(&dir);
+
+            // earlier candidates win over later ones
+            assert_eq!(
+                icon_name_for_app_id("org.example.IronbarTest.desktop", &theme).as_deref(),
+                Some("org.example.ironbartest")
+            );
+            assert_eq!(
+                icon_name_for_app_id("com.example.IronbarTest", &theme).as_deref(),
+                Some("ironbartest")
+            );
+            assert_eq!(
+                icon_name_for_app_id("Ironbar_Test", &theme).as_deref(),
+                Some("ironbar-test")
+            );
+            assert_eq!(icon_name_for_app_id("ironbar-missing", &theme), None);
+
+            fs::remove_dir_all(&dir).ok();
+        });
+    }
+}
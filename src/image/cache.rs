@@ -0,0 +1,148 @@
+use gtk::gdk_pixbuf::Pixbuf;
+use indexmap::IndexMap;
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Number of images to keep in the cache
+/// if not set in the config.
+pub const DEFAULT_CACHE_SIZE: usize = 256;
+
+/// Cache of scaled images, shared by all image lookups on the GTK thread.
+pub type ImageCache = LruCache<(ImageKey, i32), Pixbuf>;
+
+/// Identifies the source of a cached image.
+/// This is combined with the scaled size to form the cache key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ImageKey {
+    Icon {
+        name: String,
+        /// Address of the icon theme the icon was loaded from.
+        theme: usize,
+    },
+    File {
+        path: PathBuf,
+        /// Last modification time,
+        /// so that files which change on disk are reloaded.
+        modified: Option<SystemTime>,
+    },
+}
+
+/// A bounded cache which evicts the least-recently used entry
+/// once it reaches capacity.
+///
+/// Entries are kept in access order,
+/// with the most-recently used entry last.
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    entries: IndexMap<K, V>,
+    capacity: usize,
+}
+
+impl<K: Hash + Eq, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: IndexMap::new(),
+            capacity,
+        }
+    }
+
+    /// Gets a clone of the entry for `key`,
+    /// marking it as the most-recently used.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let index = self.entries.get_index_of(key)?;
+        self.entries.move_index(index, self.entries.len() - 1);
+        self.entries.last().map(|(_, value)| value.clone())
+    }
+
+    /// Inserts an entry,
+    /// evicting the least-recently used entries if over capacity.
+    ///
+    /// Nothing is stored if the capacity is zero.
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.entries.shift_remove(&key);
+        self.entries.insert(key, value);
+        self.evict();
+    }
+
+    /// Sets the maximum number of entries,
+    /// evicting the least-recently used entries if over the new capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.shift_remove_index(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit() {
+        let mut cache = LruCache::new(2);
+        cache.insert(("firefox", 32), 1);
+
+        assert_eq!(cache.get(&("firefox", 32)), Some(1));
+        assert_eq!(cache.get(&("firefox", 64)), None);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        // `a` is now more recent than `b`
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        cache.insert("c", 3);
+
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_replace_existing() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("a", 3);
+        cache.insert("c", 4);
+
+        assert_eq!(cache.get(&"a"), Some(3));
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn test_shrink_capacity() {
+        let mut cache = LruCache::new(3);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("c", 3);
+
+        cache.set_capacity(1);
+
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = LruCache::new(0);
+        cache.insert("a", 1);
+
+        assert_eq!(cache.get(&"a"), None);
+    }
+}
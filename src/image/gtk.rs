@@ -3,8 +3,13 @@ use crate::gtk_helpers::IronbarGtkExt;
 use gtk::prelude::*;
 use gtk::{Button, IconTheme, Image, Label, Orientation};
 
-#[cfg(any(feature = "music", feature = "workspaces", feature = "clipboard"))]
-pub fn new_icon_button(input: &str, icon_theme: &IconTheme, size: i32) -> Button {
+#[cfg(any(
+    feature = "music",
+    feature = "workspaces",
+    feature = "clipboard",
+    feature = "quicksettings"
+))]
+pub fn new_icon_button(input: &str, icon_theme: &IconTheme, size: i32, scale: i32) -> Button {
     let button = Button::new();
 
     if ImageProvider::is_definitely_image_input(input) {
@@ -13,7 +18,7 @@ pub fn new_icon_button(input: &str, icon_theme: &IconTheme, size: i32) -> Button
         image.add_class("icon");
 
         match ImageProvider::parse(input, icon_theme, false, size)
-            .map(|provider| provider.load_into_image(image.clone()))
+            .map(|provider| provider.with_scale(scale).load_into_image(image.clone()))
         {
             Some(_) => {
                 button.set_image(Some(&image));
@@ -30,8 +35,8 @@ pub fn new_icon_button(input: &str, icon_theme: &IconTheme, size: i32) -> Button
     button
 }
 
-#[cfg(feature = "music")]
-pub fn new_icon_label(input: &str, icon_theme: &IconTheme, size: i32) -> gtk::Box {
+#[cfg(any(feature = "caffeine", feature = "music"))]
+pub fn new_icon_label(input: &str, icon_theme: &IconTheme, size: i32, scale: i32) -> gtk::Box {
     let container = gtk::Box::new(Orientation::Horizontal, 0);
 
     if ImageProvider::is_definitely_image_input(input) {
@@ -42,7 +47,7 @@ pub fn new_icon_label(input: &str, icon_theme: &IconTheme, size: i32) -> gtk::Bo
         container.add(&image);
 
         ImageProvider::parse(input, icon_theme, false, size)
-            .map(|provider| provider.load_into_image(image));
+            .map(|provider| provider.with_scale(scale).load_into_image(image));
     } else {
         let label = Label::new(Some(input));
         label.add_class("icon");
@@ -1,7 +1,24 @@
-#[cfg(any(feature = "music", feature = "workspaces", feature = "clipboard"))]
+mod app_icon;
+mod cache;
+#[cfg(any(
+    feature = "caffeine",
+    feature = "clipboard",
+    feature = "music",
+    feature = "quicksettings",
+    feature = "workspaces"
+))]
 mod gtk;
 mod provider;
 
-#[cfg(any(feature = "music", feature = "workspaces"))]
+#[cfg(any(
+    feature = "caffeine",
+    feature = "music",
+    feature = "quicksettings",
+    feature = "workspaces"
+))]
 pub use self::gtk::*;
+pub use app_icon::icon_name_for_app_id;
+pub use cache::{ImageCache, DEFAULT_CACHE_SIZE};
+#[cfg(feature = "tray")]
+pub use provider::pixel_size;
 pub use provider::ImageProvider;
@@ -1,13 +1,20 @@
+use super::cache::ImageKey;
+use super::icon_name_for_app_id;
 use crate::desktop_file::get_desktop_icon_name;
+use crate::Ironbar;
 #[cfg(feature = "http")]
 use crate::{glib_recv_mpsc, send_async, spawn};
 use cfg_if::cfg_if;
 use color_eyre::{Help, Report, Result};
 use gtk::cairo::Surface;
 use gtk::gdk::ffi::gdk_cairo_surface_create_from_pixbuf;
+use gtk::gdk::RGBA;
 use gtk::gdk_pixbuf::Pixbuf;
+use gtk::gio::{Cancellable, MemoryInputStream};
 use gtk::prelude::*;
 use gtk::{IconLookupFlags, IconTheme};
+use std::cell::RefCell;
+use std::fs;
 use std::path::{Path, PathBuf};
 #[cfg(feature = "http")]
 use tokio::sync::mpsc;
@@ -15,11 +22,20 @@ use tracing::{debug, warn};
 
 cfg_if!(
     if #[cfg(feature = "http")] {
-        use gtk::gio::{Cancellable, MemoryInputStream};
         use tracing::error;
     }
 );
 
+/// Built-in icons to show when neither the requested icon
+/// nor the configured fallback icon exist, in order of preference.
+/// GTK bundles `image-missing`, so the last always exists.
+const BUILTIN_FALLBACK_ICONS: [&str; 2] = ["dialog-question-symbolic", "image-missing"];
+
+thread_local! {
+    /// The configured `fallback_icon`.
+    static FALLBACK_ICON: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
 #[derive(Debug)]
 enum ImageLocation<'a> {
     Icon {
@@ -35,6 +51,19 @@ enum ImageLocation<'a> {
 pub struct ImageProvider<'a> {
     location: ImageLocation<'a>,
     size: i32,
+    /// Scale factor of the output the image is shown on.
+    scale: Option<i32>,
+}
+
+/// Gets the size in physical pixels to load an image at
+/// for a size in logical pixels.
+pub const fn pixel_size(size: i32, scale: i32) -> i32 {
+    // scale factors below 1 are not valid
+    if scale > 1 {
+        size * scale
+    } else {
+        size
+    }
 }
 
 impl<'a> ImageProvider<'a> {
@@ -47,7 +76,27 @@ impl<'a> ImageProvider<'a> {
         let location = Self::get_location(input, theme, size, use_fallback, 0)?;
         debug!("Resolved {input} --> {location:?} (size: {size})");
 
-        Some(Self { location, size })
+        Some(Self {
+            location,
+            size,
+            scale: None,
+        })
+    }
+
+    /// Sets the scale factor of the output the image is shown on,
+    /// so that it is loaded at the correct resolution.
+    ///
+    /// Without this, the image widget's scale factor is used,
+    /// which is not necessarily that of the output until the widget is shown.
+    #[must_use]
+    pub const fn with_scale(mut self, scale: i32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Gets the scale factor to load the image at.
+    fn scale(&self, image: &gtk::Image) -> i32 {
+        self.scale.unwrap_or_else(|| image.scale_factor())
     }
 
     /// Returns true if the input starts with a prefix
@@ -71,7 +120,7 @@ impl<'a> ImageProvider<'a> {
         macro_rules! fallback {
             () => {
                 if use_fallback {
-                    Some(Self::get_fallback_icon(theme))
+                    Self::get_fallback_icon(theme, size)
                 } else {
                     None
                 }
@@ -87,10 +136,9 @@ impl<'a> ImageProvider<'a> {
             .map_or((None, input), |(t, n)| (Some(t), n));
 
         match input_type {
-            Some(input_type) if input_type == "icon" => Some(ImageLocation::Icon {
-                name: input_name.to_string(),
-                theme,
-            }),
+            Some(input_type) if input_type == "icon" => {
+                Self::resolve_icon(Some(input_name), theme, size)
+            }
             Some(input_type) if input_type == "file" => Some(ImageLocation::Local(PathBuf::from(
                 input_name[2..].to_string(),
             ))),
@@ -123,10 +171,14 @@ impl<'a> ImageProvider<'a> {
             }
             None if recurse_depth == MAX_RECURSE_DEPTH => fallback!(),
             None if should_parse_desktop_file => {
-                if let Some(location) = get_desktop_icon_name(input_name).map(|input| {
-                    Self::get_location(&input, theme, size, use_fallback, recurse_depth + 1)
+                // the app ID heuristics are only used
+                // if the desktop file does not give a usable icon
+                if let Some(location) = get_desktop_icon_name(input_name).and_then(|input| {
+                    Self::get_location(&input, theme, size, false, recurse_depth + 1)
                 }) {
-                    location
+                    Some(location)
+                } else if let Some(name) = icon_name_for_app_id(input_name, theme) {
+                    Some(ImageLocation::Icon { name, theme })
                 } else {
                     warn!("Failed to find image: {input}");
                     fallback!()
@@ -157,11 +209,12 @@ impl<'a> ImageProvider<'a> {
 
             {
                 let size = self.size;
+                let scale = self.scale;
                 glib_recv_mpsc!(rx, bytes => {
                     let stream = MemoryInputStream::from_bytes(&bytes);
 
-                    let scale = image.scale_factor();
-                    let scaled_size = size * scale;
+                    let scale = scale.unwrap_or_else(|| image.scale_factor());
+                    let scaled_size = pixel_size(size, scale);
 
                     let pixbuf = Pixbuf::from_stream_at_scale(
                         &stream,
@@ -172,7 +225,7 @@ impl<'a> ImageProvider<'a> {
                     );
 
                     // Different error types makes this a bit awkward
-                    match pixbuf.map(|pixbuf| Self::create_and_load_surface(&pixbuf, &image))
+                    match pixbuf.map(|pixbuf| Self::create_and_load_surface(&pixbuf, &image, scale))
                     {
                         Ok(Err(err)) => error!("{err:?}"),
                         Err(err) => error!("{err:?}"),
@@ -190,20 +243,128 @@ impl<'a> ImageProvider<'a> {
         Ok(())
     }
 
-    /// Attempts to synchronously fetch an image from location
-    /// and load into into the image.
-    fn load_into_image_sync(&self, image: &gtk::Image) -> Result<()> {
-        let scale = image.scale_factor();
+    /// Attempts to synchronously fetch the image from its location
+    /// at its original size, scaled down to fit within `max_size` if larger,
+    /// and load it into the provided image.
+    ///
+    /// Icons have no original size, so are loaded at the provider's size.
+    /// Remote images are not supported.
+    pub fn load_full_size_into_image(&self, image: &gtk::Image, max_size: i32) -> Result<()> {
+        let scale = self.scale(image);
 
         let pixbuf = match &self.location {
             ImageLocation::Icon { name, theme } => self.get_from_icon(name, theme, scale),
-            ImageLocation::Local(path) => self.get_from_file(path, scale),
-            ImageLocation::Steam(steam_id) => self.get_from_steam_id(steam_id, scale),
+            ImageLocation::Local(path) => load_capped(path, max_size, scale),
+            ImageLocation::Steam(steam_id) => {
+                load_capped(&Self::get_steam_icon_path(steam_id)?, max_size, scale)
+            }
             #[cfg(feature = "http")]
-            _ => unreachable!(), // handled above
+            ImageLocation::Remote(_) => {
+                Err(Report::msg("Remote images cannot be loaded at full size"))
+            }
         }?;
 
-        Self::create_and_load_surface(&pixbuf, image)
+        Self::create_and_load_surface(&pixbuf, image, scale)
+    }
+
+    /// Attempts to synchronously fetch an image from location
+    /// and load into into the image.
+    ///
+    /// Scaled images are stored in the shared image cache,
+    /// so repeated lookups of the same image and size skip loading.
+    fn load_into_image_sync(&self, image: &gtk::Image) -> Result<()> {
+        let scale = self.scale(image);
+        let key = self
+            .cache_key()
+            .map(|key| (key, pixel_size(self.size, scale)));
+
+        let cache = Ironbar::image_cache();
+        let cached = key.as_ref().and_then(|key| cache.borrow_mut().get(key));
+
+        let pixbuf = match cached {
+            Some(pixbuf) => pixbuf,
+            None => {
+                let pixbuf = match &self.location {
+                    ImageLocation::Icon { name, theme } => self.get_from_icon(name, theme, scale),
+                    ImageLocation::Local(path) => self.get_from_file(path, scale),
+                    ImageLocation::Steam(steam_id) => self.get_from_steam_id(steam_id, scale),
+                    #[cfg(feature = "http")]
+                    _ => unreachable!(), // handled above
+                }?;
+
+                if let Some(key) = key {
+                    cache.borrow_mut().insert(key, pixbuf.clone());
+                }
+
+                pixbuf
+            }
+        };
+
+        Self::create_and_load_surface(&pixbuf, image, scale)
+    }
+
+    /// Gets the path to the image if it is an SVG file,
+    /// including icons from the theme which are SVGs.
+    pub fn svg_path(&self) -> Option<PathBuf> {
+        let path = match &self.location {
+            ImageLocation::Icon { name, theme } => theme
+                .lookup_icon(name, self.size, IconLookupFlags::empty())?
+                .filename()?,
+            ImageLocation::Local(path) => path.clone(),
+            _ => return None,
+        };
+
+        is_svg(&path).then_some(path)
+    }
+
+    /// Attempts to synchronously load an SVG image with its colours replaced by `color`,
+    /// and load it into the provided image.
+    ///
+    /// Recoloured images are not cached, as the colour may change.
+    pub fn load_recolored_into_image(&self, image: &gtk::Image, color: &RGBA) -> Result<()> {
+        let path = self
+            .svg_path()
+            .ok_or_else(|| Report::msg("Only SVG images can be recolored"))?;
+
+        let svg = recolor_svg(&fs::read_to_string(&path)?, color)
+            .ok_or_else(|| Report::msg(format!("Invalid SVG image: '{}'", path.display())))?;
+
+        let stream = MemoryInputStream::from_bytes(&glib::Bytes::from_owned(svg.into_bytes()));
+
+        let scale = self.scale(image);
+        let scaled_size = pixel_size(self.size, scale);
+        let pixbuf = Pixbuf::from_stream_at_scale(
+            &stream,
+            scaled_size,
+            scaled_size,
+            true,
+            None::<&Cancellable>,
+        )?;
+
+        Self::create_and_load_surface(&pixbuf, image, scale)
+    }
+
+    /// Gets the key used to store the image in the image cache.
+    ///
+    /// Returns `None` for locations that should not be cached.
+    fn cache_key(&self) -> Option<ImageKey> {
+        let file_key = |path: PathBuf| {
+            let modified = path.metadata().and_then(|meta| meta.modified()).ok();
+            ImageKey::File { path, modified }
+        };
+
+        match &self.location {
+            ImageLocation::Icon { name, theme } => Some(ImageKey::Icon {
+                name: name.clone(),
+                theme: theme.as_ptr() as usize,
+            }),
+            ImageLocation::Local(path) => Some(file_key(path.clone())),
+            ImageLocation::Steam(steam_id) => {
+                Self::get_steam_icon_path(steam_id).ok().map(file_key)
+            }
+            #[cfg(feature = "http")]
+            ImageLocation::Remote(_) => None,
+        }
     }
 
     /// Attempts to create a Cairo surface from the provided `Pixbuf`,
@@ -211,11 +372,11 @@ impl<'a> ImageProvider<'a> {
     /// The surface is then loaded into the provided image.
     ///
     /// This is necessary for HiDPI since `Pixbuf`s are always treated as scale factor 1.
-    pub fn create_and_load_surface(pixbuf: &Pixbuf, image: &gtk::Image) -> Result<()> {
+    pub fn create_and_load_surface(pixbuf: &Pixbuf, image: &gtk::Image, scale: i32) -> Result<()> {
         let surface = unsafe {
             let ptr = gdk_cairo_surface_create_from_pixbuf(
                 pixbuf.as_ptr(),
-                image.scale_factor(),
+                scale.max(1),
                 std::ptr::null_mut(),
             );
             Surface::from_raw_full(ptr)
@@ -230,7 +391,11 @@ impl<'a> ImageProvider<'a> {
     fn get_from_icon(&self, name: &str, theme: &IconTheme, scale: i32) -> Result<Pixbuf> {
         let pixbuf =
             match theme.lookup_icon_for_scale(name, self.size, scale, IconLookupFlags::empty()) {
-                Some(_) => theme.load_icon(name, self.size * scale, IconLookupFlags::FORCE_SIZE),
+                Some(_) => theme.load_icon(
+                    name,
+                    pixel_size(self.size, scale),
+                    IconLookupFlags::FORCE_SIZE,
+                ),
                 None => Ok(None),
             }?;
 
@@ -242,7 +407,7 @@ impl<'a> ImageProvider<'a> {
 
     /// Attempts to get a `Pixbuf` from a local file.
     fn get_from_file(&self, path: &Path, scale: i32) -> Result<Pixbuf> {
-        let scaled_size = self.size * scale;
+        let scaled_size = pixel_size(self.size, scale);
         let pixbuf = Pixbuf::from_file_at_scale(path, scaled_size, scaled_size, true)?;
         Ok(pixbuf)
     }
@@ -250,17 +415,21 @@ impl<'a> ImageProvider<'a> {
     /// Attempts to get a `Pixbuf` from a local file,
     /// using the Steam game ID to look it up.
     fn get_from_steam_id(&self, steam_id: &str, scale: i32) -> Result<Pixbuf> {
+        let path = Self::get_steam_icon_path(steam_id)?;
+        self.get_from_file(&path, scale)
+    }
+
+    /// Gets the path to the icon for a Steam game ID.
+    fn get_steam_icon_path(steam_id: &str) -> Result<PathBuf> {
         // TODO: Can we load this from icon theme with app id `steam_icon_{}`?
-        let path = dirs::data_dir().map_or_else(
+        dirs::data_dir().map_or_else(
             || Err(Report::msg("Missing XDG data dir")),
             |dir| {
                 Ok(dir.join(format!(
                     "icons/hicolor/32x32/apps/steam_icon_{steam_id}.png"
                 )))
             },
-        )?;
-
-        self.get_from_file(&path, scale)
+        )
     }
 
     /// Attempts to get `Bytes` from an HTTP resource asynchronously.
@@ -279,10 +448,232 @@ impl<'a> ImageProvider<'a> {
         }
     }
 
-    fn get_fallback_icon(theme: &'a IconTheme) -> ImageLocation<'a> {
-        ImageLocation::Icon {
-            name: "dialog-question-symbolic".to_string(),
-            theme,
+    /// Sets the icon to use in place of icons which cannot be found,
+    /// before falling back to a built-in icon.
+    ///
+    /// This is local to the GTK thread.
+    pub fn set_fallback_icon(name: Option<String>) {
+        let name = name.map(|name| match name.strip_prefix("icon:") {
+            Some(name) => name.to_string(),
+            None => name,
+        });
+        FALLBACK_ICON.with_borrow_mut(|fallback| *fallback = name);
+    }
+
+    fn get_fallback_icon(theme: &'a IconTheme, size: i32) -> Option<ImageLocation<'a>> {
+        Self::resolve_icon(None, theme, size)
+    }
+
+    /// Gets the first icon in the fallback chain which is in the theme,
+    /// starting with `name` if set.
+    fn resolve_icon(
+        name: Option<&str>,
+        theme: &'a IconTheme,
+        size: i32,
+    ) -> Option<ImageLocation<'a>> {
+        let fallback = FALLBACK_ICON.with_borrow(Clone::clone);
+
+        let resolved = first_existing_icon(icon_chain(name, fallback.as_deref()), |name| {
+            theme
+                .lookup_icon(name, size, IconLookupFlags::empty())
+                .is_some()
+        });
+
+        match (name, resolved) {
+            (Some(name), Some(resolved)) if name != resolved => {
+                debug!("Icon '{name}' not found in theme, using '{resolved}' instead");
+            }
+            (_, None) => warn!("No fallback icons found in theme"),
+            _ => {}
         }
+
+        resolved.map(|name| ImageLocation::Icon {
+            name: name.to_string(),
+            theme,
+        })
+    }
+}
+
+/// Gets the icons to try in order:
+/// the requested icon if set, then the configured fallback if set,
+/// then the built-in fallbacks.
+fn icon_chain<'n>(
+    name: Option<&'n str>,
+    fallback: Option<&'n str>,
+) -> impl Iterator<Item = &'n str> {
+    name.into_iter()
+        .chain(fallback)
+        .chain(BUILTIN_FALLBACK_ICONS)
+}
+
+/// Walks the chain until an icon which `exists` is found.
+fn first_existing_icon<'n>(
+    mut chain: impl Iterator<Item = &'n str>,
+    exists: impl Fn(&str) -> bool,
+) -> Option<&'n str> {
+    chain.find(|name| exists(name))
+}
+
+/// Loads the image at `path` at its original size multiplied by `scale`,
+/// scaling it down to fit within `max_size` if larger.
+fn load_capped(path: &Path, max_size: i32, scale: i32) -> Result<Pixbuf> {
+    let pixbuf = match Pixbuf::file_info(path) {
+        Some((_, width, height)) => Pixbuf::from_file_at_size(
+            path,
+            pixel_size(width.min(max_size), scale),
+            pixel_size(height.min(max_size), scale),
+        )?,
+        None => Pixbuf::from_file(path)?,
+    };
+
+    Ok(pixbuf)
+}
+
+/// Checks whether the path is an SVG image, by its extension.
+fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+/// Adds a stylesheet to the SVG
+/// which replaces the colour of every filled or stroked shape with `color`,
+/// similar to how GTK recolours symbolic icons.
+///
+/// Only elements which already set a fill or stroke are changed.
+///
+/// Returns `None` if the input has no `<svg>` element.
+fn recolor_svg(svg: &str, color: &RGBA) -> Option<String> {
+    let start = svg.find("<svg")?;
+    let end = start + svg[start..].find('>')? + 1;
+
+    // a self-closing root element has no content to recolour
+    if svg[..end].ends_with("/>") {
+        return None;
+    }
+
+    Some(format!(
+        "{}<style>\
+        * {{ color: {color} !important; }} \
+        [fill]:not([fill=\"none\"]) {{ fill: {color} !important; }} \
+        [stroke]:not([stroke=\"none\"]) {{ stroke: {color} !important; }}\
+        </style>{}",
+        &svg[..end],
+        &svg[end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve<'n>(
+        name: Option<&'n str>,
+        fallback: Option<&'n str>,
+        theme: &[&str],
+    ) -> Option<&'n str> {
+        first_existing_icon(icon_chain(name, fallback), |name| theme.contains(&name))
+    }
+
+    #[test]
+    fn test_pixel_size() {
+        assert_eq!(pixel_size(24, 1), 24);
+        assert_eq!(pixel_size(24, 2), 48);
+        assert_eq!(pixel_size(16, 3), 48);
+    }
+
+    #[test]
+    fn test_pixel_size_invalid_scale() {
+        assert_eq!(pixel_size(24, 0), 24);
+        assert_eq!(pixel_size(24, -2), 24);
+    }
+
+    #[test]
+    fn test_icon_chain_order() {
+        assert_eq!(
+            icon_chain(Some("firefox"), Some("application-x-executable")).collect::<Vec<_>>(),
+            [
+                "firefox",
+                "application-x-executable",
+                "dialog-question-symbolic",
+                "image-missing"
+            ]
+        );
+        assert_eq!(
+            icon_chain(None, None).collect::<Vec<_>>(),
+            BUILTIN_FALLBACK_ICONS
+        );
+    }
+
+    #[test]
+    fn test_resolve_requested_icon() {
+        let theme = ["firefox", "custom-fallback", "image-missing"];
+        assert_eq!(
+            resolve(Some("firefox"), Some("custom-fallback"), &theme),
+            Some("firefox")
+        );
+    }
+
+    #[test]
+    fn test_resolve_configured_fallback() {
+        let theme = ["custom-fallback", "dialog-question-symbolic"];
+        assert_eq!(
+            resolve(Some("missing"), Some("custom-fallback"), &theme),
+            Some("custom-fallback")
+        );
+        assert_eq!(
+            resolve(None, Some("custom-fallback"), &theme),
+            Some("custom-fallback")
+        );
+    }
+
+    #[test]
+    fn test_resolve_builtin_fallback() {
+        let theme = ["image-missing"];
+        assert_eq!(
+            resolve(Some("missing"), Some("also-missing"), &theme),
+            Some("image-missing")
+        );
+        assert_eq!(
+            resolve(Some("missing"), None, &theme),
+            Some("image-missing")
+        );
+    }
+
+    #[test]
+    fn test_resolve_nothing() {
+        assert_eq!(resolve(Some("missing"), Some("also-missing"), &[]), None);
+    }
+
+    #[test]
+    fn test_is_svg() {
+        assert!(is_svg(Path::new(
+            "/usr/share/icons/audio-volume-high-symbolic.svg"
+        )));
+        assert!(is_svg(Path::new("logo.SVG")));
+
+        assert!(!is_svg(Path::new("/home/user/avatar.png")));
+        assert!(!is_svg(Path::new("/home/user/svg")));
+        assert!(!is_svg(Path::new("icon.svgz.png")));
+    }
+
+    #[test]
+    fn test_recolor_svg() {
+        let svg = r##"<?xml version="1.0"?><svg width="16" height="16"><path fill="#000"/></svg>"##;
+
+        let red = RGBA::new(1.0, 0.0, 0.0, 1.0);
+
+        let recolored = recolor_svg(svg, &red).unwrap();
+        assert!(
+            recolored.starts_with(r##"<?xml version="1.0"?><svg width="16" height="16"><style>"##)
+        );
+        assert!(
+            recolored.contains("[fill]:not([fill=\"none\"]) { fill: rgb(255,0,0) !important; }")
+        );
+        assert!(recolored
+            .contains("[stroke]:not([stroke=\"none\"]) { stroke: rgb(255,0,0) !important; }"));
+        assert!(recolored.ends_with(r##"</style><path fill="#000"/></svg>"##));
+
+        assert!(recolor_svg("not an svg", &red).is_none());
+        assert!(recolor_svg("<svg/>", &red).is_none());
     }
 }
@@ -35,6 +35,26 @@ pub enum Command {
     /// Gets the current value of all `ironvar`s.
     List,
 
+    /// Reload the Lua scripts of all `cairo` modules,
+    /// without restarting the bars.
+    /// Scripts which fail to load keep drawing with their previous version.
+    #[cfg(feature = "cairo")]
+    LuaReload,
+
+    /// Tear down a module and create it again from the config,
+    /// without restarting the bars.
+    /// The module keeps its position on the bar.
+    #[command(name = "module:reload", alias = "module-reload")]
+    #[serde(alias = "module:reload")]
+    ModuleReload {
+        /// The name of the module.
+        /// This is the `name` option if set, otherwise the module type.
+        name: String,
+        /// Only reload the module on the bar with this name.
+        #[arg(long)]
+        bar_name: Option<String>,
+    },
+
     /// Load an additional CSS stylesheet.
     /// The sheet is automatically hot-reloaded.
     LoadCss {
@@ -51,12 +71,31 @@ pub enum Command {
         visible: bool,
     },
 
+    /// Toggle the visibility of the bar with the given name.
+    ToggleVisible {
+        /// Bar name to target.
+        bar_name: String,
+    },
+
     /// Get the visibility of the bar with the given name.
     GetVisible {
         /// Bar name to target.
         bar_name: String,
     },
 
+    /// Toggle an outline around every module,
+    /// with a tooltip naming each one, to show the layout of the bars.
+    DebugOutline,
+
+    /// Get a JSON snapshot of the running bars and their modules.
+    #[command(alias = "introspect")]
+    #[serde(alias = "introspect")]
+    State {
+        /// Omit script commands from the output.
+        #[arg(long)]
+        redact: bool,
+    },
+
     /// Toggle a popup open/closed.
     /// If opening this popup, and a different popup on the same bar is already open, the other is closed.
     TogglePopup {
@@ -74,6 +113,11 @@ pub enum Command {
         name: String,
     },
 
+    /// Print a JSON Schema describing the config file,
+    /// for editors to validate and autocomplete against.
+    /// This does not need Ironbar to be running.
+    Schema,
+
     /// Close a popup, regardless of current state.
     ClosePopup {
         /// The name of the monitor the bar is located on.
@@ -0,0 +1,219 @@
+use super::{Command, Ipc, IpcRequest, Response};
+use crate::spawn;
+use color_eyre::{Help, Report};
+use std::future::pending;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use zbus::{dbus_interface, fdo, ConnectionBuilder};
+
+const BUS_NAME: &str = "dev.ironbar.Ironbar";
+const OBJECT_PATH: &str = "/dev/ironbar/Ironbar";
+
+/// Starts the D-Bus service on the session bus.
+///
+/// Method calls are converted into IPC commands,
+/// and run through the same handler as the IPC socket.
+pub fn start(cmd_tx: mpsc::Sender<IpcRequest>) {
+    spawn(async move {
+        let interface = IronbarInterface { cmd_tx };
+
+        let connection = ConnectionBuilder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, interface));
+
+        let connection = match connection {
+            Ok(builder) => builder.build().await,
+            Err(err) => Err(err),
+        };
+
+        match connection {
+            Ok(_connection) => {
+                info!("Starting D-Bus service on {BUS_NAME}");
+
+                // the service stops when the connection is dropped,
+                // so this must be kept alive.
+                pending::<()>().await;
+            }
+            Err(zbus::Error::NameTaken) => warn!(
+                "{:?}",
+                Report::msg(format!("D-Bus name '{BUS_NAME}' is already taken"))
+                    .wrap_err("Unable to start D-Bus service")
+                    .suggestion("Is another instance of Ironbar running?")
+                    .note("The IPC socket is still available")
+            ),
+            Err(err) => error!(
+                "{:?}",
+                Report::new(err).wrap_err("Unable to start D-Bus service")
+            ),
+        }
+    });
+}
+
+struct IronbarInterface {
+    cmd_tx: mpsc::Sender<IpcRequest>,
+}
+
+impl IronbarInterface {
+    /// Runs the command,
+    /// converting its response into a D-Bus method result.
+    async fn run(&self, command: Command) -> fdo::Result<Option<String>> {
+        match Ipc::dispatch(&self.cmd_tx, command).await {
+            Response::Ok => Ok(None),
+            Response::OkValue { value } => Ok(Some(value)),
+            Response::Err { message } => Err(fdo::Error::Failed(
+                message.unwrap_or_else(|| "Unknown error".to_string()),
+            )),
+        }
+    }
+}
+
+#[dbus_interface(name = "dev.ironbar.Ironbar")]
+impl IronbarInterface {
+    /// Reloads the config.
+    async fn reload(&self) -> fdo::Result<()> {
+        self.run(Command::Reload).await.map(|_| ())
+    }
+
+    /// Loads an additional CSS stylesheet.
+    async fn load_css(&self, path: String) -> fdo::Result<()> {
+        self.run(Command::LoadCss {
+            path: PathBuf::from(path),
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Sets the visibility of a bar.
+    async fn set_visible(&self, bar_name: String, visible: bool) -> fdo::Result<()> {
+        self.run(Command::SetVisible { bar_name, visible })
+            .await
+            .map(|_| ())
+    }
+
+    /// Toggles the visibility of a bar.
+    async fn toggle_visible(&self, bar_name: String) -> fdo::Result<()> {
+        self.run(Command::ToggleVisible { bar_name })
+            .await
+            .map(|_| ())
+    }
+
+    /// Gets the visibility of a bar.
+    async fn get_visible(&self, bar_name: String) -> fdo::Result<bool> {
+        self.run(Command::GetVisible { bar_name })
+            .await
+            .map(|value| value.as_deref() == Some("true"))
+    }
+
+    /// Toggles a popup open/closed.
+    async fn toggle_popup(&self, bar_name: String, name: String) -> fdo::Result<()> {
+        self.run(Command::TogglePopup { bar_name, name })
+            .await
+            .map(|_| ())
+    }
+
+    /// Opens a popup, regardless of its current state.
+    async fn open_popup(&self, bar_name: String, name: String) -> fdo::Result<()> {
+        self.run(Command::OpenPopup { bar_name, name })
+            .await
+            .map(|_| ())
+    }
+
+    /// Closes the popup on a bar, regardless of its current state.
+    async fn close_popup(&self, bar_name: String) -> fdo::Result<()> {
+        self.run(Command::ClosePopup { bar_name }).await.map(|_| ())
+    }
+
+    /// Sets an `ironvar` value.
+    /// Any widgets referencing the variable are updated.
+    async fn set_variable(&self, key: String, value: String) -> fdo::Result<()> {
+        self.run(Command::Set {
+            key: key.into(),
+            value,
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Gets the current value of an `ironvar`.
+    async fn get_variable(&self, key: String) -> fdo::Result<String> {
+        self.run(Command::Get { key: key.into() })
+            .await
+            .map(Option::unwrap_or_default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an interface whose commands are handled by `handler`,
+    /// rather than by the main thread.
+    fn interface<F>(handler: F) -> IronbarInterface
+    where
+        F: Fn(Command) -> Response + Send + 'static,
+    {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel::<IpcRequest>(32);
+
+        tokio::spawn(async move {
+            while let Some((command, res_tx)) = cmd_rx.recv().await {
+                res_tx.send(handler(command)).ok();
+            }
+        });
+
+        IronbarInterface { cmd_tx }
+    }
+
+    #[tokio::test]
+    async fn test_commands_dispatched() {
+        let interface = interface(|command| match command {
+            Command::TogglePopup { bar_name, name } if bar_name == "bar" && name == "clock" => {
+                Response::Ok
+            }
+            _ => Response::error("unexpected command"),
+        });
+
+        assert!(interface
+            .toggle_popup("bar".to_string(), "clock".to_string())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_values_returned() {
+        let interface = interface(|command| match command {
+            Command::Get { key } => Response::OkValue {
+                value: format!("value of {key}"),
+            },
+            Command::GetVisible { .. } => Response::OkValue {
+                value: "true".to_string(),
+            },
+            _ => Response::Ok,
+        });
+
+        assert_eq!(
+            interface.get_variable("foo".to_string()).await.unwrap(),
+            "value of foo"
+        );
+        assert!(interface.get_visible("bar".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_errors_returned() {
+        let interface = interface(|_| Response::error("Bar not found"));
+
+        let res = interface.set_visible("missing".to_string(), true).await;
+        assert!(matches!(res, Err(fdo::Error::Failed(message)) if message == "Bar not found"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_response() {
+        let (cmd_tx, mut cmd_rx) = mpsc::channel::<IpcRequest>(32);
+
+        // drop each request without responding
+        tokio::spawn(async move { while cmd_rx.recv().await.is_some() {} });
+
+        let interface = IronbarInterface { cmd_tx };
+        assert!(interface.reload().await.is_err());
+    }
+}
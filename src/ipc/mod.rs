@@ -1,13 +1,22 @@
 mod client;
 pub mod commands;
+#[cfg(feature = "ipc+dbus")]
+mod dbus;
 pub mod responses;
 mod server;
+mod state;
 
 use std::path::{Path, PathBuf};
+use tokio::sync::oneshot;
 use tracing::warn;
 
 pub use commands::Command;
 pub use responses::Response;
+pub use state::{remove_bar_modules, ModuleBuilder, ModuleRecord, OUTLINE_CSS};
+
+/// A command sent to the main thread,
+/// along with the channel to send its response on.
+type IpcRequest = (Command, oneshot::Sender<Response>);
 
 #[derive(Debug)]
 pub struct Ipc {
@@ -7,15 +7,16 @@ use gtk::prelude::*;
 use gtk::Application;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::oneshot;
 use tracing::{debug, error, info, warn};
 
 use crate::ipc::{Command, Response};
-use crate::modules::PopupButton;
 use crate::style::load_css;
-use crate::{glib_recv_mpsc, read_lock, send_async, spawn, try_send, write_lock, Ironbar};
+use crate::{glib_recv_mpsc, read_lock, send_async, spawn, write_lock, Ironbar};
 
-use super::Ipc;
+use super::state::IronbarState;
+use super::{Ipc, IpcRequest};
 
 impl Ipc {
     /// Starts the IPC server on its socket.
@@ -23,7 +24,9 @@ impl Ipc {
     /// Once started, the server will begin accepting connections.
     pub fn start(&self, application: &Application, ironbar: Rc<Ironbar>) {
         let (cmd_tx, cmd_rx) = mpsc::channel(32);
-        let (res_tx, mut res_rx) = mpsc::channel(32);
+
+        #[cfg(feature = "ipc+dbus")]
+        super::dbus::start(cmd_tx.clone());
 
         let path = self.path.clone();
 
@@ -50,9 +53,7 @@ impl Ipc {
             loop {
                 match listener.accept().await {
                     Ok((stream, _addr)) => {
-                        if let Err(err) =
-                            Self::handle_connection(stream, &cmd_tx, &mut res_rx).await
-                        {
+                        if let Err(err) = Self::handle_connection(stream, &cmd_tx).await {
                             error!("{err:?}");
                         }
                     }
@@ -64,9 +65,10 @@ impl Ipc {
         });
 
         let application = application.clone();
-        glib_recv_mpsc!(cmd_rx, command => {
+        glib_recv_mpsc!(cmd_rx, request => {
+            let (command, res_tx) = request;
             let res = Self::handle_command(command, &application, &ironbar);
-            try_send!(res_tx, res);
+            res_tx.send(res).ok();
         });
     }
 
@@ -74,11 +76,7 @@ impl Ipc {
     /// reads the command message, and sends the response.
     ///
     /// The connection is closed once the response has been written.
-    async fn handle_connection(
-        mut stream: UnixStream,
-        cmd_tx: &Sender<Command>,
-        res_rx: &mut Receiver<Response>,
-    ) -> Result<()> {
+    async fn handle_connection(mut stream: UnixStream, cmd_tx: &Sender<IpcRequest>) -> Result<()> {
         let (mut stream_read, mut stream_write) = stream.split();
 
         let mut read_buffer = vec![0; 1024];
@@ -88,11 +86,7 @@ impl Ipc {
 
         debug!("Received command: {command:?}");
 
-        send_async!(cmd_tx, command);
-        let res = res_rx
-            .recv()
-            .await
-            .unwrap_or(Response::Err { message: None });
+        let res = Self::dispatch(cmd_tx, command).await;
         let res = serde_json::to_vec(&res)?;
 
         stream_write.write_all(&res).await?;
@@ -101,6 +95,15 @@ impl Ipc {
         Ok(())
     }
 
+    /// Sends a command to the main thread to be run,
+    /// and waits for its response.
+    pub(super) async fn dispatch(cmd_tx: &Sender<IpcRequest>, command: Command) -> Response {
+        let (res_tx, res_rx) = oneshot::channel();
+        send_async!(cmd_tx, (command, res_tx));
+
+        res_rx.await.unwrap_or(Response::Err { message: None })
+    }
+
     /// Takes an input command, runs it and returns with the appropriate response.
     ///
     /// This runs on the main thread, allowing commands to interact with GTK.
@@ -117,6 +120,7 @@ impl Ipc {
             Command::Reload => {
                 info!("Closing existing bars");
                 ironbar.bars.borrow_mut().clear();
+                ironbar.modules.borrow_mut().clear();
 
                 let windows = application.windows();
                 for window in windows {
@@ -167,6 +171,23 @@ impl Ipc {
 
                 Response::OkValue { value }
             }
+            #[cfg(feature = "cairo")]
+            Command::LuaReload => {
+                let lua = ironbar.clients.borrow().lua_if_loaded();
+                match lua {
+                    Some(lua) => {
+                        lua.reload();
+                        Response::Ok
+                    }
+                    None => Response::error("No Lua scripts are loaded"),
+                }
+            }
+            Command::ModuleReload { name, bar_name } => {
+                match ironbar.reload_module(&name, bar_name.as_deref()) {
+                    0 => Response::error("Module not found"),
+                    _ => Response::Ok,
+                }
+            }
             Command::LoadCss { path } => {
                 if path.exists() {
                     load_css(path);
@@ -175,40 +196,24 @@ impl Ipc {
                     Response::error("File not found")
                 }
             }
+            Command::DebugOutline => Response::OkValue {
+                value: ironbar.toggle_debug_outline().to_string(),
+            },
+            Command::State { redact } => {
+                let state = IronbarState::collect(ironbar, redact);
+                match serde_json::to_string(&state) {
+                    Ok(value) => Response::OkValue { value },
+                    Err(err) => Response::error(&format!("{err}")),
+                }
+            }
             Command::TogglePopup { bar_name, name } => {
                 let bar = ironbar.bar_by_name(&bar_name);
 
                 match bar {
-                    Some(bar) => {
-                        let popup = bar.popup();
-                        let current_widget = popup.current_widget();
-
-                        popup.hide();
-
-                        let data = popup
-                            .container_cache
-                            .borrow()
-                            .iter()
-                            .find(|(_, value)| value.name == name)
-                            .map(|(id, value)| (*id, value.content.buttons.first().cloned()));
-
-                        match data {
-                            Some((id, Some(button))) if current_widget != Some(id) => {
-                                let button_id = button.popup_id();
-
-                                if popup.is_visible() {
-                                    popup.hide();
-                                } else {
-                                    popup.show(id, button_id);
-                                }
-
-                                Response::Ok
-                            }
-                            Some((_, None)) => Response::error("Module has no popup functionality"),
-                            Some(_) => Response::Ok,
-                            None => Response::error("Invalid module name"),
-                        }
-                    }
+                    Some(bar) => match bar.popup().toggle_named(&name) {
+                        Ok(()) => Response::Ok,
+                        Err(err) => Response::error(err),
+                    },
                     None => Response::error("Invalid bar name"),
                 }
             }
@@ -216,30 +221,10 @@ impl Ipc {
                 let bar = ironbar.bar_by_name(&bar_name);
 
                 match bar {
-                    Some(bar) => {
-                        let popup = bar.popup();
-
-                        // only one popup per bar, so hide if open for another widget
-                        popup.hide();
-
-                        let data = popup
-                            .container_cache
-                            .borrow()
-                            .iter()
-                            .find(|(_, value)| value.name == name)
-                            .map(|(id, value)| (*id, value.content.buttons.first().cloned()));
-
-                        match data {
-                            Some((id, Some(button))) => {
-                                let button_id = button.popup_id();
-                                popup.show(id, button_id);
-
-                                Response::Ok
-                            }
-                            Some((_, None)) => Response::error("Module has no popup functionality"),
-                            None => Response::error("Invalid module name"),
-                        }
-                    }
+                    Some(bar) => match bar.popup().open_named(&name) {
+                        Ok(()) => Response::Ok,
+                        Err(err) => Response::error(err),
+                    },
                     None => Response::error("Invalid bar name"),
                 }
             }
@@ -257,6 +242,9 @@ impl Ipc {
                 }
             }
             Command::Ping => Response::Ok,
+            Command::Schema => Response::OkValue {
+                value: crate::config::schema().to_string(),
+            },
             Command::SetVisible { bar_name, visible } => {
                 let windows = application.windows();
                 let found = windows
@@ -270,6 +258,19 @@ impl Ipc {
                     Response::error("Bar not found")
                 }
             }
+            Command::ToggleVisible { bar_name } => {
+                let windows = application.windows();
+                let found = windows
+                    .iter()
+                    .find(|window| window.widget_name() == bar_name);
+
+                if let Some(window) = found {
+                    window.set_visible(!window.is_visible());
+                    Response::Ok
+                } else {
+                    Response::error("Bar not found")
+                }
+            }
             Command::GetVisible { bar_name } => {
                 let windows = application.windows();
                 let found = windows
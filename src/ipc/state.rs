@@ -0,0 +1,504 @@
+use crate::bar::Bar;
+use crate::gtk_helpers::{replace_child, IronbarGtkExt};
+use crate::modules::tasks::TaskScope;
+use crate::modules::ModuleLocation;
+use crate::Ironbar;
+use gtk::prelude::*;
+use gtk::{EventBox, Revealer};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+/// Class added to module containers while the debug outline is shown.
+pub const OUTLINE_CLASS: &str = "debug-outline";
+
+/// Default look for the debug outline,
+/// loaded while it is shown.
+pub const OUTLINE_CSS: &str = "
+.debug-outline { outline: 1px dashed #ff00ff; outline-offset: -1px; }
+";
+
+/// Tag storing a module's tooltip from before the outline was shown.
+const OUTLINE_TOOLTIP_TAG: &str = "debug-outline-tooltip";
+
+/// A module created on a bar,
+/// kept so that its state can be queried over IPC.
+#[derive(Debug)]
+pub struct ModuleRecord {
+    pub id: usize,
+    pub bar_name: String,
+    pub name: String,
+    pub module_type: &'static str,
+    pub location: ModuleLocation,
+    /// The outer module container, created by `wrap_widget`.
+    pub container: EventBox,
+    /// Commands for each configured event script.
+    pub scripts: BTreeMap<&'static str, String>,
+    /// Tasks spawned by the module.
+    pub tasks: TaskScope,
+    /// Creates the module again from its config.
+    /// This is set once the module has been created.
+    pub builder: Option<ModuleBuilder>,
+}
+
+/// Creates a module from its config,
+/// adding it to the given container.
+#[derive(Clone)]
+pub struct ModuleBuilder(pub Rc<dyn Fn(&gtk::Box)>);
+
+impl Debug for ModuleBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ModuleBuilder").finish_non_exhaustive()
+    }
+}
+
+/// Removes the records for each module on the bar called `bar_name`,
+/// cancelling the tasks spawned by those modules.
+///
+/// This is used once the bar has been destroyed.
+pub fn remove_bar_modules(modules: &mut Vec<ModuleRecord>, bar_name: &str) {
+    modules.retain(|module| {
+        let keep = module.bar_name != bar_name;
+        if !keep {
+            module.tasks.cancel();
+        }
+
+        keep
+    });
+}
+
+impl ModuleRecord {
+    /// Tears down the module and creates it again from its config,
+    /// in the same position within its container.
+    ///
+    /// All tasks spawned by the old module are cancelled.
+    /// The new module registers itself,
+    /// so this record should be dropped afterwards.
+    ///
+    /// Returns the record back if the module cannot be recreated.
+    pub fn recreate(self) -> Result<(), Box<Self>> {
+        let parent = self
+            .container
+            .parent()
+            .and_then(|parent| parent.downcast::<gtk::Box>().ok());
+
+        let (Some(builder), Some(parent)) = (self.builder.clone(), parent) else {
+            return Err(Box::new(self));
+        };
+
+        self.tasks.cancel();
+        replace_child(&parent, &self.container, |parent| (builder.0)(parent));
+
+        Ok(())
+    }
+
+    /// Checks whether the module is currently shown.
+    /// This takes into account the `show_if` option.
+    fn is_visible(&self) -> bool {
+        self.container.is_visible()
+            && self
+                .container
+                .child()
+                .and_then(|child| child.downcast::<Revealer>().ok())
+                .is_none_or(|revealer| revealer.reveals_child())
+    }
+
+    /// Text naming the module, shown as its tooltip while outlined.
+    fn outline_label(&self) -> String {
+        let location = match self.location {
+            ModuleLocation::Left => "start",
+            ModuleLocation::Center => "center",
+            ModuleLocation::Right => "end",
+        };
+
+        format!(
+            "{} '{}' ({}, {location})",
+            self.module_type, self.name, self.bar_name
+        )
+    }
+
+    /// Shows or removes the debug outline around the module,
+    /// along with a tooltip naming it.
+    ///
+    /// The module's own tooltip is restored afterwards,
+    /// unless it has been changed in the meantime.
+    pub fn set_outlined(&self, outlined: bool) {
+        let container = &self.container;
+        let style = container.style_context();
+
+        if outlined == style.has_class(OUTLINE_CLASS) {
+            return;
+        }
+
+        let label = self.outline_label();
+
+        if outlined {
+            style.add_class(OUTLINE_CLASS);
+            container.set_tag(OUTLINE_TOOLTIP_TAG, container.tooltip_markup());
+            container.set_tooltip_text(Some(&label));
+        } else {
+            style.remove_class(OUTLINE_CLASS);
+
+            let previous = container
+                .get_tag::<Option<glib::GString>>(OUTLINE_TOOLTIP_TAG)
+                .cloned()
+                .flatten();
+
+            if container.tooltip_text().as_deref() == Some(label.as_str()) {
+                container.set_tooltip_markup(previous.as_deref());
+            }
+        }
+    }
+}
+
+/// Snapshot of the running bars,
+/// as returned by the `state` command.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct IronbarState {
+    pub bars: Vec<BarState>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct BarState {
+    pub name: String,
+    pub monitor: String,
+    pub visible: bool,
+    pub popup: PopupState,
+    pub modules: Vec<ModuleState>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct PopupState {
+    pub open: bool,
+    /// ID of the module the popup is open for.
+    pub module: Option<usize>,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ModuleState {
+    pub id: usize,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub module_type: String,
+    pub location: &'static str,
+    pub visible: bool,
+    /// Commands for each configured event script.
+    /// Omitted when redacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scripts: Option<BTreeMap<String, String>>,
+}
+
+impl IronbarState {
+    /// Collects the current state of each bar from the running instance.
+    ///
+    /// If `redact` is set, script commands are omitted.
+    pub fn collect(ironbar: &Ironbar, redact: bool) -> Self {
+        let modules = ironbar.modules.borrow();
+
+        let bars = ironbar
+            .bars
+            .borrow()
+            .iter()
+            .map(|bar| BarState::collect(bar, &modules, redact))
+            .collect();
+
+        Self { bars }
+    }
+}
+
+impl BarState {
+    fn collect(bar: &Bar, modules: &[ModuleRecord], redact: bool) -> Self {
+        let popup = bar.popup();
+        let open = popup.is_visible();
+
+        let modules = modules
+            .iter()
+            .filter(|module| module.bar_name == bar.name())
+            .map(|module| ModuleState {
+                id: module.id,
+                name: module.name.clone(),
+                module_type: module.module_type.to_string(),
+                location: match module.location {
+                    ModuleLocation::Left => "start",
+                    ModuleLocation::Center => "center",
+                    ModuleLocation::Right => "end",
+                },
+                visible: module.is_visible(),
+                scripts: (!redact).then(|| {
+                    module
+                        .scripts
+                        .iter()
+                        .map(|(&event, cmd)| (event.to_string(), cmd.clone()))
+                        .collect()
+                }),
+            })
+            .collect();
+
+        Self {
+            name: bar.name().to_string(),
+            monitor: bar.monitor_name().to_string(),
+            visible: bar.is_visible(),
+            popup: PopupState {
+                open,
+                module: popup.current_widget().filter(|_| open),
+            },
+            modules,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use serde_json::json;
+
+    fn record(name: &str, tooltip: Option<&str>) -> ModuleRecord {
+        record_on("bar-1", name, tooltip)
+    }
+
+    fn record_on(bar_name: &str, name: &str, tooltip: Option<&str>) -> ModuleRecord {
+        let container = EventBox::new();
+        container.set_tooltip_markup(tooltip);
+
+        ModuleRecord {
+            id: 1,
+            bar_name: bar_name.to_string(),
+            name: name.to_string(),
+            module_type: "label",
+            location: ModuleLocation::Left,
+            container,
+            scripts: BTreeMap::new(),
+            tasks: TaskScope::default(),
+            builder: None,
+        }
+    }
+
+    #[test]
+    fn test_remove_bar_modules() {
+        with_gtk(|| {
+            let mut records = vec![
+                record_on("bar-1", "first", None),
+                record_on("bar-2", "second", None),
+                record_on("bar-1", "third", None),
+            ];
+
+            remove_bar_modules(&mut records, "bar-1");
+
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].name, "second");
+
+            remove_bar_modules(&mut records, "bar-3");
+            assert_eq!(records.len(), 1);
+        });
+    }
+
+    fn outlined(record: &ModuleRecord) -> bool {
+        record.container.style_context().has_class(OUTLINE_CLASS)
+    }
+
+    #[test]
+    fn test_toggle_outline() {
+        with_gtk(|| {
+            let records = [
+                record("first", Some("<b>hello</b>")),
+                record("second", None),
+            ];
+
+            for record in &records {
+                record.set_outlined(true);
+            }
+
+            for record in &records {
+                assert!(outlined(record));
+            }
+            assert_eq!(
+                records[0].container.tooltip_text().as_deref(),
+                Some("label 'first' (bar-1, start)")
+            );
+
+            for record in &records {
+                record.set_outlined(false);
+            }
+
+            for record in &records {
+                assert!(!outlined(record));
+            }
+            assert_eq!(
+                records[0].container.tooltip_markup().as_deref(),
+                Some("<b>hello</b>")
+            );
+            assert_eq!(records[1].container.tooltip_markup(), None);
+        });
+    }
+
+    #[test]
+    fn test_outline_repeated() {
+        with_gtk(|| {
+            let record = record("first", Some("hello"));
+
+            record.set_outlined(true);
+            record.set_outlined(true);
+            record.set_outlined(false);
+
+            // the original tooltip is not lost by outlining twice
+            assert!(!outlined(&record));
+            assert_eq!(record.container.tooltip_markup().as_deref(), Some("hello"));
+        });
+    }
+
+    #[test]
+    fn test_outline_keeps_changed_tooltip() {
+        with_gtk(|| {
+            let record = record("first", Some("hello"));
+
+            record.set_outlined(true);
+            record.container.set_tooltip_markup(Some("updated"));
+            record.set_outlined(false);
+
+            assert_eq!(
+                record.container.tooltip_markup().as_deref(),
+                Some("updated")
+            );
+        });
+    }
+
+    /// Container with three modules, returning the record for the middle one.
+    fn bar_section() -> (gtk::Box, ModuleRecord) {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        let mut middle = record("middle", None);
+
+        middle.container.set_widget_name("old");
+        middle.builder = Some(ModuleBuilder(Rc::new(|container: &gtk::Box| {
+            let widget = EventBox::new();
+            widget.set_widget_name("new");
+            container.add(&widget);
+        })));
+
+        container.add(&EventBox::builder().name("first").build());
+        container.add(&middle.container);
+        container.add(&EventBox::builder().name("last").build());
+
+        (container, middle)
+    }
+
+    fn widget_names(container: &gtk::Box) -> Vec<String> {
+        container
+            .children()
+            .iter()
+            .map(|child| child.widget_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_recreate_keeps_position() {
+        with_gtk(|| {
+            let (container, record) = bar_section();
+            let old = record.container.clone();
+
+            assert!(record.recreate().is_ok());
+
+            assert_eq!(widget_names(&container), ["first", "new", "last"]);
+            assert!(old.parent().is_none());
+        });
+    }
+
+    #[test]
+    fn test_recreate_cancels_tasks() {
+        with_gtk(|| {
+            let (_container, record) = bar_section();
+
+            let handle = {
+                let _entered = record.tasks.enter();
+                crate::spawn(std::future::pending::<()>())
+            };
+
+            assert!(record.recreate().is_ok());
+
+            let res = Ironbar::runtime().block_on(handle);
+            assert!(res.is_err_and(|err| err.is_cancelled()));
+        });
+    }
+
+    #[test]
+    fn test_recreate_requires_builder() {
+        with_gtk(|| {
+            let (container, mut record) = bar_section();
+            record.builder = None;
+
+            assert!(record.recreate().is_err());
+            assert_eq!(widget_names(&container), ["first", "old", "last"]);
+
+            // not added to a container
+            let (_, mut orphan) = bar_section();
+            orphan.container = EventBox::new();
+            assert!(orphan.recreate().is_err());
+        });
+    }
+
+    fn module(scripts: Option<BTreeMap<String, String>>) -> ModuleState {
+        ModuleState {
+            id: 3,
+            name: "clock".to_string(),
+            module_type: "clock".to_string(),
+            location: "end",
+            visible: true,
+            scripts,
+        }
+    }
+
+    fn state(module: ModuleState) -> IronbarState {
+        IronbarState {
+            bars: vec![BarState {
+                name: "bar-1".to_string(),
+                monitor: "DP-1".to_string(),
+                visible: true,
+                popup: PopupState {
+                    open: true,
+                    module: Some(3),
+                },
+                modules: vec![module],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_serialize_state() {
+        let scripts = BTreeMap::from([(
+            "on_click_right".to_string(),
+            "notify-send hello".to_string(),
+        )]);
+
+        let value = serde_json::to_value(state(module(Some(scripts)))).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "bars": [{
+                    "name": "bar-1",
+                    "monitor": "DP-1",
+                    "visible": true,
+                    "popup": { "open": true, "module": 3 },
+                    "modules": [{
+                        "id": 3,
+                        "name": "clock",
+                        "type": "clock",
+                        "location": "end",
+                        "visible": true,
+                        "scripts": { "on_click_right": "notify-send hello" }
+                    }]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_redacted_state() {
+        let value = serde_json::to_value(state(module(None))).unwrap();
+        let module = &value["bars"][0]["modules"][0];
+
+        assert!(module.get("scripts").is_none());
+        assert_eq!(module["name"], "clock");
+    }
+}
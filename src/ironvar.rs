@@ -105,3 +105,63 @@ impl IronVar {
         rx
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[test]
+    fn test_subscribe_sends_current_value() {
+        let mut manager = VariableManager::new();
+        manager.set("volume".into(), "50".to_string()).unwrap();
+
+        let mut rx = manager.subscribe("volume".into());
+        assert_eq!(rx.try_recv(), Ok(Some("50".to_string())));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_subscribe_creates_variable() {
+        let mut manager = VariableManager::new();
+
+        let mut rx = manager.subscribe("volume".into());
+        assert_eq!(rx.try_recv(), Ok(None));
+        assert!(manager.get_all().contains_key("volume"));
+        assert_eq!(manager.get("volume"), None);
+    }
+
+    #[test]
+    fn test_set_notifies_subscribers() {
+        let mut manager = VariableManager::new();
+
+        let mut rx_a = manager.subscribe("volume".into());
+        let mut rx_b = manager.subscribe("volume".into());
+        let mut rx_other = manager.subscribe("brightness".into());
+
+        // drain initial values
+        while rx_a.try_recv().is_ok() {}
+        while rx_b.try_recv().is_ok() {}
+        while rx_other.try_recv().is_ok() {}
+
+        manager.set("volume".into(), "20".to_string()).unwrap();
+        manager.set("volume".into(), "30".to_string()).unwrap();
+
+        for rx in [&mut rx_a, &mut rx_b] {
+            assert_eq!(rx.try_recv(), Ok(Some("20".to_string())));
+            assert_eq!(rx.try_recv(), Ok(Some("30".to_string())));
+        }
+
+        assert_eq!(rx_other.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(manager.get("volume").as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let mut manager = VariableManager::new();
+
+        assert!(manager.set("".into(), "1".to_string()).is_err());
+        assert!(manager.set("my var".into(), "1".to_string()).is_err());
+        assert!(manager.set("my-var_2".into(), "1".to_string()).is_ok());
+    }
+}
@@ -0,0 +1,368 @@
+use crate::command::Command;
+use crate::popup::Popup;
+use crate::Ironbar;
+use color_eyre::{Help, Report, Result};
+use glib::Propagation;
+use gtk::gdk::ModifierType;
+use gtk::prelude::*;
+use gtk::ApplicationWindow;
+use gtk_layer_shell::{KeyboardMode, LayerShell};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::rc::Rc;
+use std::str::FromStr;
+use tracing::{debug, error};
+
+/// Map of key combinations against the command to run.
+///
+/// Entries are kept in the order they are defined,
+/// including duplicates, so that these can be reported.
+#[derive(Debug, Default, Clone)]
+pub struct KeybindsConfig(pub Vec<(String, String)>);
+
+impl JsonSchema for KeybindsConfig {
+    fn schema_name() -> String {
+        "KeybindsConfig".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        HashMap::<String, String>::json_schema(gen)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeybindsConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeybindsVisitor;
+
+        impl<'de> Visitor<'de> for KeybindsVisitor {
+            type Value = KeybindsConfig;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of key combinations to commands")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut binds = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    binds.push(entry);
+                }
+
+                Ok(KeybindsConfig(binds))
+            }
+        }
+
+        deserializer.deserialize_map(KeybindsVisitor)
+    }
+}
+
+/// Keyboard modifiers which can be part of a key combination.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    /// Sets the modifier with the given name.
+    /// Returns `false` if the name is not a known modifier.
+    fn set(&mut self, name: &str) -> bool {
+        match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" | "primary" => self.ctrl = true,
+            "shift" => self.shift = true,
+            "alt" | "mod1" => self.alt = true,
+            "super" | "mod4" | "logo" => self.super_key = true,
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+impl From<ModifierType> for Modifiers {
+    fn from(state: ModifierType) -> Self {
+        Self {
+            ctrl: state.contains(ModifierType::CONTROL_MASK),
+            shift: state.contains(ModifierType::SHIFT_MASK),
+            alt: state.contains(ModifierType::MOD1_MASK),
+            super_key: state.intersects(ModifierType::SUPER_MASK | ModifierType::MOD4_MASK),
+        }
+    }
+}
+
+/// A key combination.
+///
+/// This accepts both the GTK accelerator format (`<Ctrl><Shift>p`),
+/// and the `Ctrl+Shift+p` format.
+/// Keys use their GDK names, for example `Escape`, `F1` or `plus`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accelerator {
+    /// Lowercase key name.
+    pub key: String,
+    pub modifiers: Modifiers,
+}
+
+impl Accelerator {
+    /// Checks whether a key press with the given key name and modifiers
+    /// triggers this combination.
+    pub fn matches(&self, key: &str, modifiers: Modifiers) -> bool {
+        self.key.eq_ignore_ascii_case(key) && self.modifiers == modifiers
+    }
+}
+
+impl FromStr for Accelerator {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut rest = s.trim();
+
+        let invalid_modifier = |name: &str| {
+            Report::msg(format!(
+                "Invalid modifier '{name}' in key combination '{s}'"
+            ))
+            .suggestion("Valid modifiers are `Ctrl`, `Shift`, `Alt` and `Super`")
+        };
+
+        while let Some(tail) = rest.strip_prefix('<') {
+            let (name, tail) = tail.split_once('>').ok_or_else(|| {
+                Report::msg(format!("Unclosed modifier in key combination '{s}'"))
+            })?;
+
+            if !modifiers.set(name) {
+                return Err(invalid_modifier(name));
+            }
+
+            rest = tail.trim_start();
+        }
+
+        let mut parts = rest.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+
+        for name in parts {
+            if !modifiers.set(name) {
+                return Err(invalid_modifier(name));
+            }
+        }
+
+        if key.is_empty() {
+            return Err(Report::msg(format!("Missing key in key combination '{s}'")));
+        }
+
+        Ok(Self {
+            key: key.to_ascii_lowercase(),
+            modifiers,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keybind {
+    pub accelerator: Accelerator,
+    pub command: Command,
+}
+
+/// Parses the configured keybinds.
+///
+/// Invalid and duplicate bindings are skipped,
+/// and returned as a list of error messages.
+/// Where a key combination is bound more than once, the first binding is kept.
+pub fn parse_keybinds(config: &KeybindsConfig) -> (Vec<Keybind>, Vec<String>) {
+    let mut keybinds: Vec<Keybind> = vec![];
+    let mut errors = vec![];
+
+    for (accelerator_str, command) in &config.0 {
+        let parsed = accelerator_str
+            .parse::<Accelerator>()
+            .and_then(|accelerator| Ok((accelerator, Command::parse_standalone(command)?)));
+
+        match parsed {
+            Ok((accelerator, command)) => {
+                if keybinds.iter().any(|bind| bind.accelerator == accelerator) {
+                    errors.push(format!(
+                        "Duplicate keybind '{accelerator_str}' for command '{command:?}'"
+                    ));
+                } else {
+                    keybinds.push(Keybind {
+                        accelerator,
+                        command,
+                    });
+                }
+            }
+            Err(err) => errors.push(format!("{err}")),
+        }
+    }
+
+    (keybinds, errors)
+}
+
+/// Installs the configured keybinds on the bar window.
+///
+/// The window can only receive key presses once it has keyboard focus,
+/// which the compositor gives it on demand, typically when it is clicked.
+pub fn install(
+    window: &ApplicationWindow,
+    config: &KeybindsConfig,
+    popup: Rc<Popup>,
+    ironbar: Rc<Ironbar>,
+) {
+    let (keybinds, errors) = parse_keybinds(config);
+
+    if !errors.is_empty() {
+        let report = errors
+            .iter()
+            .fold(Report::msg("Invalid keybinds in config"), |report, err| {
+                report.note(err.clone())
+            });
+
+        error!("{report:?}");
+    }
+
+    if keybinds.is_empty() {
+        return;
+    }
+
+    window.set_keyboard_mode(KeyboardMode::OnDemand);
+
+    window.connect_key_press_event(move |_, event| {
+        let key = event.keyval().to_lower().name().unwrap_or_default();
+        let modifiers = Modifiers::from(event.state());
+
+        let Some(keybind) = keybinds
+            .iter()
+            .find(|bind| bind.accelerator.matches(&key, modifiers))
+        else {
+            return Propagation::Proceed;
+        };
+
+        debug!("Running keybind command: {:?}", keybind.command);
+
+        if let Err(err) = keybind.command.run(&popup, &ironbar) {
+            error!("{err:?}");
+        }
+
+        Propagation::Stop
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(binds: &[(&str, &str)]) -> KeybindsConfig {
+        KeybindsConfig(
+            binds
+                .iter()
+                .map(|(key, cmd)| ((*key).to_string(), (*cmd).to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_gtk_accelerator() {
+        let accelerator = "<Ctrl><Shift>p".parse::<Accelerator>().unwrap();
+
+        assert_eq!(accelerator.key, "p");
+        assert_eq!(
+            accelerator.modifiers,
+            Modifiers {
+                ctrl: true,
+                shift: true,
+                ..Modifiers::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_plus_accelerator() {
+        let accelerator = "Super+Alt+Escape".parse::<Accelerator>().unwrap();
+
+        assert_eq!(accelerator.key, "escape");
+        assert_eq!(
+            accelerator.modifiers,
+            Modifiers {
+                alt: true,
+                super_key: true,
+                ..Modifiers::default()
+            }
+        );
+
+        assert_eq!(
+            "<Control>p".parse::<Accelerator>().unwrap(),
+            "ctrl+P".parse::<Accelerator>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_accelerator() {
+        assert!("".parse::<Accelerator>().is_err());
+        assert!("<Ctrl>".parse::<Accelerator>().is_err());
+        assert!("<Ctrl p".parse::<Accelerator>().is_err());
+        assert!("<Hyper>p".parse::<Accelerator>().is_err());
+        assert!("Foo+p".parse::<Accelerator>().is_err());
+    }
+
+    #[test]
+    fn test_accelerator_matches() {
+        let accelerator = "<Ctrl>F1".parse::<Accelerator>().unwrap();
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+
+        assert!(accelerator.matches("F1", ctrl));
+        assert!(!accelerator.matches("F1", Modifiers::default()));
+        assert!(!accelerator.matches("F2", ctrl));
+    }
+
+    #[test]
+    fn test_duplicate_keybinds() {
+        let (keybinds, errors) = parse_keybinds(&config(&[
+            ("<Ctrl>p", "popup:toggle:clock"),
+            ("Control+P", "popup:close"),
+            ("<Ctrl><Shift>p", "popup:close"),
+        ]));
+
+        assert_eq!(keybinds.len(), 2);
+        assert_eq!(
+            keybinds[0].command,
+            Command::TogglePopup(Some("clock".to_string()))
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Control+P"));
+    }
+
+    #[test]
+    fn test_invalid_keybinds_skipped() {
+        let (keybinds, errors) = parse_keybinds(&config(&[
+            ("<Ctrl>p", "popup:toggle:clock"),
+            ("<Ctrl>q", "invalid"),
+            ("<Meh>q", "popup:close"),
+        ]));
+
+        assert_eq!(keybinds.len(), 1);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_deserialize_keeps_duplicates() {
+        let config: KeybindsConfig =
+            serde_json::from_str(r#"{ "<Ctrl>p": "popup:close", "<Ctrl>p": "popup:close" }"#)
+                .unwrap();
+
+        assert_eq!(config.0.len(), 2);
+        assert_eq!(parse_keybinds(&config).1.len(), 1);
+    }
+}
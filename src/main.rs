@@ -1,6 +1,7 @@
 #![doc = include_str!("../README.md")]
 
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::env;
 use std::future::Future;
 use std::path::PathBuf;
@@ -9,7 +10,7 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 #[cfg(feature = "ipc")]
 use std::sync::RwLock;
-use std::sync::{mpsc, Arc, OnceLock};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
 
 use cfg_if::cfg_if;
 #[cfg(feature = "cli")]
@@ -18,7 +19,7 @@ use color_eyre::eyre::Result;
 use color_eyre::Report;
 use dirs::config_dir;
 use glib::PropertySet;
-use gtk::gdk::Display;
+use gtk::gdk::{Display, Monitor};
 use gtk::prelude::*;
 use gtk::Application;
 use smithay_client_toolkit::output::OutputInfo;
@@ -27,33 +28,44 @@ use tokio::task::{block_in_place, JoinHandle};
 use tracing::{debug, error, info, warn};
 use universal_config::ConfigLoader;
 
-use crate::bar::{create_bar, Bar};
+use crate::bar::{create_bar, Bar, VisibilityCommand};
 use crate::clients::wayland::OutputEventType;
 use crate::clients::Clients;
-use crate::config::{Config, MonitorConfig};
+use crate::config::Config;
 use crate::error::ExitCode;
+use crate::hooks::{SHUTDOWN_TIMEOUT, STARTUP_TIMEOUT};
+use crate::image::{ImageCache, ImageProvider, DEFAULT_CACHE_SIZE};
+#[cfg(feature = "ipc")]
+use crate::ipc::{ModuleBuilder, ModuleRecord, OUTLINE_CSS};
 #[cfg(feature = "ipc")]
 use crate::ironvar::VariableManager;
+use crate::modules::tasks::TaskScope;
+use crate::script::{RefreshRegistry, Script};
 use crate::style::load_css;
 
 mod bar;
 #[cfg(feature = "cli")]
 mod cli;
 mod clients;
+mod command;
 mod config;
 mod desktop_file;
 mod dynamic_value;
 mod error;
+mod format;
 mod gtk_helpers;
+mod hooks;
 mod image;
 #[cfg(feature = "ipc")]
 mod ipc;
 #[cfg(feature = "ipc")]
 mod ironvar;
+mod keybinds;
 mod logging;
 mod macros;
 mod modules;
 mod popup;
+mod schedule;
 mod script;
 mod style;
 
@@ -77,6 +89,15 @@ fn run_with_args() {
     let args = cli::Args::parse();
 
     match args.command {
+        // the schema only depends on the build,
+        // so is printed without needing a running instance
+        Some(ipc::commands::Command::Schema) => {
+            let schema = config::schema();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&schema).unwrap_or_else(|_| schema.to_string())
+            );
+        }
         Some(command) => {
             let rt = create_runtime();
             rt.block_on(async move {
@@ -97,17 +118,41 @@ pub struct Ironbar {
     clients: Rc<RefCell<Clients>>,
     config: Rc<RefCell<Config>>,
     config_dir: PathBuf,
+    /// Names of monitors whose bars have been hidden by a command.
+    /// This is kept across reloads.
+    hidden_outputs: Rc<RefCell<HashSet<String>>>,
+    /// Names of bars which have been hidden by a command targeting them by name.
+    /// This is kept across reloads.
+    hidden_bars: Rc<RefCell<HashSet<String>>>,
+    /// Commands to run on exit.
+    /// These are shared with the thread which handles shutdown.
+    shutdown_hooks: Arc<Mutex<Vec<Script>>>,
+    /// Modules created on each bar.
+    #[cfg(feature = "ipc")]
+    modules: Rc<RefCell<Vec<ModuleRecord>>>,
+    /// Stylesheet for the debug outline,
+    /// which is only set while the outline is shown.
+    #[cfg(feature = "ipc")]
+    debug_outline: RefCell<Option<gtk::CssProvider>>,
 }
 
 impl Ironbar {
     fn new() -> Self {
         let (config, config_dir) = load_config();
+        let shutdown_hooks = hooks::scripts(&config.on_shutdown);
 
         Self {
             bars: Rc::new(RefCell::new(vec![])),
             clients: Rc::new(RefCell::new(Clients::new())),
             config: Rc::new(RefCell::new(config)),
             config_dir,
+            hidden_outputs: Rc::new(RefCell::new(HashSet::new())),
+            hidden_bars: Rc::new(RefCell::new(HashSet::new())),
+            shutdown_hooks: Arc::new(Mutex::new(shutdown_hooks)),
+            #[cfg(feature = "ipc")]
+            modules: Rc::new(RefCell::new(vec![])),
+            #[cfg(feature = "ipc")]
+            debug_outline: RefCell::new(None),
         }
     }
 
@@ -138,6 +183,9 @@ impl Ironbar {
 
             running.set(true);
 
+            let on_startup = hooks::scripts(&instance.config.borrow().on_startup);
+            spawn(async move { hooks::run_in_order(&on_startup, Some(STARTUP_TIMEOUT)).await });
+
             cfg_if! {
                 if #[cfg(feature = "ipc")] {
                     let ipc = ipc::Ipc::new();
@@ -167,11 +215,15 @@ impl Ironbar {
 
             #[cfg(feature = "ipc")]
             let ipc_path = ipc.path().to_path_buf();
+            let shutdown_hooks = instance.shutdown_hooks.clone();
             spawn_blocking(move || {
                 rx.recv().expect("to receive from channel");
 
                 info!("Shutting down");
 
+                let scripts = lock!(shutdown_hooks).clone();
+                await_sync(hooks::run_in_order(&scripts, Some(SHUTDOWN_TIMEOUT)));
+
                 #[cfg(feature = "ipc")]
                 ipc::Ipc::shutdown(ipc_path);
 
@@ -202,15 +254,14 @@ impl Ironbar {
                                 }
                                 Err(err) => error!("{err:?}"),
                             }
+
+                            watch_scale_factor(&instance, &app, &event.output);
                         }
                         OutputEventType::Destroyed => {
                             let Some(name) = event.output.name else {
                                 continue;
                             };
-                            instance
-                                .bars
-                                .borrow_mut()
-                                .retain(|bar| bar.monitor_name() != name);
+                            instance.remove_output_bars(&name);
                         }
                         OutputEventType::Update => {}
                     }
@@ -247,6 +298,30 @@ impl Ironbar {
             .clone()
     }
 
+    /// Gets the registry of named polling scripts,
+    /// which can be re-run on demand.
+    #[must_use]
+    pub fn script_refresh() -> Arc<Mutex<RefreshRegistry>> {
+        static SCRIPT_REFRESH: OnceLock<Arc<Mutex<RefreshRegistry>>> = OnceLock::new();
+        SCRIPT_REFRESH
+            .get_or_init(|| arc_mut!(RefreshRegistry::default()))
+            .clone()
+    }
+
+    /// Gets the image cache singleton.
+    ///
+    /// Since `Pixbuf`s cannot be sent between threads,
+    /// this is local to the GTK thread.
+    #[must_use]
+    pub fn image_cache() -> Rc<RefCell<ImageCache>> {
+        thread_local! {
+            static IMAGE_CACHE: Rc<RefCell<ImageCache>> =
+                Rc::new(RefCell::new(ImageCache::new(DEFAULT_CACHE_SIZE)));
+        }
+
+        IMAGE_CACHE.with(Rc::clone)
+    }
+
     /// Gets a clone of a bar by its unique name.
     ///
     /// Since the bar contains mostly GTK objects,
@@ -260,11 +335,198 @@ impl Ironbar {
             .cloned()
     }
 
+    /// Shows or hides the bars targeted by the command.
+    pub fn set_bars_visible(&self, command: &VisibilityCommand) {
+        let mut hidden_outputs = self.hidden_outputs.borrow_mut();
+        let mut hidden_bars = self.hidden_bars.borrow_mut();
+
+        for bar in self
+            .bars
+            .borrow()
+            .iter()
+            .filter(|bar| command.targets(bar.name(), bar.monitor_name()))
+        {
+            let visible = command.action.apply(bar.is_visible());
+            bar.set_visible(visible);
+
+            if visible {
+                hidden_bars.remove(bar.name());
+            }
+
+            // bars targeted by name are tracked on their own,
+            // so that the rest of the output is unaffected
+            if command.targets_by_name(bar.name()) {
+                if !visible {
+                    hidden_bars.insert(bar.name().to_string());
+                }
+            } else if visible {
+                hidden_outputs.remove(bar.monitor_name());
+            } else {
+                hidden_outputs.insert(bar.monitor_name().to_string());
+            }
+        }
+    }
+
+    /// Adds a module to the registry of created modules.
+    #[cfg(feature = "ipc")]
+    pub fn register_module(&self, module: ModuleRecord) {
+        if self.debug_outline.borrow().is_some() {
+            module.set_outlined(true);
+        }
+
+        self.modules.borrow_mut().push(module);
+    }
+
+    /// Sets the function used to recreate a registered module.
+    #[cfg(feature = "ipc")]
+    pub fn set_module_builder(&self, id: usize, builder: ModuleBuilder) {
+        if let Some(module) = self
+            .modules
+            .borrow_mut()
+            .iter_mut()
+            .find(|module| module.id == id)
+        {
+            module.builder = Some(builder);
+        }
+    }
+
+    /// Removes the bars on the named output,
+    /// along with their modules.
+    ///
+    /// Returns the removed bars.
+    fn remove_output_bars(&self, monitor_name: &str) -> Vec<Bar> {
+        let mut removed = vec![];
+
+        self.bars.borrow_mut().retain(|bar| {
+            let keep = bar.monitor_name() != monitor_name;
+
+            if !keep {
+                #[cfg(feature = "ipc")]
+                ipc::remove_bar_modules(&mut self.modules.borrow_mut(), bar.name());
+
+                removed.push(bar.clone());
+            }
+
+            keep
+        });
+
+        removed
+    }
+
+    /// Tears down each module with the given name and creates it again from its config,
+    /// keeping its position on the bar.
+    /// If `bar_name` is set, only modules on that bar are reloaded.
+    ///
+    /// Returns the number of modules reloaded.
+    #[cfg(feature = "ipc")]
+    pub fn reload_module(&self, name: &str, bar_name: Option<&str>) -> usize {
+        // released before recreating, as each new module registers itself
+        let matching = {
+            let mut modules = self.modules.borrow_mut();
+            let (matching, rest) = modules.drain(..).partition::<Vec<_>, _>(|module| {
+                module.name == name && bar_name.is_none_or(|bar_name| module.bar_name == bar_name)
+            });
+
+            *modules = rest;
+            matching
+        };
+
+        let mut count = 0;
+
+        for module in matching {
+            let id = module.id;
+            let popup = self.bar_by_name(&module.bar_name).map(|bar| bar.popup());
+
+            info!("Reloading module '{name}' [#{id}]");
+
+            match module.recreate() {
+                Ok(()) => {
+                    if let Some(popup) = popup {
+                        popup.unregister_content(id);
+                    }
+
+                    count += 1;
+                }
+                Err(module) => {
+                    warn!("Module '{name}' [#{id}] cannot be reloaded");
+                    self.modules.borrow_mut().push(*module);
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Shows or hides an outline around every module,
+    /// with a tooltip naming each one.
+    ///
+    /// Returns whether the outline is now shown.
+    #[cfg(feature = "ipc")]
+    pub fn toggle_debug_outline(&self) -> bool {
+        let mut debug_outline = self.debug_outline.borrow_mut();
+        let screen = gtk::gdk::Screen::default();
+
+        let outlined = match debug_outline.take() {
+            Some(provider) => {
+                if let Some(screen) = &screen {
+                    gtk::StyleContext::remove_provider_for_screen(screen, &provider);
+                }
+                false
+            }
+            None => {
+                let provider = gtk::CssProvider::new();
+                match provider.load_from_data(OUTLINE_CSS.as_bytes()) {
+                    Ok(()) => {
+                        // drawn above the user's own styles
+                        if let Some(screen) = &screen {
+                            gtk::StyleContext::add_provider_for_screen(
+                                screen,
+                                &provider,
+                                gtk::STYLE_PROVIDER_PRIORITY_USER + 1,
+                            );
+                        }
+                    }
+                    Err(err) => error!("Failed to load debug outline styles: {err:?}"),
+                }
+
+                *debug_outline = Some(provider);
+                true
+            }
+        };
+
+        for module in self.modules.borrow().iter() {
+            module.set_outlined(outlined);
+        }
+
+        outlined
+    }
+
+    /// Checks whether a bar has been hidden by a command,
+    /// either by its name or by its monitor.
+    pub fn is_bar_hidden(&self, bar_name: &str, monitor_name: &str) -> bool {
+        self.hidden_bars.borrow().contains(bar_name)
+            || self.hidden_outputs.borrow().contains(monitor_name)
+    }
+
     /// Re-reads the config file from disk and replaces the active config.
     /// Note this does *not* reload bars, which must be performed separately.
+    ///
+    /// The old config's shutdown hooks are run,
+    /// followed by the new config's startup hooks.
     #[cfg(feature = "ipc")]
     fn reload_config(&self) {
+        let on_shutdown = lock!(self.shutdown_hooks).clone();
+
         self.config.replace(load_config().0);
+
+        let config = self.config.borrow();
+        let on_startup = hooks::scripts(&config.on_startup);
+        *lock!(self.shutdown_hooks) = hooks::scripts(&config.on_shutdown);
+
+        spawn(async move {
+            hooks::run_in_order(&on_shutdown, Some(SHUTDOWN_TIMEOUT)).await;
+            hooks::run_in_order(&on_startup, Some(STARTUP_TIMEOUT)).await;
+        });
     }
 }
 
@@ -280,7 +542,7 @@ fn load_config() -> (Config, PathBuf) {
     let (config, directory) = if let Ok(config_path) = config_path {
         let path = PathBuf::from(config_path);
         (
-            ConfigLoader::load(&path),
+            config::load(&path),
             path.parent()
                 .map(PathBuf::from)
                 .ok_or_else(|| Report::msg("Specified path has no parent")),
@@ -288,13 +550,17 @@ fn load_config() -> (Config, PathBuf) {
     } else {
         let config_loader = ConfigLoader::new("ironbar");
         (
-            config_loader.find_and_load(),
+            config_loader
+                .config_dir()
+                .map_err(Report::new)
+                .and_then(|dir| config::find_file(&dir))
+                .and_then(|path| config::load(&path)),
             config_loader.config_dir().map_err(Report::new),
         )
     };
 
     let mut config = config.unwrap_or_else(|err| {
-        error!("Failed to load config: {}", err);
+        error!("Failed to load config: {err:?}");
         warn!("Falling back to the default config");
         info!("If this is your first time using Ironbar, you should create a config in ~/.config/ironbar/");
         info!("More info here: https://github.com/JakeStanger/ironbar/wiki/configuration-guide");
@@ -308,6 +574,12 @@ fn load_config() -> (Config, PathBuf) {
 
     debug!("Loaded config file");
 
+    Ironbar::image_cache()
+        .borrow_mut()
+        .set_capacity(config.icon_cache_size.unwrap_or(DEFAULT_CACHE_SIZE));
+
+    ImageProvider::set_fallback_icon(config.fallback_icon.clone());
+
     #[cfg(feature = "ipc")]
     if let Some(ironvars) = config.ironvar_defaults.take() {
         let variable_manager = Ironbar::variable_manager();
@@ -345,54 +617,65 @@ fn load_output_bars(
 
     let config = ironbar.config.borrow();
     let display = get_display();
+    let monitor = output_monitor(&display, output);
 
-    let pos = output.logical_position.unwrap_or_default();
-    let monitor = display
-        .monitor_at_point(pos.0, pos.1)
-        .expect("monitor to exist");
-
-    let show_default_bar =
-        config.bar.start.is_some() || config.bar.center.is_some() || config.bar.end.is_some();
-
-    let bars = match config
-        .monitors
-        .as_ref()
-        .and_then(|config| config.get(monitor_name))
-    {
-        Some(MonitorConfig::Single(config)) => {
-            vec![create_bar(
+    let index = (0..display.n_monitors())
+        .find(|&i| display.monitor(i).as_ref() == Some(&monitor))
+        .and_then(|i| usize::try_from(i).ok());
+
+    let bars = config
+        .bars_for_monitor(monitor_name, index)
+        .into_iter()
+        .map(|config| {
+            create_bar(
                 app,
                 &monitor,
                 monitor_name.to_string(),
-                config.clone(),
+                config,
                 ironbar.clone(),
-            )?]
-        }
-        Some(MonitorConfig::Multiple(configs)) => configs
-            .iter()
-            .map(|config| {
-                create_bar(
-                    app,
-                    &monitor,
-                    monitor_name.to_string(),
-                    config.clone(),
-                    ironbar.clone(),
-                )
-            })
-            .collect::<Result<_>>()?,
-        None if show_default_bar => vec![create_bar(
-            app,
-            &monitor,
-            monitor_name.to_string(),
-            config.bar.clone(),
-            ironbar.clone(),
-        )?],
-        None => vec![],
-    };
+            )
+        })
+        .collect::<Result<_>>()?;
 
     Ok(bars)
 }
 
+/// Gets the GDK monitor for an output.
+fn output_monitor(display: &Display, output: &OutputInfo) -> Monitor {
+    let pos = output.logical_position.unwrap_or_default();
+    display
+        .monitor_at_point(pos.0, pos.1)
+        .expect("monitor to exist")
+}
+
+/// Recreates the bars on an output whenever its scale factor changes,
+/// as their images are loaded for the scale of the output.
+fn watch_scale_factor(ironbar: &Rc<Ironbar>, app: &Application, output: &OutputInfo) {
+    let Some(monitor_name) = output.name.clone() else {
+        return;
+    };
+
+    let ironbar = ironbar.clone();
+    let app = app.clone();
+    let output = output.clone();
+
+    output_monitor(&get_display(), &output).connect_scale_factor_notify(move |monitor| {
+        info!(
+            "Scale factor of '{monitor_name}' changed to {}, reloading bars",
+            monitor.scale_factor()
+        );
+
+        for bar in ironbar.remove_output_bars(&monitor_name) {
+            bar.close();
+        }
+
+        match load_output_bars(&ironbar, &app, &output) {
+            Ok(mut new_bars) => ironbar.bars.borrow_mut().append(&mut new_bars),
+            Err(err) => error!("{err:?}"),
+        }
+    });
+}
+
 fn create_runtime() -> Runtime {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -401,12 +684,18 @@ fn create_runtime() -> Runtime {
 }
 
 /// Calls `spawn` on the Tokio runtime.
+///
+/// If called while creating a module, or from one of its tasks,
+/// the task is tracked so that it is cancelled along with the module.
 pub fn spawn<F>(f: F) -> JoinHandle<F::Output>
 where
     F: Future + Send + 'static,
     F::Output: Send + 'static,
 {
-    Ironbar::runtime().spawn(f)
+    match TaskScope::current() {
+        Some(scope) => scope.spawn(f),
+        None => Ironbar::runtime().spawn(f),
+    }
 }
 
 /// Calls `spawn_blocking` on the Tokio runtime.
@@ -0,0 +1,172 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::Debug;
+use std::rc::Rc;
+use tokio::sync::broadcast;
+
+use crate::send;
+
+/// Checks whether the `next` update replaces the `previous` one entirely.
+pub type Supersedes<T> = fn(next: &T, previous: &T) -> bool;
+
+/// Updates received within a single frame.
+#[derive(Debug)]
+pub struct UpdateBatch<T> {
+    updates: Vec<T>,
+    supersedes: Supersedes<T>,
+}
+
+impl<T> UpdateBatch<T> {
+    pub fn new(supersedes: Supersedes<T>) -> Self {
+        Self {
+            updates: vec![],
+            supersedes,
+        }
+    }
+
+    /// Adds an update to the batch,
+    /// dropping any earlier updates which it supersedes.
+    ///
+    /// The latest update is always kept,
+    /// and the order of those remaining is unchanged.
+    pub fn push(&mut self, update: T) {
+        let supersedes = self.supersedes;
+        self.updates
+            .retain(|previous| !supersedes(&update, previous));
+        self.updates.push(update);
+    }
+
+    /// Takes the updates to apply, oldest first,
+    /// leaving the batch empty.
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.updates)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+}
+
+/// Forwards updates to the module widgets,
+/// coalescing those received within the same main loop iteration
+/// so that a burst of updates only causes a single redraw.
+#[derive(Debug, Clone)]
+pub struct BatchedSender<T> {
+    tx: broadcast::Sender<T>,
+    batch: Rc<RefCell<UpdateBatch<T>>>,
+    scheduled: Rc<Cell<bool>>,
+}
+
+impl<T> BatchedSender<T>
+where
+    T: Debug + Clone + 'static,
+{
+    pub fn new(tx: broadcast::Sender<T>, supersedes: Supersedes<T>) -> Self {
+        Self {
+            tx,
+            batch: Rc::new(RefCell::new(UpdateBatch::new(supersedes))),
+            scheduled: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Queues an update to be sent once the main loop is idle.
+    pub fn send(&self, update: T) {
+        self.batch.borrow_mut().push(update);
+
+        if self.scheduled.replace(true) {
+            return;
+        }
+
+        let sender = self.clone();
+        glib::idle_add_local_once(move || sender.flush());
+    }
+
+    /// Sends all queued updates immediately.
+    ///
+    /// This should be called before handling any other event,
+    /// so that it is not applied ahead of earlier updates.
+    pub fn flush(&self) {
+        self.scheduled.set(false);
+
+        let updates = self.batch.borrow_mut().take();
+        for update in updates {
+            send!(self.tx, update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Event {
+        Progress(u32),
+        Song(u32),
+    }
+
+    fn same_variant(next: &Event, previous: &Event) -> bool {
+        std::mem::discriminant(next) == std::mem::discriminant(previous)
+    }
+
+    #[test]
+    fn test_keeps_last_update() {
+        let mut batch = UpdateBatch::new(|_, _| true);
+        for i in 0..10 {
+            batch.push(i);
+        }
+
+        assert_eq!(batch.take(), [9]);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_keeps_all_by_default() {
+        let mut batch = UpdateBatch::new(|_, _| false);
+        for i in 0..3 {
+            batch.push(i);
+        }
+
+        assert_eq!(batch.take(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_keeps_last_of_each_variant() {
+        let mut batch = UpdateBatch::new(same_variant);
+        batch.push(Event::Song(1));
+        batch.push(Event::Progress(1));
+        batch.push(Event::Progress(2));
+        batch.push(Event::Song(2));
+        batch.push(Event::Progress(3));
+
+        assert_eq!(batch.take(), [Event::Song(2), Event::Progress(3)]);
+    }
+
+    #[test]
+    fn test_separate_windows() {
+        let mut batch = UpdateBatch::new(|_, _| true);
+        batch.push(1);
+        batch.push(2);
+        assert_eq!(batch.take(), [2]);
+
+        batch.push(3);
+        assert_eq!(batch.take(), [3]);
+    }
+
+    #[test]
+    fn test_sender_flushes_latest_when_idle() {
+        crate::gtk_helpers::with_gtk(|| {
+            let (tx, mut rx) = broadcast::channel(16);
+            let sender = BatchedSender::new(tx, |_, _| true);
+
+            for i in 0..5 {
+                sender.send(i);
+            }
+            assert!(rx.try_recv().is_err());
+
+            while glib::MainContext::default().iteration(false) {}
+
+            assert_eq!(rx.try_recv(), Ok(4));
+            assert!(rx.try_recv().is_err());
+        });
+    }
+}
@@ -0,0 +1,253 @@
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::Button;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::error;
+
+use crate::clients::logind;
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::image::new_icon_label;
+use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CaffeineModule {
+    /// Icon to show while inhibiting.
+    #[serde(default = "default_icon_on")]
+    icon_on: String,
+
+    /// Icon to show while not inhibiting.
+    #[serde(default = "default_icon_off")]
+    icon_off: String,
+
+    #[serde(default = "default_icon_size")]
+    icon_size: i32,
+
+    /// Colon-separated list of operations to inhibit.
+    #[serde(default = "default_inhibit")]
+    inhibit: String,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_icon_on() -> String {
+    String::from("󰅶")
+}
+
+fn default_icon_off() -> String {
+    String::from("󰾪")
+}
+
+const fn default_icon_size() -> i32 {
+    24
+}
+
+fn default_inhibit() -> String {
+    String::from("sleep:idle")
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InhibitState {
+    #[default]
+    Inactive,
+    /// Waiting for logind to grant the lock.
+    Pending,
+    Active,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InhibitEvent {
+    Toggle,
+    Acquired,
+    Failed,
+}
+
+/// Work the controller needs to do after a state change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    Acquire,
+    Release,
+}
+
+impl InhibitState {
+    const fn reduce(self, event: InhibitEvent) -> (Self, Option<Effect>) {
+        match (self, event) {
+            (Self::Inactive, InhibitEvent::Toggle) => (Self::Pending, Some(Effect::Acquire)),
+            (Self::Active, InhibitEvent::Toggle) => (Self::Inactive, Some(Effect::Release)),
+            (Self::Pending, InhibitEvent::Acquired) => (Self::Active, None),
+            (Self::Pending, InhibitEvent::Failed) => (Self::Inactive, None),
+            // ignore toggles while waiting, and stale results
+            (state, _) => (state, None),
+        }
+    }
+}
+
+impl Module<Button> for CaffeineModule {
+    type SendMessage = InhibitState;
+    type ReceiveMessage = ();
+
+    module_impl!("caffeine");
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        // fails if logind is unavailable, disabling the module
+        let client = context.try_client::<logind::Client>()?;
+
+        let tx = context.tx.clone();
+        let what = self.inhibit.clone();
+
+        spawn(async move { run_inhibitor(&client, &what, &mut rx, &tx, |state| state).await });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = Button::new();
+
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        button.add(&container);
+
+        let scale = info.scale_factor();
+        let icon_on = new_icon_label(&self.icon_on, info.icon_theme, self.icon_size, scale);
+        let icon_off = new_icon_label(&self.icon_off, info.icon_theme, self.icon_size, scale);
+
+        container.add(&icon_on);
+        container.add(&icon_off);
+
+        let tx = context.controller_tx.clone();
+        button.connect_clicked(move |_| {
+            try_send!(tx, ());
+        });
+
+        {
+            let button = button.clone();
+
+            glib_recv!(context.subscribe(), state => {
+                let active = state == InhibitState::Active;
+
+                icon_on.set_visible(active);
+                icon_off.set_visible(!active);
+
+                for (class, enabled) in [
+                    ("active", active),
+                    ("pending", state == InhibitState::Pending),
+                ] {
+                    if enabled {
+                        button.add_class(class);
+                    } else {
+                        button.style_context().remove_class(class);
+                    }
+                }
+            });
+        }
+
+        Ok(ModuleParts {
+            widget: button,
+            popup: None,
+        })
+    }
+}
+
+/// Takes and releases an inhibitor lock on `what` each time a toggle is received on `rx`,
+/// sending each change of state to `tx`.
+///
+/// This is shared with the quick settings module, so `map` wraps the state into its update type.
+pub(crate) async fn run_inhibitor<T: Clone + std::fmt::Debug>(
+    client: &logind::Client,
+    what: &str,
+    rx: &mut mpsc::Receiver<()>,
+    tx: &mpsc::Sender<ModuleUpdateEvent<T>>,
+    map: impl Fn(InhibitState) -> T,
+) {
+    let mut state = InhibitState::default();
+
+    // the lock is held for as long as this is `Some`,
+    // and released when the module is dropped on shutdown
+    let mut inhibitor = None;
+
+    send_async!(tx, ModuleUpdateEvent::Update(map(state)));
+
+    while rx.recv().await.is_some() {
+        let (new_state, effect) = state.reduce(InhibitEvent::Toggle);
+        state = new_state;
+
+        match effect {
+            Some(Effect::Acquire) => {
+                send_async!(tx, ModuleUpdateEvent::Update(map(state)));
+
+                let event = match client.inhibit(what, "Caffeine enabled").await {
+                    Ok(lock) => {
+                        inhibitor = Some(lock);
+                        InhibitEvent::Acquired
+                    }
+                    Err(err) => {
+                        error!("Failed to inhibit '{what}': {err:?}");
+                        InhibitEvent::Failed
+                    }
+                };
+
+                (state, _) = state.reduce(event);
+            }
+            Some(Effect::Release) => inhibitor = None,
+            None => {}
+        }
+
+        send_async!(tx, ModuleUpdateEvent::Update(map(state)));
+    }
+
+    drop(inhibitor);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_on_and_off() {
+        let (state, effect) = InhibitState::Inactive.reduce(InhibitEvent::Toggle);
+        assert_eq!(state, InhibitState::Pending);
+        assert_eq!(effect, Some(Effect::Acquire));
+
+        let (state, effect) = state.reduce(InhibitEvent::Acquired);
+        assert_eq!(state, InhibitState::Active);
+        assert_eq!(effect, None);
+
+        let (state, effect) = state.reduce(InhibitEvent::Toggle);
+        assert_eq!(state, InhibitState::Inactive);
+        assert_eq!(effect, Some(Effect::Release));
+    }
+
+    #[test]
+    fn test_failed_inhibit() {
+        let (state, effect) = InhibitState::Pending.reduce(InhibitEvent::Failed);
+        assert_eq!(state, InhibitState::Inactive);
+        assert_eq!(effect, None);
+    }
+
+    #[test]
+    fn test_toggle_while_pending() {
+        let (state, effect) = InhibitState::Pending.reduce(InhibitEvent::Toggle);
+        assert_eq!(state, InhibitState::Pending);
+        assert_eq!(effect, None);
+    }
+
+    #[test]
+    fn test_stale_result_ignored() {
+        let (state, effect) = InhibitState::Inactive.reduce(InhibitEvent::Acquired);
+        assert_eq!(state, InhibitState::Inactive);
+        assert_eq!(effect, None);
+    }
+}
@@ -1,23 +1,29 @@
+use crate::command::Command;
 use crate::config::CommonConfig;
 use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
 use crate::{glib_recv, module_impl, spawn, try_send};
+#[cfg(feature = "ipc")]
+use crate::{write_lock, Ironbar};
 use cairo::{Format, ImageSurface};
 use glib::translate::IntoGlibPtr;
 use glib::Propagation;
 use gtk::prelude::*;
 use gtk::DrawingArea;
-use mlua::{Error, Function, LightUserData};
+use mlua::{Error, Function, LightUserData, Lua, Table, Variadic};
 use notify::event::ModifyKind;
 use notify::{recommended_watcher, Event, EventKind, RecursiveMode, Watcher};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::Duration;
 use tokio::sync::mpsc::Receiver;
 use tokio::time::sleep;
 use tracing::{debug, error};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct CairoModule {
     path: PathBuf,
 
@@ -116,11 +122,20 @@ impl Module<gtk::Box> for CairoModule {
             .borrow_mut()
             .lua(&context.ironbar.config_dir);
 
-        // this feels kinda dirty,
-        // but it keeps draw functions separate in the global scope
-        let script = fs::read_to_string(&self.path)?
-            .replace("function draw", format!("function __draw_{id}").as_str());
-        lua.load(&script).exec()?;
+        let api = {
+            let popup = context.popup.clone();
+            // held weakly, as the Lua state is itself owned by ironbar
+            let ironbar = Rc::downgrade(&context.ironbar);
+
+            let api = create_api(&lua, move |command| match ironbar.upgrade() {
+                Some(ironbar) => command.run(&popup, &ironbar),
+                None => Ok(()),
+            })?;
+            Rc::new(lua.create_registry_value(api)?)
+        };
+
+        let script = fs::read_to_string(&self.path)?;
+        load_script(&lua, &id, &script, &lua.registry_value(&api)?)?;
 
         {
             let lua = lua.clone();
@@ -146,14 +161,7 @@ impl Module<gtk::Box> for CairoModule {
                 if let Err(err) =
                     function.call::<_, Option<bool>>((id.as_str(), LightUserData(ptr)))
                 {
-                    match err {
-                        Error::RuntimeError(message) => {
-                            let message = message.split_once("]:").expect("to exist").1;
-                            error!("[lua runtime error] {}:{message}", path.display())
-                        }
-                        _ => error!("{err}"),
-                    }
-
+                    log_error(&path, &err);
                     return Propagation::Stop;
                 }
 
@@ -161,6 +169,18 @@ impl Module<gtk::Box> for CairoModule {
             });
         }
 
+        // the draw function is global, so is removed along with the module
+        {
+            let lua = lua.clone();
+            let id = id.clone();
+
+            area.connect_destroy(move |_| {
+                if let Err(err) = lua.globals().set(format!("__draw_{id}"), mlua::Nil) {
+                    error!("{err}");
+                }
+            });
+        }
+
         area.set_size_request(self.width as i32, self.height as i32);
         container.add(&area);
 
@@ -171,24 +191,29 @@ impl Module<gtk::Box> for CairoModule {
             }
         });
 
-        glib_recv!(context.subscribe(), _ev => {
-            let res = fs::read_to_string(&self.path)
-                .map(|s| s.replace("function draw", format!("function __draw_{id}").as_str()));
-
-            match res {
-                Ok(script) => {
-                    match lua.load(&script).exec() {
-                        Ok(_) => {},
-                        Err(Error::SyntaxError { message, ..}) => {
-                            let message = message.split_once("]:").expect("to exist").1;
-                            error!("[lua syntax error] {}:{message}", self.path.display())
-                        },
-                        Err(err) => error!("lua error: {err:?}")
-                    }
+        // the previous script keeps drawing if the new one fails to load
+        let reload = {
+            let lua = lua.clone();
+            let path = self.path.clone();
+
+            move || match fs::read_to_string(&path) {
+                Ok(script) => match lua
+                    .registry_value(&api)
+                    .and_then(|api| load_script(&lua, &id, &script, &api))
+                {
+                    Ok(()) => debug!("reloaded {}", path.display()),
+                    Err(err) => log_error(&path, &err),
                 },
-                Err(err) => error!("{err:?}")
+                Err(err) => error!("{err:?}"),
             }
-        });
+        };
+
+        {
+            let reload = reload.clone();
+            glib_recv!(context.subscribe(), _ev => reload());
+        }
+
+        glib_recv!(lua.subscribe_reload(), _ev => reload());
 
         Ok(ModuleParts {
             widget: container,
@@ -196,3 +221,275 @@ impl Module<gtk::Box> for CairoModule {
         })
     }
 }
+
+/// Loads a script for the module with the given ID,
+/// making its `draw` function available to `draw.lua`.
+///
+/// The script runs in its own environment,
+/// which falls back to the global scope for reads.
+/// This keeps each module's draw function and state separate,
+/// and means globals set by the script are reset when it reloads.
+///
+/// If the script fails to load,
+/// the previously loaded draw function is kept.
+fn load_script(lua: &Lua, id: &str, script: &str, api: &Table) -> mlua::Result<()> {
+    let env = lua.create_table()?;
+    env.set("ironbar", api.clone())?;
+
+    let meta = lua.create_table()?;
+    meta.set("__index", lua.globals())?;
+    env.set_metatable(Some(meta));
+
+    lua.load(script).set_environment(env.clone()).exec()?;
+
+    let draw = env.get::<_, Option<Function>>("draw")?.ok_or_else(|| {
+        Error::RuntimeError(String::from("script does not define a `draw` function"))
+    })?;
+
+    lua.globals().set(format!("__draw_{id}"), draw)
+}
+
+/// Creates the `ironbar` table available to scripts.
+///
+/// `ironbar.command` runs a command using the same syntax as keybinds,
+/// passing it to `dispatch`.
+/// Invalid commands raise a Lua error rather than being ignored,
+/// so that scripts can handle them with `pcall`.
+fn create_api<F>(lua: &Lua, dispatch: F) -> mlua::Result<Table<'_>>
+where
+    F: Fn(Command) -> color_eyre::Result<()> + 'static,
+{
+    let api = lua.create_table()?;
+
+    let command = lua.create_function(move |_, (command, args): (String, Variadic<String>)| {
+        parse_command(&command, &args)
+            .and_then(&dispatch)
+            .map_err(|err| Error::RuntimeError(format!("ironbar.command: {err}")))
+    })?;
+    api.set("command", command)?;
+
+    #[cfg(feature = "ipc")]
+    {
+        let set_var = lua.create_function(|_, (key, value): (String, String)| {
+            write_lock!(Ironbar::variable_manager())
+                .set(key.into(), value)
+                .map_err(|err| Error::RuntimeError(format!("ironbar.set_var: {err}")))
+        })?;
+        api.set("set_var", set_var)?;
+    }
+
+    Ok(api)
+}
+
+/// Builds a command from the arguments passed to `ironbar.command`.
+///
+/// Arguments are appended to the command separated by `:`,
+/// so `("popup:toggle", "clock")` becomes `popup:toggle:clock`.
+/// For shell commands, they are separated by spaces instead.
+fn parse_command(command: &str, args: &[String]) -> color_eyre::Result<Command> {
+    let separator = if command.starts_with('!') { " " } else { ":" };
+
+    let command = std::iter::once(command)
+        .chain(args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    Command::parse_standalone(&command)
+}
+
+fn log_error(path: &Path, err: &Error) {
+    // strip the chunk name, which is the script contents
+    let strip = |message: &str| {
+        message
+            .split_once("]:")
+            .map_or_else(|| message.to_string(), |(_, message)| message.to_string())
+    };
+
+    match err {
+        Error::SyntaxError { message, .. } => {
+            error!("[lua syntax error] {}:{}", path.display(), strip(message));
+        }
+        Error::RuntimeError(message) => {
+            error!("[lua runtime error] {}:{}", path.display(), strip(message));
+        }
+        Error::CallbackError { cause, .. } => log_error(path, cause),
+        _ => error!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn load_script(lua: &Lua, id: &str, script: &str) -> mlua::Result<()> {
+        let api = lua.create_table()?;
+        super::load_script(lua, id, script, &api)
+    }
+
+    /// Loads a script with an API which records the commands it receives.
+    fn load_recorded(lua: &Lua, script: &str) -> Rc<RefCell<Vec<Command>>> {
+        let commands = Rc::new(RefCell::new(vec![]));
+
+        let api = {
+            let commands = commands.clone();
+            create_api(lua, move |command| {
+                commands.borrow_mut().push(command);
+                Ok(())
+            })
+            .unwrap()
+        };
+
+        super::load_script(lua, "1", script, &api).unwrap();
+        commands
+    }
+
+    fn draw(lua: &Lua) -> i32 {
+        let draw: Function = lua.globals().get("__draw_1").unwrap();
+        draw.call(()).unwrap()
+    }
+
+    #[test]
+    fn test_load_script() {
+        let lua = Lua::new();
+
+        load_script(&lua, "1", "function draw() return 1 end").unwrap();
+        assert_eq!(draw(&lua), 1);
+
+        load_script(&lua, "1", "function draw() return 2 end").unwrap();
+        assert_eq!(draw(&lua), 2);
+    }
+
+    #[test]
+    fn test_syntax_error_keeps_previous() {
+        let lua = Lua::new();
+        load_script(&lua, "1", "function draw() return 1 end").unwrap();
+
+        let res = load_script(&lua, "1", "function draw() return 2");
+        assert!(matches!(res, Err(Error::SyntaxError { .. })));
+        assert_eq!(draw(&lua), 1);
+    }
+
+    #[test]
+    fn test_runtime_error_keeps_previous() {
+        let lua = Lua::new();
+        load_script(&lua, "1", "function draw() return 1 end").unwrap();
+
+        // the new draw function is defined before the error
+        let res = load_script(&lua, "1", "function draw() return 2 end error('oops')");
+        assert!(matches!(res, Err(Error::RuntimeError(_))));
+        assert_eq!(draw(&lua), 1);
+
+        let res = load_script(&lua, "1", "local x = 1");
+        assert!(res.is_err());
+        assert_eq!(draw(&lua), 1);
+    }
+
+    #[test]
+    fn test_state_reset_on_reload() {
+        let lua = Lua::new();
+        lua.globals().set("shared", 10).unwrap();
+
+        let script = "count = (count or 0) + shared; function draw() return count end";
+
+        load_script(&lua, "1", script).unwrap();
+        assert_eq!(draw(&lua), 10);
+
+        // script globals start fresh, but the global scope is still readable
+        load_script(&lua, "1", script).unwrap();
+        assert_eq!(draw(&lua), 10);
+        assert!(lua
+            .globals()
+            .get::<_, Option<i32>>("count")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_command_dispatch() {
+        let lua = Lua::new();
+        let commands = load_recorded(
+            &lua,
+            r#"
+            function draw()
+                ironbar.command("popup:toggle", "clock")
+                ironbar.command("popup:open:clock")
+                ironbar.command("popup:close")
+                ironbar.command("!notify-send", "hello")
+                return 0
+            end
+            "#,
+        );
+
+        draw(&lua);
+
+        assert_eq!(
+            *commands.borrow(),
+            [
+                Command::TogglePopup(Some("clock".to_string())),
+                Command::OpenPopup(Some("clock".to_string())),
+                Command::ClosePopup,
+                Command::Script("notify-send hello".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_visibility() {
+        let lua = Lua::new();
+        let commands = load_recorded(
+            &lua,
+            "function draw() ironbar.command('bar:toggle') return 0 end",
+        );
+
+        draw(&lua);
+
+        assert!(matches!(commands.borrow()[..], [Command::Visibility(_)]));
+    }
+
+    #[test]
+    fn test_invalid_command_raises_error() {
+        let lua = Lua::new();
+        let commands = load_recorded(
+            &lua,
+            r#"
+            function draw()
+                local ok, err = pcall(ironbar.command, "popup:toggle")
+                assert(not ok)
+                assert(string.find(tostring(err), "custom widgets"))
+
+                ironbar.command("not-a-command")
+            end
+            "#,
+        );
+
+        let draw: Function = lua.globals().get("__draw_1").unwrap();
+        let err = draw.call::<_, ()>(()).unwrap_err();
+
+        assert!(err.to_string().contains("Invalid command: 'not-a-command'"));
+        assert!(commands.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_dispatch_error_raised() {
+        let lua = Lua::new();
+        let api = create_api(&lua, |_| Err(color_eyre::Report::msg("Popup not found"))).unwrap();
+        super::load_script(
+            &lua,
+            "1",
+            r#"
+            function draw()
+                local ok, err = pcall(ironbar.command, "popup:open:missing")
+                if ok or not string.find(tostring(err), "Popup not found") then
+                    return 0
+                end
+                return 1
+            end
+            "#,
+            &api,
+        )
+        .unwrap();
+
+        assert_eq!(draw(&lua), 1);
+    }
+}
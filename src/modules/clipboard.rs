@@ -11,12 +11,14 @@ use gtk::gdk_pixbuf::Pixbuf;
 use gtk::gio::{Cancellable, MemoryInputStream};
 use gtk::prelude::*;
 use gtk::{Button, EventBox, Image, Label, Orientation, RadioButton, Widget};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ClipboardModule {
     #[serde(default = "default_icon")]
     icon: String,
@@ -52,12 +54,14 @@ pub enum ControllerEvent {
     Remove(usize),
     Activate(usize),
     Deactivate,
+    Pin(usize, bool),
 }
 
 #[derive(Debug, Clone)]
 pub enum UIEvent {
     Copy(usize),
     Remove(usize),
+    Pin(usize, bool),
 }
 
 impl Module<Button> for ClipboardModule {
@@ -98,6 +102,12 @@ impl Module<Button> for ClipboardModule {
                     ClipboardEvent::Activate(id) => {
                         try_send!(tx, ModuleUpdateEvent::Update(ControllerEvent::Activate(id)));
                     }
+                    ClipboardEvent::Pin(id, pinned) => {
+                        try_send!(
+                            tx,
+                            ModuleUpdateEvent::Update(ControllerEvent::Pin(id, pinned))
+                        );
+                    }
                 }
             }
 
@@ -112,6 +122,7 @@ impl Module<Button> for ClipboardModule {
                 match event {
                     UIEvent::Copy(id) => client.copy(id),
                     UIEvent::Remove(id) => client.remove(id),
+                    UIEvent::Pin(id, pinned) => client.set_pinned(id, pinned),
                 }
             }
         });
@@ -124,7 +135,12 @@ impl Module<Button> for ClipboardModule {
         context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
         info: &ModuleInfo,
     ) -> color_eyre::Result<ModuleParts<Button>> {
-        let button = new_icon_button(&self.icon, info.icon_theme, self.icon_size);
+        let button = new_icon_button(
+            &self.icon,
+            info.icon_theme,
+            self.icon_size,
+            info.scale_factor(),
+        );
         button.style_context().add_class("btn");
 
         let tx = context.tx.clone();
@@ -152,6 +168,11 @@ impl Module<Button> for ClipboardModule {
     {
         let container = gtk::Box::new(Orientation::Vertical, 10);
 
+        // pinned items are kept separately, above the history
+        let pinned_entries = gtk::Box::new(Orientation::Vertical, 5);
+        pinned_entries.style_context().add_class("pinned");
+        container.add(&pinned_entries);
+
         let entries = gtk::Box::new(Orientation::Vertical, 5);
         container.add(&entries);
 
@@ -233,13 +254,30 @@ impl Module<Button> for ClipboardModule {
                             );
                         }
 
+                        let pin_button = Button::with_label("󰐃");
+                        pin_button.set_widget_name(&format!("pin-{id}"));
+                        pin_button.style_context().add_class("btn-pin");
+
+                        {
+                            let tx = tx.clone();
+                            let row = row.clone();
+
+                            pin_button.connect_clicked(move |button| {
+                                let id = get_button_id(button)
+                                    .expect("Failed to get id from button name");
+                                let pinned = !row.style_context().has_class("pinned");
+
+                                debug!("Setting item with id {id} pinned: {pinned}");
+                                try_send!(tx, UIEvent::Pin(id, pinned));
+                            });
+                        }
+
                         let remove_button = Button::with_label("x");
                         remove_button.set_widget_name(&format!("remove-{id}"));
                         remove_button.style_context().add_class("btn-remove");
 
                         {
                             let tx = tx.clone();
-                            let entries = entries.clone();
                             let row = row.clone();
 
                             remove_button.connect_clicked(move |button| {
@@ -249,12 +287,13 @@ impl Module<Button> for ClipboardModule {
                                 debug!("Removing item with id: {id}");
                                 try_send!(tx, UIEvent::Remove(id));
 
-                                entries.remove(&row);
+                                remove_row(&row);
                             });
                         }
 
                         row.add(&button_wrapper);
                         row.pack_end(&remove_button, false, false, 0);
+                        row.pack_end(&pin_button, false, false, 0);
 
                         entries.add(&row);
                         entries.reorder_child(&row, 0);
@@ -270,7 +309,7 @@ impl Module<Button> for ClipboardModule {
                                 hidden_option.set_active(true);
                             }
 
-                            entries.remove(&row);
+                            remove_row(&row);
                         }
                     }
                     ControllerEvent::Activate(id) => {
@@ -286,6 +325,22 @@ impl Module<Button> for ClipboardModule {
                         debug!("Deactivating current option");
                         hidden_option.set_active(true);
                     }
+                    ControllerEvent::Pin(id, pinned) => {
+                        debug!("Setting option with ID {id} pinned: {pinned}");
+
+                        if let Some((row, _)) = items.get(&id) {
+                            remove_row(row);
+
+                            if pinned {
+                                row.style_context().add_class("pinned");
+                                pinned_entries.add(row);
+                            } else {
+                                row.style_context().remove_class("pinned");
+                                entries.add(row);
+                                entries.reorder_child(row, 0);
+                            }
+                        }
+                    }
                 }
             });
         }
@@ -297,6 +352,16 @@ impl Module<Button> for ClipboardModule {
     }
 }
 
+/// Removes an item row from whichever list it is in.
+fn remove_row(row: &gtk::Box) {
+    if let Some(parent) = row
+        .parent()
+        .and_then(|parent| parent.downcast::<gtk::Box>().ok())
+    {
+        parent.remove(row);
+    }
+}
+
 /// Gets the ID from a widget's name.
 ///
 /// This expects the button name to be
@@ -1,21 +1,28 @@
+use std::cell::Cell;
 use std::env;
+use std::ffi::CString;
+use std::rc::Rc;
 
-use chrono::{DateTime, Local, Locale};
+use chrono::{DateTime, Datelike, Local, Locale, NaiveDate};
 use color_eyre::Result;
 use gtk::prelude::*;
 use gtk::{Align, Button, Calendar, Label, Orientation};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::{broadcast, mpsc};
 use tokio::time::sleep;
+use tracing::debug;
 
 use crate::config::{CommonConfig, ModuleOrientation};
 use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::{
     Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
 };
+use crate::script::{Script, ScriptInput};
 use crate::{glib_recv, module_impl, send_async, spawn, try_send};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ClockModule {
     /// Date/time format string.
     /// Default: `%d/%m/%Y %H:%M`
@@ -34,6 +41,14 @@ pub struct ClockModule {
     #[serde(default)]
     orientation: ModuleOrientation,
 
+    /// Whether to show week numbers on the popup calendar.
+    #[serde(default)]
+    show_week_numbers: bool,
+
+    /// Command to run when a date is picked on the popup calendar.
+    /// The date is passed as an argument, formatted as `YYYY-MM-DD`.
+    on_date_select: Option<ScriptInput>,
+
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
 }
@@ -45,6 +60,8 @@ impl Default for ClockModule {
             format_popup: default_popup_format(),
             locale: default_locale(),
             orientation: ModuleOrientation::Horizontal,
+            show_week_numbers: false,
+            on_date_select: None,
             common: Some(CommonConfig::default()),
         }
     }
@@ -71,12 +88,90 @@ fn strip_tail(string: String) -> String {
         .unwrap_or(string)
 }
 
+/// Gets the C library locale names to try for a locale such as `en_GB`,
+/// in order of preference.
+fn locale_names(locale: &str) -> Vec<String> {
+    if locale.contains('.') || !locale.contains('_') {
+        return vec![locale.to_string()];
+    }
+
+    vec![
+        format!("{locale}.UTF-8"),
+        format!("{locale}.utf8"),
+        locale.to_string(),
+    ]
+}
+
+/// `LC_GLOBAL_LOCALE` from the C library,
+/// which is not exported by the `libc` crate.
+const LC_GLOBAL_LOCALE: libc::locale_t = -1isize as libc::locale_t;
+
+/// Creates a C library locale with its time category set to `locale`,
+/// trying each name from [`locale_names`] in turn.
+/// Returns `None` if the locale is not installed.
+fn new_time_locale(locale: &str) -> Option<libc::locale_t> {
+    locale_names(locale)
+        .into_iter()
+        .filter_map(|name| CString::new(name).ok())
+        .find_map(|name| {
+            // SAFETY: `duplocale` creates a copy of the global locale, which `newlocale`
+            // either takes ownership of or leaves untouched on failure.
+            unsafe {
+                let base = libc::duplocale(LC_GLOBAL_LOCALE);
+                let handle = libc::newlocale(libc::LC_TIME_MASK, name.as_ptr(), base);
+
+                if handle.is_null() {
+                    libc::freelocale(base);
+                    None
+                } else {
+                    Some(handle)
+                }
+            }
+        })
+}
+
+/// Runs `f` with the current thread's time locale set to `locale`,
+/// restoring the previous locale afterwards.
+///
+/// GTK reads the first day of the week from the C library
+/// when a calendar is created, so this is used to make it follow the configured locale.
+/// If the locale is not installed, `f` runs with the current locale.
+fn with_time_locale<T>(locale: &str, f: impl FnOnce() -> T) -> T {
+    let Some(handle) = new_time_locale(locale) else {
+        debug!("Locale '{locale}' is not installed, using the system locale for the calendar");
+        return f();
+    };
+
+    // SAFETY: `handle` is valid until it is freed,
+    // which only happens once the previous locale is restored.
+    let previous = unsafe { libc::uselocale(handle) };
+    let result = f();
+
+    unsafe {
+        libc::uselocale(previous);
+        libc::freelocale(handle);
+    }
+
+    result
+}
+
+/// Formats a date from a calendar as `YYYY-MM-DD`.
+/// The calendar counts months from zero.
+fn iso_date(year: u32, month0: u32, day: u32) -> Option<String> {
+    NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month0 + 1, day)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
 impl Module<Button> for ClockModule {
     type SendMessage = DateTime<Local>;
     type ReceiveMessage = ();
 
     module_impl!("clock");
 
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
@@ -122,7 +217,7 @@ impl Module<Button> for ClockModule {
         });
 
         let popup = self
-            .into_popup(
+            .into_lazy_popup(
                 context.controller_tx.clone(),
                 context.subscribe(),
                 context,
@@ -150,16 +245,52 @@ impl Module<Button> for ClockModule {
 
         container.add(&clock);
 
-        let calendar = Calendar::new();
+        let calendar = with_time_locale(&self.locale, Calendar::new);
         calendar.add_class("calendar");
+        calendar.set_show_week_numbers(self.show_week_numbers);
         container.add(&calendar);
 
+        // the month shown when a day was last selected.
+        // changing month also selects a day,
+        // which should not count as picking a date.
+        let shown_month = {
+            let (year, month, _) = calendar.date();
+            Rc::new(Cell::new(Some((year, month))))
+        };
+
+        if let Some(on_date_select) = self.on_date_select {
+            let script = Script::from(on_date_select);
+            let shown_month = shown_month.clone();
+
+            calendar.connect_day_selected(move |calendar| {
+                let (year, month, day) = calendar.date();
+                if shown_month.replace(Some((year, month))) != Some((year, month)) {
+                    return;
+                }
+
+                if let Some(date) = iso_date(year, month, day) {
+                    script.run_as_oneshot(Some(&[date]));
+                }
+            });
+        }
+
         let format = self.format_popup;
         let locale = Locale::try_from(self.locale.as_str()).unwrap_or(Locale::POSIX);
 
+        let mut today = Local::now().date_naive();
+
         glib_recv!(rx, date => {
             let date_string = format!("{}", date.format_localized(&format, locale));
             clock.set_label(&date_string);
+
+            // move the calendar on to the new day at midnight
+            if date.date_naive() != today {
+                today = date.date_naive();
+
+                shown_month.set(None);
+                calendar.select_month(today.month0(), today.year() as u32);
+                calendar.select_day(today.day());
+            }
         });
 
         container.show_all();
@@ -167,3 +298,64 @@ impl Module<Button> for ClockModule {
         Some(container)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_names() {
+        assert_eq!(
+            locale_names("de_DE"),
+            ["de_DE.UTF-8", "de_DE.utf8", "de_DE"]
+        );
+        assert_eq!(locale_names("en_GB.UTF-8"), ["en_GB.UTF-8"]);
+        assert_eq!(locale_names("POSIX"), ["POSIX"]);
+    }
+
+    #[test]
+    fn test_with_time_locale_restores() {
+        // SAFETY: passing a null locale only queries the current one
+        let before = unsafe { libc::uselocale(std::ptr::null_mut()) };
+
+        // the C locale is always available
+        assert_eq!(with_time_locale("C", || 1), 1);
+        // missing locales fall back to the current one
+        assert_eq!(with_time_locale("xx_XX", || 2), 2);
+
+        let after = unsafe { libc::uselocale(std::ptr::null_mut()) };
+        assert_eq!(before, after);
+    }
+
+    /// `_NL_TIME_FIRST_WEEKDAY` from glibc's `langinfo.h`,
+    /// which is what GTK reads the calendar's first day of the week from.
+    const NL_TIME_FIRST_WEEKDAY: libc::nl_item = 0x20068;
+
+    /// Gets the current thread's first day of the week,
+    /// where 1 is Sunday.
+    fn first_weekday() -> u8 {
+        // SAFETY: the result points to a string owned by the current locale
+        unsafe { *libc::nl_langinfo(NL_TIME_FIRST_WEEKDAY) as u8 }
+    }
+
+    #[test]
+    fn test_first_weekday_from_locale() {
+        // weeks start on Sunday in the C locale
+        assert_eq!(with_time_locale("C", first_weekday), 1);
+
+        // and on Monday in German locales, where installed
+        if let Some(handle) = new_time_locale("de_DE") {
+            unsafe { libc::freelocale(handle) };
+            assert_eq!(with_time_locale("de_DE", first_weekday), 2);
+        }
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date(2024, 0, 5).as_deref(), Some("2024-01-05"));
+        assert_eq!(iso_date(2024, 11, 31).as_deref(), Some("2024-12-31"));
+        assert_eq!(iso_date(2024, 1, 29).as_deref(), Some("2024-02-29"));
+        assert_eq!(iso_date(2023, 1, 29), None);
+        assert_eq!(iso_date(2024, 0, 0), None);
+    }
+}
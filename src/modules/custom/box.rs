@@ -3,26 +3,67 @@ use crate::build;
 use crate::config::ModuleOrientation;
 use crate::modules::custom::WidgetConfig;
 use gtk::prelude::*;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct BoxWidget {
     name: Option<String>,
     class: Option<String>,
     orientation: Option<ModuleOrientation>,
     widgets: Option<Vec<WidgetConfig>>,
+
+    /// Space in pixels between each child widget.
+    #[serde(default)]
+    spacing: i32,
+    /// Whether all child widgets should be given the same size.
+    #[serde(default)]
+    homogeneous: bool,
+    /// Where to position children along the baseline
+    /// when the box has more space than it needs.
+    #[serde(default)]
+    baseline_position: BaselinePosition,
 }
 
-impl CustomWidget for BoxWidget {
-    type Widget = gtk::Box;
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BaselinePosition {
+    Top,
+    #[default]
+    Center,
+    Bottom,
+}
 
-    fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
-        let container = build!(self, Self::Widget);
+impl From<BaselinePosition> for gtk::BaselinePosition {
+    fn from(position: BaselinePosition) -> Self {
+        match position {
+            BaselinePosition::Top => Self::Top,
+            BaselinePosition::Center => Self::Center,
+            BaselinePosition::Bottom => Self::Bottom,
+        }
+    }
+}
 
+impl BoxWidget {
+    /// Applies the layout options to the container.
+    fn configure(&self, container: &gtk::Box) {
         if let Some(orientation) = self.orientation {
             container.set_orientation(orientation.into());
         }
 
+        container.set_spacing(self.spacing);
+        container.set_homogeneous(self.homogeneous);
+        container.set_baseline_position(self.baseline_position.into());
+    }
+}
+
+impl CustomWidget for BoxWidget {
+    type Widget = gtk::Box;
+
+    fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
+        let container = build!(self, Self::Widget);
+        self.configure(&container);
+
         if let Some(widgets) = self.widgets {
             for widget in widgets {
                 widget.widget.add_to(&container, &context, widget.common);
@@ -32,3 +73,63 @@ impl CustomWidget for BoxWidget {
         container
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtk::Orientation;
+    use serde_json::json;
+
+    #[test]
+    fn test_layout_defaults() {
+        let widget: BoxWidget = serde_json::from_value(json!({})).unwrap();
+
+        assert!(widget.orientation.is_none());
+        assert_eq!(widget.spacing, 0);
+        assert!(!widget.homogeneous);
+        assert_eq!(widget.baseline_position, BaselinePosition::Center);
+    }
+
+    #[test]
+    fn test_layout_parsing() {
+        let widget: BoxWidget = serde_json::from_value(json!({
+            "orientation": "v",
+            "spacing": 8,
+            "homogeneous": true,
+            "baseline_position": "bottom"
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            widget.orientation,
+            Some(ModuleOrientation::Vertical)
+        ));
+        assert_eq!(widget.spacing, 8);
+        assert!(widget.homogeneous);
+        assert_eq!(widget.baseline_position, BaselinePosition::Bottom);
+    }
+
+    #[test]
+    fn test_layout_applied() {
+        // no display available to create widgets on
+        if gtk::init().is_err() {
+            return;
+        }
+
+        let widget: BoxWidget = serde_json::from_value(json!({
+            "orientation": "vertical",
+            "spacing": 4,
+            "homogeneous": true,
+            "baseline_position": "top"
+        }))
+        .unwrap();
+
+        let container = gtk::Box::new(Orientation::Horizontal, 0);
+        widget.configure(&container);
+
+        assert_eq!(container.orientation(), Orientation::Vertical);
+        assert_eq!(container.spacing(), 4);
+        assert!(container.is_homogeneous());
+        assert_eq!(container.baseline_position(), gtk::BaselinePosition::Top);
+    }
+}
@@ -1,15 +1,24 @@
+use glib::{Propagation, SourceId};
 use gtk::prelude::*;
-use gtk::{Button, Label, Orientation};
+use gtk::{Button, GestureLongPress, Label, Orientation, Stack};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
 
 use crate::config::ModuleOrientation;
-use crate::dynamic_value::dynamic_string;
+use crate::dynamic_value::dynamic_string_when_visible;
+use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::PopupButton;
 use crate::{build, try_send};
 
 use super::{CustomWidget, CustomWidgetContext, ExecEvent, WidgetConfig};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct ButtonWidget {
     name: Option<String>,
     class: Option<String>,
@@ -18,6 +27,256 @@ pub struct ButtonWidget {
     widgets: Option<Vec<WidgetConfig>>,
     #[serde(default)]
     orientation: ModuleOrientation,
+
+    /// Whether to repeat `on_click` while the button is held.
+    #[serde(default)]
+    repeat: bool,
+    /// Time in milliseconds before the first repeat.
+    #[serde(default = "default_repeat_delay")]
+    repeat_delay: u64,
+    /// Time in milliseconds between each subsequent repeat.
+    #[serde(default = "default_repeat_interval")]
+    repeat_interval: u64,
+
+    /// Command to execute when the button is held.
+    on_long_press: Option<String>,
+    /// Time in milliseconds the button must be held to trigger `on_long_press`.
+    #[serde(default = "default_long_press_duration")]
+    long_press_duration: u64,
+
+    /// Whether `on_click` must be confirmed with a second click.
+    #[serde(default)]
+    confirm: bool,
+    /// Text shown in place of the button contents while awaiting confirmation.
+    #[serde(default = "default_confirm_label")]
+    confirm_label: String,
+    /// Time in milliseconds before an unconfirmed click is cancelled.
+    #[serde(default = "default_confirm_timeout")]
+    confirm_timeout: u64,
+}
+
+const fn default_repeat_delay() -> u64 {
+    500
+}
+
+const fn default_repeat_interval() -> u64 {
+    100
+}
+
+const fn default_long_press_duration() -> u64 {
+    500
+}
+
+fn default_confirm_label() -> String {
+    String::from("Sure?")
+}
+
+const fn default_confirm_timeout() -> u64 {
+    3000
+}
+
+/// GTK's own default for the `gtk-long-press-time` setting.
+const DEFAULT_LONG_PRESS_TIME: u64 = 500;
+
+/// Bounds of the long-press gesture's `delay-factor` property.
+const LONG_PRESS_DELAY_FACTOR_RANGE: (f64, f64) = (0.5, 2.0);
+
+/// Gets the delay factor to apply to the long-press gesture
+/// so that it triggers after `duration` milliseconds,
+/// given GTK's `gtk-long-press-time` setting.
+///
+/// GTK only accepts factors between `0.5` and `2.0`,
+/// so the resulting delay is clamped to within that range.
+fn long_press_delay_factor(duration: u64, long_press_time: u64) -> f64 {
+    let long_press_time = if long_press_time == 0 {
+        DEFAULT_LONG_PRESS_TIME
+    } else {
+        long_press_time
+    };
+
+    (duration as f64 / long_press_time as f64).clamp(
+        LONG_PRESS_DELAY_FACTOR_RANGE.0,
+        LONG_PRESS_DELAY_FACTOR_RANGE.1,
+    )
+}
+
+/// Decides whether a click should run `on_click`,
+/// resetting the long-press state for the next press.
+///
+/// A click which ends a recognized long-press is suppressed.
+fn should_click(long_pressed: &Cell<bool>) -> bool {
+    !long_pressed.replace(false)
+}
+
+/// Timings for repeating a command while the button is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RepeatTiming {
+    delay: Duration,
+    interval: Duration,
+}
+
+impl RepeatTiming {
+    /// Repeating faster than this would flood the command queue.
+    const MIN_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn new(delay: u64, interval: u64) -> Self {
+        Self {
+            delay: Duration::from_millis(delay),
+            interval: Duration::from_millis(interval).max(Self::MIN_INTERVAL),
+        }
+    }
+
+    /// Gets the time to wait before running the repeat with the given index,
+    /// counting from the initial press.
+    const fn wait_before(self, repeat: u32) -> Duration {
+        if repeat == 0 {
+            self.delay
+        } else {
+            self.interval
+        }
+    }
+}
+
+/// Marks a command as running.
+/// Returns `false` if the previous run has not yet completed.
+fn try_start(in_flight: &AtomicBool) -> bool {
+    !in_flight.swap(true, Ordering::AcqRel)
+}
+
+/// Schedules the next repeat,
+/// which runs `fire` and then schedules the one after.
+///
+/// The pending timeout is stored in `pending` so it can be cancelled.
+fn schedule_repeat(
+    pending: Rc<RefCell<Option<SourceId>>>,
+    timing: RepeatTiming,
+    repeat: u32,
+    fire: Rc<dyn Fn()>,
+) {
+    let source = {
+        let pending = pending.clone();
+        glib::timeout_add_local_once(timing.wait_before(repeat), move || {
+            // this source is removed once run
+            pending.take();
+
+            fire();
+            schedule_repeat(pending, timing, repeat + 1, fire);
+        })
+    };
+
+    pending.replace(Some(source));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfirmState {
+    Idle,
+    /// Clicked once, waiting for the second click.
+    Armed,
+}
+
+/// Advances the confirmation on click.
+/// Returns `true` if this click confirms the command.
+fn confirm_click(state: &Cell<ConfirmState>) -> bool {
+    match state.get() {
+        ConfirmState::Idle => {
+            state.set(ConfirmState::Armed);
+            false
+        }
+        ConfirmState::Armed => {
+            state.set(ConfirmState::Idle);
+            true
+        }
+    }
+}
+
+/// Cancels an unconfirmed click once the timeout ends.
+/// Returns `true` if the button was awaiting confirmation.
+fn confirm_expire(state: &Cell<ConfirmState>) -> bool {
+    state.replace(ConfirmState::Idle) == ConfirmState::Armed
+}
+
+/// Swaps the button contents for a confirmation label on the first click,
+/// reverting after a timeout or once confirmed.
+#[derive(Debug, Clone)]
+struct Confirm {
+    button: Button,
+    stack: Stack,
+    state: Rc<Cell<ConfirmState>>,
+    pending: Rc<RefCell<Option<SourceId>>>,
+    timeout: Duration,
+}
+
+impl Confirm {
+    const CONTENT: &'static str = "content";
+    const CONFIRM: &'static str = "confirm";
+
+    fn new(button: &Button, label: &str, timeout: u64) -> Self {
+        let stack = Stack::new();
+        // size to the visible child, so the bar does not keep space for the label
+        stack.set_hhomogeneous(false);
+
+        let label = Label::new(Some(label));
+        label.add_class("confirm");
+        stack.add_named(&label, Self::CONFIRM);
+
+        button.add(&stack);
+
+        Self {
+            button: button.clone(),
+            stack,
+            state: Rc::new(Cell::new(ConfirmState::Idle)),
+            pending: Rc::new(RefCell::new(None)),
+            timeout: Duration::from_millis(timeout),
+        }
+    }
+
+    /// Adds the button's normal contents.
+    fn add_content(&self, widget: &impl IsA<gtk::Widget>) {
+        self.stack.add_named(widget, Self::CONTENT);
+        self.stack.set_visible_child_name(Self::CONTENT);
+    }
+
+    /// Handles a click.
+    /// Returns `true` if the command should run.
+    fn click(&self) -> bool {
+        cancel_repeat(&self.pending);
+
+        let confirmed = confirm_click(&self.state);
+        self.show_armed(!confirmed);
+
+        if !confirmed {
+            let confirm = self.clone();
+            let source = glib::timeout_add_local_once(self.timeout, move || {
+                // this source is removed once run
+                confirm.pending.take();
+
+                if confirm_expire(&confirm.state) {
+                    confirm.show_armed(false);
+                }
+            });
+
+            self.pending.replace(Some(source));
+        }
+
+        confirmed
+    }
+
+    fn show_armed(&self, armed: bool) {
+        if armed {
+            self.stack.set_visible_child_name(Self::CONFIRM);
+            self.button.add_class("confirming");
+        } else {
+            self.stack.set_visible_child_name(Self::CONTENT);
+            self.button.style_context().remove_class("confirming");
+        }
+    }
+}
+
+/// Cancels the pending repeat, if there is one.
+fn cancel_repeat(pending: &RefCell<Option<SourceId>>) {
+    if let Some(source) = pending.take() {
+        source.remove();
+    }
 }
 
 impl CustomWidget for ButtonWidget {
@@ -27,6 +286,18 @@ impl CustomWidget for ButtonWidget {
         let button = build!(self, Self::Widget);
         context.popup_buttons.borrow_mut().push(button.clone());
 
+        if self.confirm && (self.on_click.is_none() || self.repeat) {
+            warn!("`confirm` is ignored on buttons without `on_click` or with `repeat` enabled");
+        }
+
+        let confirm = (self.confirm && self.on_click.is_some() && !self.repeat)
+            .then(|| Confirm::new(&button, &self.confirm_label, self.confirm_timeout));
+
+        let add_content = |widget: &gtk::Widget| match &confirm {
+            Some(confirm) => confirm.add_content(widget),
+            None => button.add(widget),
+        };
+
         if let Some(widgets) = self.widgets {
             let container = gtk::Box::new(Orientation::Horizontal, 0);
 
@@ -34,16 +305,16 @@ impl CustomWidget for ButtonWidget {
                 widget.widget.add_to(&container, &context, widget.common);
             }
 
-            button.add(&container);
+            add_content(container.upcast_ref());
         } else if let Some(text) = self.label {
             let label = Label::new(None);
             label.set_use_markup(true);
 
             label.set_angle(self.orientation.to_angle());
 
-            button.add(&label);
+            add_content(label.upcast_ref());
 
-            dynamic_string(&text, move |string| {
+            dynamic_string_when_visible(&text, context.visibility.clone(), move |string| {
                 label.set_markup(&string);
             });
         }
@@ -51,18 +322,318 @@ impl CustomWidget for ButtonWidget {
         if let Some(exec) = self.on_click {
             let tx = context.tx.clone();
 
+            if self.repeat {
+                if self.on_long_press.is_some() {
+                    warn!("`on_long_press` is ignored on buttons with `repeat` enabled");
+                }
+
+                let timing = RepeatTiming::new(self.repeat_delay, self.repeat_interval);
+                let in_flight = Arc::new(AtomicBool::new(false));
+                let pending = Rc::new(RefCell::new(None));
+
+                let fire: Rc<dyn Fn()> = {
+                    let button = button.clone();
+                    Rc::new(move || {
+                        // skip this repeat if the command is still running
+                        if !try_start(&in_flight) {
+                            return;
+                        }
+
+                        try_send!(
+                            tx,
+                            ExecEvent {
+                                cmd: exec.clone(),
+                                args: None,
+                                id: button.try_popup_id().unwrap_or(usize::MAX), // may not be a popup button
+                                in_flight: Some(in_flight.clone()),
+                            }
+                        );
+                    })
+                };
+
+                {
+                    let pending = pending.clone();
+                    button.connect_button_press_event(move |_, event| {
+                        if event.button() == 1 {
+                            cancel_repeat(&pending);
+
+                            fire();
+                            schedule_repeat(pending.clone(), timing, 0, fire.clone());
+                        }
+
+                        Propagation::Proceed
+                    });
+                }
+
+                {
+                    let pending = pending.clone();
+                    button.connect_button_release_event(move |_, _| {
+                        cancel_repeat(&pending);
+                        Propagation::Proceed
+                    });
+                }
+
+                button.connect_leave_notify_event(move |_, _| {
+                    cancel_repeat(&pending);
+                    Propagation::Proceed
+                });
+
+                return button;
+            }
+
+            let long_pressed = Rc::new(Cell::new(false));
+
+            // the gesture is not owned by the button,
+            // so must be kept alive by the click handler
+            let gesture = self.on_long_press.map(|exec| {
+                connect_long_press(
+                    &button,
+                    &context,
+                    exec,
+                    self.long_press_duration,
+                    &long_pressed,
+                )
+            });
+
             button.connect_clicked(move |button| {
+                let _ = &gesture;
+
+                if !should_click(&long_pressed) {
+                    return;
+                }
+
+                if let Some(confirm) = &confirm {
+                    if !confirm.click() {
+                        return;
+                    }
+                }
+
                 try_send!(
                     tx,
                     ExecEvent {
                         cmd: exec.clone(),
                         args: None,
                         id: button.try_popup_id().unwrap_or(usize::MAX), // may not be a popup button
+                        in_flight: None,
                     }
                 );
             });
+        } else if let Some(exec) = self.on_long_press {
+            let long_pressed = Rc::new(Cell::new(false));
+            let gesture = connect_long_press(
+                &button,
+                &context,
+                exec,
+                self.long_press_duration,
+                &long_pressed,
+            );
+
+            button.connect_clicked(move |_| {
+                let _ = &gesture;
+                long_pressed.set(false);
+            });
         }
 
         button
     }
 }
+
+/// Creates a long-press gesture on the button which runs `exec`
+/// once the button has been held for `duration` milliseconds.
+///
+/// `long_pressed` is set when the gesture is recognized,
+/// so that the click which follows can be suppressed.
+fn connect_long_press(
+    button: &Button,
+    context: &CustomWidgetContext,
+    exec: String,
+    duration: u64,
+    long_pressed: &Rc<Cell<bool>>,
+) -> GestureLongPress {
+    let long_press_time = gtk::Settings::default()
+        .map(|settings| settings.property::<i32>("gtk-long-press-time"))
+        .and_then(|time| u64::try_from(time).ok())
+        .unwrap_or(DEFAULT_LONG_PRESS_TIME);
+
+    let gesture = GestureLongPress::new(button);
+    gesture.set_property(
+        "delay-factor",
+        long_press_delay_factor(duration, long_press_time),
+    );
+
+    {
+        let long_pressed = long_pressed.clone();
+        gesture.connect_begin(move |_, _| long_pressed.set(false));
+    }
+
+    let tx = context.tx.clone();
+    let long_pressed = long_pressed.clone();
+    gesture.connect_pressed(move |gesture, _, _| {
+        long_pressed.set(true);
+
+        let id = gesture
+            .widget()
+            .and_then(|widget| widget.downcast::<Button>().ok())
+            .and_then(|button| button.try_popup_id())
+            .unwrap_or(usize::MAX); // may not be a popup button
+
+        try_send!(
+            tx,
+            ExecEvent {
+                cmd: exec.clone(),
+                args: None,
+                id,
+                in_flight: None,
+            }
+        );
+    });
+
+    gesture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repeat_defaults() {
+        let button: ButtonWidget = serde_json::from_value(json!({ "on_click": "!true" })).unwrap();
+
+        assert!(!button.repeat);
+        assert_eq!(button.repeat_delay, 500);
+        assert_eq!(button.repeat_interval, 100);
+    }
+
+    #[test]
+    fn test_repeat_timing_parsing() {
+        let button: ButtonWidget = serde_json::from_value(json!({
+            "on_click": "!true",
+            "repeat": true,
+            "repeat_delay": 250,
+            "repeat_interval": 50
+        }))
+        .unwrap();
+
+        assert!(button.repeat);
+        assert_eq!(
+            RepeatTiming::new(button.repeat_delay, button.repeat_interval),
+            RepeatTiming {
+                delay: Duration::from_millis(250),
+                interval: Duration::from_millis(50),
+            }
+        );
+    }
+
+    #[test]
+    fn test_repeat_interval_clamped() {
+        let timing = RepeatTiming::new(0, 0);
+
+        assert_eq!(timing.delay, Duration::ZERO);
+        assert_eq!(timing.interval, RepeatTiming::MIN_INTERVAL);
+    }
+
+    #[test]
+    fn test_repeat_schedule() {
+        let timing = RepeatTiming::new(400, 75);
+
+        assert_eq!(timing.wait_before(0), Duration::from_millis(400));
+        assert_eq!(timing.wait_before(1), Duration::from_millis(75));
+        assert_eq!(timing.wait_before(10), Duration::from_millis(75));
+    }
+
+    #[test]
+    fn test_overlapping_runs_skipped() {
+        let in_flight = AtomicBool::new(false);
+
+        assert!(try_start(&in_flight));
+        assert!(!try_start(&in_flight));
+
+        // controller clears the flag once the command completes
+        in_flight.store(false, Ordering::Release);
+        assert!(try_start(&in_flight));
+    }
+
+    #[test]
+    fn test_long_press_defaults() {
+        let button: ButtonWidget = serde_json::from_value(json!({ "on_click": "!true" })).unwrap();
+
+        assert!(button.on_long_press.is_none());
+        assert_eq!(button.long_press_duration, 500);
+    }
+
+    #[test]
+    fn test_long_press_duration_parsing() {
+        let button: ButtonWidget = serde_json::from_value(json!({
+            "on_click": "!true",
+            "on_long_press": "!false",
+            "long_press_duration": 800
+        }))
+        .unwrap();
+
+        assert_eq!(button.on_long_press.as_deref(), Some("!false"));
+        assert_eq!(button.long_press_duration, 800);
+    }
+
+    #[test]
+    fn test_long_press_delay_factor() {
+        assert!((long_press_delay_factor(500, 500) - 1.0).abs() < f64::EPSILON);
+        assert!((long_press_delay_factor(750, 500) - 1.5).abs() < f64::EPSILON);
+        assert!((long_press_delay_factor(250, 0) - 0.5).abs() < f64::EPSILON);
+
+        // outside the range GTK accepts
+        assert!((long_press_delay_factor(2000, 500) - 2.0).abs() < f64::EPSILON);
+        assert!((long_press_delay_factor(50, 500) - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_confirm_defaults() {
+        let button: ButtonWidget = serde_json::from_value(json!({ "on_click": "!true" })).unwrap();
+
+        assert!(!button.confirm);
+        assert_eq!(button.confirm_label, "Sure?");
+        assert_eq!(button.confirm_timeout, 3000);
+    }
+
+    #[test]
+    fn test_confirm_second_click_runs() {
+        let state = Cell::new(ConfirmState::Idle);
+
+        assert!(!confirm_click(&state));
+        assert_eq!(state.get(), ConfirmState::Armed);
+
+        assert!(confirm_click(&state));
+        assert_eq!(state.get(), ConfirmState::Idle);
+
+        // starts over for the next run
+        assert!(!confirm_click(&state));
+    }
+
+    #[test]
+    fn test_confirm_timeout_reverts() {
+        let state = Cell::new(ConfirmState::Idle);
+
+        assert!(!confirm_click(&state));
+        assert!(confirm_expire(&state));
+        assert_eq!(state.get(), ConfirmState::Idle);
+
+        // the click after the timeout must be confirmed again
+        assert!(!confirm_click(&state));
+        assert!(confirm_click(&state));
+
+        // nothing to revert once confirmed
+        assert!(!confirm_expire(&state));
+    }
+
+    #[test]
+    fn test_long_press_suppresses_click() {
+        let long_pressed = Cell::new(false);
+        assert!(should_click(&long_pressed));
+
+        long_pressed.set(true);
+        assert!(!should_click(&long_pressed));
+
+        // only the click ending the long-press is suppressed
+        assert!(should_click(&long_pressed));
+    }
+}
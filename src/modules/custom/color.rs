@@ -0,0 +1,192 @@
+use gtk::gdk::RGBA;
+use gtk::prelude::*;
+use gtk::ColorButton;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::dynamic_value::DynamicOutput;
+use crate::{build, try_send};
+
+use super::{CustomWidget, CustomWidgetContext, ExecEvent};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct ColorWidget {
+    name: Option<String>,
+    class: Option<String>,
+    /// Hex colour to show, such as `#3584e4`.
+    value: Option<DynamicOutput>,
+    /// Command to run when a colour is chosen,
+    /// which is passed the colour as hex.
+    on_change: Option<String>,
+    /// Whether the colour can be made transparent.
+    #[serde(default)]
+    use_alpha: bool,
+    /// Title of the colour chooser dialog.
+    title: Option<String>,
+}
+
+/// Colour channels, in `[red, green, blue, alpha]` order.
+type Channels = [u8; 4];
+
+/// Parses a hex colour in `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` form.
+/// The leading `#` is optional.
+fn parse_hex(input: &str) -> Option<Channels> {
+    let hex = input.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize, width: usize| {
+        let value = u8::from_str_radix(&hex[i * width..(i + 1) * width], 16).ok()?;
+        // expand shorthand, so that `f` becomes `ff`
+        Some(if width == 1 { value * 0x11 } else { value })
+    };
+
+    let (count, width) = match hex.len() {
+        3 => (3, 1),
+        4 => (4, 1),
+        6 => (3, 2),
+        8 => (4, 2),
+        _ => return None,
+    };
+
+    let mut channels = [u8::MAX; 4];
+    for (i, value) in channels.iter_mut().enumerate().take(count) {
+        *value = channel(i, width)?;
+    }
+
+    Some(channels)
+}
+
+/// Formats a colour as `#rrggbb`,
+/// or `#rrggbbaa` if it is not fully opaque.
+fn format_hex([red, green, blue, alpha]: Channels) -> String {
+    if alpha == u8::MAX {
+        format!("#{red:02x}{green:02x}{blue:02x}")
+    } else {
+        format!("#{red:02x}{green:02x}{blue:02x}{alpha:02x}")
+    }
+}
+
+/// Parses a value output by the value script,
+/// logging a warning and returning `None` if it is not a valid colour.
+fn parse_value(value: &str) -> Option<Channels> {
+    let channels = parse_hex(value);
+    if channels.is_none() {
+        warn!("Ignoring invalid colour '{value}', expected hex such as '#3584e4'");
+    }
+
+    channels
+}
+
+fn to_rgba([red, green, blue, alpha]: Channels) -> RGBA {
+    let channel = |value: u8| f64::from(value) / f64::from(u8::MAX);
+    RGBA::new(channel(red), channel(green), channel(blue), channel(alpha))
+}
+
+fn from_rgba(rgba: &RGBA) -> Channels {
+    let channel = |value: f64| (value.clamp(0.0, 1.0) * f64::from(u8::MAX)).round() as u8;
+    [
+        channel(rgba.red()),
+        channel(rgba.green()),
+        channel(rgba.blue()),
+        channel(rgba.alpha()),
+    ]
+}
+
+impl CustomWidget for ColorWidget {
+    type Widget = ColorButton;
+
+    fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
+        let button = build!(self, ColorButton);
+        button.set_use_alpha(self.use_alpha);
+
+        if let Some(title) = &self.title {
+            button.set_title(title);
+        }
+
+        // only emitted when the user picks a colour,
+        // so setting it from the value script does not run the command
+        if let Some(on_change) = self.on_change {
+            let tx = context.tx.clone();
+            let use_alpha = self.use_alpha;
+
+            button.connect_color_set(move |button| {
+                let mut channels = from_rgba(&button.rgba());
+                if !use_alpha {
+                    channels[3] = u8::MAX;
+                }
+
+                try_send!(
+                    tx,
+                    ExecEvent {
+                        cmd: on_change.clone(),
+                        args: Some(vec![format_hex(channels)]),
+                        id: usize::MAX, // ignored
+                        in_flight: None,
+                    }
+                );
+            });
+        }
+
+        if let Some(value) = self.value {
+            let button = button.clone();
+
+            value.subscribe_when_visible(context.visibility.clone(), move |value| {
+                if let Some(channels) = value.as_deref().and_then(parse_value) {
+                    button.set_rgba(&to_rgba(channels));
+                }
+            });
+        }
+
+        button
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("#3584e4"), Some([0x35, 0x84, 0xe4, 0xff]));
+        assert_eq!(parse_hex("3584E4"), Some([0x35, 0x84, 0xe4, 0xff]));
+        assert_eq!(parse_hex("#3584e480"), Some([0x35, 0x84, 0xe4, 0x80]));
+        assert_eq!(parse_hex(" #3584e4\n"), Some([0x35, 0x84, 0xe4, 0xff]));
+    }
+
+    #[test]
+    fn test_parse_hex_shorthand() {
+        assert_eq!(parse_hex("#f0a"), Some([0xff, 0x00, 0xaa, 0xff]));
+        assert_eq!(parse_hex("#f0a8"), Some([0xff, 0x00, 0xaa, 0x88]));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for input in [
+            "", "#", "#12", "#12345", "#1234567", "#ggg", "red", "#+12345", "#é12",
+        ] {
+            assert_eq!(parse_hex(input), None, "{input}");
+            assert_eq!(parse_value(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_format_hex() {
+        assert_eq!(format_hex([0x35, 0x84, 0xe4, 0xff]), "#3584e4");
+        assert_eq!(format_hex([0x35, 0x84, 0xe4, 0x80]), "#3584e480");
+        assert_eq!(format_hex([0, 0, 0, 0xff]), "#000000");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for hex in ["#3584e4", "#00000000", "#ffffff", "#12345678"] {
+            let channels = parse_hex(hex).unwrap();
+            assert_eq!(format_hex(channels), hex);
+            assert_eq!(from_rgba(&to_rgba(channels)), channels);
+        }
+    }
+}
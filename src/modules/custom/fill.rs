@@ -0,0 +1,333 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use glib::Propagation;
+use gtk::cairo::Context;
+use gtk::prelude::*;
+use gtk::{CssProvider, DrawingArea, Orientation, Widget};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::gtk_helpers::IronbarGtkExt;
+
+/// Thickness in pixels of the fill drawn along a slider's trough.
+const SLIDER_FILL_THICKNESS: f64 = 4.0;
+
+/// Default look for drawn fills,
+/// which the user's stylesheet takes precedence over.
+const FILL_CSS: &str = "
+.center-origin { background-color: alpha(currentColor, 0.2); min-height: 6px; min-width: 6px; }
+.fill { background-color: @theme_selected_bg_color; }
+";
+
+/// Where a progress bar or slider fills from.
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FillOrigin {
+    /// The bar fills from the start.
+    #[default]
+    Start,
+    /// The bar fills outwards from the center, where zero sits.
+    /// Negative values fill towards the start, and positive values towards the end.
+    Center,
+}
+
+/// A CSS class to add to the widget while its value is in a range.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ColorStop {
+    /// Lowest value in the range, inclusive.
+    /// The range has no lower bound if not set.
+    min: Option<f64>,
+    /// Highest value in the range, exclusive.
+    /// The range has no upper bound if not set.
+    max: Option<f64>,
+    class: String,
+}
+
+/// Gets the classes of each stop whose range contains `value`.
+pub fn stop_classes(stops: &[ColorStop], value: f64) -> Vec<String> {
+    stops
+        .iter()
+        .filter(|stop| stop.min.is_none_or(|min| value >= min))
+        .filter(|stop| stop.max.is_none_or(|max| value < max))
+        .map(|stop| stop.class.clone())
+        .collect()
+}
+
+/// Gets the pixels to fill for `value` on a bar `length` pixels long,
+/// as `(start, end)` measured from the start of the bar.
+///
+/// With a center origin, negative values fill as a proportion of `min`
+/// and positive values as a proportion of `max`.
+/// For odd lengths, the center is rounded down,
+/// so the extra pixel always goes to the positive side.
+pub fn fill_region(value: f64, min: f64, max: f64, length: i32, origin: FillOrigin) -> (i32, i32) {
+    let length = length.max(0);
+
+    let extent = |fraction: f64, available: i32| {
+        (fraction.clamp(0.0, 1.0) * f64::from(available)).round() as i32
+    };
+
+    match origin {
+        FillOrigin::Start if max > min => (0, extent((value - min) / (max - min), length)),
+        FillOrigin::Start => (0, 0),
+        FillOrigin::Center => {
+            let center = length / 2;
+
+            if value > 0.0 && max > 0.0 {
+                (center, center + extent(value / max, length - center))
+            } else if value < 0.0 && min < 0.0 {
+                (center - extent(value / min, center), center)
+            } else {
+                (center, center)
+            }
+        }
+    }
+}
+
+/// Flips a region to measure from the other end of the bar.
+pub const fn mirror_region((start, end): (i32, i32), length: i32) -> (i32, i32) {
+    (length - end, length - start)
+}
+
+/// Draws the fill for a region along the widget,
+/// styled by its `.fill` class.
+///
+/// `cross` is the offset and size of the fill across the widget.
+fn draw_fill(
+    widget: &impl IsA<Widget>,
+    cr: &Context,
+    orientation: Orientation,
+    (start, end): (i32, i32),
+    (cross_offset, cross_size): (f64, f64),
+) {
+    if end <= start {
+        return;
+    }
+
+    let (start, size) = (f64::from(start), f64::from(end - start));
+    let (x, y, width, height) = if orientation == Orientation::Horizontal {
+        (start, cross_offset, size, cross_size)
+    } else {
+        (cross_offset, start, cross_size, size)
+    };
+
+    let style = widget.style_context();
+    style.save();
+    style.add_class("fill");
+    gtk::render_background(&style, cr, x, y, width, height);
+    style.restore();
+}
+
+/// Adds the default fill styles to a widget.
+fn add_fill_css(widget: &impl IsA<Widget>) {
+    let provider = CssProvider::new();
+    if provider.load_from_data(FILL_CSS.as_bytes()).is_ok() {
+        widget
+            .style_context()
+            .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_FALLBACK);
+    }
+}
+
+/// A progress bar which fills outwards from its center.
+///
+/// GTK progress bars can only fill from one end,
+/// so this is drawn manually.
+#[derive(Debug, Clone)]
+pub struct CenterFill {
+    pub area: DrawingArea,
+    /// Value between -1 and 1.
+    fraction: Rc<Cell<f64>>,
+}
+
+impl CenterFill {
+    pub fn new(area: DrawingArea, orientation: Orientation) -> Self {
+        let fraction = Rc::new(Cell::new(0.0));
+
+        area.add_class("center-origin");
+        add_fill_css(&area);
+
+        {
+            let fraction = fraction.clone();
+            area.connect_draw(move |area, cr| {
+                let style = area.style_context();
+                let width = area.allocated_width();
+                let height = area.allocated_height();
+
+                gtk::render_background(&style, cr, 0.0, 0.0, f64::from(width), f64::from(height));
+
+                let (length, cross) = if orientation == Orientation::Horizontal {
+                    (width, height)
+                } else {
+                    (height, width)
+                };
+
+                let region = fill_region(fraction.get(), -1.0, 1.0, length, FillOrigin::Center);
+                draw_fill(area, cr, orientation, region, (0.0, f64::from(cross)));
+
+                Propagation::Stop
+            });
+        }
+
+        Self { area, fraction }
+    }
+
+    /// Sets the value shown, between -1 and 1.
+    pub fn set_fraction(&self, fraction: f64) {
+        self.fraction.set(fraction);
+        self.area.queue_draw();
+    }
+}
+
+/// Draws a slider's fill outwards from the center of its trough,
+/// in place of the fill GTK draws from the start.
+pub fn fill_scale_from_center(scale: &gtk::Scale, orientation: Orientation, inverted: bool) {
+    scale.set_has_origin(false);
+    add_fill_css(scale);
+
+    // drawn after GTK has drawn the trough,
+    // leaving a gap for the slider so that it stays visible
+    scale.connect_local("draw", true, move |values| {
+        let scale = values[0].get::<gtk::Scale>().ok()?;
+        let cr = values[1].get::<Context>().ok()?;
+
+        let trough = scale.range_rect();
+        let (trough_start, length, cross_start, cross_size) =
+            if orientation == Orientation::Horizontal {
+                (trough.x(), trough.width(), trough.y(), trough.height())
+            } else {
+                (trough.y(), trough.height(), trough.x(), trough.width())
+            };
+
+        let adjustment = scale.adjustment();
+        let region = fill_region(
+            scale.value(),
+            adjustment.lower(),
+            adjustment.upper(),
+            length,
+            FillOrigin::Center,
+        );
+        let region = if inverted {
+            mirror_region(region, length)
+        } else {
+            region
+        };
+
+        let (slider_start, slider_end) = scale.slider_range();
+        let cross = (
+            f64::from(cross_start) + (f64::from(cross_size) - SLIDER_FILL_THICKNESS) / 2.0,
+            SLIDER_FILL_THICKNESS,
+        );
+
+        let (start, end) = (region.0 + trough_start, region.1 + trough_start);
+        draw_fill(
+            &scale,
+            &cr,
+            orientation,
+            (start, end.min(slider_start)),
+            cross,
+        );
+        draw_fill(
+            &scale,
+            &cr,
+            orientation,
+            (start.max(slider_end), end),
+            cross,
+        );
+
+        Some(false.to_value())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn center(value: f64, length: i32) -> (i32, i32) {
+        fill_region(value, -100.0, 100.0, length, FillOrigin::Center)
+    }
+
+    #[test]
+    fn test_start_region() {
+        let start = |value| fill_region(value, 0.0, 100.0, 200, FillOrigin::Start);
+
+        assert_eq!(start(0.0), (0, 0));
+        assert_eq!(start(25.0), (0, 50));
+        assert_eq!(start(100.0), (0, 200));
+        assert_eq!(start(150.0), (0, 200));
+        assert_eq!(fill_region(5.0, 10.0, 10.0, 200, FillOrigin::Start), (0, 0));
+    }
+
+    #[test]
+    fn test_center_region() {
+        assert_eq!(center(0.0, 200), (100, 100));
+        assert_eq!(center(50.0, 200), (100, 150));
+        assert_eq!(center(100.0, 200), (100, 200));
+        assert_eq!(center(-50.0, 200), (50, 100));
+        assert_eq!(center(-100.0, 200), (0, 100));
+    }
+
+    #[test]
+    fn test_center_region_clamped() {
+        assert_eq!(center(500.0, 200), (100, 200));
+        assert_eq!(center(-500.0, 200), (0, 100));
+        assert_eq!(center(f64::NAN, 200), (100, 100));
+        assert_eq!(center(10.0, 0), (0, 0));
+        assert_eq!(center(10.0, -5), (0, 0));
+    }
+
+    #[test]
+    fn test_center_region_odd_length() {
+        // the center rounds down, leaving the extra pixel on the positive side
+        assert_eq!(center(0.0, 101), (50, 50));
+        assert_eq!(center(100.0, 101), (50, 101));
+        assert_eq!(center(-100.0, 101), (0, 50));
+
+        // halves round the same way on both sides
+        assert_eq!(center(50.0, 101), (50, 76));
+        assert_eq!(center(-50.0, 101), (25, 50));
+    }
+
+    #[test]
+    fn test_center_region_asymmetric() {
+        // each side is scaled to its own end of the range
+        assert_eq!(
+            fill_region(-5.0, -10.0, 100.0, 200, FillOrigin::Center),
+            (50, 100)
+        );
+        assert_eq!(
+            fill_region(50.0, -10.0, 100.0, 200, FillOrigin::Center),
+            (100, 150)
+        );
+
+        // no negative side
+        assert_eq!(
+            fill_region(-5.0, 0.0, 100.0, 200, FillOrigin::Center),
+            (100, 100)
+        );
+    }
+
+    #[test]
+    fn test_mirror_region() {
+        assert_eq!(mirror_region((100, 150), 200), (50, 100));
+        assert_eq!(mirror_region((0, 200), 200), (0, 200));
+    }
+
+    #[test]
+    fn test_stop_classes() {
+        let stops: Vec<ColorStop> = serde_json::from_value(json!([
+            { "max": 0, "class": "negative" },
+            { "min": 0, "class": "positive" },
+            { "min": 80, "class": "high" },
+            { "min": -10, "max": 10, "class": "near-zero" }
+        ]))
+        .unwrap();
+
+        assert_eq!(stop_classes(&stops, -20.0), ["negative"]);
+        assert_eq!(stop_classes(&stops, -5.0), ["negative", "near-zero"]);
+        assert_eq!(stop_classes(&stops, 0.0), ["positive", "near-zero"]);
+        assert_eq!(stop_classes(&stops, 90.0), ["positive", "high"]);
+        assert!(stop_classes(&[], 90.0).is_empty());
+    }
+}
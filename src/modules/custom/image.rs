@@ -1,42 +1,329 @@
+use glib::Propagation;
+use gtk::gdk::RGBA;
 use gtk::prelude::*;
-use gtk::Image;
+use gtk::{EventBox, Image, ImageType, Orientation};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tracing::warn;
 
-use crate::build;
-use crate::dynamic_value::dynamic_string;
+use crate::dynamic_value::dynamic_string_when_visible;
+use crate::gtk_helpers::IronbarGtkExt;
 use crate::image::ImageProvider;
+use crate::modules::ModulePopupParts;
+use crate::popup::PopupOptions;
+use crate::{build, Ironbar};
 
 use super::{CustomWidget, CustomWidgetContext};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct ImageWidget {
     name: Option<String>,
     class: Option<String>,
     src: String,
     #[serde(default = "default_size")]
     size: i32,
+
+    /// Whether clicking the image opens a popup showing it at full size.
+    #[serde(default)]
+    zoomable: bool,
+
+    /// Recolours SVG images, like GTK does for symbolic icons.
+    recolor: Option<Recolor>,
 }
 
 const fn default_size() -> i32 {
     32
 }
 
+/// Largest width or height in pixels to show a zoomed image at.
+/// Larger images are scaled down to fit.
+const MAX_ZOOM_SIZE: i32 = 1024;
+
+/// The colour to recolour an SVG image with.
+#[derive(Debug, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum Recolor {
+    /// Whether to use the widget's CSS text colour.
+    Theme(bool),
+    /// A CSS colour.
+    Color(String),
+}
+
+/// Gets the colour to recolour the image with,
+/// or `None` to load it as-is.
+///
+/// Only SVG images can be recoloured, so other images are always loaded as-is.
+/// Invalid colours are ignored.
+fn recolor_color(
+    recolor: Option<&Recolor>,
+    is_svg: bool,
+    theme_color: impl FnOnce() -> RGBA,
+) -> Option<RGBA> {
+    match recolor? {
+        _ if !is_svg => None,
+        Recolor::Theme(true) => Some(theme_color()),
+        Recolor::Theme(false) => None,
+        Recolor::Color(color) => RGBA::parse(color)
+            .map_err(|_| warn!("Invalid recolor colour: '{color}'"))
+            .ok(),
+    }
+}
+
+/// Loads the image at `src` into `image`,
+/// recoloured using the image's current style if configured.
+///
+/// Returns whether the source is a valid image.
+fn load_image(
+    image: &Image,
+    src: &str,
+    icon_theme: &gtk::IconTheme,
+    size: i32,
+    scale: i32,
+    recolor: Option<&Recolor>,
+) -> bool {
+    let Some(provider) = ImageProvider::parse(src, icon_theme, false, size)
+        .map(|provider| provider.with_scale(scale))
+    else {
+        return false;
+    };
+
+    let color = recolor_color(recolor, provider.svg_path().is_some(), || {
+        image.style_context().color(image.state_flags())
+    });
+
+    let recolored = color.map(|color| {
+        provider
+            .load_recolored_into_image(image, &color)
+            .map_err(|err| warn!("Failed to recolor image: {err:?}"))
+    });
+
+    if !matches!(recolored, Some(Ok(()))) {
+        provider.load_into_image(image.clone()).ok();
+    }
+
+    true
+}
+
+impl ImageWidget {
+    /// Creates the popup container used to show the image at full size,
+    /// along with the image inside it.
+    ///
+    /// Returns `None` if the image is not zoomable.
+    fn zoom_content(&self) -> Option<(gtk::Box, Image)> {
+        self.zoomable.then(|| {
+            let container = gtk::Box::new(Orientation::Vertical, 0);
+            container.add_class("image-zoom");
+
+            let image = Image::new();
+            container.add(&image);
+            container.show_all();
+
+            (container, image)
+        })
+    }
+}
+
 impl CustomWidget for ImageWidget {
-    type Widget = Image;
+    type Widget = gtk::Widget;
 
     fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
-        let gtk_image = build!(self, Self::Widget);
+        let gtk_image = build!(self, Image);
+        let zoom = self.zoom_content();
+
+        // the zoomed image is only loaded once the popup is first opened
+        let zoom_src = Rc::new(RefCell::new(None::<String>));
+
+        // kept to reload the image when the theme colour changes
+        let src = Rc::new(RefCell::new(None::<String>));
 
         {
             let gtk_image = gtk_image.clone();
             let icon_theme = context.icon_theme.clone();
+            let scale = context.scale_factor;
+            let zoom_image = zoom.as_ref().map(|(_, image)| image.clone());
+            let zoom_src = zoom_src.clone();
+            let current_src = src.clone();
+            let size = self.size;
+            let recolor = self.recolor.clone();
+
+            dynamic_string_when_visible(&self.src, context.visibility.clone(), move |src| {
+                let loaded =
+                    load_image(&gtk_image, &src, &icon_theme, size, scale, recolor.as_ref());
+
+                current_src.replace(loaded.then(|| src.clone()));
 
-            dynamic_string(&self.src, move |src| {
-                ImageProvider::parse(&src, &icon_theme, false, self.size)
-                    .map(|image| image.load_into_image(gtk_image.clone()));
+                // the previous zoomed image is stale, so is dropped
+                if let Some(zoom_image) = &zoom_image {
+                    zoom_image.clear();
+                    zoom_src.replace(loaded.then_some(src));
+                }
             });
         }
 
-        gtk_image
+        // the style colour is only known once the image is realized,
+        // and changes with its state and the theme
+        if self.recolor == Some(Recolor::Theme(true)) {
+            let icon_theme = context.icon_theme.clone();
+            let scale = context.scale_factor;
+            let size = self.size;
+            let recolor = self.recolor.clone();
+
+            let reload = move |image: &Image| {
+                if let Some(src) = src.borrow().as_deref() {
+                    load_image(image, src, &icon_theme, size, scale, recolor.as_ref());
+                }
+            };
+
+            gtk_image.connect_realize(reload.clone());
+            gtk_image.connect_style_updated(reload);
+        }
+
+        let Some((container, zoom_image)) = zoom else {
+            return gtk_image.upcast();
+        };
+
+        // images do not receive events, so need wrapping to be clicked
+        let event_box = EventBox::new();
+        event_box.add(&gtk_image);
+
+        let id = Ironbar::unique_id();
+        context.popup.register_content(
+            id,
+            format!("image-zoom-{id}"),
+            ModulePopupParts {
+                container,
+                buttons: vec![],
+            },
+            PopupOptions::default(),
+        );
+
+        let popup = context.popup.clone();
+        let orientation = context.bar_orientation;
+        let icon_theme = context.icon_theme.clone();
+        let scale = context.scale_factor;
+        let size = self.size;
+
+        event_box.connect_button_press_event(move |event_box, event| {
+            if event.button() != 1 {
+                return Propagation::Proceed;
+            }
+
+            let open = popup.is_visible();
+            popup.hide();
+
+            if !open && zoom_image.storage_type() == ImageType::Empty {
+                if let Some(src) = zoom_src.borrow().as_deref() {
+                    load_zoom_image(&zoom_image, src, &icon_theme, size, scale);
+                }
+            }
+
+            // leave zoom disabled for images which fail to load
+            if !open && zoom_image.storage_type() != ImageType::Empty {
+                popup.show_at(id, event_box.geometry(orientation));
+            }
+
+            Propagation::Proceed
+        });
+
+        event_box.upcast()
+    }
+}
+
+/// Loads the image at `src` into the zoom popup's image,
+/// scaled down to fit within [`MAX_ZOOM_SIZE`].
+fn load_zoom_image(
+    zoom_image: &Image,
+    src: &str,
+    icon_theme: &gtk::IconTheme,
+    size: i32,
+    scale: i32,
+) {
+    let Some(provider) = ImageProvider::parse(src, icon_theme, false, size) else {
+        return;
+    };
+
+    if let Err(err) = provider
+        .with_scale(scale)
+        .load_full_size_into_image(zoom_image, MAX_ZOOM_SIZE)
+    {
+        warn!("Failed to load zoomed image: {err:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use serde_json::json;
+
+    fn image(zoomable: bool) -> ImageWidget {
+        serde_json::from_value(json!({ "src": "icon:firefox", "zoomable": zoomable })).unwrap()
+    }
+
+    #[test]
+    fn test_recolor_only_svg() {
+        let theme = || RGBA::new(1.0, 1.0, 1.0, 1.0);
+        let color = Recolor::Color("#f00".to_string());
+
+        assert_eq!(
+            recolor_color(Some(&color), true, theme),
+            Some(RGBA::new(1.0, 0.0, 0.0, 1.0))
+        );
+        assert_eq!(recolor_color(Some(&color), false, theme), None);
+        assert_eq!(recolor_color(None, true, theme), None);
+    }
+
+    #[test]
+    fn test_recolor_invalid_color() {
+        let theme = || RGBA::new(1.0, 1.0, 1.0, 1.0);
+        let color = Recolor::Color("red } * { fill: blue".to_string());
+
+        assert_eq!(recolor_color(Some(&color), true, theme), None);
+    }
+
+    #[test]
+    fn test_recolor_theme() {
+        let theme = || RGBA::new(1.0, 1.0, 1.0, 1.0);
+
+        assert_eq!(
+            recolor_color(Some(&Recolor::Theme(true)), true, theme),
+            Some(RGBA::new(1.0, 1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            recolor_color(Some(&Recolor::Theme(false)), true, theme),
+            None
+        );
+
+        let widget: ImageWidget =
+            serde_json::from_value(json!({ "src": "icon:firefox", "recolor": true })).unwrap();
+        assert_eq!(widget.recolor, Some(Recolor::Theme(true)));
+
+        let widget: ImageWidget =
+            serde_json::from_value(json!({ "src": "icon:firefox", "recolor": "red" })).unwrap();
+        assert_eq!(widget.recolor, Some(Recolor::Color("red".to_string())));
+    }
+
+    #[test]
+    fn test_zoomable_default() {
+        let widget: ImageWidget = serde_json::from_value(json!({ "src": "icon:firefox" })).unwrap();
+        assert!(!widget.zoomable);
+    }
+
+    #[test]
+    fn test_zoom_only_for_zoomable() {
+        with_gtk(|| {
+            assert!(image(false).zoom_content().is_none());
+
+            let (container, zoom_image) = image(true).zoom_content().unwrap();
+            assert_eq!(
+                container.children(),
+                vec![zoom_image.clone().upcast::<gtk::Widget>()]
+            );
+
+            // nothing is loaded until the popup is opened
+            assert_eq!(zoom_image.storage_type(), ImageType::Empty);
+        });
     }
 }
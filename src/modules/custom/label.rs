@@ -1,39 +1,357 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gtk::pango::WrapMode;
 use gtk::prelude::*;
-use gtk::Label;
+use gtk::{Justification, Label};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::error;
 
-use crate::build;
-use crate::config::ModuleOrientation;
-use crate::dynamic_value::dynamic_string;
+use crate::config::{MarkupTruncate, ModuleOrientation};
+use crate::dynamic_value::dynamic_string_when_visible;
+use crate::script::{OutputStream, Script, ScriptInput};
+use crate::{build, glib_recv_mpsc, spawn, try_send};
 
 use super::{CustomWidget, CustomWidgetContext};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct LabelWidget {
     name: Option<String>,
     class: Option<String>,
     label: String,
     #[serde(default)]
     orientation: ModuleOrientation,
+    truncate: Option<MarkupTruncate>,
+
+    /// Scripts to fill `{name}` placeholders in the label, keyed by name.
+    tokens: Option<HashMap<String, ScriptInput>>,
+    /// Text to show for a token without a value,
+    /// because its script has not output yet or failed.
+    #[serde(default)]
+    placeholder: String,
+
+    /// Whether to wrap long text onto multiple lines.
+    #[serde(default)]
+    wrap: bool,
+    /// Width in characters to wrap text at.
+    wrap_width: Option<i32>,
+    /// Alignment of the lines of multi-line text relative to each other.
+    justify: Option<Justify>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Justify {
+    Left,
+    Center,
+    Right,
+    /// Each line is stretched to fill the width.
+    Fill,
+}
+
+impl From<Justify> for Justification {
+    fn from(justify: Justify) -> Self {
+        match justify {
+            Justify::Left => Self::Left,
+            Justify::Center => Self::Center,
+            Justify::Right => Self::Right,
+            Justify::Fill => Self::Fill,
+        }
+    }
+}
+
+/// A label template with values for its `{name}` placeholders.
+#[derive(Debug, Default)]
+struct TokenLabel {
+    template: String,
+    /// Latest value of each token,
+    /// or `None` if it has not output yet or failed.
+    values: HashMap<String, Option<String>>,
+}
+
+impl TokenLabel {
+    fn new(names: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            template: String::new(),
+            values: names.into_iter().map(|name| (name, None)).collect(),
+        }
+    }
+
+    /// Renders the template,
+    /// replacing each placeholder for a known token with its value.
+    ///
+    /// Other text in braces is left as-is,
+    /// and values are not themselves searched for placeholders.
+    fn render(&self, placeholder: &str) -> String {
+        let mut output = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let value = rest
+                .find('}')
+                .and_then(|end| Some((end, self.values.get(&rest[1..end])?)));
+
+            match value {
+                Some((end, value)) => {
+                    output.push_str(value.as_deref().unwrap_or(placeholder));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    output.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        output.push_str(rest);
+        output
+    }
+}
+
+fn set_markup(label: &Label, truncate: Option<&MarkupTruncate>, markup: &str) {
+    match truncate {
+        Some(truncate) => label.set_markup(&truncate.truncate(markup)),
+        None => label.set_markup(markup),
+    }
+}
+
+impl LabelWidget {
+    /// Sets up wrapping and justification,
+    /// leaving GTK's single-line defaults for anything not configured.
+    fn apply_layout(&self, label: &Label) {
+        if self.wrap {
+            label.set_line_wrap(true);
+            label.set_line_wrap_mode(WrapMode::WordChar);
+        }
+
+        if let Some(width) = self.wrap_width {
+            label.set_max_width_chars(width);
+        }
+
+        if let Some(justify) = self.justify {
+            label.set_justify(justify.into());
+        }
+    }
 }
 
 impl CustomWidget for LabelWidget {
     type Widget = Label;
 
-    fn into_widget(self, _context: CustomWidgetContext) -> Self::Widget {
+    fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
         let label = build!(self, Self::Widget);
 
         label.set_angle(self.orientation.to_angle());
 
         label.set_use_markup(true);
+        self.apply_layout(&label);
 
-        {
+        let Some(tokens) = self.tokens else {
+            let truncate = self.truncate;
+
+            {
+                let label = label.clone();
+                dynamic_string_when_visible(&self.label, context.visibility, move |string| {
+                    set_markup(&label, truncate.as_ref(), &string);
+                });
+            }
+
+            return label;
+        };
+
+        let state = Rc::new(RefCell::new(TokenLabel::new(tokens.keys().cloned())));
+
+        let render = {
             let label = label.clone();
-            dynamic_string(&self.label, move |string| {
-                label.set_markup(&string);
+            let state = state.clone();
+            let truncate = self.truncate;
+            let placeholder = self.placeholder;
+
+            Rc::new(move || {
+                set_markup(
+                    &label,
+                    truncate.as_ref(),
+                    &state.borrow().render(&placeholder),
+                );
+            })
+        };
+
+        let (tx, rx) = mpsc::channel(32);
+
+        for (name, input) in tokens {
+            let script = Script::from(input);
+            let tx = tx.clone();
+            let visible = context.visibility.clone();
+
+            spawn(async move {
+                let callback = |stream: OutputStream, _success: bool| {
+                    let value = match stream {
+                        OutputStream::Stdout(out) => Some(out),
+                        OutputStream::Stderr(err) => {
+                            error!("Script for token '{name}' failed: {err}");
+                            None
+                        }
+                    };
+
+                    try_send!(tx, (name.clone(), value));
+                };
+
+                match visible {
+                    Some(visible) => script.run_when_visible(None, visible, callback).await,
+                    None => script.run(None, callback).await,
+                }
             });
         }
 
+        {
+            let state = state.clone();
+            let render = render.clone();
+
+            glib_recv_mpsc!(rx, update => {
+                let (name, value) = update;
+                state.borrow_mut().values.insert(name, value);
+                render();
+            });
+        }
+
+        dynamic_string_when_visible(&self.label, context.visibility, move |template| {
+            state.borrow_mut().template = template;
+            render();
+        });
+
         label
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use serde_json::json;
+
+    fn label_widget(config: serde_json::Value) -> LabelWidget {
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn token_label(template: &str) -> TokenLabel {
+        let mut label = TokenLabel::new(["cpu".to_string(), "mem".to_string()]);
+        label.template = template.to_string();
+        label
+    }
+
+    #[test]
+    fn test_render_tokens() {
+        let mut label = token_label("{cpu}% / {mem}%");
+        label
+            .values
+            .insert("cpu".to_string(), Some("12".to_string()));
+        label
+            .values
+            .insert("mem".to_string(), Some("40".to_string()));
+
+        assert_eq!(label.render(""), "12% / 40%");
+    }
+
+    #[test]
+    fn test_partial_updates() {
+        let mut label = token_label("{cpu}% / {mem}%");
+        assert_eq!(label.render("?"), "?% / ?%");
+
+        label
+            .values
+            .insert("cpu".to_string(), Some("12".to_string()));
+        assert_eq!(label.render("?"), "12% / ?%");
+
+        label
+            .values
+            .insert("mem".to_string(), Some("40".to_string()));
+        assert_eq!(label.render("?"), "12% / 40%");
+
+        // failed scripts go back to the placeholder
+        label.values.insert("cpu".to_string(), None);
+        assert_eq!(label.render(""), "% / 40%");
+    }
+
+    #[test]
+    fn test_unknown_placeholders_kept() {
+        let mut label = token_label("{cpu} {gpu} {} {mem");
+        label
+            .values
+            .insert("cpu".to_string(), Some("1".to_string()));
+
+        assert_eq!(label.render(""), "1 {gpu} {} {mem");
+    }
+
+    #[test]
+    fn test_values_not_substituted() {
+        let mut label = token_label("{cpu} {mem}");
+        label
+            .values
+            .insert("cpu".to_string(), Some("{mem}".to_string()));
+        label
+            .values
+            .insert("mem".to_string(), Some("40".to_string()));
+
+        assert_eq!(label.render(""), "{mem} 40");
+    }
+
+    #[test]
+    fn test_deserialize_layout() {
+        let widget = label_widget(json!({
+            "label": "hello",
+            "wrap": true,
+            "wrap_width": 40,
+            "justify": "center"
+        }));
+
+        assert!(widget.wrap);
+        assert_eq!(widget.wrap_width, Some(40));
+        assert_eq!(widget.justify, Some(Justify::Center));
+
+        let widget = label_widget(json!({ "label": "hello" }));
+        assert!(!widget.wrap);
+        assert_eq!(widget.wrap_width, None);
+        assert_eq!(widget.justify, None);
+
+        assert!(
+            serde_json::from_value::<LabelWidget>(json!({ "label": "", "justify": "middle" }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_layout_applied_when_configured() {
+        with_gtk(|| {
+            let label = Label::new(None);
+            label_widget(json!({
+                "label": "hello",
+                "wrap": true,
+                "wrap_width": 40,
+                "justify": "fill"
+            }))
+            .apply_layout(&label);
+
+            assert!(label.is_line_wrap());
+            assert_eq!(label.line_wrap_mode(), WrapMode::WordChar);
+            assert_eq!(label.max_width_chars(), 40);
+            assert_eq!(label.justify(), Justification::Fill);
+        });
+    }
+
+    #[test]
+    fn test_layout_unchanged_by_default() {
+        with_gtk(|| {
+            let label = Label::new(None);
+            label_widget(json!({ "label": "hello" })).apply_layout(&label);
+
+            assert!(!label.is_line_wrap());
+            assert_eq!(label.max_width_chars(), -1);
+            assert_eq!(label.justify(), Justification::Left);
+        });
+    }
+}
@@ -1,44 +1,65 @@
 mod r#box;
 mod button;
+mod color;
+mod fill;
 mod image;
 mod label;
 mod progress;
 mod slider;
+mod stack;
+mod subpopup;
 
+pub use self::stack::StackCommand;
+pub use self::subpopup::SubpopupCommand;
+
+use self::color::ColorWidget;
 use self::image::ImageWidget;
 use self::label::LabelWidget;
 use self::r#box::BoxWidget;
 use self::slider::SliderWidget;
-use crate::config::{CommonConfig, ModuleConfig};
+use self::stack::StackWidget;
+use self::subpopup::{Subpopup, Subpopups};
+use crate::command::Command;
+use crate::config::{field_names, CommonConfig, ModuleConfig};
+use crate::gtk_helpers::{watch_mapped, IronbarGtkExt};
 use crate::modules::custom::button::ButtonWidget;
 use crate::modules::custom::progress::ProgressWidget;
 use crate::modules::{
     wrap_widget, AnyModuleFactory, BarModuleFactory, Module, ModuleInfo, ModuleParts, ModulePopup,
-    ModuleUpdateEvent, PopupButton, PopupModuleFactory, WidgetContext,
+    ModulePopupParts, ModuleUpdateEvent, PopupButton, PopupModuleFactory, WidgetContext,
 };
+use crate::popup::{Popup, PopupOptions};
 use crate::script::Script;
-use crate::{module_impl, send_async, spawn};
+use crate::{glib_recv, lock, module_impl, send_async, spawn, Ironbar};
 use color_eyre::Result;
 use gtk::prelude::*;
 use gtk::{Button, IconTheme, Orientation};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
-use tokio::sync::{broadcast, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::{debug, error};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct CustomModule {
     /// Widgets to add to the bar container
     bar: Vec<WidgetConfig>,
     /// Widgets to add to the popup container
     popup: Option<Vec<WidgetConfig>>,
+    /// Widgets to add to each named subpopup,
+    /// which can be opened from buttons inside the popup.
+    subpopups: Option<HashMap<String, Vec<WidgetConfig>>>,
 
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct WidgetConfig {
     #[serde(flatten)]
     widget: WidgetOrModule,
@@ -46,14 +67,14 @@ pub struct WidgetConfig {
     common: CommonConfig,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum WidgetOrModule {
-    Widget(Widget),
+    Widget(Box<Widget>),
     Module(ModuleConfig),
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Widget {
     Box(BoxWidget),
@@ -62,6 +83,42 @@ pub enum Widget {
     Image(ImageWidget),
     Slider(SliderWidget),
     Progress(ProgressWidget),
+    ColorButton(ColorWidget),
+    Stack(StackWidget),
+}
+
+impl Widget {
+    /// The `type` of each widget.
+    pub const TYPES: [&'static str; 8] = [
+        "box",
+        "label",
+        "button",
+        "image",
+        "slider",
+        "progress",
+        "color_button",
+        "stack",
+    ];
+
+    /// Gets the names of the options accepted by the widget with the given `type`,
+    /// excluding common options.
+    ///
+    /// Returns `None` if the type does not exist.
+    pub fn fields(r#type: &str) -> Option<Vec<String>> {
+        let fields = match r#type {
+            "box" => field_names::<BoxWidget>(),
+            "label" => field_names::<LabelWidget>(),
+            "button" => field_names::<ButtonWidget>(),
+            "image" => field_names::<ImageWidget>(),
+            "slider" => field_names::<SliderWidget>(),
+            "progress" => field_names::<ProgressWidget>(),
+            "color_button" => field_names::<ColorWidget>(),
+            "stack" => field_names::<StackWidget>(),
+            _ => return None,
+        };
+
+        Some(fields)
+    }
 }
 
 #[derive(Clone)]
@@ -70,8 +127,17 @@ struct CustomWidgetContext<'a> {
     tx: &'a mpsc::Sender<ExecEvent>,
     bar_orientation: Orientation,
     icon_theme: &'a IconTheme,
+    /// Scale factor of the bar's output.
+    scale_factor: i32,
+    /// Whether the widgets are shown,
+    /// if scripts should be deferred until they are.
+    visibility: Option<watch::Receiver<bool>>,
     popup_buttons: Rc<RefCell<Vec<Button>>>,
+    popup: Rc<Popup>,
     module_factory: AnyModuleFactory,
+    /// Commands sent from the controller,
+    /// for widgets which handle their own.
+    updates: broadcast::Sender<CustomCommand>,
 }
 
 trait CustomWidget {
@@ -102,6 +168,15 @@ macro_rules! build {
     }};
 }
 
+/// Gets the value to set from a `var:set:` command.
+///
+/// If the value is omitted, the first argument passed by the widget is used,
+/// such as the value of a slider.
+#[cfg(feature = "ipc")]
+fn variable_value(value: Option<String>, args: Option<&[String]>) -> Option<String> {
+    value.or_else(|| args?.first().cloned())
+}
+
 /// Sets the widget length,
 /// using either a width or height request
 /// based on the bar's orientation.
@@ -118,9 +193,7 @@ impl WidgetOrModule {
         match self {
             WidgetOrModule::Widget(widget) => widget.add_to(parent, context, common),
             WidgetOrModule::Module(config) => {
-                if let Err(err) = config.create(&context.module_factory, parent, context.info) {
-                    error!("{err:?}");
-                }
+                config.create_or_fallback(&context.module_factory, parent, context.info);
             }
         }
     }
@@ -135,6 +208,7 @@ impl Widget {
                     &$widget.into_widget(context.clone()),
                     common,
                     context.bar_orientation,
+                    context.popup.animations_enabled(),
                 )
             };
         }
@@ -146,21 +220,38 @@ impl Widget {
             Self::Image(widget) => create!(widget),
             Self::Slider(widget) => create!(widget),
             Self::Progress(widget) => create!(widget),
+            Self::ColorButton(widget) => create!(widget),
+            Self::Stack(widget) => create!(widget),
         };
 
         parent.add(&event_box);
     }
 }
 
+/// A command sent from the controller to the module's widgets.
+#[derive(Debug, Clone)]
+pub enum CustomCommand {
+    /// A command which acts on the bar,
+    /// so is run on the GTK thread.
+    Run(Command),
+    /// Takes the ID of the button the command came from.
+    Subpopup(SubpopupCommand, usize),
+    /// Switches the visible page of stack widgets.
+    Stack(StackCommand),
+}
+
 #[derive(Debug)]
 pub struct ExecEvent {
     cmd: String,
     args: Option<Vec<String>>,
     id: usize,
+    /// Flag set by the sender while the command is running,
+    /// which is cleared once it has completed.
+    in_flight: Option<Arc<AtomicBool>>,
 }
 
 impl Module<gtk::Box> for CustomModule {
-    type SendMessage = ();
+    type SendMessage = CustomCommand;
     type ReceiveMessage = ExecEvent;
 
     module_impl!("custom");
@@ -174,24 +265,58 @@ impl Module<gtk::Box> for CustomModule {
         let tx = context.tx.clone();
         spawn(async move {
             while let Some(event) = rx.recv().await {
-                if event.cmd.starts_with('!') {
-                    let script = Script::from(&event.cmd[1..]);
+                match event.cmd.parse::<Command>() {
+                    Ok(Command::Script(cmd)) => {
+                        let script = Script::from(cmd.as_str());
 
-                    debug!("executing command: '{}'", script.cmd);
+                        debug!("executing command: '{}'", script.cmd);
 
-                    let args = event.args.unwrap_or_default();
+                        let args = event.args.unwrap_or_default();
 
-                    if let Err(err) = script.get_output(Some(&args)).await {
-                        error!("{err:?}");
+                        if let Err(err) = script.get_output(Some(&args)).await {
+                            error!("{err:?}");
+                        }
+                    }
+                    Ok(Command::TogglePopup(None)) => {
+                        send_async!(tx, ModuleUpdateEvent::TogglePopup(event.id));
+                    }
+                    Ok(Command::OpenPopup(None)) => {
+                        send_async!(tx, ModuleUpdateEvent::OpenPopup(event.id));
+                    }
+                    Ok(Command::ClosePopup) => send_async!(tx, ModuleUpdateEvent::ClosePopup),
+                    Ok(Command::Subpopup(command)) => send_async!(
+                        tx,
+                        ModuleUpdateEvent::Update(CustomCommand::Subpopup(command, event.id))
+                    ),
+                    Ok(Command::Stack(command)) => {
+                        send_async!(tx, ModuleUpdateEvent::Update(CustomCommand::Stack(command)))
+                    }
+                    Ok(Command::Refresh(name)) => {
+                        if !lock!(Ironbar::script_refresh()).refresh(&name) {
+                            error!("No script to refresh: '{name}'");
+                        }
+                    }
+                    #[cfg(feature = "ipc")]
+                    Ok(Command::SetVariable(key, value)) => {
+                        match variable_value(value, event.args.as_deref()) {
+                            Some(value) => {
+                                if let Err(err) =
+                                    crate::write_lock!(Ironbar::variable_manager()).set(key, value)
+                                {
+                                    error!("{err:?}");
+                                }
+                            }
+                            None => error!("Invalid variable command: '{}'", event.cmd),
+                        }
+                    }
+                    Ok(command) => {
+                        send_async!(tx, ModuleUpdateEvent::Update(CustomCommand::Run(command)));
                     }
-                } else if event.cmd == "popup:toggle" {
-                    send_async!(tx, ModuleUpdateEvent::TogglePopup(event.id));
-                } else if event.cmd == "popup:open" {
-                    send_async!(tx, ModuleUpdateEvent::OpenPopup(event.id));
-                } else if event.cmd == "popup:close" {
-                    send_async!(tx, ModuleUpdateEvent::ClosePopup);
-                } else {
-                    error!("Received invalid command: '{}'", event.cmd);
+                    Err(err) => error!("{err:?}"),
+                }
+
+                if let Some(in_flight) = event.in_flight {
+                    in_flight.store(false, Ordering::Release);
                 }
             }
         });
@@ -209,14 +334,31 @@ impl Module<gtk::Box> for CustomModule {
 
         let popup_buttons = Rc::new(RefCell::new(Vec::new()));
 
+        {
+            let ironbar = context.ironbar.clone();
+            let popup = context.popup.clone();
+            let rx = context.subscribe();
+            glib_recv!(rx, command => {
+                if let CustomCommand::Run(command) = command {
+                    if let Err(err) = command.run(&popup, &ironbar) {
+                        error!("{err:?}");
+                    }
+                }
+            });
+        }
+
         let custom_context = CustomWidgetContext {
             info,
             tx: &context.controller_tx,
             bar_orientation: orientation,
             icon_theme: info.icon_theme,
+            scale_factor: info.scale_factor(),
+            visibility: None,
             popup_buttons: popup_buttons.clone(),
+            popup: context.popup.clone(),
             module_factory: BarModuleFactory::new(context.ironbar.clone(), context.popup.clone())
                 .into(),
+            updates: context.update_tx.clone(),
         };
 
         self.bar.clone().into_iter().for_each(|widget| {
@@ -252,7 +394,7 @@ impl Module<gtk::Box> for CustomModule {
     fn into_popup(
         self,
         tx: mpsc::Sender<Self::ReceiveMessage>,
-        _rx: broadcast::Receiver<Self::SendMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
         context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
         info: &ModuleInfo,
     ) -> Option<gtk::Box>
@@ -262,18 +404,26 @@ impl Module<gtk::Box> for CustomModule {
         let container = gtk::Box::new(Orientation::Horizontal, 0);
 
         if let Some(popup) = self.popup {
+            let popup_buttons = Rc::new(RefCell::new(vec![]));
+
             let custom_context = CustomWidgetContext {
                 info,
                 tx: &tx,
                 bar_orientation: info.bar_position.orientation(),
                 icon_theme: info.icon_theme,
-                popup_buttons: Rc::new(RefCell::new(vec![])),
+                scale_factor: info.scale_factor(),
+                visibility: context
+                    .defer_until_visible
+                    .then(|| watch_mapped(&container)),
+                popup_buttons: popup_buttons.clone(),
+                popup: context.popup.clone(),
                 module_factory: PopupModuleFactory::new(
-                    context.ironbar,
-                    context.popup,
+                    context.ironbar.clone(),
+                    context.popup.clone(),
                     context.button_id,
                 )
                 .into(),
+                updates: context.update_tx.clone(),
             };
 
             for widget in popup {
@@ -281,6 +431,65 @@ impl Module<gtk::Box> for CustomModule {
                     .widget
                     .add_to(&container, &custom_context, widget.common);
             }
+
+            if let Some(subpopups) = self.subpopups {
+                let mut anchors = anchors(&popup_buttons, None);
+                let gap = context.popup.gap();
+
+                let popups = subpopups
+                    .into_iter()
+                    .map(|(name, widgets)| {
+                        let popup = Popup::new(info, gap);
+                        popup.set_animations_enabled(context.popup.animations_enabled());
+                        let subpopup_container = gtk::Box::new(Orientation::Horizontal, 0);
+                        let popup_buttons = Rc::new(RefCell::new(vec![]));
+
+                        let custom_context = CustomWidgetContext {
+                            popup_buttons: popup_buttons.clone(),
+                            popup: Rc::new(popup.clone()),
+                            visibility: custom_context
+                                .visibility
+                                .as_ref()
+                                .map(|_| watch_mapped(&subpopup_container)),
+                            ..custom_context.clone()
+                        };
+
+                        for widget in widgets {
+                            widget.widget.add_to(
+                                &subpopup_container,
+                                &custom_context,
+                                widget.common,
+                            );
+                        }
+
+                        subpopup_container.add_class("popup-custom");
+                        subpopup_container.add_class("subpopup");
+                        subpopup_container.show_all();
+
+                        let id = Ironbar::unique_id();
+                        popup.register_content(
+                            id,
+                            name.clone(),
+                            ModulePopupParts {
+                                container: subpopup_container,
+                                buttons: vec![],
+                            },
+                            PopupOptions::default(),
+                        );
+
+                        anchors.extend(self::anchors(&popup_buttons, Some(&name)));
+                        (name, Subpopup { popup, id })
+                    })
+                    .collect();
+
+                let subpopups = Subpopups::new(context.popup, popups, anchors, gap);
+
+                glib_recv!(rx, command => {
+                    if let CustomCommand::Subpopup(command, button_id) = command {
+                        subpopups.handle(&command, button_id);
+                    }
+                });
+            }
         }
 
         container.show_all();
@@ -288,3 +497,42 @@ impl Module<gtk::Box> for CustomModule {
         Some(container)
     }
 }
+
+/// Gets the buttons inside a popup,
+/// keyed by their popup ID,
+/// along with the subpopup they are inside.
+fn anchors(
+    buttons: &RefCell<Vec<Button>>,
+    parent: Option<&str>,
+) -> HashMap<usize, (Button, Option<String>)> {
+    buttons
+        .borrow()
+        .iter()
+        .map(|button| {
+            (
+                button.ensure_popup_id(),
+                (button.clone(), parent.map(ToString::to_string)),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg(feature = "ipc")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variable_value_from_args() {
+        let args = ["0.5".to_string()];
+
+        assert_eq!(variable_value(None, Some(&args)), Some("0.5".to_string()));
+        // explicit values take priority
+        assert_eq!(
+            variable_value(Some("1".to_string()), Some(&args)),
+            Some("1".to_string())
+        );
+        assert_eq!(variable_value(None, None), None);
+        assert_eq!(variable_value(None, Some(&[])), None);
+    }
+}
@@ -1,76 +1,506 @@
+use std::cell::RefCell;
+use std::num::ParseFloatError;
+use std::rc::Rc;
+use std::time::Duration;
+
+use glib::{ControlFlow, SourceId};
 use gtk::prelude::*;
-use gtk::ProgressBar;
+use gtk::{CssProvider, DrawingArea, Orientation, ProgressBar};
+use schemars::JsonSchema;
 use serde::Deserialize;
-use tokio::sync::mpsc;
 use tracing::error;
 
+use crate::build;
 use crate::config::ModuleOrientation;
-use crate::dynamic_value::dynamic_string;
+use crate::dynamic_value::{dynamic_string_when_visible, DynamicOutput};
+use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::custom::set_length;
-use crate::script::{OutputStream, Script, ScriptInput};
-use crate::{build, glib_recv_mpsc, spawn, try_send};
+use crate::script::{JsonOutput, OutputFormat};
 
+use super::fill::{stop_classes, CenterFill, ColorStop, FillOrigin};
 use super::{CustomWidget, CustomWidgetContext};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct ProgressWidget {
     name: Option<String>,
     class: Option<String>,
     #[serde(default)]
     orientation: ModuleOrientation,
     label: Option<String>,
-    value: Option<ScriptInput>,
+    value: Option<DynamicOutput>,
+    #[serde(default)]
+    output: OutputFormat,
     #[serde(default = "default_max")]
     max: f64,
     length: Option<i32>,
+
+    /// Number of discrete segments to render instead of a continuous bar.
+    segments: Option<u32>,
+    /// Minimum number of segments to fill for any non-zero value.
+    #[serde(default)]
+    min_filled: u32,
+
+    /// Colours to fill the bar with,
+    /// as a gradient from the start to the end of the bar.
+    gradient: Option<Vec<String>>,
+    /// Time in milliseconds between steps of the pulse animation,
+    /// shown while the value is unknown.
+    #[serde(default = "default_pulse_interval")]
+    pulse_interval: u64,
+
+    /// Where the bar fills from.
+    /// With `center`, negative values fill towards the start.
+    /// Ignored when `segments` is set.
+    #[serde(default)]
+    origin: FillOrigin,
+    /// Classes to add to the bar while its value is in a range.
+    #[serde(default)]
+    color_stops: Vec<ColorStop>,
 }
 
 const fn default_max() -> f64 {
     100.0
 }
 
+const fn default_pulse_interval() -> u64 {
+    100
+}
+
+/// What the progress bar shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProgressState {
+    /// The bar is filled to a fraction between 0 and 1.
+    Determinate(f64),
+    /// The value is unknown, so the bar pulses.
+    Indeterminate,
+}
+
+impl ProgressState {
+    /// Gets the state to show before the value script first outputs.
+    ///
+    /// Without a value script, the value is never known.
+    const fn initial(has_value: bool) -> Self {
+        if has_value {
+            Self::Determinate(0.0)
+        } else {
+            Self::Indeterminate
+        }
+    }
+
+    /// Parses plain output from the value script.
+    /// Empty output or `null` means the value is unknown.
+    fn parse(output: &str, max: f64) -> Result<Self, ParseFloatError> {
+        match output.trim() {
+            "" | "null" => Ok(Self::Indeterminate),
+            value => value
+                .parse::<f64>()
+                .map(|value| Self::Determinate(value / max)),
+        }
+    }
+}
+
+/// Builds the CSS to fill the bar with a gradient through `colors`.
+fn gradient_css(colors: &[String], orientation: ModuleOrientation) -> String {
+    let direction = match orientation {
+        ModuleOrientation::Horizontal => "to right",
+        ModuleOrientation::Vertical => "to bottom",
+    };
+
+    // a gradient needs at least two stops
+    let stops = match colors {
+        [color] => format!("{color}, {color}"),
+        colors => colors.join(", "),
+    };
+
+    format!("progress {{ background-image: linear-gradient({direction}, {stops}); }}")
+}
+
+/// Pulses a progress bar on a timeout while its value is unknown.
+struct Pulse {
+    progress: ProgressBar,
+    interval: Duration,
+    animated: bool,
+    /// The running timeout, if pulsing.
+    source: RefCell<Option<SourceId>>,
+}
+
+impl Pulse {
+    fn new(progress: ProgressBar, interval: Duration, animated: bool) -> Rc<Self> {
+        let pulse = Rc::new(Self {
+            progress,
+            interval,
+            animated,
+            source: RefCell::new(None),
+        });
+
+        // the timeout would otherwise keep pulsing the destroyed bar
+        let weak = Rc::downgrade(&pulse);
+        pulse.progress.connect_destroy(move |_| {
+            if let Some(pulse) = weak.upgrade() {
+                pulse.stop();
+            }
+        });
+
+        pulse
+    }
+
+    fn start(&self) {
+        if !self.animated || self.source.borrow().is_some() {
+            return;
+        }
+
+        let progress = self.progress.clone();
+        let source = glib::timeout_add_local(self.interval, move || {
+            progress.pulse();
+            ControlFlow::Continue
+        });
+
+        self.source.replace(Some(source));
+    }
+
+    fn stop(&self) {
+        if let Some(source) = self.source.take() {
+            source.remove();
+        }
+    }
+}
+
+/// Gets the number of segments to fill
+/// to represent `fraction` of the total.
+///
+/// Any value above zero fills at least `min_filled` segments,
+/// so small values are not rounded down to nothing.
+fn filled_segments(fraction: f64, segments: u32, min_filled: u32) -> u32 {
+    let fraction = fraction.clamp(0.0, 1.0);
+
+    let filled = (fraction * f64::from(segments)).round() as u32;
+
+    if fraction > 0.0 {
+        filled.max(min_filled.min(segments))
+    } else {
+        filled
+    }
+}
+
+/// A progress bar made up of discrete segments.
+#[derive(Debug, Clone)]
+struct Segments {
+    container: gtk::Box,
+    cells: Vec<gtk::Box>,
+    min_filled: u32,
+}
+
+impl Segments {
+    fn new(container: gtk::Box, count: u32, min_filled: u32) -> Self {
+        container.set_homogeneous(true);
+
+        let cells = (0..count)
+            .map(|_| {
+                let cell = gtk::Box::new(Orientation::Horizontal, 0);
+                cell.add_class("segment");
+                cell.set_hexpand(true);
+                cell.set_vexpand(true);
+
+                container.add(&cell);
+                cell
+            })
+            .collect();
+
+        Self {
+            container,
+            cells,
+            min_filled,
+        }
+    }
+
+    /// Updates which segments are filled.
+    fn set_fraction(&self, fraction: f64) {
+        let filled = filled_segments(fraction, self.cells.len() as u32, self.min_filled) as usize;
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i < filled {
+                cell.add_class("segment-filled");
+            } else {
+                cell.style_context().remove_class("segment-filled");
+            }
+        }
+    }
+}
+
+/// How the bar is drawn.
+#[derive(Debug, Clone)]
+enum Display {
+    Continuous(ProgressBar),
+    Segmented(Segments),
+    CenterFill(CenterFill),
+}
+
+impl Display {
+    fn widget(&self) -> gtk::Widget {
+        match self {
+            Self::Continuous(progress) => progress.clone().upcast(),
+            Self::Segmented(segments) => segments.container.clone().upcast(),
+            Self::CenterFill(fill) => fill.area.clone().upcast(),
+        }
+    }
+}
+
+/// Shows the state on the bar, however it is drawn.
+fn show_state(state: ProgressState, display: &Display, widget: &gtk::Widget, pulse: &Pulse) {
+    match state {
+        ProgressState::Determinate(fraction) => {
+            pulse.stop();
+            widget.style_context().remove_class("indeterminate");
+
+            match display {
+                Display::Continuous(progress) => progress.set_fraction(fraction),
+                Display::Segmented(segments) => segments.set_fraction(fraction),
+                Display::CenterFill(fill) => fill.set_fraction(fraction),
+            }
+        }
+        ProgressState::Indeterminate => {
+            widget.add_class("indeterminate");
+
+            match display {
+                Display::Continuous(_) => pulse.start(),
+                Display::Segmented(segments) => segments.set_fraction(0.0),
+                Display::CenterFill(fill) => fill.set_fraction(0.0),
+            }
+        }
+    }
+}
+
+/// Gets the color stop classes for a state.
+/// The value is unknown while indeterminate, so no stops apply.
+fn state_classes(stops: &[ColorStop], state: ProgressState, max: f64) -> Vec<String> {
+    match state {
+        ProgressState::Determinate(fraction) => stop_classes(stops, fraction * max),
+        ProgressState::Indeterminate => vec![],
+    }
+}
+
 impl CustomWidget for ProgressWidget {
-    type Widget = ProgressBar;
+    type Widget = gtk::Widget;
 
     fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
-        let progress = build!(self, Self::Widget);
+        let progress = build!(self, ProgressBar);
 
         progress.set_orientation(self.orientation.into());
 
+        let display = match (self.segments, self.origin) {
+            (Some(count), _) => {
+                let container = build!(self, gtk::Box);
+                container.add_class("segmented");
+                container.set_orientation(self.orientation.into());
+                Display::Segmented(Segments::new(container, count, self.min_filled))
+            }
+            (None, FillOrigin::Center) => {
+                let area = build!(self, DrawingArea);
+                Display::CenterFill(CenterFill::new(area, self.orientation.into()))
+            }
+            (None, FillOrigin::Start) => Display::Continuous(progress.clone()),
+        };
+
+        let widget = display.widget();
+
         if let Some(length) = self.length {
-            set_length(&progress, length, context.bar_orientation);
+            set_length(&widget, length, context.bar_orientation);
         }
 
+        if let Some(colors) = self.gradient.as_deref().filter(|colors| !colors.is_empty()) {
+            let provider = CssProvider::new();
+
+            match provider.load_from_data(gradient_css(colors, self.orientation).as_bytes()) {
+                Ok(()) => progress
+                    .style_context()
+                    .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION),
+                Err(err) => error!("Invalid progress gradient {colors:?}: {err}"),
+            }
+        }
+
+        let pulse = Pulse::new(
+            progress.clone(),
+            Duration::from_millis(self.pulse_interval),
+            context.popup.animations_enabled(),
+        );
+
+        let initial = ProgressState::initial(self.value.is_some());
+        show_state(initial, &display, &widget, &pulse);
+
+        let mut color_classes = state_classes(&self.color_stops, initial, self.max);
+        widget.replace_classes(&[], &color_classes);
+
         if let Some(value) = self.value {
-            let script = Script::from(value);
             let progress = progress.clone();
+            let widget = widget.clone();
+
+            let format = self.output;
+            let max = self.max;
+            let color_stops = self.color_stops;
 
-            let (tx, rx) = mpsc::channel(128);
+            let mut classes = vec![];
 
-            spawn(async move {
-                script
-                    .run(None, move |stream, _success| match stream {
-                        OutputStream::Stdout(out) => match out.parse::<f64>() {
-                            Ok(value) => try_send!(tx, value),
-                            Err(err) => error!("{err:?}"),
+            value.subscribe_when_visible(context.visibility.clone(), move |value| {
+                let (state, output) = match value {
+                    Some(out) => match format {
+                        OutputFormat::Plain => match ProgressState::parse(&out, max) {
+                            Ok(state) => (Some(state), JsonOutput::default()),
+                            Err(err) => {
+                                error!("{err:?}");
+                                return;
+                            }
                         },
-                        OutputStream::Stderr(err) => error!("{err:?}"),
-                    })
-                    .await;
-            });
+                        // on malformed output, keep showing the previous output
+                        OutputFormat::Json => match JsonOutput::parse(&out) {
+                            Ok(output) => {
+                                let state = output.percentage.map(|percentage| {
+                                    ProgressState::Determinate(percentage / 100.0)
+                                });
+                                (state, output)
+                            }
+                            Err(err) => {
+                                error!("{err:?}");
+                                return;
+                            }
+                        },
+                    },
+                    // unset variable
+                    None => (Some(ProgressState::Indeterminate), JsonOutput::default()),
+                };
+
+                if let Some(state) = state {
+                    show_state(state, &display, &widget, &pulse);
+
+                    let new_classes = state_classes(&color_stops, state, max);
+                    widget.replace_classes(&color_classes, &new_classes);
+                    color_classes = new_classes;
+                }
 
-            glib_recv_mpsc!(rx, value => progress.set_fraction(value / self.max));
+                if let Some(text) = output.text {
+                    progress.set_show_text(true);
+                    progress.set_text(Some(&text));
+                }
+
+                if output.tooltip.is_some() || widget.has_tooltip() {
+                    widget.set_tooltip_markup(output.tooltip.as_deref());
+                }
+
+                widget.replace_classes(&classes, &output.class);
+                classes = output.class;
+            });
         }
 
         if let Some(text) = self.label {
             let progress = progress.clone();
             progress.set_show_text(true);
 
-            dynamic_string(&text, move |string| {
+            dynamic_string_when_visible(&text, context.visibility.clone(), move |string| {
                 progress.set_text(Some(&string));
             });
         }
 
-        progress
+        widget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_state() {
+        assert_eq!(
+            ProgressState::initial(true),
+            ProgressState::Determinate(0.0)
+        );
+        assert_eq!(ProgressState::initial(false), ProgressState::Indeterminate);
+    }
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(
+            ProgressState::parse("50\n", 100.0),
+            Ok(ProgressState::Determinate(0.5))
+        );
+        assert_eq!(
+            ProgressState::parse("3", 4.0),
+            Ok(ProgressState::Determinate(0.75))
+        );
+        assert_eq!(
+            ProgressState::parse("", 100.0),
+            Ok(ProgressState::Indeterminate)
+        );
+        assert_eq!(
+            ProgressState::parse("null", 100.0),
+            Ok(ProgressState::Indeterminate)
+        );
+        assert!(ProgressState::parse("half", 100.0).is_err());
+    }
+
+    #[test]
+    fn test_state_classes() {
+        let stops: Vec<ColorStop> = serde_json::from_value(serde_json::json!([
+            { "max": 20, "class": "low" },
+            { "min": 80, "class": "high" }
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            state_classes(&stops, ProgressState::Determinate(0.1), 100.0),
+            ["low"]
+        );
+        assert_eq!(
+            state_classes(&stops, ProgressState::Determinate(0.9), 100.0),
+            ["high"]
+        );
+        assert_eq!(
+            state_classes(&stops, ProgressState::Determinate(-0.5), 10.0),
+            ["low"]
+        );
+        assert!(state_classes(&stops, ProgressState::Indeterminate, 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_gradient_css() {
+        let colors = ["#f00".to_string(), "yellow 30%".to_string()];
+        assert_eq!(
+            gradient_css(&colors, ModuleOrientation::Horizontal),
+            "progress { background-image: linear-gradient(to right, #f00, yellow 30%); }"
+        );
+
+        assert_eq!(
+            gradient_css(&["red".to_string()], ModuleOrientation::Vertical),
+            "progress { background-image: linear-gradient(to bottom, red, red); }"
+        );
+    }
+
+    #[test]
+    fn test_filled_segments_rounding() {
+        assert_eq!(filled_segments(0.0, 5, 0), 0);
+        assert_eq!(filled_segments(0.09, 5, 0), 0);
+        assert_eq!(filled_segments(0.1, 5, 0), 1);
+        assert_eq!(filled_segments(0.5, 4, 0), 2);
+        assert_eq!(filled_segments(0.69, 5, 0), 3);
+        assert_eq!(filled_segments(1.0, 5, 0), 5);
+    }
+
+    #[test]
+    fn test_filled_segments_out_of_range() {
+        assert_eq!(filled_segments(-0.5, 5, 0), 0);
+        assert_eq!(filled_segments(1.5, 5, 0), 5);
+        assert_eq!(filled_segments(f64::NAN, 5, 1), 0);
+    }
+
+    #[test]
+    fn test_filled_segments_min_filled() {
+        assert_eq!(filled_segments(0.01, 10, 1), 1);
+        assert_eq!(filled_segments(0.01, 10, 2), 2);
+        assert_eq!(filled_segments(0.0, 10, 1), 0);
+
+        // does not override larger values
+        assert_eq!(filled_segments(0.5, 10, 1), 5);
+
+        // cannot fill more segments than exist
+        assert_eq!(filled_segments(0.01, 3, 5), 3);
     }
 }
@@ -1,28 +1,40 @@
-use glib::Propagation;
-use std::cell::Cell;
+use glib::{Propagation, SourceId};
+use std::cell::{Cell, RefCell};
 use std::ops::Neg;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use gtk::prelude::*;
-use gtk::Scale;
+use gtk::{Orientation, Scale, ScrollType};
+use schemars::JsonSchema;
 use serde::Deserialize;
-use tokio::sync::mpsc;
 use tracing::error;
 
 use crate::config::ModuleOrientation;
+use crate::dynamic_value::DynamicOutput;
+use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::custom::set_length;
-use crate::script::{OutputStream, Script, ScriptInput};
-use crate::{build, glib_recv_mpsc, spawn, try_send};
+use crate::{build, try_send};
 
+use super::fill::{fill_scale_from_center, stop_classes, ColorStop, FillOrigin};
 use super::{CustomWidget, CustomWidgetContext, ExecEvent};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct SliderWidget {
     name: Option<String>,
     class: Option<String>,
+    /// Defaults to the bar's orientation.
+    orientation: Option<ModuleOrientation>,
+    /// Whether to reverse the direction of the slider,
+    /// so that a vertical slider increases upwards.
     #[serde(default)]
-    orientation: ModuleOrientation,
-    value: Option<ScriptInput>,
+    inverted: bool,
+    value: Option<DynamicOutput>,
     on_change: Option<String>,
+    /// Minimum time in milliseconds between running `on_change`
+    /// while the slider is dragged.
+    /// The final value is always sent.
+    throttle: Option<u64>,
     #[serde(default = "default_min")]
     min: f64,
     #[serde(default = "default_max")]
@@ -31,6 +43,45 @@ pub struct SliderWidget {
     length: Option<i32>,
     #[serde(default = "crate::config::default_true")]
     show_label: bool,
+
+    /// When to show the current value.
+    /// Takes precedence over `show_label` when set.
+    show_value: Option<ShowValue>,
+    /// Format string for the value, where `{}` is replaced with the value.
+    format: Option<String>,
+
+    /// Where the trough fills from.
+    /// With `center`, the fill starts at zero.
+    #[serde(default)]
+    origin: FillOrigin,
+    /// Classes to add to the slider while its value is in a range.
+    #[serde(default)]
+    color_stops: Vec<ColorStop>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShowValue {
+    /// Always show the value label.
+    Always,
+    /// Show the value label only while the slider is hovered or dragged.
+    Hover,
+    /// Show the value in the slider's tooltip.
+    Tooltip,
+    /// Never show the value.
+    Never,
+}
+
+impl ShowValue {
+    /// Whether the value label should be drawn,
+    /// given the current pointer state.
+    const fn is_label_visible(self, hovered: bool, dragging: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Hover => hovered || dragging,
+            Self::Tooltip | Self::Never => false,
+        }
+    }
 }
 
 const fn default_min() -> f64 {
@@ -41,20 +92,214 @@ const fn default_max() -> f64 {
     100.0
 }
 
+/// Formats the value to `digits` decimal places,
+/// then substitutes it into `format` if set.
+fn format_value(format: Option<&str>, value: f64, digits: i32) -> String {
+    let digits = usize::try_from(digits).unwrap_or_default();
+    let value = format!("{value:.digits$}");
+
+    match format {
+        Some(format) => format.replace("{}", &value),
+        None => value,
+    }
+}
+
+/// Maps a pointer position along the slider's trough to a value.
+///
+/// `position` is relative to the start (left or top) of the trough,
+/// which is `length` long.
+fn value_at_position(position: f64, length: f64, min: f64, max: f64, inverted: bool) -> f64 {
+    if length <= 0.0 {
+        return min;
+    }
+
+    let fraction = (position / length).clamp(0.0, 1.0);
+    let fraction = if inverted { 1.0 - fraction } else { fraction };
+
+    min + fraction * (max - min)
+}
+
+/// Limits how often changed values are sent,
+/// without ever losing the latest value.
+#[derive(Debug)]
+struct Throttle {
+    interval: Duration,
+    last_sent: Option<Instant>,
+    /// Latest value held back during the interval.
+    pending: Option<f64>,
+}
+
+impl Throttle {
+    const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_sent: None,
+            pending: None,
+        }
+    }
+
+    /// Records a new value.
+    ///
+    /// Returns the value if it can be sent immediately.
+    /// Otherwise, it is held until [`Self::flush`],
+    /// replacing any value already held.
+    fn push(&mut self, value: f64, now: Instant) -> Option<f64> {
+        match self.remaining(now) {
+            Some(_) => {
+                self.pending = Some(value);
+                None
+            }
+            None => {
+                self.pending = None;
+                self.last_sent = Some(now);
+                Some(value)
+            }
+        }
+    }
+
+    /// Takes the value held back, if any, to send now.
+    fn flush(&mut self, now: Instant) -> Option<f64> {
+        let value = self.pending.take()?;
+        self.last_sent = Some(now);
+        Some(value)
+    }
+
+    /// Gets the time left until the next value can be sent immediately,
+    /// or `None` if it can be sent now.
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.last_sent?);
+        self.interval
+            .checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+impl SliderWidget {
+    fn show_value(&self) -> ShowValue {
+        self.show_value.unwrap_or(if self.show_label {
+            ShowValue::Always
+        } else {
+            ShowValue::Never
+        })
+    }
+}
+
 impl CustomWidget for SliderWidget {
     type Widget = Scale;
 
     fn into_widget(self, context: CustomWidgetContext) -> Self::Widget {
         let scale = build!(self, Self::Widget);
 
-        scale.set_orientation(self.orientation.into());
+        let orientation = self
+            .orientation
+            .map_or(context.bar_orientation, Orientation::from);
+
+        scale.set_orientation(orientation);
+        scale.set_inverted(self.inverted);
 
         if let Some(length) = self.length {
-            set_length(&scale, length, context.bar_orientation);
+            set_length(&scale, length, orientation);
         }
 
         scale.set_range(self.min, self.max);
-        scale.set_draw_value(self.show_label);
+
+        if self.origin == FillOrigin::Center {
+            fill_scale_from_center(&scale, orientation, self.inverted);
+        }
+
+        if !self.color_stops.is_empty() {
+            let stops = self.color_stops.clone();
+            let classes = RefCell::new(stop_classes(&stops, scale.value()));
+            scale.replace_classes(&[], &classes.borrow());
+
+            scale.connect_value_changed(move |scale| {
+                let new_classes = stop_classes(&stops, scale.value());
+                scale.replace_classes(&classes.borrow(), &new_classes);
+                classes.replace(new_classes);
+            });
+        }
+
+        {
+            let min = self.min;
+            let max = self.max;
+            let inverted = self.inverted;
+
+            // jump straight to the clicked position,
+            // regardless of the `gtk-primary-button-warps-slider` setting
+            scale.connect_button_press_event(move |scale, event| {
+                if event.button() == 1 {
+                    let trough = scale.range_rect();
+                    let (x, y) = event.position();
+
+                    let (position, length) = match orientation {
+                        Orientation::Vertical => (y - f64::from(trough.y()), trough.height()),
+                        _ => (x - f64::from(trough.x()), trough.width()),
+                    };
+
+                    let value = value_at_position(position, f64::from(length), min, max, inverted);
+
+                    scale.emit_by_name::<bool>("change-value", &[&ScrollType::Jump, &value]);
+                }
+
+                Propagation::Proceed
+            });
+        }
+
+        let show_value = self.show_value();
+        scale.set_draw_value(show_value.is_label_visible(false, false));
+
+        if let Some(format) = self.format.clone() {
+            scale.connect_format_value(move |scale, value| {
+                format_value(Some(&format), value, scale.digits())
+            });
+        }
+
+        match show_value {
+            ShowValue::Hover => {
+                let hovered = Rc::new(Cell::new(false));
+                let dragging = Rc::new(Cell::new(false));
+
+                let update = {
+                    let hovered = hovered.clone();
+                    let dragging = dragging.clone();
+
+                    Rc::new(move |scale: &Scale| {
+                        scale.set_draw_value(
+                            show_value.is_label_visible(hovered.get(), dragging.get()),
+                        );
+                    })
+                };
+
+                macro_rules! track {
+                    ($connect:ident, $state:ident, $value:expr) => {{
+                        let $state = $state.clone();
+                        let update = update.clone();
+
+                        scale.$connect(move |scale, _| {
+                            $state.set($value);
+                            update(scale);
+                            Propagation::Proceed
+                        });
+                    }};
+                }
+
+                track!(connect_enter_notify_event, hovered, true);
+                track!(connect_leave_notify_event, hovered, false);
+                track!(connect_button_press_event, dragging, true);
+                track!(connect_button_release_event, dragging, false);
+            }
+            ShowValue::Tooltip => {
+                let format = self.format.clone();
+                let update = move |scale: &Scale| {
+                    let text = format_value(format.as_deref(), scale.value(), scale.digits());
+                    scale.set_tooltip_text(Some(&text));
+                };
+
+                update(&scale);
+                scale.connect_value_changed(update);
+            }
+            ShowValue::Always | ShowValue::Never => {}
+        }
 
         if let Some(on_change) = self.on_change {
             let min = self.min;
@@ -79,21 +324,96 @@ impl CustomWidget for SliderWidget {
                 Propagation::Proceed
             });
 
+            let send = move |val: f64| {
+                try_send!(
+                    tx,
+                    ExecEvent {
+                        cmd: on_change.clone(),
+                        args: Some(vec![val.to_string()]),
+                        id: usize::MAX, // ignored
+                        in_flight: None,
+                    }
+                );
+            };
+
+            let throttle = self
+                .throttle
+                .filter(|&throttle| throttle > 0)
+                .map(|throttle| {
+                    Rc::new(RefCell::new(Throttle::new(Duration::from_millis(throttle))))
+                });
+
+            let timeout: Rc<RefCell<Option<SourceId>>> = Rc::default();
+
+            {
+                let timeout = timeout.clone();
+                scale.connect_destroy(move |_| {
+                    if let Some(source) = timeout.borrow_mut().take() {
+                        source.remove();
+                    }
+                });
+            }
+
+            // sends the held back value, cancelling the timeout waiting to do so
+            let flush = {
+                let throttle = throttle.clone();
+                let timeout = timeout.clone();
+                let send = send.clone();
+
+                move || {
+                    if let Some(source) = timeout.borrow_mut().take() {
+                        source.remove();
+                    }
+
+                    let value = throttle
+                        .as_ref()
+                        .and_then(|throttle| throttle.borrow_mut().flush(Instant::now()));
+
+                    if let Some(value) = value {
+                        send(value);
+                    }
+                }
+            };
+
+            let flush = Rc::new(flush);
+
+            // the final value is sent as soon as dragging stops
+            {
+                let flush = flush.clone();
+                scale.connect_button_release_event(move |_, _| {
+                    flush();
+                    Propagation::Proceed
+                });
+            }
+
             scale.connect_change_value(move |_, _, val| {
                 // GTK will send values outside min/max range
                 let val = val.clamp(min, max);
 
                 if val != prev_value.get() {
-                    try_send!(
-                        tx,
-                        ExecEvent {
-                            cmd: on_change.clone(),
-                            args: Some(vec![val.to_string()]),
-                            id: usize::MAX // ignored
-                        }
-                    );
-
                     prev_value.set(val);
+
+                    let Some(throttle) = &throttle else {
+                        send(val);
+                        return Propagation::Proceed;
+                    };
+
+                    let now = Instant::now();
+                    if let Some(val) = throttle.borrow_mut().push(val, now) {
+                        send(val);
+                    } else if timeout.borrow().is_none() {
+                        let remaining = throttle.borrow().remaining(now).unwrap_or_default();
+                        let flush = flush.clone();
+                        let timeout_ref = timeout.clone();
+
+                        let source = glib::timeout_add_local_once(remaining, move || {
+                            // the source is removed once run, so must not be removed again
+                            timeout_ref.borrow_mut().take();
+                            flush();
+                        });
+
+                        timeout.replace(Some(source));
+                    }
                 }
 
                 Propagation::Proceed
@@ -101,26 +421,187 @@ impl CustomWidget for SliderWidget {
         }
 
         if let Some(value) = self.value {
-            let script = Script::from(value);
             let scale = scale.clone();
 
-            let (tx, rx) = mpsc::channel(128);
-
-            spawn(async move {
-                script
-                    .run(None, move |stream, _success| match stream {
-                        OutputStream::Stdout(out) => match out.parse() {
-                            Ok(value) => try_send!(tx, value),
-                            Err(err) => error!("{err:?}"),
-                        },
-                        OutputStream::Stderr(err) => error!("{err:?}"),
-                    })
-                    .await;
+            value.subscribe_when_visible(context.visibility.clone(), move |value| {
+                match value.map(|value| value.parse()) {
+                    Some(Ok(value)) => scale.set_value(value),
+                    Some(Err(err)) => error!("{err:?}"),
+                    // unset variable
+                    None => {}
+                }
             });
-
-            glib_recv_mpsc!(rx, value => scale.set_value(value));
         }
 
         scale
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn slider(config: serde_json::Value) -> SliderWidget {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn test_orientation_default() {
+        let widget = slider(json!({}));
+
+        assert!(widget.orientation.is_none());
+        assert!(!widget.inverted);
+        assert_eq!(widget.origin, FillOrigin::Start);
+        assert!(widget.color_stops.is_empty());
+    }
+
+    #[test]
+    fn test_center_origin() {
+        let widget = slider(json!({
+            "origin": "center",
+            "min": -50,
+            "max": 50,
+            "color_stops": [{ "max": 0, "class": "negative" }]
+        }));
+
+        assert_eq!(widget.origin, FillOrigin::Center);
+        assert_eq!(stop_classes(&widget.color_stops, -10.0), ["negative"]);
+        assert!(stop_classes(&widget.color_stops, 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_throttle_sends_first_value() {
+        let mut throttle = Throttle::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert_eq!(throttle.push(1.0, start), Some(1.0));
+        assert_eq!(throttle.flush(start), None);
+    }
+
+    #[test]
+    fn test_throttle_keeps_trailing_value() {
+        let mut throttle = Throttle::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert_eq!(throttle.push(1.0, start), Some(1.0));
+        assert_eq!(throttle.push(2.0, start + Duration::from_millis(10)), None);
+        assert_eq!(throttle.push(3.0, start + Duration::from_millis(20)), None);
+
+        assert_eq!(
+            throttle.remaining(start + Duration::from_millis(20)),
+            Some(Duration::from_millis(80))
+        );
+
+        // only the latest value is sent at the end of the interval
+        let end = start + Duration::from_millis(100);
+        assert_eq!(throttle.flush(end), Some(3.0));
+        assert_eq!(throttle.flush(end), None);
+    }
+
+    #[test]
+    fn test_throttle_after_interval() {
+        let mut throttle = Throttle::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        assert_eq!(throttle.push(1.0, start), Some(1.0));
+        assert_eq!(throttle.remaining(start + Duration::from_millis(100)), None);
+        assert_eq!(
+            throttle.push(2.0, start + Duration::from_millis(150)),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn test_throttle_flush_restarts_interval() {
+        let mut throttle = Throttle::new(Duration::from_millis(100));
+        let start = Instant::now();
+
+        throttle.push(1.0, start);
+        throttle.push(2.0, start + Duration::from_millis(50));
+
+        // released early, before the interval ended
+        let release = start + Duration::from_millis(60);
+        assert_eq!(throttle.flush(release), Some(2.0));
+
+        assert_eq!(
+            throttle.push(3.0, release + Duration::from_millis(10)),
+            None
+        );
+        assert_eq!(
+            throttle.flush(release + Duration::from_millis(100)),
+            Some(3.0)
+        );
+    }
+
+    #[test]
+    fn test_value_at_position() {
+        assert!((value_at_position(0.0, 100.0, 0.0, 50.0, false) - 0.0).abs() < f64::EPSILON);
+        assert!((value_at_position(25.0, 100.0, 0.0, 50.0, false) - 12.5).abs() < f64::EPSILON);
+        assert!((value_at_position(100.0, 100.0, 0.0, 50.0, false) - 50.0).abs() < f64::EPSILON);
+        assert!((value_at_position(50.0, 100.0, -10.0, 10.0, false) - 0.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_value_at_position_inverted() {
+        assert!((value_at_position(0.0, 100.0, 0.0, 50.0, true) - 50.0).abs() < f64::EPSILON);
+        assert!((value_at_position(25.0, 100.0, 0.0, 50.0, true) - 37.5).abs() < f64::EPSILON);
+        assert!((value_at_position(100.0, 100.0, 0.0, 50.0, true) - 0.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_value_at_position_out_of_bounds() {
+        assert!((value_at_position(-20.0, 100.0, 0.0, 50.0, false) - 0.0).abs() < f64::EPSILON);
+        assert!((value_at_position(150.0, 100.0, 0.0, 50.0, false) - 50.0).abs() < f64::EPSILON);
+        assert!((value_at_position(150.0, 100.0, 0.0, 50.0, true) - 0.0).abs() < f64::EPSILON);
+
+        // not yet allocated
+        assert!((value_at_position(10.0, 0.0, 5.0, 50.0, false) - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(None, 42.0, 1), "42.0");
+        assert_eq!(format_value(Some("{}%"), 42.0, 0), "42%");
+        assert_eq!(format_value(Some("{}%"), 42.56, 1), "42.6%");
+        assert_eq!(format_value(Some("vol: {} ({})"), 7.0, 0), "vol: 7 (7)");
+        assert_eq!(format_value(Some("static"), 7.0, 0), "static");
+    }
+
+    #[test]
+    fn test_format_value_negative_digits() {
+        assert_eq!(format_value(Some("{}%"), 42.4, -1), "42%");
+    }
+
+    #[test]
+    fn test_show_value_from_show_label() {
+        assert_eq!(slider(json!({})).show_value(), ShowValue::Always);
+        assert_eq!(
+            slider(json!({ "show_label": false })).show_value(),
+            ShowValue::Never
+        );
+        assert_eq!(
+            slider(json!({ "show_label": false, "show_value": "hover" })).show_value(),
+            ShowValue::Hover
+        );
+    }
+
+    #[test]
+    fn test_always_visible() {
+        for (hovered, dragging) in [(false, false), (true, false), (false, true), (true, true)] {
+            assert!(ShowValue::Always.is_label_visible(hovered, dragging));
+            assert!(!ShowValue::Never.is_label_visible(hovered, dragging));
+            assert!(!ShowValue::Tooltip.is_label_visible(hovered, dragging));
+        }
+    }
+
+    #[test]
+    fn test_hover_visible() {
+        assert!(!ShowValue::Hover.is_label_visible(false, false));
+        assert!(ShowValue::Hover.is_label_visible(true, false));
+        assert!(ShowValue::Hover.is_label_visible(true, true));
+
+        // stays visible when dragged outside the slider
+        assert!(ShowValue::Hover.is_label_visible(false, true));
+    }
+}
@@ -0,0 +1,328 @@
+use color_eyre::{Help, Report, Result};
+use gtk::prelude::*;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::str::FromStr;
+use tracing::{debug, warn};
+
+use super::{CustomCommand, CustomWidget, CustomWidgetContext, WidgetConfig};
+use crate::config::ModuleOrientation;
+use crate::dynamic_value::dynamic_string_when_visible;
+use crate::{build, glib_recv};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct StackWidget {
+    name: Option<String>,
+    class: Option<String>,
+    /// Named pages, of which only one is shown at a time.
+    /// The first page is shown initially.
+    #[serde(default)]
+    pages: Vec<StackPage>,
+    /// Name of the page to show.
+    /// Can be a dynamic string, to switch pages from a script or variable.
+    page: Option<String>,
+    /// Orientation of the container inside each page.
+    orientation: Option<ModuleOrientation>,
+    /// Animation used when switching pages.
+    #[serde(default)]
+    transition_type: StackTransition,
+    /// The length of the animation in milliseconds.
+    #[serde(default = "default_transition_duration")]
+    transition_duration: u32,
+}
+
+const fn default_transition_duration() -> u32 {
+    250
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+pub struct StackPage {
+    name: String,
+    #[serde(default)]
+    widgets: Vec<WidgetConfig>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StackTransition {
+    None,
+    #[default]
+    Crossfade,
+    SlideLeft,
+    SlideRight,
+    SlideUp,
+    SlideDown,
+    /// Slides left or right depending on the order of the pages.
+    SlideLeftRight,
+    /// Slides up or down depending on the order of the pages.
+    SlideUpDown,
+}
+
+impl From<StackTransition> for gtk::StackTransitionType {
+    fn from(transition: StackTransition) -> Self {
+        match transition {
+            StackTransition::None => Self::None,
+            StackTransition::Crossfade => Self::Crossfade,
+            StackTransition::SlideLeft => Self::SlideLeft,
+            StackTransition::SlideRight => Self::SlideRight,
+            StackTransition::SlideUp => Self::SlideUp,
+            StackTransition::SlideDown => Self::SlideDown,
+            StackTransition::SlideLeftRight => Self::SlideLeftRight,
+            StackTransition::SlideUpDown => Self::SlideUpDown,
+        }
+    }
+}
+
+/// A change to the visible page of a stack.
+///
+/// These are parsed from the part of a `stack:` command after the prefix,
+/// in the format `show page`, or `show stack/page` to target a stack by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackCommand {
+    /// Name of the stack widget to switch.
+    /// Every stack in the module is switched if this is `None`.
+    pub stack: Option<String>,
+    pub page: String,
+}
+
+impl FromStr for StackCommand {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (action, target) = s.split_once(' ').unwrap_or((s, ""));
+
+        if action != "show" {
+            return Err(Report::msg(format!("Invalid stack action: '{action}'"))
+                .suggestion("Use `show <page>`"));
+        }
+
+        let target = target.trim();
+        let (stack, page) = match target.split_once('/') {
+            Some((stack, page)) => (Some(stack.trim()), page.trim()),
+            None => (None, target),
+        };
+
+        if page.is_empty() || stack.is_some_and(str::is_empty) {
+            return Err(Report::msg("Stack page name cannot be empty"));
+        }
+
+        Ok(Self {
+            stack: stack.map(ToString::to_string),
+            page: page.to_string(),
+        })
+    }
+}
+
+/// Shows the page called `name`.
+///
+/// Returns `false` and leaves the current page visible
+/// if the stack has no such page.
+fn show_page(stack: &gtk::Stack, name: &str) -> bool {
+    let name = name.trim();
+
+    match stack.child_by_name(name) {
+        Some(page) => {
+            stack.set_visible_child(&page);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Shows the page called `name`, logging a warning if it does not exist.
+fn show_page_or_warn(stack: &gtk::Stack, name: &str) {
+    if show_page(stack, name) {
+        debug!("Showing stack page '{name}'");
+    } else {
+        warn!("Stack has no page called '{name}'");
+    }
+}
+
+impl StackWidget {
+    /// Applies the transition options to the stack.
+    fn configure(&self, stack: &gtk::Stack) {
+        stack.set_transition_type(self.transition_type.into());
+        stack.set_transition_duration(self.transition_duration);
+    }
+
+    /// Checks whether a command targets this stack.
+    fn accepts(&self, command: &StackCommand) -> bool {
+        command
+            .stack
+            .as_ref()
+            .is_none_or(|stack| self.name.as_ref() == Some(stack))
+    }
+}
+
+impl CustomWidget for StackWidget {
+    type Widget = gtk::Stack;
+
+    fn into_widget(mut self, context: CustomWidgetContext) -> Self::Widget {
+        let stack = build!(self, Self::Widget);
+        self.configure(&stack);
+
+        let orientation = self
+            .orientation
+            .map_or(gtk::Orientation::Horizontal, Into::into);
+
+        for page in std::mem::take(&mut self.pages) {
+            if stack.child_by_name(&page.name).is_some() {
+                warn!("Ignoring duplicate stack page '{}'", page.name);
+                continue;
+            }
+
+            let container = gtk::Box::new(orientation, 0);
+
+            for widget in page.widgets {
+                widget.widget.add_to(&container, &context, widget.common);
+            }
+
+            // pages must be visible to be switched to
+            container.show();
+            stack.add_named(&container, &page.name);
+        }
+
+        if let Some(page) = &self.page {
+            let stack = stack.clone();
+            dynamic_string_when_visible(page, context.visibility.clone(), move |page| {
+                show_page_or_warn(&stack, &page);
+            });
+        }
+
+        {
+            let stack = stack.clone();
+            let rx = context.updates.subscribe();
+
+            glib_recv!(rx, command => {
+                if let CustomCommand::Stack(command) = command {
+                    if self.accepts(&command) {
+                        show_page_or_warn(&stack, &command.page);
+                    }
+                }
+            });
+        }
+
+        stack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            "show media".parse::<StackCommand>().unwrap(),
+            StackCommand {
+                stack: None,
+                page: "media".to_string()
+            }
+        );
+        assert_eq!(
+            "show  media ".parse::<StackCommand>().unwrap(),
+            StackCommand {
+                stack: None,
+                page: "media".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_targeted_command() {
+        assert_eq!(
+            "show info/media".parse::<StackCommand>().unwrap(),
+            StackCommand {
+                stack: Some("info".to_string()),
+                page: "media".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_command() {
+        for input in [
+            "",
+            "show",
+            "show ",
+            "hide media",
+            "show info/",
+            "show /media",
+        ] {
+            assert!(input.parse::<StackCommand>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_accepts() {
+        let widget: StackWidget = serde_json::from_value(json!({
+            "name": "info",
+            "pages": []
+        }))
+        .unwrap();
+
+        let command = |stack: Option<&str>| StackCommand {
+            stack: stack.map(ToString::to_string),
+            page: "media".to_string(),
+        };
+
+        assert!(widget.accepts(&command(None)));
+        assert!(widget.accepts(&command(Some("info"))));
+        assert!(!widget.accepts(&command(Some("other"))));
+    }
+
+    #[test]
+    fn test_transition_parsing() {
+        let widget: StackWidget = serde_json::from_value(json!({ "pages": [] })).unwrap();
+        assert_eq!(widget.transition_type, StackTransition::Crossfade);
+        assert_eq!(widget.transition_duration, 250);
+
+        let widget: StackWidget = serde_json::from_value(json!({
+            "pages": [{ "name": "one" }],
+            "transition_type": "slide_left_right",
+            "transition_duration": 100
+        }))
+        .unwrap();
+        assert_eq!(widget.transition_type, StackTransition::SlideLeftRight);
+        assert_eq!(widget.transition_duration, 100);
+        assert_eq!(widget.pages[0].name, "one");
+        assert!(widget.pages[0].widgets.is_empty());
+    }
+
+    #[test]
+    fn test_show_page() {
+        with_gtk(|| {
+            let stack = gtk::Stack::new();
+
+            for name in ["first", "second"] {
+                let page = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+                page.show();
+                stack.add_named(&page, name);
+            }
+
+            assert_eq!(stack.visible_child_name().as_deref(), Some("first"));
+
+            assert!(show_page(&stack, "second"));
+            assert_eq!(stack.visible_child_name().as_deref(), Some("second"));
+
+            assert!(show_page(&stack, " first\n"));
+            assert_eq!(stack.visible_child_name().as_deref(), Some("first"));
+        });
+    }
+
+    #[test]
+    fn test_show_missing_page() {
+        with_gtk(|| {
+            let stack = gtk::Stack::new();
+
+            let page = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            page.show();
+            stack.add_named(&page, "first");
+
+            assert!(!show_page(&stack, "missing"));
+            assert_eq!(stack.visible_child_name().as_deref(), Some("first"));
+        });
+    }
+}
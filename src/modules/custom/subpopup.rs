@@ -0,0 +1,372 @@
+use color_eyre::{Help, Report, Result};
+use gtk::prelude::*;
+use gtk::Button;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::str::FromStr;
+use tracing::{debug, error};
+
+use crate::modules::PopupButton;
+use crate::popup::Popup;
+
+/// A change to a named subpopup.
+///
+/// These are parsed from the part of a `subpopup:` command after the prefix,
+/// in the format `action name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubpopupCommand {
+    pub action: SubpopupAction,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubpopupAction {
+    Open,
+    Close,
+    Toggle,
+}
+
+impl FromStr for SubpopupCommand {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (action, name) = s.split_once(' ').unwrap_or((s, ""));
+
+        let action = match action {
+            "open" => SubpopupAction::Open,
+            "close" => SubpopupAction::Close,
+            "toggle" => SubpopupAction::Toggle,
+            _ => {
+                return Err(Report::msg(format!("Invalid subpopup action: '{action}'"))
+                    .suggestion("Use one of `open`, `close` or `toggle`"))
+            }
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Report::msg("Subpopup name cannot be empty"));
+        }
+
+        Ok(Self {
+            action,
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Tracks the chain of open subpopups,
+/// each opened from the one before it.
+/// The first is opened from the module's main popup.
+#[derive(Debug, Default)]
+pub struct SubpopupStack(Vec<String>);
+
+impl SubpopupStack {
+    /// Opens `name` from `parent`,
+    /// or from the main popup if `parent` is `None`.
+    ///
+    /// Returns the subpopups which must be closed to make way for it,
+    /// innermost first, which never includes `name` itself.
+    /// Returns `None` if `name` cannot be opened,
+    /// because `parent` is not open or `name` is already open above it.
+    pub fn open(&mut self, name: &str, parent: Option<&str>) -> Option<Vec<String>> {
+        let start = match parent {
+            Some(parent) => self.position(parent)? + 1,
+            None => 0,
+        };
+
+        if self.0[..start].iter().any(|open| open == name) {
+            return None;
+        }
+
+        // already open from the same parent, so only close its children
+        let keep = if self.0.get(start).is_some_and(|open| open == name) {
+            start + 1
+        } else {
+            start
+        };
+
+        let closed = self.0.split_off(keep).into_iter().rev().collect();
+
+        if keep == start {
+            self.0.push(name.to_string());
+        }
+
+        Some(closed)
+    }
+
+    /// Closes `name` and any subpopups opened from it.
+    ///
+    /// Returns the closed subpopups, innermost first.
+    pub fn close(&mut self, name: &str) -> Vec<String> {
+        self.position(name).map_or_else(Vec::new, |index| {
+            self.0.split_off(index).into_iter().rev().collect()
+        })
+    }
+
+    /// Closes every subpopup, for when the main popup closes.
+    ///
+    /// Returns the closed subpopups, innermost first.
+    pub fn close_all(&mut self) -> Vec<String> {
+        self.0.drain(..).rev().collect()
+    }
+
+    pub fn is_open(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Checks whether any subpopup is open from `parent`,
+    /// or from the main popup if `parent` is `None`.
+    pub fn has_children(&self, parent: Option<&str>) -> bool {
+        match parent {
+            Some(parent) => self
+                .position(parent)
+                .is_some_and(|index| index + 1 < self.0.len()),
+            None => !self.0.is_empty(),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.0.iter().position(|open| open == name)
+    }
+}
+
+/// A popup window opened from a widget inside another popup.
+#[derive(Debug, Clone)]
+pub struct Subpopup {
+    pub popup: Popup,
+    /// The key the popup's content is registered under.
+    pub id: usize,
+}
+
+/// The subpopups belonging to a custom module.
+#[derive(Debug)]
+pub struct Subpopups {
+    main: Rc<Popup>,
+    popups: HashMap<String, Subpopup>,
+    /// Buttons which may open subpopups, keyed by their popup ID,
+    /// along with the subpopup they are inside.
+    /// Buttons in the main popup have no parent.
+    anchors: HashMap<usize, (Button, Option<String>)>,
+    stack: RefCell<SubpopupStack>,
+    gap: i32,
+}
+
+impl Subpopups {
+    /// Creates the subpopup state,
+    /// closing subpopups whenever the popup they were opened from closes.
+    pub fn new(
+        main: Rc<Popup>,
+        popups: HashMap<String, Subpopup>,
+        anchors: HashMap<usize, (Button, Option<String>)>,
+        gap: i32,
+    ) -> Rc<Self> {
+        let subpopups = Rc::new(Self {
+            main,
+            popups,
+            anchors,
+            stack: RefCell::default(),
+            gap,
+        });
+
+        {
+            let handler = subpopups.clone();
+            subpopups
+                .main
+                .window
+                .connect_hide(move |_| handler.close_all());
+        }
+
+        for (name, subpopup) in &subpopups.popups {
+            let name = name.clone();
+            let handler = subpopups.clone();
+
+            subpopup
+                .popup
+                .window
+                .connect_hide(move |_| handler.close(&name));
+        }
+
+        subpopups
+    }
+
+    /// Runs the command, using the button it came from as the anchor.
+    pub fn handle(&self, command: &SubpopupCommand, button_id: usize) {
+        match command.action {
+            SubpopupAction::Open => self.open(&command.name, button_id),
+            SubpopupAction::Close => self.close(&command.name),
+            SubpopupAction::Toggle => {
+                if self.stack.borrow().is_open(&command.name) {
+                    self.close(&command.name);
+                } else {
+                    self.open(&command.name, button_id);
+                }
+            }
+        }
+    }
+
+    fn open(&self, name: &str, button_id: usize) {
+        let Some(subpopup) = self.popups.get(name) else {
+            error!("Invalid subpopup name: '{name}'");
+            return;
+        };
+
+        let Some((button, parent)) = self.anchors.get(&button_id) else {
+            error!("Subpopups can only be opened from a button inside a popup");
+            return;
+        };
+
+        let parent_popup = match parent {
+            Some(parent) => match self.popups.get(parent) {
+                Some(parent) => &parent.popup,
+                None => return,
+            },
+            None => &*self.main,
+        };
+
+        // release the borrow before hiding,
+        // as each popup updates the stack when hidden
+        let closed = self.stack.borrow_mut().open(name, parent.as_deref());
+        let Some(closed) = closed else {
+            debug!("Not opening subpopup '{name}' as its parent is not open");
+            return;
+        };
+
+        self.hide(&closed);
+
+        debug!("Opening subpopup '{name}' [#{}]", button.popup_id());
+
+        subpopup.popup.set_gap(parent_popup.outer_gap() + self.gap);
+        subpopup
+            .popup
+            .show_at(subpopup.id, parent_popup.child_geometry(button));
+
+        self.update_keep_open();
+    }
+
+    fn close(&self, name: &str) {
+        let closed = self.stack.borrow_mut().close(name);
+        self.hide(&closed);
+        self.update_keep_open();
+    }
+
+    fn close_all(&self) {
+        let closed = self.stack.borrow_mut().close_all();
+        self.hide(&closed);
+        self.update_keep_open();
+    }
+
+    fn hide(&self, names: &[String]) {
+        for name in names {
+            if let Some(subpopup) = self.popups.get(name) {
+                subpopup.popup.hide();
+            }
+        }
+    }
+
+    /// Stops popups with open children from closing
+    /// as the pointer moves across to their children.
+    fn update_keep_open(&self) {
+        let stack = self.stack.borrow();
+
+        self.main.set_keep_open(stack.has_children(None));
+        for (name, subpopup) in &self.popups {
+            subpopup.popup.set_keep_open(stack.has_children(Some(name)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(open: &[&str]) -> SubpopupStack {
+        SubpopupStack(open.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            "open volume".parse::<SubpopupCommand>().unwrap(),
+            SubpopupCommand {
+                action: SubpopupAction::Open,
+                name: "volume".to_string()
+            }
+        );
+        assert_eq!(
+            "toggle  power menu ".parse::<SubpopupCommand>().unwrap(),
+            SubpopupCommand {
+                action: SubpopupAction::Toggle,
+                name: "power menu".to_string()
+            }
+        );
+
+        assert!("open".parse::<SubpopupCommand>().is_err());
+        assert!("show volume".parse::<SubpopupCommand>().is_err());
+    }
+
+    #[test]
+    fn test_open_does_not_close_parent() {
+        let mut stack = stack(&["a"]);
+
+        assert_eq!(stack.open("b", Some("a")), Some(vec![]));
+        assert!(stack.is_open("a"));
+        assert!(stack.is_open("b"));
+        assert!(stack.has_children(None));
+        assert!(stack.has_children(Some("a")));
+        assert!(!stack.has_children(Some("b")));
+    }
+
+    #[test]
+    fn test_open_replaces_sibling() {
+        let mut stack = stack(&["a", "b", "c"]);
+
+        assert_eq!(
+            stack.open("d", Some("a")),
+            Some(vec!["c".to_string(), "b".to_string()])
+        );
+        assert_eq!(stack.0, ["a", "d"]);
+
+        assert_eq!(
+            stack.open("e", None),
+            Some(vec!["d".to_string(), "a".to_string()])
+        );
+        assert_eq!(stack.0, ["e"]);
+    }
+
+    #[test]
+    fn test_reopen_closes_children_only() {
+        let mut stack = stack(&["a", "b", "c"]);
+
+        assert_eq!(stack.open("b", Some("a")), Some(vec!["c".to_string()]));
+        assert_eq!(stack.0, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_open_invalid_parent() {
+        let mut stack = stack(&["a"]);
+
+        assert_eq!(stack.open("b", Some("missing")), None);
+        assert_eq!(stack.open("a", Some("a")), None);
+        assert_eq!(stack.0, ["a"]);
+    }
+
+    #[test]
+    fn test_close_closes_children() {
+        let mut stack = stack(&["a", "b", "c"]);
+
+        assert_eq!(stack.close("b"), ["c", "b"]);
+        assert_eq!(stack.0, ["a"]);
+
+        assert!(stack.close("missing").is_empty());
+        assert_eq!(stack.0, ["a"]);
+    }
+
+    #[test]
+    fn test_close_all() {
+        let mut stack = stack(&["a", "b"]);
+
+        assert_eq!(stack.close_all(), ["b", "a"]);
+        assert!(!stack.has_children(None));
+    }
+}
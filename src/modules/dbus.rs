@@ -0,0 +1,307 @@
+use color_eyre::Result;
+use futures_lite::StreamExt;
+use gtk::prelude::*;
+use gtk::Label;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::names::{BusName, InterfaceName};
+use zbus::zvariant::{ObjectPath, Value};
+use zbus::Connection;
+
+use crate::config::CommonConfig;
+use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
+use crate::{glib_recv, module_impl, send_async, spawn};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Bus {
+    #[default]
+    Session,
+    System,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DbusModule {
+    /// Message bus the service is on.
+    #[serde(default)]
+    bus: Bus,
+
+    /// Well-known name of the service, such as `org.freedesktop.UPower`.
+    service: String,
+    /// Object path the property is on.
+    path: String,
+    /// Interface the property belongs to.
+    interface: String,
+    /// Name of the property to show.
+    property: String,
+
+    /// Label format. `{value}` is replaced with the property value.
+    #[serde(default = "default_format")]
+    format: String,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_format() -> String {
+    String::from("{value}")
+}
+
+/// Converts a property value into text for the label.
+///
+/// Variants are unwrapped and arrays are joined with commas.
+/// Other container types fall back to their debug representation.
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Str(string) => string.to_string(),
+        Value::Bool(bool) => bool.to_string(),
+        Value::U8(num) => num.to_string(),
+        Value::I16(num) => num.to_string(),
+        Value::U16(num) => num.to_string(),
+        Value::I32(num) => num.to_string(),
+        Value::U32(num) => num.to_string(),
+        Value::I64(num) => num.to_string(),
+        Value::U64(num) => num.to_string(),
+        Value::F64(num) => num.to_string(),
+        Value::ObjectPath(path) => path.to_string(),
+        Value::Signature(signature) => signature.to_string(),
+        Value::Value(value) => format_value(value),
+        Value::Array(array) => array
+            .get()
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        value => format!("{value:?}"),
+    }
+}
+
+/// Renders the label format, replacing `{value}` with the value.
+fn render_label(format: &str, value: &str) -> String {
+    format.replace("{value}", value)
+}
+
+/// Target of the module, parsed from the config.
+#[derive(Debug, Clone)]
+struct Target {
+    service: BusName<'static>,
+    path: ObjectPath<'static>,
+    interface: InterfaceName<'static>,
+    property: String,
+}
+
+impl DbusModule {
+    fn target(&self) -> Result<Target> {
+        Ok(Target {
+            service: BusName::try_from(self.service.clone())?,
+            path: ObjectPath::try_from(self.path.clone())?,
+            interface: InterfaceName::try_from(self.interface.clone())?,
+            property: self.property.clone(),
+        })
+    }
+}
+
+/// Gets the current value of the property,
+/// or `None` if it could not be read.
+async fn get_property(properties: &PropertiesProxy<'_>, target: &Target) -> Option<String> {
+    match properties
+        .get(target.interface.clone(), &target.property)
+        .await
+    {
+        Ok(value) => Some(format_value(&value)),
+        Err(err) => {
+            error!(
+                "Failed to get property '{}' of '{}': {err}",
+                target.property, target.service
+            );
+            None
+        }
+    }
+}
+
+/// Watches the property, calling `send` with each new value,
+/// or `None` while the service is not running.
+///
+/// If the service is not running yet,
+/// this waits for it to appear on the bus.
+async fn watch<F, Fut>(bus: Bus, target: Target, send: F) -> Result<()>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let dbus = match bus {
+        Bus::Session => Box::pin(Connection::session()).await?,
+        Bus::System => Box::pin(Connection::system()).await?,
+    };
+
+    let dbus_proxy = DBusProxy::new(&dbus).await?;
+    let properties = PropertiesProxy::builder(&dbus)
+        .destination(target.service.clone())?
+        .path(target.path.clone())?
+        .build()
+        .await?;
+
+    // subscribe before checking for the service,
+    // so that it cannot appear in-between unnoticed
+    let mut owner_changes = dbus_proxy
+        .receive_name_owner_changed_with_args(&[(0, target.service.as_str())])
+        .await?;
+    let mut property_changes = properties.receive_properties_changed().await?;
+
+    if dbus_proxy.name_has_owner(target.service.clone()).await? {
+        send(get_property(&properties, &target).await).await;
+    } else {
+        debug!("Waiting for '{}' to appear on the bus", target.service);
+        send(None).await;
+    }
+
+    loop {
+        tokio::select! {
+            Some(signal) = owner_changes.next() => {
+                let args = signal.args()?;
+
+                if args.new_owner().is_some() {
+                    send(get_property(&properties, &target).await).await;
+                } else {
+                    debug!("'{}' left the bus", target.service);
+                    send(None).await;
+                }
+            }
+            Some(signal) = property_changes.next() => {
+                let args = signal.args()?;
+                if args.interface_name != target.interface {
+                    continue;
+                }
+
+                if let Some(value) = args.changed_properties.get(target.property.as_str()) {
+                    send(Some(format_value(value))).await;
+                } else if args.invalidated_properties.contains(&target.property.as_str()) {
+                    send(get_property(&properties, &target).await).await;
+                }
+            }
+            else => break Ok(()),
+        }
+    }
+}
+
+impl Module<Label> for DbusModule {
+    type SendMessage = Option<String>;
+    type ReceiveMessage = ();
+
+    module_impl!("dbus");
+
+    fn error(update: &Self::SendMessage) -> Option<String> {
+        update
+            .is_none()
+            .then(|| "D-Bus property is unavailable".to_string())
+    }
+
+    fn reports_errors() -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        // invalid names disable the module
+        let target = self.target()?;
+
+        let bus = self.bus;
+        let tx = context.tx.clone();
+
+        spawn(async move {
+            let send = |value| {
+                let tx = tx.clone();
+                async move {
+                    send_async!(tx, ModuleUpdateEvent::Update(value));
+                }
+            };
+
+            if let Err(err) = watch(bus, target, send).await {
+                error!("D-Bus property watcher failed: {err:?}");
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Result<ModuleParts<Label>> {
+        let label = Label::new(None);
+
+        {
+            let label = label.clone();
+            let format = self.format;
+
+            glib_recv!(context.subscribe(), value => {
+                match value {
+                    Some(value) => {
+                        label.set_label(&render_label(&format, &value));
+                        label.show();
+                    }
+                    None => label.hide(),
+                }
+            });
+        }
+
+        Ok(ModuleParts {
+            widget: label,
+            popup: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(format: &str, value: &Value) -> String {
+        render_label(format, &format_value(value))
+    }
+
+    #[test]
+    fn test_format_string() {
+        assert_eq!(label("{value}", &Value::from("hello")), "hello");
+        assert_eq!(
+            label("Profile: {value}", &Value::from("balanced")),
+            "Profile: balanced"
+        );
+    }
+
+    #[test]
+    fn test_format_int() {
+        assert_eq!(label("{value}%", &Value::from(42u32)), "42%");
+        assert_eq!(label("{value}", &Value::from(-3i64)), "-3");
+        assert_eq!(label("{value}", &Value::from(255u8)), "255");
+    }
+
+    #[test]
+    fn test_format_bool() {
+        assert_eq!(label("on: {value}", &Value::from(true)), "on: true");
+        assert_eq!(label("{value}", &Value::from(false)), "false");
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(label("{value}", &Value::from(1.5f64)), "1.5");
+    }
+
+    #[test]
+    fn test_format_nested() {
+        let value = Value::new(Value::from("inner"));
+        assert_eq!(label("{value}", &value), "inner");
+
+        let value = Value::from(vec!["a", "b"]);
+        assert_eq!(label("{value}", &value), "a, b");
+    }
+}
@@ -0,0 +1,157 @@
+use super::batch::{Supersedes, UpdateBatch};
+use super::ModuleUpdateEvent;
+use crate::spawn;
+use std::future::pending;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+/// Limits the rate of `Update` events from `rx`
+/// to at most one batch per `window`.
+///
+/// The first update in a burst is forwarded immediately.
+/// Updates received during the window are held back,
+/// and forwarded in order once the window ends.
+/// A held update is only dropped if a later one `supersedes` it,
+/// so modules which send partial updates never lose any,
+/// and the final value of a burst is never dropped.
+///
+/// All other events are forwarded immediately.
+pub fn debounce_updates<T>(
+    mut rx: mpsc::Receiver<ModuleUpdateEvent<T>>,
+    window: Duration,
+    supersedes: Supersedes<T>,
+) -> mpsc::Receiver<ModuleUpdateEvent<T>>
+where
+    T: Clone + Send + 'static,
+{
+    let (tx, debounced_rx) = mpsc::channel(64);
+
+    spawn(async move {
+        let mut pending_updates = UpdateBatch::new(supersedes);
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let window_end = async {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => pending().await,
+                }
+            };
+
+            let events = tokio::select! {
+                event = rx.recv() => match event {
+                    Some(ModuleUpdateEvent::Update(update)) if deadline.is_some() => {
+                        pending_updates.push(update);
+                        continue;
+                    }
+                    Some(event @ ModuleUpdateEvent::Update(_)) => {
+                        deadline = Some(Instant::now() + window);
+                        vec![event]
+                    }
+                    Some(event) => vec![event],
+                    None => break,
+                },
+                () = window_end => {
+                    if pending_updates.is_empty() {
+                        deadline = None;
+                        continue;
+                    }
+
+                    deadline = Some(Instant::now() + window);
+                    pending_updates
+                        .take()
+                        .into_iter()
+                        .map(ModuleUpdateEvent::Update)
+                        .collect()
+                }
+            };
+
+            for event in events {
+                if tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        // flush the trailing updates once the sender is dropped
+        for update in pending_updates.take() {
+            if tx.send(ModuleUpdateEvent::Update(update)).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    debounced_rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::test_helpers::{channel, collect, next, send_all, Receiver, Sender};
+
+    /// Every value contains the full state.
+    fn debounce_channel(window: Duration) -> (Sender, Receiver) {
+        channel(|rx| debounce_updates(rx, window, |_, _| true))
+    }
+
+    #[tokio::test]
+    async fn test_burst_keeps_first_and_last() {
+        let (tx, rx) = debounce_channel(Duration::from_millis(100));
+
+        send_all(&tx, 0..10).await;
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(0), Some(9)]);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_update_flushed_on_close() {
+        let (tx, rx) = debounce_channel(Duration::from_secs(10));
+
+        send_all(&tx, [1, 2, 3]).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(1), Some(3)]);
+    }
+
+    #[tokio::test]
+    async fn test_separate_windows() {
+        let (tx, rx) = debounce_channel(Duration::from_millis(50));
+
+        send_all(&tx, [1]).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        send_all(&tx, [2]).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_partial_updates_kept() {
+        // only even values contain the full state
+        let (tx, rx) =
+            channel(|rx| debounce_updates(rx, Duration::from_millis(100), |next, _| next % 2 == 0));
+
+        send_all(&tx, [1, 3, 5, 6, 7]).await;
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(1), Some(6), Some(7)]);
+    }
+
+    #[tokio::test]
+    async fn test_other_events_not_delayed() {
+        let (tx, mut rx) = debounce_channel(Duration::from_secs(10));
+
+        send_all(&tx, [1, 2]).await;
+        tx.send(ModuleUpdateEvent::ClosePopup).await.unwrap();
+
+        assert_eq!(next(&mut rx).await, Some(1));
+        assert_eq!(next(&mut rx).await, None);
+    }
+}
@@ -0,0 +1,127 @@
+use super::ModuleUpdateEvent;
+use crate::script::Script;
+use crate::spawn;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Gets the error message for an update,
+/// if it puts the module into an error state.
+pub type ErrorMessage<T> = fn(update: &T) -> Option<String>;
+
+/// Tracks whether a module is in an error state,
+/// so that a hook only fires on entering it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ErrorEdge {
+    in_error: bool,
+}
+
+impl ErrorEdge {
+    /// Updates the state from the error of the latest update, if any.
+    ///
+    /// Returns the message if the module has just entered the error state.
+    fn update(&mut self, error: Option<String>) -> Option<String> {
+        let entered = error.is_some() && !self.in_error;
+        self.in_error = error.is_some();
+
+        error.filter(|_| entered)
+    }
+}
+
+/// Runs `script` each time an `Update` event from `rx`
+/// puts the module into an error state,
+/// passing the error message as an argument.
+///
+/// The script does not run again while the error persists,
+/// only after the module has recovered and then failed again.
+///
+/// All events are forwarded as-is.
+pub fn hook_errors<T>(
+    mut rx: mpsc::Receiver<ModuleUpdateEvent<T>>,
+    script: Script,
+    error_message: ErrorMessage<T>,
+) -> mpsc::Receiver<ModuleUpdateEvent<T>>
+where
+    T: Clone + Send + 'static,
+{
+    let (tx, hooked_rx) = mpsc::channel(64);
+
+    spawn(async move {
+        let mut edge = ErrorEdge::default();
+
+        while let Some(event) = rx.recv().await {
+            if let ModuleUpdateEvent::Update(update) = &event {
+                if let Some(message) = edge.update(error_message(update)) {
+                    run_hook(&script, message);
+                }
+            }
+
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    hooked_rx
+}
+
+/// Runs the `on_error` script,
+/// passing the error message as an argument.
+pub fn run_hook(script: &Script, message: String) {
+    debug!("Running on-error script: {message}");
+    script.run_as_oneshot(Some(&[message]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::test_helpers::{channel, collect, send_all};
+
+    fn error(message: &str) -> Option<String> {
+        Some(message.to_string())
+    }
+
+    #[test]
+    fn test_fires_on_entering_error() {
+        let mut edge = ErrorEdge::default();
+
+        assert_eq!(edge.update(None), None);
+        assert_eq!(edge.update(error("failed")), error("failed"));
+    }
+
+    #[test]
+    fn test_does_not_refire_while_persisting() {
+        let mut edge = ErrorEdge::default();
+
+        assert_eq!(edge.update(error("failed")), error("failed"));
+        assert_eq!(edge.update(error("failed")), None);
+        assert_eq!(edge.update(error("failed differently")), None);
+    }
+
+    #[test]
+    fn test_refires_after_recovery() {
+        let mut edge = ErrorEdge::default();
+
+        assert_eq!(edge.update(error("first")), error("first"));
+        assert_eq!(edge.update(None), None);
+        assert_eq!(edge.update(None), None);
+        assert_eq!(edge.update(error("second")), error("second"));
+    }
+
+    #[tokio::test]
+    async fn test_events_forwarded() {
+        let (tx, rx) = channel(|rx| {
+            hook_errors(rx, Script::from(":"), |value| {
+                (*value < 0).then(|| value.to_string())
+            })
+        });
+
+        send_all(&tx, [1, -1, -2, 2]).await;
+        tx.send(ModuleUpdateEvent::ClosePopup).await.unwrap();
+        drop(tx);
+
+        assert_eq!(
+            collect(rx).await,
+            [Some(1), Some(-1), Some(-2), Some(2), None]
+        );
+    }
+}
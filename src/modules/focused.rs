@@ -1,17 +1,19 @@
 use crate::clients::wayland::{self, ToplevelEvent};
 use crate::config::{CommonConfig, TruncateMode};
-use crate::gtk_helpers::IronbarGtkExt;
+use crate::gtk_helpers::{format_markup, IronbarGtkExt};
 use crate::image::ImageProvider;
 use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
 use crate::{glib_recv, module_impl, send_async, spawn, try_send};
 use color_eyre::Result;
 use gtk::prelude::*;
 use gtk::Label;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::mpsc;
 use tracing::debug;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct FocusedModule {
     /// Whether to show icon on the bar.
     #[serde(default = "crate::config::default_true")]
@@ -26,6 +28,10 @@ pub struct FocusedModule {
 
     truncate: Option<TruncateMode>,
 
+    /// Format of the tooltip shown when hovering the widget.
+    /// Supports the `{title}` and `{app_id}` tokens, as well as Pango markup.
+    tooltip_format: Option<String>,
+
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
 }
@@ -37,6 +43,7 @@ impl Default for FocusedModule {
             show_title: crate::config::default_true(),
             icon_size: default_icon_size(),
             truncate: None,
+            tooltip_format: None,
             common: Some(CommonConfig::default()),
         }
     }
@@ -52,6 +59,10 @@ impl Module<gtk::Box> for FocusedModule {
 
     module_impl!("focused");
 
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
@@ -121,6 +132,7 @@ impl Module<gtk::Box> for FocusedModule {
         info: &ModuleInfo,
     ) -> Result<ModuleParts<gtk::Box>> {
         let icon_theme = info.icon_theme;
+        let scale = info.scale_factor();
 
         let container = gtk::Box::new(info.bar_position.orientation(), 5);
 
@@ -141,11 +153,12 @@ impl Module<gtk::Box> for FocusedModule {
 
         {
             let icon_theme = icon_theme.clone();
+            let container = container.clone();
             glib_recv!(context.subscribe(), data => {
                 if let Some((name, id)) = data {
                     if self.show_icon {
                         match ImageProvider::parse(&id, &icon_theme, true, self.icon_size)
-                            .map(|image| image.load_into_image(icon.clone()))
+                            .map(|image| image.with_scale(scale).load_into_image(icon.clone()))
                         {
                             Some(Ok(())) => icon.show(),
                             _ => icon.hide(),
@@ -156,9 +169,18 @@ impl Module<gtk::Box> for FocusedModule {
                         label.show();
                         label.set_label(&name);
                     }
+
+                    if let Some(format) = &self.tooltip_format {
+                        let tooltip = tooltip_markup(format, &name, &id);
+                        container.set_tooltip_markup(Some(&tooltip));
+                    }
                 } else {
                     icon.hide();
                     label.hide();
+
+                    if self.tooltip_format.is_some() {
+                        container.set_tooltip_markup(None);
+                    }
                 }
             });
         }
@@ -169,3 +191,40 @@ impl Module<gtk::Box> for FocusedModule {
         })
     }
 }
+
+/// Replaces the `{title}` and `{app_id}` tokens in a Pango markup string,
+/// escaping the values so that they are always shown as plain text.
+fn tooltip_markup(format: &str, title: &str, app_id: &str) -> String {
+    format_markup(format, |token| match token {
+        "title" => Some(title.to_string()),
+        "app_id" => Some(app_id.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip_tokens() {
+        assert_eq!(
+            tooltip_markup("<b>{title}</b> ({app_id}) {other}", "Inbox", "thunderbird"),
+            "<b>Inbox</b> (thunderbird) {other}"
+        );
+    }
+
+    #[test]
+    fn test_tooltip_values_escaped() {
+        // values are never treated as tokens or markup
+        let markup = tooltip_markup("{title}", "<b>{app_id} & co</b>", "firefox");
+
+        assert_eq!(markup, "&lt;b&gt;{app_id} &amp; co&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_invalid_format_falls_back() {
+        let markup = tooltip_markup("<b>{title}", "Tom & Jerry", "thunderbird");
+        assert_eq!(markup, "&lt;b&gt;Tom &amp; Jerry");
+    }
+}
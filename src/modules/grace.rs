@@ -0,0 +1,188 @@
+use super::ModuleUpdateEvent;
+use crate::spawn;
+use std::future::pending;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+
+/// Checks whether an update puts the module into an error or unavailable state.
+pub type IsError<T> = fn(update: &T) -> bool;
+
+/// Holds back error `Update` events from `rx`
+/// until they have persisted for `period`,
+/// so the widget keeps showing the last good state during brief failures.
+///
+/// If a good update arrives within the period, the error is dropped
+/// and the good update is forwarded immediately.
+/// Further errors during the period replace the held error
+/// without extending the period.
+/// Once an error has been shown, later errors are forwarded immediately
+/// until a good update arrives.
+///
+/// Good updates and all other events are forwarded immediately.
+pub fn grace_updates<T>(
+    mut rx: mpsc::Receiver<ModuleUpdateEvent<T>>,
+    period: Duration,
+    is_error: IsError<T>,
+) -> mpsc::Receiver<ModuleUpdateEvent<T>>
+where
+    T: Clone + Send + 'static,
+{
+    let (tx, grace_rx) = mpsc::channel(64);
+
+    spawn(async move {
+        let mut pending_error = None;
+        let mut deadline: Option<Instant> = None;
+        let mut showing_error = false;
+
+        loop {
+            let period_end = async {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => pending().await,
+                }
+            };
+
+            let event = tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event @ ModuleUpdateEvent::Update(_)) if showing_error => {
+                        if let ModuleUpdateEvent::Update(update) = &event {
+                            showing_error = is_error(update);
+                        }
+                        event
+                    }
+                    Some(ModuleUpdateEvent::Update(update)) if is_error(&update) => {
+                        deadline.get_or_insert_with(|| Instant::now() + period);
+                        pending_error = Some(update);
+                        continue;
+                    }
+                    Some(event @ ModuleUpdateEvent::Update(_)) => {
+                        // recovered within the period
+                        pending_error = None;
+                        deadline = None;
+                        event
+                    }
+                    Some(event) => event,
+                    None => break,
+                },
+                () = period_end => {
+                    deadline = None;
+                    match pending_error.take() {
+                        Some(update) => {
+                            showing_error = true;
+                            ModuleUpdateEvent::Update(update)
+                        }
+                        None => continue,
+                    }
+                }
+            };
+
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+
+        // the error never recovered, so show it once the sender is dropped
+        if let Some(update) = pending_error {
+            tx.send(ModuleUpdateEvent::Update(update)).await.ok();
+        }
+    });
+
+    grace_rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::test_helpers::{
+        channel, collect, next, send_all, update, Receiver, Sender,
+    };
+
+    /// Negative values are errors.
+    fn grace_channel(period: Duration) -> (Sender, Receiver) {
+        channel(|rx| grace_updates(rx, period, |value| *value < 0))
+    }
+
+    #[tokio::test]
+    async fn test_error_shown_after_period() {
+        let (tx, mut rx) = grace_channel(Duration::from_millis(100));
+
+        send_all(&tx, [1, -1]).await;
+        assert_eq!(next(&mut rx).await, Some(1));
+
+        // held back during the period
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(update(&rx.try_recv().unwrap()), Some(-1));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_within_period_suppresses_error() {
+        let (tx, rx) = grace_channel(Duration::from_millis(100));
+
+        send_all(&tx, [1, -1]).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        send_all(&tx, [2]).await;
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(1), Some(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_errors_do_not_extend_period() {
+        let (tx, rx) = grace_channel(Duration::from_millis(100));
+
+        send_all(&tx, [-1]).await;
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        send_all(&tx, [-2]).await;
+
+        // the period started by the first error has ended
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(-2)]);
+    }
+
+    #[tokio::test]
+    async fn test_errors_not_delayed_while_shown() {
+        let (tx, mut rx) = grace_channel(Duration::from_millis(100));
+
+        send_all(&tx, [-1]).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(update(&rx.try_recv().unwrap()), Some(-1));
+
+        send_all(&tx, [-2]).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(update(&rx.try_recv().unwrap()), Some(-2));
+
+        // held back again once recovered
+        send_all(&tx, [1, -3]).await;
+        assert_eq!(next(&mut rx).await, Some(1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pending_error_flushed_on_close() {
+        let (tx, rx) = grace_channel(Duration::from_secs(10));
+
+        send_all(&tx, [1, -1]).await;
+        drop(tx);
+
+        assert_eq!(collect(rx).await, vec![Some(1), Some(-1)]);
+    }
+
+    #[tokio::test]
+    async fn test_other_events_not_delayed() {
+        let (tx, mut rx) = grace_channel(Duration::from_secs(10));
+
+        send_all(&tx, [-1]).await;
+        tx.send(ModuleUpdateEvent::ClosePopup).await.unwrap();
+
+        assert_eq!(next(&mut rx).await, None);
+    }
+}
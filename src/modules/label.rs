@@ -1,14 +1,17 @@
 use crate::config::CommonConfig;
 use crate::dynamic_value::dynamic_string;
+use crate::format::resolve_conditionals;
 use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
 use crate::{glib_recv, module_impl, try_send};
 use color_eyre::Result;
 use gtk::prelude::*;
 use gtk::Label;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::mpsc;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LabelModule {
     label: String,
 
@@ -31,6 +34,10 @@ impl Module<Label> for LabelModule {
 
     module_impl!("label");
 
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
@@ -39,6 +46,9 @@ impl Module<Label> for LabelModule {
     ) -> Result<()> {
         let tx = context.tx.clone();
         dynamic_string(&self.label, move |string| {
+            // resolved once the values are known,
+            // so conditions can compare them
+            let string = resolve_conditionals(&string, &[]);
             try_send!(tx, ModuleUpdateEvent::Update(string));
         });
 
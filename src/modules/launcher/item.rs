@@ -7,8 +7,9 @@ use crate::modules::launcher::{ItemEvent, LauncherUpdate};
 use crate::modules::ModuleUpdateEvent;
 use crate::{read_lock, try_send};
 use glib::Propagation;
+use gtk::gdk::{DragAction, ModifierType};
 use gtk::prelude::*;
-use gtk::{Button, IconTheme};
+use gtk::{Button, DestDefaults, IconTheme, TargetEntry, TargetFlags};
 use indexmap::IndexMap;
 use std::rc::Rc;
 use std::sync::RwLock;
@@ -145,6 +146,8 @@ pub struct AppearanceOptions {
     pub show_names: bool,
     pub show_icons: bool,
     pub icon_size: i32,
+    /// Scale factor of the bar's output.
+    pub scale: i32,
 }
 
 impl ItemButton {
@@ -171,7 +174,8 @@ impl ItemButton {
             } else {
                 item.app_id.clone()
             };
-            let image = ImageProvider::parse(&input, icon_theme, true, appearance.icon_size);
+            let image = ImageProvider::parse(&input, icon_theme, true, appearance.icon_size)
+                .map(|image| image.with_scale(appearance.scale));
             if let Some(image) = image {
                 button.set_image(Some(&gtk_image));
                 button.set_always_show_image(true);
@@ -282,6 +286,37 @@ impl ItemButton {
         }
     }
 
+    /// Allows the button to be dragged onto other item buttons to reorder it.
+    pub fn enable_reorder(&self, app_id: &str, controller_tx: &Sender<ItemEvent>) {
+        let targets = [TargetEntry::new(
+            "ironbar/launcher-item",
+            TargetFlags::SAME_APP,
+            0,
+        )];
+
+        self.button
+            .drag_source_set(ModifierType::BUTTON1_MASK, &targets, DragAction::MOVE);
+        self.button
+            .drag_dest_set(DestDefaults::ALL, &targets, DragAction::MOVE);
+
+        {
+            let app_id = app_id.to_string();
+            self.button.connect_drag_data_get(move |_, _, data, _, _| {
+                data.set(&data.target(), 8, app_id.as_bytes());
+            });
+        }
+
+        {
+            let app_id = app_id.to_string();
+            let tx = controller_tx.clone();
+            self.button
+                .connect_drag_data_received(move |_, _, _, _, data, _, _| {
+                    let dragged = String::from_utf8_lossy(&data.data()).to_string();
+                    try_send!(tx, ItemEvent::Reorder(dragged, app_id.clone()));
+                });
+        }
+    }
+
     pub fn set_focused(&self, focused: bool) {
         self.update_class("focused", focused);
     }
@@ -1,8 +1,10 @@
 mod item;
 mod open_state;
+mod order;
 
 use self::item::{AppearanceOptions, Item, ItemButton, Window};
 use self::open_state::OpenState;
+use self::order::SavedOrder;
 use super::{Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, WidgetContext};
 use crate::clients::wayland::{self, ToplevelEvent};
 use crate::config::CommonConfig;
@@ -12,13 +14,15 @@ use color_eyre::{Help, Report};
 use gtk::prelude::*;
 use gtk::{Button, Orientation};
 use indexmap::IndexMap;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::process::{Command, Stdio};
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, trace};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LauncherModule {
     /// List of app IDs (or classes) to always show regardless of open state,
     /// in the order specified.
@@ -36,6 +40,11 @@ pub struct LauncherModule {
     #[serde(default = "crate::config::default_false")]
     reversed: bool,
 
+    /// Whether items can be dragged to reorder them.
+    /// The order is saved, and restored on the next start.
+    #[serde(default = "crate::config::default_false")]
+    reorderable: bool,
+
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
 }
@@ -60,6 +69,8 @@ pub enum LauncherUpdate {
     Focus(String, bool),
     /// Declares the item with `app_id` has been hovered over
     Hover(String),
+    /// Sets the order of items by `app_id`
+    Order(Vec<String>),
 }
 
 #[derive(Debug)]
@@ -67,6 +78,8 @@ pub enum ItemEvent {
     FocusItem(String),
     FocusWindow(usize),
     OpenItem(String),
+    /// Moves the item with the first `app_id` into the position of the second
+    Reorder(String, String),
 }
 
 enum ItemOrWindow {
@@ -87,7 +100,7 @@ impl Module<gtk::Box> for LauncherModule {
 
     fn spawn_controller(
         &self,
-        _info: &ModuleInfo,
+        info: &ModuleInfo,
         context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
         mut rx: mpsc::Receiver<Self::ReceiveMessage>,
     ) -> crate::Result<()> {
@@ -110,12 +123,20 @@ impl Module<gtk::Box> for LauncherModule {
 
         let items2 = Arc::clone(&items);
 
+        let saved_order = if self.reorderable {
+            SavedOrder::load(info.bar_name, &context.name).map(|order| arc_mut!(order))
+        } else {
+            None
+        };
+        let saved_order2 = saved_order.clone();
+
         let tx = context.tx.clone();
         let tx2 = context.tx.clone();
 
         let wl = context.client::<wayland::Client>();
         spawn(async move {
             let items = items2;
+            let saved_order = saved_order2;
             let tx = tx2;
 
             let mut wlrx = wl.subscribe_toplevels();
@@ -145,6 +166,14 @@ impl Module<gtk::Box> for LauncherModule {
                 }
             }
 
+            if let Some(order) = &saved_order {
+                let order = {
+                    let (order, items) = (lock!(order), lock!(items));
+                    sorted_ids(&order, &items)
+                };
+                try_send!(tx, ModuleUpdateEvent::Update(LauncherUpdate::Order(order)));
+            }
+
             let send_update = |update: LauncherUpdate| tx.send(ModuleUpdateEvent::Update(update));
 
             while let Ok(event) = wlrx.recv().await {
@@ -174,12 +203,21 @@ impl Module<gtk::Box> for LauncherModule {
 
                         match new_item {
                             ItemOrWindow::Item(item) => {
-                                send_update(LauncherUpdate::AddItem(item)).await
+                                send_update(LauncherUpdate::AddItem(item)).await?;
+
+                                // move the new item into its saved position
+                                if let Some(order) = &saved_order {
+                                    let order = {
+                                        let (order, items) = (lock!(order), lock!(items));
+                                        sorted_ids(&order, &items)
+                                    };
+                                    send_update(LauncherUpdate::Order(order)).await?;
+                                }
                             }
                             ItemOrWindow::Window(window) => {
-                                send_update(LauncherUpdate::AddWindow(app_id, window)).await
+                                send_update(LauncherUpdate::AddWindow(app_id, window)).await?;
                             }
-                        }?;
+                        }
                     }
                     ToplevelEvent::Update(info) => {
                         if let Some(item) = lock!(items).get_mut(&info.app_id) {
@@ -240,7 +278,29 @@ impl Module<gtk::Box> for LauncherModule {
         let wl = context.client::<wayland::Client>();
         spawn(async move {
             while let Some(event) = rx.recv().await {
-                if let ItemEvent::OpenItem(app_id) = event {
+                if let ItemEvent::Reorder(app_id, target) = event {
+                    let Some(saved_order) = &saved_order else {
+                        continue;
+                    };
+
+                    let order = {
+                        let mut saved_order = lock!(saved_order);
+                        let items = lock!(items);
+
+                        let current = items.keys().cloned().collect::<Vec<_>>();
+                        if !saved_order.move_item(&current, &app_id, &target) {
+                            continue;
+                        }
+
+                        if let Err(err) = saved_order.save() {
+                            error!("{:?}", err.wrap_err("Failed to save launcher order"));
+                        }
+
+                        sorted_ids(&saved_order, &items)
+                    };
+
+                    send_async!(tx, ModuleUpdateEvent::Update(LauncherUpdate::Order(order)));
+                } else if let ItemEvent::OpenItem(app_id) = event {
                     find_desktop_file(&app_id).map_or_else(
                         || error!("Could not find desktop file for {}", app_id),
                         |file| {
@@ -276,7 +336,7 @@ impl Module<gtk::Box> for LauncherModule {
                             })
                         }
                         ItemEvent::FocusWindow(id) => Some(id),
-                        ItemEvent::OpenItem(_) => unreachable!(),
+                        ItemEvent::OpenItem(_) | ItemEvent::Reorder(..) => unreachable!(),
                     };
 
                     if let Some(id) = id {
@@ -314,9 +374,11 @@ impl Module<gtk::Box> for LauncherModule {
                 show_names: self.show_names,
                 show_icons: self.show_icons,
                 icon_size: self.icon_size,
+                scale: info.scale_factor(),
             };
 
             let show_names = self.show_names;
+            let reorderable = self.reorderable;
             let bar_position = info.bar_position;
 
             let mut buttons = IndexMap::<String, ItemButton>::new();
@@ -341,6 +403,10 @@ impl Module<gtk::Box> for LauncherModule {
                                 &controller_tx,
                             );
 
+                            if reorderable && !item.app_id.is_empty() {
+                                button.enable_reorder(&item.app_id, &controller_tx);
+                            }
+
                             if self.reversed {
                                 container.pack_end(&button.button, false, false, 0);
                             } else {
@@ -400,6 +466,13 @@ impl Module<gtk::Box> for LauncherModule {
                             }
                         }
                     }
+                    LauncherUpdate::Order(app_ids) => {
+                        for (position, app_id) in app_ids.iter().enumerate() {
+                            if let Some(button) = buttons.get(app_id) {
+                                container.reorder_child(&button.button, position as i32);
+                            }
+                        }
+                    }
                     LauncherUpdate::Hover(_) => {}
                 };
             });
@@ -530,6 +603,11 @@ impl Module<gtk::Box> for LauncherModule {
     }
 }
 
+/// Gets the app IDs of the items, sorted into the saved order.
+fn sorted_ids(order: &SavedOrder, items: &IndexMap<String, Item>) -> Vec<String> {
+    order.sort(&items.keys().cloned().collect::<Vec<_>>())
+}
+
 /// Clamps a string at 24 characters.
 ///
 /// This is a hacky number derived from
@@ -0,0 +1,228 @@
+use color_eyre::Result;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tracing::{debug, error};
+
+/// Saved orders for every launcher,
+/// keyed by bar and module name.
+type Orders = BTreeMap<String, Vec<String>>;
+
+/// Item order set by dragging buttons on the bar,
+/// persisted to a state file as a list of app IDs.
+///
+/// The state file is shared between launchers,
+/// with each one's order stored under its bar and module name.
+#[derive(Debug)]
+pub struct SavedOrder {
+    path: PathBuf,
+    key: String,
+    app_ids: Vec<String>,
+}
+
+impl SavedOrder {
+    /// Loads the order for the launcher called `module_name` on `bar_name`
+    /// from the state file in the user's data directory.
+    pub fn load(bar_name: &str, module_name: &str) -> Option<Self> {
+        let path = dirs::data_dir()?
+            .join("ironbar")
+            .join("launcher_order.json");
+        Some(Self::load_from(path, format!("{bar_name}/{module_name}")))
+    }
+
+    /// Loads the order stored under `key` from `path`.
+    /// If the file or key is missing or invalid, the order starts empty.
+    fn load_from(path: PathBuf, key: String) -> Self {
+        let app_ids = read_orders(&path).remove(&key).unwrap_or_default();
+
+        debug!("Loaded launcher order for '{key}': {app_ids:?}");
+        Self { path, key, app_ids }
+    }
+
+    /// Writes the order to the state file,
+    /// leaving the orders of other launchers in place.
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut orders = read_orders(&self.path);
+        orders.insert(self.key.clone(), self.app_ids.clone());
+
+        fs::write(&self.path, serde_json::to_string(&orders)?)?;
+        Ok(())
+    }
+
+    /// Sorts the current app IDs into the saved order.
+    /// Apps which have not been saved keep their relative order after the saved ones.
+    pub fn sort(&self, current: &[String]) -> Vec<String> {
+        let position = |app_id: &String| {
+            self.app_ids
+                .iter()
+                .position(|saved| saved == app_id)
+                .unwrap_or(self.app_ids.len())
+        };
+
+        let mut sorted = current.to_vec();
+        sorted.sort_by_key(position);
+        sorted
+    }
+
+    /// Moves `app_id` into the position of `target`,
+    /// where `current` is the app IDs currently on the bar.
+    ///
+    /// Returns `false` if either item cannot be moved.
+    /// Items without an app ID, such as transient windows, cannot be moved.
+    pub fn move_item(&mut self, current: &[String], app_id: &str, target: &str) -> bool {
+        if app_id.is_empty() || target.is_empty() || app_id == target {
+            return false;
+        }
+
+        let mut order = self.sort(current);
+        order.retain(|id| !id.is_empty());
+
+        let (Some(from), Some(to)) = (
+            order.iter().position(|id| id == app_id),
+            order.iter().position(|id| id == target),
+        ) else {
+            return false;
+        };
+
+        let item = order.remove(from);
+        order.insert(to, item);
+
+        // keep the positions of apps which are not currently open
+        order.extend(
+            self.app_ids
+                .iter()
+                .filter(|id| !current.contains(id))
+                .cloned(),
+        );
+
+        self.app_ids = order;
+        true
+    }
+}
+
+/// Reads the orders for every launcher from the state file at `path`.
+/// If the file is missing or invalid, no orders are returned.
+fn read_orders(path: &Path) -> Orders {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|err| {
+            error!("Invalid launcher order in '{}': {err}", path.display());
+            Orders::new()
+        }),
+        Err(err) if err.kind() == ErrorKind::NotFound => Orders::new(),
+        Err(err) => {
+            error!("Failed to read '{}': {err}", path.display());
+            Orders::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn ids(app_ids: &[&str]) -> Vec<String> {
+        app_ids.iter().map(ToString::to_string).collect()
+    }
+
+    fn order(app_ids: &[&str]) -> SavedOrder {
+        SavedOrder {
+            path: PathBuf::new(),
+            key: String::new(),
+            app_ids: ids(app_ids),
+        }
+    }
+
+    #[test]
+    fn test_sort_merges_current_apps() {
+        let saved = order(&["kitty", "gimp", "firefox"]);
+
+        assert_eq!(
+            saved.sort(&ids(&["firefox", "discord", "kitty", "steam"])),
+            ["kitty", "firefox", "discord", "steam"]
+        );
+        assert_eq!(order(&[]).sort(&ids(&["b", "a"])), ["b", "a"]);
+    }
+
+    #[test]
+    fn test_move_item() {
+        let current = ids(&["a", "b", "c", "d"]);
+
+        let mut saved = order(&[]);
+        assert!(saved.move_item(&current, "a", "c"));
+        assert_eq!(saved.sort(&current), ["b", "c", "a", "d"]);
+
+        assert!(saved.move_item(&current, "d", "b"));
+        assert_eq!(saved.sort(&current), ["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn test_move_item_keeps_closed_apps() {
+        let mut saved = order(&["gimp", "kitty", "firefox"]);
+
+        assert!(saved.move_item(&ids(&["firefox", "kitty"]), "firefox", "kitty"));
+        assert_eq!(saved.app_ids, ["firefox", "kitty", "gimp"]);
+    }
+
+    #[test]
+    fn test_move_item_fixed() {
+        let current = ids(&["a", "", "b"]);
+        let mut saved = order(&[]);
+
+        assert!(!saved.move_item(&current, "", "a"));
+        assert!(!saved.move_item(&current, "a", ""));
+        assert!(!saved.move_item(&current, "a", "a"));
+        assert!(!saved.move_item(&current, "a", "missing"));
+        assert!(saved.app_ids.is_empty());
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let path = env::temp_dir()
+            .join(format!("ironbar-test-{}", process::id()))
+            .join("launcher_order.json");
+        let current = ids(&["a", "b", "c"]);
+
+        let key = || "bar-1/launcher".to_string();
+
+        let mut saved = SavedOrder::load_from(path.clone(), key());
+        assert_eq!(saved.sort(&current), ["a", "b", "c"]);
+
+        assert!(saved.move_item(&current, "c", "a"));
+        saved.save().unwrap();
+
+        let loaded = SavedOrder::load_from(path.clone(), key());
+        assert_eq!(loaded.sort(&current), ["c", "a", "b"]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_save_keeps_other_launchers() {
+        let path = env::temp_dir()
+            .join(format!("ironbar-test-launchers-{}", process::id()))
+            .join("launcher_order.json");
+        let current = ids(&["a", "b"]);
+
+        let mut first = SavedOrder::load_from(path.clone(), "bar-1/launcher".to_string());
+        let second = SavedOrder::load_from(path.clone(), "bar-2/launcher".to_string());
+
+        assert!(first.move_item(&current, "b", "a"));
+        first.save().unwrap();
+
+        second.save().unwrap();
+
+        let first = SavedOrder::load_from(path.clone(), "bar-1/launcher".to_string());
+        let second = SavedOrder::load_from(path.clone(), "bar-2/launcher".to_string());
+        assert_eq!(first.sort(&current), ["b", "a"]);
+        assert_eq!(second.sort(&current), ["a", "b"]);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,21 +1,35 @@
+use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
-use color_eyre::Result;
-use glib::IsA;
-use gtk::gdk::{EventMask, Monitor};
+use color_eyre::{Report, Result};
+use glib::{IsA, Propagation};
+use gtk::gdk::{EventMask, Monitor, NotifyType};
 use gtk::prelude::*;
-use gtk::{Application, Button, EventBox, IconTheme, Orientation, Revealer, Widget};
+use gtk::{
+    Align, Application, Button, EventBox, IconSize, IconTheme, Image, Label, Orientation, Overlay,
+    Revealer, RevealerTransitionType, Widget,
+};
 use tokio::sync::{broadcast, mpsc};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::clients::{ClientResult, ProvidesClient, ProvidesFallibleClient};
-use crate::config::{BarPosition, CommonConfig, TransitionType};
+use crate::config::{BarPosition, CommonConfig, RevealOnHover, TransitionType};
+use crate::dynamic_value::dynamic_string;
 use crate::gtk_helpers::{IronbarGtkExt, WidgetGeometry};
+#[cfg(feature = "ipc")]
+use crate::ipc::ModuleRecord;
 use crate::popup::Popup;
-use crate::{glib_recv_mpsc, send, Ironbar};
-
+use crate::script::Script;
+use crate::{glib_recv, glib_recv_mpsc, Ironbar};
+use batch::BatchedSender;
+use tasks::TaskScope;
+
+mod batch;
+#[cfg(feature = "caffeine")]
+pub mod caffeine;
 #[cfg(feature = "cairo")]
 pub mod cairo;
 #[cfg(feature = "clipboard")]
@@ -29,18 +43,44 @@ pub mod clipboard;
 #[cfg(feature = "clock")]
 pub mod clock;
 pub mod custom;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod debounce;
+pub mod error_hook;
 #[cfg(feature = "focused")]
 pub mod focused;
+mod grace;
 pub mod label;
 #[cfg(feature = "launcher")]
 pub mod launcher;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 #[cfg(feature = "music")]
 pub mod music;
 #[cfg(feature = "notifications")]
 pub mod notifications;
+#[cfg(feature = "power")]
+pub mod power;
+#[cfg(feature = "quicksettings")]
+pub mod quicksettings;
+#[cfg(feature = "screenshot")]
+pub mod screenshot;
 pub mod script;
+#[cfg(feature = "sink")]
+pub mod sink;
 #[cfg(feature = "sys_info")]
 pub mod sysinfo;
+#[cfg(feature = "taskbar")]
+pub mod taskbar;
+pub mod tasks;
+#[cfg(feature = "temperature")]
+pub mod temperature;
+#[cfg(test)]
+mod test_helpers;
+#[cfg(any(feature = "sys_info", feature = "temperature"))]
+mod thermal;
+#[cfg(feature = "timer")]
+pub mod timer;
 #[cfg(feature = "tray")]
 pub mod tray;
 #[cfg(feature = "upower")]
@@ -50,7 +90,7 @@ pub mod volume;
 #[cfg(feature = "workspaces")]
 pub mod workspaces;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum ModuleLocation {
     Left,
     Center,
@@ -60,11 +100,23 @@ pub enum ModuleLocation {
 #[derive(Clone)]
 pub struct ModuleInfo<'a> {
     pub app: &'a Application,
+    pub bar_name: &'a str,
     pub location: ModuleLocation,
     pub bar_position: BarPosition,
     pub monitor: &'a Monitor,
     pub output_name: &'a str,
     pub icon_theme: &'a IconTheme,
+    /// Whether to leave out modules which fail to load,
+    /// rather than showing an error in their place.
+    pub hide_on_error: bool,
+}
+
+impl ModuleInfo<'_> {
+    /// Gets the scale factor of the bar's output,
+    /// to load images at for a crisp result on HiDPI outputs.
+    pub fn scale_factor(&self) -> i32 {
+        self.monitor.scale_factor()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +140,10 @@ where
     TSend: Clone,
 {
     pub id: usize,
+    /// The module's `name` option, or its type if not set.
+    pub name: String,
+    /// The module's `defer_until_visible` option.
+    pub defer_until_visible: bool,
     pub ironbar: Rc<Ironbar>,
     pub popup: Rc<Popup>,
     pub tx: mpsc::Sender<ModuleUpdateEvent<TSend>>,
@@ -236,6 +292,32 @@ where
 
     fn name() -> &'static str;
 
+    /// Checks whether the `next` message replaces the `previous` one entirely,
+    /// so that `previous` can be skipped if both are received in the same frame.
+    ///
+    /// This should only be true for messages which each contain the full state.
+    /// By default, every message is applied.
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        false
+    }
+
+    /// Gets the error message if the message puts the module into an error or unavailable state.
+    ///
+    /// Errors are held back for the `grace_period` in case the module recovers,
+    /// and run the `on_error` script once shown.
+    /// By default, no messages are errors.
+    fn error(_update: &Self::SendMessage) -> Option<String> {
+        None
+    }
+
+    /// Whether the module can enter an error state, as reported by [`Module::error`].
+    ///
+    /// The `grace_period` option has no effect on modules which cannot,
+    /// and `on_error` only runs if they fail to load.
+    fn reports_errors() -> bool {
+        false
+    }
+
     fn spawn_controller(
         &self,
         info: &ModuleInfo,
@@ -267,16 +349,126 @@ where
         None
     }
 
+    /// Creates an empty popup container immediately,
+    /// but defers building its contents with [`Module::into_popup`]
+    /// until the popup is first opened.
+    ///
+    /// `rx` should be subscribed before calling.
+    /// Until the popup is opened, only the latest update is kept,
+    /// and is replayed to the popup once it is built.
+    ///
+    /// This is only suitable for modules whose updates each contain the full state.
+    /// Modules which also send partial updates, such as progress ticks,
+    /// must build their popup eagerly, or the last full update may be lost.
+    fn into_lazy_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized + 'static,
+        <Self as Module<W>>::SendMessage: Clone + 'static,
+        <Self as Module<W>>::ReceiveMessage: 'static,
+    {
+        let container = gtk::Box::new(Orientation::Horizontal, 0);
+
+        let popup = context.popup.clone();
+        let id = context.id;
+        let info = OwnedModuleInfo::from(info);
+        let tasks = TaskScope::current();
+
+        // updates are forwarded through a separate channel,
+        // so the receiver cannot lag behind while the popup is closed
+        let (popup_tx, _) = broadcast::channel(64);
+        let latest = Rc::new(RefCell::new(None));
+
+        {
+            let popup_tx = popup_tx.clone();
+            let latest = latest.clone();
+            glib_recv!(rx, update => {
+                latest.replace(Some(update.clone()));
+                let _ = popup_tx.send(update);
+            });
+        }
+
+        {
+            let container = container.clone();
+            popup.register_builder(id, move || {
+                let _entered = tasks.as_ref().map(TaskScope::enter);
+
+                let rx = popup_tx.subscribe();
+                if let Some(update) = latest.take() {
+                    let _ = popup_tx.send(update);
+                }
+
+                if let Some(content) = self.into_popup(tx, rx, context, &info.as_info()) {
+                    container.add(&content);
+                    container.show_all();
+                }
+            });
+        }
+
+        Some(container)
+    }
+
     fn take_common(&mut self) -> CommonConfig;
 }
 
+/// Owned copy of [`ModuleInfo`],
+/// for when it needs to outlive the bar setup.
+pub struct OwnedModuleInfo {
+    app: Application,
+    bar_name: String,
+    location: ModuleLocation,
+    bar_position: BarPosition,
+    monitor: Monitor,
+    output_name: String,
+    icon_theme: IconTheme,
+    hide_on_error: bool,
+}
+
+impl From<&ModuleInfo<'_>> for OwnedModuleInfo {
+    fn from(info: &ModuleInfo) -> Self {
+        Self {
+            app: info.app.clone(),
+            bar_name: info.bar_name.to_string(),
+            location: info.location.clone(),
+            bar_position: info.bar_position,
+            monitor: info.monitor.clone(),
+            output_name: info.output_name.to_string(),
+            icon_theme: info.icon_theme.clone(),
+            hide_on_error: info.hide_on_error,
+        }
+    }
+}
+
+impl OwnedModuleInfo {
+    pub fn as_info(&self) -> ModuleInfo<'_> {
+        ModuleInfo {
+            app: &self.app,
+            bar_name: &self.bar_name,
+            location: self.location.clone(),
+            bar_position: self.bar_position,
+            monitor: &self.monitor,
+            output_name: &self.output_name,
+            icon_theme: &self.icon_theme,
+            hide_on_error: self.hide_on_error,
+        }
+    }
+}
+
 pub trait ModuleFactory {
+    /// Creates the module and adds it to the container.
+    ///
+    /// Returns the module's ID.
     fn create<TModule, TWidget, TSend, TRev>(
         &self,
         mut module: TModule,
         container: &gtk::Box,
         info: &ModuleInfo,
-    ) -> Result<()>
+    ) -> Result<usize>
     where
         TModule: Module<TWidget, SendMessage = TSend, ReceiveMessage = TRev>,
         TWidget: IsA<Widget>,
@@ -285,13 +477,26 @@ pub trait ModuleFactory {
         let id = Ironbar::unique_id();
         let common = module.take_common();
 
+        // tracks everything the module spawns,
+        // so that it can be torn down cleanly
+        let tasks = TaskScope::new();
+        let _entered = tasks.enter();
+
         let (ui_tx, ui_rx) = mpsc::channel::<ModuleUpdateEvent<TSend>>(64);
         let (controller_tx, controller_rx) = mpsc::channel::<TRev>(64);
 
         let (tx, rx) = broadcast::channel(64);
 
+        let module_name = TModule::name();
+        let instance_name = common
+            .name
+            .clone()
+            .unwrap_or_else(|| module_name.to_string());
+
         let context = WidgetContext {
             id,
+            name: instance_name.clone(),
+            defer_until_visible: common.defer_until_visible,
             ironbar: self.ironbar().clone(),
             popup: self.popup().clone(),
             tx: ui_tx,
@@ -303,12 +508,6 @@ pub trait ModuleFactory {
 
         module.spawn_controller(info, &context, controller_rx)?;
 
-        let module_name = TModule::name();
-        let instance_name = common
-            .name
-            .clone()
-            .unwrap_or_else(|| module_name.to_string());
-
         let module_parts = module.into_widget(context, info)?;
         module_parts.widget.add_class("widget");
         module_parts.widget.add_class(module_name);
@@ -319,27 +518,78 @@ pub trait ModuleFactory {
                 .style_context()
                 .add_class(&format!("popup-{module_name}"));
 
-            self.popup()
-                .register_content(id, instance_name, popup_content);
+            self.popup().register_content(
+                id,
+                instance_name.clone(),
+                popup_content,
+                common.popup_options(),
+            );
+        }
+
+        if !TModule::reports_errors() && common.grace_period.is_some() {
+            warn!("Module '{instance_name}' has no error state, so `grace_period` has no effect");
         }
 
+        let ui_rx = match common.grace_period {
+            Some(period) if period > 0 => {
+                grace::grace_updates(ui_rx, Duration::from_millis(period), |update| {
+                    TModule::error(update).is_some()
+                })
+            }
+            _ => ui_rx,
+        };
+
+        let ui_rx = match common.on_error.clone() {
+            Some(script) => {
+                error_hook::hook_errors(ui_rx, Script::new_polling(script), TModule::error)
+            }
+            None => ui_rx,
+        };
+
+        let ui_rx = match common.debounce {
+            Some(debounce) if debounce > 0 => debounce::debounce_updates(
+                ui_rx,
+                Duration::from_millis(debounce),
+                TModule::supersedes,
+            ),
+            _ => ui_rx,
+        };
+
+        let tx = BatchedSender::new(tx, TModule::supersedes);
         self.setup_receiver(tx, ui_rx, module_name, id, common.disable_popup);
 
         module_parts.setup_identifiers(&common);
 
+        #[cfg(feature = "ipc")]
+        let scripts = common.event_scripts();
+
         let ev_container = wrap_widget(
             &module_parts.widget,
             common,
             info.bar_position.orientation(),
+            self.popup().animations_enabled(),
         );
         container.add(&ev_container);
 
-        Ok(())
+        #[cfg(feature = "ipc")]
+        self.ironbar().register_module(ModuleRecord {
+            id,
+            bar_name: info.bar_name.to_string(),
+            name: instance_name,
+            module_type: module_name,
+            location: info.location.clone(),
+            container: ev_container,
+            scripts,
+            tasks,
+            builder: None,
+        });
+
+        Ok(id)
     }
 
     fn setup_receiver<TSend>(
         &self,
-        tx: broadcast::Sender<TSend>,
+        tx: BatchedSender<TSend>,
         rx: mpsc::Receiver<ModuleUpdateEvent<TSend>>,
         name: &'static str,
         id: usize,
@@ -366,7 +616,7 @@ impl BarModuleFactory {
 impl ModuleFactory for BarModuleFactory {
     fn setup_receiver<TSend>(
         &self,
-        tx: broadcast::Sender<TSend>,
+        tx: BatchedSender<TSend>,
         rx: mpsc::Receiver<ModuleUpdateEvent<TSend>>,
         name: &'static str,
         id: usize,
@@ -376,9 +626,13 @@ impl ModuleFactory for BarModuleFactory {
     {
         let popup = self.popup.clone();
         glib_recv_mpsc!(rx, ev => {
+            if !matches!(ev, ModuleUpdateEvent::Update(_)) {
+                tx.flush();
+            }
+
             match ev {
                 ModuleUpdateEvent::Update(update) => {
-                    send!(tx, update);
+                    tx.send(update);
                 }
                 ModuleUpdateEvent::TogglePopup(button_id) if !disable_popup => {
                     debug!("Toggling popup for {} [#{}] (button id: {button_id})", name, id);
@@ -438,7 +692,7 @@ impl PopupModuleFactory {
 impl ModuleFactory for PopupModuleFactory {
     fn setup_receiver<TSend>(
         &self,
-        tx: broadcast::Sender<TSend>,
+        tx: BatchedSender<TSend>,
         rx: mpsc::Receiver<ModuleUpdateEvent<TSend>>,
         name: &'static str,
         id: usize,
@@ -449,9 +703,13 @@ impl ModuleFactory for PopupModuleFactory {
         let popup = self.popup.clone();
         let button_id = self.button_id;
         glib_recv_mpsc!(rx, ev => {
+            if !matches!(ev, ModuleUpdateEvent::Update(_)) {
+                tx.flush();
+            }
+
             match ev {
                 ModuleUpdateEvent::Update(update) => {
-                    send!(tx, update);
+                    tx.send(update);
                 }
                 ModuleUpdateEvent::TogglePopup(_) if !disable_popup => {
                     debug!("Toggling popup for {} [#{}] (button id: {button_id})", name, id);
@@ -500,7 +758,7 @@ pub enum AnyModuleFactory {
 impl ModuleFactory for AnyModuleFactory {
     fn setup_receiver<TSend>(
         &self,
-        tx: broadcast::Sender<TSend>,
+        tx: BatchedSender<TSend>,
         rx: mpsc::Receiver<ModuleUpdateEvent<TSend>>,
         name: &'static str,
         id: usize,
@@ -545,8 +803,9 @@ impl From<PopupModuleFactory> for AnyModuleFactory {
 /// The event box container is returned.
 pub fn wrap_widget<W: IsA<Widget>>(
     widget: &W,
-    common: CommonConfig,
+    mut common: CommonConfig,
     orientation: Orientation,
+    animations_enabled: bool,
 ) -> EventBox {
     let transition_type = common
         .transition_type
@@ -559,16 +818,471 @@ pub fn wrap_widget<W: IsA<Widget>>(
         .transition_duration(common.transition_duration.unwrap_or(250))
         .build();
 
-    revealer.add(widget);
+    let content: Widget = match common.badge.take() {
+        Some(badge) => wrap_badge(widget, &badge).upcast(),
+        None => widget.clone().upcast(),
+    };
+
+    let hover_revealer = common.reveal_on_hover.take().map(|options| {
+        let hover_revealer = hover_revealer(&options, orientation, animations_enabled);
+        hover_revealer.add(&content);
+        hover_revealer
+    });
+
+    match &hover_revealer {
+        Some(hover_revealer) => revealer.add(hover_revealer),
+        None => revealer.add(&content),
+    }
     revealer.set_reveal_child(true);
 
+    common.apply_alignment(widget.upcast_ref());
+    common.apply_rotation(widget.upcast_ref(), orientation);
+    common.install_blink(widget.upcast_ref(), animations_enabled);
+
     let container = EventBox::new();
     container.add_class("widget-container");
 
-    container.add_events(EventMask::SCROLL_MASK);
+    if common.passthrough {
+        set_passthrough(&container);
+    } else {
+        container.add_events(EventMask::SCROLL_MASK);
+    }
     container.add(&revealer);
 
     common.install_events(&container, &revealer);
 
+    if let Some(hover_revealer) = hover_revealer {
+        install_hover_reveal(&container, &hover_revealer);
+    }
+
     container
 }
+
+/// Time to wait after the pointer leaves before hiding a hover-revealed widget,
+/// so that briefly crossing a gap does not close it.
+const HOVER_HIDE_DELAY: Duration = Duration::from_millis(200);
+
+/// Creates the revealer for a widget with `reveal_on_hover`,
+/// which starts hidden.
+fn hover_revealer(
+    options: &RevealOnHover,
+    orientation: Orientation,
+    animations_enabled: bool,
+) -> Revealer {
+    let transition_type = if animations_enabled {
+        options
+            .transition_type
+            .as_ref()
+            .unwrap_or(&TransitionType::SlideStart)
+            .to_revealer_transition_type(orientation)
+    } else {
+        RevealerTransitionType::None
+    };
+
+    let revealer = Revealer::builder()
+        .transition_type(transition_type)
+        .transition_duration(options.transition_duration.unwrap_or(250))
+        .reveal_child(false)
+        .build();
+
+    revealer.add_class("hover-reveal");
+    revealer
+}
+
+/// Reveals the widget while the pointer is over the module containing it.
+///
+/// The hidden widget takes up no space, so cannot be hovered itself.
+/// Instead, the nearest enclosing module container is used,
+/// such as a custom module with an always-visible icon.
+fn install_hover_reveal(container: &EventBox, revealer: &Revealer) {
+    let installed = Cell::new(false);
+    let revealer = revealer.clone();
+
+    // the enclosing container is only known once the widget is on the bar
+    container.connect_realize(move |container| {
+        if installed.replace(true) {
+            return;
+        }
+
+        let Some(companion) = enclosing_container(container) else {
+            warn!("`reveal_on_hover` only works on widgets inside another module");
+            revealer.set_reveal_child(true);
+            return;
+        };
+
+        companion.add_events(EventMask::ENTER_NOTIFY_MASK | EventMask::LEAVE_NOTIFY_MASK);
+
+        let state = Rc::new(RefCell::new(HoverState::default()));
+
+        let update = {
+            let revealer = revealer.clone();
+
+            move |hovered: bool| {
+                let generation = state.borrow_mut().set_hovered(hovered);
+
+                if hovered {
+                    revealer.set_reveal_child(true);
+                    return;
+                }
+
+                let state = state.clone();
+                let revealer = revealer.clone();
+                glib::timeout_add_local_once(HOVER_HIDE_DELAY, move || {
+                    if let Some(reveal) = state.borrow().reveal(generation) {
+                        revealer.set_reveal_child(reveal);
+                    }
+                });
+            }
+        };
+
+        let update = Rc::new(update);
+
+        {
+            let update = update.clone();
+            companion.connect_enter_notify_event(move |_, event| {
+                if let Some(hovered) = crossing_hover(true, event.detail()) {
+                    update(hovered);
+                }
+                Propagation::Proceed
+            });
+        }
+
+        companion.connect_leave_notify_event(move |_, event| {
+            if let Some(hovered) = crossing_hover(false, event.detail()) {
+                update(hovered);
+            }
+            Propagation::Proceed
+        });
+    });
+}
+
+/// Finds the module container enclosing the widget, if there is one.
+fn enclosing_container(widget: &impl IsA<Widget>) -> Option<EventBox> {
+    let mut parent = widget.parent();
+
+    while let Some(widget) = parent {
+        if widget.style_context().has_class("widget-container") {
+            if let Ok(container) = widget.clone().downcast::<EventBox>() {
+                return Some(container);
+            }
+        }
+
+        parent = widget.parent();
+    }
+
+    None
+}
+
+/// Gets whether the pointer is over the container after a crossing event,
+/// or `None` if it only moved between the container and one of its children.
+///
+/// Without ignoring these, moving over a button inside the container
+/// would count as leaving it.
+fn crossing_hover(entered: bool, detail: NotifyType) -> Option<bool> {
+    (detail != NotifyType::Inferior).then_some(entered)
+}
+
+/// Tracks the hover state of a hover-revealed widget.
+///
+/// Hiding is delayed, so each change is numbered,
+/// and a delayed update only applies if nothing has changed since it was scheduled.
+/// This stops rapid hovering leaving the widget stuck open or closed,
+/// as the last change always wins.
+#[derive(Debug, Default)]
+struct HoverState {
+    hovered: bool,
+    generation: u64,
+}
+
+impl HoverState {
+    /// Records the pointer entering or leaving,
+    /// returning the number of this change.
+    fn set_hovered(&mut self, hovered: bool) -> u64 {
+        self.hovered = hovered;
+        self.generation = self.generation.wrapping_add(1);
+        self.generation
+    }
+
+    /// Gets whether to reveal the widget for an update scheduled by change `generation`,
+    /// or `None` if a newer change has superseded it.
+    fn reveal(&self, generation: u64) -> Option<bool> {
+        (generation == self.generation).then_some(self.hovered)
+    }
+}
+
+/// Makes the container ignore pointer events,
+/// so that they fall through to any widgets beneath it.
+///
+/// This only affects the container and its contents.
+/// Other modules on the bar still receive events as normal.
+fn set_passthrough(container: &EventBox) {
+    container.add_class("passthrough");
+
+    // an empty input shape stops the window from being
+    // picked as the target for pointer events
+    container.connect_realize(|container| {
+        if let Some(window) = container.window() {
+            window.input_shape_combine_region(&gtk::cairo::Region::create(), 0, 0);
+        }
+    });
+}
+
+/// Places the widget in an overlay
+/// with a badge label in its top-right corner.
+///
+/// The badge text is a dynamic string,
+/// and the badge is hidden while it is empty or zero.
+fn wrap_badge<W: IsA<Widget>>(widget: &W, badge: &str) -> Overlay {
+    let overlay = Overlay::new();
+    overlay.add(widget);
+
+    let label = Label::builder()
+        .halign(Align::End)
+        .valign(Align::Start)
+        .build();
+    label.add_class("badge");
+
+    // visibility is controlled by the badge content,
+    // so must not be overridden when the module is shown
+    label.set_no_show_all(true);
+
+    overlay.add_overlay(&label);
+    overlay.set_overlay_pass_through(&label, true);
+
+    {
+        let label = label.clone();
+        dynamic_string(badge, move |text| {
+            label.set_label(&text);
+            label.set_visible(is_badge_visible(&text));
+        });
+    }
+
+    overlay
+}
+
+/// Checks whether the badge should be shown for the given text.
+fn is_badge_visible(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty() && text.parse::<f64>().ok().is_none_or(|count| count != 0.0)
+}
+
+/// Creates a widget to show in place of a module which failed to load,
+/// with the error in its tooltip.
+pub fn create_error_widget(err: &Report) -> Image {
+    let image = Image::from_icon_name(Some("dialog-error-symbolic"), IconSize::Button);
+    image.add_class("widget");
+    image.add_class("error");
+
+    image.set_tooltip_text(Some(&format!("{err:#}")));
+    image.show();
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gtk_helpers::with_gtk;
+
+    #[test]
+    fn test_badge_visibility() {
+        assert!(is_badge_visible("3"));
+        assert!(is_badge_visible("new"));
+        assert!(is_badge_visible("99+"));
+
+        assert!(!is_badge_visible(""));
+        assert!(!is_badge_visible(" \n"));
+        assert!(!is_badge_visible("0"));
+        assert!(!is_badge_visible("00"));
+    }
+
+    #[test]
+    fn test_crossing_hover() {
+        assert_eq!(crossing_hover(true, NotifyType::Ancestor), Some(true));
+        assert_eq!(crossing_hover(false, NotifyType::Nonlinear), Some(false));
+        assert_eq!(crossing_hover(false, NotifyType::Virtual), Some(false));
+
+        // moving onto or off a child of the container
+        assert_eq!(crossing_hover(true, NotifyType::Inferior), None);
+        assert_eq!(crossing_hover(false, NotifyType::Inferior), None);
+    }
+
+    #[test]
+    fn test_hover_state() {
+        let mut state = HoverState::default();
+
+        let enter = state.set_hovered(true);
+        assert_eq!(state.reveal(enter), Some(true));
+
+        let leave = state.set_hovered(false);
+        assert_eq!(state.reveal(leave), Some(false));
+    }
+
+    #[test]
+    fn test_hover_state_rapid_toggling() {
+        let mut state = HoverState::default();
+
+        // out then back in before the hide delay
+        state.set_hovered(true);
+        let leave = state.set_hovered(false);
+        let enter = state.set_hovered(true);
+
+        // the pending hide is superseded, so the widget stays open
+        assert_eq!(state.reveal(leave), None);
+        assert_eq!(state.reveal(enter), Some(true));
+
+        // many quick crossings ending outside close it
+        let pending = (0..10)
+            .map(|i| state.set_hovered(i % 2 == 0))
+            .collect::<Vec<_>>();
+        let last = state.set_hovered(false);
+
+        assert!(pending.iter().all(|&change| state.reveal(change).is_none()));
+        assert_eq!(state.reveal(last), Some(false));
+    }
+
+    #[test]
+    fn test_hover_revealer_wraps_widget() {
+        with_gtk(|| {
+            let common = CommonConfig {
+                reveal_on_hover: Some(RevealOnHover::default()),
+                ..CommonConfig::default()
+            };
+
+            let container = wrap_widget(&Label::new(None), common, Orientation::Horizontal, false);
+
+            let hover_revealer = container
+                .child()
+                .and_then(|revealer| revealer.downcast::<Revealer>().ok())
+                .and_then(|revealer| revealer.child())
+                .and_then(|revealer| revealer.downcast::<Revealer>().ok())
+                .unwrap();
+
+            assert!(!hover_revealer.reveals_child());
+            assert_eq!(
+                hover_revealer.transition_type(),
+                RevealerTransitionType::None
+            );
+            assert!(hover_revealer.child().unwrap().is::<Label>());
+
+            // a module container inside another is found as its companion
+            let outer = wrap_widget(
+                &gtk::Box::new(Orientation::Horizontal, 0),
+                CommonConfig::default(),
+                Orientation::Horizontal,
+                false,
+            );
+            let inner = outer
+                .child()
+                .and_then(|revealer| revealer.downcast::<Revealer>().ok())
+                .and_then(|revealer| revealer.child())
+                .and_then(|widget| widget.downcast::<gtk::Box>().ok())
+                .unwrap();
+            inner.add(&container);
+
+            assert_eq!(enclosing_container(&container), Some(outer));
+        });
+    }
+
+    #[test]
+    fn test_badge_wraps_widget() {
+        with_gtk(|| {
+            let child = |common: CommonConfig| {
+                let container =
+                    wrap_widget(&Label::new(None), common, Orientation::Horizontal, true);
+
+                container
+                    .child()
+                    .and_then(|revealer| revealer.downcast::<Revealer>().ok())
+                    .and_then(|revealer| revealer.child())
+                    .unwrap()
+            };
+
+            assert!(child(CommonConfig::default()).is::<Label>());
+
+            let common = CommonConfig {
+                badge: Some("3".to_string()),
+                ..CommonConfig::default()
+            };
+            assert!(child(common).is::<Overlay>());
+        });
+    }
+
+    #[test]
+    fn test_passthrough_only_when_configured() {
+        with_gtk(|| {
+            let container = wrap_widget(
+                &Label::new(None),
+                CommonConfig::default(),
+                Orientation::Horizontal,
+                true,
+            );
+
+            assert!(container.events().contains(EventMask::SCROLL_MASK));
+            assert!(!container.style_context().has_class("passthrough"));
+
+            let common = CommonConfig {
+                passthrough: true,
+                ..CommonConfig::default()
+            };
+            let container = wrap_widget(&Label::new(None), common, Orientation::Horizontal, true);
+
+            assert!(!container.events().contains(EventMask::SCROLL_MASK));
+            assert!(container.style_context().has_class("passthrough"));
+        });
+    }
+
+    #[test]
+    fn test_alignment_applied() {
+        with_gtk(|| {
+            let label = Label::new(None);
+            let common: CommonConfig = serde_json::from_value(serde_json::json!({
+                "halign": "end",
+                "valign": "fill",
+                "hexpand": true
+            }))
+            .unwrap();
+
+            wrap_widget(&label, common, Orientation::Horizontal, true);
+
+            assert_eq!(label.halign(), Align::End);
+            assert_eq!(label.valign(), Align::Fill);
+            assert!(label.hexpands());
+            assert!(!label.vexpands());
+        });
+    }
+
+    #[test]
+    fn test_alignment_defaults_unchanged() {
+        with_gtk(|| {
+            let label = Label::new(None);
+            let default = Label::new(None);
+
+            wrap_widget(
+                &label,
+                CommonConfig::default(),
+                Orientation::Horizontal,
+                true,
+            );
+
+            assert_eq!(label.halign(), default.halign());
+            assert_eq!(label.valign(), default.valign());
+            assert_eq!(label.hexpands(), default.hexpands());
+            assert_eq!(label.vexpands(), default.vexpands());
+        });
+    }
+
+    #[test]
+    fn test_error_widget_tooltip() {
+        with_gtk(|| {
+            let err = Report::msg("Invalid format string").wrap_err("Failed to create clock");
+            let widget = create_error_widget(&err);
+
+            let tooltip = widget.tooltip_text().unwrap();
+            assert!(tooltip.contains("Failed to create clock"));
+            assert!(tooltip.contains("Invalid format string"));
+
+            assert!(widget.style_context().has_class("error"));
+        });
+    }
+}
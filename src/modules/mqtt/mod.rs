@@ -0,0 +1,435 @@
+use std::time::Duration;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::Label;
+use rumqttc::{
+    AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS, SubscribeReasonCode, Transport,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
+use crate::{glib_recv, module_impl, send_async, spawn, Ironbar};
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_TLS_PORT: u16 = 8883;
+
+/// Seconds between pings to the broker.
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Number of pending requests to the event loop.
+const REQUEST_CAPACITY: usize = 10;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MqttModule {
+    /// Broker address, as `mqtt://host:port`,
+    /// or `mqtts://host:port` to connect over TLS.
+    broker: String,
+
+    username: Option<String>,
+    password: Option<String>,
+
+    /// Topic to subscribe to. May include wildcards.
+    topic: String,
+
+    /// Quality of service level to subscribe with.
+    #[serde(default)]
+    qos: u8,
+
+    /// Dot-separated path to a field in a JSON payload,
+    /// such as `sensor.temperature` or `readings.0`.
+    /// If not set, the whole payload is shown.
+    field: Option<String>,
+
+    /// Format string for the label.
+    /// `{value}` is replaced with the payload or field.
+    #[serde(default = "default_format")]
+    format: String,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_format() -> String {
+    String::from("{value}")
+}
+
+#[derive(Debug, Clone)]
+pub enum MqttEvent {
+    Connected,
+    Disconnected,
+    /// Payload of a message on the topic.
+    Payload(String),
+}
+
+/// Host and port of a broker,
+/// and whether to connect to it over TLS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Broker {
+    host: String,
+    port: u16,
+    tls: bool,
+}
+
+/// Parses a broker address.
+/// Addresses without a scheme use an unencrypted connection.
+fn parse_broker(broker: &str) -> Result<Broker> {
+    let (address, tls) = match broker.split_once("://") {
+        Some(("mqtt" | "tcp", address)) => (address, false),
+        Some(("mqtts" | "ssl", address)) => (address, true),
+        Some((scheme, _)) => return Err(eyre!("Unsupported broker scheme '{scheme}'")),
+        None => (broker, false),
+    };
+
+    let address = address.trim_end_matches('/');
+
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse()?),
+        None if tls => (address, DEFAULT_TLS_PORT),
+        None => (address, DEFAULT_PORT),
+    };
+
+    Ok(Broker {
+        host: host.to_string(),
+        port,
+        tls,
+    })
+}
+
+fn parse_qos(level: u8) -> Result<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(eyre!("Invalid QoS level {level}, must be 0, 1 or 2")),
+    }
+}
+
+/// Gets the value at a dot-separated path in a JSON value.
+/// Numeric segments index into arrays.
+fn extract_field<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        serde_json::Value::Object(map) => map.get(key),
+        serde_json::Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Renders a message payload into the label format.
+///
+/// Returns `None` if a field is set,
+/// and the payload is not JSON or does not contain it.
+fn render_payload(payload: &str, field: Option<&str>, format: &str) -> Option<String> {
+    let value = match field {
+        Some(field) => {
+            let json = serde_json::from_str::<serde_json::Value>(payload).ok()?;
+
+            match extract_field(&json, field)? {
+                serde_json::Value::String(string) => string.clone(),
+                value => value.to_string(),
+            }
+        }
+        None => payload.trim().to_string(),
+    };
+
+    Some(format.replace("{value}", &value))
+}
+
+/// Exponential delay between reconnection attempts.
+#[derive(Debug)]
+struct Backoff {
+    delay: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { delay: MIN_BACKOFF }
+    }
+}
+
+impl Backoff {
+    /// Gets the delay before the next attempt,
+    /// doubling it for the attempt after.
+    fn next(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(MAX_BACKOFF);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.delay = MIN_BACKOFF;
+    }
+}
+
+struct Session {
+    broker: Broker,
+    client: AsyncClient,
+    event_loop: EventLoop,
+    topic: String,
+    qos: QoS,
+}
+
+impl Session {
+    fn new(module: &MqttModule) -> Result<Self> {
+        let broker = parse_broker(&module.broker)?;
+        let qos = parse_qos(module.qos)?;
+
+        let client_id = format!("ironbar-{}-{}", std::process::id(), Ironbar::unique_id());
+
+        let mut options = MqttOptions::new(client_id, &broker.host, broker.port);
+        options.set_keep_alive(KEEP_ALIVE);
+
+        if broker.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+
+        match (&module.username, &module.password) {
+            (Some(username), password) => {
+                options.set_credentials(username, password.as_deref().unwrap_or_default());
+            }
+            (None, Some(_)) => warn!("Ignoring MQTT password, as no username is set"),
+            (None, None) => {}
+        }
+
+        let (client, event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+
+        Ok(Self {
+            broker,
+            client,
+            event_loop,
+            topic: module.topic.clone(),
+            qos,
+        })
+    }
+
+    /// Polls the connection, sending messages to the widget.
+    ///
+    /// The event loop reconnects on the next poll after an error,
+    /// so this waits for the backoff before returning to it.
+    async fn run(mut self, tx: mpsc::Sender<ModuleUpdateEvent<MqttEvent>>) {
+        let mut backoff = Backoff::default();
+
+        loop {
+            match self.event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    debug!(
+                        "Connected to MQTT broker at {}:{}",
+                        self.broker.host, self.broker.port
+                    );
+                    backoff.reset();
+
+                    // subscriptions do not persist with a clean session
+                    if let Err(err) = self.client.try_subscribe(&self.topic, self.qos) {
+                        warn!("Failed to subscribe to '{}': {err}", self.topic);
+                    }
+
+                    send_async!(tx, ModuleUpdateEvent::Update(MqttEvent::Connected));
+                }
+                Ok(Event::Incoming(Packet::SubAck(ack)))
+                    if ack
+                        .return_codes
+                        .iter()
+                        .any(|code| matches!(code, SubscribeReasonCode::Failure)) =>
+                {
+                    warn!("Broker rejected subscription to '{}'", self.topic);
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    debug!("Received message on '{}'", publish.topic);
+
+                    let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                    send_async!(tx, ModuleUpdateEvent::Update(MqttEvent::Payload(payload)));
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(
+                        "MQTT connection to {}:{} failed: {err}",
+                        self.broker.host, self.broker.port
+                    );
+
+                    send_async!(tx, ModuleUpdateEvent::Update(MqttEvent::Disconnected));
+                    sleep(backoff.next()).await;
+                }
+            }
+        }
+    }
+}
+
+impl Module<Label> for MqttModule {
+    type SendMessage = MqttEvent;
+    type ReceiveMessage = ();
+
+    module_impl!("mqtt");
+
+    fn error(update: &Self::SendMessage) -> Option<String> {
+        matches!(update, MqttEvent::Disconnected)
+            .then(|| "Disconnected from MQTT broker".to_string())
+    }
+
+    fn reports_errors() -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let tx = context.tx.clone();
+        let session = Session::new(self)?;
+
+        spawn(session.run(tx));
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Result<ModuleParts<Label>> {
+        let label = Label::new(None);
+
+        {
+            let label = label.clone();
+            let field = self.field;
+            let format = self.format;
+
+            // the last value is kept while disconnected,
+            // and marked as stale using the class
+            glib_recv!(context.subscribe(), event => {
+                match event {
+                    MqttEvent::Connected => label.style_context().remove_class("disconnected"),
+                    MqttEvent::Disconnected => label.add_class("disconnected"),
+                    MqttEvent::Payload(payload) => {
+                        match render_payload(&payload, field.as_deref(), &format) {
+                            Some(text) => label.set_label(&text),
+                            None => warn!(
+                                "Field '{}' not found in payload: {payload}",
+                                field.as_deref().unwrap_or_default()
+                            ),
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(ModuleParts {
+            widget: label,
+            popup: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_plain_payload() {
+        assert_eq!(
+            render_payload("21.5\n", None, "{value}°C").unwrap(),
+            "21.5°C"
+        );
+        assert_eq!(
+            render_payload(r#"{"on":true}"#, None, "{value}").unwrap(),
+            r#"{"on":true}"#
+        );
+    }
+
+    #[test]
+    fn test_render_field() {
+        let payload = r#"{"state":"ON","sensor":{"temperature":21.5,"tags":["a","b"]}}"#;
+
+        assert_eq!(
+            render_payload(payload, Some("state"), "Light: {value}").unwrap(),
+            "Light: ON"
+        );
+        assert_eq!(
+            render_payload(payload, Some("sensor.temperature"), "{value}").unwrap(),
+            "21.5"
+        );
+        assert_eq!(
+            render_payload(payload, Some("sensor.tags.1"), "{value}").unwrap(),
+            "b"
+        );
+    }
+
+    #[test]
+    fn test_render_missing_field() {
+        assert_eq!(render_payload(r#"{"a":1}"#, Some("b"), "{value}"), None);
+        assert_eq!(render_payload("not json", Some("a"), "{value}"), None);
+    }
+
+    #[test]
+    fn test_extract_field() {
+        let value = json!({ "a": { "b": [1, { "c": null }] } });
+
+        assert_eq!(extract_field(&value, "a.b.0"), Some(&json!(1)));
+        assert_eq!(extract_field(&value, "a.b.1.c"), Some(&json!(null)));
+        assert_eq!(extract_field(&value, "a.b.x"), None);
+        assert_eq!(extract_field(&value, "a.b.0.c"), None);
+    }
+
+    fn broker(host: &str, port: u16, tls: bool) -> Broker {
+        Broker {
+            host: host.to_string(),
+            port,
+            tls,
+        }
+    }
+
+    #[test]
+    fn test_parse_broker() {
+        assert_eq!(
+            parse_broker("mqtt://broker.local:1884").unwrap(),
+            broker("broker.local", 1884, false)
+        );
+        assert_eq!(
+            parse_broker("192.168.1.2").unwrap(),
+            broker("192.168.1.2", DEFAULT_PORT, false)
+        );
+        assert!(parse_broker("ws://broker.local").is_err());
+    }
+
+    #[test]
+    fn test_parse_tls_broker() {
+        assert_eq!(
+            parse_broker("mqtts://broker.local").unwrap(),
+            broker("broker.local", DEFAULT_TLS_PORT, true)
+        );
+        assert_eq!(
+            parse_broker("ssl://broker.local:8884/").unwrap(),
+            broker("broker.local", 8884, true)
+        );
+    }
+
+    #[test]
+    fn test_parse_qos() {
+        assert_eq!(parse_qos(2).unwrap(), QoS::ExactlyOnce);
+        assert!(parse_qos(3).is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::default();
+
+        let delays: Vec<_> = (0..8).map(|_| backoff.next().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+
+        backoff.reset();
+        assert_eq!(backoff.next(), MIN_BACKOFF);
+    }
+}
@@ -1,9 +1,12 @@
 use crate::config::{CommonConfig, TruncateMode};
 use dirs::{audio_dir, home_dir};
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[schemars(rename = "MusicIcons")]
 pub struct Icons {
     /// Icon to display when playing.
     #[serde(default = "default_icon_play")]
@@ -53,7 +56,7 @@ impl Default for Icons {
     }
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum PlayerType {
     Mpd,
@@ -66,7 +69,33 @@ impl Default for PlayerType {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Pointer gestures on the bar widget.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Gesture {
+    ClickLeft,
+    ClickRight,
+    ClickMiddle,
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Actions which can be bound to a gesture.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MusicAction {
+    TogglePopup,
+    PlayPause,
+    Play,
+    Pause,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct MusicModule {
     /// Type of player to connect to
     #[serde(default)]
@@ -76,6 +105,24 @@ pub struct MusicModule {
     #[serde(default = "default_format")]
     pub(crate) format: String,
 
+    /// Format of the tooltip shown when hovering the bar widget.
+    /// Supports the same tokens as `format`, as well as Pango markup.
+    pub(crate) tooltip_format: Option<String>,
+
+    /// Format overrides for specific players, keyed by player name.
+    #[serde(default)]
+    pub(crate) player_formats: HashMap<String, String>,
+
+    /// MPRIS player names to prefer when several are running,
+    /// highest priority first.
+    #[serde(default)]
+    pub(crate) player_priority: Vec<String>,
+
+    /// Actions to run on gestures over the bar widget.
+    /// Left click toggles the popup unless set.
+    #[serde(default)]
+    pub(crate) actions: HashMap<Gesture, MusicAction>,
+
     /// Player state icons
     #[serde(default)]
     pub(crate) icons: Icons,
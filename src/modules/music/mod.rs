@@ -1,4 +1,5 @@
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -6,26 +7,27 @@ use std::time::Duration;
 
 use color_eyre::Result;
 use glib::{Propagation, PropertySet};
+use gtk::gdk::{EventMask, ScrollDirection};
 use gtk::prelude::*;
 use gtk::{Button, IconTheme, Label, Orientation, Scale};
 use regex::Regex;
 use tokio::sync::{broadcast, mpsc};
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::clients::music::{
     self, MusicClient, PlayerState, PlayerUpdate, ProgressTick, Status, Track,
 };
 use crate::clients::Clients;
-use crate::gtk_helpers::IronbarGtkExt;
+use crate::gtk_helpers::{format_markup, IronbarGtkExt};
 use crate::image::{new_icon_button, new_icon_label, ImageProvider};
 use crate::modules::PopupButton;
 use crate::modules::{
     Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, WidgetContext,
 };
-use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+use crate::{arc_mut, glib_recv, lock, module_impl, send_async, spawn, try_send};
 
 pub use self::config::MusicModule;
-use self::config::PlayerType;
+use self::config::{Gesture, MusicAction, PlayerType};
 
 mod config;
 
@@ -37,6 +39,85 @@ pub enum PlayerCommand {
     Next,
     Volume(u8),
     Seek(Duration),
+    /// Action bound to a gesture on the bar widget.
+    Action(MusicAction),
+}
+
+/// Percentage to change the volume by for each volume action.
+const VOLUME_STEP: u8 = 5;
+
+/// Gets the action bound to the gesture, if any.
+///
+/// Left click toggles the popup unless bound to something else.
+fn action_for(actions: &HashMap<Gesture, MusicAction>, gesture: Gesture) -> Option<MusicAction> {
+    actions.get(&gesture).copied().or(match gesture {
+        Gesture::ClickLeft => Some(MusicAction::TogglePopup),
+        _ => None,
+    })
+}
+
+const fn gesture_for_button(button: u32) -> Option<Gesture> {
+    match button {
+        1 => Some(Gesture::ClickLeft),
+        2 => Some(Gesture::ClickMiddle),
+        3 => Some(Gesture::ClickRight),
+        _ => None,
+    }
+}
+
+const fn gesture_for_scroll(direction: ScrollDirection) -> Option<Gesture> {
+    match direction {
+        ScrollDirection::Up => Some(Gesture::ScrollUp),
+        ScrollDirection::Down => Some(Gesture::ScrollDown),
+        _ => None,
+    }
+}
+
+/// Converts an action into the command to send to the player,
+/// based on its current status.
+///
+/// Returns `None` if the player does not support the action.
+fn action_command(action: MusicAction, status: &Status) -> Option<PlayerCommand> {
+    let capabilities = status.capabilities;
+
+    match action {
+        MusicAction::PlayPause => match status.state {
+            PlayerState::Playing => capabilities.pause.then_some(PlayerCommand::Pause),
+            _ => capabilities.play.then_some(PlayerCommand::Play),
+        },
+        MusicAction::Play => capabilities.play.then_some(PlayerCommand::Play),
+        MusicAction::Pause => capabilities.pause.then_some(PlayerCommand::Pause),
+        MusicAction::Next => capabilities.next.then_some(PlayerCommand::Next),
+        MusicAction::Previous => capabilities.previous.then_some(PlayerCommand::Previous),
+        MusicAction::VolumeUp => status
+            .volume_percent
+            .filter(|_| capabilities.volume)
+            .map(|volume| PlayerCommand::Volume(volume.saturating_add(VOLUME_STEP).min(100))),
+        MusicAction::VolumeDown => status
+            .volume_percent
+            .filter(|_| capabilities.volume)
+            .map(|volume| PlayerCommand::Volume(volume.saturating_sub(VOLUME_STEP))),
+        // handled by the widget
+        MusicAction::TogglePopup => None,
+    }
+}
+
+fn run_command(client: &dyn MusicClient, command: PlayerCommand, status: &Status) -> Result<()> {
+    match command {
+        PlayerCommand::Previous => client.prev(),
+        PlayerCommand::Play => client.play(),
+        PlayerCommand::Pause => client.pause(),
+        PlayerCommand::Next => client.next(),
+        PlayerCommand::Volume(vol) => client.set_volume_percent(vol),
+        PlayerCommand::Seek(duration) => client.seek(duration),
+        PlayerCommand::Action(action) => match action_command(action, status) {
+            Some(command) => run_command(client, command, status),
+            None => {
+                warn!("Current player does not support action '{action:?}'");
+                Ok(())
+            }
+        },
+    }
 }
 
 /// Formats a duration given in seconds
@@ -56,9 +137,27 @@ fn get_tokens(re: &Regex, format_string: &str) -> Vec<String> {
         .collect::<Vec<_>>()
 }
 
+/// Gets the format string to use for the named player,
+/// falling back to the default format if it has no override.
+///
+/// Player names are matched ignoring case.
+fn format_for_player<'a>(
+    default: &'a str,
+    overrides: &'a HashMap<String, String>,
+    player: Option<&str>,
+) -> &'a str {
+    player
+        .and_then(|player| {
+            overrides
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(player))
+        })
+        .map_or(default, |(_, format)| format)
+}
+
 #[derive(Clone, Debug)]
 pub enum ControllerEvent {
-    Update(Option<SongUpdate>),
+    Update(Option<Box<SongUpdate>>),
     UpdateProgress(ProgressTick),
 }
 
@@ -67,6 +166,7 @@ pub struct SongUpdate {
     song: Track,
     status: Status,
     display_string: String,
+    tooltip: Option<String>,
 }
 
 fn get_client(
@@ -74,10 +174,11 @@ fn get_client(
     player_type: PlayerType,
     host: String,
     music_dir: PathBuf,
+    priority: Vec<String>,
 ) -> Arc<dyn MusicClient> {
     let client_type = match player_type {
         PlayerType::Mpd => music::ClientType::Mpd { host, music_dir },
-        PlayerType::Mpris => music::ClientType::Mpris,
+        PlayerType::Mpris => music::ClientType::Mpris { priority },
     };
 
     clients.music(client_type)
@@ -89,6 +190,10 @@ impl Module<Button> for MusicModule {
 
     module_impl!("music");
 
+    fn supersedes(next: &Self::SendMessage, previous: &Self::SendMessage) -> bool {
+        std::mem::discriminant(next) == std::mem::discriminant(previous)
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
@@ -96,21 +201,27 @@ impl Module<Button> for MusicModule {
         mut rx: mpsc::Receiver<Self::ReceiveMessage>,
     ) -> Result<()> {
         let format = self.format.clone();
+        let player_formats = self.player_formats.clone();
+        let tooltip_format = self.tooltip_format.clone();
 
         let re = Regex::new(r"\{([\w-]+)}")?;
-        let tokens = get_tokens(&re, self.format.as_str());
 
         let client = get_client(
             context.ironbar.clients.borrow_mut(),
             self.player_type,
             self.host.clone(),
             self.music_dir.clone(),
+            self.player_priority.clone(),
         );
 
+        // latest status, used to resolve gesture actions
+        let status = arc_mut!(Status::default());
+
         // receive player updates
         {
             let tx = context.tx.clone();
             let client = client.clone();
+            let status = status.clone();
 
             spawn(async move {
                 loop {
@@ -118,29 +229,44 @@ impl Module<Button> for MusicModule {
 
                     while let Ok(update) = rx.recv().await {
                         match update {
-                            PlayerUpdate::Update(track, status) => match *track {
-                                Some(track) => {
-                                    let display_string =
-                                        replace_tokens(format.as_str(), &tokens, &track);
-
-                                    let update = SongUpdate {
-                                        song: track,
-                                        status,
-                                        display_string,
-                                    };
-
-                                    send_async!(
+                            PlayerUpdate::Update(track, new_status) => {
+                                *lock!(status) = new_status;
+
+                                match *track {
+                                    Some(track) => {
+                                        let format = format_for_player(
+                                            &format,
+                                            &player_formats,
+                                            track.player.as_deref(),
+                                        );
+                                        let tokens = get_tokens(&re, format);
+                                        let display_string =
+                                            replace_tokens(format, &tokens, &track);
+
+                                        let tooltip = tooltip_format
+                                            .as_deref()
+                                            .map(|format| replace_tokens_markup(format, &track));
+
+                                        let update = SongUpdate {
+                                            song: track,
+                                            status: new_status,
+                                            display_string,
+                                            tooltip,
+                                        };
+
+                                        send_async!(
+                                            tx,
+                                            ModuleUpdateEvent::Update(ControllerEvent::Update(
+                                                Some(Box::new(update))
+                                            ))
+                                        );
+                                    }
+                                    None => send_async!(
                                         tx,
-                                        ModuleUpdateEvent::Update(ControllerEvent::Update(Some(
-                                            update
-                                        )))
-                                    );
+                                        ModuleUpdateEvent::Update(ControllerEvent::Update(None))
+                                    ),
                                 }
-                                None => send_async!(
-                                    tx,
-                                    ModuleUpdateEvent::Update(ControllerEvent::Update(None))
-                                ),
-                            },
+                            }
                             PlayerUpdate::ProgressTick(progress_tick) => send_async!(
                                 tx,
                                 ModuleUpdateEvent::Update(ControllerEvent::UpdateProgress(
@@ -157,14 +283,8 @@ impl Module<Button> for MusicModule {
         {
             spawn(async move {
                 while let Some(event) = rx.recv().await {
-                    let res = match event {
-                        PlayerCommand::Previous => client.prev(),
-                        PlayerCommand::Play => client.play(),
-                        PlayerCommand::Pause => client.pause(),
-                        PlayerCommand::Next => client.next(),
-                        PlayerCommand::Volume(vol) => client.set_volume_percent(vol),
-                        PlayerCommand::Seek(duration) => client.seek(duration),
-                    };
+                    let status = *lock!(status);
+                    let res = run_command(client.as_ref(), event, &status);
 
                     if let Err(err) = res {
                         error!("Failed to send command to server: {:?}", err);
@@ -187,8 +307,9 @@ impl Module<Button> for MusicModule {
 
         button.add(&button_contents);
 
-        let icon_play = new_icon_label(&self.icons.play, info.icon_theme, self.icon_size);
-        let icon_pause = new_icon_label(&self.icons.pause, info.icon_theme, self.icon_size);
+        let scale = info.scale_factor();
+        let icon_play = new_icon_label(&self.icons.play, info.icon_theme, self.icon_size, scale);
+        let icon_pause = new_icon_label(&self.icons.pause, info.icon_theme, self.icon_size, scale);
         let label = Label::new(None);
 
         label.set_angle(info.bar_position.get_angle());
@@ -203,9 +324,39 @@ impl Module<Button> for MusicModule {
 
         {
             let tx = context.tx.clone();
+            let controller_tx = context.controller_tx.clone();
+            let actions = self.actions.clone();
+
+            let dispatch = move |button: &Button, gesture: Option<Gesture>| match gesture
+                .and_then(|gesture| action_for(&actions, gesture))
+            {
+                Some(MusicAction::TogglePopup) => {
+                    try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+                }
+                Some(action) => try_send!(controller_tx, PlayerCommand::Action(action)),
+                None => {}
+            };
+
+            button.add_events(EventMask::SCROLL_MASK);
 
+            let dispatch_scroll = dispatch.clone();
+            button.connect_scroll_event(move |button, event| {
+                dispatch_scroll(button, gesture_for_scroll(event.direction()));
+                Propagation::Proceed
+            });
+
+            // left clicks go through `clicked`,
+            // so the button can still be activated from the keyboard
+            let dispatch_click = dispatch.clone();
             button.connect_clicked(move |button| {
-                try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+                dispatch_click(button, Some(Gesture::ClickLeft));
+            });
+
+            button.connect_button_release_event(move |button, event| {
+                if event.button() != 1 {
+                    dispatch(button, gesture_for_button(event.button()));
+                }
+                Propagation::Proceed
             });
         }
 
@@ -223,6 +374,10 @@ impl Module<Button> for MusicModule {
                 if let Some(event) = event.take() {
                     label.set_label(&event.display_string);
 
+                    if let Some(tooltip) = &event.tooltip {
+                        button.set_tooltip_markup(Some(tooltip));
+                    }
+
                     button.show();
 
                     match event.status.state {
@@ -267,6 +422,7 @@ impl Module<Button> for MusicModule {
         info: &ModuleInfo,
     ) -> Option<gtk::Box> {
         let icon_theme = info.icon_theme;
+        let scale = info.scale_factor();
 
         let container = gtk::Box::new(Orientation::Vertical, 10);
         let main_container = gtk::Box::new(Orientation::Horizontal, 10);
@@ -280,9 +436,9 @@ impl Module<Button> for MusicModule {
         let icons = self.icons;
 
         let info_box = gtk::Box::new(Orientation::Vertical, 10);
-        let title_label = IconLabel::new(&icons.track, None, icon_theme);
-        let album_label = IconLabel::new(&icons.album, None, icon_theme);
-        let artist_label = IconLabel::new(&icons.artist, None, icon_theme);
+        let title_label = IconLabel::new(&icons.track, None, icon_theme, scale);
+        let album_label = IconLabel::new(&icons.album, None, icon_theme, scale);
+        let artist_label = IconLabel::new(&icons.artist, None, icon_theme, scale);
 
         title_label.container.add_class("title");
         album_label.container.add_class("album");
@@ -295,16 +451,16 @@ impl Module<Button> for MusicModule {
         let controls_box = gtk::Box::new(Orientation::Horizontal, 0);
         controls_box.add_class("controls");
 
-        let btn_prev = new_icon_button(&icons.prev, icon_theme, self.icon_size);
+        let btn_prev = new_icon_button(&icons.prev, icon_theme, self.icon_size, scale);
         btn_prev.add_class("btn-prev");
 
-        let btn_play = new_icon_button(&icons.play, icon_theme, self.icon_size);
+        let btn_play = new_icon_button(&icons.play, icon_theme, self.icon_size, scale);
         btn_play.add_class("btn-play");
 
-        let btn_pause = new_icon_button(&icons.pause, icon_theme, self.icon_size);
+        let btn_pause = new_icon_button(&icons.pause, icon_theme, self.icon_size, scale);
         btn_pause.add_class("btn-pause");
 
-        let btn_next = new_icon_button(&icons.next, icon_theme, self.icon_size);
+        let btn_next = new_icon_button(&icons.next, icon_theme, self.icon_size, scale);
         btn_next.add_class("btn-next");
 
         controls_box.add(&btn_prev);
@@ -321,7 +477,7 @@ impl Module<Button> for MusicModule {
         volume_slider.set_inverted(true);
         volume_slider.add_class("slider");
 
-        let volume_icon = new_icon_label(&icons.volume, icon_theme, self.icon_size);
+        let volume_icon = new_icon_label(&icons.volume, icon_theme, self.icon_size, scale);
         volume_icon.add_class("icon");
 
         volume_box.pack_start(&volume_slider, true, true, 0);
@@ -411,6 +567,7 @@ impl Module<Button> for MusicModule {
                             prev_cover = new_cover.clone();
                             let res = if let Some(image) = new_cover.and_then(|cover_path| {
                                 ImageProvider::parse(&cover_path, &icon_theme, false, image_size)
+                                    .map(|image| image.with_scale(scale))
                             }) {
                                 album_image.show();
                                 image.load_into_image(album_image.clone())
@@ -517,6 +674,12 @@ fn replace_tokens(format_string: &str, tokens: &Vec<String>, song: &Track) -> St
     compiled_string
 }
 
+/// Replaces each of the formatting tokens in a Pango markup string,
+/// escaping the values so that they are always shown as plain text.
+fn replace_tokens_markup(format_string: &str, song: &Track) -> String {
+    format_markup(format_string, |token| Some(get_token_value(song, token)))
+}
+
 /// Converts a string format token value
 /// into its respective value.
 fn get_token_value(song: &Track, token: &str) -> String {
@@ -540,10 +703,10 @@ struct IconLabel {
 }
 
 impl IconLabel {
-    fn new(icon_input: &str, label: Option<&str>, icon_theme: &IconTheme) -> Self {
+    fn new(icon_input: &str, label: Option<&str>, icon_theme: &IconTheme, scale: i32) -> Self {
         let container = gtk::Box::new(Orientation::Horizontal, 5);
 
-        let icon = new_icon_label(icon_input, icon_theme, 24);
+        let icon = new_icon_label(icon_input, icon_theme, 24, scale);
         let label = Label::new(label);
 
         icon.add_class("icon-box");
@@ -555,3 +718,177 @@ impl IconLabel {
         Self { label, container }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::music::Capabilities;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_for_player() {
+        let overrides = HashMap::from([
+            ("spotify".to_string(), "{title} - {artist}".to_string()),
+            ("Mozilla Firefox".to_string(), "{title}".to_string()),
+        ]);
+
+        assert_eq!(
+            format_for_player("{title} / {artist}", &overrides, Some("Spotify")),
+            "{title} - {artist}"
+        );
+        assert_eq!(
+            format_for_player("{title} / {artist}", &overrides, Some("Mozilla Firefox")),
+            "{title}"
+        );
+    }
+
+    #[test]
+    fn test_format_fallback() {
+        let overrides = HashMap::from([("spotify".to_string(), "{title}".to_string())]);
+
+        assert_eq!(
+            format_for_player("{title} / {artist}", &overrides, Some("vlc")),
+            "{title} / {artist}"
+        );
+        assert_eq!(
+            format_for_player("{title} / {artist}", &overrides, None),
+            "{title} / {artist}"
+        );
+        assert_eq!(
+            format_for_player("{title} / {artist}", &HashMap::new(), Some("spotify")),
+            "{title} / {artist}"
+        );
+    }
+
+    fn track() -> Track {
+        Track {
+            title: Some("Fight Fire with Fire".to_string()),
+            album: Some("Ride the Lightning".to_string()),
+            artist: Some("Simon & <Garfunkel>".to_string()),
+            date: None,
+            disc: None,
+            genre: None,
+            track: Some(1),
+            cover_path: None,
+            player: None,
+        }
+    }
+
+    #[test]
+    fn test_tooltip_tokens() {
+        let format = "<b>{title}</b>\n{album} ({track}){date}";
+
+        assert_eq!(
+            replace_tokens_markup(format, &track()),
+            "<b>Fight Fire with Fire</b>\nRide the Lightning (1)"
+        );
+    }
+
+    #[test]
+    fn test_tooltip_tokens_escaped() {
+        let format = "<i>{artist}</i>";
+
+        assert_eq!(
+            replace_tokens_markup(format, &track()),
+            "<i>Simon &amp; &lt;Garfunkel&gt;</i>"
+        );
+
+        // an invalid format is shown as plain text, with the value escaped once
+        assert_eq!(
+            replace_tokens_markup("<i>{artist}", &track()),
+            "&lt;i&gt;Simon &amp; &lt;Garfunkel&gt;"
+        );
+    }
+
+    fn status(state: PlayerState, capabilities: Capabilities) -> Status {
+        Status {
+            state,
+            volume_percent: Some(50),
+            capabilities,
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn test_gesture_actions() {
+        let actions: HashMap<Gesture, MusicAction> = serde_json::from_value(json!({
+            "click_middle": "next",
+            "scroll_up": "volume_up",
+            "scroll_down": "volume_down",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            action_for(&actions, gesture_for_button(2).unwrap()),
+            Some(MusicAction::Next)
+        );
+        assert_eq!(
+            action_for(&actions, gesture_for_scroll(ScrollDirection::Up).unwrap()),
+            Some(MusicAction::VolumeUp)
+        );
+        assert_eq!(action_for(&actions, Gesture::ClickRight), None);
+
+        // left click keeps opening the popup unless overridden
+        assert_eq!(
+            action_for(&actions, Gesture::ClickLeft),
+            Some(MusicAction::TogglePopup)
+        );
+
+        let actions = HashMap::from([(Gesture::ClickLeft, MusicAction::PlayPause)]);
+        assert_eq!(
+            action_for(&actions, gesture_for_button(1).unwrap()),
+            Some(MusicAction::PlayPause)
+        );
+    }
+
+    #[test]
+    fn test_action_commands() {
+        let playing = status(PlayerState::Playing, Capabilities::default());
+
+        assert!(matches!(
+            action_command(MusicAction::PlayPause, &playing),
+            Some(PlayerCommand::Pause)
+        ));
+        assert!(matches!(
+            action_command(
+                MusicAction::PlayPause,
+                &status(PlayerState::Paused, Capabilities::default())
+            ),
+            Some(PlayerCommand::Play)
+        ));
+        assert!(matches!(
+            action_command(MusicAction::VolumeUp, &playing),
+            Some(PlayerCommand::Volume(55))
+        ));
+        assert!(matches!(
+            action_command(MusicAction::VolumeDown, &playing),
+            Some(PlayerCommand::Volume(45))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_actions() {
+        let capabilities = Capabilities {
+            next: false,
+            pause: false,
+            volume: false,
+            ..Capabilities::default()
+        };
+        let playing = status(PlayerState::Playing, capabilities);
+
+        assert!(action_command(MusicAction::Next, &playing).is_none());
+        assert!(action_command(MusicAction::PlayPause, &playing).is_none());
+        assert!(action_command(MusicAction::VolumeUp, &playing).is_none());
+        assert!(matches!(
+            action_command(MusicAction::Previous, &playing),
+            Some(PlayerCommand::Previous)
+        ));
+
+        // no volume reported
+        let status = Status {
+            volume_percent: None,
+            ..status(PlayerState::Playing, Capabilities::default())
+        };
+        assert!(action_command(MusicAction::VolumeDown, &status).is_none());
+    }
+}
@@ -5,11 +5,13 @@ use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetC
 use crate::{glib_recv, module_impl, send_async, spawn, try_send};
 use gtk::prelude::*;
 use gtk::{Align, Button, Label, Overlay};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::mpsc::Receiver;
 use tracing::error;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct NotificationsModule {
     #[serde(default = "crate::config::default_true")]
     show_count: bool,
@@ -21,7 +23,8 @@ pub struct NotificationsModule {
     pub common: Option<CommonConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[schemars(rename = "NotificationsIcons")]
 struct Icons {
     #[serde(default = "default_icon_closed_none")]
     closed_none: String,
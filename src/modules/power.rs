@@ -0,0 +1,425 @@
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
+
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::{Button, Label, Orientation, Stack};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+use tracing::debug;
+
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::image::ImageProvider;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::script::Script;
+use crate::{module_impl, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PowerModule {
+    /// Image to show on the bar button.
+    #[serde(default = "default_icon")]
+    icon: String,
+
+    #[serde(default = "default_icon_size")]
+    icon_size: i32,
+
+    /// Actions to show in the popup, in order.
+    #[serde(default = "default_actions")]
+    actions: Vec<PowerAction>,
+
+    /// Whether dangerous actions must be confirmed before running.
+    #[serde(default)]
+    confirm: bool,
+
+    /// Command overrides for each action.
+    /// Actions without one use a command detected from the system.
+    lock: Option<String>,
+    logout: Option<String>,
+    suspend: Option<String>,
+    reboot: Option<String>,
+    shutdown: Option<String>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_icon() -> String {
+    String::from("icon:system-shutdown-symbolic")
+}
+
+const fn default_icon_size() -> i32 {
+    24
+}
+
+fn default_actions() -> Vec<PowerAction> {
+    vec![
+        PowerAction::Lock,
+        PowerAction::Logout,
+        PowerAction::Suspend,
+        PowerAction::Reboot,
+        PowerAction::Shutdown,
+    ]
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    Lock,
+    Logout,
+    Suspend,
+    Reboot,
+    Shutdown,
+}
+
+/// The service manager the default commands are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitSystem {
+    Systemd,
+    /// Any other init system,
+    /// which is assumed to run elogind.
+    Other,
+}
+
+impl InitSystem {
+    fn detect() -> Self {
+        if Path::new("/run/systemd/system").exists() {
+            Self::Systemd
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl PowerAction {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Lock => "lock",
+            Self::Logout => "logout",
+            Self::Suspend => "suspend",
+            Self::Reboot => "reboot",
+            Self::Shutdown => "shutdown",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Lock => "Lock",
+            Self::Logout => "Log out",
+            Self::Suspend => "Suspend",
+            Self::Reboot => "Reboot",
+            Self::Shutdown => "Shut down",
+        }
+    }
+
+    /// Whether the action loses unsaved work,
+    /// so can require confirmation.
+    const fn is_dangerous(self) -> bool {
+        matches!(self, Self::Reboot | Self::Shutdown)
+    }
+
+    /// Gets the command to run the action
+    /// when no override is configured.
+    const fn default_command(self, init: InitSystem) -> &'static str {
+        match (self, init) {
+            (Self::Lock, _) => "loginctl lock-session",
+            (Self::Logout, _) => "loginctl terminate-session \"$XDG_SESSION_ID\"",
+            (Self::Suspend, InitSystem::Systemd) => "systemctl suspend",
+            (Self::Reboot, InitSystem::Systemd) => "systemctl reboot",
+            (Self::Shutdown, InitSystem::Systemd) => "systemctl poweroff",
+            (Self::Suspend, InitSystem::Other) => "loginctl suspend",
+            (Self::Reboot, InitSystem::Other) => "loginctl reboot",
+            (Self::Shutdown, InitSystem::Other) => "loginctl poweroff",
+        }
+    }
+}
+
+impl PowerModule {
+    fn command(&self, action: PowerAction, init: InitSystem) -> String {
+        let command = match action {
+            PowerAction::Lock => &self.lock,
+            PowerAction::Logout => &self.logout,
+            PowerAction::Suspend => &self.suspend,
+            PowerAction::Reboot => &self.reboot,
+            PowerAction::Shutdown => &self.shutdown,
+        };
+
+        command
+            .clone()
+            .unwrap_or_else(|| action.default_command(init).to_string())
+    }
+}
+
+/// Whether the popup is waiting for an action to be confirmed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ConfirmState {
+    #[default]
+    Idle,
+    Pending(PowerAction),
+}
+
+impl ConfirmState {
+    /// Selects an action from the popup.
+    ///
+    /// Returns the new state,
+    /// and the action to run if it does not need confirming.
+    const fn select(self, action: PowerAction, confirm: bool) -> (Self, Option<PowerAction>) {
+        if confirm && action.is_dangerous() {
+            (Self::Pending(action), None)
+        } else {
+            (Self::Idle, Some(action))
+        }
+    }
+
+    /// Confirms the pending action, returning it to run.
+    const fn confirm(self) -> (Self, Option<PowerAction>) {
+        match self {
+            Self::Pending(action) => (Self::Idle, Some(action)),
+            Self::Idle => (Self::Idle, None),
+        }
+    }
+}
+
+impl Module<Button> for PowerModule {
+    type SendMessage = ();
+    type ReceiveMessage = PowerAction;
+
+    module_impl!("power");
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        _context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let init = InitSystem::detect();
+        let module = self.clone();
+
+        spawn(async move {
+            while let Some(action) = rx.recv().await {
+                let command = module.command(action, init);
+                debug!("Running {} action: '{command}'", action.name());
+
+                Script::from(command.as_str()).run_as_oneshot(None);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = Button::new();
+
+        let icon = gtk::Image::new();
+        icon.add_class("icon");
+        button.add(&icon);
+
+        ImageProvider::parse(&self.icon, info.icon_theme, false, self.icon_size).map(|provider| {
+            provider
+                .with_scale(info.scale_factor())
+                .load_into_image(icon)
+        });
+
+        let tx = context.tx.clone();
+        button.connect_clicked(move |button| {
+            try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+        });
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_lazy_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        _rx: broadcast::Receiver<Self::SendMessage>,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized,
+    {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        let stack = Stack::new();
+        container.add(&stack);
+
+        let actions = gtk::Box::new(Orientation::Vertical, 0);
+        actions.add_class("actions");
+        stack.add_named(&actions, "actions");
+
+        let confirm_page = gtk::Box::new(Orientation::Vertical, 0);
+        confirm_page.add_class("confirm");
+        stack.add_named(&confirm_page, "confirm");
+
+        let message = Label::new(None);
+        message.add_class("message");
+        confirm_page.add(&message);
+
+        let confirm_buttons = gtk::Box::new(Orientation::Horizontal, 0);
+        confirm_page.add(&confirm_buttons);
+
+        let btn_confirm = Button::with_label("Confirm");
+        btn_confirm.add_class("btn-confirm");
+        confirm_buttons.add(&btn_confirm);
+
+        let btn_cancel = Button::with_label("Cancel");
+        btn_cancel.add_class("btn-cancel");
+        confirm_buttons.add(&btn_cancel);
+
+        let state = Rc::new(Cell::new(ConfirmState::default()));
+
+        // runs the action and closes the popup
+        let run = {
+            let tx = tx.clone();
+            let popup_tx = context.tx.clone();
+
+            move |action: Option<PowerAction>| {
+                if let Some(action) = action {
+                    try_send!(tx, action);
+                    try_send!(popup_tx, ModuleUpdateEvent::ClosePopup);
+                }
+            }
+        };
+
+        for action in self.actions {
+            let button = Button::with_label(action.label());
+            button.add_class("btn-action");
+            button.add_class(action.name());
+            actions.add(&button);
+
+            let state = state.clone();
+            let stack = stack.clone();
+            let message = message.clone();
+            let run = run.clone();
+            let confirm = self.confirm;
+
+            button.connect_clicked(move |_| {
+                let (new_state, action) = state.get().select(action, confirm);
+                state.set(new_state);
+
+                if let ConfirmState::Pending(pending) = new_state {
+                    message.set_label(&format!("{}?", pending.label()));
+                    stack.set_visible_child_name("confirm");
+                }
+
+                run(action);
+            });
+        }
+
+        {
+            let state = state.clone();
+            btn_confirm.connect_clicked(move |_| {
+                let (new_state, action) = state.get().confirm();
+                state.set(new_state);
+                run(action);
+            });
+        }
+
+        // go back to the actions when cancelled or the popup closes
+        {
+            let state = state.clone();
+            let stack = stack.clone();
+            btn_cancel.connect_clicked(move |_| {
+                state.set(ConfirmState::Idle);
+                stack.set_visible_child_name("actions");
+            });
+        }
+
+        container.connect_unmap(move |_| {
+            state.set(ConfirmState::Idle);
+            stack.set_visible_child_name("actions");
+        });
+
+        container.show_all();
+
+        Some(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_commands() {
+        assert_eq!(
+            PowerAction::Shutdown.default_command(InitSystem::Systemd),
+            "systemctl poweroff"
+        );
+        assert_eq!(
+            PowerAction::Shutdown.default_command(InitSystem::Other),
+            "loginctl poweroff"
+        );
+        assert_eq!(
+            PowerAction::Suspend.default_command(InitSystem::Systemd),
+            "systemctl suspend"
+        );
+        assert_eq!(
+            PowerAction::Lock.default_command(InitSystem::Other),
+            "loginctl lock-session"
+        );
+    }
+
+    #[test]
+    fn test_command_override() {
+        let module: PowerModule = serde_json::from_value(json!({ "lock": "swaylock -f" })).unwrap();
+
+        assert_eq!(
+            module.command(PowerAction::Lock, InitSystem::Systemd),
+            "swaylock -f"
+        );
+        assert_eq!(
+            module.command(PowerAction::Reboot, InitSystem::Systemd),
+            "systemctl reboot"
+        );
+    }
+
+    #[test]
+    fn test_confirm_dangerous_action() {
+        let (state, action) = ConfirmState::Idle.select(PowerAction::Reboot, true);
+        assert_eq!(state, ConfirmState::Pending(PowerAction::Reboot));
+        assert_eq!(action, None);
+
+        let (state, action) = state.confirm();
+        assert_eq!(state, ConfirmState::Idle);
+        assert_eq!(action, Some(PowerAction::Reboot));
+    }
+
+    #[test]
+    fn test_safe_action_runs_immediately() {
+        let (state, action) = ConfirmState::Idle.select(PowerAction::Lock, true);
+        assert_eq!(state, ConfirmState::Idle);
+        assert_eq!(action, Some(PowerAction::Lock));
+
+        let (state, action) = ConfirmState::Idle.select(PowerAction::Shutdown, false);
+        assert_eq!(state, ConfirmState::Idle);
+        assert_eq!(action, Some(PowerAction::Shutdown));
+    }
+
+    #[test]
+    fn test_confirm_without_pending_action() {
+        assert_eq!(ConfirmState::Idle.confirm(), (ConfirmState::Idle, None));
+    }
+
+    #[test]
+    fn test_select_replaces_pending_action() {
+        let (state, _) =
+            ConfirmState::Pending(PowerAction::Reboot).select(PowerAction::Shutdown, true);
+        assert_eq!(state, ConfirmState::Pending(PowerAction::Shutdown));
+    }
+}
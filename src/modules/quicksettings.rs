@@ -0,0 +1,880 @@
+use color_eyre::Result;
+use futures_lite::StreamExt;
+use glib::{Propagation, SignalHandlerId};
+use gtk::prelude::*;
+use gtk::{Button, Label, Orientation, Scale, ToggleButton};
+use schemars::JsonSchema;
+use serde::Deserialize;
+#[cfg(feature = "volume")]
+use std::cell::RefCell;
+#[cfg(feature = "volume")]
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+use tracing::{debug, error};
+
+#[cfg(feature = "caffeine")]
+use super::caffeine::{run_inhibitor, InhibitState};
+#[cfg(feature = "caffeine")]
+use crate::clients::logind;
+#[cfg(feature = "notifications")]
+use crate::clients::swaync;
+#[cfg(feature = "volume")]
+use crate::clients::volume::{self, Event, Sink};
+#[cfg(feature = "volume")]
+use crate::lock;
+#[cfg(feature = "volume")]
+use crate::modules::volume::Update as VolumeUpdate;
+
+use crate::clients::{bluetooth, brightness, network};
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::image::new_icon_button;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct QuickSettingsModule {
+    #[serde(default = "default_icon")]
+    icon: String,
+
+    #[serde(default = "default_icon_size")]
+    icon_size: i32,
+
+    /// Rows to show in the popup, in order.
+    #[serde(default = "default_rows")]
+    rows: Vec<Row>,
+
+    /// Colon-separated list of operations for the caffeine row to inhibit.
+    #[serde(default = "default_inhibit")]
+    inhibit: String,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_icon() -> String {
+    String::from("󰒓")
+}
+
+const fn default_icon_size() -> i32 {
+    24
+}
+
+fn default_rows() -> Vec<Row> {
+    vec![
+        Row::Brightness,
+        Row::Volume,
+        Row::Network,
+        Row::Bluetooth,
+        Row::Dnd,
+        Row::Caffeine,
+    ]
+}
+
+/// How often to check for brightness changes made elsewhere,
+/// as the backlight does not notify of them.
+const BRIGHTNESS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn default_inhibit() -> String {
+    String::from("sleep:idle")
+}
+
+/// A control in the quick settings popup.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Row {
+    /// Screen brightness slider.
+    Brightness,
+    /// Volume slider and mute toggle for the active output.
+    Volume,
+    /// Wi-Fi toggle for NetworkManager.
+    Network,
+    /// Bluetooth adapter power toggle.
+    Bluetooth,
+    /// Do not disturb toggle for SwayNC.
+    Dnd,
+    /// Idle inhibitor toggle.
+    Caffeine,
+}
+
+impl Row {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Brightness => "brightness",
+            Self::Volume => "volume",
+            Self::Network => "network",
+            Self::Bluetooth => "bluetooth",
+            Self::Dnd => "dnd",
+            Self::Caffeine => "caffeine",
+        }
+    }
+}
+
+/// Gets the rows to show, in the configured order.
+///
+/// `start` is called once for each row to start its controller,
+/// returning `false` if its subsystem is unavailable.
+/// Those rows are left out, as are any duplicates.
+fn assemble_rows(rows: &[Row], mut start: impl FnMut(Row) -> bool) -> Vec<Row> {
+    let mut seen = Vec::with_capacity(rows.len());
+    let mut assembled = Vec::with_capacity(rows.len());
+
+    for &row in rows {
+        if seen.contains(&row) {
+            continue;
+        }
+
+        seen.push(row);
+        if start(row) {
+            assembled.push(row);
+        }
+    }
+
+    assembled
+}
+
+#[derive(Debug, Clone)]
+pub enum QuickSettingsUpdate {
+    /// The rows which are available.
+    Rows(Vec<Row>),
+    /// Brightness percentage.
+    Brightness(f64),
+    /// Whether Wi-Fi is enabled.
+    Wifi(bool),
+    /// Whether the bluetooth adapter is powered.
+    Bluetooth(bool),
+    #[cfg(feature = "volume")]
+    Sink(Sink),
+    #[cfg(feature = "notifications")]
+    Dnd(bool),
+    #[cfg(feature = "caffeine")]
+    Caffeine(InhibitState),
+}
+
+#[derive(Debug, Clone)]
+pub enum QuickSettingsCommand {
+    SetBrightness(f64),
+    SetWifi(bool),
+    SetBluetooth(bool),
+    #[cfg(feature = "volume")]
+    Volume(VolumeUpdate),
+    #[cfg(feature = "notifications")]
+    SetDnd(bool),
+    #[cfg(feature = "caffeine")]
+    ToggleCaffeine,
+}
+
+/// Clients and channels used to control each running row.
+#[derive(Debug, Default)]
+struct Controllers {
+    brightness: Option<Arc<brightness::Client>>,
+    network: Option<Arc<network::Client>>,
+    bluetooth: Option<Arc<bluetooth::Client>>,
+    #[cfg(feature = "volume")]
+    volume: Option<Arc<volume::Client>>,
+    #[cfg(feature = "notifications")]
+    dnd: Option<Arc<swaync::Client>>,
+    #[cfg(feature = "caffeine")]
+    caffeine: Option<mpsc::Sender<()>>,
+}
+
+impl QuickSettingsModule {
+    /// Starts the controller for a row,
+    /// returning `false` if its subsystem is unavailable.
+    fn start_row(
+        &self,
+        row: Row,
+        context: &WidgetContext<QuickSettingsUpdate, QuickSettingsCommand>,
+        controllers: &mut Controllers,
+    ) -> bool {
+        let tx = context.tx.clone();
+
+        match row {
+            Row::Brightness => match context.try_client::<brightness::Client>() {
+                Ok(client) => {
+                    {
+                        let client = client.clone();
+                        spawn(async move { watch_brightness(&client, &tx).await });
+                    }
+
+                    controllers.brightness = Some(client);
+                    true
+                }
+                Err(err) => {
+                    debug!("Brightness unavailable: {err}");
+                    false
+                }
+            },
+            Row::Network => match context.try_client::<network::Client>() {
+                Ok(client) => {
+                    {
+                        let client = client.clone();
+                        spawn(async move { watch_wifi(&client, &tx).await });
+                    }
+
+                    controllers.network = Some(client);
+                    true
+                }
+                Err(err) => {
+                    debug!("NetworkManager unavailable: {err}");
+                    false
+                }
+            },
+            Row::Bluetooth => match context.try_client::<bluetooth::Client>() {
+                Ok(client) => {
+                    {
+                        let client = client.clone();
+                        spawn(async move { watch_bluetooth(&client, &tx).await });
+                    }
+
+                    controllers.bluetooth = Some(client);
+                    true
+                }
+                Err(err) => {
+                    debug!("Bluetooth unavailable: {err}");
+                    false
+                }
+            },
+            #[cfg(feature = "volume")]
+            Row::Volume => {
+                let client = context.client::<volume::Client>();
+
+                {
+                    let client = client.clone();
+                    spawn(async move { watch_sinks(&client, &tx).await });
+                }
+
+                controllers.volume = Some(client);
+                true
+            }
+            #[cfg(feature = "notifications")]
+            Row::Dnd => match context.try_client::<swaync::Client>() {
+                Ok(client) => {
+                    {
+                        let client = client.clone();
+                        spawn(async move { watch_dnd(&client, &tx).await });
+                    }
+
+                    controllers.dnd = Some(client);
+                    true
+                }
+                Err(err) => {
+                    debug!("Do not disturb unavailable: {err}");
+                    false
+                }
+            },
+            #[cfg(feature = "caffeine")]
+            Row::Caffeine => match context.try_client::<logind::Client>() {
+                Ok(client) => {
+                    let (caffeine_tx, mut caffeine_rx) = mpsc::channel(8);
+                    let what = self.inhibit.clone();
+
+                    spawn(async move {
+                        run_inhibitor(&client, &what, &mut caffeine_rx, &tx, |state| {
+                            QuickSettingsUpdate::Caffeine(state)
+                        })
+                        .await;
+                    });
+
+                    controllers.caffeine = Some(caffeine_tx);
+                    true
+                }
+                Err(err) => {
+                    debug!("Idle inhibitor unavailable: {err}");
+                    false
+                }
+            },
+            // the module for this row is not compiled in
+            #[allow(unreachable_patterns)]
+            _ => {
+                debug!("Row '{}' is not supported by this build", row.name());
+                false
+            }
+        }
+    }
+}
+
+impl Module<Button> for QuickSettingsModule {
+    type SendMessage = QuickSettingsUpdate;
+    type ReceiveMessage = QuickSettingsCommand;
+
+    module_impl!("quicksettings");
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let mut controllers = Controllers::default();
+        let rows = assemble_rows(&self.rows, |row| {
+            self.start_row(row, context, &mut controllers)
+        });
+
+        debug!("Showing quick settings rows: {rows:?}");
+        try_send!(
+            context.tx,
+            ModuleUpdateEvent::Update(QuickSettingsUpdate::Rows(rows))
+        );
+
+        spawn(async move {
+            while let Some(command) = rx.recv().await {
+                controllers.handle(command).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = new_icon_button(
+            &self.icon,
+            info.icon_theme,
+            self.icon_size,
+            info.scale_factor(),
+        );
+
+        let tx = context.tx.clone();
+        button.connect_clicked(move |button| {
+            try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+        });
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box> {
+        let container = gtk::Box::new(Orientation::Vertical, 5);
+
+        // each row is built up-front and hidden,
+        // then shown once the controller reports it is available
+        let row = |row: Row| {
+            let container = gtk::Box::new(Orientation::Horizontal, 5);
+            container.add_class("row");
+            container.add_class(row.name());
+            container.set_no_show_all(true);
+            container
+        };
+
+        let (brightness, brightness_slider) = {
+            let container = row(Row::Brightness);
+
+            let label = Label::new(Some("Brightness"));
+            label.add_class("label");
+
+            let slider = Scale::with_range(Orientation::Horizontal, 0.0, 100.0, 1.0);
+            slider.set_hexpand(true);
+            slider.set_draw_value(false);
+            slider.add_class("slider");
+
+            container.add(&label);
+            container.add(&slider);
+
+            let tx = tx.clone();
+            slider.connect_button_release_event(move |scale, _| {
+                // GTK will send values outside min/max range
+                let value = scale.value().clamp(0.0, 100.0);
+                try_send!(tx, QuickSettingsCommand::SetBrightness(value));
+
+                Propagation::Proceed
+            });
+
+            (container, slider)
+        };
+
+        // the handler is returned so it can be blocked
+        // while the button is synced with the reported state
+        let toggle_row = |kind: Row, label: &str, command: fn(bool) -> QuickSettingsCommand| {
+            let container = row(kind);
+
+            let button = ToggleButton::with_label(label);
+            button.set_hexpand(true);
+            button.add_class("btn-toggle");
+            container.add(&button);
+
+            let tx = tx.clone();
+            let handler = button.connect_toggled(move |button| {
+                try_send!(tx, command(button.is_active()));
+            });
+
+            (container, button, handler)
+        };
+
+        let (network, btn_wifi, wifi_handler) =
+            toggle_row(Row::Network, "Wi-Fi", QuickSettingsCommand::SetWifi);
+        let (bluetooth, btn_bluetooth, bluetooth_handler) = toggle_row(
+            Row::Bluetooth,
+            "Bluetooth",
+            QuickSettingsCommand::SetBluetooth,
+        );
+
+        #[cfg(feature = "volume")]
+        let volume = {
+            let container = row(Row::Volume);
+
+            let label = Label::new(Some("Volume"));
+            label.add_class("label");
+
+            let slider = Scale::with_range(Orientation::Horizontal, 0.0, 100.0, 1.0);
+            slider.set_hexpand(true);
+            slider.set_draw_value(false);
+            slider.add_class("slider");
+
+            let btn_mute = ToggleButton::with_label("Mute");
+            btn_mute.add_class("btn-mute");
+
+            container.add(&label);
+            container.add(&slider);
+            container.add(&btn_mute);
+
+            let sink = Rc::new(RefCell::new(None::<String>));
+
+            let mute_handler = {
+                let tx = tx.clone();
+                let sink = sink.clone();
+
+                btn_mute.connect_toggled(move |btn| {
+                    if let Some(name) = sink.borrow().clone() {
+                        try_send!(
+                            tx,
+                            QuickSettingsCommand::Volume(VolumeUpdate::SinkMute(
+                                name,
+                                btn.is_active()
+                            ))
+                        );
+                    }
+                })
+            };
+
+            VolumeRow {
+                container,
+                slider,
+                btn_mute,
+                mute_handler,
+                sink,
+            }
+        };
+
+        #[cfg(feature = "volume")]
+        {
+            let tx = tx.clone();
+            let sink = volume.sink.clone();
+
+            volume.slider.connect_button_release_event(move |scale, _| {
+                if let Some(name) = sink.borrow().clone() {
+                    // GTK will send values outside min/max range
+                    let value = scale.value().clamp(0.0, 100.0);
+                    try_send!(
+                        tx,
+                        QuickSettingsCommand::Volume(VolumeUpdate::SinkVolume(name, value))
+                    );
+                }
+
+                Propagation::Proceed
+            });
+        }
+
+        #[cfg(feature = "notifications")]
+        let (dnd, btn_dnd, dnd_handler) =
+            toggle_row(Row::Dnd, "Do not disturb", QuickSettingsCommand::SetDnd);
+
+        #[cfg(feature = "caffeine")]
+        let (caffeine, btn_caffeine) = {
+            let container = row(Row::Caffeine);
+
+            // toggled from the state reported by the controller,
+            // as taking the lock can fail
+            let button = Button::with_label("Caffeine");
+            button.set_hexpand(true);
+            button.add_class("btn-toggle");
+            container.add(&button);
+
+            let tx = tx.clone();
+            button.connect_clicked(move |_| {
+                try_send!(tx, QuickSettingsCommand::ToggleCaffeine);
+            });
+
+            (container, button)
+        };
+
+        let widgets = [
+            (Row::Brightness, brightness),
+            #[cfg(feature = "volume")]
+            (Row::Volume, volume.container.clone()),
+            (Row::Network, network),
+            (Row::Bluetooth, bluetooth),
+            #[cfg(feature = "notifications")]
+            (Row::Dnd, dnd),
+            #[cfg(feature = "caffeine")]
+            (Row::Caffeine, caffeine),
+        ];
+
+        // rows are added in the configured order,
+        // which is the same order the controller reports them in
+        for row in assemble_rows(&self.rows, |row| widgets.iter().any(|(r, _)| *r == row)) {
+            if let Some((_, widget)) = widgets.iter().find(|(r, _)| *r == row) {
+                container.add(widget);
+            }
+        }
+
+        glib_recv!(rx, update => {
+            match update {
+                QuickSettingsUpdate::Rows(rows) => {
+                    // the volume row is shown once there is a sink to control
+                    for (_, widget) in widgets
+                        .iter()
+                        .filter(|(row, _)| *row != Row::Volume && rows.contains(row))
+                    {
+                        show_row(widget);
+                    }
+                }
+                QuickSettingsUpdate::Brightness(value) => brightness_slider.set_value(value),
+                QuickSettingsUpdate::Wifi(enabled) => {
+                    set_active_silently(&btn_wifi, &wifi_handler, enabled);
+                }
+                QuickSettingsUpdate::Bluetooth(powered) => {
+                    set_active_silently(&btn_bluetooth, &bluetooth_handler, powered);
+                }
+                #[cfg(feature = "volume")]
+                QuickSettingsUpdate::Sink(sink) => {
+                    volume.sink.replace(Some(sink.name));
+                    volume.slider.set_value(sink.volume);
+                    set_active_silently(&volume.btn_mute, &volume.mute_handler, sink.muted);
+                    show_row(&volume.container);
+                }
+                #[cfg(feature = "notifications")]
+                QuickSettingsUpdate::Dnd(enabled) => {
+                    set_active_silently(&btn_dnd, &dnd_handler, enabled);
+                }
+                #[cfg(feature = "caffeine")]
+                QuickSettingsUpdate::Caffeine(state) => {
+                    for (class, enabled) in [
+                        ("active", state == InhibitState::Active),
+                        ("pending", state == InhibitState::Pending),
+                    ] {
+                        if enabled {
+                            btn_caffeine.add_class(class);
+                        } else {
+                            btn_caffeine.style_context().remove_class(class);
+                        }
+                    }
+                }
+            }
+        });
+
+        container.show_all();
+
+        Some(container)
+    }
+}
+
+/// Shows a row which was hidden until available.
+fn show_row(row: &gtk::Box) {
+    row.set_no_show_all(false);
+    row.show_all();
+}
+
+/// Sets the state of a toggle button
+/// without running its handler,
+/// so that syncing it with the reported state does not send the state back.
+fn set_active_silently(button: &ToggleButton, handler: &SignalHandlerId, active: bool) {
+    button.block_signal(handler);
+    button.set_active(active);
+    button.unblock_signal(handler);
+}
+
+#[cfg(feature = "volume")]
+struct VolumeRow {
+    container: gtk::Box,
+    slider: Scale,
+    btn_mute: ToggleButton,
+    mute_handler: SignalHandlerId,
+    /// Name of the active sink, which the controls change.
+    sink: Rc<RefCell<Option<String>>>,
+}
+
+impl Controllers {
+    async fn handle(&self, command: QuickSettingsCommand) {
+        match command {
+            QuickSettingsCommand::SetBrightness(percent) => {
+                if let Some(client) = &self.brightness {
+                    if let Err(err) = client.set_brightness(percent).await {
+                        error!("{err:?}");
+                    }
+                }
+            }
+            QuickSettingsCommand::SetWifi(enabled) => {
+                if let Some(client) = &self.network {
+                    if let Err(err) = client.set_wireless_enabled(enabled).await {
+                        error!("{err:?}");
+                    }
+                }
+            }
+            QuickSettingsCommand::SetBluetooth(powered) => {
+                if let Some(client) = &self.bluetooth {
+                    if let Err(err) = client.set_powered(powered).await {
+                        error!("{err:?}");
+                    }
+                }
+            }
+            #[cfg(feature = "volume")]
+            QuickSettingsCommand::Volume(update) => {
+                let Some(client) = &self.volume else { return };
+
+                match update {
+                    VolumeUpdate::SinkVolume(name, volume) => client.set_sink_volume(&name, volume),
+                    VolumeUpdate::SinkMute(name, muted) => client.set_sink_muted(&name, muted),
+                    _ => {}
+                }
+            }
+            #[cfg(feature = "notifications")]
+            QuickSettingsCommand::SetDnd(dnd) => {
+                if let Some(client) = &self.dnd {
+                    client.set_dnd(dnd).await;
+                }
+            }
+            #[cfg(feature = "caffeine")]
+            QuickSettingsCommand::ToggleCaffeine => {
+                if let Some(tx) = &self.caffeine {
+                    send_async!(tx, ());
+                }
+            }
+        }
+    }
+}
+
+/// Sends the active sink, and again each time it changes.
+#[cfg(feature = "volume")]
+async fn watch_sinks(
+    client: &volume::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<QuickSettingsUpdate>>,
+) {
+    let mut rx = client.subscribe();
+
+    // any playing sink is reported as active,
+    // so the server's default is used instead
+    let is_default = |sink: &Sink| client.default_sink_name().as_ref() == Some(&sink.name);
+
+    let active = {
+        let sinks = client.sinks();
+        let sinks = lock!(sinks);
+        sinks.iter().find(|sink| is_default(sink)).cloned()
+    };
+
+    if let Some(sink) = active {
+        send_async!(
+            tx,
+            ModuleUpdateEvent::Update(QuickSettingsUpdate::Sink(sink))
+        );
+    }
+
+    while let Ok(event) = rx.recv().await {
+        if let Event::AddSink(sink) | Event::UpdateSink(sink) = event {
+            if is_default(&sink) {
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(QuickSettingsUpdate::Sink(sink))
+                );
+            }
+        }
+    }
+}
+
+/// Sends the brightness, and again each time it changes.
+async fn watch_brightness(
+    client: &brightness::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<QuickSettingsUpdate>>,
+) {
+    let mut last = None;
+
+    loop {
+        match client.brightness() {
+            Ok(brightness) if last != Some(brightness) => {
+                last = Some(brightness);
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(QuickSettingsUpdate::Brightness(brightness))
+                );
+            }
+            Ok(_) => {}
+            Err(err) => error!("{err:?}"),
+        }
+
+        sleep(BRIGHTNESS_POLL_INTERVAL).await;
+    }
+}
+
+/// Sends whether Wi-Fi is enabled, and again each time it changes.
+async fn watch_wifi(
+    client: &network::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<QuickSettingsUpdate>>,
+) {
+    let mut changes = client.receive_wireless_enabled_changed().await;
+
+    match client.wireless_enabled().await {
+        Ok(enabled) => {
+            send_async!(
+                tx,
+                ModuleUpdateEvent::Update(QuickSettingsUpdate::Wifi(enabled))
+            );
+        }
+        Err(err) => error!("{err:?}"),
+    }
+
+    while let Some(change) = changes.next().await {
+        match change.get().await {
+            Ok(enabled) => {
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(QuickSettingsUpdate::Wifi(enabled))
+                );
+            }
+            Err(err) => error!("{err:?}"),
+        }
+    }
+}
+
+/// Sends whether the bluetooth adapter is powered, and again each time it changes.
+async fn watch_bluetooth(
+    client: &bluetooth::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<QuickSettingsUpdate>>,
+) {
+    let mut changes = client.receive_powered_changed().await;
+
+    match client.powered().await {
+        Ok(powered) => {
+            send_async!(
+                tx,
+                ModuleUpdateEvent::Update(QuickSettingsUpdate::Bluetooth(powered))
+            );
+        }
+        Err(err) => error!("{err:?}"),
+    }
+
+    while let Some(change) = changes.next().await {
+        match change.get().await {
+            Ok(powered) => {
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(QuickSettingsUpdate::Bluetooth(powered))
+                );
+            }
+            Err(err) => error!("{err:?}"),
+        }
+    }
+}
+
+/// Sends the do not disturb state, and again each time it changes.
+#[cfg(feature = "notifications")]
+async fn watch_dnd(
+    client: &swaync::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<QuickSettingsUpdate>>,
+) {
+    let mut rx = client.subscribe();
+
+    match client.state().await {
+        Ok(state) => {
+            send_async!(
+                tx,
+                ModuleUpdateEvent::Update(QuickSettingsUpdate::Dnd(state.dnd))
+            );
+        }
+        Err(err) => error!("{err:?}"),
+    }
+
+    while let Ok(event) = rx.recv().await {
+        send_async!(
+            tx,
+            ModuleUpdateEvent::Update(QuickSettingsUpdate::Dnd(event.dnd))
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_only_available() {
+        let rows = assemble_rows(&[Row::Volume, Row::Dnd, Row::Caffeine], |row| {
+            row != Row::Dnd
+        });
+        assert_eq!(rows, [Row::Volume, Row::Caffeine]);
+    }
+
+    #[test]
+    fn test_rows_keep_order() {
+        let rows = assemble_rows(&[Row::Caffeine, Row::Volume], |_| true);
+        assert_eq!(rows, [Row::Caffeine, Row::Volume]);
+    }
+
+    #[test]
+    fn test_rows_none_available() {
+        let rows = assemble_rows(&[Row::Volume, Row::Dnd], |_| false);
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_rows_started_once() {
+        let mut started = vec![];
+        let rows = assemble_rows(&[Row::Dnd, Row::Volume, Row::Dnd], |row| {
+            started.push(row);
+            true
+        });
+
+        assert_eq!(rows, [Row::Dnd, Row::Volume]);
+        assert_eq!(started, [Row::Dnd, Row::Volume]);
+    }
+
+    #[test]
+    fn test_set_active_silently() {
+        crate::gtk_helpers::with_gtk(|| {
+            let toggled = std::rc::Rc::new(std::cell::Cell::new(0));
+
+            let button = ToggleButton::new();
+            let handler = {
+                let toggled = toggled.clone();
+                button.connect_toggled(move |_| toggled.set(toggled.get() + 1))
+            };
+
+            set_active_silently(&button, &handler, true);
+            assert!(button.is_active());
+            assert_eq!(toggled.get(), 0);
+
+            button.set_active(false);
+            assert_eq!(toggled.get(), 1);
+        });
+    }
+
+    #[test]
+    fn test_unavailable_row_started_once() {
+        let mut started = 0;
+        let rows = assemble_rows(&[Row::Dnd, Row::Dnd], |_| {
+            started += 1;
+            false
+        });
+
+        assert!(rows.is_empty());
+        assert_eq!(started, 1);
+    }
+}
@@ -0,0 +1,430 @@
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::{Help, Report, Result};
+use gtk::prelude::*;
+use gtk::{Button, Orientation};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+use tracing::{debug, error};
+
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::image::ImageProvider;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::script::Script;
+use crate::{module_impl, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScreenshotModule {
+    /// Image to show on the bar button.
+    #[serde(default = "default_icon")]
+    icon: String,
+
+    #[serde(default = "default_icon_size")]
+    icon_size: i32,
+
+    /// Modes to show in the popup, in order.
+    #[serde(default = "default_modes")]
+    modes: Vec<ScreenshotMode>,
+
+    /// Directory to save screenshots to.
+    /// Shell expansions are supported.
+    #[serde(default = "default_directory")]
+    directory: String,
+
+    /// File name to save screenshots as.
+    /// Shell expansions are supported.
+    #[serde(default = "default_filename")]
+    filename: String,
+
+    /// Time in milliseconds to wait after the popup closes
+    /// before capturing, so that it is not included.
+    #[serde(default = "default_delay")]
+    delay: u64,
+
+    /// Command overrides for each mode.
+    /// `{path}` is replaced with the quoted path to save to.
+    screen: Option<String>,
+    window: Option<String>,
+    region: Option<String>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_icon() -> String {
+    String::from("icon:camera-photo-symbolic")
+}
+
+const fn default_icon_size() -> i32 {
+    24
+}
+
+fn default_modes() -> Vec<ScreenshotMode> {
+    vec![
+        ScreenshotMode::Screen,
+        ScreenshotMode::Window,
+        ScreenshotMode::Region,
+    ]
+}
+
+fn default_directory() -> String {
+    String::from("${XDG_PICTURES_DIR:-$HOME/Pictures}")
+}
+
+fn default_filename() -> String {
+    String::from("screenshot-$(date +%Y%m%d-%H%M%S).png")
+}
+
+const fn default_delay() -> u64 {
+    300
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenshotMode {
+    Screen,
+    Window,
+    Region,
+}
+
+impl ScreenshotMode {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Screen => "screen",
+            Self::Window => "window",
+            Self::Region => "region",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Screen => "Screen",
+            Self::Window => "Window",
+            Self::Region => "Region",
+        }
+    }
+}
+
+/// Compositors which the active window's geometry can be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compositor {
+    Sway,
+    Hyprland,
+}
+
+impl Compositor {
+    fn detect() -> Option<Self> {
+        if env::var("SWAYSOCK").is_ok() {
+            Some(Self::Sway)
+        } else if env::var("HYPRLAND_INSTANCE_SIGNATURE").is_ok() {
+            Some(Self::Hyprland)
+        } else {
+            None
+        }
+    }
+
+    /// Gets a command printing the active window's geometry
+    /// in the format accepted by `grim -g`.
+    const fn window_geometry(self) -> &'static str {
+        match self {
+            Self::Sway => {
+                r#"swaymsg -t get_tree | jq -r '.. | select(.focused?) | .rect | "\(.x),\(.y) \(.width)x\(.height)"'"#
+            }
+            Self::Hyprland => {
+                r#"hyprctl -j activewindow | jq -r '"\(.at[0]),\(.at[1]) \(.size[0])x\(.size[1])"'"#
+            }
+        }
+    }
+
+    const fn programs(self) -> &'static [&'static str] {
+        match self {
+            Self::Sway => &["swaymsg", "jq"],
+            Self::Hyprland => &["hyprctl", "jq"],
+        }
+    }
+}
+
+/// A shell command to take a screenshot,
+/// along with the programs it needs installed.
+#[derive(Debug, PartialEq, Eq)]
+struct ScreenshotCommand {
+    command: String,
+    programs: Vec<String>,
+}
+
+impl ScreenshotModule {
+    /// Gets the command to take a screenshot in `mode`,
+    /// using `grim` and `slurp` unless overridden.
+    fn resolve(
+        &self,
+        mode: ScreenshotMode,
+        compositor: Option<Compositor>,
+    ) -> Result<ScreenshotCommand> {
+        let path = format!("\"{}/{}\"", self.directory, self.filename);
+
+        let command = match mode {
+            ScreenshotMode::Screen => &self.screen,
+            ScreenshotMode::Window => &self.window,
+            ScreenshotMode::Region => &self.region,
+        };
+
+        if let Some(command) = command {
+            let program = command.split_whitespace().next().unwrap_or_default();
+
+            return Ok(ScreenshotCommand {
+                command: command.replace("{path}", &path),
+                programs: vec![program.to_string()],
+            });
+        }
+
+        let (geometry, mut programs) = match mode {
+            ScreenshotMode::Screen => (None, vec![]),
+            ScreenshotMode::Region => (Some("slurp"), vec!["slurp"]),
+            ScreenshotMode::Window => {
+                let compositor = compositor.ok_or_else(|| {
+                    Report::msg("Unable to find the active window on this compositor")
+                        .suggestion("Set the `window` option to a command to capture it")
+                })?;
+
+                (
+                    Some(compositor.window_geometry()),
+                    compositor.programs().to_vec(),
+                )
+            }
+        };
+
+        programs.insert(0, "grim");
+
+        Ok(ScreenshotCommand {
+            command: format!(
+                "mkdir -p \"{}\" && {}",
+                self.directory,
+                grim_command(geometry, &path)
+            ),
+            programs: programs.into_iter().map(ToString::to_string).collect(),
+        })
+    }
+}
+
+/// Builds the `grim` command to save a screenshot to `path`,
+/// limited to the region printed by the `geometry` command if set.
+fn grim_command(geometry: Option<&str>, path: &str) -> String {
+    match geometry {
+        Some(geometry) => format!("grim -g \"$({geometry})\" {path}"),
+        None => format!("grim {path}"),
+    }
+}
+
+/// Gets the first program which cannot be found in any directory in `path`.
+fn find_missing<'a>(programs: &'a [String], path: &OsStr) -> Option<&'a str> {
+    programs
+        .iter()
+        .find(|program| !env::split_paths(path).any(|dir| Path::new(&dir).join(program).is_file()))
+        .map(String::as_str)
+}
+
+impl Module<Button> for ScreenshotModule {
+    type SendMessage = ();
+    type ReceiveMessage = ScreenshotMode;
+
+    module_impl!("screenshot");
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        _context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let compositor = Compositor::detect();
+        let module = self.clone();
+
+        spawn(async move {
+            while let Some(mode) = rx.recv().await {
+                let command = match module.resolve(mode, compositor) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        error!("{err:?}");
+                        continue;
+                    }
+                };
+
+                let path = env::var_os("PATH").unwrap_or_default();
+                if let Some(program) = find_missing(&command.programs, &path) {
+                    let err = Report::msg(format!(
+                        "Unable to take {} screenshot: `{program}` is not installed",
+                        mode.name()
+                    ))
+                    .suggestion(format!(
+                        "Install `{program}` or set the `{}` option",
+                        mode.name()
+                    ));
+
+                    error!("{err:?}");
+                    continue;
+                }
+
+                sleep(Duration::from_millis(module.delay)).await;
+
+                debug!("Taking {} screenshot: '{}'", mode.name(), command.command);
+                Script::from(command.command.as_str()).run_as_oneshot(None);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = Button::new();
+
+        let icon = gtk::Image::new();
+        icon.add_class("icon");
+        button.add(&icon);
+
+        ImageProvider::parse(&self.icon, info.icon_theme, false, self.icon_size).map(|provider| {
+            provider
+                .with_scale(info.scale_factor())
+                .load_into_image(icon)
+        });
+
+        let tx = context.tx.clone();
+        button.connect_clicked(move |button| {
+            try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+        });
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_lazy_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        _rx: broadcast::Receiver<Self::SendMessage>,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized,
+    {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        for mode in self.modes {
+            let button = Button::with_label(mode.label());
+            button.add_class("btn-mode");
+            button.add_class(mode.name());
+            container.add(&button);
+
+            let tx = tx.clone();
+            let popup_tx = context.tx.clone();
+
+            button.connect_clicked(move |_| {
+                // close first so the popup is not captured
+                try_send!(popup_tx, ModuleUpdateEvent::ClosePopup);
+                try_send!(tx, mode);
+            });
+        }
+
+        container.show_all();
+
+        Some(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn module(config: serde_json::Value) -> ScreenshotModule {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn test_default_commands() {
+        let module = module(json!({ "directory": "/tmp/shots", "filename": "shot.png" }));
+
+        let screen = module.resolve(ScreenshotMode::Screen, None).unwrap();
+        assert_eq!(
+            screen.command,
+            r#"mkdir -p "/tmp/shots" && grim "/tmp/shots/shot.png""#
+        );
+        assert_eq!(screen.programs, ["grim"]);
+
+        let region = module.resolve(ScreenshotMode::Region, None).unwrap();
+        assert_eq!(
+            region.command,
+            r#"mkdir -p "/tmp/shots" && grim -g "$(slurp)" "/tmp/shots/shot.png""#
+        );
+        assert_eq!(region.programs, ["grim", "slurp"]);
+    }
+
+    #[test]
+    fn test_window_command() {
+        let module = module(json!({}));
+
+        let window = module
+            .resolve(ScreenshotMode::Window, Some(Compositor::Hyprland))
+            .unwrap();
+        assert!(window
+            .command
+            .contains("grim -g \"$(hyprctl -j activewindow"));
+        assert_eq!(window.programs, ["grim", "hyprctl", "jq"]);
+
+        // no way to find the window without a supported compositor
+        assert!(module.resolve(ScreenshotMode::Window, None).is_err());
+    }
+
+    #[test]
+    fn test_command_override() {
+        let module = module(json!({
+            "directory": "/tmp",
+            "filename": "shot.png",
+            "region": "grimshot save area {path}",
+            "window": "my-window-shot"
+        }));
+
+        let region = module.resolve(ScreenshotMode::Region, None).unwrap();
+        assert_eq!(region.command, r#"grimshot save area "/tmp/shot.png""#);
+        assert_eq!(region.programs, ["grimshot"]);
+
+        // overrides work without a supported compositor
+        let window = module.resolve(ScreenshotMode::Window, None).unwrap();
+        assert_eq!(window.command, "my-window-shot");
+    }
+
+    #[test]
+    fn test_grim_command() {
+        assert_eq!(grim_command(None, "\"a.png\""), r#"grim "a.png""#);
+        assert_eq!(
+            grim_command(Some("slurp -d"), "\"a.png\""),
+            r#"grim -g "$(slurp -d)" "a.png""#
+        );
+    }
+
+    #[test]
+    fn test_find_missing() {
+        let programs = vec!["grim".to_string()];
+        assert_eq!(find_missing(&programs, OsStr::new("")), Some("grim"));
+        assert_eq!(
+            find_missing(&programs, OsStr::new("/nonexistent/bin")),
+            Some("grim")
+        );
+        assert_eq!(find_missing(&[], OsStr::new("")), None);
+    }
+}
@@ -1,15 +1,20 @@
-use crate::config::CommonConfig;
+use crate::config::{CommonConfig, Config};
+use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
-use crate::script::{OutputStream, Script, ScriptMode};
-use crate::{glib_recv, module_impl, spawn, try_send};
+use crate::script::{JsonOutput, OutputFormat, OutputStream, RealtimeSignal, Script, ScriptMode};
+use crate::{glib_recv, lock, module_impl, spawn, try_send, Ironbar};
 use color_eyre::{Help, Report, Result};
 use gtk::prelude::*;
 use gtk::Label;
+use schemars::JsonSchema;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use tokio::sync::mpsc;
 use tracing::error;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ScriptModule {
     /// Path to script to execute.
     cmd: String,
@@ -17,8 +22,32 @@ pub struct ScriptModule {
     #[serde(default = "default_mode")]
     mode: ScriptMode,
     /// Time in milliseconds between executions.
-    #[serde(default = "default_interval")]
-    interval: u64,
+    /// Defaults to the global `interval`, or 5000ms.
+    interval: Option<u64>,
+    /// Maximum time in milliseconds to randomly delay each execution by.
+    /// Defaults to the global `jitter`.
+    jitter: Option<u64>,
+    /// Path to watch for changes, re-running the script on change.
+    watch_path: Option<PathBuf>,
+    /// Real-time signal to re-run the script on.
+    signal: Option<RealtimeSignal>,
+    /// Whether to run the script only once,
+    /// unless refreshed or re-triggered.
+    #[serde(default)]
+    once: bool,
+    /// Environment variables to set on the script process.
+    #[serde(default)]
+    env: HashMap<String, String>,
+    /// Directory to run the script in.
+    cwd: Option<PathBuf>,
+    /// Time in milliseconds after which a polling script is killed.
+    timeout: Option<u64>,
+    /// How the script output is interpreted.
+    #[serde(default)]
+    output: OutputFormat,
+    /// Whether to show script errors in the tooltip.
+    #[serde(default)]
+    show_stderr_in_tooltip: bool,
 
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
@@ -34,37 +63,108 @@ const fn default_interval() -> u64 {
     5000
 }
 
-impl From<&ScriptModule> for Script {
-    fn from(module: &ScriptModule) -> Self {
-        Self {
-            mode: module.mode,
-            cmd: module.cmd.clone(),
-            interval: module.interval,
+impl ScriptModule {
+    /// Creates the script to run,
+    /// using the global `interval` and `jitter` for any not set on the module.
+    fn script(&self, config: &Config) -> Script {
+        let interval = self
+            .interval
+            .or(config.interval)
+            .unwrap_or_else(default_interval);
+
+        Script {
+            mode: self.mode,
+            cmd: self.cmd.clone(),
+            interval,
+            jitter: self.jitter.or(config.jitter).unwrap_or_default(),
+            watch_path: self.watch_path.clone(),
+            signal: self.signal,
+            once: self.once,
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+            timeout: self.timeout,
+            transform: None,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum ScriptUpdate {
+    Output(JsonOutput),
+    /// A running script wrote a line to `stderr`,
+    /// without failing.
+    Stderr(String),
+    /// The script failed.
+    /// Takes its error output.
+    Error(String),
+}
+
 impl Module<Label> for ScriptModule {
-    type SendMessage = String;
+    type SendMessage = ScriptUpdate;
     type ReceiveMessage = ();
 
     module_impl!("script");
 
+    fn supersedes(next: &Self::SendMessage, previous: &Self::SendMessage) -> bool {
+        match (next, previous) {
+            // JSON output without text keeps the previous text,
+            // so can only replace an update which sets it too
+            (ScriptUpdate::Output(next), ScriptUpdate::Output(_)) => next.text.is_some(),
+            (
+                ScriptUpdate::Stderr(_) | ScriptUpdate::Error(_),
+                ScriptUpdate::Stderr(_) | ScriptUpdate::Error(_),
+            ) => true,
+            _ => false,
+        }
+    }
+
+    fn error(update: &Self::SendMessage) -> Option<String> {
+        match update {
+            ScriptUpdate::Error(stderr) => Some(stderr.clone()),
+            ScriptUpdate::Output(_) | ScriptUpdate::Stderr(_) => None,
+        }
+    }
+
+    fn reports_errors() -> bool {
+        true
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
         context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
         _rx: mpsc::Receiver<Self::ReceiveMessage>,
     ) -> Result<()> {
-        let script: Script = self.into();
+        let script = self.script(&context.ironbar.config.borrow());
+        let format = self.output;
+
+        let refresh = Some(lock!(Ironbar::script_refresh()).register(&context.name));
 
         let tx = context.tx.clone();
         spawn(async move {
-            script.run(None, move |out, _| match out {
+            script.run_refreshable(None, refresh, move |out, success| match out {
                OutputStream::Stdout(stdout) => {
-                   try_send!(tx, ModuleUpdateEvent::Update(stdout));
+                   let output = match format {
+                       OutputFormat::Plain => Ok(JsonOutput::text(stdout)),
+                       OutputFormat::Json => JsonOutput::parse(&stdout),
+                   };
+
+                   // on malformed output, keep showing the previous output
+                   match output {
+                       Ok(output) => try_send!(tx, ModuleUpdateEvent::Update(ScriptUpdate::Output(output))),
+                       Err(err) => error!("{err:?}"),
+                   }
                },
                OutputStream::Stderr(stderr) => {
+                   // only a failed run is an error, as running scripts may log to stderr
+                   let update = if success {
+                       ScriptUpdate::Stderr(stderr.clone())
+                   } else {
+                       ScriptUpdate::Error(stderr.clone())
+                   };
+
+                   try_send!(tx, ModuleUpdateEvent::Update(update));
+
                    error!("{:?}", Report::msg(stderr)
                                     .wrap_err("Watched script error:")
                                     .suggestion("Check the path to your script")
@@ -87,7 +187,32 @@ impl Module<Label> for ScriptModule {
 
         {
             let label = label.clone();
-            glib_recv!(context.subscribe(), s => label.set_markup(s.as_str()));
+            let show_stderr = self.show_stderr_in_tooltip;
+            let mut classes = vec![];
+
+            glib_recv!(context.subscribe(), update => {
+                let output = match update {
+                    ScriptUpdate::Output(output) => output,
+                    ScriptUpdate::Stderr(stderr) | ScriptUpdate::Error(stderr) if show_stderr => {
+                        JsonOutput {
+                            tooltip: Some(stderr),
+                            ..JsonOutput::default()
+                        }
+                    }
+                    ScriptUpdate::Stderr(_) | ScriptUpdate::Error(_) => continue,
+                };
+
+                if let Some(text) = output.text {
+                    label.set_markup(&text);
+                }
+
+                if output.tooltip.is_some() || label.has_tooltip() {
+                    label.set_tooltip_markup(output.tooltip.as_deref());
+                }
+
+                label.replace_classes(&classes, &output.class);
+                classes = output.class;
+            });
         }
 
         Ok(ModuleParts {
@@ -96,3 +221,56 @@ impl Module<Label> for ScriptModule {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(text: Option<&str>, tooltip: Option<&str>) -> ScriptUpdate {
+        ScriptUpdate::Output(JsonOutput {
+            text: text.map(str::to_string),
+            tooltip: tooltip.map(str::to_string),
+            ..JsonOutput::default()
+        })
+    }
+
+    fn supersedes(next: &ScriptUpdate, previous: &ScriptUpdate) -> bool {
+        <ScriptModule as Module<Label>>::supersedes(next, previous)
+    }
+
+    #[test]
+    fn test_full_output_supersedes() {
+        assert!(supersedes(
+            &output(Some("b"), None),
+            &output(Some("a"), Some("tooltip"))
+        ));
+    }
+
+    #[test]
+    fn test_partial_output_keeps_previous() {
+        assert!(!supersedes(
+            &output(None, Some("tooltip")),
+            &output(Some("a"), None)
+        ));
+    }
+
+    #[test]
+    fn test_only_failure_is_error() {
+        let error = <ScriptModule as Module<Label>>::error;
+
+        assert_eq!(
+            error(&ScriptUpdate::Error("failed".to_string())),
+            Some("failed".to_string())
+        );
+        assert_eq!(error(&ScriptUpdate::Stderr("warning".to_string())), None);
+        assert_eq!(error(&output(Some("a"), None)), None);
+    }
+
+    #[test]
+    fn test_error_keeps_output() {
+        assert!(!supersedes(
+            &ScriptUpdate::Error("failed".to_string()),
+            &output(Some("a"), None)
+        ));
+    }
+}
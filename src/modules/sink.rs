@@ -0,0 +1,640 @@
+use crate::clients::volume::{self, Event, Sink};
+use crate::config::{CommonConfig, TruncateMode};
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::{glib_recv, lock, module_impl, send_async, spawn, try_send};
+use gtk::prelude::*;
+use gtk::{Button, Label, Orientation};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+use tracing::debug;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SinkModule {
+    /// Format of the bar label.
+    /// `{icon}` and `{name}` are replaced with those of the current device.
+    #[serde(default = "default_format")]
+    format: String,
+
+    #[serde(default)]
+    icons: Icons,
+
+    // -- Common --
+    truncate: Option<TruncateMode>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_format() -> String {
+    String::from("{icon} {name}")
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(rename = "SinkIcons")]
+pub struct Icons {
+    #[serde(default = "default_icon_speaker")]
+    speaker: String,
+    #[serde(default = "default_icon_headphones")]
+    headphones: String,
+    #[serde(default = "default_icon_hdmi")]
+    hdmi: String,
+    #[serde(default = "default_icon_bluetooth")]
+    bluetooth: String,
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Self {
+            speaker: default_icon_speaker(),
+            headphones: default_icon_headphones(),
+            hdmi: default_icon_hdmi(),
+            bluetooth: default_icon_bluetooth(),
+        }
+    }
+}
+
+impl Icons {
+    fn icon(&self, kind: DeviceKind) -> &str {
+        match kind {
+            DeviceKind::Speaker => &self.speaker,
+            DeviceKind::Headphones => &self.headphones,
+            DeviceKind::Hdmi => &self.hdmi,
+            DeviceKind::Bluetooth => &self.bluetooth,
+        }
+    }
+}
+
+fn default_icon_speaker() -> String {
+    String::from("󰓃")
+}
+
+fn default_icon_headphones() -> String {
+    String::from("󰋋")
+}
+
+fn default_icon_hdmi() -> String {
+    String::from("󰡁")
+}
+
+fn default_icon_bluetooth() -> String {
+    String::from("󰂰")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    Speaker,
+    Headphones,
+    Hdmi,
+    Bluetooth,
+}
+
+impl DeviceKind {
+    /// Guesses the type of device from the sink's name,
+    /// active port and form factor.
+    fn classify(name: &str, port: Option<&str>, form_factor: Option<&str>) -> Self {
+        let port = port.unwrap_or_default();
+
+        if name.starts_with("bluez") {
+            Self::Bluetooth
+        } else if matches!(form_factor, Some("headphone" | "headset"))
+            || port.contains("headphone")
+            || port.contains("headset")
+        {
+            Self::Headphones
+        } else if name.contains("hdmi")
+            || port.contains("hdmi")
+            || name.contains("displayport")
+            || port.contains("displayport")
+        {
+            Self::Hdmi
+        } else {
+            Self::Speaker
+        }
+    }
+
+    const fn class(self) -> &'static str {
+        match self {
+            Self::Speaker => "speaker",
+            Self::Headphones => "headphones",
+            Self::Hdmi => "hdmi",
+            Self::Bluetooth => "bluetooth",
+        }
+    }
+}
+
+/// An audio output device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// Server name of the sink, used to set it as the default.
+    name: String,
+    /// Human-readable name.
+    description: String,
+    kind: DeviceKind,
+}
+
+impl From<&Sink> for Device {
+    fn from(sink: &Sink) -> Self {
+        Self {
+            name: sink.name.clone(),
+            description: sink.description.clone(),
+            kind: DeviceKind::classify(
+                &sink.name,
+                sink.port.as_deref(),
+                sink.form_factor.as_deref(),
+            ),
+        }
+    }
+}
+
+/// Change to the available devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DeviceEvent {
+    /// A device was added or changed.
+    Upsert(Device),
+    Removed(String),
+    /// The server's default sink, which is the current device.
+    ///
+    /// This is tracked separately from the sinks,
+    /// as any sink which is playing is reported as active.
+    Default(Option<String>),
+}
+
+impl DeviceEvent {
+    /// Gets the device change from a volume client event,
+    /// if there is one.
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::AddSink(sink) | Event::UpdateSink(sink) => {
+                Some(Self::Upsert(Device::from(sink)))
+            }
+            Event::RemoveSink(name) => Some(Self::Removed(name.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The available devices and which is current.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceList {
+    devices: Vec<Device>,
+    current: Option<String>,
+}
+
+impl DeviceList {
+    /// Applies a change to the list.
+    /// Returns `true` if anything changed.
+    fn apply(&mut self, event: DeviceEvent) -> bool {
+        match event {
+            DeviceEvent::Upsert(device) => {
+                match self.devices.iter_mut().find(|d| d.name == device.name) {
+                    Some(existing) if *existing == device => false,
+                    Some(existing) => {
+                        *existing = device;
+                        true
+                    }
+                    None => {
+                        self.devices.push(device);
+                        true
+                    }
+                }
+            }
+            DeviceEvent::Removed(name) => {
+                let len = self.devices.len();
+                self.devices.retain(|device| device.name != name);
+
+                if self.current.as_ref() == Some(&name) {
+                    self.current = None;
+                }
+
+                self.devices.len() != len
+            }
+            DeviceEvent::Default(name) => {
+                let changed = self.current != name;
+                self.current = name;
+                changed
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&Device> {
+        let current = self.current.as_ref()?;
+        self.devices.iter().find(|device| &device.name == current)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.devices.iter().any(|device| device.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SinkCommand {
+    /// Sets the device with the given name as the default output.
+    SetDefault(String),
+}
+
+/// Something which can change the default output device.
+trait SetDefaultSink {
+    fn set_default_sink(&self, name: &str);
+}
+
+impl SetDefaultSink for volume::Client {
+    fn set_default_sink(&self, name: &str) {
+        volume::Client::set_default_sink(self, name);
+    }
+}
+
+/// Carries out a command from the UI.
+///
+/// Unknown devices and the device which is already current are skipped.
+/// Returns `true` if the default was changed.
+fn dispatch(command: SinkCommand, devices: &DeviceList, client: &impl SetDefaultSink) -> bool {
+    match command {
+        SinkCommand::SetDefault(name) => {
+            if !devices.contains(&name) || devices.current.as_ref() == Some(&name) {
+                return false;
+            }
+
+            debug!("Setting default sink to '{name}'");
+            client.set_default_sink(&name);
+            true
+        }
+    }
+}
+
+impl Module<Button> for SinkModule {
+    type SendMessage = DeviceList;
+    type ReceiveMessage = SinkCommand;
+
+    module_impl!("sink");
+
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> color_eyre::Result<()> {
+        let client = context.client::<volume::Client>();
+        let (devices_tx, mut devices_rx) = mpsc::channel(16);
+
+        {
+            let client = client.clone();
+            let tx = context.tx.clone();
+
+            spawn(async move {
+                let mut events = client.subscribe();
+
+                let mut devices = DeviceList::default();
+                {
+                    let sinks = client.sinks();
+                    for sink in lock!(sinks).iter() {
+                        devices.apply(DeviceEvent::Upsert(Device::from(sink)));
+                    }
+                }
+                devices.apply(DeviceEvent::Default(client.default_sink_name()));
+
+                send_async!(devices_tx, devices.clone());
+                send_async!(tx, ModuleUpdateEvent::Update(devices.clone()));
+
+                while let Ok(event) = events.recv().await {
+                    let Some(event) = DeviceEvent::from_event(&event) else {
+                        continue;
+                    };
+
+                    // the default changing is sent as an update to the new default sink,
+                    // so it is checked after each sink event
+                    let changed = devices.apply(event)
+                        | devices.apply(DeviceEvent::Default(client.default_sink_name()));
+
+                    if changed {
+                        send_async!(devices_tx, devices.clone());
+                        send_async!(tx, ModuleUpdateEvent::Update(devices.clone()));
+                    }
+                }
+            });
+        }
+
+        spawn(async move {
+            let mut devices = DeviceList::default();
+
+            loop {
+                tokio::select! {
+                    Some(update) = devices_rx.recv() => devices = update,
+                    Some(command) = rx.recv() => {
+                        dispatch(command, &devices, client.as_ref());
+                    }
+                    else => break,
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> color_eyre::Result<ModuleParts<Button>> {
+        let button = Button::new();
+        let label = Label::new(None);
+        label.add_class("label");
+        button.add(&label);
+
+        if let Some(truncate) = self.truncate {
+            truncate.truncate_label(&label);
+        }
+
+        {
+            let tx = context.tx.clone();
+            button.connect_clicked(move |button| {
+                try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+            });
+        }
+
+        {
+            let format = self.format.clone();
+            let icons = self.icons.clone();
+            let button = button.clone();
+            let mut kind_class = None;
+
+            let rx = context.subscribe();
+            glib_recv!(rx, devices => {
+                if let Some(class) = kind_class.take() {
+                    button.style_context().remove_class(class);
+                }
+
+                match devices.current() {
+                    Some(device) => {
+                        let text = format
+                            .replace("{icon}", icons.icon(device.kind))
+                            .replace("{name}", &device.description);
+                        label.set_label(&text);
+
+                        button.add_class(device.kind.class());
+                        kind_class = Some(device.kind.class());
+                        button.show();
+                    }
+                    None => button.hide(),
+                }
+            });
+        }
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized,
+    {
+        let container = gtk::Box::new(Orientation::Vertical, 5);
+
+        {
+            let container = container.clone();
+
+            glib_recv!(rx, devices => {
+                for child in container.children() {
+                    container.remove(&child);
+                }
+
+                for device in &devices.devices {
+                    let button = Button::new();
+                    button.add_class("device");
+                    button.add_class(device.kind.class());
+
+                    if devices.current.as_ref() == Some(&device.name) {
+                        button.add_class("active");
+                    }
+
+                    let row = gtk::Box::new(Orientation::Horizontal, 5);
+
+                    let icon = Label::new(Some(self.icons.icon(device.kind)));
+                    icon.add_class("icon");
+                    row.add(&icon);
+
+                    let label = Label::new(Some(&device.description));
+                    label.add_class("label");
+                    row.add(&label);
+
+                    button.add(&row);
+
+                    {
+                        let tx = tx.clone();
+                        let name = device.name.clone();
+                        button.connect_clicked(move |_| {
+                            try_send!(tx, SinkCommand::SetDefault(name.clone()));
+                        });
+                    }
+
+                    container.add(&button);
+                }
+
+                container.show_all();
+            });
+        }
+
+        Some(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn device(name: &str, kind: DeviceKind) -> Device {
+        Device {
+            name: name.to_string(),
+            description: name.to_uppercase(),
+            kind,
+        }
+    }
+
+    fn list(events: impl IntoIterator<Item = DeviceEvent>) -> DeviceList {
+        let mut devices = DeviceList::default();
+        for event in events {
+            devices.apply(event);
+        }
+        devices
+    }
+
+    #[derive(Default)]
+    struct MockClient {
+        defaults: RefCell<Vec<String>>,
+    }
+
+    impl SetDefaultSink for MockClient {
+        fn set_default_sink(&self, name: &str) {
+            self.defaults.borrow_mut().push(name.to_string());
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            DeviceKind::classify(
+                "alsa_output.pci-0000_00_1f.3.analog-stereo",
+                Some("analog-output-speaker"),
+                None
+            ),
+            DeviceKind::Speaker
+        );
+        assert_eq!(
+            DeviceKind::classify(
+                "alsa_output.pci-0000_00_1f.3.analog-stereo",
+                Some("analog-output-headphones"),
+                None
+            ),
+            DeviceKind::Headphones
+        );
+        assert_eq!(
+            DeviceKind::classify("usb-headset", None, Some("headset")),
+            DeviceKind::Headphones
+        );
+        assert_eq!(
+            DeviceKind::classify(
+                "alsa_output.pci-0000_01_00.1.hdmi-stereo",
+                Some("hdmi-output-0"),
+                None
+            ),
+            DeviceKind::Hdmi
+        );
+        assert_eq!(
+            DeviceKind::classify("bluez_output.00_11_22_33_44_55.1", None, Some("headphone")),
+            DeviceKind::Bluetooth
+        );
+    }
+
+    fn upsert(name: &str, kind: DeviceKind) -> DeviceEvent {
+        DeviceEvent::Upsert(device(name, kind))
+    }
+
+    fn default(name: &str) -> DeviceEvent {
+        DeviceEvent::Default(Some(name.to_string()))
+    }
+
+    #[test]
+    fn test_add_devices() {
+        let devices = list([
+            upsert("speakers", DeviceKind::Speaker),
+            upsert("hdmi", DeviceKind::Hdmi),
+            default("speakers"),
+        ]);
+
+        assert_eq!(devices.devices.len(), 2);
+        assert_eq!(devices.current().map(|d| d.name.as_str()), Some("speakers"));
+    }
+
+    #[test]
+    fn test_update_device() {
+        let mut devices = list([upsert("speakers", DeviceKind::Speaker), default("speakers")]);
+
+        // unchanged
+        assert!(!devices.apply(upsert("speakers", DeviceKind::Speaker)));
+        assert!(!devices.apply(default("speakers")));
+
+        // port switched to headphones
+        assert!(devices.apply(upsert("speakers", DeviceKind::Headphones)));
+        assert_eq!(devices.devices.len(), 1);
+        assert_eq!(devices.devices[0].kind, DeviceKind::Headphones);
+        assert_eq!(devices.current.as_deref(), Some("speakers"));
+    }
+
+    #[test]
+    fn test_switch_current() {
+        let mut devices = list([
+            upsert("speakers", DeviceKind::Speaker),
+            upsert("hdmi", DeviceKind::Hdmi),
+            default("speakers"),
+        ]);
+
+        assert!(devices.apply(default("hdmi")));
+        assert_eq!(devices.current().map(|d| d.name.as_str()), Some("hdmi"));
+    }
+
+    #[test]
+    fn test_running_sink_not_current() {
+        let mut devices = list([
+            upsert("speakers", DeviceKind::Speaker),
+            upsert("hdmi", DeviceKind::Hdmi),
+            default("speakers"),
+        ]);
+
+        // a sink starting to play is reported as an update,
+        // but the server default is unchanged
+        assert!(devices.apply(upsert("hdmi", DeviceKind::Headphones)));
+        assert!(!devices.apply(default("speakers")));
+
+        assert_eq!(devices.current().map(|d| d.name.as_str()), Some("speakers"));
+    }
+
+    #[test]
+    fn test_remove_device() {
+        let mut devices = list([
+            upsert("speakers", DeviceKind::Speaker),
+            upsert("bluez", DeviceKind::Bluetooth),
+            default("bluez"),
+        ]);
+
+        assert!(devices.apply(DeviceEvent::Removed("bluez".to_string())));
+        assert_eq!(devices.devices, [device("speakers", DeviceKind::Speaker)]);
+        assert!(devices.current().is_none());
+
+        assert!(!devices.apply(DeviceEvent::Removed("bluez".to_string())));
+    }
+
+    #[test]
+    fn test_dispatch_set_default() {
+        let devices = list([
+            upsert("speakers", DeviceKind::Speaker),
+            upsert("hdmi", DeviceKind::Hdmi),
+            default("speakers"),
+        ]);
+        let client = MockClient::default();
+
+        assert!(dispatch(
+            SinkCommand::SetDefault("hdmi".to_string()),
+            &devices,
+            &client
+        ));
+        assert_eq!(*client.defaults.borrow(), ["hdmi"]);
+    }
+
+    #[test]
+    fn test_dispatch_skips_current_and_unknown() {
+        let devices = list([upsert("speakers", DeviceKind::Speaker), default("speakers")]);
+        let client = MockClient::default();
+
+        assert!(!dispatch(
+            SinkCommand::SetDefault("speakers".to_string()),
+            &devices,
+            &client
+        ));
+        assert!(!dispatch(
+            SinkCommand::SetDefault("missing".to_string()),
+            &devices,
+            &client
+        ));
+        assert!(client.defaults.borrow().is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::modules::thermal::parse_millidegrees;
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
+use nvml_wrapper::Nvml;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const DRM_PATH: &str = "/sys/class/drm";
+const AMD_VENDOR_ID: &str = "0x1002";
+
+/// A GPU to read statistics from.
+pub enum Gpu {
+    /// An AMD card, read through the `amdgpu` sysfs interface.
+    /// Contains the path to the card's `device` directory.
+    Amd(PathBuf),
+    /// An NVIDIA card, read through NVML.
+    Nvidia { nvml: Box<Nvml>, index: u32 },
+}
+
+impl Debug for Gpu {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Amd(path) => f.debug_tuple("Amd").field(path).finish(),
+            Self::Nvidia { index, .. } => f.debug_struct("Nvidia").field("index", index).finish(),
+        }
+    }
+}
+
+/// A snapshot of a GPU's statistics.
+/// Values the driver does not report are `None`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GpuStats {
+    /// Utilisation percentage.
+    pub usage: Option<f64>,
+    /// VRAM used in bytes.
+    pub vram_used: Option<u64>,
+    /// VRAM total in bytes.
+    pub vram_total: Option<u64>,
+    /// Temperature in degrees C.
+    pub temp: Option<f64>,
+}
+
+impl Gpu {
+    /// Finds the GPU to read from.
+    ///
+    /// `device` can be a DRM card name such as `card1` for AMD cards,
+    /// or `nvidia` followed by an index such as `nvidia0` for NVIDIA cards.
+    /// If not set, the first AMD card is used, falling back to the first NVIDIA card.
+    ///
+    /// Logs a warning if no supported GPU is found.
+    pub fn detect(device: Option<&str>) -> Option<Self> {
+        let gpu = match device {
+            Some(device) => {
+                if let Some(index) = device.strip_prefix("nvidia") {
+                    index.parse().ok().and_then(nvidia)
+                } else {
+                    let path = Path::new(DRM_PATH).join(device).join("device");
+                    is_amd(&path).then_some(Self::Amd(path))
+                }
+            }
+            None => amd_cards()
+                .into_iter()
+                .next()
+                .map(Self::Amd)
+                .or_else(|| nvidia(0)),
+        };
+
+        match &gpu {
+            Some(gpu) => debug!("Reading GPU statistics from {gpu:?}"),
+            None => warn!(
+                "No supported GPU found{}, GPU tokens will be empty",
+                device
+                    .map(|device| format!(" for '{device}'"))
+                    .unwrap_or_default()
+            ),
+        }
+
+        gpu
+    }
+
+    pub fn stats(&self) -> GpuStats {
+        match self {
+            Self::Amd(path) => amd_stats(path),
+            Self::Nvidia { nvml, index } => nvidia_stats(nvml, *index),
+        }
+    }
+}
+
+fn is_amd(device_path: &Path) -> bool {
+    fs::read_to_string(device_path.join("vendor"))
+        .is_ok_and(|vendor| vendor.trim() == AMD_VENDOR_ID)
+}
+
+/// Gets the `device` directories of all AMD cards,
+/// ordered by card number.
+fn amd_cards() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(DRM_PATH) else {
+        return vec![];
+    };
+
+    let mut cards = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let number = name.to_str()?.strip_prefix("card")?.parse::<u32>().ok()?;
+            Some((number, entry.path().join("device")))
+        })
+        .filter(|(_, path)| is_amd(path))
+        .collect::<Vec<_>>();
+
+    cards.sort_by_key(|(number, _)| *number);
+    cards.into_iter().map(|(_, path)| path).collect()
+}
+
+fn amd_stats(device_path: &Path) -> GpuStats {
+    let read = |file: &str| fs::read_to_string(device_path.join(file)).ok();
+
+    // the first sensor is the edge temperature
+    let temp = fs::read_dir(device_path.join("hwmon"))
+        .ok()
+        .and_then(|mut entries| entries.find_map(Result::ok))
+        .and_then(|entry| fs::read_to_string(entry.path().join("temp1_input")).ok())
+        .and_then(|temp| parse_millidegrees(&temp));
+
+    GpuStats {
+        usage: read("gpu_busy_percent").and_then(|usage| parse_busy_percent(&usage)),
+        vram_used: read("mem_info_vram_used").and_then(|used| used.trim().parse().ok()),
+        vram_total: read("mem_info_vram_total").and_then(|total| total.trim().parse().ok()),
+        temp,
+    }
+}
+
+/// Parses the contents of `gpu_busy_percent`.
+fn parse_busy_percent(value: &str) -> Option<f64> {
+    value.trim().parse::<u8>().ok().map(f64::from)
+}
+
+/// Loads NVML and checks the card at `index` exists.
+///
+/// Returns `None` if the proprietary driver is not installed.
+fn nvidia(index: u32) -> Option<Gpu> {
+    let nvml = Nvml::init()
+        .inspect_err(|err| debug!("NVML is not available: {err}"))
+        .ok()?;
+
+    nvml.device_by_index(index)
+        .inspect_err(|err| debug!("No NVIDIA card at index {index}: {err}"))
+        .ok()?;
+
+    Some(Gpu::Nvidia {
+        nvml: Box::new(nvml),
+        index,
+    })
+}
+
+/// Reads a card's statistics from NVML.
+/// Values the card does not support are left empty.
+fn nvidia_stats(nvml: &Nvml, index: u32) -> GpuStats {
+    let Ok(device) = nvml.device_by_index(index) else {
+        return GpuStats::default();
+    };
+
+    let memory = device.memory_info().ok();
+
+    GpuStats {
+        usage: device
+            .utilization_rates()
+            .ok()
+            .map(|utilization| f64::from(utilization.gpu)),
+        vram_used: memory.as_ref().map(|memory| memory.used),
+        vram_total: memory.as_ref().map(|memory| memory.total),
+        temp: device
+            .temperature(TemperatureSensor::Gpu)
+            .ok()
+            .map(f64::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_busy_percent() {
+        assert_eq!(parse_busy_percent("42\n"), Some(42.0));
+        assert_eq!(parse_busy_percent("0"), Some(0.0));
+        assert_eq!(parse_busy_percent(""), None);
+        assert_eq!(parse_busy_percent("-1"), None);
+    }
+}
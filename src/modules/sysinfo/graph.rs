@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use glib::Propagation;
+use gtk::cairo::{self, Context};
+use gtk::gdk::RGBA;
+use gtk::prelude::*;
+use gtk::{CssProvider, DrawingArea, StateFlags};
+use tracing::error;
+
+use crate::gtk_helpers::IronbarGtkExt;
+
+/// Lowest value the graph scales to, in bits per second,
+/// so that an idle connection does not fill the graph with noise.
+const MIN_SCALE: f64 = 10_000.0;
+
+/// Default colours for each direction,
+/// which the user's stylesheet takes precedence over.
+const GRAPH_CSS: &str = "
+.down { color: #3584e4; }
+.up { color: #e66100; }
+";
+
+/// Fixed-size buffer which drops its oldest item
+/// once full.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingBuffer<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        // a buffer which can hold nothing would never draw anything
+        let capacity = capacity.max(2);
+
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+
+        self.items.push_back(item);
+    }
+
+    /// Iterates over the items, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Network traffic over a single refresh,
+/// in bits per second.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub down: f64,
+    pub up: f64,
+}
+
+/// Gets the value at the top of the graph,
+/// which is the highest sample in either direction
+/// rounded up to the next 1, 2 or 5 step.
+pub fn scale_max(samples: &RingBuffer<Sample>) -> f64 {
+    let max = samples
+        .iter()
+        .map(|sample| sample.down.max(sample.up))
+        .fold(MIN_SCALE, f64::max);
+
+    let magnitude = 10_f64.powi(max.log10().floor() as i32);
+
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|&step| step >= max)
+        .unwrap_or(max)
+}
+
+/// Formats a rate in bits per second
+/// using the largest unit it fills.
+pub fn format_rate(bits: f64) -> String {
+    const UNITS: [&str; 4] = ["bps", "kbps", "Mbps", "Gbps"];
+
+    let mut value = bits;
+    let mut unit = 0;
+
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{value:.0} {}", UNITS[unit])
+}
+
+/// Rolling graph of download and upload rates.
+///
+/// Each direction is coloured by the `color` of its `.down` or `.up` class.
+#[derive(Debug, Clone)]
+pub struct NetworkGraph {
+    pub area: DrawingArea,
+    samples: Rc<RefCell<RingBuffer<Sample>>>,
+}
+
+impl NetworkGraph {
+    pub fn new(area: DrawingArea, capacity: usize) -> Self {
+        let samples = Rc::new(RefCell::new(RingBuffer::new(capacity)));
+
+        area.add_class("graph");
+
+        let provider = CssProvider::new();
+        if provider.load_from_data(GRAPH_CSS.as_bytes()).is_ok() {
+            area.style_context()
+                .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_FALLBACK);
+        }
+
+        {
+            let samples = samples.clone();
+            area.connect_draw(move |area, cr| {
+                if let Err(err) = draw(area, cr, &samples.borrow()) {
+                    error!("Failed to draw network graph: {err:?}");
+                }
+
+                Propagation::Stop
+            });
+        }
+
+        Self { area, samples }
+    }
+
+    /// Replaces the samples shown.
+    ///
+    /// The graph is only redrawn while it is on screen,
+    /// and catches up the next time it is shown otherwise.
+    pub fn set_samples(&self, samples: RingBuffer<Sample>) {
+        *self.samples.borrow_mut() = samples;
+
+        if self.area.is_mapped() {
+            self.area.queue_draw();
+        }
+    }
+}
+
+fn draw(
+    area: &DrawingArea,
+    cr: &Context,
+    samples: &RingBuffer<Sample>,
+) -> Result<(), cairo::Error> {
+    let style = area.style_context();
+    let width = f64::from(area.allocated_width());
+    let height = f64::from(area.allocated_height());
+
+    gtk::render_background(&style, cr, 0.0, 0.0, width, height);
+
+    let max = scale_max(samples);
+    // newest sample sits against the right edge
+    let step = width / (samples.capacity() - 1) as f64;
+    let offset = (samples.capacity() - samples.len()) as f64;
+
+    let points = |value: fn(&Sample) -> f64| {
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                let x = (offset + i as f64) * step;
+                let y = height - (value(sample) / max).clamp(0.0, 1.0) * height;
+                (x, y)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (class, points) in [
+        ("down", points(|sample| sample.down)),
+        ("up", points(|sample| sample.up)),
+    ] {
+        let (Some(&(first_x, _)), Some(&(last_x, _))) = (points.first(), points.last()) else {
+            continue;
+        };
+
+        style.save();
+        style.add_class(class);
+        let color = style.color(StateFlags::NORMAL);
+        style.restore();
+
+        for &(x, y) in &points {
+            cr.line_to(x, y);
+        }
+
+        cr.line_to(last_x, height);
+        cr.line_to(first_x, height);
+        cr.close_path();
+        set_source(cr, &color, 0.25);
+        cr.fill()?;
+
+        for &(x, y) in &points {
+            cr.line_to(x, y);
+        }
+
+        set_source(cr, &color, 1.0);
+        cr.set_line_width(1.5);
+        cr.stroke()?;
+    }
+
+    Ok(())
+}
+
+fn set_source(cr: &Context, color: &RGBA, opacity: f64) {
+    cr.set_source_rgba(
+        color.red(),
+        color.green(),
+        color.blue(),
+        color.alpha() * opacity,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(values: &[f64]) -> RingBuffer<Sample> {
+        let mut buffer = RingBuffer::new(values.len());
+        for &down in values {
+            buffer.push(Sample { down, up: 0.0 });
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_push_under_capacity() {
+        let mut buffer = RingBuffer::new(4);
+        buffer.push(1);
+        buffer.push(2);
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn test_push_drops_oldest() {
+        let mut buffer = RingBuffer::new(3);
+        for i in 1..=5 {
+            buffer.push(i);
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [3, 4, 5]);
+    }
+
+    #[test]
+    fn test_minimum_capacity() {
+        let mut buffer = RingBuffer::new(0);
+        for i in 1..=3 {
+            buffer.push(i);
+        }
+
+        assert_eq!(buffer.capacity(), 2);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn test_scale_max_steps() {
+        assert_eq!(scale_max(&buffer(&[12_000.0])), 20_000.0);
+        assert_eq!(scale_max(&buffer(&[30_000.0, 10_000.0])), 50_000.0);
+        assert_eq!(scale_max(&buffer(&[51_000.0])), 100_000.0);
+        assert_eq!(scale_max(&buffer(&[2_000_000.0])), 2_000_000.0);
+    }
+
+    #[test]
+    fn test_scale_max_uses_both_directions() {
+        let mut buffer = RingBuffer::new(2);
+        buffer.push(Sample {
+            down: 15_000.0,
+            up: 0.0,
+        });
+        buffer.push(Sample {
+            down: 0.0,
+            up: 300_000.0,
+        });
+
+        assert_eq!(scale_max(&buffer), 500_000.0);
+    }
+
+    #[test]
+    fn test_scale_max_minimum() {
+        assert_eq!(scale_max(&RingBuffer::new(10)), MIN_SCALE);
+        assert_eq!(scale_max(&buffer(&[0.0, 5.0])), MIN_SCALE);
+        assert_eq!(scale_max(&buffer(&[f64::NAN])), MIN_SCALE);
+    }
+
+    #[test]
+    fn test_scale_max_drops_old_peaks() {
+        let mut buffer = buffer(&[900_000.0, 1000.0]);
+        buffer.push(Sample {
+            down: 40_000.0,
+            up: 0.0,
+        });
+
+        assert_eq!(scale_max(&buffer), 50_000.0);
+    }
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(0.0), "0 bps");
+        assert_eq!(format_rate(20_000.0), "20 kbps");
+        assert_eq!(format_rate(5_000_000.0), "5 Mbps");
+        assert_eq!(format_rate(3e12), "3000 Gbps");
+    }
+}
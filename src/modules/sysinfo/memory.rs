@@ -0,0 +1,112 @@
+use std::fs;
+
+use color_eyre::Result;
+
+const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// Memory statistics from `/proc/meminfo`, in bytes.
+///
+/// Fields missing from the file are `0`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    /// Estimate of the memory available to start new applications,
+    /// including reclaimable caches.
+    pub available: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+impl MemInfo {
+    pub fn read() -> Result<Self> {
+        let contents = fs::read_to_string(MEMINFO_PATH)?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Parses the contents of `/proc/meminfo`,
+    /// in which each line is in the form `Key:   1234 kB`.
+    fn parse(contents: &str) -> Self {
+        let mut info = Self::default();
+
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let field = match key {
+                "MemTotal" => &mut info.total,
+                "MemFree" => &mut info.free,
+                "MemAvailable" => &mut info.available,
+                "SwapTotal" => &mut info.swap_total,
+                "SwapFree" => &mut info.swap_free,
+                _ => continue,
+            };
+
+            let kibibytes = value
+                .trim()
+                .trim_end_matches("kB")
+                .trim_end()
+                .parse::<u64>()
+                .unwrap_or_default();
+
+            *field = kibibytes * 1024;
+        }
+
+        info
+    }
+
+    /// Memory in use, excluding reclaimable caches.
+    pub const fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub const fn swap_used(&self) -> u64 {
+        self.swap_total.saturating_sub(self.swap_free)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "MemTotal:       16318696 kB
+MemFree:         1218804 kB
+MemAvailable:    9824308 kB
+Buffers:          412208 kB
+Cached:          8416124 kB
+SwapCached:         1024 kB
+SwapTotal:       8388604 kB
+SwapFree:        7340028 kB
+HugePages_Total:       0
+";
+
+    #[test]
+    fn test_parse() {
+        let info = MemInfo::parse(SAMPLE);
+
+        assert_eq!(info.total, 16_318_696 * 1024);
+        assert_eq!(info.free, 1_218_804 * 1024);
+        assert_eq!(info.available, 9_824_308 * 1024);
+        assert_eq!(info.swap_total, 8_388_604 * 1024);
+        assert_eq!(info.swap_free, 7_340_028 * 1024);
+    }
+
+    #[test]
+    fn test_used() {
+        let info = MemInfo::parse(SAMPLE);
+
+        assert_eq!(info.used(), (16_318_696 - 9_824_308) * 1024);
+        assert_eq!(info.swap_used(), (8_388_604 - 7_340_028) * 1024);
+    }
+
+    #[test]
+    fn test_no_swap() {
+        let info = MemInfo::parse(
+            "MemTotal: 16318696 kB\nMemAvailable: 9824308 kB\nSwapTotal: 0 kB\nSwapFree: 0 kB\n",
+        );
+
+        assert_eq!(info.swap_total, 0);
+        assert_eq!(info.swap_used(), 0);
+    }
+}
@@ -0,0 +1,673 @@
+mod gpu;
+mod graph;
+mod memory;
+mod system;
+
+use self::gpu::Gpu;
+use self::graph::{format_rate, scale_max, NetworkGraph, RingBuffer, Sample};
+use self::memory::MemInfo;
+use self::system::{read_uptime, LoadAverage, UptimeFormat};
+use crate::config::{CommonConfig, ModuleOrientation};
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::modules::thermal::c_to_f;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::schedule::Schedule;
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::{Button, DrawingArea, Label, Orientation};
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use sysinfo::{ComponentExt, CpuExt, DiskExt, NetworkExt, RefreshKind, System, SystemExt};
+use tokio::sync::{broadcast, mpsc};
+use tracing::error;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SysInfoModule {
+    /// List of formatting strings.
+    format: Vec<String>,
+    /// Number of seconds between refresh.
+    /// Defaults to the global `interval` rounded up to whole seconds, or 5 seconds.
+    interval: Option<Interval>,
+
+    /// Maximum time in milliseconds to randomly delay each refresh by.
+    /// Defaults to the global `jitter`.
+    jitter: Option<u64>,
+
+    #[serde(default)]
+    orientation: ModuleOrientation,
+
+    direction: Option<ModuleOrientation>,
+
+    /// How to format the `uptime` token.
+    #[serde(default)]
+    uptime_format: UptimeFormat,
+
+    /// GPU to read `gpu_` tokens from.
+    /// Detected automatically if not set.
+    gpu: Option<String>,
+
+    /// Shows a graph of recent network traffic
+    /// in a popup when the module is clicked.
+    network_graph: Option<NetworkGraphConfig>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkGraphConfig {
+    /// Adapter to graph.
+    /// Adds up all adapters except loopback if not set.
+    interface: Option<String>,
+
+    /// Number of refreshes shown in the graph.
+    #[serde(default = "default_graph_samples")]
+    samples: usize,
+
+    #[serde(default = "default_graph_width")]
+    width: i32,
+
+    #[serde(default = "default_graph_height")]
+    height: i32,
+}
+
+const fn default_graph_samples() -> usize {
+    60
+}
+
+const fn default_graph_width() -> i32 {
+    300
+}
+
+const fn default_graph_height() -> i32 {
+    100
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Copy, Clone)]
+pub struct Intervals {
+    #[serde(default = "default_interval")]
+    memory: u64,
+    #[serde(default = "default_interval")]
+    cpu: u64,
+    #[serde(default = "default_interval")]
+    temps: u64,
+    #[serde(default = "default_interval")]
+    disks: u64,
+    #[serde(default = "default_interval")]
+    networks: u64,
+    #[serde(default = "default_interval")]
+    system: u64,
+    #[serde(default = "default_interval")]
+    gpu: u64,
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Copy, Clone)]
+#[serde(untagged)]
+pub enum Interval {
+    All(u64),
+    Individual(Intervals),
+}
+
+impl Default for Interval {
+    fn default() -> Self {
+        Self::All(default_interval())
+    }
+}
+
+impl Interval {
+    const fn memory(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.memory,
+        }
+    }
+
+    const fn cpu(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.cpu,
+        }
+    }
+
+    const fn temps(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.temps,
+        }
+    }
+
+    const fn disks(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.disks,
+        }
+    }
+
+    const fn networks(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.networks,
+        }
+    }
+
+    const fn system(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.system,
+        }
+    }
+
+    const fn gpu(self) -> u64 {
+        match self {
+            Self::All(n) => n,
+            Self::Individual(intervals) => intervals.gpu,
+        }
+    }
+}
+
+const fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone)]
+pub enum SysInfoUpdate {
+    Tokens(HashMap<String, String>),
+    /// Recent network traffic, for the graph.
+    Network(RingBuffer<Sample>),
+}
+
+#[derive(Debug)]
+enum RefreshType {
+    Memory,
+    Cpu,
+    Temps,
+    Disks,
+    Network,
+    System,
+    Gpu,
+}
+
+impl Module<gtk::Box> for SysInfoModule {
+    type SendMessage = SysInfoUpdate;
+    type ReceiveMessage = ();
+
+    module_impl!("sysinfo");
+
+    fn supersedes(next: &Self::SendMessage, previous: &Self::SendMessage) -> bool {
+        std::mem::discriminant(next) == std::mem::discriminant(previous)
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let (interval, jitter) = {
+            let config = context.ironbar.config.borrow();
+
+            // the global interval is in milliseconds,
+            // but rates are calculated per whole second
+            let interval = self.interval.unwrap_or_else(|| {
+                config.interval.map_or_else(Interval::default, |millis| {
+                    Interval::All(millis.div_ceil(1000).max(1))
+                })
+            });
+            let jitter = self.jitter.or(config.jitter).unwrap_or_default();
+
+            (interval, Duration::from_millis(jitter))
+        };
+        let uptime_format = self.uptime_format;
+
+        let refresh_kind = RefreshKind::everything()
+            .without_processes()
+            .without_users_list()
+            .without_memory();
+
+        let mut sys = System::new_with_specifics(refresh_kind);
+        sys.refresh_components_list();
+        sys.refresh_disks_list();
+        sys.refresh_networks_list();
+
+        let (refresh_tx, mut refresh_rx) = mpsc::channel(16);
+
+        macro_rules! spawn_refresh {
+            ($refresh_type:expr, $func:ident) => {{
+                let tx = refresh_tx.clone();
+                spawn(async move {
+                    // each refresh type is jittered separately,
+                    // so they do not all run together
+                    let mut schedule = Schedule::new(Duration::from_secs(interval.$func()), jitter);
+
+                    loop {
+                        send_async!(tx, $refresh_type);
+                        schedule.tick().await;
+                    }
+                });
+            }};
+        }
+
+        spawn_refresh!(RefreshType::Memory, memory);
+        spawn_refresh!(RefreshType::Cpu, cpu);
+        spawn_refresh!(RefreshType::Temps, temps);
+        spawn_refresh!(RefreshType::Disks, disks);
+        spawn_refresh!(RefreshType::Network, networks);
+        spawn_refresh!(RefreshType::System, system);
+
+        // only look for a gpu if it is used,
+        // to avoid warning systems without one
+        let use_gpu = self.format.iter().any(|format| format.contains("{gpu_"));
+        if use_gpu {
+            spawn_refresh!(RefreshType::Gpu, gpu);
+        }
+
+        let gpu_device = self.gpu.clone();
+
+        let graph = self
+            .network_graph
+            .as_ref()
+            .map(|graph| (graph.interface.clone(), RingBuffer::new(graph.samples)));
+
+        let tx = context.tx.clone();
+        spawn(async move {
+            let mut format_info = HashMap::new();
+            let mut graph = graph;
+
+            // detection loads NVML and reads sysfs, so is kept off the GTK thread
+            let gpu = if use_gpu {
+                Gpu::detect(gpu_device.as_deref())
+            } else {
+                None
+            };
+
+            while let Some(refresh) = refresh_rx.recv().await {
+                match refresh {
+                    RefreshType::Memory => refresh_memory_tokens(&mut format_info),
+                    RefreshType::Cpu => refresh_cpu_tokens(&mut format_info, &mut sys),
+                    RefreshType::Temps => refresh_temp_tokens(&mut format_info, &mut sys),
+                    RefreshType::Disks => refresh_disk_tokens(&mut format_info, &mut sys),
+                    RefreshType::Network => {
+                        refresh_network_tokens(&mut format_info, &mut sys, interval.networks());
+
+                        if let Some((interface, samples)) = &mut graph {
+                            samples.push(network_sample(
+                                &sys,
+                                interface.as_deref(),
+                                interval.networks(),
+                            ));
+
+                            send_async!(
+                                tx,
+                                ModuleUpdateEvent::Update(SysInfoUpdate::Network(samples.clone()))
+                            );
+                        }
+                    }
+                    RefreshType::System => refresh_system_tokens(&mut format_info, uptime_format),
+                    RefreshType::Gpu => refresh_gpu_tokens(&mut format_info, gpu.as_ref()),
+                };
+
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(SysInfoUpdate::Tokens(format_info.clone()))
+                );
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<gtk::Box>> {
+        let re = Regex::new(r"\{([^}]+)}")?;
+
+        let layout = match self.direction {
+            Some(orientation) => orientation,
+            None => self.orientation,
+        };
+
+        let container = gtk::Box::new(layout.into(), 10);
+
+        // labels are wrapped in a button to open the graph,
+        // so the layout is unchanged without one
+        let (labels_box, button) = if self.network_graph.is_some() {
+            let labels_box = gtk::Box::new(layout.into(), 10);
+            let button = Button::new();
+            button.add(&labels_box);
+            container.add(&button);
+
+            let tx = context.tx.clone();
+            button.connect_clicked(move |button| {
+                try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+            });
+
+            (labels_box, Some(button))
+        } else {
+            (container.clone(), None)
+        };
+
+        let mut labels = Vec::new();
+
+        for format in &self.format {
+            let label = Label::builder().label(format).use_markup(true).build();
+
+            label.add_class("item");
+            label.set_angle(self.orientation.to_angle());
+
+            labels_box.add(&label);
+            labels.push(label);
+        }
+
+        {
+            let formats = self.format.clone();
+            let rx = context.subscribe();
+            glib_recv!(rx, update => {
+                let SysInfoUpdate::Tokens(info) = update else {
+                    continue;
+                };
+
+                for (format, label) in formats.iter().zip(labels.clone()) {
+                    let format_compiled = re.replace_all(format, |caps: &Captures| {
+                        info.get(&caps[1])
+                            .unwrap_or(&caps[0].to_string())
+                            .to_string()
+                    });
+
+                    label.set_markup(format_compiled.as_ref());
+                }
+            });
+        }
+
+        let popup = button.and_then(|button| {
+            self.into_popup(
+                context.controller_tx.clone(),
+                context.subscribe(),
+                context,
+                info,
+            )
+            .into_popup_parts(vec![&button])
+        });
+
+        Ok(ModuleParts::new(container, popup))
+    }
+
+    fn into_popup(
+        self,
+        _tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box> {
+        let config = self.network_graph?;
+
+        let container = gtk::Box::new(Orientation::Vertical, 5);
+
+        let area = DrawingArea::new();
+        area.set_size_request(config.width, config.height);
+        let graph = NetworkGraph::new(area, config.samples);
+        container.add(&graph.area);
+
+        let rates = gtk::Box::new(Orientation::Horizontal, 10);
+        let down = Label::new(None);
+        down.add_class("down");
+        let up = Label::new(None);
+        up.add_class("up");
+        let max = Label::new(None);
+        max.add_class("max");
+        max.set_hexpand(true);
+        max.set_halign(gtk::Align::End);
+
+        rates.add(&down);
+        rates.add(&up);
+        rates.add(&max);
+        container.add(&rates);
+
+        glib_recv!(rx, update => {
+            let SysInfoUpdate::Network(samples) = update else {
+                continue;
+            };
+
+            let latest = samples.iter().last().copied().unwrap_or_default();
+            down.set_label(&format!("↓ {}", format_rate(latest.down)));
+            up.set_label(&format!("↑ {}", format_rate(latest.up)));
+            max.set_label(&format_rate(scale_max(&samples)));
+
+            graph.set_samples(samples);
+        });
+
+        Some(container)
+    }
+}
+
+fn refresh_memory_tokens(format_info: &mut HashMap<String, String>) {
+    let info = match MemInfo::read() {
+        Ok(info) => info,
+        Err(err) => {
+            error!("Failed to read memory info: {err:?}");
+            return;
+        }
+    };
+
+    let mut insert_gigabytes = |key: &str, bytes: u64| {
+        format_info.insert(String::from(key), bytes_to_gigabytes(bytes).to_string());
+    };
+
+    insert_gigabytes("mem_used", info.used());
+    insert_gigabytes("mem_free", info.free);
+    insert_gigabytes("mem_available", info.available);
+    insert_gigabytes("mem_total", info.total);
+
+    // `memory_free` has always been the available memory
+    insert_gigabytes("memory_used", info.used());
+    insert_gigabytes("memory_free", info.available);
+    insert_gigabytes("memory_total", info.total);
+
+    insert_gigabytes("swap_used", info.swap_used());
+    insert_gigabytes("swap_free", info.swap_free);
+    insert_gigabytes("swap_total", info.swap_total);
+
+    let memory_percent = format!("{:0>2.0}", percent(info.used(), info.total));
+    format_info.insert(String::from("mem_percent"), memory_percent.clone());
+    format_info.insert(String::from("memory_percent"), memory_percent);
+
+    format_info.insert(
+        String::from("swap_percent"),
+        format!("{:0>2.0}", percent(info.swap_used(), info.swap_total)),
+    );
+}
+
+fn refresh_cpu_tokens(format_info: &mut HashMap<String, String>, sys: &mut System) {
+    sys.refresh_cpu();
+
+    let cpu_info = sys.global_cpu_info();
+    let cpu_percent = cpu_info.cpu_usage();
+
+    format_info.insert(String::from("cpu_percent"), format!("{cpu_percent:0>2.0}"));
+}
+
+fn refresh_temp_tokens(format_info: &mut HashMap<String, String>, sys: &mut System) {
+    sys.refresh_components();
+
+    let components = sys.components();
+    for component in components {
+        let key = component.label().replace(' ', "-");
+        let temp = component.temperature();
+
+        format_info.insert(format!("temp_c:{key}"), format!("{temp:.0}"));
+        format_info.insert(
+            format!("temp_f:{key}"),
+            format!("{:.0}", c_to_f(f64::from(temp))),
+        );
+    }
+}
+
+fn refresh_disk_tokens(format_info: &mut HashMap<String, String>, sys: &mut System) {
+    sys.refresh_disks();
+
+    for disk in sys.disks() {
+        // replace braces to avoid conflict with regex
+        let key = disk
+            .mount_point()
+            .to_str()
+            .map(|s| s.replace(['{', '}'], ""));
+
+        if let Some(key) = key {
+            let total = disk.total_space();
+            let available = disk.available_space();
+            let used = total - available;
+
+            format_info.insert(
+                format!("disk_free:{key}"),
+                bytes_to_gigabytes(available).to_string(),
+            );
+
+            format_info.insert(
+                format!("disk_used:{key}"),
+                bytes_to_gigabytes(used).to_string(),
+            );
+
+            format_info.insert(
+                format!("disk_total:{key}"),
+                bytes_to_gigabytes(total).to_string(),
+            );
+
+            format_info.insert(
+                format!("disk_percent:{key}"),
+                format!("{:0>2.0}", used as f64 / total as f64 * 100.0),
+            );
+        }
+    }
+}
+
+fn refresh_network_tokens(
+    format_info: &mut HashMap<String, String>,
+    sys: &mut System,
+    interval: u64,
+) {
+    sys.refresh_networks();
+
+    for (iface, network) in sys.networks() {
+        format_info.insert(
+            format!("net_down:{iface}"),
+            format!("{:0>2.0}", bytes_to_megabits(network.received()) / interval),
+        );
+
+        format_info.insert(
+            format!("net_up:{iface}"),
+            format!(
+                "{:0>2.0}",
+                bytes_to_megabits(network.transmitted()) / interval
+            ),
+        );
+    }
+}
+
+/// Gets the current traffic for the graph,
+/// from the networks refreshed by [`refresh_network_tokens`].
+fn network_sample(sys: &System, interface: Option<&str>, interval: u64) -> Sample {
+    sys.networks()
+        .into_iter()
+        .filter(|(iface, _)| {
+            interface.map_or(iface.as_str() != "lo", |name| iface.as_str() == name)
+        })
+        .fold(Sample::default(), |sample, (_, network)| Sample {
+            down: sample.down + bytes_to_bits_per_second(network.received(), interval),
+            up: sample.up + bytes_to_bits_per_second(network.transmitted(), interval),
+        })
+}
+
+fn refresh_system_tokens(format_info: &mut HashMap<String, String>, uptime_format: UptimeFormat) {
+    match LoadAverage::read() {
+        Ok(load_average) => {
+            for (period, value) in [
+                ("1", load_average.one),
+                ("5", load_average.five),
+                ("15", load_average.fifteen),
+            ] {
+                let value = format!("{value:.2}");
+                format_info.insert(format!("load{period}"), value.clone());
+                format_info.insert(format!("load_average:{period}"), value);
+            }
+        }
+        Err(err) => error!("Failed to read load average: {err:?}"),
+    }
+
+    match read_uptime() {
+        Ok(uptime) => {
+            format_info.insert(String::from("uptime"), uptime_format.format(uptime));
+        }
+        Err(err) => error!("Failed to read uptime: {err:?}"),
+    }
+}
+
+fn refresh_gpu_tokens(format_info: &mut HashMap<String, String>, gpu: Option<&Gpu>) {
+    let stats = gpu.map(Gpu::stats).unwrap_or_default();
+
+    // unsupported values are left empty
+    let mut insert = |key: &str, value: Option<String>| {
+        format_info.insert(String::from(key), value.unwrap_or_default());
+    };
+
+    insert(
+        "gpu_usage",
+        stats.usage.map(|usage| format!("{usage:0>2.0}")),
+    );
+    insert(
+        "gpu_vram_used",
+        stats
+            .vram_used
+            .map(|used| bytes_to_gigabytes(used).to_string()),
+    );
+    insert(
+        "gpu_vram_total",
+        stats
+            .vram_total
+            .map(|total| bytes_to_gigabytes(total).to_string()),
+    );
+    insert(
+        "gpu_vram_percent",
+        stats
+            .vram_used
+            .zip(stats.vram_total)
+            .map(|(used, total)| format!("{:0>2.0}", used as f64 / total as f64 * 100.0)),
+    );
+    insert("gpu_temp", stats.temp.map(|temp| format!("{temp:.0}")));
+    insert(
+        "gpu_temp_f",
+        stats.temp.map(|temp| format!("{:.0}", c_to_f(temp))),
+    );
+}
+
+/// Gets `used` as a percentage of `total`,
+/// or `0` if `total` is `0`.
+fn percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        used as f64 / total as f64 * 100.0
+    }
+}
+
+const fn bytes_to_gigabytes(b: u64) -> u64 {
+    const BYTES_IN_GIGABYTE: u64 = 1_000_000_000;
+    b / BYTES_IN_GIGABYTE
+}
+
+fn bytes_to_bits_per_second(b: u64, interval: u64) -> f64 {
+    (b * 8) as f64 / interval.max(1) as f64
+}
+
+const fn bytes_to_megabits(b: u64) -> u64 {
+    const BYTES_IN_MEGABIT: u64 = 125_000;
+    b / BYTES_IN_MEGABIT
+}
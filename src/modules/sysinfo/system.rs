@@ -0,0 +1,167 @@
+use std::fs;
+
+use color_eyre::{Report, Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+const UPTIME_PATH: &str = "/proc/uptime";
+const LOADAVG_PATH: &str = "/proc/loadavg";
+
+const SECONDS_IN_MINUTE: u64 = 60;
+const SECONDS_IN_HOUR: u64 = SECONDS_IN_MINUTE * 60;
+const SECONDS_IN_DAY: u64 = SECONDS_IN_HOUR * 24;
+
+#[derive(Debug, Default, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UptimeFormat {
+    /// The two largest non-zero units, such as `3d 4h`.
+    Compact,
+    /// The two largest non-zero units spelled out, such as `3 days, 4 hours`.
+    Verbose,
+    /// Hours and minutes, such as `76:12`.
+    #[default]
+    Clock,
+}
+
+impl UptimeFormat {
+    pub fn format(self, seconds: u64) -> String {
+        if self == Self::Clock {
+            let hours = seconds / SECONDS_IN_HOUR;
+            let minutes = (seconds % SECONDS_IN_HOUR) / SECONDS_IN_MINUTE;
+            return format!("{hours:0>2}:{minutes:0>2}");
+        }
+
+        let units = [
+            (seconds / SECONDS_IN_DAY, "d", "day"),
+            ((seconds % SECONDS_IN_DAY) / SECONDS_IN_HOUR, "h", "hour"),
+            (
+                (seconds % SECONDS_IN_HOUR) / SECONDS_IN_MINUTE,
+                "m",
+                "minute",
+            ),
+        ];
+
+        // skip leading zero units,
+        // but always show minutes
+        let first = units
+            .iter()
+            .position(|(value, _, _)| *value > 0)
+            .unwrap_or(units.len() - 1);
+
+        let parts = units[first..].iter().take(2).map(|&(value, short, long)| {
+            if self == Self::Compact {
+                format!("{value}{short}")
+            } else if value == 1 {
+                format!("{value} {long}")
+            } else {
+                format!("{value} {long}s")
+            }
+        });
+
+        let separator = if self == Self::Compact { " " } else { ", " };
+        parts.collect::<Vec<_>>().join(separator)
+    }
+}
+
+/// Reads the number of seconds since boot.
+pub fn read_uptime() -> Result<u64> {
+    let contents = fs::read_to_string(UPTIME_PATH)?;
+    parse_uptime(&contents)
+        .ok_or_else(|| Report::msg(format!("Invalid uptime: '{}'", contents.trim())))
+}
+
+/// Parses the contents of `/proc/uptime`,
+/// which contains the uptime and the idle time in seconds.
+fn parse_uptime(contents: &str) -> Option<u64> {
+    let uptime = contents.split_whitespace().next()?.parse::<f64>().ok()?;
+    Some(uptime as u64)
+}
+
+/// The 1, 5 and 15 minute load averages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+impl LoadAverage {
+    pub fn read() -> Result<Self> {
+        let contents = fs::read_to_string(LOADAVG_PATH)?;
+        Self::parse(&contents)
+            .ok_or_else(|| Report::msg(format!("Invalid load average: '{}'", contents.trim())))
+    }
+
+    /// Parses the contents of `/proc/loadavg`,
+    /// which starts with the three load averages.
+    fn parse(contents: &str) -> Option<Self> {
+        let mut values = contents.split_whitespace().map(str::parse::<f64>);
+
+        Some(Self {
+            one: values.next()?.ok()?,
+            five: values.next()?.ok()?,
+            fifteen: values.next()?.ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn seconds(days: u64, hours: u64, minutes: u64) -> u64 {
+        days * SECONDS_IN_DAY + hours * SECONDS_IN_HOUR + minutes * SECONDS_IN_MINUTE
+    }
+
+    #[test]
+    fn test_format_compact() {
+        let format = |seconds| UptimeFormat::Compact.format(seconds);
+
+        assert_eq!(format(0), "0m");
+        assert_eq!(format(59), "0m");
+        assert_eq!(format(seconds(0, 0, 12)), "12m");
+        assert_eq!(format(seconds(0, 4, 12)), "4h 12m");
+        assert_eq!(format(seconds(0, 4, 0)), "4h 0m");
+        assert_eq!(format(seconds(3, 4, 12)), "3d 4h");
+        assert_eq!(format(seconds(3, 0, 12)), "3d 0h");
+        assert_eq!(format(seconds(400, 23, 59)), "400d 23h");
+    }
+
+    #[test]
+    fn test_format_verbose() {
+        let format = |seconds| UptimeFormat::Verbose.format(seconds);
+
+        assert_eq!(format(0), "0 minutes");
+        assert_eq!(format(seconds(0, 0, 1)), "1 minute");
+        assert_eq!(format(seconds(0, 1, 30)), "1 hour, 30 minutes");
+        assert_eq!(format(seconds(1, 1, 1)), "1 day, 1 hour");
+        assert_eq!(format(seconds(3, 4, 12)), "3 days, 4 hours");
+    }
+
+    #[test]
+    fn test_format_clock() {
+        let format = |seconds| UptimeFormat::Clock.format(seconds);
+
+        assert_eq!(format(0), "00:00");
+        assert_eq!(format(seconds(0, 4, 2)), "04:02");
+        assert_eq!(format(seconds(3, 4, 12)), "76:12");
+    }
+
+    #[test]
+    fn test_parse_uptime() {
+        assert_eq!(parse_uptime("273945.42 2019284.73\n"), Some(273_945));
+        assert_eq!(parse_uptime(""), None);
+    }
+
+    #[test]
+    fn test_parse_loadavg() {
+        let load = LoadAverage::parse("0.52 0.58 0.59 1/1032 48213\n").unwrap();
+
+        assert_eq!(load.one, 0.52);
+        assert_eq!(load.five, 0.58);
+        assert_eq!(load.fifteen, 0.59);
+
+        assert_eq!(LoadAverage::parse("0.52 0.58"), None);
+        assert_eq!(LoadAverage::parse("0.52 high 0.59"), None);
+    }
+}
@@ -0,0 +1,445 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::{Button, IconTheme, Label};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::clients::wayland::{self, ToplevelEvent, ToplevelInfo};
+use crate::config::{CommonConfig, TruncateMode};
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::image::ImageProvider;
+use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TaskbarModule {
+    /// Whether to show each window's icon.
+    #[serde(default = "crate::config::default_true")]
+    show_icon: bool,
+    /// Whether to show each window's title.
+    #[serde(default = "crate::config::default_true")]
+    show_title: bool,
+
+    /// Icon size in pixels.
+    #[serde(default = "default_icon_size")]
+    icon_size: i32,
+
+    /// Whether to show a single button for all windows of the same app.
+    #[serde(default)]
+    group_by_app: bool,
+
+    truncate: Option<TruncateMode>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+const fn default_icon_size() -> i32 {
+    24
+}
+
+/// The open windows, in the order they were opened.
+#[derive(Debug, Default)]
+struct WindowList(Vec<ToplevelInfo>);
+
+impl WindowList {
+    /// Updates the list from a toplevel event.
+    fn apply(&mut self, event: ToplevelEvent) {
+        match event {
+            ToplevelEvent::New(info) | ToplevelEvent::Update(info) => {
+                // only one window can have focus
+                if info.focused {
+                    for window in &mut self.0 {
+                        window.focused = false;
+                    }
+                }
+
+                match self.0.iter_mut().find(|window| window.id == info.id) {
+                    Some(window) => *window = info,
+                    None => self.0.push(info),
+                }
+            }
+            ToplevelEvent::Remove(info) => self.0.retain(|window| window.id != info.id),
+        }
+    }
+}
+
+/// Identifies a button on the taskbar.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum EntryKey {
+    Window(usize),
+    App(String),
+}
+
+/// A button on the taskbar,
+/// representing a single window or a group of windows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    key: EntryKey,
+    app_id: String,
+    title: String,
+    /// IDs of the windows, in the order they were opened.
+    windows: Vec<usize>,
+    /// ID of the window with focus, if it is in this entry.
+    focused: Option<usize>,
+}
+
+impl Entry {
+    /// Gets the window to focus when the entry is clicked.
+    ///
+    /// This cycles through grouped windows,
+    /// starting from the first if none of them have focus.
+    fn target(&self) -> Option<usize> {
+        let next = self
+            .focused
+            .and_then(|focused| self.windows.iter().position(|&id| id == focused))
+            .map_or(0, |index| (index + 1) % self.windows.len());
+
+        self.windows.get(next).copied()
+    }
+}
+
+/// Gets the taskbar buttons to show for the windows.
+///
+/// Groups are placed where the first window of the app opened,
+/// and are titled after their focused window if they have one.
+fn entries(windows: &[ToplevelInfo], group_by_app: bool) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = vec![];
+
+    for window in windows {
+        let key = if group_by_app {
+            EntryKey::App(window.app_id.clone())
+        } else {
+            EntryKey::Window(window.id)
+        };
+
+        let focused = window.focused.then_some(window.id);
+
+        match entries.iter_mut().find(|entry| entry.key == key) {
+            Some(entry) => {
+                entry.windows.push(window.id);
+
+                if focused.is_some() {
+                    entry.focused = focused;
+                    entry.title.clone_from(&window.title);
+                }
+            }
+            None => entries.push(Entry {
+                key,
+                app_id: window.app_id.clone(),
+                title: window.title.clone(),
+                windows: vec![window.id],
+                focused,
+            }),
+        }
+    }
+
+    entries
+}
+
+/// The widgets making up a taskbar button.
+struct EntryButton {
+    button: Button,
+    label: Label,
+    count: Label,
+    entry: Rc<RefCell<Entry>>,
+}
+
+impl EntryButton {
+    fn new(
+        entry: Entry,
+        module: &TaskbarModule,
+        icon_theme: &IconTheme,
+        scale: i32,
+        tx: &mpsc::Sender<usize>,
+    ) -> Self {
+        let button = Button::new();
+        button.add_class("item");
+
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        button.add(&container);
+
+        if module.show_icon {
+            let icon = gtk::Image::new();
+            icon.add_class("icon");
+            container.add(&icon);
+
+            ImageProvider::parse(&entry.app_id, icon_theme, true, module.icon_size)
+                .map(|image| image.with_scale(scale).load_into_image(icon));
+        }
+
+        let label = Label::new(None);
+        label.add_class("label");
+        if let Some(truncate) = module.truncate {
+            truncate.truncate_label(&label);
+        }
+        if module.show_title {
+            container.add(&label);
+        }
+
+        let count = Label::new(None);
+        count.add_class("count");
+        container.add(&count);
+
+        let entry = Rc::new(RefCell::new(entry));
+
+        {
+            let entry = entry.clone();
+            let tx = tx.clone();
+            button.connect_clicked(move |_| {
+                if let Some(id) = entry.borrow().target() {
+                    try_send!(tx, id);
+                }
+            });
+        }
+
+        button.show_all();
+
+        let button = Self {
+            button,
+            label,
+            count,
+            entry,
+        };
+
+        button.update_widgets();
+        button
+    }
+
+    fn update(&self, entry: Entry) {
+        *self.entry.borrow_mut() = entry;
+        self.update_widgets();
+    }
+
+    fn update_widgets(&self) {
+        let entry = self.entry.borrow();
+
+        self.label.set_label(&entry.title);
+        self.button.set_tooltip_text(Some(&entry.title));
+
+        self.count.set_label(&entry.windows.len().to_string());
+        self.count.set_visible(entry.windows.len() > 1);
+
+        if entry.focused.is_some() {
+            self.button.add_class("focused");
+        } else {
+            self.button.style_context().remove_class("focused");
+        }
+    }
+}
+
+impl Module<gtk::Box> for TaskbarModule {
+    type SendMessage = Vec<ToplevelInfo>;
+    /// ID of the window to focus.
+    type ReceiveMessage = usize;
+
+    module_impl!("taskbar");
+
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let tx = context.tx.clone();
+        let wl = context.client::<wayland::Client>();
+
+        {
+            let wl = wl.clone();
+            spawn(async move {
+                let mut windows = WindowList::default();
+
+                let mut wlrx = wl.subscribe_toplevels();
+                for info in wl.toplevel_info_all() {
+                    windows.apply(ToplevelEvent::New(info));
+                }
+
+                send_async!(tx, ModuleUpdateEvent::Update(windows.0.clone()));
+
+                while let Ok(event) = wlrx.recv().await {
+                    windows.apply(event);
+                    send_async!(tx, ModuleUpdateEvent::Update(windows.0.clone()));
+                }
+            });
+        }
+
+        spawn(async move {
+            while let Some(id) = rx.recv().await {
+                debug!("Focusing window {id}");
+                wl.toplevel_focus(id);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<gtk::Box>> {
+        let container = gtk::Box::new(info.bar_position.orientation(), 0);
+
+        {
+            let container = container.clone();
+            let icon_theme = info.icon_theme.clone();
+            let scale = info.scale_factor();
+            let tx = context.controller_tx.clone();
+
+            let mut buttons = HashMap::<EntryKey, EntryButton>::new();
+
+            glib_recv!(context.subscribe(), windows => {
+                let entries = entries(&windows, self.group_by_app);
+
+                buttons.retain(|key, button| {
+                    let open = entries.iter().any(|entry| &entry.key == key);
+                    if !open {
+                        container.remove(&button.button);
+                    }
+                    open
+                });
+
+                for (index, entry) in entries.into_iter().enumerate() {
+                    let key = entry.key.clone();
+
+                    match buttons.get(&key) {
+                        Some(button) => button.update(entry),
+                        None => {
+                            let button = EntryButton::new(entry, &self, &icon_theme, scale, &tx);
+                            container.add(&button.button);
+                            buttons.insert(key.clone(), button);
+                        }
+                    }
+
+                    container.reorder_child(&buttons[&key].button, index as i32);
+                }
+            });
+        }
+
+        Ok(ModuleParts {
+            widget: container,
+            popup: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(id: usize, app_id: &str, focused: bool) -> ToplevelInfo {
+        ToplevelInfo {
+            id,
+            app_id: app_id.to_string(),
+            title: format!("{app_id} {id}"),
+            fullscreen: false,
+            focused,
+        }
+    }
+
+    fn ids(list: &WindowList) -> Vec<usize> {
+        list.0.iter().map(|window| window.id).collect()
+    }
+
+    #[test]
+    fn test_window_opened_and_closed() {
+        let mut list = WindowList::default();
+
+        list.apply(ToplevelEvent::New(window(1, "firefox", false)));
+        list.apply(ToplevelEvent::New(window(2, "kitty", false)));
+        list.apply(ToplevelEvent::New(window(3, "kitty", false)));
+        assert_eq!(ids(&list), [1, 2, 3]);
+
+        list.apply(ToplevelEvent::Remove(window(2, "kitty", false)));
+        assert_eq!(ids(&list), [1, 3]);
+
+        // removing an unknown window does nothing
+        list.apply(ToplevelEvent::Remove(window(9, "kitty", false)));
+        assert_eq!(ids(&list), [1, 3]);
+    }
+
+    #[test]
+    fn test_window_updated_in_place() {
+        let mut list = WindowList::default();
+
+        list.apply(ToplevelEvent::New(window(1, "firefox", false)));
+        list.apply(ToplevelEvent::New(window(2, "kitty", false)));
+
+        let mut renamed = window(1, "firefox", false);
+        renamed.title = "New tab".to_string();
+        list.apply(ToplevelEvent::Update(renamed));
+
+        assert_eq!(ids(&list), [1, 2]);
+        assert_eq!(list.0[0].title, "New tab");
+    }
+
+    #[test]
+    fn test_focus_moves_between_windows() {
+        let mut list = WindowList::default();
+
+        list.apply(ToplevelEvent::New(window(1, "firefox", true)));
+        list.apply(ToplevelEvent::New(window(2, "kitty", false)));
+        list.apply(ToplevelEvent::Update(window(2, "kitty", true)));
+
+        let focused: Vec<_> = list.0.iter().map(|window| window.focused).collect();
+        assert_eq!(focused, [false, true]);
+    }
+
+    #[test]
+    fn test_group_by_app() {
+        let windows = [
+            window(1, "kitty", false),
+            window(2, "firefox", false),
+            window(3, "kitty", true),
+        ];
+
+        let ungrouped = entries(&windows, false);
+        assert_eq!(ungrouped.len(), 3);
+        assert_eq!(ungrouped[2].key, EntryKey::Window(3));
+
+        let grouped = entries(&windows, true);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].key, EntryKey::App("kitty".to_string()));
+        assert_eq!(grouped[0].windows, [1, 3]);
+        assert_eq!(grouped[0].focused, Some(3));
+        assert_eq!(grouped[0].title, "kitty 3");
+        assert_eq!(grouped[1].focused, None);
+    }
+
+    #[test]
+    fn test_click_cycles_group() {
+        let windows = [
+            window(1, "kitty", false),
+            window(2, "kitty", false),
+            window(3, "kitty", false),
+        ];
+
+        let entry = &entries(&windows, true)[0];
+        assert_eq!(entry.target(), Some(1));
+
+        let windows = [
+            window(1, "kitty", false),
+            window(2, "kitty", false),
+            window(3, "kitty", true),
+        ];
+
+        let entry = &entries(&windows, true)[0];
+        assert_eq!(entry.target(), Some(1));
+
+        let windows = [window(1, "kitty", true), window(2, "kitty", false)];
+
+        let entry = &entries(&windows, true)[0];
+        assert_eq!(entry.target(), Some(2));
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::{lock, Ironbar};
+
+tokio::task_local! {
+    /// Scope of the task currently being polled,
+    /// so that tasks it spawns join the same scope.
+    static TASK_SCOPE: Option<TaskScope>;
+}
+
+thread_local! {
+    /// Scope entered on the current thread,
+    /// used while a module is being created on the GTK thread.
+    static CURRENT_SCOPE: RefCell<Option<TaskScope>> = const { RefCell::new(None) };
+}
+
+/// Tracks the Tokio tasks spawned for a single module,
+/// so that they can all be cancelled when it is torn down.
+///
+/// Tasks spawned from inside a tracked task are also tracked.
+#[derive(Debug, Clone, Default)]
+pub struct TaskScope {
+    handles: Arc<Mutex<Vec<AbortHandle>>>,
+    /// Scopes of modules created inside this one,
+    /// such as those inside a custom module.
+    children: Arc<Mutex<Vec<TaskScope>>>,
+}
+
+impl TaskScope {
+    /// Creates a new scope inside the current one, if there is one,
+    /// so that it is cancelled along with it.
+    pub fn new() -> Self {
+        let scope = Self::default();
+
+        if let Some(parent) = Self::current() {
+            lock!(parent.children).push(scope.clone());
+        }
+
+        scope
+    }
+
+    /// Gets the scope of the current task or thread, if there is one.
+    pub fn current() -> Option<Self> {
+        TASK_SCOPE
+            .try_with(Clone::clone)
+            .ok()
+            .flatten()
+            .or_else(|| CURRENT_SCOPE.with_borrow(Clone::clone))
+    }
+
+    /// Runs `f` outside of any scope,
+    /// so that tasks it spawns outlive the current module.
+    ///
+    /// This should be used for anything shared between modules, such as clients.
+    pub fn detached<R>(f: impl FnOnce() -> R) -> R {
+        let previous = CURRENT_SCOPE.take();
+        let res = TASK_SCOPE.sync_scope(None, f);
+        CURRENT_SCOPE.set(previous);
+        res
+    }
+
+    /// Enters this scope on the current thread until the guard is dropped,
+    /// so that any tasks spawned in the meantime are tracked.
+    pub fn enter(&self) -> EnteredScope {
+        EnteredScope {
+            previous: CURRENT_SCOPE.replace(Some(self.clone())),
+        }
+    }
+
+    /// Spawns a task on the Tokio runtime inside this scope.
+    pub fn spawn<F>(&self, f: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let handle = Ironbar::runtime().spawn(TASK_SCOPE.scope(Some(self.clone()), f));
+
+        let mut handles = lock!(self.handles);
+        handles.retain(|handle| !handle.is_finished());
+        handles.push(handle.abort_handle());
+
+        handle
+    }
+
+    /// Aborts every task spawned inside this scope,
+    /// and inside any scopes created within it.
+    ///
+    /// Tasks stop at their next `.await`.
+    pub fn cancel(&self) {
+        for handle in lock!(self.handles).drain(..) {
+            handle.abort();
+        }
+
+        for child in lock!(self.children).drain(..) {
+            child.cancel();
+        }
+    }
+
+    /// Gets the number of tasks which are still running.
+    pub fn running(&self) -> usize {
+        lock!(self.handles)
+            .iter()
+            .filter(|handle| !handle.is_finished())
+            .count()
+    }
+}
+
+/// Guard which leaves a [`TaskScope`] when dropped,
+/// going back to whichever scope was entered before.
+#[must_use]
+#[derive(Debug)]
+pub struct EnteredScope {
+    previous: Option<TaskScope>,
+}
+
+impl Drop for EnteredScope {
+    fn drop(&mut self) {
+        CURRENT_SCOPE.set(self.previous.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawn;
+    use std::future::pending;
+    use std::time::Duration;
+
+    #[test]
+    fn test_spawn_outside_scope_untracked() {
+        let scope = TaskScope::default();
+        let handle = spawn(pending::<()>());
+
+        assert_eq!(scope.running(), 0);
+        handle.abort();
+    }
+
+    #[test]
+    fn test_cancel_aborts_tasks() {
+        let scope = TaskScope::default();
+        let handle = {
+            let _entered = scope.enter();
+            spawn(pending::<()>())
+        };
+
+        assert_eq!(scope.running(), 1);
+        scope.cancel();
+
+        let res = Ironbar::runtime().block_on(handle);
+        assert!(res.is_err_and(|err| err.is_cancelled()));
+    }
+
+    #[test]
+    fn test_nested_tasks_tracked() {
+        let scope = TaskScope::default();
+
+        let outer = {
+            let _entered = scope.enter();
+            spawn(async {
+                let inner = spawn(pending::<()>());
+                pending::<()>().await;
+                inner.await.ok();
+            })
+        };
+
+        // give the outer task time to spawn the inner one
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(scope.running(), 2);
+
+        scope.cancel();
+        let res = Ironbar::runtime().block_on(outer);
+        assert!(res.is_err_and(|err| err.is_cancelled()));
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(scope.running(), 0);
+    }
+
+    #[test]
+    fn test_cancel_child_scopes() {
+        let parent = TaskScope::new();
+
+        let (child, handle) = {
+            let _parent = parent.enter();
+            let child = TaskScope::new();
+
+            let _child = child.enter();
+            (child.clone(), spawn(pending::<()>()))
+        };
+
+        assert_eq!(parent.running(), 0);
+        assert_eq!(child.running(), 1);
+
+        parent.cancel();
+
+        let res = Ironbar::runtime().block_on(handle);
+        assert!(res.is_err_and(|err| err.is_cancelled()));
+    }
+
+    #[test]
+    fn test_detached_untracked() {
+        let scope = TaskScope::default();
+
+        let _entered = scope.enter();
+
+        let handle = TaskScope::detached(|| spawn(pending::<()>()));
+        assert_eq!(scope.running(), 0);
+
+        // the scope is entered again afterwards
+        let tracked = spawn(pending::<()>());
+        assert_eq!(scope.running(), 1);
+
+        handle.abort();
+        tracked.abort();
+    }
+
+    #[test]
+    fn test_enter_restores_previous() {
+        let outer = TaskScope::default();
+        let inner = TaskScope::default();
+
+        {
+            let _outer = outer.enter();
+            drop(inner.enter());
+            let handle = spawn(pending::<()>());
+
+            assert_eq!(outer.running(), 1);
+            assert_eq!(inner.running(), 0);
+            handle.abort();
+        }
+
+        assert!(CURRENT_SCOPE.with_borrow(Option::is_none));
+    }
+}
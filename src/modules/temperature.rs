@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::{Button, Grid, Label, Orientation};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+use crate::config::{CommonConfig, ModuleOrientation};
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::modules::thermal::{c_to_f, parse_millidegrees};
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::schedule::Schedule;
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+
+const HWMON_PATH: &str = "/sys/class/hwmon";
+
+/// Sensors reporting the CPU package temperature,
+/// as `(chip, label)` pairs in order of preference.
+const CPU_PACKAGE_SENSORS: &[(&str, &str)] = &[
+    ("coretemp", "Package id 0"),
+    ("k10temp", "Tctl"),
+    ("k10temp", "Tdie"),
+    ("zenpower", "Tdie"),
+    ("cpu_thermal", "temp1"),
+];
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TemperatureModule {
+    /// Sensor to show on the bar,
+    /// as `chip:label` or `chip`.
+    /// Detects the CPU package sensor if not set.
+    sensor: Option<String>,
+
+    /// Format string for the bar label.
+    /// `{temp_c}` and `{temp_f}` are replaced with the temperature.
+    #[serde(default = "default_format")]
+    format: String,
+
+    /// Temperature in degrees C at or above which
+    /// the `critical` class is applied.
+    #[serde(default = "default_critical")]
+    critical: f64,
+
+    /// Number of seconds between refreshing.
+    /// Defaults to the global `interval`, or 5 seconds.
+    interval: Option<u64>,
+
+    /// Maximum time in milliseconds to randomly delay each refresh by.
+    /// Defaults to the global `jitter`.
+    jitter: Option<u64>,
+
+    #[serde(default)]
+    orientation: ModuleOrientation,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+fn default_format() -> String {
+    String::from("{temp_c}°C")
+}
+
+const fn default_critical() -> f64 {
+    80.0
+}
+
+const fn default_interval() -> u64 {
+    5
+}
+
+/// A single temperature reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    /// Name of the hwmon chip, such as `coretemp`.
+    chip: String,
+    /// Label of the sensor on the chip,
+    /// or the name of its input file if it has none.
+    label: String,
+    /// Temperature in degrees C.
+    temp: f64,
+}
+
+impl Sensor {
+    fn name(&self) -> String {
+        format!("{}:{}", self.chip, self.label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemperatureUpdate {
+    /// All sensors, ordered by chip and label.
+    sensors: Vec<Sensor>,
+    /// Index of the sensor to show on the bar, if found.
+    selected: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Normal,
+    Critical,
+}
+
+impl Level {
+    fn classify(temp: f64, critical: f64) -> Self {
+        if temp >= critical {
+            Self::Critical
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Reads all temperature sensors from hwmon.
+///
+/// Chips are numbered in the order the kernel probes them,
+/// which can change between boots,
+/// so sensors are sorted by name instead.
+fn read_sensors() -> Vec<Sensor> {
+    let Ok(entries) = fs::read_dir(HWMON_PATH) else {
+        return vec![];
+    };
+
+    let mut sensors = entries
+        .filter_map(Result::ok)
+        .flat_map(|entry| read_chip(&entry.path()))
+        .collect::<Vec<_>>();
+
+    sensors.sort_by(|a, b| a.chip.cmp(&b.chip).then_with(|| a.label.cmp(&b.label)));
+    sensors
+}
+
+fn read_chip(path: &Path) -> Vec<Sensor> {
+    let Ok(chip) = fs::read_to_string(path.join("name")) else {
+        return vec![];
+    };
+    let chip = chip.trim();
+
+    let Ok(entries) = fs::read_dir(path) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let input = file_name.to_str()?;
+            let prefix = input.strip_prefix("temp")?.strip_suffix("_input")?;
+
+            let temp = parse_millidegrees(&fs::read_to_string(entry.path()).ok()?)?;
+
+            let label = fs::read_to_string(path.join(format!("temp{prefix}_label"))).map_or_else(
+                |_| format!("temp{prefix}"),
+                |label| label.trim().to_string(),
+            );
+
+            Some(Sensor {
+                chip: chip.to_string(),
+                label,
+                temp,
+            })
+        })
+        .collect()
+}
+
+/// Finds the sensor to show on the bar.
+///
+/// `selector` matches either `chip:label` or the first sensor on `chip`.
+/// If not set, the CPU package sensor is used.
+fn find_sensor(sensors: &[Sensor], selector: Option<&str>) -> Option<usize> {
+    match selector {
+        Some(selector) => sensors
+            .iter()
+            .position(|sensor| match selector.split_once(':') {
+                Some((chip, label)) => sensor.chip == chip && sensor.label == label,
+                None => sensor.chip == selector,
+            }),
+        None => CPU_PACKAGE_SENSORS.iter().find_map(|&(chip, label)| {
+            sensors
+                .iter()
+                .position(|sensor| sensor.chip == chip && sensor.label == label)
+        }),
+    }
+}
+
+fn format_temp(format: &str, temp: f64) -> String {
+    format
+        .replace("{temp_c}", &format!("{temp:.0}"))
+        .replace("{temp_f}", &format!("{:.0}", c_to_f(temp)))
+}
+
+impl Module<Button> for TemperatureModule {
+    type SendMessage = TemperatureUpdate;
+    type ReceiveMessage = ();
+
+    module_impl!("temperature");
+
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let tx = context.tx.clone();
+        let selector = self.sensor.clone();
+        let mut schedule = {
+            let config = context.ironbar.config.borrow();
+
+            // the global interval is in milliseconds
+            let interval = self
+                .interval
+                .map(Duration::from_secs)
+                .or(config.interval.map(Duration::from_millis))
+                .unwrap_or_else(|| Duration::from_secs(default_interval()));
+            let jitter = self.jitter.or(config.jitter).unwrap_or_default();
+
+            Schedule::new(interval, Duration::from_millis(jitter))
+        };
+
+        spawn(async move {
+            // only warn when the sensor goes missing, not on every refresh
+            let mut found = true;
+
+            loop {
+                let sensors = read_sensors();
+                let selected = find_sensor(&sensors, selector.as_deref());
+
+                if selected.is_none() && found {
+                    match &selector {
+                        Some(selector) => warn!("Temperature sensor '{selector}' not found"),
+                        None => warn!("Unable to detect CPU temperature sensor"),
+                    }
+                }
+                found = selected.is_some();
+
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(TemperatureUpdate { sensors, selected })
+                );
+
+                schedule.tick().await;
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = Button::new();
+
+        let label = Label::builder()
+            .angle(self.orientation.to_angle())
+            .use_markup(true)
+            .build();
+        label.add_class("label");
+        button.add(&label);
+
+        let tx = context.tx.clone();
+        button.connect_clicked(move |button| {
+            try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+        });
+
+        {
+            let button = button.clone();
+            let format = self.format.clone();
+            let critical = self.critical;
+
+            let rx = context.subscribe();
+            glib_recv!(rx, update => {
+                let sensor = update.selected.and_then(|index| update.sensors.get(index));
+
+                match sensor {
+                    Some(sensor) => {
+                        label.set_label(&format_temp(&format, sensor.temp));
+
+                        if Level::classify(sensor.temp, critical) == Level::Critical {
+                            button.add_class("critical");
+                        } else {
+                            button.style_context().remove_class("critical");
+                        }
+
+                        button.show();
+                    }
+                    None => button.hide(),
+                }
+            });
+        }
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_lazy_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        _tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized,
+    {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        let grid = Grid::new();
+        grid.add_class("sensors");
+        container.add(&grid);
+
+        let critical = self.critical;
+
+        {
+            let grid = grid.clone();
+            glib_recv!(rx, update => {
+                for child in grid.children() {
+                    grid.remove(&child);
+                }
+
+                for (row, sensor) in update.sensors.iter().enumerate() {
+                    let row = row as i32;
+
+                    let name = Label::new(Some(&sensor.name()));
+                    name.add_class("name");
+                    name.set_halign(gtk::Align::Start);
+                    grid.attach(&name, 0, row, 1, 1);
+
+                    let value = Label::new(Some(&format!("{:.0}°C", sensor.temp)));
+                    value.add_class("value");
+                    value.set_halign(gtk::Align::End);
+                    grid.attach(&value, 1, row, 1, 1);
+
+                    for label in [&name, &value] {
+                        label.add_class("sensor");
+                        if Level::classify(sensor.temp, critical) == Level::Critical {
+                            label.add_class("critical");
+                        }
+                    }
+                }
+
+                grid.show_all();
+            });
+        }
+
+        container.show_all();
+
+        Some(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sensor(chip: &str, label: &str, temp: f64) -> Sensor {
+        Sensor {
+            chip: chip.to_string(),
+            label: label.to_string(),
+            temp,
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Level::classify(45.0, 80.0), Level::Normal);
+        assert_eq!(Level::classify(79.9, 80.0), Level::Normal);
+        assert_eq!(Level::classify(80.0, 80.0), Level::Critical);
+        assert_eq!(Level::classify(95.0, 80.0), Level::Critical);
+    }
+
+    #[test]
+    fn test_format_temp() {
+        assert_eq!(format_temp("{temp_c}°C", 54.4), "54°C");
+        assert_eq!(format_temp("{temp_f}°F", 100.0), "212°F");
+    }
+
+    #[test]
+    fn test_find_sensor_by_label() {
+        let sensors = [
+            sensor("amdgpu", "edge", 50.0),
+            sensor("k10temp", "Tccd1", 60.0),
+            sensor("k10temp", "Tctl", 65.0),
+            sensor("nvme", "Composite", 40.0),
+        ];
+
+        assert_eq!(find_sensor(&sensors, Some("k10temp:Tctl")), Some(2));
+        assert_eq!(find_sensor(&sensors, Some("k10temp")), Some(1));
+        assert_eq!(find_sensor(&sensors, Some("nvme:Sensor 1")), None);
+    }
+
+    #[test]
+    fn test_find_cpu_package() {
+        let sensors = [
+            sensor("acpitz", "temp1", 30.0),
+            sensor("coretemp", "Core 0", 50.0),
+            sensor("coretemp", "Package id 0", 55.0),
+        ];
+        assert_eq!(find_sensor(&sensors, None), Some(2));
+
+        let sensors = [sensor("acpitz", "temp1", 30.0)];
+        assert_eq!(find_sensor(&sensors, None), None);
+    }
+}
@@ -0,0 +1,44 @@
+use super::ModuleUpdateEvent;
+use tokio::sync::mpsc;
+
+pub type Sender = mpsc::Sender<ModuleUpdateEvent<i32>>;
+pub type Receiver = mpsc::Receiver<ModuleUpdateEvent<i32>>;
+
+/// Creates a channel whose receiver is passed through `wrap`,
+/// returning the sender along with the wrapped receiver.
+pub fn channel(wrap: impl FnOnce(Receiver) -> Receiver) -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel(64);
+    (tx, wrap(rx))
+}
+
+/// Sends each value as an `Update` event.
+pub async fn send_all(tx: &Sender, values: impl IntoIterator<Item = i32>) {
+    for value in values {
+        tx.send(ModuleUpdateEvent::Update(value)).await.unwrap();
+    }
+}
+
+/// Gets the value of an `Update` event,
+/// or `None` for any other event.
+pub fn update(event: &ModuleUpdateEvent<i32>) -> Option<i32> {
+    match event {
+        ModuleUpdateEvent::Update(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Waits for the next event, then gets its value as per [`update`].
+pub async fn next(rx: &mut Receiver) -> Option<i32> {
+    update(&rx.recv().await.expect("channel to be open"))
+}
+
+/// Receives events until the channel closes,
+/// getting the value of each as per [`update`].
+pub async fn collect(mut rx: Receiver) -> Vec<Option<i32>> {
+    let mut values = vec![];
+    while let Some(event) = rx.recv().await {
+        values.push(update(&event));
+    }
+
+    values
+}
@@ -0,0 +1,35 @@
+/// Parses the contents of a hwmon `temp*_input` file,
+/// which is in millidegrees C.
+pub fn parse_millidegrees(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .map(|millidegrees| millidegrees as f64 / 1000.0)
+}
+
+/// Converts celsius to fahrenheit.
+pub fn c_to_f(c: f64) -> f64 {
+    c * 9.0 / 5.0 + 32.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_millidegrees() {
+        assert_eq!(parse_millidegrees("54000\n"), Some(54.0));
+        assert_eq!(parse_millidegrees("61500"), Some(61.5));
+        assert_eq!(parse_millidegrees("-5000"), Some(-5.0));
+        assert_eq!(parse_millidegrees(""), None);
+        assert_eq!(parse_millidegrees("hot"), None);
+    }
+
+    #[test]
+    fn test_c_to_f() {
+        assert_eq!(c_to_f(0.0), 32.0);
+        assert_eq!(c_to_f(100.0), 212.0);
+        assert_eq!(c_to_f(-40.0), -40.0);
+    }
+}
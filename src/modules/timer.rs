@@ -0,0 +1,493 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use color_eyre::Result;
+use gtk::prelude::*;
+use gtk::{Button, Label, Orientation, ProgressBar};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tracing::debug;
+
+use crate::config::CommonConfig;
+use crate::gtk_helpers::IronbarGtkExt;
+use crate::modules::{
+    Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
+};
+use crate::script::Script;
+use crate::{glib_recv, module_impl, send_async, spawn, try_send};
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TimerModule {
+    /// Length of each work phase, in minutes.
+    #[serde(default = "default_work_duration")]
+    work_duration: u64,
+
+    /// Length of each break phase, in minutes.
+    #[serde(default = "default_break_duration")]
+    break_duration: u64,
+
+    /// Format string for the bar label.
+    /// `{phase}` and `{remaining}` are replaced.
+    #[serde(default = "default_format")]
+    format: String,
+
+    /// Whether to show a progress bar for the current phase.
+    #[serde(default)]
+    show_progress: bool,
+
+    /// Command to run when a phase ends.
+    /// `{phase}` is replaced with the phase starting.
+    on_phase_change: Option<String>,
+
+    #[serde(flatten)]
+    pub common: Option<CommonConfig>,
+}
+
+const fn default_work_duration() -> u64 {
+    25
+}
+
+const fn default_break_duration() -> u64 {
+    5
+}
+
+fn default_format() -> String {
+    String::from("{phase} {remaining}")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+impl Phase {
+    const fn next(self) -> Self {
+        match self {
+            Self::Work => Self::Break,
+            Self::Break => Self::Work,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Work => "work",
+            Self::Break => "break",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TimerAction {
+    Start,
+    Pause,
+    Reset,
+}
+
+/// The countdown for the current phase.
+///
+/// Time is measured from when the timer was last started,
+/// rather than by counting ticks,
+/// so that it does not drift when ticks are late or the timer is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Timer {
+    phase: Phase,
+    work_duration: Duration,
+    break_duration: Duration,
+    /// Time spent in this phase before the timer was last started.
+    elapsed: Duration,
+    /// When the timer was last started, if it is running.
+    started: Option<Instant>,
+}
+
+impl Timer {
+    const fn new(work_duration: Duration, break_duration: Duration) -> Self {
+        Self {
+            phase: Phase::Work,
+            work_duration,
+            break_duration,
+            elapsed: Duration::ZERO,
+            started: None,
+        }
+    }
+
+    const fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    const fn duration(&self) -> Duration {
+        match self.phase {
+            Phase::Work => self.work_duration,
+            Phase::Break => self.break_duration,
+        }
+    }
+
+    fn elapsed(&self, now: Instant) -> Duration {
+        self.elapsed + self.started.map_or(Duration::ZERO, |started| now - started)
+    }
+
+    fn remaining(&self, now: Instant) -> Duration {
+        self.duration().saturating_sub(self.elapsed(now))
+    }
+
+    fn start(&mut self, now: Instant) {
+        if self.started.is_none() {
+            self.started = Some(now);
+        }
+    }
+
+    fn pause(&mut self, now: Instant) {
+        self.elapsed = self.elapsed(now);
+        self.started = None;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.work_duration, self.break_duration);
+    }
+
+    /// Moves to the next phase if the current one has ended,
+    /// returning the new phase.
+    ///
+    /// Any time beyond the end of the old phase
+    /// counts towards the new one.
+    fn tick(&mut self, now: Instant) -> Option<Phase> {
+        if !self.is_running() {
+            return None;
+        }
+
+        let elapsed = self.elapsed(now);
+        let duration = self.duration();
+
+        if elapsed < duration {
+            return None;
+        }
+
+        self.phase = self.phase.next();
+        self.elapsed = elapsed - duration;
+        self.started = Some(now);
+
+        Some(self.phase)
+    }
+
+    fn status(&self, now: Instant) -> TimerStatus {
+        TimerStatus {
+            phase: self.phase,
+            remaining: self.remaining(now),
+            duration: self.duration(),
+            running: self.is_running(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimerStatus {
+    phase: Phase,
+    remaining: Duration,
+    duration: Duration,
+    running: bool,
+}
+
+impl TimerStatus {
+    fn fraction(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        1.0 - self.remaining.as_secs_f64() / self.duration.as_secs_f64()
+    }
+}
+
+/// Formats a duration as `MM:SS`,
+/// or `H:MM:SS` if it is an hour or longer.
+///
+/// Partial seconds are rounded up,
+/// so that the timer does not show `00:00` until it ends.
+fn format_remaining(remaining: Duration) -> String {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let seconds = seconds % 60;
+
+    if hours > 0 {
+        format!("{hours}:{minutes:0>2}:{seconds:0>2}")
+    } else {
+        format!("{minutes:0>2}:{seconds:0>2}")
+    }
+}
+
+fn format_status(format: &str, status: &TimerStatus) -> String {
+    format
+        .replace("{phase}", status.phase.name())
+        .replace("{remaining}", &format_remaining(status.remaining))
+}
+
+impl Module<Button> for TimerModule {
+    type SendMessage = TimerStatus;
+    type ReceiveMessage = TimerAction;
+
+    module_impl!("timer");
+
+    fn supersedes(_next: &Self::SendMessage, _previous: &Self::SendMessage) -> bool {
+        true
+    }
+
+    fn spawn_controller(
+        &self,
+        _info: &ModuleInfo,
+        context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
+    ) -> Result<()> {
+        let tx = context.tx.clone();
+
+        let mut timer = Timer::new(
+            Duration::from_secs(self.work_duration * 60),
+            Duration::from_secs(self.break_duration * 60),
+        );
+
+        let on_phase_change = self.on_phase_change.clone();
+
+        spawn(async move {
+            let mut ticks = interval(Duration::from_secs(1));
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    _ = ticks.tick() => {
+                        if let Some(phase) = timer.tick(Instant::now()) {
+                            debug!("Timer moved to {} phase", phase.name());
+
+                            if let Some(command) = &on_phase_change {
+                                let command = command.replace("{phase}", phase.name());
+                                Script::from(command.as_str()).run_as_oneshot(None);
+                            }
+                        }
+                    }
+                    action = rx.recv() => {
+                        let now = Instant::now();
+                        match action {
+                            Some(TimerAction::Start) => timer.start(now),
+                            Some(TimerAction::Pause) => timer.pause(now),
+                            Some(TimerAction::Reset) => timer.reset(),
+                            None => break,
+                        }
+                    }
+                }
+
+                send_async!(tx, ModuleUpdateEvent::Update(timer.status(Instant::now())));
+            }
+        });
+
+        Ok(())
+    }
+
+    fn into_widget(
+        self,
+        context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        info: &ModuleInfo,
+    ) -> Result<ModuleParts<Button>> {
+        let button = Button::new();
+
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+        button.add(&container);
+
+        let label = Label::new(None);
+        label.add_class("label");
+        container.add(&label);
+
+        let progress = ProgressBar::new();
+        progress.add_class("progress");
+        if self.show_progress {
+            container.add(&progress);
+        }
+
+        let tx = context.tx.clone();
+        button.connect_clicked(move |button| {
+            try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
+        });
+
+        {
+            let button = button.clone();
+            let format = self.format.clone();
+
+            let rx = context.subscribe();
+            glib_recv!(rx, status => {
+                label.set_label(&format_status(&format, &status));
+                progress.set_fraction(status.fraction());
+
+                for phase in [Phase::Work, Phase::Break] {
+                    button.style_context().remove_class(phase.name());
+                }
+                button.add_class(status.phase.name());
+
+                if status.running {
+                    button.style_context().remove_class("paused");
+                } else {
+                    button.add_class("paused");
+                }
+            });
+        }
+
+        let rx = context.subscribe();
+        let popup = self
+            .into_lazy_popup(context.controller_tx.clone(), rx, context, info)
+            .into_popup_parts(vec![&button]);
+
+        Ok(ModuleParts::new(button, popup))
+    }
+
+    fn into_popup(
+        self,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
+        rx: broadcast::Receiver<Self::SendMessage>,
+        _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
+        _info: &ModuleInfo,
+    ) -> Option<gtk::Box>
+    where
+        Self: Sized,
+    {
+        let container = gtk::Box::new(Orientation::Vertical, 0);
+
+        let status_label = Label::new(None);
+        status_label.add_class("status");
+        container.add(&status_label);
+
+        let controls = gtk::Box::new(Orientation::Horizontal, 0);
+        controls.add_class("controls");
+        container.add(&controls);
+
+        let btn_toggle = Button::with_label("Start");
+        btn_toggle.add_class("btn-toggle");
+        controls.add(&btn_toggle);
+
+        let btn_reset = Button::with_label("Reset");
+        btn_reset.add_class("btn-reset");
+        controls.add(&btn_reset);
+
+        let running = Rc::new(Cell::new(false));
+
+        {
+            let tx = tx.clone();
+            let running = running.clone();
+            btn_toggle.connect_clicked(move |_| {
+                let action = if running.get() {
+                    TimerAction::Pause
+                } else {
+                    TimerAction::Start
+                };
+
+                try_send!(tx, action);
+            });
+        }
+
+        btn_reset.connect_clicked(move |_| {
+            try_send!(tx, TimerAction::Reset);
+        });
+
+        glib_recv!(rx, status => {
+            status_label.set_label(&format_status("{phase} {remaining}", &status));
+            btn_toggle.set_label(if status.running { "Pause" } else { "Start" });
+            running.set(status.running);
+        });
+
+        container.show_all();
+
+        Some(container)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORK: Duration = Duration::from_secs(25 * 60);
+    const BREAK: Duration = Duration::from_secs(5 * 60);
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_phase_cycle() {
+        let start = Instant::now();
+        let mut timer = Timer::new(WORK, BREAK);
+
+        timer.start(start);
+        assert_eq!(timer.tick(start + WORK - secs(1)), None);
+        assert_eq!(timer.phase, Phase::Work);
+
+        assert_eq!(timer.tick(start + WORK), Some(Phase::Break));
+        assert_eq!(timer.remaining(start + WORK), BREAK);
+
+        assert_eq!(timer.tick(start + WORK + BREAK), Some(Phase::Work));
+        assert_eq!(timer.remaining(start + WORK + BREAK), WORK);
+    }
+
+    #[test]
+    fn test_late_tick_carries_over() {
+        let start = Instant::now();
+        let mut timer = Timer::new(WORK, BREAK);
+
+        timer.start(start);
+
+        let late = start + WORK + secs(3);
+        assert_eq!(timer.tick(late), Some(Phase::Break));
+        assert_eq!(timer.remaining(late), BREAK - secs(3));
+    }
+
+    #[test]
+    fn test_stopped_timer_does_not_tick() {
+        let start = Instant::now();
+        let mut timer = Timer::new(WORK, BREAK);
+
+        assert_eq!(timer.tick(start + WORK * 2), None);
+        assert_eq!(timer.remaining(start + WORK * 2), WORK);
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let start = Instant::now();
+        let mut timer = Timer::new(WORK, BREAK);
+
+        timer.start(start);
+        timer.pause(start + secs(60));
+        assert!(!timer.is_running());
+
+        // time passing while paused is not counted
+        assert_eq!(timer.remaining(start + secs(600)), WORK - secs(60));
+
+        timer.start(start + secs(600));
+        assert_eq!(timer.remaining(start + secs(630)), WORK - secs(90));
+
+        // starting again while running does not restart the count
+        timer.start(start + secs(700));
+        assert_eq!(timer.remaining(start + secs(700)), WORK - secs(160));
+    }
+
+    #[test]
+    fn test_reset() {
+        let start = Instant::now();
+        let mut timer = Timer::new(WORK, BREAK);
+
+        timer.start(start);
+        timer.tick(start + WORK);
+        timer.reset();
+
+        assert_eq!(timer.phase, Phase::Work);
+        assert!(!timer.is_running());
+        assert_eq!(timer.remaining(start + WORK * 2), WORK);
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(secs(25 * 60)), "25:00");
+        assert_eq!(format_remaining(secs(61)), "01:01");
+        assert_eq!(format_remaining(Duration::from_millis(59_200)), "01:00");
+        assert_eq!(format_remaining(Duration::ZERO), "00:00");
+        assert_eq!(format_remaining(secs(90 * 60)), "1:30:00");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::image::ImageProvider;
+use crate::image::{icon_name_for_app_id, pixel_size, ImageProvider};
 use crate::modules::tray::interface::TrayMenu;
 use color_eyre::{Report, Result};
 use glib::ffi::g_strfreev;
@@ -38,37 +38,52 @@ fn get_icon_theme_search_paths(icon_theme: &IconTheme) -> HashSet<String> {
     paths
 }
 
-pub fn get_image(
-    item: &TrayMenu,
-    icon_theme: &IconTheme,
-    size: u32,
-    prefer_icons: bool,
-) -> Result<Image> {
-    if !prefer_icons && item.icon_pixmap.is_some() {
-        get_image_from_pixmap(item, size)
+/// Options for loading the icons of tray items.
+#[derive(Debug, Clone)]
+pub struct IconOptions {
+    pub theme: IconTheme,
+    pub size: u32,
+    /// The scale factor of the output the tray is shown on.
+    pub scale: i32,
+    pub prefer_theme: bool,
+}
+
+pub fn get_image(item: &TrayMenu, options: &IconOptions) -> Result<Image> {
+    if !options.prefer_theme && item.icon_pixmap.is_some() {
+        get_image_from_pixmap(item, options)
     } else {
-        get_image_from_icon_name(item, icon_theme, size)
-            .or_else(|_| get_image_from_pixmap(item, size))
+        get_image_from_icon_name(item, options).or_else(|_| get_image_from_pixmap(item, options))
     }
 }
 
 /// Attempts to get a GTK `Image` component
 /// for the status notifier item's icon.
-fn get_image_from_icon_name(item: &TrayMenu, icon_theme: &IconTheme, size: u32) -> Result<Image> {
+fn get_image_from_icon_name(item: &TrayMenu, options: &IconOptions) -> Result<Image> {
+    let icon_theme = &options.theme;
+
     if let Some(path) = item.icon_theme_path.as_ref() {
         if !path.is_empty() && !get_icon_theme_search_paths(icon_theme).contains(path) {
             icon_theme.append_search_path(path);
         }
     }
 
-    let icon_info = item.icon_name.as_ref().and_then(|icon_name| {
-        icon_theme.lookup_icon(icon_name, size as i32, IconLookupFlags::empty())
-    });
+    let icon_info = item
+        .icon_name
+        .as_ref()
+        .and_then(|icon_name| icon_name_for_app_id(icon_name, icon_theme))
+        .and_then(|icon_name| {
+            icon_theme.lookup_icon_for_scale(
+                &icon_name,
+                options.size as i32,
+                options.scale,
+                IconLookupFlags::empty(),
+            )
+        });
 
     if let Some(icon_info) = icon_info {
         let pixbuf = icon_info.load_icon()?;
         let image = Image::new();
-        ImageProvider::create_and_load_surface(&pixbuf, &image)?;
+        ImageProvider::create_and_load_surface(&pixbuf, &image, options.scale)?;
         Ok(image)
     } else {
         Err(Report::msg("could not find icon"))
@@ -81,7 +96,7 @@ fn get_image_from_icon_name(item: &TrayMenu, icon_theme: &IconTheme, size: u32)
 /// which has 8 bits per sample and a bit stride of `4*width`.
 /// The Pixbuf expects RGBA32 format, so some channel shuffling
 /// is required.
-fn get_image_from_pixmap(item: &TrayMenu, size: u32) -> Result<Image> {
+fn get_image_from_pixmap(item: &TrayMenu, options: &IconOptions) -> Result<Image> {
     const BITS_PER_SAMPLE: i32 = 8;
 
     let pixmap = item
@@ -113,11 +128,12 @@ fn get_image_from_pixmap(item: &TrayMenu, size: u32) -> Result<Image> {
         row_stride,
     );
 
+    let size = pixel_size(options.size as i32, options.scale);
     let pixbuf = pixbuf
-        .scale_simple(size as i32, size as i32, InterpType::Bilinear)
+        .scale_simple(size, size, InterpType::Bilinear)
         .unwrap_or(pixbuf);
 
     let image = Image::new();
-    ImageProvider::create_and_load_surface(&pixbuf, &image)?;
+    ImageProvider::create_and_load_surface(&pixbuf, &image, options.scale)?;
     Ok(image)
 }
@@ -8,9 +8,11 @@ use crate::modules::tray::diff::get_diffs;
 use crate::modules::{Module, ModuleInfo, ModuleParts, ModuleUpdateEvent, WidgetContext};
 use crate::{glib_recv, lock, module_impl, send_async, spawn};
 use color_eyre::{Report, Result};
+use gtk::MenuBar;
 use gtk::{prelude::*, PackDirection};
-use gtk::{IconTheme, MenuBar};
+use icon::IconOptions;
 use interface::TrayMenu;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 use system_tray::client::Event;
@@ -18,7 +20,8 @@ use system_tray::client::{ActivateRequest, UpdateEvent};
 use tokio::sync::mpsc;
 use tracing::{debug, error, warn};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TrayModule {
     #[serde(default = "crate::config::default_true")]
     prefer_theme_icons: bool,
@@ -26,7 +29,9 @@ pub struct TrayModule {
     #[serde(default = "default_icon_size")]
     icon_size: u32,
 
+    /// One of `left_to_right`, `right_to_left`, `top_to_bottom` or `bottom_to_top`.
     #[serde(default, deserialize_with = "deserialize_orientation")]
+    #[schemars(with = "Option<String>")]
     direction: Option<PackDirection>,
 
     #[serde(flatten)]
@@ -126,11 +131,16 @@ impl Module<MenuBar> for TrayModule {
         {
             let container = container.clone();
             let mut menus = HashMap::new();
-            let icon_theme = info.icon_theme.clone();
+            let icons = IconOptions {
+                theme: info.icon_theme.clone(),
+                size: self.icon_size,
+                scale: info.scale_factor(),
+                prefer_theme: self.prefer_theme_icons,
+            };
 
             // listen for UI updates
             glib_recv!(context.subscribe(), update =>
-                on_update(update, &container, &mut menus, &icon_theme, self.icon_size, self.prefer_theme_icons, &context.controller_tx)
+                on_update(update, &container, &mut menus, &icons, &context.controller_tx)
             );
         };
 
@@ -147,9 +157,7 @@ fn on_update(
     update: Event,
     container: &MenuBar,
     menus: &mut HashMap<Box<str>, TrayMenu>,
-    icon_theme: &IconTheme,
-    icon_size: u32,
-    prefer_icons: bool,
+    icons: &IconOptions,
     tx: &mpsc::Sender<ActivateRequest>,
 ) {
     match update {
@@ -159,7 +167,7 @@ fn on_update(
             let mut menu_item = TrayMenu::new(tx.clone(), address.clone(), *item);
             container.add(&menu_item.widget);
 
-            if let Ok(image) = icon::get_image(&menu_item, icon_theme, icon_size, prefer_icons) {
+            if let Ok(image) = icon::get_image(&menu_item, icons) {
                 menu_item.set_image(&image);
             } else {
                 let label = menu_item.title.clone().unwrap_or(address.clone());
@@ -183,7 +191,7 @@ fn on_update(
                 }
                 UpdateEvent::Icon(icon) => {
                     if icon.as_ref() != menu_item.icon_name() {
-                        match icon::get_image(menu_item, icon_theme, icon_size, prefer_icons) {
+                        match icon::get_image(menu_item, icons) {
                             Ok(image) => menu_item.set_image(&image),
                             Err(_) => menu_item.show_label(),
                         };
@@ -2,13 +2,17 @@ use color_eyre::Result;
 use futures_lite::stream::StreamExt;
 use gtk::{prelude::*, Button};
 use gtk::{Label, Orientation};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error};
 use upower_dbus::BatteryState;
 use zbus;
 use zbus::fdo::PropertiesProxy;
 
+use crate::clients::power_profiles;
 use crate::config::CommonConfig;
+use crate::format::resolve_conditionals;
 use crate::gtk_helpers::IronbarGtkExt;
 use crate::image::ImageProvider;
 use crate::modules::PopupButton;
@@ -21,7 +25,8 @@ const DAY: i64 = 24 * 60 * 60;
 const HOUR: i64 = 60 * 60;
 const MINUTE: i64 = 60;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct UpowerModule {
     #[serde(default = "default_format")]
     format: String,
@@ -29,6 +34,10 @@ pub struct UpowerModule {
     #[serde(default = "default_icon_size")]
     icon_size: i32,
 
+    /// Whether to show an icon for the active power profile on the bar.
+    #[serde(default)]
+    show_profile_icon: bool,
+
     #[serde(flatten)]
     pub common: Option<CommonConfig>,
 }
@@ -50,18 +59,125 @@ pub struct UpowerProperties {
     time_to_empty: i64,
 }
 
+/// Known power profiles, in the order they are shown.
+const PROFILE_ORDER: [&str; 3] = ["power-saver", "balanced", "performance"];
+
+/// Power profiles from `power-profiles-daemon`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileState {
+    /// Available profiles, in display order.
+    /// Empty if the daemon is not running.
+    profiles: Vec<String>,
+    active: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum ProfileEvent {
+    Listed(Vec<String>),
+    Activated(String),
+    Unavailable,
+}
+
+impl ProfileState {
+    fn reduce(mut self, event: ProfileEvent) -> Self {
+        match event {
+            ProfileEvent::Listed(mut profiles) => {
+                // unknown profiles go last, keeping their order
+                profiles.sort_by_key(|profile| {
+                    PROFILE_ORDER
+                        .iter()
+                        .position(|known| known == profile)
+                        .unwrap_or(PROFILE_ORDER.len())
+                });
+                profiles.dedup();
+
+                self.profiles = profiles;
+            }
+            ProfileEvent::Activated(profile) => self.active = Some(profile),
+            ProfileEvent::Unavailable => self = Self::default(),
+        }
+
+        self
+    }
+
+    fn is_available(&self) -> bool {
+        !self.profiles.is_empty()
+    }
+
+    /// Gets the command to switch to the profile.
+    ///
+    /// Returns `None` if the profile is unknown or already active.
+    fn set_profile(&self, profile: &str) -> Option<UpowerCommand> {
+        let known = self.profiles.iter().any(|name| name == profile);
+        let active = self.active.as_deref() == Some(profile);
+
+        (known && !active).then(|| UpowerCommand::SetProfile(profile.to_string()))
+    }
+}
+
+/// Converts a profile name such as `power-saver`
+/// into a label such as `Power saver`.
+fn profile_label(profile: &str) -> String {
+    let label = profile.replace('-', " ");
+    let mut chars = label.chars();
+
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+#[derive(Debug, Clone)]
+pub enum UpowerUpdate {
+    Properties(UpowerProperties),
+    Profiles(ProfileState),
+}
+
+#[derive(Debug, Clone)]
+pub enum UpowerCommand {
+    SetProfile(String),
+}
+
 impl Module<gtk::Button> for UpowerModule {
-    type SendMessage = UpowerProperties;
-    type ReceiveMessage = ();
+    type SendMessage = UpowerUpdate;
+    type ReceiveMessage = UpowerCommand;
 
     module_impl!("upower");
 
+    fn supersedes(next: &Self::SendMessage, previous: &Self::SendMessage) -> bool {
+        std::mem::discriminant(next) == std::mem::discriminant(previous)
+    }
+
     fn spawn_controller(
         &self,
         _info: &ModuleInfo,
         context: &WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
-        _rx: mpsc::Receiver<Self::ReceiveMessage>,
+        mut rx: mpsc::Receiver<Self::ReceiveMessage>,
     ) -> Result<()> {
+        // profile controls are hidden if the daemon is not running
+        match context.try_client::<power_profiles::Client>() {
+            Ok(client) => {
+                let tx = context.tx.clone();
+
+                {
+                    let client = client.clone();
+                    spawn(async move { watch_profiles(&client, &tx).await });
+                }
+
+                spawn(async move {
+                    while let Some(command) = rx.recv().await {
+                        match command {
+                            UpowerCommand::SetProfile(profile) => {
+                                if let Err(err) = client.set_active_profile(&profile).await {
+                                    error!("Failed to set power profile to '{profile}': {err:?}");
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+            Err(err) => debug!("Power profiles unavailable: {err}"),
+        }
+
         let tx = context.tx.clone();
 
         let display_proxy = context.client::<PropertiesProxy>();
@@ -102,7 +218,10 @@ impl Module<gtk::Button> for UpowerModule {
                 time_to_empty,
             };
 
-            send_async!(tx, ModuleUpdateEvent::Update(properties.clone()));
+            send_async!(
+                tx,
+                ModuleUpdateEvent::Update(UpowerUpdate::Properties(properties.clone()))
+            );
 
             while let Some(signal) = prop_changed_stream.next().await {
                 let args = signal.args().expect("Invalid signal arguments");
@@ -142,7 +261,10 @@ impl Module<gtk::Button> for UpowerModule {
                     }
                 }
 
-                send_async!(tx, ModuleUpdateEvent::Update(properties.clone()));
+                send_async!(
+                    tx,
+                    ModuleUpdateEvent::Update(UpowerUpdate::Properties(properties.clone()))
+                );
             }
 
             Result::<()>::Ok(())
@@ -157,6 +279,7 @@ impl Module<gtk::Button> for UpowerModule {
         info: &ModuleInfo,
     ) -> Result<ModuleParts<Button>> {
         let icon_theme = info.icon_theme.clone();
+        let scale = info.scale_factor();
         let icon = gtk::Image::new();
         icon.add_class("icon");
 
@@ -176,6 +299,13 @@ impl Module<gtk::Button> for UpowerModule {
         container.add(&label);
         button.add(&container);
 
+        let profile_icon = gtk::Image::new();
+        profile_icon.add_class("profile-icon");
+
+        if self.show_profile_icon {
+            container.add(&profile_icon);
+        }
+
         let tx = context.tx.clone();
         button.connect_clicked(move |button| {
             try_send!(tx, ModuleUpdateEvent::TogglePopup(button.popup_id()));
@@ -184,7 +314,24 @@ impl Module<gtk::Button> for UpowerModule {
         let format = self.format.clone();
 
         let rx = context.subscribe();
-        glib_recv!(rx, properties => {
+        glib_recv!(rx, update => {
+            let properties = match update {
+                UpowerUpdate::Properties(properties) => properties,
+                UpowerUpdate::Profiles(state) => {
+                    match state.active {
+                        Some(active) if state.is_available() => {
+                            let icon_name = format!("icon:power-profile-{active}-symbolic");
+                            ImageProvider::parse(&icon_name, &icon_theme, false, self.icon_size)
+                                .map(|provider| provider.with_scale(scale).load_into_image(profile_icon.clone()));
+                            profile_icon.show();
+                        }
+                        _ => profile_icon.hide(),
+                    }
+
+                    continue;
+                }
+            };
+
             let state = properties.state;
             let is_charging = state == BatteryState::Charging || state == BatteryState::PendingCharge;
             let time_remaining = if is_charging {
@@ -193,15 +340,25 @@ impl Module<gtk::Button> for UpowerModule {
             else {
                 seconds_to_string(properties.time_to_empty)
             };
-            let format = format.replace("{percentage}", &properties.percentage.to_string())
+            let percentage = properties.percentage.to_string();
+            let state = battery_state_to_string(state);
+
+            let format = resolve_conditionals(&format, &[
+                ("percentage", &percentage),
+                ("time_remaining", &time_remaining),
+                ("state", state),
+                ("charging", if is_charging { "true" } else { "false" }),
+            ]);
+
+            let format = format.replace("{percentage}", &percentage)
                 .replace("{time_remaining}", &time_remaining)
-                .replace("{state}", battery_state_to_string(state));
+                .replace("{state}", state);
 
             let mut icon_name = String::from("icon:");
             icon_name.push_str(&properties.icon_name);
 
             ImageProvider::parse(&icon_name, &icon_theme, false, self.icon_size)
-                    .map(|provider| provider.load_into_image(icon.clone()));
+                    .map(|provider| provider.with_scale(scale).load_into_image(icon.clone()));
 
             label.set_markup(format.as_ref());
         });
@@ -216,7 +373,7 @@ impl Module<gtk::Button> for UpowerModule {
 
     fn into_popup(
         self,
-        _tx: mpsc::Sender<Self::ReceiveMessage>,
+        tx: mpsc::Sender<Self::ReceiveMessage>,
         rx: broadcast::Receiver<Self::SendMessage>,
         _context: WidgetContext<Self::SendMessage, Self::ReceiveMessage>,
         _info: &ModuleInfo,
@@ -225,14 +382,53 @@ impl Module<gtk::Button> for UpowerModule {
         Self: Sized,
     {
         let container = gtk::Box::builder()
-            .orientation(Orientation::Horizontal)
+            .orientation(Orientation::Vertical)
             .build();
 
         let label = Label::new(None);
         label.add_class("upower-details");
         container.add(&label);
 
-        glib_recv!(rx, properties => {
+        let profiles = gtk::Box::new(Orientation::Horizontal, 0);
+        profiles.add_class("profiles");
+        container.add(&profiles);
+
+        glib_recv!(rx, update => {
+            let properties = match update {
+                UpowerUpdate::Properties(properties) => properties,
+                UpowerUpdate::Profiles(state) => {
+                    for child in profiles.children() {
+                        profiles.remove(&child);
+                    }
+
+                    for profile in &state.profiles {
+                        let button = Button::with_label(&profile_label(profile));
+                        button.add_class("profile");
+                        button.add_class(profile);
+
+                        if state.active.as_ref() == Some(profile) {
+                            button.add_class("active");
+                        }
+
+                        let tx = tx.clone();
+                        let state = state.clone();
+                        let profile = profile.clone();
+                        button.connect_clicked(move |_| {
+                            if let Some(command) = state.set_profile(&profile) {
+                                try_send!(tx, command);
+                            }
+                        });
+
+                        profiles.add(&button);
+                    }
+
+                    profiles.show_all();
+                    profiles.set_visible(state.is_available());
+
+                    continue;
+                }
+            };
+
             let state = properties.state;
             let format = match state {
                 BatteryState::Charging | BatteryState::PendingCharge => {
@@ -263,6 +459,47 @@ impl Module<gtk::Button> for UpowerModule {
     }
 }
 
+/// Sends the power profiles, and again each time the active profile changes.
+async fn watch_profiles(
+    client: &power_profiles::Client,
+    tx: &mpsc::Sender<ModuleUpdateEvent<UpowerUpdate>>,
+) {
+    let mut changes = client.receive_active_profile_changed().await;
+
+    let mut state = match client.profiles().await {
+        Ok(profiles) => ProfileState::default().reduce(ProfileEvent::Listed(profiles)),
+        Err(err) => {
+            error!("Failed to get power profiles: {err:?}");
+            return;
+        }
+    };
+
+    if let Ok(active) = client.active_profile().await {
+        state = state.reduce(ProfileEvent::Activated(active));
+    }
+
+    send_async!(
+        tx,
+        ModuleUpdateEvent::Update(UpowerUpdate::Profiles(state.clone()))
+    );
+
+    while let Some(change) = changes.next().await {
+        let event = match change.get().await {
+            Ok(profile) => ProfileEvent::Activated(profile),
+            Err(err) => {
+                error!("Failed to get active power profile: {err:?}");
+                ProfileEvent::Unavailable
+            }
+        };
+
+        state = state.reduce(event);
+        send_async!(
+            tx,
+            ModuleUpdateEvent::Update(UpowerUpdate::Profiles(state.clone()))
+        );
+    }
+}
+
 fn seconds_to_string(seconds: i64) -> String {
     let mut time_string = String::new();
     let days = seconds / (DAY);
@@ -311,3 +548,73 @@ fn battery_state_to_string(state: BatteryState) -> &'static str {
         BatteryState::PendingDischarge => "Pending discharge",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(profiles: &[&str]) -> ProfileState {
+        ProfileState::default().reduce(ProfileEvent::Listed(
+            profiles.iter().map(ToString::to_string).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_profile_order() {
+        let state = listed(&["performance", "custom", "power-saver", "balanced"]);
+        assert_eq!(
+            state.profiles,
+            ["power-saver", "balanced", "performance", "custom"]
+        );
+        assert!(state.is_available());
+        assert_eq!(state.active, None);
+    }
+
+    #[test]
+    fn test_profile_activated() {
+        let state = listed(&["power-saver", "balanced"])
+            .reduce(ProfileEvent::Activated("balanced".to_string()));
+        assert_eq!(state.active.as_deref(), Some("balanced"));
+
+        // relisting keeps the active profile
+        let state = state.reduce(ProfileEvent::Listed(vec![
+            "balanced".to_string(),
+            "performance".to_string(),
+        ]));
+        assert_eq!(state.profiles, ["balanced", "performance"]);
+        assert_eq!(state.active.as_deref(), Some("balanced"));
+    }
+
+    #[test]
+    fn test_profiles_unavailable() {
+        let state = listed(&["balanced"])
+            .reduce(ProfileEvent::Activated("balanced".to_string()))
+            .reduce(ProfileEvent::Unavailable);
+
+        assert_eq!(state, ProfileState::default());
+        assert!(!state.is_available());
+    }
+
+    #[test]
+    fn test_set_profile() {
+        let state = listed(&["power-saver", "balanced", "performance"])
+            .reduce(ProfileEvent::Activated("balanced".to_string()));
+
+        assert!(matches!(
+            state.set_profile("performance"),
+            Some(UpowerCommand::SetProfile(profile)) if profile == "performance"
+        ));
+
+        // already active, or not offered by the daemon
+        assert!(state.set_profile("balanced").is_none());
+        assert!(state.set_profile("turbo").is_none());
+        assert!(ProfileState::default().set_profile("balanced").is_none());
+    }
+
+    #[test]
+    fn test_profile_label() {
+        assert_eq!(profile_label("power-saver"), "Power saver");
+        assert_eq!(profile_label("balanced"), "Balanced");
+        assert_eq!(profile_label(""), "");
+    }
+}
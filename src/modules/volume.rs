@@ -1,5 +1,6 @@
 use crate::clients::volume::{self, Event};
 use crate::config::CommonConfig;
+use crate::format::resolve_conditionals;
 use crate::gtk_helpers::IronbarGtkExt;
 use crate::modules::{
     Module, ModuleInfo, ModuleParts, ModulePopup, ModuleUpdateEvent, PopupButton, WidgetContext,
@@ -9,11 +10,13 @@ use glib::Propagation;
 use gtk::pango::EllipsizeMode;
 use gtk::prelude::*;
 use gtk::{Button, CellRendererText, ComboBoxText, Label, Orientation, Scale, ToggleButton};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::collections::HashMap;
 use tokio::sync::mpsc;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct VolumeModule {
     #[serde(default = "default_format")]
     format: String,
@@ -32,7 +35,8 @@ fn default_format() -> String {
     String::from("{icon} {percentage}%")
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[schemars(rename = "VolumeIcons")]
 pub struct Icons {
     #[serde(default = "default_icon_volume_high")]
     volume_high: String,
@@ -193,9 +197,19 @@ impl Module<Button> for VolumeModule {
             glib_recv!(rx, event => {
                 match event {
                     Event::AddSink(sink) | Event::UpdateSink(sink) if sink.active => {
-                        let label = format
-                            .replace("{icon}", if sink.muted { &icons.muted } else { icons.volume_icon(sink.volume) })
-                            .replace("{percentage}", &sink.volume.to_string())
+                        let icon: &str = if sink.muted { &icons.muted } else { icons.volume_icon(sink.volume) };
+                        let percentage = sink.volume.to_string();
+
+                        let label = resolve_conditionals(&format, &[
+                            ("icon", icon),
+                            ("percentage", &percentage),
+                            ("name", &sink.description),
+                            ("muted", if sink.muted { "true" } else { "false" }),
+                        ]);
+
+                        let label = label
+                            .replace("{icon}", icon)
+                            .replace("{percentage}", &percentage)
                             .replace("{name}", &sink.description);
 
                         button.set_label(&label);
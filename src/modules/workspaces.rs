@@ -6,13 +6,14 @@ use crate::{glib_recv, module_impl, send_async, spawn, try_send};
 use color_eyre::{Report, Result};
 use gtk::prelude::*;
 use gtk::{Button, IconTheme};
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use tokio::sync::mpsc::{Receiver, Sender};
 use tracing::{debug, trace, warn};
 
-#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SortOrder {
     /// Shows workspaces in the order they're added
@@ -28,7 +29,7 @@ impl Default for SortOrder {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum Favorites {
     ByMonitor(HashMap<String, Vec<String>>),
@@ -41,7 +42,8 @@ impl Default for Favorites {
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct WorkspacesModule {
     /// Map of actual workspace names to custom names.
     name_map: Option<HashMap<String, String>>,
@@ -79,11 +81,12 @@ fn create_button(
     name_map: &HashMap<String, String>,
     icon_theme: &IconTheme,
     icon_size: i32,
+    scale: i32,
     tx: &Sender<String>,
 ) -> Button {
     let label = name_map.get(name).map_or(name, String::as_str);
 
-    let button = new_icon_button(label, icon_theme, icon_size);
+    let button = new_icon_button(label, icon_theme, icon_size, scale);
     button.set_widget_name(name);
 
     let style_context = button.style_context();
@@ -199,6 +202,7 @@ impl Module<gtk::Box> for WorkspacesModule {
             let container = container.clone();
             let output_name = info.output_name.to_string();
             let icon_theme = info.icon_theme.clone();
+            let scale = info.scale_factor();
             let icon_size = self.icon_size;
 
             // keep track of whether init event has fired previously
@@ -220,6 +224,7 @@ impl Module<gtk::Box> for WorkspacesModule {
                                     &name_map,
                                     &icon_theme,
                                     icon_size,
+                                    scale,
                                     &context.controller_tx,
                                 );
 
@@ -295,6 +300,7 @@ impl Module<gtk::Box> for WorkspacesModule {
                                 &name_map,
                                 &icon_theme,
                                 icon_size,
+                                scale,
                                 &context.controller_tx,
                             );
 
@@ -320,6 +326,7 @@ impl Module<gtk::Box> for WorkspacesModule {
                                     &name_map,
                                     &icon_theme,
                                     icon_size,
+                                    scale,
                                     &context.controller_tx,
                                 );
 
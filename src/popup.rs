@@ -1,15 +1,18 @@
 use glib::Propagation;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::Duration;
 
-use gtk::gdk::Monitor;
+use gtk::gdk::{keys, ModifierType, Monitor, NotifyType, Screen};
 use gtk::prelude::*;
-use gtk::{ApplicationWindow, Button, Orientation};
-use gtk_layer_shell::LayerShell;
-use tracing::{debug, trace};
+use gtk::{
+    ApplicationWindow, Button, DirectionType, Orientation, Revealer, RevealerTransitionType, Widget,
+};
+use gtk_layer_shell::{KeyboardMode, LayerShell};
+use tracing::{debug, trace, warn};
 
-use crate::config::BarPosition;
+use crate::config::{BarPosition, PopupAnchor, PopupTransition};
 use crate::gtk_helpers::{IronbarGtkExt, WidgetGeometry};
 use crate::modules::{ModuleInfo, ModulePopupParts, PopupButton};
 use crate::rc_mut;
@@ -18,16 +21,209 @@ use crate::rc_mut;
 pub struct PopupCacheValue {
     pub name: String,
     pub content: ModulePopupParts,
+    pub options: PopupOptions,
+}
+
+/// Per-module popup behaviour.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PopupOptions {
+    /// Where to position the popup relative to the widget that opened it.
+    pub anchor: PopupAnchor,
+    /// Distance in pixels to shift the popup along the bar.
+    pub offset: i32,
+    /// Whether to close the popup once the pointer leaves it
+    /// or it loses focus.
+    pub auto_close: bool,
+    /// The animation to use when opening and closing the popup.
+    pub transition: Option<PopupTransition>,
+    /// The length of the animation in milliseconds.
+    pub transition_duration: u32,
+}
+
+/// Stage of the popup window's open/close animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransitionState {
+    /// The window is hidden.
+    Closed,
+    Open,
+    /// The close animation is running,
+    /// so the window must stay shown until it finishes.
+    Closing,
+}
+
+impl TransitionState {
+    const fn open(self) -> Self {
+        Self::Open
+    }
+
+    /// Gets the state after closing the popup,
+    /// which goes straight to `Closed` if there is no animation to wait for.
+    const fn close(self, animated: bool) -> Self {
+        match self {
+            Self::Open | Self::Closing if animated => Self::Closing,
+            _ => Self::Closed,
+        }
+    }
+
+    /// Gets the state once the close animation has finished.
+    ///
+    /// A popup re-opened while closing stays open.
+    const fn finish(self) -> Self {
+        match self {
+            Self::Closing => Self::Closed,
+            state => state,
+        }
+    }
+
+    const fn is_open(self) -> bool {
+        matches!(self, Self::Open)
+    }
+}
+
+/// Gets the revealer animation for the transition,
+/// with slides moving away from the bar.
+const fn revealer_transition(
+    transition: Option<PopupTransition>,
+    pos: BarPosition,
+) -> RevealerTransitionType {
+    match (transition, pos) {
+        (Some(PopupTransition::Crossfade), _) => RevealerTransitionType::Crossfade,
+        (Some(PopupTransition::Slide), BarPosition::Top) => RevealerTransitionType::SlideDown,
+        (Some(PopupTransition::Slide), BarPosition::Bottom) => RevealerTransitionType::SlideUp,
+        (Some(PopupTransition::Slide), BarPosition::Left) => RevealerTransitionType::SlideRight,
+        (Some(PopupTransition::Slide), BarPosition::Right) => RevealerTransitionType::SlideLeft,
+        (None, _) => RevealerTransitionType::None,
+    }
+}
+
+/// Where the pointer is relative to an open popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PointerLocation {
+    Popup,
+    /// Over a bar widget which opens the popup.
+    Button,
+    Outside,
+}
+
+/// Time to wait after the pointer leaves before auto-closing,
+/// so that it can cross between the popup and its button.
+const AUTO_CLOSE_DELAY: Duration = Duration::from_millis(150);
+
+/// Decides whether an auto-closing popup should close,
+/// after the pointer has left it or it has lost focus.
+fn should_auto_close(auto_close: bool, keep_open: bool, pointer: PointerLocation) -> bool {
+    auto_close && !keep_open && pointer == PointerLocation::Outside
+}
+
+/// What a key press does in an open popup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyAction {
+    Close,
+    FocusNext,
+    FocusPrevious,
+}
+
+impl KeyAction {
+    /// Gets the action for a key press, if it has one.
+    fn from_key(key: keys::Key, modifiers: ModifierType) -> Option<Self> {
+        use keys::constants;
+
+        let shift = modifiers.contains(ModifierType::SHIFT_MASK);
+
+        if key == constants::Escape {
+            Some(Self::Close)
+        } else if key == constants::ISO_Left_Tab || (key == constants::Tab && shift) {
+            Some(Self::FocusPrevious)
+        } else if [constants::Tab, constants::Down, constants::Right].contains(&key) {
+            Some(Self::FocusNext)
+        } else if [constants::Up, constants::Left].contains(&key) {
+            Some(Self::FocusPrevious)
+        } else {
+            None
+        }
+    }
+}
+
+/// Minimum distance in pixels to keep between the popup
+/// and the edges of the monitor.
+const SCREEN_EDGE_MARGIN: i32 = 5;
+
+/// Calculates the popup's position from the start edge of the screen
+/// along the bar's axis.
+///
+/// The popup is clamped so that it never extends past the edges of the screen.
+/// Popups too large to fit are centered on the screen instead.
+fn calculate_offset(
+    geometry: WidgetGeometry,
+    popup_size: i32,
+    screen_size: i32,
+    options: PopupOptions,
+) -> i32 {
+    let bar_offset = (f64::from(screen_size) - f64::from(geometry.bar_size)) / 2.0;
+    let widget_start = bar_offset + f64::from(geometry.position);
+
+    let offset = match options.anchor {
+        PopupAnchor::Start => widget_start,
+        PopupAnchor::Center => {
+            widget_start + ((f64::from(geometry.size) - f64::from(popup_size)) / 2.0)
+        }
+        PopupAnchor::End => widget_start + f64::from(geometry.size - popup_size),
+    };
+
+    let offset = offset.round() as i32 + options.offset;
+
+    let min = SCREEN_EDGE_MARGIN;
+    let max = screen_size - popup_size - SCREEN_EDGE_MARGIN;
+
+    if max < min {
+        ((screen_size - popup_size) / 2).max(0)
+    } else {
+        offset.clamp(min, max)
+    }
+}
+
+/// Deferred popup content builders, keyed by module ID.
+///
+/// Each builder is removed when taken,
+/// so runs at most once.
+#[derive(Default)]
+pub struct LazyBuilders(HashMap<usize, Box<dyn FnOnce()>>);
+
+impl LazyBuilders {
+    pub fn insert(&mut self, key: usize, builder: Box<dyn FnOnce()>) {
+        self.0.insert(key, builder);
+    }
+
+    /// Takes the builder for `key`,
+    /// if it exists and has not already been taken.
+    pub fn take(&mut self, key: usize) -> Option<Box<dyn FnOnce()>> {
+        self.0.remove(&key)
+    }
+}
+
+impl std::fmt::Debug for LazyBuilders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Popup {
     pub window: ApplicationWindow,
+    /// Wraps the current content to animate it in and out.
+    revealer: Revealer,
     pub container_cache: Rc<RefCell<HashMap<usize, PopupCacheValue>>>,
+    pub builders: Rc<RefCell<LazyBuilders>>,
     pub button_cache: Rc<RefCell<Vec<Button>>>,
     monitor: Monitor,
     pos: BarPosition,
     current_widget: Rc<RefCell<Option<(usize, usize)>>>,
+    /// Prevents the popup hiding when the pointer leaves it,
+    /// such as while a subpopup opened from it is in use.
+    keep_open: Rc<Cell<bool>>,
+    pointer: Rc<Cell<PointerLocation>>,
+    state: Rc<Cell<TransitionState>>,
+    animations_enabled: Rc<Cell<bool>>,
 }
 
 impl Popup {
@@ -75,51 +271,203 @@ impl Popup {
         );
         win.set_anchor(gtk_layer_shell::Edge::Right, pos == BarPosition::Right);
 
-        win.connect_leave_notify_event(move |win, ev| {
-            const THRESHOLD: f64 = 3.0;
+        // the popup asks for keyboard focus when it opens,
+        // but unlike exclusive mode this leaves the compositor free to move it elsewhere,
+        // and its own shortcuts keep working
+        win.set_keyboard_mode(KeyboardMode::OnDemand);
 
-            let (w, h) = win.size();
-            let (x, y) = ev.position();
+        let revealer = Revealer::new();
+        win.add(&revealer);
+        revealer.show();
 
-            // some child widgets trigger this event
-            // so check we're actually outside the window
-            let hide = match pos {
-                BarPosition::Top => {
-                    x < THRESHOLD || y > f64::from(h) - THRESHOLD || x > f64::from(w) - THRESHOLD
+        let popup = Self {
+            window: win,
+            revealer,
+            container_cache: rc_mut!(HashMap::new()),
+            builders: rc_mut!(LazyBuilders::default()),
+            button_cache: rc_mut!(vec![]),
+            monitor: module_info.monitor.clone(),
+            pos,
+            current_widget: rc_mut!(None),
+            keep_open: Rc::new(Cell::new(false)),
+            pointer: Rc::new(Cell::new(PointerLocation::Outside)),
+            state: Rc::new(Cell::new(TransitionState::Closed)),
+            animations_enabled: Rc::new(Cell::new(true)),
+        };
+
+        {
+            let popup_leave = popup.clone();
+            popup.window.connect_leave_notify_event(move |win, ev| {
+                const THRESHOLD: f64 = 3.0;
+
+                if popup_leave.keep_open.get() {
+                    return Propagation::Proceed;
                 }
-                BarPosition::Bottom => {
-                    x < THRESHOLD || y < THRESHOLD || x > f64::from(w) - THRESHOLD
+
+                let (w, h) = win.size();
+                let (x, y) = ev.position();
+
+                // some child widgets trigger this event
+                // so check we're actually outside the window
+                let hide = match pos {
+                    BarPosition::Top => {
+                        x < THRESHOLD
+                            || y > f64::from(h) - THRESHOLD
+                            || x > f64::from(w) - THRESHOLD
+                    }
+                    BarPosition::Bottom => {
+                        x < THRESHOLD || y < THRESHOLD || x > f64::from(w) - THRESHOLD
+                    }
+                    BarPosition::Left => {
+                        y < THRESHOLD
+                            || x > f64::from(w) - THRESHOLD
+                            || y > f64::from(h) - THRESHOLD
+                    }
+                    BarPosition::Right => {
+                        y < THRESHOLD || x < THRESHOLD || y > f64::from(h) - THRESHOLD
+                    }
+                };
+
+                if hide {
+                    popup_leave.hide();
                 }
-                BarPosition::Left => {
-                    y < THRESHOLD || x > f64::from(w) - THRESHOLD || y > f64::from(h) - THRESHOLD
+
+                Propagation::Proceed
+            });
+        }
+
+        {
+            let popup_key = popup.clone();
+            popup.window.connect_key_press_event(move |win, ev| {
+                match KeyAction::from_key(ev.keyval(), ev.state()) {
+                    Some(KeyAction::Close) => {
+                        popup_key.hide();
+                        Propagation::Stop
+                    }
+                    // once a widget has focus, GTK moves between widgets itself.
+                    // leaving it to GTK means sliders and text boxes still get their arrow keys.
+                    Some(KeyAction::FocusNext) if win.focused_widget().is_none() => {
+                        win.child_focus(DirectionType::TabForward);
+                        Propagation::Stop
+                    }
+                    Some(KeyAction::FocusPrevious) if win.focused_widget().is_none() => {
+                        win.child_focus(DirectionType::TabBackward);
+                        Propagation::Stop
+                    }
+                    _ => Propagation::Proceed,
                 }
-                BarPosition::Right => {
-                    y < THRESHOLD || x < THRESHOLD || y > f64::from(h) - THRESHOLD
+            });
+        }
+
+        {
+            let popup_revealed = popup.clone();
+            popup
+                .revealer
+                .connect_child_revealed_notify(move |revealer| {
+                    // notified as soon as the child starts to show when opening,
+                    // but not until it is fully hidden when closing
+                    if !revealer.is_child_revealed() {
+                        popup_revealed.finish_transition();
+                    }
+                });
+        }
+
+        popup.setup_auto_close();
+        popup
+    }
+
+    /// Tracks the pointer entering and leaving the popup,
+    /// closing it afterwards if the current widget has `auto_close` set.
+    fn setup_auto_close(&self) {
+        {
+            let pointer = self.pointer.clone();
+            self.window.connect_enter_notify_event(move |_, _| {
+                pointer.set(PointerLocation::Popup);
+                Propagation::Proceed
+            });
+        }
+
+        {
+            let popup = self.clone();
+            self.window.connect_leave_notify_event(move |_, ev| {
+                // moving onto a child widget still counts as inside
+                if ev.detail() != NotifyType::Inferior {
+                    popup.pointer.set(PointerLocation::Outside);
+                    popup.schedule_auto_close();
                 }
-            };
 
-            if hide {
-                win.hide();
-            }
+                Propagation::Proceed
+            });
+        }
+
+        {
+            let popup = self.clone();
+            self.window.connect_focus_out_event(move |_, _| {
+                popup.schedule_auto_close();
+                Propagation::Proceed
+            });
+        }
+    }
 
+    /// Tracks the pointer entering and leaving a button which opens the popup.
+    fn track_button(&self, button: &Button) {
+        {
+            let pointer = self.pointer.clone();
+            button.connect_enter_notify_event(move |_, _| {
+                pointer.set(PointerLocation::Button);
+                Propagation::Proceed
+            });
+        }
+
+        let popup = self.clone();
+        button.connect_leave_notify_event(move |_, _| {
+            popup.pointer.set(PointerLocation::Outside);
+            popup.schedule_auto_close();
             Propagation::Proceed
         });
+    }
 
-        Self {
-            window: win,
-            container_cache: rc_mut!(HashMap::new()),
-            button_cache: rc_mut!(vec![]),
-            monitor: module_info.monitor.clone(),
-            pos,
-            current_widget: rc_mut!(None),
-        }
+    /// Closes the popup after a short delay,
+    /// if it is set to auto-close and the pointer has not returned.
+    fn schedule_auto_close(&self) {
+        let popup = self.clone();
+        glib::timeout_add_local_once(AUTO_CLOSE_DELAY, move || {
+            let auto_close = popup.current_widget().is_some_and(|id| {
+                popup
+                    .container_cache
+                    .borrow()
+                    .get(&id)
+                    .is_some_and(|value| value.options.auto_close)
+            });
+
+            if popup.is_visible()
+                && should_auto_close(auto_close, popup.keep_open.get(), popup.pointer.get())
+            {
+                debug!("Auto-closing popup");
+                popup.hide();
+            }
+        });
     }
 
-    pub fn register_content(&self, key: usize, name: String, content: ModulePopupParts) {
+    pub fn register_content(
+        &self,
+        key: usize,
+        name: String,
+        content: ModulePopupParts,
+        options: PopupOptions,
+    ) {
         debug!("Registered popup content for #{}", key);
 
         for button in &content.buttons {
             button.ensure_popup_id();
+
+            if options.auto_close {
+                self.track_button(button);
+            }
+        }
+
+        if options.transition == Some(PopupTransition::Crossfade) {
+            self.setup_transparency();
         }
 
         let orientation = self.pos.orientation();
@@ -137,10 +485,13 @@ impl Popup {
                     trace!("Resized:  {}x{}", rect.width(), rect.height());
 
                     if let Some((widget_id, button_id)) = *current_widget.borrow() {
-                        if let Some(PopupCacheValue { .. }) = cache.borrow().get(&widget_id) {
+                        if let Some(PopupCacheValue { options, .. }) =
+                            cache.borrow().get(&widget_id)
+                        {
                             Self::set_position(
                                 &button_cache.borrow(),
                                 button_id,
+                                *options,
                                 orientation,
                                 &monitor,
                                 &window,
@@ -154,26 +505,78 @@ impl Popup {
             .borrow_mut()
             .append(&mut content.buttons.clone());
 
-        self.container_cache
-            .borrow_mut()
-            .insert(key, PopupCacheValue { name, content });
+        self.container_cache.borrow_mut().insert(
+            key,
+            PopupCacheValue {
+                name,
+                content,
+                options,
+            },
+        );
+    }
+
+    /// Removes the contents registered for `key`,
+    /// such as when its module is torn down.
+    /// The popup is closed if it is showing them.
+    pub fn unregister_content(&self, key: usize) {
+        if self.current_widget() == Some(key) {
+            self.hide();
+        }
+
+        self.builders.borrow_mut().take(key);
+
+        let removed = self.container_cache.borrow_mut().remove(&key);
+        if let Some(PopupCacheValue { content, .. }) = removed {
+            debug!("Unregistered popup content for #{}", key);
+
+            self.button_cache
+                .borrow_mut()
+                .retain(|button| !content.buttons.contains(button));
+        }
+    }
+
+    /// Registers a function to build the contents of the popup for `key`
+    /// the first time it is shown.
+    pub fn register_builder<F>(&self, key: usize, builder: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        debug!("Registered lazy popup content for #{}", key);
+        self.builders.borrow_mut().insert(key, Box::new(builder));
+    }
+
+    /// Builds the contents of the popup for `key`,
+    /// if it was registered lazily and has not been built yet.
+    fn build_content(&self, key: usize) {
+        // release the borrow before building,
+        // in case the builder registers further content.
+        let builder = self.builders.borrow_mut().take(key);
+
+        if let Some(builder) = builder {
+            debug!("Building popup content for #{}", key);
+            builder();
+        }
     }
 
     pub fn show(&self, widget_id: usize, button_id: usize) {
         self.clear_window();
+        self.build_content(widget_id);
 
-        if let Some(PopupCacheValue { content, .. }) = self.container_cache.borrow().get(&widget_id)
+        if let Some(PopupCacheValue {
+            content, options, ..
+        }) = self.container_cache.borrow().get(&widget_id)
         {
             *self.current_widget.borrow_mut() = Some((widget_id, button_id));
 
             content.container.add_class("popup");
-            self.window.add(&content.container);
+            self.revealer.add(&content.container);
 
-            self.window.show();
+            self.open(*options);
 
             Self::set_position(
                 &self.button_cache.borrow(),
                 button_id,
+                *options,
                 self.pos.orientation(),
                 &self.monitor,
                 &self.window,
@@ -183,15 +586,19 @@ impl Popup {
 
     pub fn show_at(&self, widget_id: usize, geometry: WidgetGeometry) {
         self.clear_window();
+        self.build_content(widget_id);
 
-        if let Some(PopupCacheValue { content, .. }) = self.container_cache.borrow().get(&widget_id)
+        if let Some(PopupCacheValue {
+            content, options, ..
+        }) = self.container_cache.borrow().get(&widget_id)
         {
             content.container.add_class("popup");
-            self.window.add(&content.container);
+            self.revealer.add(&content.container);
 
-            self.window.show();
+            self.open(*options);
             Self::set_pos(
                 geometry,
+                *options,
                 self.pos.orientation(),
                 &self.monitor,
                 &self.window,
@@ -202,6 +609,7 @@ impl Popup {
     fn set_position(
         buttons: &[Button],
         button_id: usize,
+        options: PopupOptions,
         orientation: Orientation,
         monitor: &Monitor,
         window: &ApplicationWindow,
@@ -212,25 +620,214 @@ impl Popup {
             .expect("to find valid button");
 
         let geometry = button.geometry(orientation);
-        Self::set_pos(geometry, orientation, monitor, window);
+        Self::set_pos(geometry, options, orientation, monitor, window);
     }
 
     fn clear_window(&self) {
-        let children = self.window.children();
+        let children = self.revealer.children();
         for child in children {
-            self.window.remove(&child);
+            self.revealer.remove(&child);
         }
     }
 
-    /// Hides the popup
+    /// Shows the window and starts the open animation.
+    fn open(&self, options: PopupOptions) {
+        let transition = if self.animations_enabled.get() {
+            options.transition
+        } else {
+            None
+        };
+
+        self.revealer
+            .set_transition_type(revealer_transition(transition, self.pos));
+        self.revealer
+            .set_transition_duration(options.transition_duration);
+
+        self.state.set(self.state.get().open());
+
+        self.window.set_focus(None::<&Widget>);
+        self.window.show();
+        self.window.present();
+        self.revealer.set_reveal_child(true);
+    }
+
+    /// Hides the popup.
+    ///
+    /// If the popup is animated, the window is kept
+    /// until the close animation finishes.
     pub fn hide(&self) {
         *self.current_widget.borrow_mut() = None;
-        self.window.hide();
+
+        let animated = self.revealer.transition_type() != RevealerTransitionType::None
+            && self.revealer.transition_duration() > 0;
+
+        let state = self.state.get().close(animated);
+        self.state.set(state);
+
+        self.revealer.set_reveal_child(false);
+
+        if state == TransitionState::Closed {
+            self.window.hide();
+        }
+    }
+
+    /// Hides the window once the close animation has finished,
+    /// unless the popup was re-opened in the meantime.
+    fn finish_transition(&self) {
+        let state = self.state.get().finish();
+        self.state.set(state);
+
+        if state == TransitionState::Closed {
+            self.window.hide();
+        }
+    }
+
+    /// Sets whether popups animate when opening and closing.
+    pub fn set_animations_enabled(&self, enabled: bool) {
+        self.animations_enabled.set(enabled);
+    }
+
+    /// Whether animations are enabled on this bar.
+    pub fn animations_enabled(&self) -> bool {
+        self.animations_enabled.get()
+    }
+
+    /// Lets the desktop show through the window as the content crossfades.
+    ///
+    /// This must happen before the window is first shown,
+    /// and means the window no longer paints its own background.
+    fn setup_transparency(&self) {
+        if self.window.is_realized() || self.window.is_app_paintable() {
+            return;
+        }
+
+        let visual = WidgetExt::screen(&self.window)
+            .filter(Screen::is_composited)
+            .and_then(|screen| screen.rgba_visual());
+
+        let Some(visual) = visual else {
+            warn!("Compositor does not support transparency, popups will not fade");
+            return;
+        };
+
+        self.window.set_visual(Some(&visual));
+        self.window.set_app_paintable(true);
+    }
+
+    /// Toggles the popup for the module with the given name.
+    /// If the popup is open for a different module, it is closed first.
+    ///
+    /// Errors with a user-facing message if the module does not exist,
+    /// or has no popup.
+    pub fn toggle_named(&self, name: &str) -> Result<(), &'static str> {
+        let current_widget = self.current_widget();
+        self.hide();
+
+        match self.find_named(name)? {
+            (id, button_id) if current_widget != Some(id) => {
+                self.show(id, button_id);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Opens the popup for the module with the given name,
+    /// regardless of its current state.
+    ///
+    /// Errors with a user-facing message if the module does not exist,
+    /// or has no popup.
+    pub fn open_named(&self, name: &str) -> Result<(), &'static str> {
+        // only one popup per bar, so hide if open for another widget
+        self.hide();
+
+        let (id, button_id) = self.find_named(name)?;
+        self.show(id, button_id);
+
+        Ok(())
     }
 
-    /// Checks if the popup is currently visible
+    /// Gets the module ID and popup button ID
+    /// for the module with the given name.
+    fn find_named(&self, name: &str) -> Result<(usize, usize), &'static str> {
+        let data = self
+            .container_cache
+            .borrow()
+            .iter()
+            .find(|(_, value)| value.name == name)
+            .map(|(id, value)| (*id, value.content.buttons.first().cloned()));
+
+        match data {
+            Some((id, Some(button))) => Ok((id, button.popup_id())),
+            Some((_, None)) => Err("Module has no popup functionality"),
+            None => Err("Invalid module name"),
+        }
+    }
+
+    /// Sets whether the popup should stay open
+    /// when the pointer leaves it.
+    pub fn set_keep_open(&self, keep_open: bool) {
+        self.keep_open.set(keep_open);
+    }
+
+    /// Sets the gap between the bar and the popup.
+    pub fn set_gap(&self, gap: i32) {
+        self.window.set_layer_shell_margin(self.bar_edge(), gap);
+    }
+
+    /// Gets the gap between the bar and the popup.
+    pub fn gap(&self) -> i32 {
+        self.window.layer_shell_margin(self.bar_edge())
+    }
+
+    /// Gets the distance between the bar and the far edge of the popup,
+    /// for placing another popup beyond it.
+    pub fn outer_gap(&self) -> i32 {
+        let (width, height) = self.window.size();
+        let size = if self.pos.orientation() == Orientation::Horizontal {
+            height
+        } else {
+            width
+        };
+
+        self.gap() + size
+    }
+
+    /// Gets the geometry of a widget inside the popup,
+    /// for positioning another popup against it.
+    ///
+    /// Unlike widgets on the bar, the position is relative to the screen edge.
+    pub fn child_geometry(&self, widget: &impl IsA<Widget>) -> WidgetGeometry {
+        let orientation = self.pos.orientation();
+        let geometry = widget.geometry(orientation);
+
+        let (edge, screen_size) = if orientation == Orientation::Horizontal {
+            (gtk_layer_shell::Edge::Left, self.monitor.workarea().width())
+        } else {
+            (gtk_layer_shell::Edge::Top, self.monitor.workarea().height())
+        };
+
+        WidgetGeometry {
+            position: self.window.layer_shell_margin(edge) + geometry.position,
+            size: geometry.size,
+            bar_size: screen_size,
+        }
+    }
+
+    /// Gets the screen edge the bar is on.
+    const fn bar_edge(&self) -> gtk_layer_shell::Edge {
+        match self.pos {
+            BarPosition::Top => gtk_layer_shell::Edge::Top,
+            BarPosition::Bottom => gtk_layer_shell::Edge::Bottom,
+            BarPosition::Left => gtk_layer_shell::Edge::Left,
+            BarPosition::Right => gtk_layer_shell::Edge::Right,
+        }
+    }
+
+    /// Checks if the popup is currently visible.
+    /// This is `false` while the close animation runs.
     pub fn is_visible(&self) -> bool {
-        self.window.is_visible()
+        self.state.get().is_open()
     }
 
     pub fn current_widget(&self) -> Option<usize> {
@@ -241,6 +838,7 @@ impl Popup {
     /// (depending on orientation).
     fn set_pos(
         geometry: WidgetGeometry,
+        options: PopupOptions,
         orientation: Orientation,
         monitor: &Monitor,
         window: &ApplicationWindow,
@@ -259,17 +857,7 @@ impl Popup {
             popup_height
         };
 
-        let widget_center = f64::from(geometry.position) + f64::from(geometry.size) / 2.0;
-
-        let bar_offset = (f64::from(screen_size) - f64::from(geometry.bar_size)) / 2.0;
-
-        let mut offset = bar_offset + (widget_center - (f64::from(popup_size) / 2.0)).round();
-
-        if offset < 5.0 {
-            offset = 5.0;
-        } else if offset > f64::from(screen_size - popup_size) - 5.0 {
-            offset = f64::from(screen_size - popup_size) - 5.0;
-        }
+        let offset = calculate_offset(geometry, popup_size, screen_size, options);
 
         let edge = if orientation == Orientation::Horizontal {
             gtk_layer_shell::Edge::Left
@@ -277,6 +865,242 @@ impl Popup {
             gtk_layer_shell::Edge::Top
         };
 
-        window.set_layer_shell_margin(edge, offset as i32);
+        window.set_layer_shell_margin(edge, offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const SCREEN: i32 = 1000;
+    const POPUP: i32 = 200;
+
+    fn offset(position: i32, anchor: PopupAnchor, offset: i32) -> i32 {
+        let geometry = WidgetGeometry {
+            position,
+            size: 50,
+            bar_size: SCREEN,
+        };
+
+        let options = PopupOptions {
+            anchor,
+            offset,
+            ..PopupOptions::default()
+        };
+
+        calculate_offset(geometry, POPUP, SCREEN, options)
+    }
+
+    #[test]
+    fn test_popup_anchor() {
+        assert_eq!(offset(400, PopupAnchor::Start, 0), 400);
+        assert_eq!(offset(400, PopupAnchor::Center, 0), 325);
+        assert_eq!(offset(400, PopupAnchor::End, 0), 250);
+    }
+
+    #[test]
+    fn test_popup_offset() {
+        assert_eq!(offset(400, PopupAnchor::Start, 20), 420);
+        assert_eq!(offset(400, PopupAnchor::End, -30), 220);
+    }
+
+    #[test]
+    fn test_popup_clamped_to_screen() {
+        // widget at the far end of the bar
+        assert_eq!(
+            offset(950, PopupAnchor::Start, 0),
+            SCREEN - POPUP - SCREEN_EDGE_MARGIN
+        );
+        assert_eq!(
+            offset(950, PopupAnchor::Center, 0),
+            SCREEN - POPUP - SCREEN_EDGE_MARGIN
+        );
+
+        // widget at the start of the bar
+        assert_eq!(offset(0, PopupAnchor::End, 0), SCREEN_EDGE_MARGIN);
+        assert_eq!(offset(0, PopupAnchor::Center, -100), SCREEN_EDGE_MARGIN);
+    }
+
+    #[test]
+    fn test_popup_on_shorter_bar() {
+        // bar is centered on the screen, so starts at 250
+        let geometry = WidgetGeometry {
+            position: 450,
+            size: 50,
+            bar_size: 500,
+        };
+
+        let options = PopupOptions {
+            anchor: PopupAnchor::Start,
+            ..PopupOptions::default()
+        };
+
+        assert_eq!(calculate_offset(geometry, POPUP, SCREEN, options), 700);
+
+        let options = PopupOptions {
+            anchor: PopupAnchor::Start,
+            offset: 200,
+            ..PopupOptions::default()
+        };
+
+        assert_eq!(
+            calculate_offset(geometry, POPUP, SCREEN, options),
+            SCREEN - POPUP - SCREEN_EDGE_MARGIN
+        );
+    }
+
+    #[test]
+    fn test_popup_larger_than_screen() {
+        let geometry = WidgetGeometry {
+            position: 100,
+            size: 50,
+            bar_size: SCREEN,
+        };
+
+        assert_eq!(
+            calculate_offset(geometry, SCREEN - 4, SCREEN, PopupOptions::default()),
+            2
+        );
+        assert_eq!(
+            calculate_offset(geometry, SCREEN + 100, SCREEN, PopupOptions::default()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_key_actions() {
+        use keys::constants;
+
+        let none = ModifierType::empty();
+
+        assert_eq!(
+            KeyAction::from_key(constants::Escape, none),
+            Some(KeyAction::Close)
+        );
+        assert_eq!(
+            KeyAction::from_key(constants::Tab, none),
+            Some(KeyAction::FocusNext)
+        );
+        assert_eq!(
+            KeyAction::from_key(constants::Tab, ModifierType::SHIFT_MASK),
+            Some(KeyAction::FocusPrevious)
+        );
+        assert_eq!(
+            KeyAction::from_key(constants::ISO_Left_Tab, ModifierType::SHIFT_MASK),
+            Some(KeyAction::FocusPrevious)
+        );
+        assert_eq!(
+            KeyAction::from_key(constants::Down, none),
+            Some(KeyAction::FocusNext)
+        );
+        assert_eq!(
+            KeyAction::from_key(constants::Left, none),
+            Some(KeyAction::FocusPrevious)
+        );
+        assert_eq!(KeyAction::from_key(constants::a, none), None);
+        assert_eq!(KeyAction::from_key(constants::Return, none), None);
+    }
+
+    #[test]
+    fn test_auto_close_pointer_location() {
+        assert!(should_auto_close(true, false, PointerLocation::Outside));
+
+        // pointer is crossing between the popup and its button
+        assert!(!should_auto_close(true, false, PointerLocation::Button));
+        assert!(!should_auto_close(true, false, PointerLocation::Popup));
+    }
+
+    #[test]
+    fn test_auto_close_disabled() {
+        assert!(!should_auto_close(false, false, PointerLocation::Outside));
+
+        // a subpopup opened from this popup is in use
+        assert!(!should_auto_close(true, true, PointerLocation::Outside));
+    }
+
+    #[test]
+    fn test_close_waits_for_animation() {
+        let state = TransitionState::Closed.open();
+        assert!(state.is_open());
+
+        let state = state.close(true);
+        assert_eq!(state, TransitionState::Closing);
+        assert!(!state.is_open());
+
+        assert_eq!(state.finish(), TransitionState::Closed);
+    }
+
+    #[test]
+    fn test_close_without_animation() {
+        assert_eq!(TransitionState::Open.close(false), TransitionState::Closed);
+        assert_eq!(
+            TransitionState::Closing.close(false),
+            TransitionState::Closed
+        );
+
+        // nothing to animate if already closed
+        assert_eq!(TransitionState::Closed.close(true), TransitionState::Closed);
+    }
+
+    #[test]
+    fn test_reopen_while_closing() {
+        let state = TransitionState::Open.close(true).open();
+
+        // the interrupted close animation must not hide the window
+        assert_eq!(state.finish(), TransitionState::Open);
+    }
+
+    #[test]
+    fn test_slide_away_from_bar() {
+        let slide = Some(PopupTransition::Slide);
+
+        assert_eq!(
+            revealer_transition(slide, BarPosition::Top),
+            RevealerTransitionType::SlideDown
+        );
+        assert_eq!(
+            revealer_transition(slide, BarPosition::Right),
+            RevealerTransitionType::SlideLeft
+        );
+        assert_eq!(
+            revealer_transition(None, BarPosition::Top),
+            RevealerTransitionType::None
+        );
+    }
+
+    #[test]
+    fn test_lazy_builder_runs_once() {
+        let count = Rc::new(Cell::new(0));
+        let mut builders = LazyBuilders::default();
+
+        {
+            let count = count.clone();
+            builders.insert(1, Box::new(move || count.set(count.get() + 1)));
+        }
+
+        // simulate opening the popup several times
+        for _ in 0..3 {
+            if let Some(builder) = builders.take(1) {
+                builder();
+            }
+        }
+
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn test_lazy_builder_not_run_until_opened() {
+        let count = Rc::new(Cell::new(0));
+        let mut builders = LazyBuilders::default();
+
+        {
+            let count = count.clone();
+            builders.insert(1, Box::new(move || count.set(count.get() + 1)));
+        }
+
+        assert!(builders.take(2).is_none());
+        assert_eq!(count.get(), 0);
     }
 }
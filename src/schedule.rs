@@ -0,0 +1,188 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+/// Schedules polls at a fixed average rate.
+///
+/// Each poll can be delayed by a random amount up to the jitter,
+/// so that modules sharing an interval spread their polls out
+/// rather than all running at once.
+/// Delays are measured from the unjittered tick rather than the previous poll,
+/// so they do not build up and the average rate is unchanged.
+#[derive(Debug)]
+pub struct Schedule {
+    interval: Duration,
+    jitter: Duration,
+    start: Instant,
+    /// Offset from the start of the latest unjittered tick.
+    tick: Duration,
+    rng: Rng,
+}
+
+impl Schedule {
+    /// Creates a schedule starting now.
+    /// The jitter is capped at the interval.
+    pub fn new(interval: Duration, jitter: Duration) -> Self {
+        Self::with_rng(interval, jitter, Rng::new())
+    }
+
+    fn with_rng(interval: Duration, jitter: Duration, rng: Rng) -> Self {
+        Self {
+            interval,
+            jitter: jitter.min(interval),
+            start: Instant::now(),
+            tick: Duration::ZERO,
+            rng,
+        }
+    }
+
+    /// Waits until the next poll is due.
+    pub async fn tick(&mut self) {
+        let offset = self.next_offset(self.start.elapsed());
+        sleep_until(self.start + offset).await;
+    }
+
+    /// Gets the offset from the start of the next poll,
+    /// given the time `elapsed` since the start.
+    ///
+    /// Any ticks which have already passed are skipped,
+    /// rather than being run back-to-back to catch up.
+    fn next_offset(&mut self, elapsed: Duration) -> Duration {
+        if self.interval.is_zero() {
+            return elapsed;
+        }
+
+        loop {
+            self.tick += self.interval;
+
+            let offset = self.tick + self.rng.duration(self.jitter);
+            if offset >= elapsed {
+                return offset;
+            }
+        }
+    }
+}
+
+/// Xorshift generator.
+/// This is nowhere near good enough for anything security-related,
+/// but plenty for spreading out polls.
+#[derive(Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        // the standard library seeds each `RandomState` randomly
+        let seed = RandomState::new().build_hasher().finish();
+        Self::seeded(seed)
+    }
+
+    const fn seeded(seed: u64) -> Self {
+        // xorshift gets stuck at zero
+        Self(seed | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// Gets a random duration between zero and `max` inclusive.
+    fn duration(&mut self, max: Duration) -> Duration {
+        if max.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let max = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+        Duration::from_nanos(self.next_u64() % max.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn schedule(interval: Duration, jitter: Duration) -> Schedule {
+        Schedule::with_rng(interval, jitter, Rng::seeded(0x5EED))
+    }
+
+    /// Gets the offsets of the first `ticks` polls,
+    /// assuming each poll runs instantly.
+    fn offsets(schedule: &mut Schedule, ticks: u32) -> Vec<Duration> {
+        let mut elapsed = Duration::ZERO;
+
+        (0..ticks)
+            .map(|_| {
+                elapsed = schedule.next_offset(elapsed);
+                elapsed
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_no_jitter() {
+        let mut schedule = schedule(SECOND, Duration::ZERO);
+
+        assert_eq!(offsets(&mut schedule, 3), [SECOND, SECOND * 2, SECOND * 3]);
+    }
+
+    #[test]
+    fn test_jitter_within_rate_bounds() {
+        const TICKS: u32 = 10_000;
+
+        let jitter = Duration::from_millis(300);
+        let mut schedule = schedule(SECOND, jitter);
+        let offsets = offsets(&mut schedule, TICKS);
+
+        let mut previous = Duration::ZERO;
+        for (tick, &offset) in (1..).zip(&offsets) {
+            assert!(offset >= SECOND * tick);
+            assert!(offset <= SECOND * tick + jitter);
+
+            let gap = offset - previous;
+            assert!(gap >= SECOND - jitter && gap <= SECOND + jitter);
+            previous = offset;
+        }
+
+        // the average rate does not drift
+        let average = offsets[offsets.len() - 1] / TICKS;
+        assert!(average >= SECOND);
+        assert!(average <= SECOND + jitter / TICKS);
+
+        // and polls are actually spread out
+        let distinct = offsets
+            .iter()
+            .map(|offset| offset.subsec_millis())
+            .collect::<std::collections::HashSet<_>>();
+        assert!(distinct.len() > 100);
+    }
+
+    #[test]
+    fn test_jitter_capped_at_interval() {
+        let mut schedule = schedule(SECOND, SECOND * 10);
+
+        for (tick, offset) in (1..).zip(offsets(&mut schedule, 1000)) {
+            assert!(offset <= SECOND * tick + SECOND);
+        }
+    }
+
+    #[test]
+    fn test_skips_missed_ticks() {
+        let mut schedule = schedule(SECOND, Duration::ZERO);
+
+        assert_eq!(schedule.next_offset(SECOND * 3 + SECOND / 2), SECOND * 4);
+        assert_eq!(schedule.next_offset(SECOND * 4), SECOND * 5);
+    }
+
+    #[test]
+    fn test_zero_interval() {
+        let mut schedule = schedule(Duration::ZERO, SECOND);
+        assert_eq!(schedule.next_offset(SECOND), SECOND);
+    }
+}
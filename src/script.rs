@@ -1,29 +1,72 @@
+use crate::schedule::Schedule;
 use crate::{send_async, spawn};
 use color_eyre::eyre::WrapErr;
-use color_eyre::{Report, Result};
+use color_eyre::{Help, Report, Result};
+use notify::{recommended_watcher, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use std::cmp::min;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use std::future::pending;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, Command};
 use tokio::select;
-use tokio::sync::mpsc;
-use tokio::time::sleep;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, timeout};
 use tracing::{debug, error, trace, warn};
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 #[serde(untagged)]
 pub enum ScriptInput {
     String(String),
-    Struct(Script),
+    Struct(Box<Script>),
 }
 
-#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ScriptMode {
     Poll,
     Watch,
+    /// Like `Watch`, but the process is respawned with a backoff if it fails.
+    Stream,
+}
+
+/// The captured result of a finished script.
+#[derive(Debug, Clone)]
+pub struct ScriptOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+impl ScriptOutput {
+    /// Describes why the script failed,
+    /// including its exit code and `stderr`.
+    pub fn error_message(&self, cmd: &str) -> String {
+        let reason = failure_reason(self.status);
+
+        if self.stderr.is_empty() {
+            format!("Script '{cmd}' {reason}")
+        } else {
+            format!("Script '{cmd}' {reason}: {}", self.stderr)
+        }
+    }
+}
+
+/// Describes why a script with the given exit status failed.
+fn failure_reason(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exited with code {code}"),
+        None => "was terminated by a signal".to_string(),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,11 +75,73 @@ pub enum OutputStream {
     Stderr(String),
 }
 
+/// How the output of a script is interpreted.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// The output is used as-is.
+    #[default]
+    Plain,
+    /// Each output is parsed as a [`JsonOutput`] object.
+    Json,
+}
+
+/// Structured script output,
+/// used when the output format is set to `json`.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct JsonOutput {
+    /// Text to display.
+    pub text: Option<String>,
+    /// Tooltip to show on hover.
+    pub tooltip: Option<String>,
+    /// CSS classes to apply.
+    /// Accepts either a list or a space-separated string.
+    #[serde(default, deserialize_with = "deserialize_classes")]
+    pub class: Vec<String>,
+    /// Percentage between 0-100, used for progress bars.
+    pub percentage: Option<f64>,
+}
+
+impl JsonOutput {
+    /// Creates a new output
+    /// containing only text.
+    pub fn text(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Self::default()
+        }
+    }
+
+    /// Parses a single JSON object from the script output.
+    pub fn parse(output: &str) -> Result<Self> {
+        serde_json::from_str(output)
+            .wrap_err_with(|| format!("Failed to parse script output as JSON: '{output}'"))
+    }
+}
+
+fn deserialize_classes<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Classes {
+        Single(String),
+        Multiple(Vec<String>),
+    }
+
+    Ok(match Classes::deserialize(deserializer)? {
+        Classes::Single(classes) => classes.split_whitespace().map(str::to_string).collect(),
+        Classes::Multiple(classes) => classes,
+    })
+}
+
 impl From<&str> for ScriptMode {
     fn from(str: &str) -> Self {
         match str {
             "poll" | "p" => Self::Poll,
             "watch" | "w" => Self::Watch,
+            "stream" | "s" => Self::Stream,
             _ => {
                 warn!("Invalid script mode: '{str}', falling back to polling");
                 Self::Poll
@@ -59,6 +164,7 @@ impl Display for ScriptMode {
             match self {
                 Self::Poll => "poll",
                 Self::Watch => "watch",
+                Self::Stream => "stream",
             }
         )
     }
@@ -69,30 +175,224 @@ impl ScriptMode {
         match str {
             "poll" | "p" => Ok(Self::Poll),
             "watch" | "w" => Ok(Self::Watch),
+            "stream" | "s" => Ok(Self::Stream),
             _ => Err(Report::msg(format!("Invalid script mode: {str}"))),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Script {
     #[serde(default = "ScriptMode::default")]
     pub(crate) mode: ScriptMode,
     pub cmd: String,
     #[serde(default = "default_interval")]
     pub(crate) interval: u64,
+    /// Maximum time in milliseconds to randomly delay each run by,
+    /// so that scripts sharing an interval do not all run at once.
+    #[serde(default)]
+    pub(crate) jitter: u64,
+    /// Path to watch for changes.
+    /// When set, the script re-runs when the path changes
+    /// instead of on an interval.
+    pub(crate) watch_path: Option<PathBuf>,
+    /// Real-time signal to listen for.
+    /// When set, the script re-runs when the signal is received
+    /// instead of on an interval.
+    pub(crate) signal: Option<RealtimeSignal>,
+    /// Whether to run the script only once on startup,
+    /// rather than re-running it on an interval.
+    /// It can still be re-run by a refresh, path change or signal.
+    #[serde(default)]
+    pub(crate) once: bool,
+    /// Environment variables to set on the script process,
+    /// in addition to those Ironbar is running with.
+    #[serde(default)]
+    pub(crate) env: HashMap<String, String>,
+    /// Directory to run the script in.
+    /// Defaults to Ironbar's working directory.
+    pub(crate) cwd: Option<PathBuf>,
+    /// Maximum time in milliseconds a polling script may run for
+    /// before it is killed.
+    pub(crate) timeout: Option<u64>,
+    /// Post-processing to apply to numeric output.
+    pub(crate) transform: Option<Transform>,
+}
+
+/// Post-processing applied to numeric script output,
+/// in the order the fields are listed.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Transform {
+    /// Factor to multiply the value by.
+    multiply: Option<f64>,
+    /// Factor to divide the value by.
+    /// Zero is ignored.
+    divide: Option<f64>,
+    /// Lowest value to output.
+    min: Option<f64>,
+    /// Highest value to output.
+    max: Option<f64>,
+    /// Number of decimal places to round to.
+    round: Option<usize>,
+    /// Text to append to the value, such as a unit.
+    suffix: Option<String>,
+}
+
+impl Transform {
+    /// Applies the transform to a line of output.
+    ///
+    /// Output which is not a number is returned unchanged,
+    /// and a warning is logged.
+    pub fn apply(&self, output: &str) -> String {
+        let Ok(mut value) = output.trim().parse::<f64>() else {
+            warn!("Unable to transform non-numeric script output: '{output}'");
+            return output.to_string();
+        };
+
+        if let Some(factor) = self.multiply {
+            value *= factor;
+        }
+
+        if let Some(factor) = self.divide.filter(|&factor| factor != 0.0) {
+            value /= factor;
+        }
+
+        if let Some(min) = self.min {
+            value = value.max(min);
+        }
+
+        if let Some(max) = self.max {
+            value = value.min(max);
+        }
+
+        let mut output = match self.round {
+            Some(places) => format!("{value:.places$}"),
+            None => value.to_string(),
+        };
+
+        if let Some(suffix) = &self.suffix {
+            output.push_str(suffix);
+        }
+
+        output
+    }
 }
 
 const fn default_interval() -> u64 {
     5000
 }
 
+/// Time to wait after a path change for further changes
+/// before re-running the script.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Time to wait after sending `SIGTERM` to a timed-out script
+/// before sending `SIGKILL`.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Time to wait before respawning a failed streaming script.
+/// This doubles on each consecutive failure, up to the maximum.
+const STREAM_BACKOFF_INITIAL: Duration = Duration::from_secs(1);
+const STREAM_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// A real-time signal, stored as an offset from `SIGRTMIN`.
+///
+/// Can be written as either the offset (`5`),
+/// or in the form `RTMIN+5` or `SIGRTMIN+5`.
+#[derive(Debug, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(try_from = "RealtimeSignalInput")]
+pub struct RealtimeSignal(i32);
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RealtimeSignalInput {
+    Offset(i32),
+    Name(String),
+}
+
+impl JsonSchema for RealtimeSignal {
+    fn schema_name() -> String {
+        "RealtimeSignal".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RealtimeSignalInput::json_schema(gen)
+    }
+}
+
+impl TryFrom<RealtimeSignalInput> for RealtimeSignal {
+    type Error = Report;
+
+    fn try_from(input: RealtimeSignalInput) -> Result<Self> {
+        match input {
+            RealtimeSignalInput::Offset(offset) => Self::new(offset),
+            RealtimeSignalInput::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl RealtimeSignal {
+    /// Creates a new signal at `SIGRTMIN+offset`,
+    /// checking it does not exceed `SIGRTMAX`.
+    pub fn new(offset: i32) -> Result<Self> {
+        let max = libc::SIGRTMAX() - libc::SIGRTMIN();
+
+        if (0..=max).contains(&offset) {
+            Ok(Self(offset))
+        } else {
+            Err(
+                Report::msg(format!("Real-time signal offset {offset} is out of range"))
+                    .suggestion(format!("Use an offset between 0 and {max}")),
+            )
+        }
+    }
+
+    /// Gets the actual signal number.
+    pub fn number(self) -> i32 {
+        libc::SIGRTMIN() + self.0
+    }
+}
+
+impl FromStr for RealtimeSignal {
+    type Err = Report;
+
+    fn from_str(str: &str) -> Result<Self> {
+        let offset = str.trim();
+        let offset = offset.strip_prefix("SIG").unwrap_or(offset);
+
+        let offset = match offset.strip_prefix("RTMIN") {
+            Some("") => "0",
+            Some(offset) => offset.strip_prefix('+').ok_or_else(|| {
+                Report::msg(format!("Invalid real-time signal: '{str}'"))
+                    .suggestion("Use the form `RTMIN+n`")
+            })?,
+            None => offset,
+        };
+
+        let offset = offset
+            .parse()
+            .wrap_err_with(|| format!("Invalid real-time signal: '{str}'"))?;
+
+        Self::new(offset)
+    }
+}
+
 impl Default for Script {
     fn default() -> Self {
         Self {
             mode: ScriptMode::default(),
             interval: default_interval(),
+            jitter: 0,
             cmd: String::new(),
+            watch_path: None,
+            signal: None,
+            once: false,
+            env: HashMap::new(),
+            cwd: None,
+            timeout: None,
+            transform: None,
         }
     }
 }
@@ -101,7 +401,7 @@ impl From<ScriptInput> for Script {
     fn from(input: ScriptInput) -> Self {
         match input {
             ScriptInput::String(string) => Self::from(string.as_str()),
-            ScriptInput::Struct(script) => script,
+            ScriptInput::Struct(script) => *script,
         }
     }
 }
@@ -135,7 +435,7 @@ impl From<&str> for Script {
                 CurrentToken::Mode => {
                     current_state = CurrentToken::Interval;
 
-                    if matches!(char, 'p' | 'w') {
+                    if matches!(char, 'p' | 'w' | 's') {
                         let mode_str = chars.iter().take_while(|&c| c != &':').collect::<String>();
                         let len = mode_str.len();
 
@@ -195,11 +495,76 @@ impl Script {
 
     /// Runs the script, passing `args` if provided.
     /// Runs `f`, passing the output stream and whether the command returned 0.
+    ///
+    /// The script runs straight away,
+    /// then is re-run after each interval,
+    /// or when its path or signal trigger fires if either is set.
+    /// Any watchers are owned by the returned future,
+    /// so are torn down when it is dropped.
     pub async fn run<F>(&self, args: Option<&[String]>, callback: F)
     where
         F: Fn(OutputStream, bool),
     {
+        self.run_refreshable(args, None, callback).await;
+    }
+
+    /// Runs the script as [`Script::run`] does,
+    /// also re-running it each time `refresh` receives.
+    ///
+    /// Refreshes requested while the script is already running are dropped,
+    /// rather than running it again straight after.
+    pub async fn run_refreshable<F>(
+        &self,
+        args: Option<&[String]>,
+        refresh: Option<mpsc::Receiver<()>>,
+        callback: F,
+    ) where
+        F: Fn(OutputStream, bool),
+    {
+        self.run_gated(args, refresh, None, callback).await;
+    }
+
+    /// Runs the script as [`Script::run`] does,
+    /// but only while `visible` is `true`.
+    ///
+    /// The first run is deferred until `visible` is first `true`.
+    /// Runs which fall due while it is `false` are held back,
+    /// and the script is run straight away once it is `true` again.
+    ///
+    /// Only runs are gated, so this is only useful for polling scripts.
+    /// Watch and stream mode processes are not stopped once started,
+    /// and keep running while `visible` is `false`.
+    pub async fn run_when_visible<F>(
+        &self,
+        args: Option<&[String]>,
+        visible: watch::Receiver<bool>,
+        callback: F,
+    ) where
+        F: Fn(OutputStream, bool),
+    {
+        self.run_gated(args, None, Some(VisibilityGate::new(visible)), callback)
+            .await;
+    }
+
+    async fn run_gated<F>(
+        &self,
+        args: Option<&[String]>,
+        refresh: Option<mpsc::Receiver<()>>,
+        mut gate: Option<VisibilityGate>,
+        callback: F,
+    ) where
+        F: Fn(OutputStream, bool),
+    {
+        let mut trigger = Trigger::new(self, refresh);
+        let mut backoff = Backoff::new(STREAM_BACKOFF_INITIAL, STREAM_BACKOFF_MAX);
+
+        let callback = |output, success| callback(self.transform_output(output), success);
+
         loop {
+            if let Some(gate) = &mut gate {
+                gate.wait().await;
+            }
+
             match self.mode {
                 ScriptMode::Poll => match self.get_output(args).await {
                     Ok(output) => callback(output.0, output.1),
@@ -207,25 +572,82 @@ impl Script {
                 },
                 ScriptMode::Watch => match self.spawn() {
                     Ok(mut rx) => {
-                        while let Some(msg) = rx.recv().await {
-                            callback(msg, true);
+                        while let Some((msg, success)) = rx.recv().await {
+                            callback(msg, success);
                         }
                     }
                     Err(err) => error!("{err:?}"),
                 },
+                ScriptMode::Stream => self.stream(args, &callback, &mut backoff).await,
             };
 
-            sleep(tokio::time::Duration::from_millis(self.interval)).await;
+            trigger.discard_refreshes();
+            trigger.wait().await;
+        }
+    }
+
+    /// Applies the transform, if any, to `stdout`.
+    fn transform_output(&self, output: OutputStream) -> OutputStream {
+        match (output, &self.transform) {
+            (OutputStream::Stdout(out), Some(transform)) => {
+                OutputStream::Stdout(transform.apply(&out))
+            }
+            (output, _) => output,
+        }
+    }
+
+    /// Creates the base `sh` command,
+    /// with the configured environment and working directory applied.
+    fn command(&self) -> Result<Command> {
+        let mut command = Command::new("/bin/sh");
+        command.envs(&self.env);
+
+        if let Some(cwd) = &self.cwd {
+            if !cwd.is_dir() {
+                return Err(Report::msg(format!(
+                    "Script working directory '{}' does not exist",
+                    cwd.display()
+                ))
+                .note(format!("Script: '{}'", self.cmd))
+                .suggestion("Check the `cwd` option points to a directory"));
+            }
+
+            command.current_dir(cwd);
         }
+
+        Ok(command)
     }
 
     /// Attempts to execute a given command,
     /// waiting for it to finish.
     /// If the command returns status 0,
-    /// the `stdout` is returned.
+    /// the `stdout` is returned,
+    /// and anything written to `stderr` is logged as a warning.
     /// Otherwise, an `Err` variant
-    /// containing the `stderr` is returned.
+    /// containing the exit code and `stderr` is returned.
     pub async fn get_output(&self, args: Option<&[String]>) -> Result<(OutputStream, bool)> {
+        let output = self.execute(args).await?;
+
+        if output.status.success() {
+            if !output.stderr.is_empty() {
+                warn!("Script '{}' wrote to stderr: {}", self.cmd, output.stderr);
+            }
+
+            debug!("sending stdout: '{}'", output.stdout);
+
+            Ok((OutputStream::Stdout(output.stdout), true))
+        } else {
+            let message = output.error_message(&self.cmd);
+
+            debug!("sending stderr: '{message}'");
+
+            Ok((OutputStream::Stderr(message), false))
+        }
+    }
+
+    /// Executes the command, waiting for it to finish,
+    /// and captures `stdout` and `stderr` separately.
+    pub async fn execute(&self, args: Option<&[String]>) -> Result<ScriptOutput> {
         let mut args_list = vec!["-c", &self.cmd];
 
         if let Some(args) = args {
@@ -234,38 +656,142 @@ impl Script {
 
         debug!("Running sh with args: {args_list:?}");
 
-        let output = Command::new("/bin/sh")
-            .args(&args_list)
-            .output()
-            .await
-            .wrap_err("Failed to get script output")?;
+        let mut command = self.command()?;
+        command.args(&args_list);
+
+        // if the module is torn down mid-run, the script should not outlive it
+        command.kill_on_drop(true);
+
+        let output = match self.timeout {
+            Some(limit) => {
+                self.output_with_timeout(command, Duration::from_millis(limit))
+                    .await
+            }
+            None => command
+                .output()
+                .await
+                .wrap_err("Failed to get script output"),
+        }?;
 
         trace!("Script output with args: {output:?}");
 
-        if output.status.success() {
-            let stdout = String::from_utf8(output.stdout)
-                .map(|output| output.trim().to_string())
-                .wrap_err("Script stdout not valid UTF-8")?;
+        let stdout = String::from_utf8(output.stdout)
+            .map(|output| output.trim().to_string())
+            .wrap_err("Script stdout not valid UTF-8")?;
 
-            debug!("sending stdout: '{stdout}'");
+        let stderr = String::from_utf8(output.stderr)
+            .map(|output| output.trim().to_string())
+            .wrap_err("Script stderr not valid UTF-8")?;
 
-            Ok((OutputStream::Stdout(stdout), true))
-        } else {
-            let stderr = String::from_utf8(output.stderr)
-                .map(|output| output.trim().to_string())
-                .wrap_err("Script stderr not valid UTF-8")?;
+        Ok(ScriptOutput {
+            stdout,
+            stderr,
+            status: output.status,
+        })
+    }
+
+    /// Runs the command to completion, capturing its output.
+    ///
+    /// If it does not exit within `limit`, it is killed and an error is returned.
+    async fn output_with_timeout(&self, mut command: Command, limit: Duration) -> Result<Output> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .wrap_err("Failed to get script output")?;
+
+        let mut stdout_pipe = child.stdout.take().expect("to be piped");
+        let mut stderr_pipe = child.stderr.take().expect("to be piped");
+
+        let mut stdout = vec![];
+        let mut stderr = vec![];
+
+        let res = timeout(limit, async {
+            tokio::try_join!(
+                child.wait(),
+                stdout_pipe.read_to_end(&mut stdout),
+                stderr_pipe.read_to_end(&mut stderr)
+            )
+        })
+        .await;
+
+        match res {
+            Ok(res) => {
+                let (status, _, _) = res.wrap_err("Failed to get script output")?;
+                Ok(Output {
+                    status,
+                    stdout,
+                    stderr,
+                })
+            }
+            Err(_) => {
+                if let Err(err) = child.start_kill() {
+                    debug!("Failed to kill script '{}': {err}", self.cmd);
+                }
+                let _ = child.wait().await;
+
+                Err(Report::msg(format!(
+                    "Script '{}' timed out after {}ms and was killed",
+                    self.cmd,
+                    limit.as_millis()
+                ))
+                .suggestion("Increase the script `timeout`")
+                .suggestion("Check the script is not waiting for input"))
+            }
+        }
+    }
+
+    /// Runs the script to completion without capturing its output,
+    /// so that any background processes it starts are not tied to the pipes.
+    ///
+    /// If `limit` is set and the script does not exit within it,
+    /// the process group is killed and an error is returned.
+    pub async fn run_to_completion(&self, limit: Option<Duration>) -> Result<ExitStatus> {
+        let mut command = self.command()?;
+        command
+            .args(["-c", &self.cmd])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        let Some(limit) = limit else {
+            return command
+                .status()
+                .await
+                .wrap_err_with(|| format!("Failed to run script '{}'", self.cmd));
+        };
 
-            debug!("sending stderr: '{stderr}'");
+        set_process_group(&mut command);
 
-            Ok((OutputStream::Stderr(stderr), false))
+        let mut child = command
+            .spawn()
+            .wrap_err_with(|| format!("Failed to run script '{}'", self.cmd))?;
+
+        match timeout(limit, child.wait()).await {
+            Ok(status) => status.wrap_err_with(|| format!("Failed to run script '{}'", self.cmd)),
+            Err(_) => {
+                terminate_group(&mut child).await;
+
+                Err(Report::msg(format!(
+                    "Script '{}' timed out after {}ms and was killed",
+                    self.cmd,
+                    limit.as_millis()
+                )))
+            }
         }
     }
 
     /// Spawns a long-running process.
     /// Returns a `mpsc::Receiver` that sends a message
-    /// every time a new line is written to `stdout` or `stderr`.
-    pub fn spawn(&self) -> Result<mpsc::Receiver<OutputStream>> {
-        let mut handle = Command::new("/bin/sh")
+    /// every time a new line is written to `stdout` or `stderr`,
+    /// along with whether the process is still running successfully.
+    ///
+    /// If the process exits with a non-zero code,
+    /// a final `stderr` message describing the failure is sent.
+    pub fn spawn(&self) -> Result<mpsc::Receiver<(OutputStream, bool)>> {
+        let mut handle = self
+            .command()?
             .args(["-c", &self.cmd])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
@@ -292,18 +818,26 @@ impl Script {
         .lines();
 
         let (tx, rx) = mpsc::channel(32);
+        let cmd = self.cmd.clone();
 
         spawn(async move {
             loop {
                 select! {
-                    _ = handle.wait() => break,
+                    status = handle.wait() => {
+                        if let Some(status) = status.ok().filter(|status| !status.success()) {
+                            let message = format!("Script '{cmd}' {}", failure_reason(status));
+                            send_async!(tx, (OutputStream::Stderr(message), false));
+                        }
+
+                        break;
+                    }
                     Ok(Some(line)) = stdout_lines.next_line() => {
                         debug!("sending stdout line: '{line}'");
-                        send_async!(tx, OutputStream::Stdout(line));
+                        send_async!(tx, (OutputStream::Stdout(line), true));
                     }
                     Ok(Some(line)) = stderr_lines.next_line() => {
                         debug!("sending stderr line: '{line}'");
-                        send_async!(tx, OutputStream::Stderr(line));
+                        send_async!(tx, (OutputStream::Stderr(line), true));
                     }
                 }
             }
@@ -312,6 +846,84 @@ impl Script {
         Ok(rx)
     }
 
+    /// Runs the script as a long-running process,
+    /// calling `callback` with each line it writes.
+    ///
+    /// If the process fails, it is killed and respawned after the backoff delay.
+    /// Returns once the process exits successfully.
+    async fn stream<F>(&self, args: Option<&[String]>, callback: &F, backoff: &mut Backoff)
+    where
+        F: Fn(OutputStream, bool),
+    {
+        loop {
+            let started = Instant::now();
+
+            match self.stream_once(args, callback).await {
+                Ok(status) if status.success() => {
+                    debug!("Streaming script '{}' exited", self.cmd);
+                    backoff.reset();
+                    return;
+                }
+                Ok(status) => {
+                    warn!("Streaming script '{}' failed: {status}", self.cmd);
+                    let message = format!("Script '{}' {}", self.cmd, failure_reason(status));
+                    callback(OutputStream::Stderr(message), false);
+                }
+                Err(err) => error!("{err:?}"),
+            }
+
+            let delay = backoff.delay(started.elapsed());
+            debug!(
+                "Respawning script '{}' in {}ms",
+                self.cmd,
+                delay.as_millis()
+            );
+
+            sleep(delay).await;
+        }
+    }
+
+    /// Spawns the script once, reading its output until it exits.
+    ///
+    /// If its output cannot be read, the process group is killed
+    /// and an error is returned.
+    async fn stream_once<F>(&self, args: Option<&[String]>, callback: &F) -> Result<ExitStatus>
+    where
+        F: Fn(OutputStream, bool),
+    {
+        let mut command = self.command()?;
+        command.args(["-c", &self.cmd]);
+
+        if let Some(args) = args {
+            command.args(args);
+        }
+
+        set_process_group(&mut command);
+
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("Failed to spawn script '{}'", self.cmd))?;
+
+        debug!("Spawned a streaming process for '{}'", self.cmd);
+
+        let stdout = child.stdout.take().expect("to be piped");
+        let stderr = child.stderr.take().expect("to be piped");
+
+        if let Err(err) = read_lines(stdout, stderr, callback).await {
+            terminate_group(&mut child).await;
+            return Err(err.wrap_err(format!("Failed to read output of script '{}'", self.cmd)));
+        }
+
+        child
+            .wait()
+            .await
+            .wrap_err_with(|| format!("Failed to run script '{}'", self.cmd))
+    }
+
     /// Executes the script in oneshot mode,
     /// meaning it is not awaited and output cannot be captured.
     ///
@@ -334,70 +946,1119 @@ impl Script {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Runs the command in its own process group,
+/// so any children the script spawns are killed with it.
+fn set_process_group(command: &mut Command) {
+    // SAFETY: `setpgid` is async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
 
-    #[test]
-    fn test_parse_basic() {
-        let cmd = "echo 'hello'";
-        let script = Script::from(cmd);
+/// Kills a child started with [`set_process_group`], and its children.
+///
+/// The group is sent `SIGTERM`,
+/// followed by `SIGKILL` if it has not exited after the grace period.
+async fn terminate_group(child: &mut Child) {
+    // the child has not been awaited, so its pid cannot have been reused
+    if let Some(pid) = child.id() {
+        signal_group(pid, libc::SIGTERM);
 
-        assert_eq!(script.cmd, cmd);
-        assert_eq!(script.interval, default_interval());
-        assert_eq!(script.mode, ScriptMode::default());
+        if timeout(KILL_GRACE_PERIOD, child.wait()).await.is_err() {
+            signal_group(pid, libc::SIGKILL);
+            let _ = child.wait().await;
+        }
     }
+}
 
-    #[test]
-    fn test_parse_full() {
-        let cmd = "echo 'hello'";
-        let mode = ScriptMode::Watch;
-        let interval = 300;
-
-        let full_cmd = format!("{mode}:{interval}:{cmd}");
-        let script = Script::from(full_cmd.as_str());
+/// Sends `signal` to every process in the group led by `pid`.
+fn signal_group(pid: u32, signal: i32) {
+    let Ok(pid) = i32::try_from(pid) else {
+        return;
+    };
 
-        assert_eq!(script.cmd, cmd);
-        assert_eq!(script.mode, mode);
-        assert_eq!(script.interval, interval);
+    // SAFETY: `kill` has no memory safety requirements
+    if unsafe { libc::kill(-pid, signal) } != 0 {
+        debug!("Failed to send signal {signal} to process group {pid}");
     }
+}
 
-    #[test]
-    fn test_parse_interval_and_cmd() {
-        let cmd = "echo 'hello'";
-        let interval = 300;
+/// Calls `callback` with each line written to `stdout` or `stderr`,
+/// until both are closed.
+async fn read_lines<F>(
+    stdout: impl AsyncRead + Unpin,
+    stderr: impl AsyncRead + Unpin,
+    callback: &F,
+) -> Result<()>
+where
+    F: Fn(OutputStream, bool),
+{
+    let mut stdout = BufReader::new(stdout).lines();
+    let mut stderr = BufReader::new(stderr).lines();
 
-        let full_cmd = format!("{interval}:{cmd}");
-        let script = Script::from(full_cmd.as_str());
+    let mut stdout_open = true;
+    let mut stderr_open = true;
 
-        assert_eq!(script.cmd, cmd);
-        assert_eq!(script.interval, interval);
-        assert_eq!(script.mode, ScriptMode::default());
+    while stdout_open || stderr_open {
+        select! {
+            line = stdout.next_line(), if stdout_open => match line? {
+                Some(line) => {
+                    debug!("sending stdout line: '{line}'");
+                    callback(OutputStream::Stdout(line), true);
+                }
+                None => stdout_open = false,
+            },
+            line = stderr.next_line(), if stderr_open => match line? {
+                Some(line) => {
+                    debug!("sending stderr line: '{line}'");
+                    callback(OutputStream::Stderr(line), true);
+                }
+                None => stderr_open = false,
+            },
+        }
     }
 
-    #[test]
-    fn test_parse_mode_and_cmd() {
-        let cmd = "echo 'hello'";
-        let mode = ScriptMode::Watch;
+    Ok(())
+}
 
-        let full_cmd = format!("{mode}:{cmd}");
-        let script = Script::from(full_cmd.as_str());
+/// Delay before respawning a failed process,
+/// which doubles on each consecutive failure.
+#[derive(Debug)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
 
-        assert_eq!(script.cmd, cmd);
-        assert_eq!(script.interval, default_interval());
-        assert_eq!(script.mode, mode);
+impl Backoff {
+    const fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            next: initial,
+        }
     }
 
-    #[test]
-    fn test_parse_cmd_with_colon() {
-        let cmd = "uptime | awk '{print \"Uptime: \" $1}'";
-        let script = Script::from(cmd);
+    /// Gets the delay before respawning a process
+    /// which failed after running for `ran_for`.
+    ///
+    /// A process which ran for longer than the maximum delay
+    /// is counted as having been stable,
+    /// so the delay starts again from the initial value.
+    fn delay(&mut self, ran_for: Duration) -> Duration {
+        if ran_for >= self.max {
+            self.reset();
+        }
 
-        assert_eq!(script.cmd, cmd);
-        assert_eq!(script.interval, default_interval());
+        let delay = self.next;
+        self.next = self.next.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+/// Lifecycle of a script which only runs while its widget is visible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GateState {
+    /// Not yet shown, so the script has never run.
+    Deferred,
+    /// Shown, so the script runs as normal.
+    Active,
+    /// Hidden again after being shown.
+    Paused,
+}
+
+impl GateState {
+    /// Gets the state after the widget's visibility changes.
+    const fn next(self, visible: bool) -> Self {
+        match (self, visible) {
+            (_, true) => Self::Active,
+            (Self::Deferred, false) => Self::Deferred,
+            (_, false) => Self::Paused,
+        }
+    }
+}
+
+/// Holds a script back while its widget is hidden.
+#[derive(Debug)]
+struct VisibilityGate {
+    visible: watch::Receiver<bool>,
+    state: GateState,
+}
+
+impl VisibilityGate {
+    const fn new(visible: watch::Receiver<bool>) -> Self {
+        Self {
+            visible,
+            state: GateState::Deferred,
+        }
+    }
+
+    /// Waits until the widget is visible,
+    /// returning straight away if it already is.
+    async fn wait(&mut self) {
+        loop {
+            let visible = *self.visible.borrow_and_update();
+            let state = self.state.next(visible);
+
+            if state != self.state {
+                debug!("Script visibility gate {:?} -> {state:?}", self.state);
+                self.state = state;
+            }
+
+            if self.state == GateState::Active {
+                return;
+            }
+
+            // the widget is gone, so it will never be shown again
+            if self.visible.changed().await.is_err() {
+                pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Waits between script runs.
+struct Trigger {
+    /// Interval to re-run on.
+    /// This is `None` for scripts which only run once.
+    schedule: Option<Schedule>,
+    path: Option<(RecommendedWatcher, mpsc::Receiver<()>)>,
+    signal: Option<Signal>,
+    refresh: Option<mpsc::Receiver<()>>,
+}
+
+impl Trigger {
+    /// Creates the trigger for the script.
+    /// If a path watcher or signal listener cannot be created,
+    /// the error is logged and it is skipped.
+    fn new(script: &Script, refresh: Option<mpsc::Receiver<()>>) -> Self {
+        let path = script
+            .watch_path
+            .as_ref()
+            .and_then(|path| watch_path(path).map_err(|err| error!("{err:?}")).ok());
+
+        let signal = script.signal.and_then(|sig| {
+            signal(SignalKind::from_raw(sig.number()))
+                .map_err(|err| {
+                    error!(
+                        "{:?}",
+                        Report::new(err).wrap_err("Failed to listen for script signal")
+                    );
+                })
+                .ok()
+        });
+
+        let schedule = (!script.once).then(|| {
+            Schedule::new(
+                Duration::from_millis(script.interval),
+                Duration::from_millis(script.jitter),
+            )
+        });
+
+        Self {
+            schedule,
+            path,
+            signal,
+            refresh,
+        }
+    }
+
+    /// Waits until the script should next be run.
+    async fn wait(&mut self) {
+        let Self {
+            schedule,
+            path,
+            signal,
+            refresh,
+        } = self;
+
+        let refresh = async {
+            if let Some(rx) = refresh {
+                if rx.recv().await.is_some() {
+                    return;
+                }
+            }
+
+            pending::<()>().await;
+        };
+
+        if path.is_none() && signal.is_none() {
+            let tick = async {
+                match schedule {
+                    Some(schedule) => schedule.tick().await,
+                    None => pending::<()>().await,
+                }
+            };
+
+            select! {
+                () = tick => {},
+                () = refresh => debug!("Script refresh requested"),
+            }
+            return;
+        }
+
+        let path = async {
+            if let Some((_, rx)) = path {
+                if debounce(rx, WATCH_DEBOUNCE).await.is_some() {
+                    return;
+                }
+            }
+
+            pending::<()>().await;
+        };
+
+        let signal = async {
+            if let Some(signal) = signal {
+                if signal.recv().await.is_some() {
+                    return;
+                }
+            }
+
+            pending::<()>().await;
+        };
+
+        select! {
+            () = path => debug!("Script path changed"),
+            () = signal => debug!("Script signal received"),
+            () = refresh => debug!("Script refresh requested"),
+        }
+    }
+
+    /// Drops any refreshes requested while the script was running,
+    /// since the run which just finished already covers them.
+    fn discard_refreshes(&mut self) {
+        if let Some(rx) = &mut self.refresh {
+            while rx.try_recv().is_ok() {}
+        }
+    }
+}
+
+/// Polling scripts which can be re-run on demand
+/// with the `refresh:<name>` command, by module name.
+#[derive(Debug, Default)]
+pub struct RefreshRegistry {
+    scripts: HashMap<String, Vec<mpsc::Sender<()>>>,
+}
+
+impl RefreshRegistry {
+    /// Registers a script under `name`,
+    /// returning a receiver which is sent to on each refresh.
+    /// Several scripts can share a name, such as the same module on each monitor.
+    pub fn register(&mut self, name: &str) -> mpsc::Receiver<()> {
+        // at most one refresh is held while waiting,
+        // so repeated requests only cause one run
+        let (tx, rx) = mpsc::channel(1);
+
+        let senders = self.scripts.entry(name.to_string()).or_default();
+        senders.retain(|tx| !tx.is_closed());
+        senders.push(tx);
+
+        rx
+    }
+
+    /// Requests each script registered under `name` re-runs.
+    ///
+    /// Returns `false` if there are no running scripts with the name.
+    pub fn refresh(&mut self, name: &str) -> bool {
+        let Some(senders) = self.scripts.get_mut(name) else {
+            return false;
+        };
+
+        senders.retain(|tx| !tx.is_closed());
+        for tx in senders.iter() {
+            // if full, a refresh is already pending
+            let _ = tx.try_send(());
+        }
+
+        !senders.is_empty()
+    }
+}
+
+/// Installs a file watcher on `path`,
+/// returning a receiver that is sent to on every change.
+fn watch_path(path: &Path) -> Result<(RecommendedWatcher, mpsc::Receiver<()>)> {
+    let path = path
+        .canonicalize()
+        .wrap_err_with(|| format!("Failed to watch script path '{}'", path.display()))
+        .suggestion("Check the path exists")?;
+
+    let (tx, rx) = mpsc::channel(32);
+
+    // watch the parent directory for files,
+    // so changes are still picked up if the file is replaced.
+    let (watch_dir, filter) = if path.is_dir() {
+        (path.clone(), None)
+    } else {
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        (parent, Some(path.clone()))
+    };
+
+    let mut watcher = recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event)
+            if !matches!(event.kind, EventKind::Access(_))
+                && filter.iter().all(|path| event.paths.contains(path)) =>
+        {
+            trace!("{event:?}");
+            // if full, a run is already pending
+            let _ = tx.try_send(());
+        }
+        Err(err) => error!("Error occurred when watching script path: {err:?}"),
+        _ => {}
+    })?;
+
+    watcher.watch(&watch_dir, RecursiveMode::NonRecursive)?;
+    debug!("Installed script file watcher on '{}'", path.display());
+
+    Ok((watcher, rx))
+}
+
+/// Waits for the next message on `rx`,
+/// then for `window` to pass without any further messages,
+/// so that a burst of changes only results in a single run.
+///
+/// Returns `None` if the channel is closed.
+async fn debounce(rx: &mut mpsc::Receiver<()>, window: Duration) -> Option<()> {
+    rx.recv().await?;
+
+    while let Ok(Some(())) = timeout(window, rx.recv()).await {}
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_basic() {
+        let cmd = "echo 'hello'";
+        let script = Script::from(cmd);
+
+        assert_eq!(script.cmd, cmd);
+        assert_eq!(script.interval, default_interval());
+        assert_eq!(script.mode, ScriptMode::default());
+    }
+
+    #[test]
+    fn test_parse_full() {
+        let cmd = "echo 'hello'";
+        let mode = ScriptMode::Watch;
+        let interval = 300;
+
+        let full_cmd = format!("{mode}:{interval}:{cmd}");
+        let script = Script::from(full_cmd.as_str());
+
+        assert_eq!(script.cmd, cmd);
+        assert_eq!(script.mode, mode);
+        assert_eq!(script.interval, interval);
+    }
+
+    #[test]
+    fn test_parse_interval_and_cmd() {
+        let cmd = "echo 'hello'";
+        let interval = 300;
+
+        let full_cmd = format!("{interval}:{cmd}");
+        let script = Script::from(full_cmd.as_str());
+
+        assert_eq!(script.cmd, cmd);
+        assert_eq!(script.interval, interval);
+        assert_eq!(script.mode, ScriptMode::default());
+    }
+
+    #[test]
+    fn test_parse_mode_and_cmd() {
+        let cmd = "echo 'hello'";
+        let mode = ScriptMode::Watch;
+
+        let full_cmd = format!("{mode}:{cmd}");
+        let script = Script::from(full_cmd.as_str());
+
+        assert_eq!(script.cmd, cmd);
+        assert_eq!(script.interval, default_interval());
+        assert_eq!(script.mode, mode);
+    }
+
+    #[test]
+    fn test_parse_cmd_with_colon() {
+        let cmd = "uptime | awk '{print \"Uptime: \" $1}'";
+        let script = Script::from(cmd);
+
+        assert_eq!(script.cmd, cmd);
+        assert_eq!(script.interval, default_interval());
         assert_eq!(script.mode, ScriptMode::default());
     }
 
+    #[test]
+    fn test_json_output_text() {
+        let output = JsonOutput::parse(r#"{ "text": "hello" }"#).expect("to parse");
+        assert_eq!(output, JsonOutput::text("hello".to_string()));
+    }
+
+    #[test]
+    fn test_json_output_tooltip() {
+        let output = JsonOutput::parse(r#"{ "tooltip": "details" }"#).expect("to parse");
+        assert_eq!(output.tooltip.as_deref(), Some("details"));
+        assert_eq!(output.text, None);
+    }
+
+    #[test]
+    fn test_json_output_class() {
+        let output = JsonOutput::parse(r#"{ "class": "warning critical" }"#).expect("to parse");
+        assert_eq!(output.class, vec!["warning", "critical"]);
+
+        let output =
+            JsonOutput::parse(r#"{ "class": ["warning", "critical"] }"#).expect("to parse");
+        assert_eq!(output.class, vec!["warning", "critical"]);
+    }
+
+    #[test]
+    fn test_json_output_percentage() {
+        let output = JsonOutput::parse(r#"{ "percentage": 42 }"#).expect("to parse");
+        assert_eq!(output.percentage, Some(42.0));
+    }
+
+    #[test]
+    fn test_json_output_malformed() {
+        assert!(JsonOutput::parse(r#"{ "text": "unterminated }"#).is_err());
+        assert!(JsonOutput::parse("plain text").is_err());
+        assert!(JsonOutput::parse(r#"{ "percentage": "high" }"#).is_err());
+    }
+
+    #[test]
+    fn test_gate_lifecycle() {
+        let state = GateState::Deferred;
+
+        // stays deferred until first shown
+        let state = state.next(false);
+        assert_eq!(state, GateState::Deferred);
+
+        let state = state.next(true);
+        assert_eq!(state, GateState::Active);
+
+        let state = state.next(false);
+        assert_eq!(state, GateState::Paused);
+
+        let state = state.next(false);
+        assert_eq!(state, GateState::Paused);
+
+        assert_eq!(state.next(true), GateState::Active);
+    }
+
+    #[tokio::test]
+    async fn test_gate_waits_until_visible() {
+        let (tx, rx) = watch::channel(false);
+        let mut gate = VisibilityGate::new(rx);
+        let window = Duration::from_millis(20);
+
+        assert!(timeout(window, gate.wait()).await.is_err());
+        assert_eq!(gate.state, GateState::Deferred);
+
+        tx.send(true).expect("to send");
+        assert!(timeout(window, gate.wait()).await.is_ok());
+        assert_eq!(gate.state, GateState::Active);
+
+        // does not block while visible
+        assert!(timeout(window, gate.wait()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_gate_pauses_while_hidden() {
+        let (tx, rx) = watch::channel(true);
+        let mut gate = VisibilityGate::new(rx);
+        let window = Duration::from_millis(20);
+
+        assert!(timeout(window, gate.wait()).await.is_ok());
+
+        tx.send(false).expect("to send");
+        assert!(timeout(window, gate.wait()).await.is_err());
+        assert_eq!(gate.state, GateState::Paused);
+
+        tx.send(true).expect("to send");
+        assert!(timeout(window, gate.wait()).await.is_ok());
+        assert_eq!(gate.state, GateState::Active);
+    }
+
+    #[tokio::test]
+    async fn test_deferred_script_runs_once_visible() {
+        let (tx, rx) = watch::channel(false);
+        let script = Script {
+            once: true,
+            ..Script::from("echo hello")
+        };
+
+        let (out_tx, mut out_rx) = mpsc::channel(8);
+        let task = tokio::spawn(async move {
+            script
+                .run_when_visible(None, rx, |out, _| {
+                    out_tx.try_send(out).expect("to send");
+                })
+                .await;
+        });
+
+        let window = Duration::from_millis(100);
+        assert!(timeout(window, out_rx.recv()).await.is_err());
+
+        tx.send(true).expect("to send");
+        let out = timeout(Duration::from_secs(5), out_rx.recv())
+            .await
+            .expect("to run once visible");
+        assert!(matches!(out, Some(OutputStream::Stdout(out)) if out == "hello"));
+
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_debounce_burst() {
+        let (tx, mut rx) = mpsc::channel(8);
+
+        for _ in 0..5 {
+            tx.send(()).await.expect("to send");
+        }
+
+        let window = Duration::from_millis(20);
+        assert_eq!(debounce(&mut rx, window).await, Some(()));
+
+        // burst should have been consumed as a single change
+        assert!(timeout(window * 2, debounce(&mut rx, window))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_debounce_separate_changes() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let window = Duration::from_millis(20);
+
+        tx.send(()).await.expect("to send");
+        assert_eq!(debounce(&mut rx, window).await, Some(()));
+
+        tx.send(()).await.expect("to send");
+        assert_eq!(debounce(&mut rx, window).await, Some(()));
+
+        drop(tx);
+        assert_eq!(debounce(&mut rx, window).await, None);
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!("5".parse::<RealtimeSignal>().ok(), Some(RealtimeSignal(5)));
+        assert_eq!(
+            "RTMIN".parse::<RealtimeSignal>().ok(),
+            Some(RealtimeSignal(0))
+        );
+        assert_eq!(
+            "RTMIN+3".parse::<RealtimeSignal>().ok(),
+            Some(RealtimeSignal(3))
+        );
+        assert_eq!(
+            "SIGRTMIN+8".parse::<RealtimeSignal>().ok(),
+            Some(RealtimeSignal(8))
+        );
+
+        assert_eq!(
+            RealtimeSignal::new(2).expect("to be valid").number(),
+            libc::SIGRTMIN() + 2
+        );
+    }
+
+    #[test]
+    fn test_parse_signal_invalid() {
+        assert!("RTMIN-1".parse::<RealtimeSignal>().is_err());
+        assert!("SIGUSR1".parse::<RealtimeSignal>().is_err());
+        assert!("-1".parse::<RealtimeSignal>().is_err());
+        assert!(RealtimeSignal::new(libc::SIGRTMAX() - libc::SIGRTMIN() + 1).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_signal() {
+        let script: Script =
+            serde_json::from_str(r#"{ "cmd": "date", "signal": 4 }"#).expect("to deserialize");
+        assert_eq!(script.signal, Some(RealtimeSignal(4)));
+
+        let script: Script = serde_json::from_str(r#"{ "cmd": "date", "signal": "RTMIN+4" }"#)
+            .expect("to deserialize");
+        assert_eq!(script.signal, Some(RealtimeSignal(4)));
+
+        assert!(serde_json::from_str::<Script>(r#"{ "cmd": "date", "signal": 999 }"#).is_err());
+    }
+
+    fn transform(config: &str) -> Transform {
+        serde_json::from_str(config).expect("to parse")
+    }
+
+    #[test]
+    fn test_transform_multiply_divide() {
+        assert_eq!(transform(r#"{ "multiply": 100 }"#).apply("0.42"), "42");
+        assert_eq!(transform(r#"{ "divide": 1024 }"#).apply("2048"), "2");
+        assert_eq!(transform(r#"{ "divide": 0 }"#).apply("5"), "5");
+    }
+
+    #[test]
+    fn test_transform_clamp() {
+        let clamp = transform(r#"{ "min": 0, "max": 100 }"#);
+
+        assert_eq!(clamp.apply("-5"), "0");
+        assert_eq!(clamp.apply("50"), "50");
+        assert_eq!(clamp.apply("150"), "100");
+    }
+
+    #[test]
+    fn test_transform_round() {
+        assert_eq!(transform(r#"{ "round": 2 }"#).apply("3.14159"), "3.14");
+        assert_eq!(transform(r#"{ "round": 0 }"#).apply("2.6"), "3");
+        assert_eq!(transform(r#"{ "round": 1 }"#).apply(" 7\n"), "7.0");
+    }
+
+    #[test]
+    fn test_transform_suffix() {
+        assert_eq!(transform(r#"{ "suffix": "%" }"#).apply("42"), "42%");
+    }
+
+    #[test]
+    fn test_transform_bytes_to_gb() {
+        let gb = transform(r#"{ "divide": 1000000000, "round": 1, "suffix": " GB" }"#);
+        assert_eq!(gb.apply("12345678901"), "12.3 GB");
+    }
+
+    #[test]
+    fn test_transform_non_numeric() {
+        let transform = transform(r#"{ "round": 1, "suffix": "%" }"#);
+
+        assert_eq!(transform.apply("N/A"), "N/A");
+        assert_eq!(transform.apply(""), "");
+    }
+
+    #[test]
+    fn test_transform_only_stdout() {
+        let script = Script {
+            transform: Some(transform(r#"{ "suffix": "%" }"#)),
+            ..Script::default()
+        };
+
+        assert!(matches!(
+            script.transform_output(OutputStream::Stdout("42".to_string())),
+            OutputStream::Stdout(out) if out == "42%"
+        ));
+        assert!(matches!(
+            script.transform_output(OutputStream::Stderr("42".to_string())),
+            OutputStream::Stderr(out) if out == "42"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_env() {
+        let script = Script {
+            cmd: "echo $IRONBAR_SCRIPT_TEST".to_string(),
+            env: HashMap::from([("IRONBAR_SCRIPT_TEST".to_string(), "hello".to_string())]),
+            ..Script::default()
+        };
+
+        let (output, success) = script.get_output(None).await.expect("to run");
+        assert!(success);
+        assert!(matches!(output, OutputStream::Stdout(out) if out == "hello"));
+    }
+
+    #[tokio::test]
+    async fn test_cwd() {
+        let dir = std::env::temp_dir().canonicalize().expect("to exist");
+
+        let script = Script {
+            cmd: "pwd".to_string(),
+            cwd: Some(dir.clone()),
+            ..Script::default()
+        };
+
+        let (output, _) = script.get_output(None).await.expect("to run");
+        assert!(matches!(output, OutputStream::Stdout(out) if Path::new(&out) == dir.as_path()));
+    }
+
+    #[tokio::test]
+    async fn test_missing_cwd() {
+        let script = Script {
+            cmd: "pwd".to_string(),
+            cwd: Some(PathBuf::from("/ironbar/does/not/exist")),
+            ..Script::default()
+        };
+
+        let err = script.get_output(None).await.expect_err("to error");
+        assert!(err.to_string().contains("/ironbar/does/not/exist"));
+        assert!(script.spawn().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let pid_file = std::env::temp_dir().join(format!("ironbar-timeout-{}", std::process::id()));
+
+        let script = Script {
+            cmd: format!("echo $$ > {}; exec sleep 10", pid_file.display()),
+            timeout: Some(100),
+            ..Script::default()
+        };
+
+        let start = std::time::Instant::now();
+        let err = script.get_output(None).await.expect_err("to time out");
+
+        assert!(err.to_string().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let pid = std::fs::read_to_string(&pid_file).expect("to write pid");
+        let pid = pid.trim().parse::<i32>().expect("to be valid pid");
+
+        // SAFETY: signal 0 only checks whether the process exists
+        assert_ne!(unsafe { libc::kill(pid, 0) }, 0);
+
+        let _ = std::fs::remove_file(pid_file);
+    }
+
+    #[tokio::test]
+    async fn test_within_timeout() {
+        let script = Script {
+            cmd: "echo hello".to_string(),
+            timeout: Some(5000),
+            ..Script::default()
+        };
+
+        let (output, success) = script.get_output(None).await.expect("to run");
+        assert!(success);
+        assert!(matches!(output, OutputStream::Stdout(out) if out == "hello"));
+    }
+
+    #[tokio::test]
+    async fn test_polls_do_not_overlap() {
+        let log_file = std::env::temp_dir().join(format!("ironbar-overlap-{}", std::process::id()));
+        let _ = std::fs::remove_file(&log_file);
+
+        // interval is much shorter than the script runtime
+        let script = Script {
+            cmd: format!(
+                "echo start >> {file}; sleep 0.1; echo end >> {file}",
+                file = log_file.display()
+            ),
+            interval: 1,
+            ..Script::default()
+        };
+
+        let _ = timeout(Duration::from_millis(550), script.run(None, |_, _| {})).await;
+
+        let log = std::fs::read_to_string(&log_file).expect("to write log");
+        let lines = log.lines().collect::<Vec<_>>();
+
+        assert!(lines.len() >= 4);
+        for pair in lines.chunks(2).filter(|pair| pair.len() == 2) {
+            assert_eq!(pair, ["start", "end"]);
+        }
+
+        let _ = std::fs::remove_file(log_file);
+    }
+
+    #[tokio::test]
+    async fn test_runs_immediately() {
+        let script = Script {
+            cmd: "echo hello".to_string(),
+            interval: 60_000,
+            ..Script::default()
+        };
+
+        let outputs = std::cell::RefCell::new(vec![]);
+        let _ = timeout(
+            Duration::from_millis(500),
+            script.run(None, |out, _| outputs.borrow_mut().push(out)),
+        )
+        .await;
+
+        let outputs = outputs.into_inner();
+        assert_eq!(outputs.len(), 1);
+        assert!(matches!(&outputs[0], OutputStream::Stdout(out) if out == "hello"));
+    }
+
+    #[test]
+    fn test_refresh_registry() {
+        let mut registry = RefreshRegistry::default();
+        assert!(!registry.refresh("weather"));
+
+        let mut first = registry.register("weather");
+        let mut second = registry.register("weather");
+
+        // repeated requests are held as a single refresh
+        assert!(registry.refresh("weather"));
+        assert!(registry.refresh("weather"));
+
+        for rx in [&mut first, &mut second] {
+            assert!(rx.try_recv().is_ok());
+            assert!(rx.try_recv().is_err());
+        }
+
+        drop(first);
+        drop(second);
+        assert!(!registry.refresh("weather"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_not_doubled_while_running() {
+        let mut registry = RefreshRegistry::default();
+        let refresh = registry.register("test");
+
+        let script = Script {
+            cmd: "sleep 0.2".to_string(),
+            interval: 60_000,
+            ..Script::default()
+        };
+
+        let runs = std::cell::Cell::new(0);
+        let run = script.run_refreshable(None, Some(refresh), |_, _| runs.set(runs.get() + 1));
+
+        let requests = async {
+            // the first run is still in flight, so these are dropped
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            registry.refresh("test");
+            registry.refresh("test");
+
+            // this starts a second run
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            registry.refresh("test");
+
+            // which is still in flight
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            registry.refresh("test");
+
+            tokio::time::sleep(Duration::from_millis(600)).await;
+        };
+
+        select! {
+            () = run => unreachable!(),
+            () = requests => {},
+        }
+
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[test]
+    fn test_once_not_scheduled() {
+        let script = Script {
+            once: true,
+            ..Script::default()
+        };
+        assert!(Trigger::new(&script, None).schedule.is_none());
+
+        assert!(Trigger::new(&Script::default(), None).schedule.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_once_runs_on_refresh() {
+        let mut registry = RefreshRegistry::default();
+        let refresh = registry.register("test");
+
+        let script = Script {
+            cmd: "echo hello".to_string(),
+            interval: 10,
+            once: true,
+            ..Script::default()
+        };
+
+        let runs = std::cell::Cell::new(0);
+        let run = script.run_refreshable(None, Some(refresh), |_, _| runs.set(runs.get() + 1));
+
+        let requests = async {
+            // many intervals pass without another run
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(runs.get(), 1);
+
+            registry.refresh("test");
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        };
+
+        select! {
+            () = run => unreachable!(),
+            () = requests => {},
+        }
+
+        assert_eq!(runs.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stderr_separate() {
+        let script = Script::from("echo out; echo err >&2");
+
+        let output = script.execute(None).await.expect("to run");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, "out");
+        assert_eq!(output.stderr, "err");
+
+        let (output, success) = script.get_output(None).await.expect("to run");
+        assert!(success);
+        assert!(matches!(output, OutputStream::Stdout(out) if out == "out"));
+    }
+
+    #[tokio::test]
+    async fn test_exit_code_reported() {
+        let script = Script::from("echo partial; echo broken >&2; exit 3");
+
+        let (output, success) = script.get_output(None).await.expect("to run");
+        assert!(!success);
+
+        let OutputStream::Stderr(message) = output else {
+            panic!("expected stderr output");
+        };
+
+        assert_eq!(
+            message,
+            "Script 'echo partial; echo broken >&2; exit 3' exited with code 3: broken"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exit_code_without_stderr() {
+        let script = Script::from("exit 1");
+        let output = script.execute(None).await.expect("to run");
+
+        assert_eq!(
+            output.error_message("exit 1"),
+            "Script 'exit 1' exited with code 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_stream_mode() {
+        let script = Script::from("stream:journalctl -f");
+        assert_eq!(script.mode, ScriptMode::Stream);
+        assert_eq!(script.cmd, "journalctl -f");
+
+        let script = Script::from("s:1000:tail -f log");
+        assert_eq!(script.mode, ScriptMode::Stream);
+        assert_eq!(script.interval, 1000);
+
+        // commands starting with `s` are not mistaken for the mode
+        let script = Script::from("sleep 1; echo done");
+        assert_eq!(script.mode, ScriptMode::Poll);
+        assert_eq!(script.cmd, "sleep 1; echo done");
+    }
+
+    /// Collects the output passed to a callback.
+    fn collector() -> (
+        std::rc::Rc<std::cell::RefCell<Vec<OutputStream>>>,
+        impl Fn(OutputStream, bool),
+    ) {
+        let lines = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let callback = {
+            let lines = lines.clone();
+            move |output, _| lines.borrow_mut().push(output)
+        };
+
+        (lines, callback)
+    }
+
+    fn stdout_lines(output: &[OutputStream]) -> Vec<&str> {
+        output
+            .iter()
+            .filter_map(|output| match output {
+                OutputStream::Stdout(line) => Some(line.as_str()),
+                OutputStream::Stderr(_) => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_read_lines() {
+        let (lines, callback) = collector();
+
+        read_lines(
+            b"first\nsecond\nthird".as_slice(),
+            b"oops\n".as_slice(),
+            &callback,
+        )
+        .await
+        .expect("to read");
+
+        let lines = lines.borrow();
+        assert_eq!(stdout_lines(&lines), ["first", "second", "third"]);
+        assert!(lines
+            .iter()
+            .any(|line| matches!(line, OutputStream::Stderr(line) if line == "oops")));
+    }
+
+    #[tokio::test]
+    async fn test_read_lines_invalid() {
+        let (lines, callback) = collector();
+
+        let res = read_lines(b"ok\n\xff\xfe\n".as_slice(), b"".as_slice(), &callback).await;
+
+        assert!(res.is_err());
+        assert_eq!(stdout_lines(&lines.borrow()), ["ok"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_pushes_lines() {
+        let script = Script::from("stream:echo one; sleep 0.05; echo two");
+        let (lines, callback) = collector();
+        let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50));
+
+        timeout(
+            Duration::from_secs(5),
+            script.stream(None, &callback, &mut backoff),
+        )
+        .await
+        .expect("to exit after success");
+
+        assert_eq!(stdout_lines(&lines.borrow()), ["one", "two"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_respawns_on_failure() {
+        let script = Script::from("stream:echo run; exit 1");
+        let (lines, callback) = collector();
+        let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(40));
+
+        let res = timeout(
+            Duration::from_millis(500),
+            script.stream(None, &callback, &mut backoff),
+        )
+        .await;
+
+        // failures never end the stream
+        assert!(res.is_err());
+        assert!(stdout_lines(&lines.borrow()).len() >= 3);
+    }
+
+    #[test]
+    fn test_backoff_doubles() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        let quick = Duration::from_millis(10);
+
+        let delays = (0..8)
+            .map(|_| backoff.delay(quick).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+
+        backoff.reset();
+        assert_eq!(backoff.delay(quick), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_backoff_resets_after_stable_run() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+
+        backoff.delay(Duration::ZERO);
+        backoff.delay(Duration::ZERO);
+        assert_eq!(backoff.delay(Duration::ZERO), Duration::from_secs(4));
+
+        assert_eq!(
+            backoff.delay(Duration::from_secs(120)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(backoff.delay(Duration::ZERO), Duration::from_secs(2));
+    }
+
     #[test]
     fn test_no_cmd() {
         let mode = ScriptMode::Watch;